@@ -0,0 +1,244 @@
+use std::{
+    cell::Cell,
+    fs::{self, create_dir_all},
+    io::{Read, Seek, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use indicatif::{ProgressBar, ProgressStyle};
+use zip::ZipArchive;
+
+use crate::MULTI_PROGRESS_BAR;
+
+/// Receives the progress of an archive being extracted or written
+pub trait ArchiveProgress {
+    /// Called once before the first entry with the number of entries and their
+    /// total uncompressed size. Archives that are streamed do not know them upfront
+    fn start(&self, _entries: Option<u64>, _bytes: Option<u64>) {}
+    /// Called when an entry is started
+    fn entry(&self, _name: &str) {}
+    /// Called as the bytes of the current entry are written
+    fn bytes(&self, _bytes: u64) {}
+    /// Called once all the entries are done
+    fn finish(&self) {}
+}
+
+/// Ignores the progress
+pub struct NoProgress;
+
+impl ArchiveProgress for NoProgress {}
+
+/// Shows the entries and bytes done on a bar of MULTI_PROGRESS_BAR
+pub struct BarProgress {
+    bar: ProgressBar,
+    entries: Cell<Option<u64>>,
+    done: Cell<u64>,
+}
+
+impl BarProgress {
+    pub fn new(message: impl Into<String>) -> Self {
+        let bar = MULTI_PROGRESS_BAR.add(ProgressBar::new(0).with_message(message.into()));
+        BarProgress {
+            bar,
+            entries: Cell::new(None),
+            done: Cell::new(0),
+        }
+    }
+}
+
+impl ArchiveProgress for BarProgress {
+    fn start(&self, entries: Option<u64>, bytes: Option<u64>) {
+        self.entries.set(entries);
+        let template = match bytes {
+            Some(bytes) => {
+                self.bar.set_length(bytes);
+                "{spinner} {msg} [{prefix}] {bar:30} {bytes}/{total_bytes} {bytes_per_sec}"
+            }
+            None => "{spinner} {msg} [{prefix}] {bytes} {bytes_per_sec}",
+        };
+        self.bar
+            .set_style(ProgressStyle::with_template(template).unwrap());
+    }
+    fn entry(&self, _name: &str) {
+        let done = self.done.get() + 1;
+        self.done.set(done);
+        match self.entries.get() {
+            Some(entries) => self.bar.set_prefix(format!("{done}/{entries} entries")),
+            None => self.bar.set_prefix(format!("{done} entries")),
+        }
+    }
+    fn bytes(&self, bytes: u64) {
+        self.bar.inc(bytes);
+    }
+    fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+}
+
+impl Drop for BarProgress {
+    fn drop(&mut self) {
+        // also clears the bar of an extraction that failed
+        self.bar.finish_and_clear();
+        MULTI_PROGRESS_BAR.remove(&self.bar);
+    }
+}
+
+/// Reports the bytes written through it
+pub struct ProgressWriter<'a, W: Write> {
+    inner: W,
+    progress: &'a dyn ArchiveProgress,
+}
+
+impl<'a, W: Write> ProgressWriter<'a, W> {
+    pub fn new(inner: W, progress: &'a dyn ArchiveProgress) -> Self {
+        ProgressWriter { inner, progress }
+    }
+}
+
+impl<W: Write> Write for ProgressWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.progress.bytes(written as u64);
+        Ok(written)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// The number of entries and the total uncompressed size of a zip
+pub fn zip_size<R: Read + Seek>(archive: &mut ZipArchive<R>) -> (u64, u64) {
+    let bytes = (0..archive.len())
+        .filter_map(|i| archive.by_index_raw(i).ok().map(|entry| entry.size()))
+        .sum();
+    (archive.len() as u64, bytes)
+}
+
+/// Extracts the zip into directory reporting its progress. Entries with names
+/// outside directory are skipped.
+///
+/// # Errors
+///
+/// Returns an error if an entry cannot be read or written
+pub fn extract_zip<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    directory: &Path,
+    progress: &dyn ArchiveProgress,
+) -> anyhow::Result<()> {
+    let (entries, bytes) = zip_size(archive);
+    progress.start(Some(entries), Some(bytes));
+
+    let make_writable_dir_all = |outpath: &Path| -> std::io::Result<()> {
+        create_dir_all(outpath)?;
+        #[cfg(unix)]
+        {
+            // Dirs must be writable until all normal files are extracted
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(
+                outpath,
+                fs::Permissions::from_mode(0o700 | fs::metadata(outpath)?.permissions().mode()),
+            )?;
+        }
+        Ok(())
+    };
+
+    for i in 0..archive.len() {
+        crate::interrupt::check()?;
+        let mut file = archive.by_index(i)?;
+        progress.entry(file.name());
+
+        let outpath = match file.enclosed_name() {
+            Some(path) => directory.join(path),
+            None => continue,
+        };
+
+        if file.is_dir() {
+            make_writable_dir_all(&outpath).context(format!("Failed to create {:?}", outpath))?;
+            continue;
+        }
+
+        if let Some(p) = outpath.parent() {
+            if !p.exists() {
+                make_writable_dir_all(p).context(format!("Failed to create {:?}", p))?;
+            }
+        }
+        let outfile =
+            fs::File::create(&outpath).context(format!("Failed to create {:?}", outpath))?;
+        std::io::copy(&mut file, &mut ProgressWriter::new(outfile, progress))
+            .context(format!("Failed to write {:?}", outpath))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Some(mode) = file.unix_mode() {
+                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode))
+                    .context(format!("Failed to set the permissions of {:?}", outpath))?;
+            }
+        }
+    }
+    progress.finish();
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn extract_zip_reports_entries_and_bytes() {
+    use std::{cell::RefCell, io::Cursor};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    #[derive(Default)]
+    struct Recorder {
+        start: Cell<Option<(Option<u64>, Option<u64>)>>,
+        entries: RefCell<Vec<String>>,
+        bytes: Cell<u64>,
+        finished: Cell<bool>,
+    }
+    impl ArchiveProgress for Recorder {
+        fn start(&self, entries: Option<u64>, bytes: Option<u64>) {
+            self.start.set(Some((entries, bytes)));
+        }
+        fn entry(&self, name: &str) {
+            self.entries.borrow_mut().push(name.to_string());
+        }
+        fn bytes(&self, bytes: u64) {
+            self.bytes.set(self.bytes.get() + bytes);
+        }
+        fn finish(&self) {
+            self.finished.set(true);
+        }
+    }
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .add_directory("res/", SimpleFileOptions::default())
+        .unwrap();
+    for (name, contents) in [
+        ("classes.jar", "0123456789"),
+        ("res/values.xml", "<resources />"),
+    ] {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let output = tmp.path().join("archive");
+    let recorder = Recorder::default();
+    let result = extract_zip(&mut archive, &output, &recorder);
+    let values = fs::read_to_string(output.join("res").join("values.xml"));
+
+    result.unwrap();
+    assert_eq!(values.unwrap(), "<resources />");
+    assert_eq!(recorder.start.get(), Some((Some(3), Some(23))));
+    assert_eq!(
+        *recorder.entries.borrow(),
+        vec!["res/", "classes.jar", "res/values.xml"]
+    );
+    assert_eq!(recorder.bytes.get(), 23);
+    assert!(recorder.finished.get());
+}
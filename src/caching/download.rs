@@ -1,37 +1,193 @@
-use std::io::{copy, BufReader, BufWriter};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::sync::Mutex;
 
 use anyhow::Context;
-use reqwest::Url;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::{header::CONTENT_LENGTH, Url};
 
+use crate::config::global::{authenticate, client_builder, send_with_retry};
+use crate::events::{emit, Event, ProgressReader};
+use crate::http_recording::SendRecorded;
+use crate::interrupt::copy;
+use crate::metrics::{self, DOWNLOADS};
 use crate::submodules::resolve::ProjectDep;
+use crate::MULTI_PROGRESS_BAR;
 
-use super::Cache;
+use super::{record_checksum, snapshot::remote_artifact_version, Cache};
+
+/// Held while the length of the overall bar is corrected so that concurrent
+/// downloads do not overwrite each others corrections
+static OVERALL_LENGTH: Mutex<()> = Mutex::new(());
+
+/// The file name of the artifact in the remote repository. Snapshot versions are named
+/// after their latest build
+pub fn artifact_file_name(project: &ProjectDep) -> String {
+    let ext = if project.packaging.is_empty() || project.packaging.eq("bundle") {
+        "jar"
+    } else {
+        project.packaging.as_str()
+    };
+    format!(
+        "{}-{}.{}",
+        project.artifact_id,
+        remote_artifact_version(project, ext),
+        ext
+    )
+}
+
+/// Returns the size of the artifact in the repository without downloading it.
+/// None if the repository does not report it.
+pub fn artifact_size(client: &reqwest::blocking::Client, project: &ProjectDep) -> Option<u64> {
+    let url = Url::parse(&project.get_root_url())
+        .ok()?
+        .join(&artifact_file_name(project))
+        .ok()?;
+    if url.scheme() == "file" {
+        return url
+            .to_file_path()
+            .ok()?
+            .metadata()
+            .ok()
+            .map(|meta| meta.len());
+    }
+    let res = authenticate(client.head(url.clone()), url.as_str())
+        .send_recorded()
+        .ok()?
+        .error_for_status()
+        .ok()?;
+    // content_length() of a HEAD response is the size of its empty body
+    res.headers()
+        .get(CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
 
 pub fn download(project: &ProjectDep) -> anyhow::Result<u64> {
-    let client = reqwest::blocking::ClientBuilder::new()
-        .user_agent(crate::USER_AGENT)
+    download_with_progress(project, None, None)
+}
+
+/// Downloads the artifact into the cache showing its byte progress. The overall bar
+/// is advanced by the downloaded bytes. Its length is corrected if the actual size
+/// differs from the expected size that was added to it.
+pub fn download_with_progress(
+    project: &ProjectDep,
+    overall: Option<&ProgressBar>,
+    expected: Option<u64>,
+) -> anyhow::Result<u64> {
+    let client = client_builder()?
         .build()
         .context("Error creating download client")?;
     let base = Url::parse(&project.get_root_url()).context("Error parsing repo url")?;
-    let ext = if project.packaging.is_empty() || project.packaging.eq("bundle") {
-        String::from("jar")
-    } else {
-        project.packaging.clone()
-    };
 
-    let url = base.join(format!("{}-{}.{}", project.artifact_id, project.version, ext).as_str())?;
-    let res = client.get(url).send()?;
+    let name = artifact_file_name(project);
+    let url = base.join(&name)?;
+    if url.scheme() == "file" {
+        return copy_local(project, &url, overall);
+    }
+    let _span = metrics::span(DOWNLOADS);
+    let res = send_with_retry(url.as_str(), || {
+        authenticate(client.get(url.clone()), url.as_str()).send_recorded()
+    })?;
     if res.status().is_success() {
         let mut cache = Cache::from(project);
         cache.use_labt_home()?;
         let cache = cache.create()?;
 
+        let coordinate = format!(
+            "{}:{}:{}",
+            project.group_id, project.artifact_id, project.version
+        );
+        let total = res.content_length();
+        let bar = match total {
+            Some(total) => ProgressBar::new(total).with_style(
+                ProgressStyle::with_template(
+                    "  {bar:30} {bytes}/{total_bytes} {bytes_per_sec} {wide_msg}",
+                )
+                .unwrap(),
+            ),
+            None => ProgressBar::new(0).with_style(
+                ProgressStyle::with_template("  {spinner} {bytes} {bytes_per_sec} {wide_msg}")
+                    .unwrap(),
+            ),
+        };
+        let bar = MULTI_PROGRESS_BAR.add(bar.with_message(name));
+        let mut progress =
+            ProgressReader::new(res, coordinate.clone(), total).with_progress_bar(bar.clone());
+        if let Some(overall) = overall {
+            // without a content length the reader grows the bar by the bytes read,
+            // so the expected size is taken back out to count the file once
+            let total = total.unwrap_or(0);
+            let expected = expected.unwrap_or(0);
+            let _guard = OVERALL_LENGTH.lock().unwrap_or_else(|err| err.into_inner());
+            if total >= expected {
+                overall.inc_length(total - expected);
+            } else {
+                overall.set_length(
+                    overall
+                        .length()
+                        .unwrap_or(0)
+                        .saturating_sub(expected - total),
+                );
+            }
+            progress = progress.with_progress_bar(overall.clone());
+        }
+
         let mut writer = BufWriter::new(cache);
-        let mut reader = BufReader::new(res);
-        return copy(&mut reader, &mut writer)
-            .context("Failed copying network bytes to cached file");
+        let mut reader = BufReader::new(progress);
+        let result =
+            copy(&mut reader, &mut writer).context("Failed copying network bytes to cached file");
+        bar.finish_and_clear();
+        MULTI_PROGRESS_BAR.remove(&bar);
+        let size = result?;
+        let mut cache = writer
+            .into_inner()
+            .context("Failed flushing downloaded bytes to cached file")?;
+        cache.complete();
+        // recorded so that labt verify can detect corruption later
+        let path = cache.get_path()?;
+        record_checksum(&path).context(format!("Failed to record checksum of {:?}", path))?;
+        emit(Event::DownloadFinished { coordinate, size });
+        return Ok(size);
     }
     res.error_for_status()
         .context("Failed to complete request")?;
     Ok(0)
 }
+
+/// Copies the artifact of a local file:// repository into the cache
+fn copy_local(
+    project: &ProjectDep,
+    url: &Url,
+    overall: Option<&ProgressBar>,
+) -> anyhow::Result<u64> {
+    let path = url
+        .to_file_path()
+        .map_err(|_| anyhow::anyhow!("Invalid file url {}", url))?;
+    let file = File::open(&path).context(format!("Failed to open {:?}", path))?;
+
+    let mut cache = Cache::from(project);
+    cache.use_labt_home()?;
+    let mut writer = BufWriter::new(cache.create()?);
+    let size = copy(&mut BufReader::new(file), &mut writer)
+        .context("Failed copying local artifact to cached file")?;
+    let mut cache = writer
+        .into_inner()
+        .context("Failed flushing local artifact to cached file")?;
+    cache.complete();
+    if let Some(overall) = overall {
+        overall.inc(size);
+    }
+    let path = cache.get_path()?;
+    record_checksum(&path).context(format!("Failed to record checksum of {:?}", path))?;
+    emit(Event::DownloadFinished {
+        coordinate: format!(
+            "{}:{}:{}",
+            project.group_id, project.artifact_id, project.version
+        ),
+        size,
+    });
+    Ok(size)
+}
@@ -0,0 +1,439 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{create_dir_all, File},
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::{trace, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::lock::{load_labt_lock, lock_file_in, strings::LOCK_FILE, LabtLock},
+    pom::License,
+    submodules::{licenses::read_cached_licenses, resolve::ProjectDep},
+};
+
+use super::file_sha256;
+
+/// The dependency graph cache under the project build directory
+pub const GRAPH_FILE: &str = "dependency-graph.json";
+
+/// The part of Labt.lock a node was locked in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GraphSection {
+    Project,
+    Test,
+    Processors,
+}
+
+/// A dependency requested by a node
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphEdge {
+    pub group_id: String,
+    pub artifact_id: String,
+    /// The version the pom asked for, which may differ from the selected one
+    pub requested: Option<String>,
+}
+
+/// A locked artifact with the details the query commands would otherwise
+/// parse from the cached poms
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphNode {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub scope: String,
+    pub packaging: String,
+    pub section: GraphSection,
+    /// The licenses of the pom or the closest parent that declares them
+    pub licenses: Vec<License>,
+    /// The version constraints the node was resolved with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<String>,
+    pub dependencies: Vec<GraphEdge>,
+}
+
+impl GraphNode {
+    pub fn coordinate(&self) -> String {
+        format!("{}:{}:{}", self.group_id, self.artifact_id, self.version)
+    }
+}
+
+/// The resolved dependency graph of the project. It is rebuilt from Labt.lock
+/// and the cached poms whenever the lock changes.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// Sha256 of the Labt.lock the graph was built from
+    pub fingerprint: String,
+    pub nodes: Vec<GraphNode>,
+}
+
+impl DependencyGraph {
+    /// Builds the graph of lock, reading the licenses from the cached poms
+    pub fn from_lock(lock: &LabtLock, fingerprint: String) -> Self {
+        Self::from_lock_with(lock, fingerprint, |dep| {
+            read_cached_licenses(&dep.group_id, &dep.artifact_id, &dep.version).unwrap_or_else(
+                |err| {
+                    trace!(target: "graph", "{:?}", err);
+                    Vec::new()
+                },
+            )
+        })
+    }
+    /// Builds the graph of lock using licenses to find the licenses of each entry
+    pub fn from_lock_with<F>(lock: &LabtLock, fingerprint: String, licenses: F) -> Self
+    where
+        F: Fn(&ProjectDep) -> Vec<License>,
+    {
+        let sections = [
+            (GraphSection::Project, &lock.resolved),
+            (GraphSection::Test, &lock.test),
+            (GraphSection::Processors, &lock.processors),
+        ];
+        let nodes = sections
+            .into_iter()
+            .flat_map(|(section, deps)| deps.iter().map(move |dep| (section, dep)))
+            .map(|(section, dep)| GraphNode {
+                group_id: dep.group_id.clone(),
+                artifact_id: dep.artifact_id.clone(),
+                version: dep.version.clone(),
+                scope: dep.scope.to_string(),
+                packaging: dep.packaging.clone(),
+                section,
+                licenses: licenses(dep),
+                constraints: dep
+                    .constraints
+                    .as_ref()
+                    .map(|constraint| constraint.to_string()),
+                dependencies: dep
+                    .dependencies
+                    .iter()
+                    .filter_map(|child| {
+                        let mut parts = child.splitn(3, ':');
+                        Some(GraphEdge {
+                            group_id: parts.next()?.to_string(),
+                            artifact_id: parts.next()?.to_string(),
+                            requested: parts.next().map(String::from),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        DependencyGraph { fingerprint, nodes }
+    }
+    /// Returns the node of group_id:artifact_id that section sees. Test
+    /// dependencies share the project dependencies they depend on.
+    pub fn find(
+        &self,
+        section: GraphSection,
+        group_id: &str,
+        artifact_id: &str,
+    ) -> Option<&GraphNode> {
+        let find = |section: GraphSection| {
+            self.nodes.iter().find(|node| {
+                node.section == section
+                    && node.group_id == group_id
+                    && node.artifact_id == artifact_id
+            })
+        };
+        find(section).or_else(|| match section {
+            GraphSection::Test => find(GraphSection::Project),
+            _ => None,
+        })
+    }
+    /// The nodes with a dependency path down to target, target included, by
+    /// their address in nodes
+    fn reaching(&self, target: &GraphNode) -> HashSet<*const GraphNode> {
+        let mut dependents: HashMap<*const GraphNode, Vec<&GraphNode>> = HashMap::new();
+        for node in &self.nodes {
+            for edge in &node.dependencies {
+                if let Some(child) = self.find(node.section, &edge.group_id, &edge.artifact_id) {
+                    dependents
+                        .entry(child as *const GraphNode)
+                        .or_default()
+                        .push(node);
+                }
+            }
+        }
+        let mut reaching = HashSet::from([target as *const GraphNode]);
+        let mut queue = vec![target as *const GraphNode];
+        while let Some(node) = queue.pop() {
+            for &dependent in dependents.get(&node).into_iter().flatten() {
+                if reaching.insert(dependent) {
+                    queue.push(dependent);
+                }
+            }
+        }
+        reaching
+    }
+    /// The dependency paths from each of roots down to target, as the nodes
+    /// along the way. At most limit paths are returned. Only the dependencies
+    /// that lead to target are walked, so shared subtrees that do not contain
+    /// it are not expanded over and over.
+    pub fn paths_to<'a>(
+        &'a self,
+        roots: &[&'a GraphNode],
+        target: &GraphNode,
+        limit: usize,
+    ) -> Vec<Vec<&'a GraphNode>> {
+        fn walk<'a>(
+            graph: &'a DependencyGraph,
+            path: &mut Vec<&'a GraphNode>,
+            target: &GraphNode,
+            reaching: &HashSet<*const GraphNode>,
+            seen: &mut HashSet<(String, String)>,
+            paths: &mut Vec<Vec<&'a GraphNode>>,
+            limit: usize,
+        ) {
+            let Some(&node) = path.last() else {
+                return;
+            };
+            if node == target {
+                paths.push(path.clone());
+                return;
+            }
+            for edge in &node.dependencies {
+                if paths.len() >= limit {
+                    return;
+                }
+                let Some(child) = graph
+                    .find(node.section, &edge.group_id, &edge.artifact_id)
+                    .filter(|&child| reaching.contains(&(child as *const GraphNode)))
+                else {
+                    continue;
+                };
+                // dependency cycles are not followed around again
+                let key = (child.group_id.clone(), child.artifact_id.clone());
+                if !seen.insert(key.clone()) {
+                    continue;
+                }
+                path.push(child);
+                walk(graph, path, target, reaching, seen, paths, limit);
+                path.pop();
+                seen.remove(&key);
+            }
+        }
+
+        let reaching = self.reaching(target);
+        let mut paths = Vec::new();
+        for &root in roots {
+            if paths.len() >= limit {
+                break;
+            }
+            if !reaching.contains(&(root as *const GraphNode)) {
+                continue;
+            }
+            let mut seen = HashSet::from([(root.group_id.clone(), root.artifact_id.clone())]);
+            walk(
+                self,
+                &mut vec![root],
+                target,
+                &reaching,
+                &mut seen,
+                &mut paths,
+                limit,
+            );
+        }
+        paths
+    }
+}
+
+/// The path of the dependency graph cache of the project at root
+pub fn graph_path(root: &Path) -> PathBuf {
+    root.join("build").join(GRAPH_FILE)
+}
+
+/// Writes graph to the cache of the project at root
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be written
+pub fn write_graph(root: &Path, graph: &DependencyGraph) -> anyhow::Result<()> {
+    let path = graph_path(root);
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+    }
+    let file = File::create(&path).context(format!("Failed to create {:?}", path))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, graph)
+        .context(format!("Failed to write dependency graph to {:?}", path))?;
+    writer
+        .flush()
+        .context(format!("Failed to write dependency graph to {:?}", path))?;
+    Ok(())
+}
+
+/// Reads the cached graph of the project at root. A missing or unreadable
+/// cache is not an error since it is rebuilt from Labt.lock.
+pub fn read_graph(root: &Path) -> Option<DependencyGraph> {
+    let path = graph_path(root);
+    let file = File::open(&path).ok()?;
+    serde_json::from_reader(BufReader::new(file))
+        .map_err(|err| trace!(target: "graph", "Ignoring {:?}: {}", path, err))
+        .ok()
+}
+
+/// Rebuilds the graph cache of the project at root from lock, which must be
+/// the contents of its Labt.lock
+///
+/// # Errors
+///
+/// Returns an error if Labt.lock cannot be read or the cache cannot be written
+pub fn refresh_graph(root: &Path, lock: &LabtLock) -> anyhow::Result<DependencyGraph> {
+    let fingerprint =
+        file_sha256(&lock_file_in(root)).context(format!("Failed to read {}", LOCK_FILE))?;
+    let graph = DependencyGraph::from_lock(lock, fingerprint);
+    write_graph(root, &graph)?;
+    Ok(graph)
+}
+
+/// Returns the graph cache of the project at root, rebuilding it if Labt.lock
+/// changed since it was written
+///
+/// # Errors
+///
+/// Returns an error if Labt.lock cannot be loaded
+pub fn load_graph(root: &Path) -> anyhow::Result<DependencyGraph> {
+    let fingerprint = file_sha256(&lock_file_in(root)).context(format!(
+        "Failed to read {}. Try running labt resolve",
+        LOCK_FILE
+    ))?;
+    if let Some(graph) = read_graph(root) {
+        if graph.fingerprint == fingerprint {
+            return Ok(graph);
+        }
+    }
+    let lock = load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+    let graph = DependencyGraph::from_lock(&lock, fingerprint);
+    if let Err(err) = write_graph(root, &graph) {
+        warn!(target: "graph", "{:?}", err);
+    }
+    Ok(graph)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn dependency_graph_paths() {
+    let dep = |artifact_id: &str, dependencies: &[&str]| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: String::from("1.0.0"),
+        packaging: String::from("jar"),
+        dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        ..Default::default()
+    };
+    let lock = LabtLock {
+        resolved: vec![
+            dep("shared", &["com.example:app:1.0.0"]),
+            dep("core", &["com.example:shared:0.9.0"]),
+            dep("app", &["com.example:core:1.0.0", "com.example:shared"]),
+        ],
+        test: vec![dep("junit", &["com.example:shared:1.0.0"])],
+        ..Default::default()
+    };
+    let graph = DependencyGraph::from_lock_with(&lock, String::from("abc"), |dep| {
+        vec![License {
+            name: format!("{} license", dep.artifact_id),
+            url: None,
+        }]
+    });
+
+    let json = serde_json::to_string(&graph).unwrap();
+    assert_eq!(
+        serde_json::from_str::<DependencyGraph>(&json).unwrap(),
+        graph
+    );
+    assert_eq!(graph.nodes[1].licenses[0].name, "core license");
+    assert_eq!(
+        graph.nodes[1].dependencies,
+        vec![GraphEdge {
+            group_id: String::from("com.example"),
+            artifact_id: String::from("shared"),
+            requested: Some(String::from("0.9.0")),
+        }]
+    );
+
+    let shared = graph
+        .find(GraphSection::Test, "com.example", "shared")
+        .unwrap();
+    assert_eq!(shared.section, GraphSection::Project);
+    assert!(graph
+        .find(GraphSection::Processors, "com.example", "shared")
+        .is_none());
+
+    let roots = [
+        graph
+            .find(GraphSection::Project, "com.example", "app")
+            .unwrap(),
+        graph
+            .find(GraphSection::Test, "com.example", "junit")
+            .unwrap(),
+    ];
+    let describe = |paths: Vec<Vec<&GraphNode>>| -> Vec<String> {
+        paths
+            .iter()
+            .map(|path| {
+                path.iter()
+                    .map(|node| node.artifact_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            })
+            .collect()
+    };
+    assert_eq!(
+        describe(graph.paths_to(&roots, shared, 10)),
+        vec!["app -> core -> shared", "app -> shared", "junit -> shared"]
+    );
+    assert_eq!(
+        describe(graph.paths_to(&roots, shared, 1)),
+        vec!["app -> core -> shared"]
+    );
+
+    // layers of diamonds that do not lead to the target are not walked
+    let layers = 40;
+    let layer = |i: usize| {
+        if i == layers {
+            Vec::new()
+        } else {
+            vec![
+                format!("com.example:a{i}:1.0.0"),
+                format!("com.example:b{i}:1.0.0"),
+            ]
+        }
+    };
+    let mut resolved = vec![dep("target", &[])];
+    for i in 0..layers {
+        let next: Vec<String> = layer(i + 1);
+        let next: Vec<&str> = next.iter().map(String::as_str).collect();
+        resolved.push(dep(&format!("a{i}"), &next));
+        resolved.push(dep(&format!("b{i}"), &next));
+    }
+    resolved.push(dep(
+        "root",
+        &[
+            "com.example:a0:1.0.0",
+            "com.example:b0:1.0.0",
+            "com.example:target:1.0.0",
+        ],
+    ));
+    let lock = LabtLock {
+        resolved,
+        ..Default::default()
+    };
+    let graph = DependencyGraph::from_lock_with(&lock, String::new(), |_| Vec::new());
+    let root = graph
+        .find(GraphSection::Project, "com.example", "root")
+        .unwrap();
+    let target = graph
+        .find(GraphSection::Project, "com.example", "target")
+        .unwrap();
+    assert_eq!(
+        describe(graph.paths_to(&[root], target, 10)),
+        vec!["root -> target"]
+    );
+}
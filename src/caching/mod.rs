@@ -1,19 +1,43 @@
 use std::{
+    collections::{HashSet, VecDeque},
     fs::{create_dir_all, File},
-    io::{Read, Write},
-    path::PathBuf,
+    io::{BufReader, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
 };
 
 pub mod download;
+pub mod graph;
 pub mod properties;
+pub mod snapshot;
+pub mod validators;
 
 use anyhow::{bail, Context};
-use indicatif::{HumanBytes, ProgressBar};
+use indicatif::{HumanBytes, ProgressBar, ProgressState, ProgressStyle};
 use log::info;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
 
-use crate::{get_home, submodules::resolve::ProjectDep, MULTI_PROGRESS_BAR};
+use crate::{
+    config::global::{client_builder, download_jobs},
+    events::{emit, Event},
+    get_home,
+    interrupt::PartialFile,
+    paths::{long_path, sanitize_component},
+    pom::{parse_pom, Project, Scope},
+    session::Session,
+    submodules::{licenses::MAX_PARENT_DEPTH, resolve::ProjectDep},
+    MULTI_PROGRESS_BAR,
+};
 
-use self::{download::download, properties::write_properties};
+use self::{
+    download::{artifact_size, download_with_progress},
+    properties::write_properties,
+};
 #[derive(Clone, Debug)]
 pub enum CacheType {
     POM,
@@ -23,6 +47,8 @@ pub enum CacheType {
     PROPERTIES,
     // The V level maven-metadata.xml
     METADATA,
+    // The maven-metadata.xml of a snapshot version listing its latest build
+    SNAPSHOT,
     UNKNOWN(String),
 }
 #[derive(Debug)]
@@ -33,6 +59,8 @@ pub struct Cache {
     cache_type: CacheType,
     path: Option<PathBuf>,
     file: Option<File>,
+    /// Set while a newly created file is being written
+    partial: Option<PartialFile>,
 }
 
 impl Cache {
@@ -49,6 +77,7 @@ impl Cache {
             cache_type,
             path: None,
             file: None,
+            partial: None,
         }
     }
     pub fn get_cache_path(&self) -> Option<PathBuf> {
@@ -73,7 +102,7 @@ impl Cache {
                 format!("{}-{}.{}", self.artifact_id, self.version, ext)
             }
             CacheType::PROPERTIES => format!("{}-{}.toml", self.artifact_id, self.version),
-            CacheType::METADATA => "maven-metadata.xml".to_string(),
+            CacheType::METADATA | CacheType::SNAPSHOT => "maven-metadata.xml".to_string(),
         }
     }
     fn build_path(&self) -> std::io::Result<PathBuf> {
@@ -85,25 +114,35 @@ impl Cache {
         }
 
         let mut path = self.path.clone().unwrap();
-        path.push(&self.group_id);
-        path.push(&self.artifact_id);
+        path.push(sanitize_component(&self.group_id).as_ref());
+        path.push(sanitize_component(&self.artifact_id).as_ref());
         if !matches!(self.cache_type, CacheType::METADATA) {
-            path.push(&self.version);
+            path.push(sanitize_component(&self.version).as_ref());
         }
+        // group ids and artifact ids add up past MAX_PATH on windows
+        let mut path = long_path(&path);
         if !path.exists() {
             create_dir_all(&path)?;
         }
-        path.push(self.get_name_from_type());
+        path.push(sanitize_component(&self.get_name_from_type()).as_ref());
 
         Ok(path)
     }
     pub fn create(self) -> std::io::Result<Cache> {
         let mut cache = self;
         let path = cache.build_path()?;
-        let file = File::create(path)?;
+        let file = File::create(&path)?;
         cache.file = Some(file);
+        cache.partial = Some(PartialFile::new(path));
         Ok(cache)
     }
+    /// Marks a created cache file as fully written. Files that are not
+    /// completed, because writing failed or labt got interrupted, are removed.
+    pub fn complete(&mut self) {
+        if let Some(partial) = &mut self.partial {
+            partial.complete();
+        }
+    }
     pub fn open(self) -> std::io::Result<Cache> {
         let mut cache = self;
         let path = cache.build_path()?;
@@ -131,10 +170,12 @@ impl Cache {
             bail!("Cache base dir not specified.");
         }
         let mut path = self.path.clone().unwrap();
-        path.push(&self.group_id);
-        path.push(&self.artifact_id);
-        path.push(&self.version);
-        path.push(self.get_name_from_type());
+        path.push(sanitize_component(&self.group_id).as_ref());
+        path.push(sanitize_component(&self.artifact_id).as_ref());
+        if !matches!(self.cache_type, CacheType::METADATA) {
+            path.push(sanitize_component(&self.version).as_ref());
+        }
+        path.push(sanitize_component(&self.get_name_from_type()).as_ref());
 
         Ok(path)
     }
@@ -145,8 +186,7 @@ impl Write for Cache {
         if let Some(file) = &mut self.file {
             file.write(buf)
         } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            Err(std::io::Error::other(
                 "Invalid state: cache file not initialized",
             ))
         }
@@ -155,8 +195,7 @@ impl Write for Cache {
         if let Some(file) = &mut self.file {
             file.flush()
         } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            Err(std::io::Error::other(
                 "Invalid state: cache file not initialized",
             ))
         }
@@ -167,8 +206,7 @@ impl Read for Cache {
         if let Some(file) = &mut self.file {
             file.read(buf)
         } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::Other,
+            Err(std::io::Error::other(
                 "Invalid state: cache file not initialized",
             ))
         }
@@ -221,37 +259,414 @@ impl From<&Cache> for Cache {
             cache_type: cache.cache_type.clone(),
             path: cache.path.clone(),
             file: None,
+            partial: None,
         }
     }
 }
 
+/// Path of the file holding the sha1 checksum recorded for a cached artifact
+pub fn checksum_path(artifact: &Path) -> PathBuf {
+    let mut name = artifact.as_os_str().to_owned();
+    name.push(".sha1");
+    PathBuf::from(name)
+}
+
+/// True if the cached maven-metadata.xml of an artifact was fetched or
+/// revalidated longer than ttl ago. Missing metadata is not stale, the cache
+/// just does not have it
+pub fn metadata_is_stale(group_id: &str, artifact_id: &str, ttl: Duration) -> bool {
+    let mut cache = Cache::new(
+        group_id.to_string(),
+        artifact_id.to_string(),
+        String::new(),
+        CacheType::METADATA,
+    );
+    let modified = cache
+        .use_labt_home()
+        .and_then(|_| cache.get_path())
+        .ok()
+        .and_then(|path| path.metadata().ok())
+        .and_then(|metadata| metadata.modified().ok());
+    modified.is_some_and(|modified| modified.elapsed().map_or(true, |age| age >= ttl))
+}
+
+/// A group_id, artifact_id and version
+pub type Coordinate = (String, String, String);
+
+/// The parent poms and the boms imported by the dependencyManagement of the pom
+/// at coordinate, followed recursively. read returns the parsed pom of a
+/// coordinate, None ends the walk along that branch
+pub fn pom_ancestors(
+    coordinate: &Coordinate,
+    mut read: impl FnMut(&Coordinate) -> anyhow::Result<Option<Project>>,
+) -> anyhow::Result<Vec<Coordinate>> {
+    let mut ancestors = Vec::new();
+    let mut seen = HashSet::from([coordinate.clone()]);
+    let mut queue = VecDeque::from([(coordinate.clone(), 0)]);
+    while let Some((coordinate, depth)) = queue.pop_front() {
+        if depth >= MAX_PARENT_DEPTH {
+            continue;
+        }
+        let Some(project) = read(&coordinate)? else {
+            continue;
+        };
+        let parent = project.parent.iter().map(|parent| {
+            (
+                parent.group_id.clone(),
+                parent.artifact_id.clone(),
+                parent.version.clone(),
+            )
+        });
+        let mut imports: Vec<Coordinate> = project
+            .get_dependency_management()
+            .values()
+            .filter(|dep| dep.get_scope() == Scope::IMPORT)
+            .filter_map(|dep| {
+                Some((
+                    dep.get_group_id(),
+                    dep.get_artifact_id(),
+                    dep.get_selected_version().clone()?,
+                ))
+            })
+            .collect();
+        imports.sort();
+        for next in parent.chain(imports) {
+            if seen.insert(next.clone()) {
+                ancestors.push(next.clone());
+                queue.push_back((next, depth + 1));
+            }
+        }
+    }
+    Ok(ancestors)
+}
+
+/// The [`pom_ancestors`] of a pom in the labt cache, which resolution reads again
+/// when it runs offline
+///
+/// # Errors
+///
+/// Returns an error if a cached pom cannot be read or parsed
+pub fn cached_pom_ancestors(coordinate: &Coordinate) -> anyhow::Result<Vec<Coordinate>> {
+    pom_ancestors(coordinate, |(group_id, artifact_id, version)| {
+        let mut cache = Cache::new(
+            group_id.clone(),
+            artifact_id.clone(),
+            version.clone(),
+            CacheType::POM,
+        );
+        cache.use_labt_home()?;
+        if !cache.exists() {
+            return Ok(None);
+        }
+        let file = cache.open()?;
+        let project = parse_pom(
+            BufReader::new(file),
+            Project::new(group_id, artifact_id, version),
+        )
+        .context(format!(
+            "Failed to parse pom for {}:{}:{}",
+            group_id, artifact_id, version
+        ))?;
+        Ok(Some(project))
+    })
+}
+
+/// Calculates the sha1 checksum of a file as a lowercase hex string
+pub fn file_sha1(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut sha = Sha1::new();
+    std::io::copy(&mut file, &mut sha)?;
+    Ok(format!("{:x}", sha.finalize()))
+}
+
+/// Calculates the sha256 checksum of a file as a lowercase hex string
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut sha = Sha256::new();
+    std::io::copy(&mut file, &mut sha)?;
+    Ok(format!("{:x}", sha.finalize()))
+}
+
+/// Checks a cached artifact against the sha256 recorded for it in Labt.lock
+///
+/// # Errors
+///
+/// Returns an error if the artifact cannot be read or its checksum differs
+pub fn check_locked_sha256(project: &ProjectDep, cache: &Cache) -> anyhow::Result<()> {
+    let Some(expected) = &project.sha256 else {
+        return Ok(());
+    };
+    let path = cache.get_path()?;
+    let actual = file_sha256(&path).context(format!("Failed to hash {:?}", path))?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(
+            "Checksum mismatch for {}:{}:{} from {}\n  Labt.lock: sha256 {}\n  artifact:  sha256 {}\nThe artifact changed since it was locked. Delete {:?} to download it again, or remove its sha256 from Labt.lock if the new artifact is trusted",
+            project.group_id,
+            project.artifact_id,
+            project.version,
+            project.get_root_url(),
+            expected,
+            actual,
+            path
+        );
+    }
+    Ok(())
+}
+
+/// Writes the sha1 checksum of a cached artifact next to it
+pub fn record_checksum(artifact: &Path) -> std::io::Result<()> {
+    let checksum = file_sha1(artifact)?;
+    std::fs::write(checksum_path(artifact), checksum)
+}
+
+/// Reads the checksum recorded for a cached artifact if any
+pub fn recorded_checksum(artifact: &Path) -> Option<String> {
+    std::fs::read_to_string(checksum_path(artifact))
+        .ok()
+        .map(|checksum| checksum.trim().to_lowercase())
+}
+
+/// Runs work for every item on up to jobs threads and returns the results in the
+/// order of items. Once work fails or labt is interrupted no more items are started
+/// and the first error is returned.
+pub fn run_parallel<T: Sync, R: Send>(
+    items: &[T],
+    jobs: usize,
+    work: impl Fn(&T) -> anyhow::Result<R> + Sync,
+) -> anyhow::Result<Vec<R>> {
+    let queue = Mutex::new(items.iter().enumerate());
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+    let failure: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+    let session = Session::current();
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                session.enter(|| loop {
+                    if crate::interrupt::is_interrupted() || failure.lock().unwrap().is_some() {
+                        return;
+                    }
+                    let Some((index, item)) = queue.lock().unwrap().next() else {
+                        return;
+                    };
+                    match work(item) {
+                        Ok(result) => results.lock().unwrap()[index] = Some(result),
+                        Err(err) => {
+                            failure.lock().unwrap().get_or_insert(err);
+                            return;
+                        }
+                    }
+                })
+            });
+        }
+    });
+    if let Some(err) = failure.into_inner().unwrap() {
+        return Err(err);
+    }
+    crate::interrupt::check()?;
+    Ok(results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect())
+}
+
 pub fn save_dependencies(deps: &Vec<ProjectDep>) -> anyhow::Result<()> {
     // if it was a cache miss, then write properties to file for the next resolution
     for project in deps.iter().filter(|p| !p.cache_hit) {
         write_properties(project)?;
     }
-    // initialize a new progressbar
-    let pb = MULTI_PROGRESS_BAR.add(ProgressBar::new(deps.len() as u64));
-    // begin the download  of the dependencies
+    let mut missing = Vec::new();
     for project in deps {
         let mut cache = Cache::from(project);
         cache.use_labt_home().context(format!(
             "Unable to access Labt home for {}:{}:{}",
             project.group_id, project.artifact_id, project.version
         ))?;
-        // increment progressbar
-        pb.inc(1);
         // if it is a cache hit, skip
         if cache.exists() {
+            check_locked_sha256(project, &cache)?;
             info!(target: "fetch", "Cache hit {}", cache.get_name_from_type());
+            emit(Event::CacheHit {
+                coordinate: format!(
+                    "{}:{}:{}",
+                    project.group_id, project.artifact_id, project.version
+                ),
+            });
             continue;
         }
-        let size = download(project).context(format!(
+        missing.push((project, cache));
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    if crate::is_offline() {
+        // local repositories are copied without network access
+        let remote: Vec<String> = missing
+            .iter()
+            .filter(|(project, _)| !project.base_url.starts_with("file:"))
+            .map(|(project, _)| {
+                format!(
+                    "{}:{}:{} ({})",
+                    project.group_id,
+                    project.artifact_id,
+                    project.version,
+                    project.get_root_url()
+                )
+            })
+            .collect();
+        if !remote.is_empty() {
+            bail!(
+                "Offline mode: these artifacts are not in the labt cache and need network access:\n  {}\nRun again without --offline to download them",
+                remote.join("\n  ")
+            );
+        }
+    }
+
+    let jobs = download_jobs();
+    // sizes are looked up first so that the bytes remaining are known from the start
+    let client = client_builder()?
+        .build()
+        .context("Error creating download client")?;
+    let checking = MULTI_PROGRESS_BAR.add(ProgressBar::new(missing.len() as u64));
+    checking
+        .set_style(ProgressStyle::with_template("{spinner} Checking sizes {pos}/{len}").unwrap());
+    let sizes = run_parallel(&missing, jobs, |(project, _)| {
+        let size = artifact_size(&client, project);
+        checking.inc(1);
+        Ok(size)
+    });
+    checking.finish_and_clear();
+    MULTI_PROGRESS_BAR.remove(&checking);
+    let sizes: Vec<Option<u64>> = sizes?;
+
+    let pb = MULTI_PROGRESS_BAR.add(ProgressBar::new(sizes.iter().flatten().sum()));
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner} Downloading [{msg}] {bytes}/{total_bytes} {remaining} remaining {bytes_per_sec}",
+        )
+        .unwrap()
+        .with_key("remaining", |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+            let remaining = state.len().unwrap_or(0).saturating_sub(state.pos());
+            let _ = write!(w, "{}", HumanBytes(remaining));
+        }),
+    );
+    let count = missing.len();
+    let done = AtomicUsize::new(0);
+    pb.set_message(format!("0/{count}"));
+    // begin the download of the dependencies, each file gets its own bar
+    let downloads: Vec<_> = missing.iter().zip(sizes).collect();
+    let result = run_parallel(&downloads, jobs, |((project, cache), size)| {
+        let downloaded = download_with_progress(project, Some(&pb), *size).context(format!(
             "Failed to download dependency from [{}]",
             project.get_root_url()
         ))?;
-        info!(target: "fetch", "Downloaded {} {}", cache.get_name_from_type(), HumanBytes(size));
-    }
+        info!(target: "fetch", "Downloaded {} {}", cache.get_name_from_type(), HumanBytes(downloaded));
+        check_locked_sha256(project, cache)?;
+        let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+        pb.set_message(format!("{done}/{count}"));
+        Ok(())
+    });
+    pb.finish_and_clear();
+    MULTI_PROGRESS_BAR.remove(&pb);
 
-    Ok(())
+    result.map(|_| ())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn cache_path_sanitizes_coordinates() {
+    let mut cache = Cache::new(
+        String::from("com.example"),
+        String::from("widgets"),
+        String::from("1.0:beta/2"),
+        CacheType::AAR,
+    );
+    cache.set_cache_path(Some(PathBuf::from("cache")));
+    assert_eq!(
+        cache.get_path().unwrap(),
+        Path::new("cache")
+            .join("com.example")
+            .join("widgets")
+            .join("1.0_beta_2")
+            .join("widgets-1.0_beta_2.aar")
+    );
+}
+
+#[test]
+fn run_parallel_keeps_order_and_stops_on_error() {
+    let items: Vec<u64> = (1..=20).collect();
+    let doubled = run_parallel(&items, 4, |item| {
+        // later items finish first
+        std::thread::sleep(std::time::Duration::from_millis(20 - item));
+        Ok(item * 2)
+    })
+    .unwrap();
+    assert_eq!(
+        doubled,
+        items.iter().map(|item| item * 2).collect::<Vec<_>>()
+    );
+
+    let started = AtomicUsize::new(0);
+    let result = run_parallel(&items, 1, |item| {
+        started.fetch_add(1, Ordering::SeqCst);
+        if *item == 3 {
+            bail!("failed {item}");
+        }
+        Ok(())
+    });
+    assert_eq!(result.unwrap_err().to_string(), "failed 3");
+    assert_eq!(started.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn pom_ancestors_follow_parents_and_imports() {
+    let poms = std::collections::HashMap::from([
+        (
+            "app",
+            r#"<project><parent><groupId>org.example</groupId><artifactId>parent</artifactId><version>1</version></parent>
+<dependencyManagement><dependencies>
+<dependency><groupId>org.example</groupId><artifactId>bom</artifactId><version>2</version><type>pom</type><scope>import</scope></dependency>
+<dependency><groupId>org.example</groupId><artifactId>managed</artifactId><version>3</version></dependency>
+</dependencies></dependencyManagement></project>"#,
+        ),
+        (
+            "bom",
+            r#"<project><parent><groupId>org.example</groupId><artifactId>bom-parent</artifactId><version>2</version></parent></project>"#,
+        ),
+        (
+            "parent",
+            r#"<project><parent><groupId>org.example</groupId><artifactId>bom-parent</artifactId><version>2</version></parent></project>"#,
+        ),
+    ]);
+    let coordinate = |artifact_id: &str, version: &str| {
+        (
+            String::from("org.example"),
+            artifact_id.to_string(),
+            version.to_string(),
+        )
+    };
+    let ancestors = pom_ancestors(
+        &coordinate("app", "1"),
+        |(group_id, artifact_id, version)| {
+            Ok(poms.get(artifact_id.as_str()).map(|pom| {
+                parse_pom(
+                    BufReader::new(pom.as_bytes()),
+                    Project::new(group_id, artifact_id, version),
+                )
+                .unwrap()
+            }))
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        ancestors,
+        vec![
+            coordinate("parent", "1"),
+            coordinate("bom", "2"),
+            coordinate("bom-parent", "2"),
+        ]
+    );
 }
@@ -66,6 +66,7 @@ pub fn write_properties(project: &ProjectDep) -> anyhow::Result<()> {
         .context(PropertiesError::IOError(
             "Failed to write properties file".to_string(),
         ))?;
+    cache.complete();
 
     Ok(())
 }
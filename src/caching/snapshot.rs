@@ -0,0 +1,125 @@
+use std::{
+    io::BufReader,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    config::maven_metadata::{is_snapshot, parse_maven_metadata, MavenMetadata},
+    submodules::resolve::ProjectDep,
+};
+
+use super::{Cache, CacheType};
+
+/// How long the cached metadata of a snapshot version is used before checking the
+/// repository for a newer build, if not configured. Maven checks daily by default.
+pub const DEFAULT_SNAPSHOT_REFRESH: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn snapshot_metadata_cache(group_id: &str, artifact_id: &str, version: &str) -> Cache {
+    Cache::new(
+        group_id.to_string(),
+        artifact_id.to_string(),
+        version.to_string(),
+        CacheType::SNAPSHOT,
+    )
+}
+
+/// Reads the cached maven-metadata.xml of a snapshot version together with when it was
+/// last fetched. None if it is not cached or cannot be parsed.
+pub fn read_snapshot_metadata(
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+) -> Option<(MavenMetadata, SystemTime)> {
+    let mut cache = snapshot_metadata_cache(group_id, artifact_id, version);
+    cache.use_labt_home().ok()?;
+    let path = cache.get_path().ok()?;
+    let fetched = path.metadata().ok()?.modified().ok()?;
+    let file = std::fs::File::open(&path).ok()?;
+    let metadata = parse_maven_metadata(BufReader::new(file)).ok()?;
+    Some((metadata, fetched))
+}
+
+/// True if the cached metadata of the snapshot version was fetched longer than refresh ago,
+/// or is missing
+pub fn snapshot_is_stale(
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    refresh: Duration,
+) -> bool {
+    match read_snapshot_metadata(group_id, artifact_id, version) {
+        Some((_, fetched)) => fetched.elapsed().map_or(true, |elapsed| elapsed >= refresh),
+        None => true,
+    }
+}
+
+/// Saves the metadata of a snapshot version fetched from a repository. If it describes
+/// a different build than the cached one, the cached files of the old build are removed
+/// so that they are downloaded again.
+///
+/// # Errors
+///
+/// Returns an error if the cache cannot be written
+pub fn save_snapshot_metadata(
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    metadata: &MavenMetadata,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    let previous = read_snapshot_metadata(group_id, artifact_id, version);
+    let mut cache = snapshot_metadata_cache(group_id, artifact_id, version);
+    cache.use_labt_home()?;
+    let path = cache.get_path()?;
+    let rebuilt = previous.is_some_and(|(previous, _)| {
+        previous.snapshot != metadata.snapshot
+            || previous.snapshot_versions != metadata.snapshot_versions
+    });
+    if rebuilt {
+        if let Some(dir) = path.parent() {
+            remove_build_files(dir, &path)?;
+        }
+    }
+    let mut cache = cache.create()?;
+    std::io::Write::write_all(&mut cache, body)?;
+    cache.complete();
+    Ok(())
+}
+
+/// Removes the files of a version directory except keep
+fn remove_build_files(dir: &Path, keep: &Path) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path != keep && path.is_file() {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The version the file with the extension is named with in the repository. For snapshot
+/// versions this is the timestamped version of the latest build in the cached metadata.
+pub fn remote_file_version(
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+    extension: &str,
+) -> String {
+    if !is_snapshot(version) {
+        return version.to_string();
+    }
+    read_snapshot_metadata(group_id, artifact_id, version)
+        .and_then(|(metadata, _)| metadata.snapshot_version(extension))
+        .unwrap_or_else(|| version.to_string())
+}
+
+/// [`remote_file_version`] of a resolved dependency
+pub fn remote_artifact_version(project: &ProjectDep, extension: &str) -> String {
+    remote_file_version(
+        &project.group_id,
+        &project.artifact_id,
+        &project.version,
+        extension,
+    )
+}
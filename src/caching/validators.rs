@@ -0,0 +1,146 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use reqwest::{
+    blocking::RequestBuilder,
+    header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+};
+use serde::{Deserialize, Serialize};
+
+/// The ETag and Last-Modified date a repository sent with a cached file. They
+/// are sent back when the file is requested again so that an unchanged file is
+/// answered with 304 Not Modified instead of its contents.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Validators {
+    /// The url the file was downloaded from. The cache is shared by all the
+    /// repositories so the validators only apply to the same url
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    /// The validators of a response for url. None if the repository sent neither
+    pub fn from_headers(url: &str, headers: &HeaderMap) -> Option<Self> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let etag = header(ETAG);
+        let last_modified = header(LAST_MODIFIED);
+        if etag.is_none() && last_modified.is_none() {
+            return None;
+        }
+        Some(Validators {
+            url: url.to_string(),
+            etag,
+            last_modified,
+        })
+    }
+    /// Makes request conditional on the cached file having changed
+    pub fn apply(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// The file the validators of the cached file at path are saved to
+pub fn validators_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".validators");
+    PathBuf::from(name)
+}
+
+/// Reads the validators saved for the cached file at path if they were
+/// received from url
+pub fn read_validators(path: &Path, url: &str) -> Option<Validators> {
+    if !path.exists() {
+        return None;
+    }
+    let file = File::open(validators_path(path)).ok()?;
+    let validators: Validators = serde_json::from_reader(BufReader::new(file)).ok()?;
+    (validators.url == url).then_some(validators)
+}
+
+/// Saves the validators received with the cached file at path. Validators
+/// left from an earlier download are removed if there are none.
+///
+/// # Errors
+///
+/// Returns an error if the validators file cannot be written or removed
+pub fn save_validators(path: &Path, validators: Option<&Validators>) -> anyhow::Result<()> {
+    let validators_path = validators_path(path);
+    let Some(validators) = validators else {
+        if validators_path.exists() {
+            std::fs::remove_file(&validators_path)
+                .context(format!("Failed to remove {:?}", validators_path))?;
+        }
+        return Ok(());
+    };
+    let file = File::create(&validators_path)
+        .context(format!("Failed to create {:?}", validators_path))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, validators)
+        .context(format!("Failed to write {:?}", validators_path))?;
+    writer
+        .flush()
+        .context(format!("Failed to write {:?}", validators_path))?;
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn validators_are_saved_per_url() {
+    use reqwest::header::HeaderValue;
+
+    let url = "https://repo1.maven.org/maven2/com/example/core/maven-metadata.xml";
+    let mut headers = HeaderMap::new();
+    assert_eq!(Validators::from_headers(url, &headers), None);
+    headers.insert(ETAG, HeaderValue::from_static("\"5f2b\""));
+    headers.insert(
+        LAST_MODIFIED,
+        HeaderValue::from_static("Wed, 01 May 2024 10:15:30 GMT"),
+    );
+    let validators = Validators::from_headers(url, &headers).unwrap();
+
+    let request = validators
+        .apply(reqwest::blocking::Client::new().get(url))
+        .build()
+        .unwrap();
+    assert_eq!(request.headers()[IF_NONE_MATCH], "\"5f2b\"");
+    assert_eq!(
+        request.headers()[IF_MODIFIED_SINCE],
+        "Wed, 01 May 2024 10:15:30 GMT"
+    );
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("validators");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("maven-metadata.xml");
+    std::fs::write(&path, "<metadata />").unwrap();
+    save_validators(&path, Some(&validators)).unwrap();
+    let saved = read_validators(&path, url);
+    let other = read_validators(
+        &path,
+        "https://maven.google.com/com/example/core/maven-metadata.xml",
+    );
+    save_validators(&path, None).unwrap();
+    let left = validators_path(&path).exists();
+
+    assert_eq!(saved, Some(validators));
+    assert_eq!(other, None);
+    assert!(!left);
+}
@@ -1,20 +1,75 @@
-use crate::submodules::add::{Add, AddArgs};
-use crate::submodules::build::{Build, BuildArgs};
-use crate::submodules::init::{Init, InitArgs};
-use crate::submodules::plugin::{Plugin, PluginArgs};
-use crate::submodules::resolve::{Resolve, ResolveArgs};
-use crate::submodules::sdk::{Sdk, SdkArgs};
-use crate::submodules::Submodule;
-use crate::LABT_VERSION;
 use clap::{CommandFactory, Parser, Subcommand};
 use console::style;
+use labt_core::config::budgets::warn_over_budget;
+use labt_core::config::global::get_global_config;
+use labt_core::events::{set_message_format, MessageFormat};
+use labt_core::http_recording::{set_http_recording, HttpRecording};
+use labt_core::metrics::{record_metrics, take_metrics};
+use labt_core::output::{set_output_format, OutputFormat};
+use labt_core::submodules::add::{Add, AddArgs};
+use labt_core::submodules::apk::{Apk, ApkArgs};
+use labt_core::submodules::build::{Build, BuildArgs};
+use labt_core::submodules::bundle_offline::{BundleOffline, BundleOfflineArgs};
+use labt_core::submodules::cache::{Cache, CacheArgs};
+use labt_core::submodules::classpath::{ExportClasspath, ExportClasspathArgs};
+use labt_core::submodules::clean::{Clean, CleanArgs};
+use labt_core::submodules::deps::{Deps, DepsArgs};
+use labt_core::submodules::doctor::{Doctor, DoctorArgs};
+use labt_core::submodules::fmt::{Fmt, FmtArgs};
+use labt_core::submodules::foreach::{Foreach, ForeachArgs};
+use labt_core::submodules::ide::{Ide, IdeArgs};
+use labt_core::submodules::index::{Index, IndexArgs};
+use labt_core::submodules::init::{Init, InitArgs};
+use labt_core::submodules::inspect::{Inspect, InspectArgs};
+use labt_core::submodules::licenses::{Licenses, LicensesArgs};
+use labt_core::submodules::lock::{Lock, LockArgs};
+use labt_core::submodules::migrate::{Migrate, MigrateArgs};
+use labt_core::submodules::outdated::{Outdated, OutdatedArgs};
+use labt_core::submodules::plugin::{Plugin, PluginArgs};
+use labt_core::submodules::prefetch::{Prefetch, PrefetchArgs};
+use labt_core::submodules::rename::{Rename, RenameArgs};
+use labt_core::submodules::resolve::{Resolve, ResolveArgs};
+use labt_core::submodules::sbom::{Sbom, SbomArgs};
+use labt_core::submodules::sdk::{Sdk, SdkArgs};
+use labt_core::submodules::search::{Search, SearchArgs};
+use labt_core::submodules::tree::{Tree, TreeArgs};
+use labt_core::submodules::update::{Update, UpdateArgs};
+use labt_core::submodules::verify::{Verify, VerifyArgs};
+use labt_core::submodules::versions::{Versions, VersionsArgs};
+use labt_core::submodules::why::{Why, WhyArgs};
+use labt_core::submodules::Submodule;
+use labt_core::LABT_VERSION;
 use log::error;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[clap(version = LABT_VERSION)]
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+    /// How progress is reported. json writes one event per line to stdout
+    #[arg(long, global = true, value_enum, default_value_t = MessageFormat::Human)]
+    message_format: MessageFormat,
+    /// How command results are printed. json prints a single document to stdout and keeps logs on stderr
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+    /// Resolve from the labt cache and Labt.lock only
+    #[arg(long, global = true)]
+    offline: bool,
+    /// The project manifest to use instead of the closest Labt.toml
+    #[arg(long, global = true, value_name = "PATH")]
+    manifest_path: Option<PathBuf>,
+    /// Save the http responses of resolvers, downloads and the sdk manager into DIR
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        conflicts_with = "replay_http"
+    )]
+    record_http: Option<PathBuf>,
+    /// Answer http requests with the responses recorded in DIR without using the network
+    #[arg(long, global = true, value_name = "DIR")]
+    replay_http: Option<PathBuf>,
 }
 
 const LOGO: &str = r#"
@@ -41,40 +96,270 @@ enum Commands {
     Plugin(PluginArgs),
     /// Sdk manager
     Sdk(SdkArgs),
+    /// Inspect built apk files
+    Apk(ApkArgs),
+    /// Dependency license tools
+    Licenses(LicensesArgs),
+    /// Formats Labt.toml
+    Fmt(FmtArgs),
+    /// Generates IDE project files from the resolved dependencies
+    Ide(IdeArgs),
+    /// Prints the project classpath for external tools
+    ExportClasspath(ExportClasspathArgs),
+    /// Checks cached dependencies against their checksums
+    Verify(VerifyArgs),
+    /// Lists the published versions of an artifact with their release dates
+    Versions(VersionsArgs),
+    /// Downloads the repository indexes used to look up dependencies offline
+    Index(IndexArgs),
+    /// Prints the resolved dependency tree from Labt.lock
+    Tree(TreeArgs),
+    /// Labt.lock tools
+    Lock(LockArgs),
+    /// Manages the labt artifact cache
+    Cache(CacheArgs),
+    /// Generates a CycloneDX or SPDX software bill of materials from Labt.lock
+    Sbom(SbomArgs),
+    /// Compares the dependency versions in Labt.toml with the newest published versions
+    Outdated(OutdatedArgs),
+    /// Rewrites deprecated dependencies in Labt.toml to their replacements
+    Migrate(MigrateArgs),
+    /// Bumps dependency versions in Labt.toml to the newest releases and resolves them
+    Update(UpdateArgs),
+    /// Shows the pom summary, files and manifest of a cached artifact
+    Inspect(InspectArgs),
+    /// Downloads the dependencies, plugins, sdk packages and JDK a build needs so it can run offline
+    Prefetch(PrefetchArgs),
+    /// Runs a labt subcommand in the project and its path dependencies in dependency order
+    Foreach(ForeachArgs),
+    /// Shows the dependency paths that pull an artifact into Labt.lock
+    Why(WhyArgs),
+    /// Copies the dependencies, plugins, sdk packages and JDK of the project into a directory that builds offline as LABT_HOME
+    BundleOffline(BundleOfflineArgs),
+    /// Searches Maven Central for artifacts and prints their newest versions
+    Search(SearchArgs),
+    /// Changes the application package in Labt.toml, the manifest, the source directories and imports
+    Rename(RenameArgs),
+    /// Deletes the build directory and the outputs declared by plugins
+    Clean(CleanArgs),
+    /// Checks labt home, the resolvers, the sdk packages, the plugins and Labt.toml and suggests fixes
+    Doctor(DoctorArgs),
+    /// Analyzes how the project uses its dependencies
+    Deps(DepsArgs),
 }
 
-pub fn parse_args() {
+/// Runs the command line. Returns false if the command failed
+pub fn parse_args() -> bool {
     let args = Cli::parse();
+    set_message_format(args.message_format);
+    set_output_format(args.output);
+    // the config.toml of an offline bundle turns on offline mode
+    let offline_home = get_global_config().is_ok_and(|config| config.offline.unwrap_or(false));
+    labt_core::set_offline(args.offline || offline_home);
+    if let Some(dir) = &args.record_http {
+        set_http_recording(Some(HttpRecording::Record(dir.clone())));
+    } else if let Some(dir) = &args.replay_http {
+        if !dir.is_dir() {
+            error!(target: "labt", "The http recording {:?} does not exist", dir);
+            return false;
+        }
+        set_http_recording(Some(HttpRecording::Replay(dir.clone())));
+    }
+    if let Some(manifest) = &args.manifest_path {
+        if !manifest.is_file() {
+            error!(target: "labt", "The manifest {:?} does not exist", manifest);
+            return false;
+        }
+        labt_core::set_manifest_path(Some(manifest.clone()));
+    }
 
+    let recording = record_metrics();
+    let mut success = true;
     match &args.command {
         Some(Commands::Add(args)) => {
             if let Err(e) = Add::new(args).run() {
                 error!(target: "add","{:?}", e);
+                success = false;
             }
         }
         Some(Commands::Init(args)) => {
             if let Err(e) = Init::new(args).run() {
                 error!(target: "init","{:?}", e);
+                success = false;
             }
         }
         Some(Commands::Resolve(args)) => {
             if let Err(e) = Resolve::new(args).run() {
                 error!(target: "resolve","{:?}", e);
+                success = false;
             }
         }
         Some(Commands::Build(args)) => {
             if let Err(e) = Build::new(args).run() {
                 error!(target: "build", "{:?}", e);
+                success = false;
             }
         }
         Some(Commands::Plugin(args)) => {
             if let Err(e) = Plugin::new(args).run() {
                 error!(target: "plugin", "{:?}", e);
+                success = false;
             }
         }
         Some(Commands::Sdk(args)) => {
             if let Err(e) = Sdk::new(args).run() {
                 error!(target: "sdk", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Apk(args)) => {
+            if let Err(e) = Apk::new(args).run() {
+                error!(target: "apk", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Licenses(args)) => {
+            if let Err(e) = Licenses::new(args).run() {
+                error!(target: "licenses", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Fmt(args)) => {
+            if let Err(e) = Fmt::new(args).run() {
+                error!(target: "fmt", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Ide(args)) => {
+            if let Err(e) = Ide::new(args).run() {
+                error!(target: "ide", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::ExportClasspath(args)) => {
+            if let Err(e) = ExportClasspath::new(args).run() {
+                error!(target: "classpath", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Verify(args)) => {
+            if let Err(e) = Verify::new(args).run() {
+                error!(target: "verify", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Versions(args)) => {
+            if let Err(e) = Versions::new(args).run() {
+                error!(target: "versions", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Index(args)) => {
+            if let Err(e) = Index::new(args).run() {
+                error!(target: "index", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Tree(args)) => {
+            if let Err(e) = Tree::new(args).run() {
+                error!(target: "tree", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Lock(args)) => {
+            if let Err(e) = Lock::new(args).run() {
+                error!(target: "lock", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Cache(args)) => {
+            if let Err(e) = Cache::new(args).run() {
+                error!(target: "cache", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Sbom(args)) => {
+            if let Err(e) = Sbom::new(args).run() {
+                error!(target: "sbom", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Outdated(args)) => {
+            if let Err(e) = Outdated::new(args).run() {
+                error!(target: "outdated", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Migrate(args)) => {
+            if let Err(e) = Migrate::new(args).run() {
+                error!(target: "migrate", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Update(args)) => {
+            if let Err(e) = Update::new(args).run() {
+                error!(target: "update", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Inspect(args)) => {
+            if let Err(e) = Inspect::new(args).run() {
+                error!(target: "inspect", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Prefetch(args)) => {
+            if let Err(e) = Prefetch::new(args).run() {
+                error!(target: "prefetch", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Foreach(args)) => {
+            if let Err(e) = Foreach::new(args).run() {
+                error!(target: "foreach", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Why(args)) => {
+            if let Err(e) = Why::new(args).run() {
+                error!(target: "why", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::BundleOffline(args)) => {
+            if let Err(e) = BundleOffline::new(args).run() {
+                error!(target: "bundle", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Search(args)) => {
+            if let Err(e) = Search::new(args).run() {
+                error!(target: "search", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Rename(args)) => {
+            if let Err(e) = Rename::new(args).run() {
+                error!(target: "rename", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Clean(args)) => {
+            if let Err(e) = Clean::new(args).run() {
+                error!(target: "clean", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Doctor(args)) => {
+            if let Err(e) = Doctor::new(args).run() {
+                error!(target: "doctor", "{:?}", e);
+                success = false;
+            }
+        }
+        Some(Commands::Deps(args)) => {
+            if let Err(e) = Deps::new(args).run() {
+                error!(target: "deps", "{:?}", e);
+                success = false;
             }
         }
         None => {
@@ -85,4 +370,7 @@ pub fn parse_args() {
             c.print_help().unwrap();
         }
     }
+    drop(recording);
+    warn_over_budget(&take_metrics());
+    success
 }
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+/// The official support library to androidx artifact mapping
+/// (androidx-artifact-mapping.csv)
+const ARTIFACT_MAPPING: &str = include_str!("../../assets/androidx/artifact-mapping.csv");
+/// The commonly used classes of the official androidx-class-mapping.csv
+const CLASS_MAPPING: &str = include_str!("../../assets/androidx/class-mapping.csv");
+
+lazy_static! {
+    /// Fully qualified support library names with an optional wildcard import.
+    /// The name must not be part of a longer qualified name e.g. com.foo.android.support
+    static ref SUPPORT_NAME: Regex =
+        Regex::new(r"(^|[^\w.])(android\.(?:support|arch)(?:\.\w+)+)(\.\*)?").unwrap();
+}
+
+/// The (old, new) rows of a mapping csv. The header and malformed rows are skipped
+fn rows(csv: &str) -> impl Iterator<Item = (&str, &str)> {
+    csv.lines().filter_map(|line| {
+        let (old, new) = line.split_once(',')?;
+        let (old, new) = (old.trim(), new.trim());
+        let valid = !old.is_empty() && !new.is_empty() && !old.contains(' ');
+        valid.then_some((old, new))
+    })
+}
+
+/// The support library artifacts as (group_id:artifact_id, group_id:artifact_id:version)
+pub fn artifact_mapping() -> impl Iterator<Item = (&'static str, &'static str)> {
+    rows(ARTIFACT_MAPPING)
+}
+
+/// The package of a qualified class name. Packages are lowercase so the name
+/// ends at the first capitalized segment
+fn package_of(class: &str) -> &str {
+    let mut end = 0;
+    for segment in class.split('.') {
+        if segment.starts_with(|c: char| c.is_ascii_uppercase()) {
+            break;
+        }
+        end += segment.len() + 1;
+    }
+    &class[..end.saturating_sub(1).min(class.len())]
+}
+
+/// The result of rewriting the support library names of a source file
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SourceRewrite {
+    pub text: String,
+    /// Number of names that were replaced
+    pub replaced: usize,
+    /// Support library names that have no androidx mapping
+    pub unmapped: Vec<String>,
+}
+
+/// Support library classes and packages mapped to their androidx names
+#[derive(Debug, Default, Clone)]
+pub struct ClassMapping {
+    classes: HashMap<String, String>,
+    /// None if the classes of the package moved to different packages, in which
+    /// case wildcard imports of it cannot be rewritten
+    packages: HashMap<String, Option<String>>,
+}
+
+impl ClassMapping {
+    /// The classes bundled with labt
+    pub fn builtin() -> Self {
+        Self::parse(CLASS_MAPPING)
+    }
+    /// Parses a class mapping in the format of the official androidx-class-mapping.csv
+    pub fn parse(csv: &str) -> Self {
+        let mut mapping = ClassMapping::default();
+        for (old, new) in rows(csv) {
+            let new_package = package_of(new).to_string();
+            mapping
+                .packages
+                .entry(package_of(old).to_string())
+                .and_modify(|package| {
+                    if package.as_ref() != Some(&new_package) {
+                        *package = None;
+                    }
+                })
+                .or_insert(Some(new_package));
+            mapping.classes.insert(old.to_string(), new.to_string());
+        }
+        mapping
+    }
+    /// The androidx name of a qualified support library name. Nested classes and
+    /// static members keep their suffix
+    fn map_name(&self, name: &str, wildcard: bool) -> Option<String> {
+        if wildcard {
+            if let Some(class) = self.classes.get(name) {
+                return Some(format!("{class}.*"));
+            }
+            return self
+                .packages
+                .get(name)
+                .cloned()
+                .flatten()
+                .map(|package| format!("{package}.*"));
+        }
+        let mut end = name.len();
+        loop {
+            if let Some(class) = self.classes.get(&name[..end]) {
+                return Some(format!("{class}{}", &name[end..]));
+            }
+            end = name[..end].rfind('.')?;
+        }
+    }
+    /// Replaces the support library class names and wildcard imports in a java,
+    /// kotlin or xml source
+    pub fn rewrite(&self, source: &str) -> SourceRewrite {
+        let mut replaced = 0;
+        let mut unmapped = Vec::new();
+        let text = SUPPORT_NAME.replace_all(source, |caps: &Captures| {
+            let name = &caps[2];
+            let wildcard = caps.get(3).is_some();
+            match self.map_name(name, wildcard) {
+                Some(new) => {
+                    replaced += 1;
+                    format!("{}{}", &caps[1], new)
+                }
+                None => {
+                    unmapped.push(format!("{name}{}", if wildcard { ".*" } else { "" }));
+                    caps[0].to_string()
+                }
+            }
+        });
+        unmapped.sort();
+        unmapped.dedup();
+        SourceRewrite {
+            text: text.into_owned(),
+            replaced,
+            unmapped,
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn androidx_class_mapping_rewrites_sources() {
+    assert!(
+        artifact_mapping().any(|(old, new)| old == "com.android.support:appcompat-v7"
+            && new == "androidx.appcompat:appcompat:1.0.0")
+    );
+
+    let mapping = ClassMapping::builtin();
+    let source = r#"package com.example.app;
+
+import android.support.annotation.*;
+import android.support.v7.app.AppCompatActivity;
+import android.support.v7.widget.*;
+import android.support.v4.media.session.MediaSessionCompat;
+import static android.support.v4.content.ContextCompat.getColor;
+import com.example.android.support.Helper;
+
+public class MainActivity extends AppCompatActivity {
+    private android.support.v7.widget.RecyclerView.ViewHolder holder;
+}
+"#;
+    let rewrite = mapping.rewrite(source);
+    assert_eq!(
+        rewrite.text,
+        r#"package com.example.app;
+
+import androidx.annotation.*;
+import androidx.appcompat.app.AppCompatActivity;
+import android.support.v7.widget.*;
+import android.support.v4.media.session.MediaSessionCompat;
+import static androidx.core.content.ContextCompat.getColor;
+import com.example.android.support.Helper;
+
+public class MainActivity extends AppCompatActivity {
+    private androidx.recyclerview.widget.RecyclerView.ViewHolder holder;
+}
+"#
+    );
+    assert_eq!(rewrite.replaced, 4);
+    assert_eq!(
+        rewrite.unmapped,
+        vec![
+            "android.support.v4.media.session.MediaSessionCompat",
+            "android.support.v7.widget.*"
+        ]
+    );
+
+    let layout = r#"<android.support.constraint.ConstraintLayout xmlns:android="http://schemas.android.com/apk/res/android">
+    <android.support.v7.widget.RecyclerView android:id="@+id/list" />
+</android.support.constraint.ConstraintLayout>"#;
+    assert_eq!(
+        mapping.rewrite(layout).text,
+        r#"<androidx.constraintlayout.widget.ConstraintLayout xmlns:android="http://schemas.android.com/apk/res/android">
+    <androidx.recyclerview.widget.RecyclerView android:id="@+id/list" />
+</androidx.constraintlayout.widget.ConstraintLayout>"#
+    );
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use log::warn;
+
+use super::get_config;
+use crate::metrics::{PhaseMetric, BUILD, DOWNLOADS, METADATA, RESOLVE};
+
+/// The phases a budget can be set for. The build steps are timed under their names
+const BUDGET_PHASES: [&str; 11] = [
+    RESOLVE,
+    METADATA,
+    DOWNLOADS,
+    BUILD,
+    "pre",
+    "aapt",
+    "compile",
+    "dex",
+    "bundle",
+    "instrument",
+    "post",
+];
+
+/// The number of seconds each phase of a command is expected to take. A warning
+/// with a hint is logged after a command that spent longer in a phase.
+/// ```toml
+/// [budgets]
+/// metadata = 30
+/// build = 120
+/// dex = 20
+/// ```
+pub type BudgetsTable = BTreeMap<String, u64>;
+
+/// Checks that every budget is set for a known phase so that typos are not silently ignored
+///
+/// # Errors
+///
+/// Returns an error naming the unknown phases
+pub fn validate_budgets(budgets: &BudgetsTable) -> anyhow::Result<()> {
+    let unknown: Vec<&str> = budgets
+        .keys()
+        .filter(|key| !BUDGET_PHASES.contains(&key.as_str()))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        bail!(
+            "Unknown phases in [budgets]: {}. Budgets can be set for {}",
+            unknown.join(", "),
+            BUDGET_PHASES.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// What took the time and how to make it faster
+fn describe(phase: &str, metric: &PhaseMetric) -> (String, &'static str) {
+    match phase {
+        RESOLVE => (
+            "Resolving dependencies".to_string(),
+            "Commit Labt.lock, it is reused without resolving again while Labt.toml declares the same dependencies",
+        ),
+        METADATA => (
+            format!("Fetching {} poms and maven-metadata.xml files", metric.count),
+            "Raise labt resolve --jobs to fetch more at once, or add a resolver or mirror closer to you",
+        ),
+        DOWNLOADS => (
+            format!("Downloading {} artifacts", metric.count),
+            "Raise downloads under [network] in config.toml, or build from a labt bundle-offline home",
+        ),
+        BUILD => (
+            "The build".to_string(),
+            "build/build-summary.json lists the duration of every step and plugin",
+        ),
+        step => (
+            format!("The {step} step"),
+            "Declare the inputs and outputs of its plugin stages so they are skipped while up to date, with change_detection = \"hash\" if their inputs are touched without changing",
+        ),
+    }
+}
+
+/// The warnings for the phases that took longer than their budget
+pub fn budget_warnings(
+    metrics: &BTreeMap<String, PhaseMetric>,
+    budgets: &BudgetsTable,
+) -> Vec<String> {
+    budgets
+        .iter()
+        .filter_map(|(phase, budget)| {
+            let metric = metrics.get(phase)?;
+            if metric.elapsed_ms <= budget * 1000 {
+                return None;
+            }
+            let (what, hint) = describe(phase, metric);
+            Some(format!(
+                "{what} took {:.1}s, over its budget of {budget}s. {hint}",
+                metric.elapsed_ms as f64 / 1000.0
+            ))
+        })
+        .collect()
+}
+
+/// Warns about the phases of the command that just ran that took longer than the
+/// budgets in Labt.toml. Commands run outside a project have no budgets
+pub fn warn_over_budget(metrics: &BTreeMap<String, PhaseMetric>) {
+    let Some(budgets) = get_config().ok().and_then(|config| config.budgets) else {
+        return;
+    };
+    if let Err(err) = validate_budgets(&budgets) {
+        warn!(target: "budget", "{}", err);
+    }
+    for warning in budget_warnings(metrics, &budgets) {
+        warn!(target: "budget", "{}", warning);
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn budgets_warn_about_slow_phases() {
+    let budgets: BudgetsTable = toml::from_str("metadata = 30\nbuild = 120\ndex = 20\n").unwrap();
+    assert!(validate_budgets(&budgets).is_ok());
+    let metrics = BTreeMap::from([
+        (
+            METADATA.to_string(),
+            PhaseMetric {
+                count: 312,
+                elapsed_ms: 40_240,
+            },
+        ),
+        (
+            BUILD.to_string(),
+            PhaseMetric {
+                count: 1,
+                elapsed_ms: 120_000,
+            },
+        ),
+        (
+            "dex".to_string(),
+            PhaseMetric {
+                count: 1,
+                elapsed_ms: 25_000,
+            },
+        ),
+        (
+            DOWNLOADS.to_string(),
+            PhaseMetric {
+                count: 4,
+                elapsed_ms: 90_000,
+            },
+        ),
+    ]);
+    let warnings = budget_warnings(&metrics, &budgets);
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].starts_with("The dex step took 25.0s, over its budget of 20s."));
+    assert!(warnings[1].starts_with(
+        "Fetching 312 poms and maven-metadata.xml files took 40.2s, over its budget of 30s."
+    ));
+
+    let typo: BudgetsTable = toml::from_str("metdata = 30\nresolve = 10\n").unwrap();
+    let err = validate_budgets(&typo).unwrap_err().to_string();
+    assert!(err.contains("budgets]: metdata."), "{err}");
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    profile::{profile_build_config_fields, ProfileName},
+    LabToml,
+};
+
+/// Default directory relative to the project root where BuildConfig is generated
+pub const BUILD_CONFIG_OUTPUT: &str = "build/generated/source/buildConfig";
+
+/// The language of the generated BuildConfig class
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildConfigLanguage {
+    #[default]
+    Java,
+    Kotlin,
+}
+
+/// Generates a BuildConfig class with the application id, version and
+/// debug flag of the project before the build runs.
+/// ```toml
+/// [build_config]
+/// language = "kotlin"
+///
+/// [build_config.fields]
+/// API_URL = "https://api.example.com"
+/// MAX_RETRIES = 3
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct BuildConfigTable {
+    /// The package of the generated class. Defaults to the project package
+    pub package: Option<String>,
+    #[serde(default)]
+    pub language: BuildConfigLanguage,
+    /// Generated sources directory relative to the project root
+    pub output: Option<String>,
+    /// Extra constants added to the class. Strings, integers, floats and booleans are supported
+    #[serde(default)]
+    pub fields: BTreeMap<String, toml::Value>,
+}
+
+/// The values that make up a BuildConfig class
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildConfigValues {
+    pub package: String,
+    pub application_id: String,
+    pub version_code: i64,
+    pub version_name: String,
+    pub debug: bool,
+    pub fields: BTreeMap<String, toml::Value>,
+}
+
+impl BuildConfigValues {
+    /// The values for a debug or release build. The `build_config` of the
+    /// matching profile overrides the fields of the table
+    pub fn from_config(
+        config: &LabToml,
+        table: &BuildConfigTable,
+        version_code: i64,
+        debug: bool,
+    ) -> Self {
+        BuildConfigValues {
+            package: table
+                .package
+                .clone()
+                .unwrap_or(config.project.package.clone()),
+            application_id: config.project.package.clone(),
+            version_code,
+            version_name: config.project.version.clone(),
+            debug,
+            fields: profile_build_config_fields(
+                &table.fields,
+                config
+                    .profile
+                    .as_ref()
+                    .and_then(|profiles| profiles.get(ProfileName::from_release(!debug))),
+            ),
+        }
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn quote(value: &str, language: BuildConfigLanguage) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // kotlin would treat it as a string template
+            '$' if language == BuildConfigLanguage::Kotlin => quoted.push_str("\\$"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the type and literal of a field for the given language
+fn field_literal(
+    language: BuildConfigLanguage,
+    name: &str,
+    value: &toml::Value,
+) -> anyhow::Result<(&'static str, String)> {
+    use BuildConfigLanguage::*;
+    let literal = match (language, value) {
+        (_, toml::Value::String(s)) => ("String", quote(s, language)),
+        (Java, toml::Value::Integer(i)) if i32::try_from(*i).is_ok() => ("int", i.to_string()),
+        (Java, toml::Value::Integer(i)) => ("long", format!("{i}L")),
+        (Kotlin, toml::Value::Integer(i)) if i32::try_from(*i).is_ok() => ("Int", i.to_string()),
+        (Kotlin, toml::Value::Integer(i)) => ("Long", format!("{i}L")),
+        (Java, toml::Value::Float(f)) => ("double", format!("{f:?}")),
+        (Kotlin, toml::Value::Float(f)) => ("Double", format!("{f:?}")),
+        (Java, toml::Value::Boolean(b)) => ("boolean", b.to_string()),
+        (Kotlin, toml::Value::Boolean(b)) => ("Boolean", b.to_string()),
+        _ => bail!(
+            "Unsupported value for build_config field {name}. Only strings, integers, floats and booleans are allowed"
+        ),
+    };
+    Ok(literal)
+}
+
+/// Renders the BuildConfig class source
+///
+/// # Errors
+///
+/// Returns an error if a field name is not a valid identifier or its value type is not supported
+pub fn render_build_config(
+    values: &BuildConfigValues,
+    language: BuildConfigLanguage,
+) -> anyhow::Result<String> {
+    let mut fields = vec![
+        (String::from("DEBUG"), toml::Value::Boolean(values.debug)),
+        (
+            String::from("APPLICATION_ID"),
+            toml::Value::String(values.application_id.clone()),
+        ),
+        (
+            String::from("VERSION_CODE"),
+            toml::Value::Integer(values.version_code),
+        ),
+        (
+            String::from("VERSION_NAME"),
+            toml::Value::String(values.version_name.clone()),
+        ),
+    ];
+    fields.extend(values.fields.clone());
+
+    let mut source = String::from("// Automatically generated by labt. DO NOT MODIFY\n");
+    match language {
+        BuildConfigLanguage::Java => {
+            source.push_str(&format!(
+                "package {};\n\npublic final class BuildConfig {{\n",
+                values.package
+            ));
+        }
+        BuildConfigLanguage::Kotlin => {
+            source.push_str(&format!(
+                "package {}\n\nobject BuildConfig {{\n",
+                values.package
+            ));
+        }
+    }
+    for (name, value) in &fields {
+        if !is_identifier(name) {
+            bail!("build_config field {name} is not a valid identifier");
+        }
+        let (kind, literal) = field_literal(language, name, value)?;
+        match language {
+            BuildConfigLanguage::Java => source.push_str(&format!(
+                "    public static final {kind} {name} = {literal};\n"
+            )),
+            BuildConfigLanguage::Kotlin => {
+                source.push_str(&format!("    const val {name}: {kind} = {literal}\n"))
+            }
+        }
+    }
+    source.push_str("}\n");
+    Ok(source)
+}
+
+/// Writes the BuildConfig class into the generated sources directory under its
+/// package directory. The file is only rewritten if its contents changed so that
+/// plugins do not see it as stale. Returns the path of the class file.
+///
+/// # Errors
+///
+/// Returns an error if rendering fails or the file cannot be written
+pub fn generate_build_config(
+    root: &Path,
+    table: &BuildConfigTable,
+    values: &BuildConfigValues,
+) -> anyhow::Result<PathBuf> {
+    let source = render_build_config(values, table.language)?;
+    let mut path = root.join(table.output.as_deref().unwrap_or(BUILD_CONFIG_OUTPUT));
+    path.extend(values.package.split('.'));
+    create_dir_all(&path).context(format!("Failed to create {:?}", path))?;
+    path.push(match table.language {
+        BuildConfigLanguage::Java => "BuildConfig.java",
+        BuildConfigLanguage::Kotlin => "BuildConfig.kt",
+    });
+
+    if read_to_string(&path).is_ok_and(|existing| existing == source) {
+        return Ok(path);
+    }
+    write(&path, source).context(format!("Failed to write {:?}", path))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn build_config_java_and_kotlin() {
+    let mut fields = BTreeMap::new();
+    fields.insert(
+        String::from("API_URL"),
+        toml::Value::String(String::from("https://example.com/$v")),
+    );
+    fields.insert(String::from("RETRIES"), toml::Value::Integer(3));
+    let values = BuildConfigValues {
+        package: String::from("com.example.app"),
+        application_id: String::from("com.example.app"),
+        version_code: 12,
+        version_name: String::from("1.2"),
+        debug: true,
+        fields,
+    };
+
+    assert_eq!(
+        render_build_config(&values, BuildConfigLanguage::Java).unwrap(),
+        r#"// Automatically generated by labt. DO NOT MODIFY
+package com.example.app;
+
+public final class BuildConfig {
+    public static final boolean DEBUG = true;
+    public static final String APPLICATION_ID = "com.example.app";
+    public static final int VERSION_CODE = 12;
+    public static final String VERSION_NAME = "1.2";
+    public static final String API_URL = "https://example.com/$v";
+    public static final int RETRIES = 3;
+}
+"#
+    );
+    assert_eq!(
+        render_build_config(&values, BuildConfigLanguage::Kotlin).unwrap(),
+        r#"// Automatically generated by labt. DO NOT MODIFY
+package com.example.app
+
+object BuildConfig {
+    const val DEBUG: Boolean = true
+    const val APPLICATION_ID: String = "com.example.app"
+    const val VERSION_CODE: Int = 12
+    const val VERSION_NAME: String = "1.2"
+    const val API_URL: String = "https://example.com/\$v"
+    const val RETRIES: Int = 3
+}
+"#
+    );
+
+    let mut invalid = values.clone();
+    invalid
+        .fields
+        .insert(String::from("not valid"), toml::Value::Boolean(true));
+    assert!(render_build_config(&invalid, BuildConfigLanguage::Java).is_err());
+}
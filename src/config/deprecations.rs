@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::submodules::resolve::ProjectDep;
+
+use super::androidx::artifact_mapping;
+
+/// Relocated and deprecated artifacts known to labt as
+/// (group_id:artifact_id, replacement group_id:artifact_id:version, note).
+/// The support libraries come from the official androidx artifact mapping.
+const BUILTIN_DEPRECATIONS: &[(&str, &str, &str)] = &[
+    (
+        "org.jetbrains.kotlin:kotlin-stdlib-jre7",
+        "org.jetbrains.kotlin:kotlin-stdlib-jdk7:",
+        "renamed in Kotlin 1.2",
+    ),
+    (
+        "org.jetbrains.kotlin:kotlin-stdlib-jre8",
+        "org.jetbrains.kotlin:kotlin-stdlib-jdk8:",
+        "renamed in Kotlin 1.2",
+    ),
+    (
+        "com.google.android.gms:play-services",
+        "",
+        "depend on the individual play-services-* artifacts instead",
+    ),
+];
+
+/// Extra deprecated artifacts declared by the project. Entries override the
+/// deprecations built into labt.
+/// ```toml
+/// [deprecations]
+/// "com.mycorp:old-http" = { replacement = "com.mycorp:http:2.0.0", message = "old-http is unmaintained" }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct DeprecationEntry {
+    /// The artifact to use instead as group_id:artifact_id or group_id:artifact_id:version
+    pub replacement: Option<String>,
+    /// Why the artifact is deprecated
+    pub message: Option<String>,
+}
+
+/// The artifact that replaces a deprecated one
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    pub group_id: String,
+    pub artifact_id: String,
+    /// The version to migrate to. None if any version of the replacement works
+    pub version: Option<String>,
+}
+
+impl Replacement {
+    /// Parses group_id:artifact_id with an optional :version
+    pub fn parse(coordinate: &str) -> Option<Self> {
+        let mut parts = coordinate.splitn(3, ':');
+        let group_id = parts.next().filter(|g| !g.is_empty())?;
+        let artifact_id = parts.next().filter(|a| !a.is_empty())?;
+        Some(Replacement {
+            group_id: group_id.to_string(),
+            artifact_id: artifact_id.to_string(),
+            version: parts.next().filter(|v| !v.is_empty()).map(String::from),
+        })
+    }
+}
+
+impl Display for Replacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.group_id, self.artifact_id)?;
+        if let Some(version) = &self.version {
+            write!(f, ":{version}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A deprecated artifact and what to use instead
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    pub replacement: Option<Replacement>,
+    pub message: Option<String>,
+}
+
+impl Deprecation {
+    /// True if the replacement is the androidx version of a support library
+    pub fn is_androidx_migration(&self) -> bool {
+        self.replacement.as_ref().is_some_and(|replacement| {
+            replacement.group_id.starts_with("androidx.")
+                || replacement.group_id == "com.google.android.material"
+        })
+    }
+}
+
+/// The deprecated artifacts keyed by group_id:artifact_id
+#[derive(Debug, Default, Clone)]
+pub struct Deprecations(HashMap<String, Deprecation>);
+
+impl Deprecations {
+    /// The built in deprecations extended with the ones from Labt.toml
+    pub fn new(configured: Option<&HashMap<String, DeprecationEntry>>) -> Self {
+        let mut deprecations: HashMap<String, Deprecation> = artifact_mapping()
+            .map(|(artifact, replacement)| (artifact, replacement, ""))
+            .chain(BUILTIN_DEPRECATIONS.iter().copied())
+            .map(|(artifact, replacement, message)| {
+                let deprecation = Deprecation {
+                    replacement: Replacement::parse(replacement),
+                    message: (!message.is_empty()).then(|| message.to_string()),
+                };
+                (artifact.to_string(), deprecation)
+            })
+            .collect();
+        for (artifact, entry) in configured.into_iter().flatten() {
+            let deprecation = Deprecation {
+                replacement: entry.replacement.as_deref().and_then(Replacement::parse),
+                message: entry.message.clone(),
+            };
+            deprecations.insert(artifact.clone(), deprecation);
+        }
+        Deprecations(deprecations)
+    }
+    pub fn find(&self, group_id: &str, artifact_id: &str) -> Option<&Deprecation> {
+        self.0.get(&format!("{group_id}:{artifact_id}"))
+    }
+    /// A warning with the suggested replacement for each deprecated dependency
+    pub fn hints(&self, deps: &[ProjectDep]) -> Vec<String> {
+        let mut hints: Vec<String> = deps
+            .iter()
+            .filter_map(|dep| {
+                let deprecation = self.find(&dep.group_id, &dep.artifact_id)?;
+                let mut hint = format!(
+                    "{}:{}:{} is deprecated",
+                    dep.group_id, dep.artifact_id, dep.version
+                );
+                if let Some(message) = &deprecation.message {
+                    hint.push_str(&format!(", {message}"));
+                }
+                if let Some(replacement) = &deprecation.replacement {
+                    hint.push_str(&format!(". Use {replacement} instead"));
+                }
+                if deprecation.is_androidx_migration() {
+                    hint.push_str(" or run labt migrate androidx");
+                }
+                Some(hint)
+            })
+            .collect();
+        hints.sort();
+        hints.dedup();
+        hints
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn deprecation_hints() {
+    let configured = HashMap::from([
+        (
+            String::from("com.mycorp:old-http"),
+            DeprecationEntry {
+                replacement: Some(String::from("com.mycorp:http")),
+                message: Some(String::from("old-http is unmaintained")),
+            },
+        ),
+        (
+            String::from("com.android.support:design"),
+            DeprecationEntry {
+                replacement: Some(String::from("com.google.android.material:material:1.11.0")),
+                message: None,
+            },
+        ),
+    ]);
+    let deprecations = Deprecations::new(Some(&configured));
+    let dep = |coordinate: &str| {
+        let mut parts = coordinate.split(':');
+        ProjectDep {
+            group_id: parts.next().unwrap().to_string(),
+            artifact_id: parts.next().unwrap().to_string(),
+            version: parts.next().unwrap().to_string(),
+            ..Default::default()
+        }
+    };
+    let deps = vec![
+        dep("com.android.support:appcompat-v7:28.0.0"),
+        dep("com.android.support:design:28.0.0"),
+        dep("com.mycorp:old-http:1.2"),
+        dep("androidx.core:core:1.12.0"),
+    ];
+    assert_eq!(
+        deprecations.hints(&deps),
+        vec![
+            "com.android.support:appcompat-v7:28.0.0 is deprecated. Use androidx.appcompat:appcompat:1.0.0 instead or run labt migrate androidx",
+            "com.android.support:design:28.0.0 is deprecated. Use com.google.android.material:material:1.11.0 instead or run labt migrate androidx",
+            "com.mycorp:old-http:1.2 is deprecated, old-http is unmaintained. Use com.mycorp:http instead",
+        ]
+    );
+    assert_eq!(
+        Replacement::parse("org.jetbrains.kotlin:kotlin-stdlib-jdk8:"),
+        Some(Replacement {
+            group_id: String::from("org.jetbrains.kotlin"),
+            artifact_id: String::from("kotlin-stdlib-jdk8"),
+            version: None,
+        })
+    );
+}
@@ -0,0 +1,244 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::get_project_root;
+
+use super::{Dependency, LabToml};
+
+/// Local overrides of the project dependencies. The file is not meant to be
+/// committed
+pub const DEV_OVERRIDES_FILE: &str = "Labt.dev.toml";
+/// The lock file written instead of Labt.lock while overrides are active
+pub const DEV_LOCK_FILE: &str = "Labt.dev.lock";
+
+/// Labt.dev.toml in the project root. It maps coordinates to a local path or
+/// another version for testing patched libraries without touching Labt.toml or
+/// Labt.lock
+/// ```toml
+/// [overrides]
+/// "androidx.core:core" = { path = "../core-patched", artifact = "build/core.aar" }
+/// "com.squareup.okhttp3:okhttp" = { version = "5.0.0-alpha.14" }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DevOverrides {
+    /// The overrides keyed by group_id:artifact_id
+    pub overrides: Option<BTreeMap<String, DevOverride>>,
+}
+
+/// What a dependency is replaced with
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DevOverride {
+    /// The version used instead of the declared or transitive one
+    pub version: Option<String>,
+    /// A local LABt library or a directory with a prebuilt artifact, relative
+    /// to the project root. It is built and used like a path dependency
+    pub path: Option<String>,
+    /// The AAR/JAR of path relative to it. Defaults to build/<artifact_id>.aar
+    pub artifact: Option<String>,
+}
+
+impl DevOverride {
+    /// A short description for the resolve output
+    pub fn describe(&self) -> String {
+        match (&self.path, &self.version) {
+            (Some(path), Some(version)) => format!("{path} as version {version}"),
+            (Some(path), None) => path.clone(),
+            (None, Some(version)) => format!("version {version}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Reads the overrides in the Labt.dev.toml of the current project. None if
+/// the file does not exist or overrides nothing
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed
+pub fn load_dev_overrides() -> anyhow::Result<Option<BTreeMap<String, DevOverride>>> {
+    let root = get_project_root().context("Failed to get project root directory")?;
+    load_dev_overrides_in(&root)
+}
+
+/// Reads the overrides in the Labt.dev.toml of the project at root
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or parsed
+pub fn load_dev_overrides_in(root: &Path) -> anyhow::Result<Option<BTreeMap<String, DevOverride>>> {
+    let path = root.join(DEV_OVERRIDES_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?;
+    let dev: DevOverrides =
+        toml::from_str(&contents).context(format!("Failed to parse {:?}", path))?;
+    Ok(dev.overrides.filter(|overrides| !overrides.is_empty()))
+}
+
+/// Returns true if the Labt.dev.toml of the project at root overrides any
+/// dependency. A file that fails to parse counts as active so that Labt.lock is
+/// never written from it
+pub fn dev_overrides_active(root: &Path) -> bool {
+    match load_dev_overrides_in(root) {
+        Ok(overrides) => overrides.is_some(),
+        Err(_) => root.join(DEV_OVERRIDES_FILE).exists(),
+    }
+}
+
+/// Applies the overrides to the [dependencies] of config. A declared dependency
+/// takes the overridden version or path. Coordinates that are not declared are
+/// added to [dependencies] since direct dependencies take precedence over the
+/// versions requested transitively.
+///
+/// # Errors
+///
+/// Returns an error if a key is not group_id:artifact_id or an override sets
+/// neither a version nor a path
+pub fn apply_dev_overrides(
+    config: &mut LabToml,
+    overrides: &BTreeMap<String, DevOverride>,
+) -> anyhow::Result<()> {
+    let dependencies = config.dependencies.get_or_insert_with(Default::default);
+    for (coordinate, dev) in overrides {
+        let Some((group_id, artifact_id)) = coordinate
+            .split_once(':')
+            .filter(|(g, a)| !g.is_empty() && !a.is_empty() && !a.contains(':'))
+        else {
+            bail!(
+                "Invalid override {coordinate} in {DEV_OVERRIDES_FILE}. Use group_id:artifact_id"
+            );
+        };
+        if dev.version.is_none() && dev.path.is_none() {
+            bail!("Override {coordinate} in {DEV_OVERRIDES_FILE} needs a version or a path");
+        }
+        let declared = dependencies
+            .iter()
+            .find(|(name, dep)| {
+                dep.group_id == group_id
+                    && dep.artifact_id.as_deref().unwrap_or(name) == artifact_id
+            })
+            .map(|(name, _)| name.clone());
+        let name = match declared {
+            Some(name) => name,
+            None if dependencies.contains_key(artifact_id) => {
+                bail!("Override {coordinate} in {DEV_OVERRIDES_FILE} clashes with dependency {artifact_id} of another group");
+            }
+            None => artifact_id.to_string(),
+        };
+        let dep = dependencies.entry(name).or_insert_with(|| Dependency {
+            group_id: group_id.to_string(),
+            ..Default::default()
+        });
+        if let Some(version) = &dev.version {
+            dep.version = version.clone();
+        }
+        if let Some(path) = &dev.path {
+            dep.path = Some(path.clone());
+            dep.artifact = dev.artifact.clone();
+            dep.git = None;
+            dep.tag = None;
+            dep.branch = None;
+            dep.rev = None;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn dev_overrides_replace_dependencies() {
+    let mut config: LabToml = toml::from_str(
+        r#"
+[project]
+name = "app"
+description = ""
+version_number = 1
+version = "1.0"
+package = "com.example.app"
+
+[dependencies]
+core = { group_id = "androidx.core", version = "1.13.1" }
+appcompat = { group_id = "androidx.appcompat", version = "1.6.1" }
+"#,
+    )
+    .unwrap();
+    let dev: DevOverrides = toml::from_str(
+        r#"
+[overrides]
+"androidx.core:core" = { path = "../core-patched", artifact = "build/core.aar" }
+"com.squareup.okhttp3:okhttp" = { version = "5.0.0-alpha.14" }
+"#,
+    )
+    .unwrap();
+    let overrides = dev.overrides.unwrap();
+    assert_eq!(
+        overrides["androidx.core:core"].describe(),
+        "../core-patched"
+    );
+    apply_dev_overrides(&mut config, &overrides).unwrap();
+
+    let dependencies = config.dependencies.as_ref().unwrap();
+    assert_eq!(dependencies.len(), 3);
+    let core = &dependencies["core"];
+    assert_eq!(core.path.as_deref(), Some("../core-patched"));
+    assert_eq!(core.artifact.as_deref(), Some("build/core.aar"));
+    assert_eq!(core.version, "1.13.1");
+    // a transitive dependency is forced by declaring it
+    let okhttp = &dependencies["okhttp"];
+    assert_eq!(okhttp.group_id, "com.squareup.okhttp3");
+    assert_eq!(okhttp.version, "5.0.0-alpha.14");
+    assert_eq!(dependencies["appcompat"].version, "1.6.1");
+
+    let invalid = BTreeMap::from([(String::from("okhttp"), DevOverride::default())]);
+    assert!(apply_dev_overrides(&mut config, &invalid).is_err());
+    let empty = BTreeMap::from([(String::from("com.example:lib"), DevOverride::default())]);
+    assert!(apply_dev_overrides(&mut config, &empty).is_err());
+}
+
+#[test]
+fn dev_overrides_only_in_resolve_config() {
+    use crate::{session::Session, set_home, set_manifest_path};
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(super::LABT_TOML_FILE_NAME),
+        r#"
+[project]
+name = "app"
+description = ""
+version_number = 1
+version = "1.0"
+package = "com.example.app"
+
+[dependencies]
+core = { group_id = "androidx.core", version = "1.13.1" }
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join(DEV_OVERRIDES_FILE),
+        "[overrides]\n\"androidx.core:core\" = { version = \"1.14.0-alpha01\" }\n",
+    )
+    .unwrap();
+    Session::new().enter(|| {
+        set_home(Some(dir.path().join("home")));
+        set_manifest_path(Some(dir.path().join(super::LABT_TOML_FILE_NAME)));
+        let version = |config: LabToml| config.dependencies.unwrap()["core"].version.clone();
+        assert_eq!(version(super::get_config().unwrap()), "1.13.1");
+        assert_eq!(
+            version(super::get_resolve_config().unwrap()),
+            "1.14.0-alpha01"
+        );
+        assert_eq!(
+            super::lock::lock_file_in(dir.path()),
+            dir.path().join(DEV_LOCK_FILE)
+        );
+    });
+}
@@ -0,0 +1,136 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::plugin::process::ProcessEnv;
+use crate::submodules::resolvers::file::user_home;
+
+use super::profile::ProfileTable;
+
+/// Environment variables set on the processes plugins spawn. The `[env]` of the
+/// active profile overrides variables of the same name.
+/// ```toml
+/// [env]
+/// GRADLE_OPTS = "-Xmx2g"
+///
+/// [profile.release.env]
+/// KEYSTORE_PASSWORD = { secret = "env:CI_KEYSTORE_PASSWORD" }
+/// UPLOAD_TOKEN = { secret = "file:~/.config/upload-token" }
+/// ```
+pub type EnvTable = BTreeMap<String, EnvValue>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Value(String),
+    /// A value read when the build starts so that it is not written in Labt.toml.
+    /// It is masked in the plugin logs
+    Secret {
+        /// `env:<NAME>` reads the variable from the environment of labt and
+        /// `file:<path>` reads the file, relative to the project root
+        secret: String,
+    },
+}
+
+/// Reads a secret reference
+fn read_secret(reference: &str, project_root: &Path) -> anyhow::Result<String> {
+    if let Some(name) = reference.strip_prefix("env:") {
+        return std::env::var(name).context(format!("The environment variable {name} is not set"));
+    }
+    if let Some(path) = reference.strip_prefix("file:") {
+        let path = match path.strip_prefix("~/") {
+            Some(path) => user_home()?.join(path),
+            None => project_root.join(path),
+        };
+        let value = std::fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?;
+        return Ok(value.trim_end_matches(['\r', '\n']).to_string());
+    }
+    bail!("Unknown secret {reference:?}, secrets are env:<NAME> or file:<path>");
+}
+
+impl EnvValue {
+    /// The value, reading it if it is a secret
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the secret cannot be read
+    pub fn resolve(&self, project_root: &Path) -> anyhow::Result<String> {
+        match self {
+            EnvValue::Value(value) => Ok(value.clone()),
+            EnvValue::Secret { secret } => read_secret(secret, project_root),
+        }
+    }
+}
+
+/// The environment for plugin processes from `[env]` and the `[env]` of the
+/// active profile, with the secrets read
+///
+/// # Errors
+///
+/// Returns an error naming the variable whose secret cannot be read
+pub fn process_env(
+    env: Option<&EnvTable>,
+    profile: Option<&ProfileTable>,
+    project_root: &Path,
+) -> anyhow::Result<ProcessEnv> {
+    let mut merged = env.cloned().unwrap_or_default();
+    if let Some(profile) = profile {
+        merged.extend(profile.env.clone());
+    }
+    let mut process_env = ProcessEnv::default();
+    for (name, value) in merged {
+        match value {
+            EnvValue::Value(value) => process_env.set(name, value),
+            EnvValue::Secret { secret } => {
+                let value = read_secret(&secret, project_root)
+                    .context(format!("Failed to read the secret of {name} in [env]"))?;
+                process_env.set_secret(name, value);
+            }
+        }
+    }
+    Ok(process_env)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn env_reads_secrets_and_profile_overrides() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("env");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(root.join("token"), "s3cr3t-token\n").unwrap();
+
+    let env: EnvTable = toml::from_str(
+        r#"
+GRADLE_OPTS = "-Xmx2g"
+MODE = "debug"
+UPLOAD_TOKEN = { secret = "file:token" }
+"#,
+    )
+    .unwrap();
+    let profile: ProfileTable =
+        toml::from_str("minify = true\n[env]\nMODE = \"release\"\n").unwrap();
+    assert!(!profile.values.contains_key("env"));
+
+    let resolved = process_env(Some(&env), Some(&profile), &root).unwrap();
+    assert_eq!(
+        resolved.vars(),
+        &BTreeMap::from([
+            (String::from("GRADLE_OPTS"), String::from("-Xmx2g")),
+            (String::from("MODE"), String::from("release")),
+            (String::from("UPLOAD_TOKEN"), String::from("s3cr3t-token")),
+        ])
+    );
+    assert_eq!(
+        resolved.mask("uploading with s3cr3t-token"),
+        "uploading with ***"
+    );
+    assert_eq!(resolved.mask("-Xmx2g"), "-Xmx2g");
+
+    let missing: EnvTable =
+        toml::from_str("KEY = { secret = \"env:LABT_ENV_TEST_UNSET\" }\n").unwrap();
+    let err = process_env(Some(&missing), None, &root).unwrap_err();
+    assert!(format!("{err:?}").contains("LABT_ENV_TEST_UNSET is not set"));
+}
@@ -0,0 +1,99 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::bail;
+use clap::ValueEnum;
+
+use crate::submodules::build::Step;
+
+/// The target of the hooks that run around the whole build
+const BUILD_TARGET: &str = "build";
+
+/// The steps a hook can run around and `build` for the whole build
+fn hook_targets() -> Vec<String> {
+    std::iter::once(BUILD_TARGET.to_string())
+        .chain(Step::value_variants().iter().map(Step::to_string))
+        .collect()
+}
+
+/// Lua scripts run before or after a build step with the same api as plugins.
+/// Keys are `pre_<step>` or `post_<step>`, and `pre_build` and `post_build` run
+/// around the whole build. Paths are relative to the project root.
+/// ```toml
+/// [hooks]
+/// pre_build = "scripts/gen.lua"
+/// post_bundle = "scripts/upload.lua"
+/// ```
+pub type HooksTable = BTreeMap<String, PathBuf>;
+
+/// Whether a hook runs before or after its step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HookTime {
+    Pre,
+    Post,
+}
+
+/// The key of the hook that runs around step, or around the whole build if step is None
+pub fn hook_name(time: HookTime, step: Option<Step>) -> String {
+    let time = match time {
+        HookTime::Pre => "pre",
+        HookTime::Post => "post",
+    };
+    match step {
+        Some(step) => format!("{time}_{step}"),
+        None => format!("{time}_{BUILD_TARGET}"),
+    }
+}
+
+/// Checks that every hook is named after a step so that typos are not silently ignored
+///
+/// # Errors
+///
+/// Returns an error naming the unknown hooks
+pub fn validate_hooks(hooks: &HooksTable) -> anyhow::Result<()> {
+    let targets = hook_targets();
+    let unknown: Vec<&str> = hooks
+        .keys()
+        .filter(|key| {
+            !key.strip_prefix("pre_")
+                .or_else(|| key.strip_prefix("post_"))
+                .is_some_and(|target| targets.iter().any(|known| known == target))
+        })
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        bail!(
+            "Unknown hooks in [hooks]: {}. Hooks are named pre_<step> or post_<step> where step is one of {}",
+            unknown.join(", "),
+            targets.join(", ")
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn hooks_are_named_after_steps() {
+    assert_eq!(hook_name(HookTime::Pre, None), "pre_build");
+    assert_eq!(hook_name(HookTime::Post, Some(Step::BUNDLE)), "post_bundle");
+
+    let hooks: HooksTable = toml::from_str(
+        r#"
+pre_build = "scripts/gen.lua"
+post_bundle = "scripts/upload.lua"
+"#,
+    )
+    .unwrap();
+    assert_eq!(hooks["pre_build"], PathBuf::from("scripts/gen.lua"));
+    assert!(validate_hooks(&hooks).is_ok());
+
+    let typo: HooksTable =
+        toml::from_str("post_bundel = \"upload.lua\"\nbuild = \"x.lua\"\n").unwrap();
+    let err = validate_hooks(&typo).unwrap_err().to_string();
+    assert!(err.contains("build, post_bundel"), "{err}");
+    assert!(
+        err.contains("build, pre, aapt, compile, dex, bundle, instrument, post"),
+        "{err}"
+    );
+}
@@ -0,0 +1,38 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+/// The vendor builds of OpenJDK that labt can download
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum JdkDistribution {
+    /// Eclipse Temurin from adoptium.net
+    #[default]
+    Temurin,
+    /// Azul Zulu
+    Zulu,
+}
+
+impl Display for JdkDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JdkDistribution::Temurin => write!(f, "temurin"),
+            JdkDistribution::Zulu => write!(f, "zulu"),
+        }
+    }
+}
+
+/// The JDK the project is built with. Labt downloads it into LABT_HOME/jdks
+/// and exposes it to plugins through JAVA_HOME.
+/// ```toml
+/// [jdk]
+/// version = 17
+/// distribution = "zulu"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JdkTable {
+    /// The java feature version e.g. 17
+    pub version: u32,
+    #[serde(default)]
+    pub distribution: JdkDistribution,
+}
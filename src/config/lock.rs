@@ -1,10 +1,16 @@
+use std::collections::HashSet;
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::str::FromStr;
-use std::{fs::File, path::PathBuf};
+use std::time::{Duration, Instant};
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
 
 use anyhow::bail;
 use anyhow::Context;
+use log::{info, warn};
 use toml_edit::value;
 use toml_edit::Array;
 use toml_edit::ArrayOfTables;
@@ -13,14 +19,16 @@ use toml_edit::Formatted;
 use toml_edit::Item;
 use toml_edit::Table;
 
-use crate::get_project_root;
+use crate::config::dev_overrides::{dev_overrides_active, DEV_LOCK_FILE};
 use crate::pom::VersionRange;
 use crate::submodules::resolve::Constraint;
+use crate::version::ComparableVersion;
+use crate::{get_home, get_project_root};
 use crate::{pom::Scope, submodules::resolve::ProjectDep};
 
 use self::strings::{
-    ARTIFACT_ID, CONSTRAINTS, DEPENDENCIES, EXACT, EXCLUDES, GROUP_ID, LOCK_FILE, MAX, MIN,
-    PACKAGING, PROJECT, SCOPE, URL, VERSION,
+    ARTIFACT_ID, CONSTRAINTS, DEPENDENCIES, EXACT, EXCLUDES, FINGERPRINT, GROUP_ID, LOCK_FILE, MAX,
+    MIN, PACKAGING, PROCESSOR, PROJECT, RESOLVED_AT, RESOLVER, SCOPE, SHA256, TEST, URL, VERSION,
 };
 
 /// containst string constants to be used in writing
@@ -40,10 +48,40 @@ pub mod strings {
     pub const EXACT: &str = "exact";
     pub const EXCLUDES: &str = "excludes";
     pub const LOCK_FILE: &str = "Labt.lock";
+    pub const FINGERPRINT: &str = "fingerprint";
+    pub const PROCESSOR: &str = "processor";
+    pub const TEST: &str = "test";
+    pub const RESOLVER: &str = "resolver";
+    pub const RESOLVED_AT: &str = "resolved_at";
+    pub const SHA256: &str = "sha256";
 }
 #[derive(Default, Clone, Debug)]
 pub struct LabtLock {
     pub resolved: Vec<ProjectDep>,
+    /// Hash of the resolution inputs (dependencies, resolvers and resolution config)
+    /// that produced this lock. If unchanged the dependency tree walk can be skipped.
+    pub fingerprint: Option<String>,
+    /// Annotation processors and their dependencies, resolved separately from
+    /// the project dependencies
+    pub processors: Vec<ProjectDep>,
+    /// Dependencies only needed by tests. Project dependencies they share are
+    /// only listed in resolved
+    pub test: Vec<ProjectDep>,
+}
+
+impl LabtLock {
+    /// The entries of each section by the name used in lock diffs
+    pub fn sections(&self) -> [(&'static str, &Vec<ProjectDep>); 3] {
+        [
+            ("project", &self.resolved),
+            ("test", &self.test),
+            ("processor", &self.processors),
+        ]
+    }
+    /// True if no section has entries
+    pub fn is_empty(&self) -> bool {
+        self.sections().iter().all(|(_, deps)| deps.is_empty())
+    }
 }
 
 impl FromStr for LabtLock {
@@ -51,221 +89,292 @@ impl FromStr for LabtLock {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let lock = s.parse::<Document>().context("Unable to parse lock file")?;
 
-        let mut m_lock = LabtLock::default();
+        Ok(LabtLock {
+            fingerprint: lock
+                .get(FINGERPRINT)
+                .and_then(|f| f.as_str())
+                .map(|f| f.to_string()),
+            resolved: parse_projects(&lock, PROJECT)?,
+            processors: parse_projects(&lock, PROCESSOR)?,
+            test: parse_projects(&lock, TEST)?,
+        })
+    }
+}
 
-        if lock.contains_array_of_tables(PROJECT) {
-            if let Some(table_arrays) = lock[PROJECT].as_array_of_tables() {
-                let missing_err = |key: &str, position: usize| -> anyhow::Result<()> {
-                    bail!(
-                        "Labt.lock: Missing {} in table at position {} ",
-                        key,
-                        position
-                    );
-                };
+/// Reads the array of project tables under key
+fn parse_projects(lock: &Document, key: &str) -> anyhow::Result<Vec<ProjectDep>> {
+    let mut projects = Vec::new();
+    if lock.contains_array_of_tables(key) {
+        if let Some(table_arrays) = lock[key].as_array_of_tables() {
+            let missing_err = |key: &str, position: usize| -> anyhow::Result<()> {
+                bail!(
+                    "Labt.lock: Missing {} in table at position {} ",
+                    key,
+                    position
+                );
+            };
 
-                for dep in table_arrays.iter() {
-                    let mut project = ProjectDep::default();
-                    let position = dep.position().unwrap_or(0);
+            for dep in table_arrays.iter() {
+                let mut project = ProjectDep::default();
+                let position = dep.position().unwrap_or(0);
 
-                    // check for artifact_id
-                    if let Some(artifact_id) = dep.get(ARTIFACT_ID) {
-                        project.artifact_id = artifact_id
-                            .as_value()
-                            .unwrap_or(&toml_edit::Value::String(Formatted::new(String::new())))
-                            .as_str()
-                            .unwrap_or("")
-                            .to_string();
-                    } else {
-                        missing_err(ARTIFACT_ID, position)?;
-                    }
+                // check for artifact_id
+                if let Some(artifact_id) = dep.get(ARTIFACT_ID) {
+                    project.artifact_id = artifact_id
+                        .as_value()
+                        .unwrap_or(&toml_edit::Value::String(Formatted::new(String::new())))
+                        .as_str()
+                        .unwrap_or("")
+                        .to_string();
+                } else {
+                    missing_err(ARTIFACT_ID, position)?;
+                }
 
-                    // check for group_id
-                    if let Some(group_id) = dep.get(GROUP_ID) {
-                        project.group_id = group_id
-                            .as_value()
-                            .unwrap_or(&toml_edit::Value::String(Formatted::new(String::new())))
-                            .as_str()
-                            .unwrap_or("")
-                            .to_string();
-                    } else {
-                        missing_err(GROUP_ID, position)?;
-                    }
+                // check for group_id
+                if let Some(group_id) = dep.get(GROUP_ID) {
+                    project.group_id = group_id
+                        .as_value()
+                        .unwrap_or(&toml_edit::Value::String(Formatted::new(String::new())))
+                        .as_str()
+                        .unwrap_or("")
+                        .to_string();
+                } else {
+                    missing_err(GROUP_ID, position)?;
+                }
 
-                    // check for version
-                    if let Some(version) = dep.get(VERSION) {
-                        project.version = version
-                            .as_value()
-                            .unwrap_or(&toml_edit::Value::String(Formatted::new(String::new())))
-                            .as_str()
-                            .unwrap_or("")
-                            .to_string();
-                    } else {
-                        missing_err(VERSION, position)?;
-                    }
-                    // check for scope
-                    if let Some(scope) = dep.get(SCOPE) {
-                        project.scope = Scope::from(
-                            scope
-                                .as_value()
-                                .unwrap_or(&toml_edit::Value::from("compile")),
-                        );
-                    }
-                    if let Some(url) = dep.get(URL) {
-                        let url = url
-                            .as_value()
-                            .unwrap_or(&toml_edit::Value::from(""))
-                            .as_str()
-                            .unwrap_or("")
-                            .to_string();
-                        project.set_base_url_from_root(url);
-                    } else {
-                        missing_err(URL, position)?;
-                    }
-                    if let Some(url) = dep.get(PACKAGING) {
-                        project.packaging = url
+                // check for version
+                if let Some(version) = dep.get(VERSION) {
+                    project.version = version
+                        .as_value()
+                        .unwrap_or(&toml_edit::Value::String(Formatted::new(String::new())))
+                        .as_str()
+                        .unwrap_or("")
+                        .to_string();
+                } else {
+                    missing_err(VERSION, position)?;
+                }
+                // check for scope
+                if let Some(scope) = dep.get(SCOPE) {
+                    project.scope = Scope::from(
+                        scope
                             .as_value()
-                            .unwrap_or(&toml_edit::Value::from("jar"))
-                            .as_str()
-                            .unwrap_or("jar")
-                            .to_string();
-                    } else {
-                        project.packaging = String::from("jar");
-                    }
+                            .unwrap_or(&toml_edit::Value::from("compile")),
+                    );
+                }
+                if let Some(url) = dep.get(URL) {
+                    let url = url
+                        .as_value()
+                        .unwrap_or(&toml_edit::Value::from(""))
+                        .as_str()
+                        .unwrap_or("")
+                        .to_string();
+                    project.set_base_url_from_root(url);
+                } else {
+                    missing_err(URL, position)?;
+                }
+                if let Some(url) = dep.get(PACKAGING) {
+                    project.packaging = url
+                        .as_value()
+                        .unwrap_or(&toml_edit::Value::from("jar"))
+                        .as_str()
+                        .unwrap_or("jar")
+                        .to_string();
+                } else {
+                    project.packaging = String::from("jar");
+                }
 
-                    if let Some(dependencies) = dep.get(DEPENDENCIES) {
-                        if let Some(array) = dependencies.as_array() {
-                            let mut deps = Vec::new();
-                            deps.extend(array.iter().map(|d| d.as_str().unwrap_or("").to_string()));
-                            project.dependencies = deps;
-                        }
+                project.resolver = dep.get(RESOLVER).and_then(|r| r.as_str()).map(String::from);
+                project.resolved_at = dep
+                    .get(RESOLVED_AT)
+                    .and_then(|r| r.as_str())
+                    .map(String::from);
+                project.sha256 = dep.get(SHA256).and_then(|s| s.as_str()).map(String::from);
+
+                if let Some(dependencies) = dep.get(DEPENDENCIES) {
+                    if let Some(array) = dependencies.as_array() {
+                        let mut deps = Vec::new();
+                        deps.extend(array.iter().map(|d| d.as_str().unwrap_or("").to_string()));
+                        project.dependencies = deps;
                     }
+                }
 
-                    if let Some(constraint) = dep.get(CONSTRAINTS) {
-                        if let Some(table) = constraint.as_table_like() {
-                            let mut c = Constraint::default();
-                            // min
-                            if let Some(min) = table.get(MIN).and_then(|d| d.as_str()) {
-                                if min.starts_with('=') {
-                                    c.min = Some((
-                                        true,
-                                        min.trim_start_matches('=').trim().to_string(),
-                                    ));
-                                } else {
-                                    c.min = Some((false, min.to_string()));
-                                }
+                if let Some(constraint) = dep.get(CONSTRAINTS) {
+                    if let Some(table) = constraint.as_table_like() {
+                        let mut c = Constraint::default();
+                        // min
+                        if let Some(min) = table.get(MIN).and_then(|d| d.as_str()) {
+                            if min.starts_with('=') {
+                                c.min =
+                                    Some((true, min.trim_start_matches('=').trim().to_string()));
+                            } else {
+                                c.min = Some((false, min.to_string()));
                             }
-                            // max
-                            if let Some(max) = table.get(MAX).and_then(|d| d.as_str()) {
-                                if max.starts_with('=') {
-                                    c.max = Some((
-                                        true,
-                                        max.trim_start_matches('=').trim().to_string(),
-                                    ));
-                                } else {
-                                    c.max = Some((false, max.to_string()));
-                                }
-                            }
-                            // exact
-                            if let Some(exact) = table.get(EXACT).and_then(|d| d.as_str()) {
-                                c.exact = Some(exact.to_string());
+                        }
+                        // max
+                        if let Some(max) = table.get(MAX).and_then(|d| d.as_str()) {
+                            if max.starts_with('=') {
+                                c.max =
+                                    Some((true, max.trim_start_matches('=').trim().to_string()));
+                            } else {
+                                c.max = Some((false, max.to_string()));
                             }
-                            // excludes
-                            if let Some(excludes) = table.get(EXCLUDES).and_then(|d| d.as_array()) {
-                                for exclude in excludes {
-                                    if let Some(exclude) = exclude.as_str() {
-                                        let mut split = exclude.split(',');
-                                        let start = split.next().context(
-                                            "Constraint exclude start range is not defined",
-                                        )?;
-                                        let start = start.parse::<VersionRange>().context("Failed to parse start range for an exclude from the given string.")?;
-
-                                        let end = split.next().context(
-                                            "Constraint exclude end range is not defined",
-                                        )?;
-                                        let end = end.parse::<VersionRange>().context("Failed to parse end range for an exclude from the given string.")?;
-
-                                        c.exclusions.push((start, end));
-                                    }
+                        }
+                        // exact
+                        if let Some(exact) = table.get(EXACT).and_then(|d| d.as_str()) {
+                            c.exact = Some(exact.to_string());
+                        }
+                        // excludes
+                        if let Some(excludes) = table.get(EXCLUDES).and_then(|d| d.as_array()) {
+                            for exclude in excludes {
+                                if let Some(exclude) = exclude.as_str() {
+                                    let mut split = exclude.split(',');
+                                    let start = split
+                                        .next()
+                                        .context("Constraint exclude start range is not defined")?;
+                                    let start = start.parse::<VersionRange>().context("Failed to parse start range for an exclude from the given string.")?;
+
+                                    let end = split
+                                        .next()
+                                        .context("Constraint exclude end range is not defined")?;
+                                    let end = end.parse::<VersionRange>().context("Failed to parse end range for an exclude from the given string.")?;
+
+                                    c.exclusions.push((start, end));
                                 }
                             }
-                            if !table.is_empty() {
-                                project.constraints = Some(c);
-                            }
+                        }
+                        if !table.is_empty() {
+                            project.constraints = Some(c);
                         }
                     }
-
-                    m_lock.resolved.push(project);
                 }
+
+                projects.push(project);
             }
         }
-        Ok(m_lock)
     }
+    Ok(projects)
 }
 
 impl Display for LabtLock {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut doc = Document::new();
-        let mut tables_array = ArrayOfTables::new();
-
-        for dep in &self.resolved {
-            let mut deps_array = Array::new();
-            deps_array.decor_mut().set_suffix("\n");
-            deps_array.extend(dep.dependencies.iter());
-
-            let mut table = Table::new();
-            table.insert(ARTIFACT_ID, value(&dep.artifact_id));
-            table.insert(GROUP_ID, value(&dep.group_id));
-            table.insert(VERSION, value(&dep.version));
-            table.insert(SCOPE, value(&dep.scope));
-            table.insert(URL, value(dep.get_root_url()));
-            table.insert(PACKAGING, value(&dep.packaging));
-            if let Some(constraint) = &dep.constraints {
-                let mut c_table = toml_edit::InlineTable::new();
-                if let Some((inclusive, min)) = &constraint.min {
-                    if *inclusive {
-                        c_table
-                            .insert(MIN, value(format!("={min}").as_str()).into_value().unwrap());
-                    } else {
-                        c_table.insert(MIN, value(min.as_str()).into_value().unwrap());
-                    }
-                }
-                if let Some((inclusive, max)) = &constraint.max {
-                    if *inclusive {
-                        c_table
-                            .insert(MAX, value(format!("={max}").as_str()).into_value().unwrap());
-                    } else {
-                        c_table.insert(MAX, value(max.as_str()).into_value().unwrap());
-                    }
-                }
-                if let Some(exact) = &constraint.exact {
-                    c_table.insert(EXACT, value(exact.as_str()).into_value().unwrap());
-                }
 
-                let mut excludes = toml_edit::Array::new();
-                for (start, end) in &constraint.exclusions {
-                    excludes.push(format!("{},{}", start, end));
+        if let Some(fingerprint) = &self.fingerprint {
+            doc.insert(FINGERPRINT, value(fingerprint));
+        }
+
+        doc.insert(PROJECT, Item::ArrayOfTables(projects_array(&self.resolved)));
+        if !self.processors.is_empty() {
+            doc.insert(
+                PROCESSOR,
+                Item::ArrayOfTables(projects_array(&self.processors)),
+            );
+        }
+        if !self.test.is_empty() {
+            doc.insert(TEST, Item::ArrayOfTables(projects_array(&self.test)));
+        }
+        write!(f, "{}", doc)
+    }
+}
+
+/// Writes the projects as an array of tables. Each table is preceded by a comment
+/// listing the projects in the same section that depend on it.
+fn projects_array(deps: &[ProjectDep]) -> ArrayOfTables {
+    let mut tables_array = ArrayOfTables::new();
+    for dep in deps {
+        let prefix = format!("{}:{}:", dep.group_id, dep.artifact_id);
+        let dependents: Vec<String> = deps
+            .iter()
+            .filter(|other| other.dependencies.iter().any(|d| d.starts_with(&prefix)))
+            .map(|other| format!("{}:{}:{}", other.group_id, other.artifact_id, other.version))
+            .collect();
+
+        let mut deps_array = Array::new();
+        deps_array.decor_mut().set_suffix("\n");
+        deps_array.extend(dep.dependencies.iter());
+
+        let mut table = Table::new();
+        table.insert(ARTIFACT_ID, value(&dep.artifact_id));
+        table.insert(GROUP_ID, value(&dep.group_id));
+        table.insert(VERSION, value(&dep.version));
+        table.insert(SCOPE, value(&dep.scope));
+        table.insert(URL, value(dep.get_root_url()));
+        table.insert(PACKAGING, value(&dep.packaging));
+        if let Some(resolver) = &dep.resolver {
+            table.insert(RESOLVER, value(resolver));
+        }
+        if let Some(resolved_at) = &dep.resolved_at {
+            table.insert(RESOLVED_AT, value(resolved_at));
+        }
+        if let Some(sha256) = &dep.sha256 {
+            table.insert(SHA256, value(sha256));
+        }
+        if let Some(constraint) = &dep.constraints {
+            let mut c_table = toml_edit::InlineTable::new();
+            if let Some((inclusive, min)) = &constraint.min {
+                if *inclusive {
+                    c_table.insert(MIN, value(format!("={min}").as_str()).into_value().unwrap());
+                } else {
+                    c_table.insert(MIN, value(min.as_str()).into_value().unwrap());
                 }
-                if !excludes.is_empty() {
-                    c_table.insert(EXCLUDES, excludes.into());
+            }
+            if let Some((inclusive, max)) = &constraint.max {
+                if *inclusive {
+                    c_table.insert(MAX, value(format!("={max}").as_str()).into_value().unwrap());
+                } else {
+                    c_table.insert(MAX, value(max.as_str()).into_value().unwrap());
                 }
+            }
+            if let Some(exact) = &constraint.exact {
+                c_table.insert(EXACT, value(exact.as_str()).into_value().unwrap());
+            }
 
-                table.insert(CONSTRAINTS, value(c_table));
+            let mut excludes = toml_edit::Array::new();
+            for (start, end) in &constraint.exclusions {
+                excludes.push(format!("{},{}", start, end));
+            }
+            if !excludes.is_empty() {
+                c_table.insert(EXCLUDES, excludes.into());
             }
 
-            table.insert(DEPENDENCIES, value(deps_array));
-            tables_array.push(table);
+            table.insert(CONSTRAINTS, value(c_table));
         }
 
-        doc.insert(PROJECT, Item::ArrayOfTables(tables_array));
-        write!(f, "{}", doc)
+        table.insert(DEPENDENCIES, value(deps_array));
+        if !dependents.is_empty() {
+            table
+                .decor_mut()
+                .set_prefix(format!("\n# required by {}\n", dependents.join(", ")));
+        }
+        tables_array.push(table);
+    }
+
+    tables_array
+}
+
+/// The lock file of the project at root. While Labt.dev.toml overrides
+/// dependencies the resolution is kept in Labt.dev.lock so that Labt.lock is
+/// left as committed
+pub fn lock_file_in(root: &Path) -> PathBuf {
+    if dev_overrides_active(root) {
+        root.join(DEV_LOCK_FILE)
+    } else {
+        root.join(LOCK_FILE)
     }
 }
 
+/// The lock file of the current project, see [`lock_file_in`]
+///
+/// # Errors
+///
+/// Returns an error if the project root cannot be found
+pub fn lock_path() -> anyhow::Result<PathBuf> {
+    let root = get_project_root().context("Unable to get project root directory.")?;
+    Ok(lock_file_in(&root))
+}
+
 pub fn load_labt_lock() -> anyhow::Result<LabtLock> {
-    let mut path: PathBuf = get_project_root()
-        .context("Unable to get project root directory.")?
-        .clone();
-    path.push(LOCK_FILE);
+    let path = lock_path()?;
 
     let mut file = File::open(path).context("Unable to open lock file")?;
 
@@ -293,6 +402,322 @@ pub fn write_lock(file: &mut File, lock: &LabtLock) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// How long a writer waits for another labt process to finish writing a lock file
+const LOCK_WRITE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Writing takes milliseconds, so a guard this old was left by a process that crashed
+const STALE_GUARD_AGE: Duration = Duration::from_secs(30);
+/// How often a waiting writer checks if the lock file was released
+const LOCK_WRITE_POLL: Duration = Duration::from_millis(50);
+
+/// Held while a lock file is read, merged and written so that labt processes
+/// resolving the same project at the same time write it one after the other.
+/// Creates `<lock file>.lck` with the process id and removes it once dropped
+pub struct LockFileGuard {
+    path: PathBuf,
+}
+
+impl LockFileGuard {
+    /// Waits up to timeout for other processes writing the lock file at path. A
+    /// guard older than 30 seconds was left by a process that crashed, so it is
+    /// taken over.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the guard cannot be created, labt is interrupted or
+    /// another process holds it for longer than timeout
+    pub fn obtain(path: &Path, timeout: Duration) -> anyhow::Result<Self> {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lck");
+        let guard = path.with_file_name(name);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&guard) {
+                Ok(mut file) => {
+                    file.write_all(std::process::id().to_string().as_bytes())
+                        .context(format!("Failed to write {:?}", guard))?;
+                    return Ok(LockFileGuard { path: guard });
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err).context(format!("Failed to create {:?}", guard)),
+            }
+            if start.elapsed() >= timeout {
+                let age = std::fs::metadata(&guard)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+                    .and_then(|modified| modified.elapsed().ok());
+                if age.is_some_and(|age| age >= STALE_GUARD_AGE) {
+                    warn!(target: "lock", "Removing {:?} left by a labt process that did not finish writing {:?}", guard, path);
+                    // another waiter may have removed it first
+                    let _ = std::fs::remove_file(&guard);
+                    continue;
+                }
+                let pid = std::fs::read_to_string(&guard).unwrap_or_default();
+                bail!(
+                    "Timed out waiting for {:?}. Another labt process ({}) is writing {:?}",
+                    guard,
+                    pid.trim(),
+                    path
+                );
+            }
+            crate::interrupt::check()?;
+            std::thread::sleep(LOCK_WRITE_POLL);
+        }
+    }
+}
+
+impl Drop for LockFileGuard {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            warn!(target: "lock", "Failed to remove {:?}: {}", self.path, err);
+        }
+    }
+}
+
+/// Writes lock to the lock file at path, merging in what other processes wrote
+/// since base was read from it.
+///
+/// The file is only written while holding a [`LockFileGuard`]. If it no longer
+/// matches base, lock and the file are merged three ways against base. Entries
+/// both sides changed keep the higher version and the fingerprint is dropped so
+/// the next resolution walks the tree again. The merged lock is written to a
+/// temporary file and renamed over the lock file so readers never see it
+/// truncated. Returns the lock that was written
+///
+/// # Errors
+///
+/// Returns an error if the guard cannot be obtained, or the lock file cannot be
+/// read, parsed or written
+pub fn update_lock(path: &Path, base: &LabtLock, lock: &LabtLock) -> anyhow::Result<LabtLock> {
+    let _guard = LockFileGuard::obtain(path, LOCK_WRITE_TIMEOUT)?;
+    let current = if path.exists() {
+        Some(
+            std::fs::read_to_string(path)
+                .context(format!("Failed to read {:?}", path))?
+                .parse::<LabtLock>()
+                .context(format!("Failed to parse {:?}", path))?,
+        )
+    } else {
+        None
+    };
+    let lock = match current {
+        Some(current) if current.to_string() != base.to_string() => {
+            let LockMerge {
+                lock: mut merged,
+                conflicts,
+            } = merge_locks(base, lock, &current);
+            info!(target: "lock", "{:?} was changed by another labt process. Merging the changes", path);
+            merged.resolved.extend(conflicts.resolved);
+            merged.test.extend(conflicts.test);
+            merged.processors.extend(conflicts.processors);
+            merged
+        }
+        _ => lock.clone(),
+    };
+    write_lock_file(path, &lock)?;
+    Ok(lock)
+}
+
+/// Replaces the lock file at path with lock, without merging what is in it,
+/// e.g. with the result of a git merge or a snapshot. The file is only written
+/// while holding a [`LockFileGuard`]
+///
+/// # Errors
+///
+/// Returns an error if the guard cannot be obtained or the lock file cannot be written
+pub fn replace_lock(path: &Path, lock: &LabtLock) -> anyhow::Result<()> {
+    let _guard = LockFileGuard::obtain(path, LOCK_WRITE_TIMEOUT)?;
+    write_lock_file(path, lock)
+}
+
+/// Writes lock to a temporary file and renames it over the lock file at path so
+/// readers never see it truncated. The caller holds the [`LockFileGuard`]
+fn write_lock_file(path: &Path, lock: &LabtLock) -> anyhow::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let mut file = File::create(&temp).context(format!("Failed to create {:?}", temp))?;
+    write_lock(&mut file, lock)?;
+    drop(file);
+    std::fs::rename(&temp, path).context(format!("Failed to replace {:?}", path))
+}
+
+/// The result of a three way merge of lock files
+#[derive(Debug, Default)]
+pub struct LockMerge {
+    /// The merged entries without the conflicts
+    pub lock: LabtLock,
+    /// Entries both sides changed differently, at the higher of their two versions.
+    /// These need to be resolved again against the merged lock.
+    pub conflicts: LabtLock,
+}
+
+/// True if both lock files lock the same versions of the same artifacts in each section
+fn same_versions(a: &LabtLock, b: &LabtLock) -> bool {
+    let versions = |deps: &[ProjectDep]| -> HashSet<(String, String, String)> {
+        deps.iter()
+            .map(|dep| {
+                (
+                    dep.group_id.clone(),
+                    dep.artifact_id.clone(),
+                    dep.version.clone(),
+                )
+            })
+            .collect()
+    };
+    a.sections()
+        .iter()
+        .zip(b.sections())
+        .all(|((_, a), (_, b))| versions(a) == versions(b))
+}
+
+fn same_entry(a: Option<&ProjectDep>, b: Option<&ProjectDep>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            a.version == b.version
+                && a.scope == b.scope
+                && a.packaging == b.packaging
+                && a.dependencies == b.dependencies
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Merges a section keeping the order of ours followed by the entries only theirs added.
+/// Returns the merged entries and the conflicts.
+fn merge_section(
+    base: &[ProjectDep],
+    ours: &[ProjectDep],
+    theirs: &[ProjectDep],
+) -> (Vec<ProjectDep>, Vec<ProjectDep>) {
+    let key = |dep: &ProjectDep| format!("{}:{}", dep.group_id, dep.artifact_id);
+    let find = |deps: &'_ [ProjectDep], coordinate: &str| -> Option<ProjectDep> {
+        deps.iter().find(|dep| key(dep) == coordinate).cloned()
+    };
+    let mut seen = HashSet::new();
+    let mut merged = Vec::new();
+    let mut conflicts = Vec::new();
+    for coordinate in ours.iter().chain(theirs).map(key) {
+        if !seen.insert(coordinate.clone()) {
+            continue;
+        }
+        let base = find(base, &coordinate);
+        let ours = find(ours, &coordinate);
+        let theirs = find(theirs, &coordinate);
+        let chosen = if same_entry(ours.as_ref(), theirs.as_ref())
+            || same_entry(base.as_ref(), theirs.as_ref())
+        {
+            ours
+        } else if same_entry(base.as_ref(), ours.as_ref()) {
+            theirs
+        } else {
+            // both sides changed it, a removal loses to a change
+            let higher = match (ours, theirs) {
+                (Some(ours), Some(theirs)) => {
+                    if ComparableVersion::new(&theirs.version)
+                        > ComparableVersion::new(&ours.version)
+                    {
+                        theirs
+                    } else {
+                        ours
+                    }
+                }
+                (Some(dep), None) | (None, Some(dep)) => dep,
+                (None, None) => continue,
+            };
+            conflicts.push(higher);
+            continue;
+        };
+        if let Some(dep) = chosen {
+            merged.push(dep);
+        }
+    }
+    (merged, conflicts)
+}
+
+/// Three way merge of lock files by group_id:artifact_id within each section. An entry
+/// changed on one side only takes that change. The fingerprint is only kept if the
+/// merged lock matches one of the sides, otherwise the next resolution walks the tree.
+pub fn merge_locks(base: &LabtLock, ours: &LabtLock, theirs: &LabtLock) -> LockMerge {
+    let (resolved, resolved_conflicts) =
+        merge_section(&base.resolved, &ours.resolved, &theirs.resolved);
+    let (test, test_conflicts) = merge_section(&base.test, &ours.test, &theirs.test);
+    let (processors, processor_conflicts) =
+        merge_section(&base.processors, &ours.processors, &theirs.processors);
+    let mut lock = LabtLock {
+        resolved,
+        fingerprint: None,
+        processors,
+        test,
+    };
+    let conflicts = LabtLock {
+        resolved: resolved_conflicts,
+        fingerprint: None,
+        processors: processor_conflicts,
+        test: test_conflicts,
+    };
+    if conflicts.is_empty() {
+        if same_versions(ours, &lock) {
+            lock.fingerprint = ours.fingerprint.clone();
+        } else if same_versions(theirs, &lock) {
+            lock.fingerprint = theirs.fingerprint.clone();
+        }
+    }
+    LockMerge { lock, conflicts }
+}
+
+/// The list of lock files written on this machine, kept in labt home so that
+/// labt cache gc knows which cached artifacts are still in use
+pub const KNOWN_LOCKS_FILE: &str = "locks.txt";
+
+/// Returns the lock files recorded in labt home
+///
+/// # Errors
+///
+/// Returns an error if labt home cannot be found or the list cannot be read
+pub fn known_locks() -> anyhow::Result<Vec<PathBuf>> {
+    let path = get_home()?.join(KNOWN_LOCKS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let contents = std::fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?;
+    Ok(contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Replaces the lock files recorded in labt home
+///
+/// # Errors
+///
+/// Returns an error if the list cannot be written
+pub fn write_known_locks(locks: &[PathBuf]) -> anyhow::Result<()> {
+    let path = get_home()?.join(KNOWN_LOCKS_FILE);
+    let mut contents = String::new();
+    for lock in locks {
+        contents.push_str(&lock.to_string_lossy());
+        contents.push('\n');
+    }
+    std::fs::write(&path, contents).context(format!("Failed to write {:?}", path))
+}
+
+/// Adds a lock file to the list in labt home if it is not there yet
+///
+/// # Errors
+///
+/// Returns an error if the list cannot be read or written
+pub fn record_known_lock(lock: &Path) -> anyhow::Result<()> {
+    let lock = lock.canonicalize().unwrap_or(lock.to_path_buf());
+    let mut locks = known_locks()?;
+    if locks.contains(&lock) {
+        return Ok(());
+    }
+    locks.push(lock);
+    write_known_locks(&locks)
+}
+
 impl From<&Scope> for toml_edit::Value {
     fn from(scope: &Scope) -> Self {
         Self::from(scope.to_string())
@@ -311,6 +736,8 @@ use pretty_assertions::assert_eq;
 #[test]
 fn labt_lock_from_string() {
     let lock_str = r#"
+fingerprint = "5f1e2a"
+
 [[project]]
 artifact_id = "grpc-stub"
 group_id = "io.grpc"
@@ -354,6 +781,7 @@ packaging = "aar"
 dependencies = ["androidx.annotation:annotation:1.0.0"]
 "#;
     let lock: LabtLock = lock_str.parse().unwrap();
+    assert_eq!(lock.fingerprint, Some(String::from("5f1e2a")));
     let mut deps = lock.resolved.iter();
     let project = deps.next().unwrap();
     assert_eq!(project.artifact_id, "grpc-stub".to_string());
@@ -432,6 +860,7 @@ constraints = { min = "1.0.0", max = "=1.3.2" }
 dependencies = []
 
 
+# required by androidx.cardview:cardview:1.0.0
 [[project]]
 artifact_id = "annotation"
 group_id = "androidx.annotation"
@@ -518,8 +947,181 @@ dependencies = ["androidx.annotation:annotation:1.0.0"]
                 ..Default::default()
             },
         ],
+        fingerprint: None,
+        processors: Vec::new(),
+        test: Vec::new(),
     };
     // println!("<<<Generated>>>\n{}<<expected>>\n{}", lock, expected);
 
     assert_eq!(lock.to_string(), expected.to_string());
 }
+#[test]
+fn labt_lock_processors_round_trip() {
+    let dep = |artifact_id: &str| ProjectDep {
+        artifact_id: artifact_id.to_string(),
+        group_id: "androidx.room".to_string(),
+        version: "2.6.1".to_string(),
+        scope: Scope::COMPILE,
+        base_url: "https://maven.google.com/".to_string(),
+        packaging: "jar".to_string(),
+        ..Default::default()
+    };
+    let lock = LabtLock {
+        resolved: vec![dep("room-runtime")],
+        processors: vec![dep("room-compiler")],
+        ..Default::default()
+    };
+    let written = lock.to_string();
+    assert!(written.contains("[[processor]]"));
+
+    let parsed: LabtLock = written.parse().unwrap();
+    let names = |deps: &[ProjectDep]| -> Vec<String> {
+        deps.iter()
+            .map(|dep| format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version))
+            .collect()
+    };
+    assert_eq!(
+        names(&parsed.resolved),
+        vec!["androidx.room:room-runtime:2.6.1"]
+    );
+    assert_eq!(
+        names(&parsed.processors),
+        vec!["androidx.room:room-compiler:2.6.1"]
+    );
+    assert_eq!(parsed.to_string(), written);
+
+    // locks without processors do not get an empty section
+    let lock = LabtLock {
+        resolved: vec![dep("room-runtime")],
+        ..Default::default()
+    };
+    assert!(!lock.to_string().contains("processor"));
+}
+
+#[test]
+fn update_lock_merges_concurrent_writes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("update-lock");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(LOCK_FILE);
+    let dep = |artifact_id: &str, version: &str| ProjectDep {
+        artifact_id: artifact_id.to_string(),
+        group_id: "com.example".to_string(),
+        version: version.to_string(),
+        scope: Scope::COMPILE,
+        base_url: "https://repo.example.com/".to_string(),
+        packaging: "jar".to_string(),
+        ..Default::default()
+    };
+    let names = |lock: &LabtLock| -> Vec<String> {
+        lock.resolved
+            .iter()
+            .map(|dep| format!("{}:{}", dep.artifact_id, dep.version))
+            .collect()
+    };
+    let base = LabtLock {
+        resolved: vec![dep("core", "1.0")],
+        fingerprint: Some(String::from("base")),
+        ..Default::default()
+    };
+    std::fs::write(&path, base.to_string()).unwrap();
+
+    // nothing else wrote the file so it is replaced as is
+    let first = LabtLock {
+        resolved: vec![dep("core", "1.0"), dep("http", "2.0")],
+        fingerprint: Some(String::from("first")),
+        ..Default::default()
+    };
+    let written = update_lock(&path, &base, &first).unwrap();
+    assert_eq!(written.fingerprint, Some(String::from("first")));
+
+    // a second process read the base lock before the first one wrote
+    let second = LabtLock {
+        resolved: vec![dep("core", "1.1"), dep("json", "3.0")],
+        fingerprint: Some(String::from("second")),
+        ..Default::default()
+    };
+    let written = update_lock(&path, &base, &second).unwrap();
+    assert_eq!(names(&written), vec!["core:1.1", "json:3.0", "http:2.0"]);
+    assert_eq!(written.fingerprint, None);
+    let on_disk: LabtLock = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+    assert_eq!(on_disk.to_string(), written.to_string());
+    assert!(!dir.join("Labt.lock.lck").exists());
+    assert!(!dir.join("Labt.lock.tmp").exists());
+
+    // a writer waits for the guard of another process
+    let guard = LockFileGuard::obtain(&path, Duration::from_secs(5)).unwrap();
+    let held = std::fs::read_to_string(dir.join("Labt.lock.lck")).unwrap();
+    assert_eq!(held, std::process::id().to_string());
+    let waited = LockFileGuard::obtain(&path, Duration::from_millis(100));
+    assert!(waited.is_err());
+    drop(guard);
+    assert!(LockFileGuard::obtain(&path, Duration::from_millis(100)).is_ok());
+}
+
+#[test]
+fn lock_three_way_merge() {
+    let dep = |artifact_id: &str, version: &str| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: version.to_string(),
+        packaging: String::from("jar"),
+        ..Default::default()
+    };
+    let versions = |deps: &[ProjectDep]| -> Vec<String> {
+        deps.iter()
+            .map(|dep| format!("{}:{}", dep.artifact_id, dep.version))
+            .collect()
+    };
+    let base = LabtLock {
+        resolved: vec![
+            dep("kept", "1.0"),
+            dep("ours", "1.0"),
+            dep("theirs", "1.0"),
+            dep("removed", "1.0"),
+            dep("both", "1.0"),
+            dep("same", "1.0"),
+        ],
+        fingerprint: Some(String::from("base")),
+        ..Default::default()
+    };
+    let ours = LabtLock {
+        resolved: vec![
+            dep("kept", "1.0"),
+            dep("ours", "1.1"),
+            dep("theirs", "1.0"),
+            dep("both", "2.0"),
+            dep("same", "1.5"),
+        ],
+        fingerprint: Some(String::from("ours")),
+        ..Default::default()
+    };
+    let theirs = LabtLock {
+        resolved: vec![
+            dep("kept", "1.0"),
+            dep("ours", "1.0"),
+            dep("theirs", "1.2"),
+            dep("removed", "1.0"),
+            dep("both", "3.0"),
+            dep("same", "1.5"),
+        ],
+        test: vec![dep("junit", "4.13")],
+        fingerprint: Some(String::from("theirs")),
+        ..Default::default()
+    };
+
+    let merge = merge_locks(&base, &ours, &theirs);
+    assert_eq!(
+        versions(&merge.lock.resolved),
+        vec!["kept:1.0", "ours:1.1", "theirs:1.2", "same:1.5"]
+    );
+    assert_eq!(versions(&merge.lock.test), vec!["junit:4.13"]);
+    assert_eq!(versions(&merge.conflicts.resolved), vec!["both:3.0"]);
+    assert_eq!(merge.lock.fingerprint, None);
+
+    // the side that did not change takes the other side with its fingerprint
+    let merge = merge_locks(&base, &base, &theirs);
+    assert!(merge.conflicts.is_empty());
+    assert!(same_versions(&theirs, &merge.lock));
+    assert_eq!(merge.lock.fingerprint, Some(String::from("theirs")));
+}
@@ -14,6 +14,17 @@ const LATEST: &[u8] = b"latest";
 const RELEASE: &[u8] = b"release";
 const VERSIONS: &[u8] = b"versions";
 const VERSION: &[u8] = b"version";
+const LAST_UPDATED: &[u8] = b"lastUpdated";
+const SNAPSHOT: &[u8] = b"snapshot";
+const TIMESTAMP: &[u8] = b"timestamp";
+const BUILD_NUMBER: &[u8] = b"buildNumber";
+const SNAPSHOT_VERSIONS: &[u8] = b"snapshotVersions";
+const SNAPSHOT_VERSION: &[u8] = b"snapshotVersion";
+const CLASSIFIER: &[u8] = b"classifier";
+const EXTENSION: &[u8] = b"extension";
+const VALUE: &[u8] = b"value";
+/// Suffix of versions whose files are replaced by each new build
+pub const SNAPSHOT_SUFFIX: &str = "-SNAPSHOT";
 const NO_SELECTABLE_VERSION_ERROR: &str =
     "No appropriate version could be selected from maven-metadata.xml";
 
@@ -39,6 +50,44 @@ pub struct MavenMetadata {
     /// The release version
     /// <release></release>
     pub release: Option<String>,
+    /// When the metadata was last updated in the form yyyyMMddHHmmss
+    /// <lastUpdated></lastUpdated>
+    pub last_updated: Option<String>,
+    /// The latest build of a snapshot version
+    /// <snapshot></snapshot>
+    pub snapshot: Option<Snapshot>,
+    /// The files of the latest build of a snapshot version
+    /// <snapshotVersions></snapshotVersions>
+    pub snapshot_versions: Vec<SnapshotVersion>,
+}
+
+/// The latest build of a snapshot version, found in the maven-metadata.xml
+/// of the version directory
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// The build time in the form yyyyMMdd.HHmmss
+    /// <timestamp></timestamp>
+    pub timestamp: Option<String>,
+    /// <buildNumber></buildNumber>
+    pub build_number: Option<String>,
+}
+
+/// A file of a snapshot build
+/// <snapshotVersion></snapshotVersion>
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct SnapshotVersion {
+    /// <classifier></classifier>
+    pub classifier: Option<String>,
+    /// <extension></extension>
+    pub extension: String,
+    /// The version the file is named with e.g. 1.0-20240501.101530-3
+    /// <value></value>
+    pub value: String,
+}
+
+/// True if the version is a snapshot e.g. 1.0-SNAPSHOT
+pub fn is_snapshot(version: &str) -> bool {
+    version.ends_with(SNAPSHOT_SUFFIX)
 }
 
 impl MavenMetadata {
@@ -50,8 +99,30 @@ impl MavenMetadata {
             version: None,
             latest: None,
             release: None,
+            last_updated: None,
+            snapshot: None,
+            snapshot_versions: vec![],
         }
     }
+    /// The version the file with the extension of the latest snapshot build is named
+    /// with e.g. 1.0-20240501.101530-3 for 1.0-SNAPSHOT. None if the metadata does not
+    /// describe a deployed snapshot, whose files are then named with the snapshot version.
+    pub fn snapshot_version(&self, extension: &str) -> Option<String> {
+        if let Some(file) = self
+            .snapshot_versions
+            .iter()
+            .find(|file| file.classifier.is_none() && file.extension == extension)
+        {
+            return Some(file.value.clone());
+        }
+        let snapshot = self.snapshot.as_ref()?;
+        let base = self.version.as_ref()?.strip_suffix("SNAPSHOT")?;
+        Some(format!(
+            "{base}{}-{}",
+            snapshot.timestamp.as_ref()?,
+            snapshot.build_number.as_ref()?
+        ))
+    }
     /// chooses appropriate version based on constraints
     /// If a soft version is specified, we immediately return it
     /// If no target version is select, we return release version or latest version if release is not set.
@@ -67,17 +138,15 @@ impl MavenMetadata {
                             Ok(latest.clone())
                         } else {
                             let mut versions = self.versions.clone();
-                            versions.sort_unstable_by(|a, b| {
-                                match version_compare::compare(b, a) {
-                                    Ok(order) => match order {
-                                        Cmp::Eq | Cmp::Le | Cmp::Ge => std::cmp::Ordering::Equal,
-                                        Cmp::Lt => std::cmp::Ordering::Less,
-                                        Cmp::Gt => std::cmp::Ordering::Greater,
-                                        Cmp::Ne => std::cmp::Ordering::Less,
-                                    },
-                                    Err(_) => {
-                                        unreachable!();
-                                    }
+                            versions.sort_unstable_by(|a, b| match crate::version::compare(b, a) {
+                                Ok(order) => match order {
+                                    Cmp::Eq | Cmp::Le | Cmp::Ge => std::cmp::Ordering::Equal,
+                                    Cmp::Lt => std::cmp::Ordering::Less,
+                                    Cmp::Gt => std::cmp::Ordering::Greater,
+                                    Cmp::Ne => std::cmp::Ordering::Less,
+                                },
+                                Err(_) => {
+                                    unreachable!();
                                 }
                             });
 
@@ -98,13 +167,13 @@ impl MavenMetadata {
                 if let Some(release) = &self.release {
                     Ok(release.clone())
                 } else if let Some(latest) = &self.latest {
-                    return Ok(latest.clone());
+                    Ok(latest.clone())
                 } else if self.versions.is_empty() {
                     bail!(NO_SELECTABLE_VERSION_ERROR);
                 } else {
                     // try to select the latest version
                     let mut versions = self.versions.clone();
-                    versions.sort_unstable_by(|a, b| match version_compare::compare(b, a) {
+                    versions.sort_unstable_by(|a, b| match crate::version::compare(b, a) {
                         Ok(order) => match order {
                             Cmp::Eq | Cmp::Le | Cmp::Ge => std::cmp::Ordering::Equal,
                             Cmp::Lt => std::cmp::Ordering::Less,
@@ -120,7 +189,7 @@ impl MavenMetadata {
                         }
                     });
 
-                    return Ok(versions.first().unwrap().to_owned());
+                    Ok(versions.first().unwrap().to_owned())
                 }
             }
             hard => {
@@ -133,12 +202,12 @@ impl MavenMetadata {
                         // check min
                         if let Some((inclusive, min)) = &constraint.min {
                             if *inclusive {
-                                if !version_compare::compare_to(version, min, Cmp::Ge)
+                                if !crate::version::compare_to(version, min, Cmp::Ge)
                                     .unwrap_or(false)
                                 {
                                     return false;
                                 }
-                            } else if !version_compare::compare_to(version, min, Cmp::Gt)
+                            } else if !crate::version::compare_to(version, min, Cmp::Gt)
                                 .unwrap_or(false)
                             {
                                 return false;
@@ -147,12 +216,12 @@ impl MavenMetadata {
                         // check max
                         if let Some((inclusive, max)) = &constraint.max {
                             if *inclusive {
-                                if !version_compare::compare_to(version, max, Cmp::Le)
+                                if !crate::version::compare_to(version, max, Cmp::Le)
                                     .unwrap_or(false)
                                 {
                                     return false;
                                 }
-                            } else if !version_compare::compare_to(version, max, Cmp::Lt)
+                            } else if !crate::version::compare_to(version, max, Cmp::Lt)
                                 .unwrap_or(false)
                             {
                                 return false;
@@ -160,8 +229,7 @@ impl MavenMetadata {
                         }
                         // check for exact
                         if let Some(exact) = &constraint.exact {
-                            if !version_compare::compare_to(version, exact, Cmp::Eq)
-                                .unwrap_or(false)
+                            if !crate::version::compare_to(version, exact, Cmp::Eq).unwrap_or(false)
                             {
                                 return false;
                             }
@@ -181,7 +249,7 @@ impl MavenMetadata {
                     bail!("No appropriate version could be selected from maven-metadata.xml");
                 }
 
-                versions.sort_unstable_by(|a, b| match version_compare::compare(b, a) {
+                versions.sort_unstable_by(|a, b| match crate::version::compare(b, a) {
                     Ok(order) => match order {
                         Cmp::Eq | Cmp::Le | Cmp::Ge => std::cmp::Ordering::Equal,
                         Cmp::Lt => std::cmp::Ordering::Less,
@@ -235,8 +303,17 @@ enum VersioningState {
     /// Read release tag
     /// <release></release>
     ReadRelease,
+    /// Read lastUpdated tag
+    /// <lastUpdated></lastUpdated>
+    ReadLastUpdated,
     /// Versions tag
     Versions(VersionsState),
+    /// Latest snapshot build
+    /// <snapshot></snapshot>
+    Snapshot(SnapshotState),
+    /// Files of the latest snapshot build
+    /// <snapshotVersions></snapshotVersions>
+    SnapshotVersions(SnapshotVersionsState),
 }
 #[derive(Clone)]
 enum VersionsState {
@@ -248,12 +325,37 @@ enum VersionsState {
     ReadVersion,
 }
 
+#[derive(Clone)]
+enum SnapshotState {
+    /// <snapshot></snapshot>
+    Snapshot,
+    /// <timestamp></timestamp>
+    ReadTimestamp,
+    /// <buildNumber></buildNumber>
+    ReadBuildNumber,
+}
+#[derive(Clone)]
+enum SnapshotVersionsState {
+    /// <snapshotVersions></snapshotVersions>
+    SnapshotVersions,
+    /// <snapshotVersion></snapshotVersion>
+    SnapshotVersion,
+    /// <classifier></classifier>
+    ReadClassifier,
+    /// <extension></extension>
+    ReadExtension,
+    /// <value></value>
+    ReadValue,
+}
+
 struct Parser {
     metadata: MavenMetadata,
     /// Tracks the parsing state of tge metadata
     state: ParserState,
     /// Tracks the current version read under versioning
     current_version: String,
+    /// Tracks the current snapshot file read under snapshotVersions
+    current_snapshot_version: SnapshotVersion,
 }
 
 impl Parser {
@@ -262,6 +364,7 @@ impl Parser {
             metadata,
             state: ParserState::Metadata,
             current_version: String::new(),
+            current_snapshot_version: SnapshotVersion::default(),
         }
     }
     fn parse_versions(&mut self, event: Event, state: VersionsState) -> Result<VersionsState> {
@@ -288,6 +391,106 @@ impl Parser {
         };
         Ok(state)
     }
+    fn parse_snapshot(&mut self, event: Event, state: SnapshotState) -> Result<SnapshotState> {
+        let snapshot = self.metadata.snapshot.get_or_insert_with(Snapshot::default);
+        let state = match state {
+            SnapshotState::Snapshot => match event {
+                Event::Start(tag) => match tag.local_name().into_inner() {
+                    TIMESTAMP => SnapshotState::ReadTimestamp,
+                    BUILD_NUMBER => SnapshotState::ReadBuildNumber,
+                    _ => SnapshotState::Snapshot,
+                },
+                _ => SnapshotState::Snapshot,
+            },
+            // <timestamp></timestamp>
+            SnapshotState::ReadTimestamp => match event {
+                Event::End(end) if end.local_name().into_inner() == TIMESTAMP => {
+                    SnapshotState::Snapshot
+                }
+                Event::Text(text) => {
+                    snapshot.timestamp = Some(text.unescape()?.to_string());
+                    SnapshotState::ReadTimestamp
+                }
+                _ => SnapshotState::ReadTimestamp,
+            },
+            // <buildNumber></buildNumber>
+            SnapshotState::ReadBuildNumber => match event {
+                Event::End(end) if end.local_name().into_inner() == BUILD_NUMBER => {
+                    SnapshotState::Snapshot
+                }
+                Event::Text(text) => {
+                    snapshot.build_number = Some(text.unescape()?.to_string());
+                    SnapshotState::ReadBuildNumber
+                }
+                _ => SnapshotState::ReadBuildNumber,
+            },
+        };
+        Ok(state)
+    }
+    fn parse_snapshot_versions(
+        &mut self,
+        event: Event,
+        state: SnapshotVersionsState,
+    ) -> Result<SnapshotVersionsState> {
+        let file = &mut self.current_snapshot_version;
+        let state = match state {
+            SnapshotVersionsState::SnapshotVersions => match event {
+                Event::Start(tag) if tag.local_name().into_inner() == SNAPSHOT_VERSION => {
+                    *file = SnapshotVersion::default();
+                    SnapshotVersionsState::SnapshotVersion
+                }
+                _ => SnapshotVersionsState::SnapshotVersions,
+            },
+            // <snapshotVersion></snapshotVersion>
+            SnapshotVersionsState::SnapshotVersion => match event {
+                Event::Start(tag) => match tag.local_name().into_inner() {
+                    CLASSIFIER => SnapshotVersionsState::ReadClassifier,
+                    EXTENSION => SnapshotVersionsState::ReadExtension,
+                    VALUE => SnapshotVersionsState::ReadValue,
+                    _ => SnapshotVersionsState::SnapshotVersion,
+                },
+                Event::End(end) if end.local_name().into_inner() == SNAPSHOT_VERSION => {
+                    self.metadata.snapshot_versions.push(file.clone());
+                    SnapshotVersionsState::SnapshotVersions
+                }
+                _ => SnapshotVersionsState::SnapshotVersion,
+            },
+            // <classifier></classifier>
+            SnapshotVersionsState::ReadClassifier => match event {
+                Event::End(end) if end.local_name().into_inner() == CLASSIFIER => {
+                    SnapshotVersionsState::SnapshotVersion
+                }
+                Event::Text(text) => {
+                    file.classifier = Some(text.unescape()?.to_string());
+                    SnapshotVersionsState::ReadClassifier
+                }
+                _ => SnapshotVersionsState::ReadClassifier,
+            },
+            // <extension></extension>
+            SnapshotVersionsState::ReadExtension => match event {
+                Event::End(end) if end.local_name().into_inner() == EXTENSION => {
+                    SnapshotVersionsState::SnapshotVersion
+                }
+                Event::Text(text) => {
+                    file.extension = text.unescape()?.to_string();
+                    SnapshotVersionsState::ReadExtension
+                }
+                _ => SnapshotVersionsState::ReadExtension,
+            },
+            // <value></value>
+            SnapshotVersionsState::ReadValue => match event {
+                Event::End(end) if end.local_name().into_inner() == VALUE => {
+                    SnapshotVersionsState::SnapshotVersion
+                }
+                Event::Text(text) => {
+                    file.value = text.unescape()?.to_string();
+                    SnapshotVersionsState::ReadValue
+                }
+                _ => SnapshotVersionsState::ReadValue,
+            },
+        };
+        Ok(state)
+    }
     fn parse_versioning(
         &mut self,
         event: Event,
@@ -298,7 +501,12 @@ impl Parser {
                 Event::Start(tag) => match tag.local_name().into_inner() {
                     LATEST => VersioningState::ReadLatest,
                     RELEASE => VersioningState::ReadRelease,
+                    LAST_UPDATED => VersioningState::ReadLastUpdated,
                     VERSIONS => VersioningState::Versions(VersionsState::Versions),
+                    SNAPSHOT => VersioningState::Snapshot(SnapshotState::Snapshot),
+                    SNAPSHOT_VERSIONS => {
+                        VersioningState::SnapshotVersions(SnapshotVersionsState::SnapshotVersions)
+                    }
                     _ => VersioningState::Versioning,
                 },
                 _ => VersioningState::Versioning,
@@ -325,6 +533,17 @@ impl Parser {
                 }
                 _ => VersioningState::ReadRelease,
             },
+            // <lastUpdated></lastUpdated>
+            VersioningState::ReadLastUpdated => match event {
+                Event::End(end) if end.local_name().into_inner() == LAST_UPDATED => {
+                    VersioningState::Versioning
+                }
+                Event::Text(text) => {
+                    self.metadata.last_updated = Some(text.unescape()?.to_string());
+                    VersioningState::ReadLastUpdated
+                }
+                _ => VersioningState::ReadLastUpdated,
+            },
             // <versions></versions>
             VersioningState::Versions(state) => match event {
                 Event::End(end) if end.local_name().into_inner() == VERSIONS => {
@@ -332,6 +551,22 @@ impl Parser {
                 }
                 event => VersioningState::Versions(self.parse_versions(event, state)?),
             },
+            // <snapshot></snapshot>
+            VersioningState::Snapshot(state) => match event {
+                Event::End(end) if end.local_name().into_inner() == SNAPSHOT => {
+                    VersioningState::Versioning
+                }
+                event => VersioningState::Snapshot(self.parse_snapshot(event, state)?),
+            },
+            // <snapshotVersions></snapshotVersions>
+            VersioningState::SnapshotVersions(state) => match event {
+                Event::End(end) if end.local_name().into_inner() == SNAPSHOT_VERSIONS => {
+                    VersioningState::Versioning
+                }
+                event => {
+                    VersioningState::SnapshotVersions(self.parse_snapshot_versions(event, state)?)
+                }
+            },
         };
 
         Ok(state)
@@ -440,6 +675,7 @@ fn maven_metadata_parsing() {
       <version>6.7.0</version>
       <version>6.6.0</version>
     </versions>
+    <lastUpdated>20240417185412</lastUpdated>
   </versioning>
 </metadata>
 "#
@@ -461,11 +697,72 @@ fn maven_metadata_parsing() {
             "6.7.0".to_string(),
             "6.6.0".to_string(),
         ],
+        last_updated: Some("20240417185412".to_string()),
+        snapshot: None,
+        snapshot_versions: vec![],
     };
 
     assert_eq!(metadata, expected);
 }
 
+#[test]
+fn maven_metadata_snapshot_parsing() {
+    let file = r#"
+<?xml version="1.0" encoding="UTF-8"?>
+<metadata modelVersion="1.1.0">
+  <groupId>com.gitlab.labt</groupId>
+  <artifactId>labt</artifactId>
+  <version>6.9.1-SNAPSHOT</version>
+  <versioning>
+    <snapshot>
+      <timestamp>20240501.101530</timestamp>
+      <buildNumber>3</buildNumber>
+    </snapshot>
+    <lastUpdated>20240501101530</lastUpdated>
+    <snapshotVersions>
+      <snapshotVersion>
+        <classifier>sources</classifier>
+        <extension>jar</extension>
+        <value>6.9.1-20240501.101530-3</value>
+        <updated>20240501101530</updated>
+      </snapshotVersion>
+      <snapshotVersion>
+        <extension>aar</extension>
+        <value>6.9.1-20240501.101530-3</value>
+        <updated>20240501101530</updated>
+      </snapshotVersion>
+      <snapshotVersion>
+        <extension>pom</extension>
+        <value>6.9.1-20240501.101529-3</value>
+        <updated>20240501101530</updated>
+      </snapshotVersion>
+    </snapshotVersions>
+  </versioning>
+</metadata>
+"#
+    .as_bytes();
+    let mut metadata = parse_maven_metadata(BufReader::new(file)).unwrap();
+
+    assert_eq!(metadata.version.as_deref(), Some("6.9.1-SNAPSHOT"));
+    assert_eq!(metadata.snapshot_versions.len(), 3);
+    assert_eq!(
+        metadata.snapshot_version("pom").as_deref(),
+        Some("6.9.1-20240501.101529-3")
+    );
+    assert_eq!(
+        metadata.snapshot_version("aar").as_deref(),
+        Some("6.9.1-20240501.101530-3")
+    );
+    // only the classified jar is listed, fall back to the snapshot build
+    assert_eq!(
+        metadata.snapshot_version("jar").as_deref(),
+        Some("6.9.1-20240501.101530-3")
+    );
+
+    metadata.snapshot = None;
+    assert_eq!(metadata.snapshot_version("jar"), None);
+}
+
 #[test]
 fn maven_metadata_select_version() {
     let metadata = MavenMetadata {
@@ -495,6 +792,9 @@ fn maven_metadata_select_version() {
             "4.7.0".to_string(),
             "4.6.0".to_string(),
         ],
+        last_updated: None,
+        snapshot: None,
+        snapshot_versions: vec![],
     };
 
     assert_eq!(
@@ -563,4 +863,29 @@ fn maven_metadata_select_version() {
             .unwrap(),
         "5.9.0".to_string()
     );
+
+    // qualifiers follow maven ordering, pre releases sort before the release
+    let metadata = MavenMetadata {
+        versions: vec![
+            "2.0-beta1".to_string(),
+            "2.0".to_string(),
+            "2.0-rc1".to_string(),
+            "1.9".to_string(),
+        ],
+        latest: None,
+        release: None,
+        ..metadata
+    };
+    assert_eq!(
+        metadata
+            .select_version(&"[1.0,2.0]".parse::<VersionRequirement>().unwrap())
+            .unwrap(),
+        "2.0".to_string()
+    );
+    assert_eq!(
+        metadata
+            .select_version(&"(,2.0)".parse::<VersionRequirement>().unwrap())
+            .unwrap(),
+        "2.0-rc1".to_string()
+    );
 }
@@ -2,19 +2,59 @@ use std::{
     collections::HashMap,
     fs::File,
     io::{Read, Write},
+    path::PathBuf,
+    time::Duration,
 };
+pub mod androidx;
+pub mod budgets;
+pub mod build_config;
+pub mod deprecations;
+pub mod dev_overrides;
+pub mod env;
+pub mod global;
+pub mod hooks;
+pub mod jdk;
 pub mod lock;
 pub mod maven_metadata;
+pub mod maven_settings;
+pub mod policy;
+pub mod profile;
+pub mod properties;
 pub mod repository;
-
-use anyhow::Context;
+pub mod resolution;
+pub mod toolchains;
+pub mod version_code;
+
+use anyhow::{bail, Context};
+use budgets::BudgetsTable;
+use build_config::BuildConfigTable;
+use deprecations::DeprecationEntry;
+use dev_overrides::{apply_dev_overrides, load_dev_overrides};
+use env::EnvTable;
+use hooks::HooksTable;
+use jdk::JdkTable;
 use mlua::UserData;
+use policy::PolicyTable;
+use profile::ProfilesTable;
+use resolution::ResolutionTable;
 use serde::{Deserialize, Serialize};
 use toml_edit::Document;
+use toolchains::ToolchainsTable;
+use version_code::VersionCodeTable;
+
+use global::get_global_config;
+use maven_settings::{apply_maven_settings, load_maven_settings};
+use properties::{merge_properties, read_version_catalog, substitute_defined};
 
 use crate::{
-    get_project_root,
-    submodules::resolvers::{get_default_resolvers, NetResolver, Resolver},
+    get_manifest_path, get_project_root,
+    pom::Scope,
+    submodules::resolvers::{
+        file::{maven_local_repository, user_home, FileResolver, MAVEN_LOCAL_REPO_STR},
+        get_default_resolvers,
+        path::{is_local, load_path_libraries, PathResolver},
+        CacheResolver, NetResolver, Resolver, CACHE_REPO_STR,
+    },
 };
 
 /// The entire project toml file,
@@ -28,6 +68,20 @@ pub struct LabToml {
     /// \[dependencies\]
     /// artifactId = {dependency inline table}
     pub dependencies: Option<HashMap<String, Dependency>>,
+    /// Annotation processors e.g. Room or Dagger compilers. They are resolved
+    /// separately from the dependencies onto the processor path.
+    /// ```toml
+    /// [annotation_processors]
+    /// room-compiler = { group_id = "androidx.room", version = "2.6.1" }
+    /// ```
+    pub annotation_processors: Option<HashMap<String, Dependency>>,
+    /// Dependencies only needed by tests. They are resolved against the project
+    /// dependencies but can never change their versions.
+    /// ```toml
+    /// [test_dependencies]
+    /// junit = { group_id = "junit", version = "4.13.2" }
+    /// ```
+    pub test_dependencies: Option<HashMap<String, Dependency>>,
     /// The list of configured resolvers
     /// ```toml
     /// [resolvers]
@@ -40,6 +94,60 @@ pub struct LabToml {
     /// core-java = {url = "https://gitlab.com/lab-tool/core-java", version="v0.1.0"}
     /// ```
     pub plugins: Option<HashMap<String, PluginTable>>,
+    /// Artifact allow/deny policy evaluated during resolution
+    /// ```toml
+    /// [policy]
+    /// blocked = ["com.evil"]
+    /// repositories = { "com.mycorp" = ["internal"] }
+    /// ```
+    pub policy: Option<PolicyTable>,
+    /// Group id prefixes pinned to a single resolver. Pinned groups are never
+    /// looked up on any other repository.
+    /// ```toml
+    /// [pins]
+    /// "com.mycorp.*" = "internal"
+    /// ```
+    pub pins: Option<HashMap<String, String>>,
+    /// Extra scopes and optional dependencies to include in resolution. See [`ResolutionTable`]
+    pub resolution: Option<ResolutionTable>,
+    /// Maximum number of seconds a plugin may run in each build step.
+    /// Overrides the timeouts declared by plugins. `default` applies to all steps.
+    /// ```toml
+    /// [timeouts]
+    /// default = 1800
+    /// compile = 600
+    /// ```
+    pub timeouts: Option<HashMap<String, u64>>,
+    /// BuildConfig class generation. See [`BuildConfigTable`]
+    pub build_config: Option<BuildConfigTable>,
+    /// How the versionCode is computed at build time. See [`VersionCodeTable`]
+    pub version_code: Option<VersionCodeTable>,
+    /// The JDK downloaded for the build. See [`JdkTable`]
+    pub jdk: Option<JdkTable>,
+    /// Required versions of the build tools. See [`ToolchainsTable`]
+    pub toolchains: Option<ToolchainsTable>,
+    /// Deprecated artifacts warned about during resolution. See [`DeprecationEntry`]
+    pub deprecations: Option<HashMap<String, DeprecationEntry>>,
+    /// Maven style properties substituted in the poms of every dependency and in
+    /// the dependency versions of this file. They override the [properties] of
+    /// config.toml in LABT_HOME
+    /// ```toml
+    /// [properties]
+    /// "kotlin.version" = "1.9.24"
+    ///
+    /// [dependencies]
+    /// kotlin-stdlib = { group_id = "org.jetbrains.kotlin", version = "${kotlin.version}" }
+    /// ```
+    pub properties: Option<HashMap<String, String>>,
+    /// Debug and release settings for plugins and BuildConfig, selected with
+    /// `labt build --release`. See [`ProfilesTable`]
+    pub profile: Option<ProfilesTable>,
+    /// Lua scripts run before and after build steps. See [`HooksTable`]
+    pub hooks: Option<HooksTable>,
+    /// Seconds each phase of a command is expected to take. See [`BudgetsTable`]
+    pub budgets: Option<BudgetsTable>,
+    /// Environment variables and secrets for the processes plugins spawn. See [`EnvTable`]
+    pub env: Option<EnvTable>,
 }
 
 /// The project details
@@ -58,30 +166,149 @@ pub struct Project {
 }
 
 // a project build dependency
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Dependency {
     /// A redundant artifact id since it can be infered from the
     /// toml dependency key. If specified, then use it instead of infered key
     pub artifact_id: Option<String>,
-    /// The project group id
+    /// The project group id. Optional for path dependencies where it
+    /// defaults to the library package name
+    #[serde(default)]
     pub group_id: String,
-    /// Project version
+    /// Project version. Optional for path dependencies where it defaults
+    /// to the library version
+    #[serde(default)]
     pub version: String,
-    /// The project dependency type i.e. jar, aar etc.
+    /// The project dependency type i.e. jar, aar etc. A `bom` is a platform pom
+    /// whose dependencyManagement supplies the versions of the dependencies
+    /// declared without one.
+    /// ```toml
+    /// compose-bom = { group_id = "androidx.compose", version = "2024.02.00", type = "bom" }
+    /// ui = { group_id = "androidx.compose.ui" }
+    /// ```
+    #[serde(alias = "type")]
     pub dep_type: Option<String>,
-    /// Where to fetch the project
+    /// The only resolver the dependency is fetched from
+    /// ```toml
+    /// widgets = { group_id = "com.mycorp", version = "1.0.0", resolver = "internal" }
+    /// ```
     pub resolver: Option<String>,
+    /// Also fetch the transitive dependencies only from `resolver`
+    pub pin_tree: Option<bool>,
+    /// Where the dependency is needed, compile by default. Runtime dependencies are
+    /// packaged but not compiled against, provided ones are compiled against but not
+    /// packaged and test ones are only used by tests. The scope is passed on to the
+    /// transitive dependencies and recorded in Labt.lock
+    /// ```toml
+    /// leakcanary = { group_id = "com.squareup.leakcanary", version = "2.14", scope = "runtime" }
+    /// ```
+    pub scope: Option<String>,
+    /// Path to a local LABt library, relative to the project root. The library is
+    /// built as part of the dependency graph instead of being fetched.
+    /// ```toml
+    /// my-lib = { path = "../my-lib" }
+    /// ```
+    pub path: Option<String>,
+    /// The AAR/JAR produced by building a path dependency, relative to the library root.
+    /// Defaults to build/<artifact_id>.aar
+    pub artifact: Option<String>,
+    /// Git repository of a library that is not published to a maven repository.
+    /// The repository is cloned into labt home and built like a path dependency.
+    /// If path is also set, it is the library directory inside the repository.
+    /// ```toml
+    /// my-lib = { git = "https://github.com/user/my-lib", tag = "v1.2" }
+    /// ```
+    pub git: Option<String>,
+    /// The git tag to check out
+    pub tag: Option<String>,
+    /// The git branch to check out
+    pub branch: Option<String>,
+    /// The git commit to check out
+    pub rev: Option<String>,
+}
+
+/// The dep_type of a bom dependency
+pub const BOM_TYPE: &str = "bom";
+
+impl Dependency {
+    /// Returns true if this is a bom imported into the dependency management
+    /// rather than an artifact of the project
+    pub fn is_bom(&self) -> bool {
+        self.dep_type.as_deref() == Some(BOM_TYPE)
+    }
+    /// Returns the scope of the dependency, compile if not set
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the scope is not compile, runtime, test or provided
+    pub fn get_scope(&self) -> anyhow::Result<Scope> {
+        match self.scope.as_deref().map(str::parse::<Scope>).transpose()? {
+            None => Ok(Scope::COMPILE),
+            Some(scope @ (Scope::COMPILE | Scope::RUNTIME | Scope::TEST | Scope::PROVIDED)) => {
+                Ok(scope)
+            }
+            Some(scope) => {
+                bail!("Unsupported dependency scope \"{scope}\". Use compile, runtime, test or provided")
+            }
+        }
+    }
 }
 
 /// A resolver table
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResolverTable {
-    /// The repo url
+    /// The repo url. file:// urls are read as local maven repositories
+    #[serde(default)]
     pub url: String,
+    /// A local directory laid out like a maven repository. Relative paths are
+    /// relative to the project root. Defaults to ~/.m2/repository for maven_local
+    pub path: Option<String>,
     /// Is this repo to be treated as a default resolver
     /// for unspecified dependencies
     #[serde(default)]
     pub priority: i32,
+    /// Url of a plain text list of the repository artifacts, one group_id:artifact_id
+    /// per line, downloaded by labt index. Repositories with a master-index.xml do not need it
+    pub index: Option<String>,
+}
+
+impl ResolverTable {
+    /// The directory of a local maven repository. None if the resolver is a
+    /// remote repository
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if neither url nor path is set or the file url is invalid
+    pub fn local_path(&self, name: &str) -> anyhow::Result<Option<PathBuf>> {
+        if let Some(path) = &self.path {
+            let path = match path.strip_prefix("~/") {
+                Some(rest) => user_home()?.join(rest),
+                None => PathBuf::from(path),
+            };
+            if path.is_relative() {
+                let root = get_project_root().context("Failed to get project root directory")?;
+                return Ok(Some(root.join(path)));
+            }
+            return Ok(Some(path));
+        }
+        if self.url.starts_with("file:") {
+            let path = reqwest::Url::parse(&self.url)
+                .ok()
+                .and_then(|url| url.to_file_path().ok())
+                .context(format!(
+                    "Invalid file url {} for resolver {}",
+                    self.url, name
+                ))?;
+            return Ok(Some(path));
+        }
+        if self.url.is_empty() {
+            if name == MAVEN_LOCAL_REPO_STR {
+                return maven_local_repository().map(Some);
+            }
+            bail!("Resolver {} needs a url or a path", name);
+        }
+        Ok(None)
+    }
 }
 
 /// The plugin toml table,
@@ -96,24 +323,21 @@ pub struct PluginTable {
 
 impl UserData for LabToml {}
 
-const LABT_TOML_FILE_NAME: &str = "Labt.toml";
+pub const LABT_TOML_FILE_NAME: &str = "Labt.toml";
 const VERSION_STRING: &str = "version";
 const GROUP_ID_STRING: &str = "group_id";
 const DEPENDENCIES_STRING: &str = "dependencies";
 const LOCATION_STRING: &str = "location";
 const PLUGINS_STRING: &str = "plugins";
 
-/// Reads Labt.toml from the current working directory, and returns
-/// its contents as string
+/// Reads the project manifest, Labt.toml or the one passed with --manifest-path,
+/// and returns its contents as string
 ///
 /// # Errors
 ///
 /// This function will return an error if IO related error is encountered.
 pub fn get_config_string() -> anyhow::Result<String> {
-    let mut path = get_project_root()
-        .context("Failed opening the project root directory")?
-        .clone();
-    path.push(LABT_TOML_FILE_NAME);
+    let path = get_manifest_path().context("Failed opening the project root directory")?;
 
     let mut file = File::open(&path).context(format!(
         "Failed opening {} at {} is this a valid Labt project directory?",
@@ -136,11 +360,66 @@ pub fn get_config_string() -> anyhow::Result<String> {
 /// encountered from [`get_config_string()`]
 pub fn get_config() -> anyhow::Result<LabToml> {
     let toml_string = get_config_string()?;
-    let toml: LabToml =
+    let mut toml: LabToml =
         toml::from_str(&toml_string).context(format!("Failed parsing {}", LABT_TOML_FILE_NAME))?;
+    // a config.toml that fails to load is reported by the commands that use it
+    let global = get_global_config().ok();
+    let root = get_project_root().context("Failed to get the project root")?;
+    let catalog = read_version_catalog(&root)?;
+    let properties = merge_properties(
+        global
+            .as_ref()
+            .and_then(|config| config.properties.as_ref()),
+        Some(&catalog),
+        Some(&toml),
+    )?;
+    substitute_versions(&mut toml, &properties)?;
     Ok(toml)
 }
 
+/// The project config with the overrides of Labt.dev.toml applied. Only resolving
+/// and building use it, the other commands see Labt.toml as it is committed
+///
+/// # Errors
+///
+/// Returns an error if [`get_config()`] fails or Labt.dev.toml is invalid
+pub fn get_resolve_config() -> anyhow::Result<LabToml> {
+    let mut toml = get_config()?;
+    if let Some(overrides) = load_dev_overrides()? {
+        apply_dev_overrides(&mut toml, &overrides)?;
+    }
+    Ok(toml)
+}
+
+/// Replaces the ${property} placeholders in the dependency versions of config
+///
+/// # Errors
+///
+/// Returns an error naming the dependency and the property if a placeholder uses
+/// a property that is not defined. An empty version would silently take the
+/// version from a bom instead
+pub fn substitute_versions(
+    config: &mut LabToml,
+    properties: &HashMap<String, String>,
+) -> anyhow::Result<()> {
+    let tables = [
+        &mut config.dependencies,
+        &mut config.annotation_processors,
+        &mut config.test_dependencies,
+    ];
+    for (name, dependency) in tables
+        .into_iter()
+        .flatten()
+        .flat_map(|table| table.iter_mut())
+    {
+        if dependency.version.contains('$') {
+            dependency.version = substitute_defined(&dependency.version, properties)
+                .context(format!("Failed to get the version of dependency {name}"))?;
+        }
+    }
+    Ok(())
+}
+
 /// Reads Labt.toml and serializes it to a [`toml_edit::Document`]. This is editable
 /// and should be used to write to the toml
 ///
@@ -171,17 +450,42 @@ pub fn get_resolvers_from_config(config: &LabToml) -> anyhow::Result<Vec<Box<dyn
     let mut resolvers =
         get_default_resolvers().context("Failed to initialize default resolvers")?;
 
+    let resolution = config.resolution.as_ref();
+    let refresh = resolution.and_then(|resolution| resolution.snapshot_refresh);
+    let ttl = resolution.and_then(|resolution| resolution.metadata_ttl);
+    if refresh.is_some() || ttl.is_some() {
+        let mut cache = CacheResolver::new();
+        if let Some(refresh) = refresh {
+            cache.set_snapshot_refresh(Duration::from_secs(refresh * 60));
+        }
+        cache.set_metadata_ttl(ttl.map(|hours| Duration::from_secs(hours * 60 * 60)));
+        for resolver in resolvers.iter_mut() {
+            if resolver.get_name() == CACHE_REPO_STR {
+                *resolver = Box::new(cache);
+                break;
+            }
+        }
+    }
+
     if let Some(config_resolvers) = &config.resolvers {
         for (name, resolver) in config_resolvers {
-            let mut net_resolver = NetResolver::init(name.as_str(), resolver.url.as_str())
-                .context(format!(
-                    "Failed to initialize resolver {} for repo at {}",
-                    name, resolver.url
-                ))?;
-            // update priority as configured
-            net_resolver.set_priority(resolver.priority);
-
-            let m_resolver: Box<dyn Resolver> = Box::new(net_resolver);
+            let m_resolver: Box<dyn Resolver> = match resolver.local_path(name)? {
+                Some(path) => {
+                    let mut file_resolver = FileResolver::new(name.as_str(), path);
+                    file_resolver.set_priority(resolver.priority);
+                    Box::new(file_resolver)
+                }
+                None => {
+                    let mut net_resolver = NetResolver::init(name.as_str(), resolver.url.as_str())
+                        .context(format!(
+                            "Failed to initialize resolver {} for repo at {}",
+                            name, resolver.url
+                        ))?;
+                    // update priority as configured
+                    net_resolver.set_priority(resolver.priority);
+                    Box::new(net_resolver)
+                }
+            };
 
             // check default resolvers if this resolver exists,
             if let Some((index, _)) = resolvers
@@ -198,9 +502,32 @@ pub fn get_resolvers_from_config(config: &LabToml) -> anyhow::Result<Vec<Box<dyn
         }
     }
 
+    if let Some(settings) = load_maven_settings().context("Failed to import maven settings.xml")? {
+        apply_maven_settings(&mut resolvers, &settings)?;
+    }
+
+    let has_path_deps = config
+        .dependencies
+        .as_ref()
+        .is_some_and(|deps| deps.values().any(is_local));
+    if has_path_deps {
+        let root = get_project_root().context("Failed to get project root directory")?;
+        let libraries =
+            load_path_libraries(&root, config).context("Failed to load path dependencies")?;
+        resolvers.push(Box::new(PathResolver::new(libraries)));
+    }
+
     // reverse sort the resolvers based on priority
     // highest priority value = top of vec
     resolvers.sort_by_key(|b| std::cmp::Reverse(b.get_priority()));
+    if crate::is_offline() {
+        // only the cache, local libraries and local repositories are available without network access
+        resolvers.retain(|resolver| {
+            resolver
+                .get_base_url()
+                .is_none_or(|url| url.starts_with("file:"))
+        });
+    }
 
     Ok(resolvers)
 }
@@ -229,8 +556,7 @@ pub fn add_dependency_to_config(
         config.insert(DEPENDENCIES_STRING, Item::Table(table));
     }
 
-    let mut path = std::env::current_dir()?;
-    path.push(LABT_TOML_FILE_NAME);
+    let path = get_manifest_path()?;
     let mut file = File::create(path)?;
     file.write_all(config.to_string().as_bytes())?;
 
@@ -297,6 +623,8 @@ pub fn remove_plugin_from_config(name: String) -> anyhow::Result<()> {
 fn get_resolvers_from_config_test() {
     let config = LabToml {
         dependencies: None,
+        annotation_processors: None,
+        test_dependencies: None,
         project: Project {
             name: String::from("labt"),
             description: String::new(),
@@ -310,6 +638,8 @@ fn get_resolvers_from_config_test() {
                 ResolverTable {
                     url: String::from("http://localhost/maven2"),
                     priority: 99,
+                    index: None,
+                    path: None,
                 },
             ),
             (
@@ -317,6 +647,8 @@ fn get_resolvers_from_config_test() {
                 ResolverTable {
                     url: String::from("http://example.com/maven2"),
                     priority: 2,
+                    index: None,
+                    path: None,
                 },
             ),
             // ovveride internal resolver
@@ -327,10 +659,26 @@ fn get_resolvers_from_config_test() {
                     url: String::from("https://maven.google.com/new-url"),
                     // above cache resolver
                     priority: 11,
+                    index: None,
+                    path: None,
                 },
             ),
         ])),
         plugins: None,
+        policy: None,
+        pins: None,
+        resolution: None,
+        timeouts: None,
+        build_config: None,
+        version_code: None,
+        jdk: None,
+        toolchains: None,
+        deprecations: None,
+        properties: None,
+        profile: None,
+        hooks: None,
+        budgets: None,
+        env: None,
     };
 
     let resolvers = get_resolvers_from_config(&config).expect("Failed to get resolvers");
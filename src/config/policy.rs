@@ -0,0 +1,328 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::submodules::resolve::ProjectDep;
+use crate::submodules::resolvers::Resolver;
+
+/// Artifact policy enforced during dependency resolution
+/// ```toml
+/// [policy]
+/// blocked = ["com.evil", "org.example:bad-artifact"]
+///
+/// [policy.repositories]
+/// "com.mycorp" = ["internal"]
+///
+/// [policy.minimum_versions]
+/// "com.squareup.okhttp3:okhttp" = "4.9.2"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct PolicyTable {
+    /// Blocked coordinates. Either a group id which blocks all
+    /// its artifacts or a group_id:artifact_id pair
+    #[serde(default)]
+    pub blocked: Vec<String>,
+    /// Group id prefixes mapped to the names of the only resolvers
+    /// allowed to serve artifacts from that group
+    #[serde(default)]
+    pub repositories: HashMap<String, Vec<String>>,
+    /// group_id:artifact_id mapped to the lowest version allowed
+    #[serde(default)]
+    pub minimum_versions: HashMap<String, String>,
+}
+
+/// A single rule broken by a resolved dependency
+#[derive(Debug, PartialEq, Eq)]
+pub enum PolicyViolation {
+    /// The coordinate matched a blocked entry
+    Blocked { coordinate: String, rule: String },
+    /// The version is below the configured minimum version
+    BelowMinimum {
+        coordinate: String,
+        version: String,
+        minimum: String,
+    },
+    /// The artifact was obtained from a repository not allowed for its group
+    Repository {
+        coordinate: String,
+        url: String,
+        allowed: Vec<String>,
+    },
+}
+
+impl Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Blocked { coordinate, rule } => {
+                write!(f, "{coordinate} is blocked by policy rule \"{rule}\"")
+            }
+            Self::BelowMinimum {
+                coordinate,
+                version,
+                minimum,
+            } => write!(
+                f,
+                "{coordinate}:{version} is below the minimum allowed version {minimum}"
+            ),
+            Self::Repository {
+                coordinate,
+                url,
+                allowed,
+            } => write!(
+                f,
+                "{coordinate} was resolved from \"{url}\" but its group is only allowed from [{}]",
+                allowed.join(", ")
+            ),
+        }
+    }
+}
+
+/// Returns true if group_id falls under the group prefix. Prefixes match
+/// on whole segments so "com.mycorp" matches "com.mycorp.lib" but not "com.mycorpx".
+/// A trailing ".*" on the prefix is ignored.
+pub fn group_matches_prefix(group_id: &str, prefix: &str) -> bool {
+    let prefix = prefix.strip_suffix(".*").unwrap_or(prefix);
+    if prefix == "*" {
+        return true;
+    }
+    match group_id.strip_prefix(prefix) {
+        Some(rest) => rest.is_empty() || rest.starts_with('.'),
+        None => false,
+    }
+}
+
+/// Strips trailing slashes so that repository urls can be compared
+fn normalize_url(url: &str) -> &str {
+    url.trim_end_matches('/')
+}
+
+impl PolicyTable {
+    /// Returns the resolver names allowed for this group. The longest
+    /// matching prefix wins.
+    pub fn allowed_repositories(&self, group_id: &str) -> Option<&Vec<String>> {
+        self.repositories
+            .iter()
+            .filter(|(prefix, _)| group_matches_prefix(group_id, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, resolvers)| resolvers)
+    }
+
+    /// Checks the coordinate against the blocked list. This can be evaluated
+    /// before fetching a dependency, whatever version conflict resolution selects.
+    pub fn check_blocked(&self, group_id: &str, artifact_id: &str) -> Vec<PolicyViolation> {
+        let coordinate = format!("{group_id}:{artifact_id}");
+        self.blocked
+            .iter()
+            .filter(|rule| match rule.split_once(':') {
+                Some((group, artifact)) => {
+                    group_matches_prefix(group_id, group)
+                        && (artifact == "*" || artifact == artifact_id)
+                }
+                None => group_matches_prefix(group_id, rule),
+            })
+            .map(|rule| PolicyViolation::Blocked {
+                coordinate: coordinate.clone(),
+                rule: rule.clone(),
+            })
+            .collect()
+    }
+
+    /// Checks the coordinate against the blocked list and minimum versions.
+    /// Minimum versions only apply to the versions selected by conflict resolution.
+    pub fn check_coordinate(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+        version: &str,
+    ) -> Vec<PolicyViolation> {
+        let mut violations = self.check_blocked(group_id, artifact_id);
+        let coordinate = format!("{group_id}:{artifact_id}");
+
+        if let Some(minimum) = self.minimum_versions.get(&coordinate) {
+            // unparsable versions are treated as violations since we cant prove them safe
+            if !crate::version::compare_to(version, minimum, version_compare::Cmp::Ge)
+                .unwrap_or(false)
+            {
+                violations.push(PolicyViolation::BelowMinimum {
+                    coordinate,
+                    version: version.to_string(),
+                    minimum: minimum.clone(),
+                });
+            }
+        }
+
+        violations
+    }
+
+    /// Checks that the dependency was resolved from one of the repositories allowed
+    /// for its group. `resolvers` is used to map the configured resolver names to urls.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the policy references a resolver that is not configured
+    pub fn check_repository(
+        &self,
+        dep: &ProjectDep,
+        resolvers: &[Box<dyn Resolver>],
+    ) -> anyhow::Result<Option<PolicyViolation>> {
+        let allowed = match self.allowed_repositories(&dep.group_id) {
+            Some(allowed) => allowed,
+            None => return Ok(None),
+        };
+
+        let mut urls = Vec::with_capacity(allowed.len());
+        for name in allowed {
+            let resolver = resolvers.iter().find(|r| r.get_name() == name);
+            match resolver.and_then(|r| r.get_base_url()) {
+                Some(url) => urls.push(normalize_url(url)),
+                None => bail!(
+                    "Policy allows resolver \"{}\" for group {} but no such repository resolver is configured",
+                    name,
+                    dep.group_id
+                ),
+            }
+        }
+
+        if urls.contains(&normalize_url(&dep.base_url)) {
+            return Ok(None);
+        }
+
+        Ok(Some(PolicyViolation::Repository {
+            coordinate: format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version),
+            url: dep.base_url.clone(),
+            allowed: allowed.clone(),
+        }))
+    }
+
+    /// Evaluates the full policy on the resolved dependencies.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error listing all the violations found or if the policy
+    /// is misconfigured.
+    pub fn enforce(
+        &self,
+        resolved: &[ProjectDep],
+        resolvers: &[Box<dyn Resolver>],
+    ) -> anyhow::Result<()> {
+        let mut violations = Vec::new();
+        for dep in resolved {
+            violations.extend(self.check_coordinate(&dep.group_id, &dep.artifact_id, &dep.version));
+            if let Some(violation) = self.check_repository(dep, resolvers)? {
+                violations.push(violation);
+            }
+        }
+
+        if !violations.is_empty() {
+            bail!(
+                "Dependency policy violations found:\n{}",
+                violations
+                    .iter()
+                    .map(|v| format!("  - {v}"))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn policy_group_prefix_matching() {
+    assert!(group_matches_prefix("com.mycorp", "com.mycorp"));
+    assert!(group_matches_prefix("com.mycorp.lib", "com.mycorp"));
+    assert!(group_matches_prefix("com.mycorp.lib", "com.mycorp.*"));
+    assert!(!group_matches_prefix("com.mycorpx", "com.mycorp"));
+    assert!(!group_matches_prefix("org.mycorp", "com.mycorp"));
+}
+
+#[test]
+fn policy_check_coordinate() {
+    let policy = PolicyTable {
+        blocked: vec![String::from("com.evil"), String::from("org.example:bad")],
+        minimum_versions: HashMap::from([(
+            String::from("org.example:good"),
+            String::from("2.0.0"),
+        )]),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        policy.check_coordinate("com.evil.sub", "lib", "1.0"),
+        vec![PolicyViolation::Blocked {
+            coordinate: String::from("com.evil.sub:lib"),
+            rule: String::from("com.evil"),
+        }]
+    );
+    assert_eq!(
+        policy.check_coordinate("org.example", "bad", "1.0").len(),
+        1
+    );
+    assert!(policy
+        .check_coordinate("org.example", "other", "1.0")
+        .is_empty());
+    assert!(policy
+        .check_coordinate("org.example", "good", "2.1")
+        .is_empty());
+    assert_eq!(
+        policy.check_coordinate("org.example", "good", "1.9"),
+        vec![PolicyViolation::BelowMinimum {
+            coordinate: String::from("org.example:good"),
+            version: String::from("1.9"),
+            minimum: String::from("2.0.0"),
+        }]
+    );
+    // a version that may lose conflict resolution is only checked for blocks
+    assert!(policy.check_blocked("org.example", "good").is_empty());
+    assert_eq!(policy.check_blocked("org.example", "bad").len(), 1);
+}
+
+#[test]
+fn policy_check_repository() {
+    use crate::submodules::resolvers::NetResolver;
+
+    let resolvers: Vec<Box<dyn Resolver>> = vec![
+        Box::new(NetResolver::init("internal", "https://maven.mycorp.com/").unwrap()),
+        Box::new(NetResolver::init("public", "https://repo.example.com").unwrap()),
+    ];
+    let policy = PolicyTable {
+        repositories: HashMap::from([
+            (String::from("com.mycorp"), vec![String::from("internal")]),
+            (String::from("com.mycorp.oss"), vec![String::from("public")]),
+        ]),
+        ..Default::default()
+    };
+
+    let mut dep = ProjectDep {
+        group_id: String::from("com.mycorp.app"),
+        artifact_id: String::from("core"),
+        version: String::from("1.0"),
+        base_url: String::from("https://maven.mycorp.com"),
+        ..Default::default()
+    };
+    assert_eq!(policy.check_repository(&dep, &resolvers).unwrap(), None);
+
+    dep.base_url = String::from("https://repo.example.com/");
+    assert!(policy.check_repository(&dep, &resolvers).unwrap().is_some());
+
+    // longest prefix wins
+    dep.group_id = String::from("com.mycorp.oss");
+    assert_eq!(policy.check_repository(&dep, &resolvers).unwrap(), None);
+
+    // ungoverned groups are allowed from anywhere
+    dep.group_id = String::from("org.example");
+    assert_eq!(policy.check_repository(&dep, &resolvers).unwrap(), None);
+
+    let policy = PolicyTable {
+        repositories: HashMap::from([(String::from("com"), vec![String::from("missing")])]),
+        ..Default::default()
+    };
+    dep.group_id = String::from("com.mycorp");
+    assert!(policy.check_repository(&dep, &resolvers).is_err());
+}
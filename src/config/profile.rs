@@ -0,0 +1,142 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use super::env::EnvTable;
+
+/// The profile a build runs with. `labt build --release` selects release
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfileName {
+    #[default]
+    Debug,
+    Release,
+}
+
+impl ProfileName {
+    pub fn from_release(release: bool) -> Self {
+        if release {
+            ProfileName::Release
+        } else {
+            ProfileName::Debug
+        }
+    }
+}
+
+impl Display for ProfileName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileName::Debug => write!(f, "debug"),
+            ProfileName::Release => write!(f, "release"),
+        }
+    }
+}
+
+/// Settings that differ between debug and release builds. The keys are passed
+/// to plugins as they are, so each plugin documents the ones it reads.
+/// ```toml
+/// [profile.debug]
+/// minify = false
+///
+/// [profile.release]
+/// minify = true
+/// signing = { keystore = "release.jks", alias = "upload" }
+///
+/// [profile.release.build_config]
+/// API_URL = "https://api.example.com"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProfilesTable {
+    pub debug: Option<ProfileTable>,
+    pub release: Option<ProfileTable>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct ProfileTable {
+    /// BuildConfig fields of this profile. They override the fields of the same
+    /// name in `[build_config.fields]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub build_config: BTreeMap<String, toml::Value>,
+    /// Environment variables of this profile. They override the variables of the
+    /// same name in `[env]`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: EnvTable,
+    /// The keys for plugins e.g. signing and optimization settings
+    #[serde(flatten)]
+    pub values: BTreeMap<String, toml::Value>,
+}
+
+impl ProfilesTable {
+    /// The table of a profile if Labt.toml has one
+    pub fn get(&self, name: ProfileName) -> Option<&ProfileTable> {
+        match name {
+            ProfileName::Debug => self.debug.as_ref(),
+            ProfileName::Release => self.release.as_ref(),
+        }
+    }
+}
+
+/// Returns the BuildConfig fields with those of the profile replacing fields of the same name
+pub fn profile_build_config_fields(
+    fields: &BTreeMap<String, toml::Value>,
+    profile: Option<&ProfileTable>,
+) -> BTreeMap<String, toml::Value> {
+    let mut fields = fields.clone();
+    if let Some(profile) = profile {
+        fields.extend(profile.build_config.clone());
+    }
+    fields
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn profiles_parse_and_override_build_config_fields() {
+    let profiles: ProfilesTable = toml::from_str(
+        r#"
+[debug]
+minify = false
+
+[release]
+minify = true
+signing = { keystore = "release.jks", alias = "upload" }
+
+[release.build_config]
+API_URL = "https://api.example.com"
+"#,
+    )
+    .unwrap();
+    let release = profiles.get(ProfileName::Release).unwrap();
+    assert_eq!(release.values["minify"], toml::Value::Boolean(true));
+    assert_eq!(
+        release.values["signing"]["alias"],
+        toml::Value::String(String::from("upload"))
+    );
+    assert!(!release.values.contains_key("build_config"));
+    assert!(profiles
+        .get(ProfileName::Debug)
+        .unwrap()
+        .build_config
+        .is_empty());
+    assert_eq!(ProfileName::from_release(true).to_string(), "release");
+
+    let fields = BTreeMap::from([
+        (
+            String::from("API_URL"),
+            toml::Value::String(String::from("https://staging.example.com")),
+        ),
+        (String::from("RETRIES"), toml::Value::Integer(3)),
+    ]);
+    let merged = profile_build_config_fields(&fields, Some(release));
+    assert_eq!(
+        merged["API_URL"],
+        toml::Value::String(String::from("https://api.example.com"))
+    );
+    assert_eq!(merged["RETRIES"], toml::Value::Integer(3));
+    assert_eq!(profile_build_config_fields(&fields, None), fields);
+
+    let typo: Result<ProfilesTable, _> = toml::from_str("[relase]\nminify = true\n");
+    assert!(typo.is_err());
+}
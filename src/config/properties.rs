@@ -0,0 +1,255 @@
+use std::{cell::RefCell, collections::HashMap, fs::read_to_string, path::Path, sync::Arc};
+
+use anyhow::{bail, Context};
+
+use crate::{get_manifest_path, get_project_root, pom::substitute_properties, session::Session};
+
+use super::{get_config, global::get_global_config, LabToml, LABT_TOML_FILE_NAME};
+
+/// The version catalog read from the project root. It has the layout of a gradle
+/// version catalog and its [versions] are used as properties
+pub const VERSION_CATALOG_FILE: &str = "libs.versions.toml";
+
+/// Reads the [versions] of the version catalog in root. A version is a string or
+/// a table with strictly, require or prefer. Returns an empty map if the project
+/// has no catalog
+pub fn read_version_catalog(root: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let path = root.join(VERSION_CATALOG_FILE);
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let contents = read_to_string(&path).context(format!("Failed reading {:?}", path))?;
+    let catalog: toml::Table =
+        toml::from_str(&contents).context(format!("Failed parsing {:?}", path))?;
+    let Some(versions) = catalog.get("versions") else {
+        return Ok(HashMap::new());
+    };
+    let versions = versions
+        .as_table()
+        .context(format!("[versions] of {:?} is not a table", path))?;
+    versions
+        .iter()
+        .map(|(name, version)| {
+            let version = match version {
+                toml::Value::String(version) => Some(version.as_str()),
+                toml::Value::Table(table) => ["strictly", "require", "prefer"]
+                    .iter()
+                    .find_map(|key| table.get(*key).and_then(toml::Value::as_str)),
+                _ => None,
+            };
+            match version {
+                Some(version) => Ok((name.clone(), version.to_string())),
+                None => bail!("Version {name} of {:?} has no version string", path),
+            }
+        })
+        .collect()
+}
+
+/// The [properties] of config.toml overridden by the [versions] of the version
+/// catalog, overridden by the [properties] of Labt.toml. The catalog versions may
+/// use the properties of config.toml and Labt.toml
+///
+/// # Errors
+///
+/// Returns an error naming the property a catalog version uses that is not defined
+pub fn merge_properties(
+    global: Option<&HashMap<String, String>>,
+    catalog: Option<&HashMap<String, String>>,
+    project: Option<&LabToml>,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut properties = global.cloned().unwrap_or_default();
+    let project = project.and_then(|config| config.properties.as_ref());
+    if let Some(project) = project {
+        properties.extend(project.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    let Some(catalog) = catalog else {
+        return Ok(properties);
+    };
+    let mut versions = HashMap::new();
+    for (name, version) in catalog {
+        let version = substitute_defined(version, &properties).context(format!(
+            "Failed to read version {name} of {VERSION_CATALOG_FILE}"
+        ))?;
+        versions.insert(name.clone(), version);
+    }
+    for (name, version) in versions {
+        let defined_by_project = project.is_some_and(|project| project.contains_key(&name));
+        if !defined_by_project {
+            properties.insert(name, version);
+        }
+    }
+    Ok(properties)
+}
+
+/// Replaces the ${property} placeholders of value
+///
+/// # Errors
+///
+/// Returns an error naming the first property that is not defined
+pub fn substitute_defined(
+    value: &str,
+    properties: &HashMap<String, String>,
+) -> anyhow::Result<String> {
+    let missing = RefCell::new(None);
+    let substituted = substitute_properties(value, |key| {
+        let found = properties.get(key).cloned();
+        if found.is_none() {
+            missing.borrow_mut().get_or_insert_with(|| key.to_string());
+        }
+        found
+    });
+    if let Some(missing) = missing.into_inner() {
+        bail!("The property {missing} used in {value} is not defined in [properties] of {LABT_TOML_FILE_NAME}, config.toml or {VERSION_CATALOG_FILE}");
+    }
+    Ok(substituted)
+}
+
+/// Uses properties instead of the ones in config.toml and Labt.toml. None reads
+/// them again on the next call, e.g. after the project changed
+pub fn set_properties(properties: Option<HashMap<String, String>>) {
+    *Session::current()
+        .properties
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = properties.map(Arc::new);
+}
+
+/// Returns the properties every pom inherits, like the properties of an active
+/// profile in a maven settings.xml. They are read once from config.toml and the
+/// Labt.toml of the current project if there is one. Files that fail to load
+/// are skipped here since the commands that need them report the error.
+pub fn get_properties() -> Arc<HashMap<String, String>> {
+    let session = Session::current();
+    if let Some(properties) = session
+        .properties
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+    {
+        return Arc::clone(properties);
+    }
+    let global = get_global_config().ok();
+    let project = get_manifest_path()
+        .ok()
+        .filter(|path| path.exists())
+        .and_then(|_| get_config().ok());
+    let catalog = project
+        .as_ref()
+        .and_then(|_| get_project_root().ok())
+        .and_then(|root| read_version_catalog(&root).ok());
+    // get_config already merged the same files without an error
+    let properties = Arc::new(
+        merge_properties(
+            global
+                .as_ref()
+                .and_then(|config| config.properties.as_ref()),
+            catalog.as_ref(),
+            project.as_ref(),
+        )
+        .unwrap_or_default(),
+    );
+    *session
+        .properties
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = Some(Arc::clone(&properties));
+    properties
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn properties_substitute_dependency_versions() {
+    use super::substitute_versions;
+
+    let manifest = r#"
+[project]
+name = "app"
+description = ""
+version_number = 1
+version = "1.0"
+package = "com.example.app"
+
+[properties]
+"kotlin.version" = "1.9.24"
+
+[dependencies]
+kotlin-stdlib = { group_id = "org.jetbrains.kotlin", version = "${kotlin.version}" }
+core = { group_id = "androidx.core", version = "${core.version}" }
+appcompat = { group_id = "androidx.appcompat", version = "1.6.1" }
+"#;
+    let mut config: LabToml = toml::from_str(manifest).unwrap();
+    let global = HashMap::from([
+        (String::from("kotlin.version"), String::from("1.8.0")),
+        (String::from("core.version"), String::from("1.13.1")),
+    ]);
+
+    let properties = merge_properties(Some(&global), None, Some(&config)).unwrap();
+    assert_eq!(properties["kotlin.version"], "1.9.24");
+    assert_eq!(properties["core.version"], "1.13.1");
+
+    substitute_versions(&mut config, &properties).unwrap();
+    let dependencies = config.dependencies.unwrap();
+    assert_eq!(dependencies["kotlin-stdlib"].version, "1.9.24");
+    assert_eq!(dependencies["core"].version, "1.13.1");
+    assert_eq!(dependencies["appcompat"].version, "1.6.1");
+
+    // an undefined property is an error instead of an empty version
+    let global = HashMap::from([(String::from("kotlin.version"), String::from("1.8.0"))]);
+    let mut typo: LabToml = toml::from_str(manifest).unwrap();
+    let properties = merge_properties(Some(&global), None, Some(&typo)).unwrap();
+    let err = substitute_versions(&mut typo, &properties).unwrap_err();
+    assert!(format!("{:?}", err).contains("core.version"), "{:?}", err);
+}
+
+#[test]
+fn properties_from_version_catalog() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(VERSION_CATALOG_FILE),
+        r#"
+[versions]
+kotlin = "${kotlin.version}"
+core = { strictly = "1.13.1" }
+appcompat = "1.6.1"
+
+[libraries]
+core = { module = "androidx.core:core", version.ref = "core" }
+"#,
+    )
+    .unwrap();
+    let catalog = read_version_catalog(dir.path()).unwrap();
+    assert_eq!(catalog["core"], "1.13.1");
+    assert_eq!(
+        read_version_catalog(&dir.path().join("none"))
+            .unwrap()
+            .len(),
+        0
+    );
+
+    let config: LabToml = toml::from_str(
+        r#"
+[project]
+name = "app"
+description = ""
+version_number = 1
+version = "1.0"
+package = "com.example.app"
+
+[properties]
+appcompat = "1.7.0"
+"#,
+    )
+    .unwrap();
+    let global = HashMap::from([
+        (String::from("kotlin.version"), String::from("1.9.24")),
+        (String::from("core"), String::from("1.12.0")),
+    ]);
+    let properties = merge_properties(Some(&global), Some(&catalog), Some(&config)).unwrap();
+    // catalog versions use global properties and override them, the project wins
+    assert_eq!(properties["kotlin"], "1.9.24");
+    assert_eq!(properties["core"], "1.13.1");
+    assert_eq!(properties["appcompat"], "1.7.0");
+
+    let err = merge_properties(None, Some(&catalog), Some(&config)).unwrap_err();
+    assert!(format!("{:?}", err).contains("kotlin.version"), "{:?}", err);
+}
@@ -107,6 +107,7 @@ impl Display for ChannelType {
 }
 
 #[derive(Debug)]
+#[allow(unused)]
 enum OsType {
     Linux,
     Macosx,
@@ -0,0 +1,54 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+/// Scopes that can be added to the resolution besides compile
+pub const INCLUDABLE_SCOPES: [&str; 3] = ["provided", "runtime", "test"];
+
+/// Selects which transitive dependencies are followed during resolution.
+/// By default only compile scoped dependencies that are not optional are resolved.
+/// ```toml
+/// [resolution]
+/// scopes = ["provided", "runtime"]
+/// optional = true
+/// jobs = 4
+/// snapshot_refresh = 60
+/// metadata_ttl = 24
+/// max_java_release = 11
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ResolutionTable {
+    /// Extra dependency scopes to resolve. One of provided, runtime or test
+    #[serde(default, deserialize_with = "deserialize_scopes")]
+    pub scopes: Vec<String>,
+    /// Also resolve dependencies marked as optional
+    #[serde(default)]
+    pub optional: bool,
+    /// Number of poms downloaded at the same time. 1 fetches them one by one
+    pub jobs: Option<usize>,
+    /// Minutes a cached -SNAPSHOT version is used before the repositories are checked
+    /// for a newer build. 0 checks on every resolution. Defaults to a day
+    pub snapshot_refresh: Option<u64>,
+    /// Hours the cached maven-metadata.xml is used to pick LATEST, RELEASE and range
+    /// versions before the repositories are asked for a newer one. Unset uses it
+    /// until it is deleted
+    pub metadata_ttl: Option<u64>,
+    /// The newest Java release the classes of the dependencies may target, e.g. the
+    /// highest one d8 can desugar for the min sdk. Dependencies compiled for a newer
+    /// release are reported after resolution. Unset skips the check
+    pub max_java_release: Option<u16>,
+}
+
+fn deserialize_scopes<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let scopes = Vec::<String>::deserialize(deserializer)?;
+    for scope in &scopes {
+        if !INCLUDABLE_SCOPES.contains(&scope.as_str()) {
+            return Err(D::Error::custom(format!(
+                "Unsupported resolution scope \"{scope}\". Expected one of {}",
+                INCLUDABLE_SCOPES.join(", ")
+            )));
+        }
+    }
+    Ok(scopes)
+}
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// The versions of the build tools the project expects. A tool found with a
+/// different version is rejected instead of silently used. A requirement is either
+/// a version prefix e.g. `"17"` matches 17.0.2, or a comparison with one of
+/// `>=`, `>`, `<=`, `<` and `=`.
+/// ```toml
+/// [toolchains]
+/// javac = "17"
+/// kotlinc = ">=1.9"
+/// aapt2 = "34.0.0"
+/// d8 = "34.0.0"
+/// apksigner = "34.0.0"
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ToolchainsTable {
+    pub javac: Option<String>,
+    pub kotlinc: Option<String>,
+    /// aapt2, d8 and apksigner versions are the build-tools package versions
+    pub aapt2: Option<String>,
+    pub d8: Option<String>,
+    pub apksigner: Option<String>,
+}
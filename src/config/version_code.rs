@@ -0,0 +1,157 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+
+use super::LabToml;
+
+/// The largest versionCode accepted by Google Play
+pub const MAX_VERSION_CODE: i64 = 2_100_000_000;
+
+/// Environment variables holding the build number on common CI services
+const CI_BUILD_NUMBER_VARS: [&str; 6] = [
+    "BUILD_NUMBER",
+    "GITHUB_RUN_NUMBER",
+    "CI_PIPELINE_IID",
+    "BITBUCKET_BUILD_NUMBER",
+    "CIRCLE_BUILD_NUM",
+    "BUILD_BUILDID",
+];
+
+/// How the versionCode of the app is derived at build time
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionCodeStrategy {
+    /// Use project.version_number as is
+    #[default]
+    Fixed,
+    /// Number of commits reachable from HEAD
+    GitCommitCount,
+    /// Minutes since the unix epoch
+    Timestamp,
+    /// The build number provided by the CI service
+    Ci,
+}
+
+/// Computes the versionCode at build time instead of using the fixed
+/// project.version_number.
+/// ```toml
+/// [version_code]
+/// strategy = "git_commit_count"
+/// offset = 1000
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct VersionCodeTable {
+    #[serde(default)]
+    pub strategy: VersionCodeStrategy,
+    /// Added to the computed version code. Useful to keep version codes
+    /// increasing after switching strategies
+    #[serde(default)]
+    pub offset: i64,
+    /// Environment variable with the build number for the ci strategy.
+    /// Defaults to checking the variables of common CI services
+    pub env: Option<String>,
+}
+
+fn git_commit_count(root: &Path) -> anyhow::Result<i64> {
+    let repo =
+        Repository::discover(root).context(format!("No git repository found at {:?}", root))?;
+    let mut walk = repo.revwalk()?;
+    walk.push_head()
+        .context("Failed to read HEAD of the project repository")?;
+    Ok(walk.count() as i64)
+}
+
+fn timestamp() -> anyhow::Result<i64> {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System clock is set before the unix epoch")?;
+    Ok((since_epoch.as_secs() / 60) as i64)
+}
+
+fn ci_build_number(env: Option<&str>) -> anyhow::Result<i64> {
+    let vars: Vec<&str> = match env {
+        Some(env) => vec![env],
+        None => CI_BUILD_NUMBER_VARS.to_vec(),
+    };
+    for var in &vars {
+        if let Ok(value) = std::env::var(var) {
+            return value
+                .trim()
+                .parse()
+                .context(format!("CI build number in {var} is not a number: {value}"));
+        }
+    }
+    bail!(
+        "No CI build number found. Checked environment variables {}",
+        vars.join(", ")
+    );
+}
+
+/// Computes the versionCode of the project using the strategy in the
+/// `[version_code]` table, or project.version_number if not configured
+///
+/// # Errors
+///
+/// Returns an error if the strategy fails or the result is not a valid versionCode
+pub fn compute_version_code(config: &LabToml, root: &Path) -> anyhow::Result<i64> {
+    let table = match &config.version_code {
+        Some(table) => table,
+        None => return Ok(config.project.version_number as i64),
+    };
+    let code = match table.strategy {
+        VersionCodeStrategy::Fixed => config.project.version_number as i64,
+        VersionCodeStrategy::GitCommitCount => {
+            git_commit_count(root).context("Failed to count git commits for versionCode")?
+        }
+        VersionCodeStrategy::Timestamp => timestamp()?,
+        VersionCodeStrategy::Ci => ci_build_number(table.env.as_deref())?,
+    } + table.offset;
+
+    if !(1..=MAX_VERSION_CODE).contains(&code) {
+        bail!("Computed versionCode {code} is outside the valid range 1 to {MAX_VERSION_CODE}");
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn version_code_from_ci_and_git() {
+    let env = format!("LABT_TEST_BUILD_NUMBER_{}", std::process::id());
+    std::env::set_var(&env, " 42 ");
+    assert_eq!(ci_build_number(Some(&env)).unwrap(), 42);
+    std::env::set_var(&env, "abc");
+    assert!(ci_build_number(Some(&env)).is_err());
+    std::env::remove_var(&env);
+    assert!(ci_build_number(Some(&env)).is_err());
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("version-code");
+    std::fs::create_dir_all(&root).unwrap();
+    let repo = Repository::init(&root).unwrap();
+    let signature = git2::Signature::now("labt", "labt@example.com").unwrap();
+    let tree = repo
+        .find_tree(repo.index().unwrap().write_tree().unwrap())
+        .unwrap();
+    let first = repo
+        .commit(Some("HEAD"), &signature, &signature, "first", &tree, &[])
+        .unwrap();
+    let first = repo.find_commit(first).unwrap();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        "second",
+        &tree,
+        &[&first],
+    )
+    .unwrap();
+    let count = git_commit_count(&root);
+    assert_eq!(count.unwrap(), 2);
+}
@@ -0,0 +1,405 @@
+use std::{
+    cell::RefCell,
+    io::{stdout, Read, Write},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    thread::ThreadId,
+};
+
+use clap::ValueEnum;
+use indicatif::{ProgressBar, ProgressDrawTarget};
+use serde::Serialize;
+
+use crate::MULTI_PROGRESS_BAR;
+
+/// How often download progress is reported in bytes
+const DOWNLOAD_PROGRESS_INTERVAL: u64 = 256 * 1024;
+
+/// Set when events should be written to stdout as json lines
+static JSON_EVENTS: AtomicBool = AtomicBool::new(false);
+/// The subscription writing the json lines
+static JSON_SUBSCRIPTION: Mutex<Option<Subscription>> = Mutex::new(None);
+
+/// A function called with every event emitted on any thread
+type Listener = Arc<dyn Fn(&Event) + Send + Sync>;
+/// A function called with the events emitted on the thread it subscribed on. None
+/// only delivers the events queued from other threads
+type LocalListener = Rc<dyn Fn(Option<&Event>)>;
+
+static LISTENERS: RwLock<Vec<(usize, Listener)>> = RwLock::new(Vec::new());
+/// The number of listeners on all threads, so that emitting is free without any
+static SUBSCRIBED: AtomicUsize = AtomicUsize::new(0);
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+thread_local! {
+    static LOCAL_LISTENERS: RefCell<Vec<(usize, LocalListener)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The format in which labt reports progress
+#[derive(Clone, Copy, ValueEnum, Debug, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Progress bars and log messages
+    #[default]
+    Human,
+    /// One json event per line on stdout. Logs are still written to stderr
+    Json,
+}
+
+/// A structured progress event for IDEs and wrappers
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// Resolution of a dependency has started
+    DependencyStarted { coordinate: String },
+    /// A dependency and its transitive dependencies have been resolved
+    DependencyFinished {
+        coordinate: String,
+        repository: String,
+        cache_hit: bool,
+    },
+    /// A dependency artifact was found in the cache
+    CacheHit { coordinate: String },
+    /// Bytes of a dependency artifact downloaded so far
+    DownloadProgress {
+        coordinate: String,
+        downloaded: u64,
+        total: Option<u64>,
+    },
+    /// A dependency artifact was downloaded
+    DownloadFinished { coordinate: String, size: u64 },
+    /// Two versions of the same dependency were requested and one was selected
+    ConflictResolved {
+        group_id: String,
+        artifact_id: String,
+        requested: String,
+        selected: String,
+    },
+    /// Resolution of the project dependencies has started
+    ResolutionStarted { dependencies: usize },
+    /// Resolution has ended. Unless it failed the dependencies were resolved and
+    /// their artifacts saved to the cache
+    ResolutionFinished {
+        resolved: usize,
+        success: bool,
+        elapsed_ms: u64,
+    },
+    /// The plugins of a build step are about to run
+    StepStarted { step: String },
+    /// The plugins of a build step have run
+    StepFinished {
+        step: String,
+        success: bool,
+        elapsed_ms: u64,
+    },
+    /// An sdk package is being installed
+    SdkInstallStarted { package: String, version: String },
+    /// An sdk package was installed
+    SdkInstallFinished { package: String, version: String },
+}
+
+/// Keeps a listener subscribed to the events until it is dropped
+#[must_use = "the listener is unsubscribed when the subscription is dropped"]
+pub struct Subscription {
+    ids: Vec<usize>,
+    /// The thread of a local listener, local listeners can only be removed from it
+    thread: Option<ThreadId>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut removed = 0;
+        {
+            let mut listeners = LISTENERS.write().unwrap_or_else(|err| err.into_inner());
+            let before = listeners.len();
+            listeners.retain(|(id, _)| !self.ids.contains(id));
+            removed += before - listeners.len();
+        }
+        if self.thread == Some(std::thread::current().id()) {
+            let _ = LOCAL_LISTENERS.try_with(|listeners| {
+                let mut listeners = listeners.borrow_mut();
+                let before = listeners.len();
+                listeners.retain(|(id, _)| !self.ids.contains(id));
+                removed += before - listeners.len();
+            });
+        }
+        SUBSCRIBED.fetch_sub(removed, Ordering::SeqCst);
+    }
+}
+
+/// Calls listener with every event emitted from now on, on the thread that emitted it
+pub fn subscribe(listener: impl Fn(&Event) + Send + Sync + 'static) -> Subscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    LISTENERS
+        .write()
+        .unwrap_or_else(|err| err.into_inner())
+        .push((id, Arc::new(listener)));
+    SUBSCRIBED.fetch_add(1, Ordering::SeqCst);
+    Subscription {
+        ids: vec![id],
+        thread: None,
+    }
+}
+
+/// Calls listener on the current thread with every event emitted from now on. It is
+/// for listeners that cannot move between threads such as lua functions. Events
+/// emitted on other threads, e.g. by the download workers, are delivered in order
+/// before the next event of this thread or by [`deliver_pending`].
+/// The subscription must be dropped on this thread.
+pub fn subscribe_local(listener: impl Fn(&Event) + 'static) -> Subscription {
+    let thread = std::thread::current().id();
+    let pending: Arc<Mutex<Vec<Event>>> = Arc::default();
+
+    let queue = Arc::clone(&pending);
+    let mut subscription = subscribe(move |event| {
+        if std::thread::current().id() != thread {
+            queue
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(event.clone());
+        }
+    });
+
+    let id = NEXT_ID.fetch_add(1, Ordering::SeqCst);
+    let local: LocalListener = Rc::new(move |event: Option<&Event>| {
+        let queued = std::mem::take(&mut *pending.lock().unwrap_or_else(|err| err.into_inner()));
+        for queued in &queued {
+            listener(queued);
+        }
+        if let Some(event) = event {
+            listener(event);
+        }
+    });
+    LOCAL_LISTENERS.with(|listeners| listeners.borrow_mut().push((id, local)));
+    SUBSCRIBED.fetch_add(1, Ordering::SeqCst);
+    subscription.ids.push(id);
+    subscription.thread = Some(thread);
+    subscription
+}
+
+/// Delivers the events emitted on other threads to the local listeners of this thread
+pub fn deliver_pending() {
+    dispatch_local(None);
+}
+
+fn dispatch_local(event: Option<&Event>) {
+    let listeners: Vec<LocalListener> = LOCAL_LISTENERS
+        .try_with(|listeners| {
+            listeners
+                .borrow()
+                .iter()
+                .map(|(_, listener)| Rc::clone(listener))
+                .collect()
+        })
+        .unwrap_or_default();
+    for listener in listeners {
+        listener(event);
+    }
+}
+
+/// Returns true if any listener is subscribed
+pub fn has_listeners() -> bool {
+    SUBSCRIBED.load(Ordering::SeqCst) > 0
+}
+
+/// Sets the message format for the rest of the run. In json mode
+/// progress bars are hidden so that they do not mix with the events.
+pub fn set_message_format(format: MessageFormat) {
+    let json = format == MessageFormat::Json;
+    JSON_EVENTS.store(json, Ordering::SeqCst);
+    let mut subscription = JSON_SUBSCRIPTION
+        .lock()
+        .unwrap_or_else(|err| err.into_inner());
+    if json {
+        MULTI_PROGRESS_BAR.set_draw_target(ProgressDrawTarget::hidden());
+        subscription.get_or_insert_with(|| subscribe(write_json));
+    } else {
+        subscription.take();
+    }
+}
+
+/// Returns true if events are emitted as json
+pub fn is_json() -> bool {
+    JSON_EVENTS.load(Ordering::SeqCst)
+}
+
+/// Writes the event to stdout as a single json line
+fn write_json(event: &Event) {
+    if let Ok(json) = serde_json::to_string(event) {
+        let mut out = stdout().lock();
+        let _ = writeln!(out, "{json}");
+        let _ = out.flush();
+    }
+}
+
+/// Sends the event to every listener. The json output is one of them when json
+/// messages are enabled
+pub fn emit(event: Event) {
+    if !has_listeners() {
+        return;
+    }
+    let listeners: Vec<Listener> = LISTENERS
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .iter()
+        .map(|(_, listener)| Arc::clone(listener))
+        .collect();
+    for listener in listeners {
+        listener(&event);
+    }
+    dispatch_local(Some(&event));
+}
+
+/// Reports the version selected for a dependency that was requested with different versions
+pub fn conflict_resolved(group_id: &str, artifact_id: &str, requested: &str, selected: &str) {
+    emit(Event::ConflictResolved {
+        group_id: group_id.to_string(),
+        artifact_id: artifact_id.to_string(),
+        requested: requested.to_string(),
+        selected: selected.to_string(),
+    });
+}
+
+/// Wraps a download stream emitting [`Event::DownloadProgress`] as bytes are read
+pub struct ProgressReader<R> {
+    inner: R,
+    coordinate: String,
+    total: Option<u64>,
+    downloaded: u64,
+    reported: u64,
+    /// Progress bars advanced by the bytes read
+    bars: Vec<ProgressBar>,
+}
+
+impl<R: Read> ProgressReader<R> {
+    pub fn new(inner: R, coordinate: String, total: Option<u64>) -> Self {
+        ProgressReader {
+            inner,
+            coordinate,
+            total,
+            downloaded: 0,
+            reported: 0,
+            bars: Vec::new(),
+        }
+    }
+    /// Advances the bar by the number of bytes read. If the total size is
+    /// unknown the length of the bar grows with the bytes read.
+    pub fn with_progress_bar(mut self, bar: ProgressBar) -> Self {
+        self.bars.push(bar);
+        self
+    }
+    fn report(&mut self) {
+        self.reported = self.downloaded;
+        emit(Event::DownloadProgress {
+            coordinate: self.coordinate.clone(),
+            downloaded: self.downloaded,
+            total: self.total,
+        });
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.downloaded += read as u64;
+        for bar in &self.bars {
+            if self.total.is_none() {
+                bar.inc_length(read as u64);
+            }
+            bar.inc(read as u64);
+        }
+        if has_listeners()
+            && (read == 0 || self.downloaded - self.reported >= DOWNLOAD_PROGRESS_INTERVAL)
+            && self.downloaded != self.reported
+        {
+            self.report();
+        }
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn events_serialize_as_tagged_json() {
+    let event = Event::ConflictResolved {
+        group_id: String::from("androidx.core"),
+        artifact_id: String::from("core"),
+        requested: String::from("1.9.0"),
+        selected: String::from("1.12.0"),
+    };
+    assert_eq!(
+        serde_json::to_string(&event).unwrap(),
+        r#"{"event":"conflict_resolved","group_id":"androidx.core","artifact_id":"core","requested":"1.9.0","selected":"1.12.0"}"#
+    );
+    let event = Event::DownloadProgress {
+        coordinate: String::from("androidx.core:core:1.12.0"),
+        downloaded: 10,
+        total: None,
+    };
+    assert_eq!(
+        serde_json::to_string(&event).unwrap(),
+        r#"{"event":"download_progress","coordinate":"androidx.core:core:1.12.0","downloaded":10,"total":null}"#
+    );
+}
+
+#[test]
+fn progress_reader_advances_bars() {
+    let known = ProgressBar::hidden();
+    known.set_length(5);
+    let mut reader = ProgressReader::new(&b"bytes"[..], String::from("a:b:1"), Some(5))
+        .with_progress_bar(known.clone());
+    std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+    assert_eq!(known.position(), 5);
+    assert_eq!(known.length(), Some(5));
+
+    // unknown sizes grow the bar as bytes arrive
+    let unknown = ProgressBar::hidden();
+    unknown.set_length(0);
+    let mut reader = ProgressReader::new(&b"bytes"[..], String::from("a:b:1"), None)
+        .with_progress_bar(unknown.clone());
+    std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+    assert_eq!(unknown.position(), 5);
+    assert_eq!(unknown.length(), Some(5));
+}
+
+#[test]
+fn listeners_receive_events_until_unsubscribed() {
+    let coordinate = |event: &Event| match event {
+        Event::CacheHit { coordinate } if coordinate.starts_with("listeners.test:") => {
+            Some(coordinate.clone())
+        }
+        _ => None,
+    };
+    let hit = |artifact: &str| Event::CacheHit {
+        coordinate: format!("listeners.test:{artifact}:1.0"),
+    };
+
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::clone(&received);
+    let subscription = subscribe(move |event| sink.lock().unwrap().extend(coordinate(event)));
+    let local = Rc::new(RefCell::new(Vec::new()));
+    let local_sink = Rc::clone(&local);
+    let local_subscription =
+        subscribe_local(move |event| local_sink.borrow_mut().extend(coordinate(event)));
+
+    emit(hit("a"));
+    std::thread::scope(|scope| {
+        scope.spawn(|| emit(hit("b")));
+    });
+    // events from other threads wait for this thread
+    assert_eq!(*local.borrow(), vec!["listeners.test:a:1.0"]);
+    deliver_pending();
+    let both = vec!["listeners.test:a:1.0", "listeners.test:b:1.0"];
+    assert_eq!(*local.borrow(), both);
+    assert_eq!(*received.lock().unwrap(), both);
+
+    drop(subscription);
+    drop(local_subscription);
+    emit(hit("c"));
+    assert_eq!(*local.borrow(), both);
+    assert_eq!(*received.lock().unwrap(), both);
+}
@@ -0,0 +1,473 @@
+use std::{
+    fs::{create_dir_all, read, read_to_string, write, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Mutex,
+    task::{Context as TaskContext, Poll},
+};
+
+use anyhow::Context;
+use futures_util::{Stream, StreamExt};
+use log::warn;
+use reqwest::{header::HeaderMap, Method, ResponseBuilderExt, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::session::Session;
+
+/// Header set on the response to a request that has no recording in the replay directory
+pub const REPLAY_MISSING_HEADER: &str = "x-labt-replay-missing";
+/// Response headers describing the connection rather than the response
+const HOP_BY_HOP_HEADERS: [&str; 3] = ["connection", "keep-alive", "transfer-encoding"];
+
+/// What the shared http layer does with the requests of resolvers, downloads and the sdk manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpRecording {
+    /// Sends requests and saves the responses into the directory
+    Record(PathBuf),
+    /// Answers requests with the responses saved in the directory without using the network
+    Replay(PathBuf),
+}
+
+/// Sets how requests are recorded or replayed for the rest of the run, as the
+/// global --record-http and --replay-http flags do
+pub fn set_http_recording(recording: Option<HttpRecording>) {
+    *Session::current()
+        .http_recording
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = recording;
+}
+
+/// Returns the recording mode if http traffic is being recorded or replayed
+pub fn http_recording() -> Option<HttpRecording> {
+    Session::current()
+        .http_recording
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+}
+
+/// A response saved to `<sha256 of method and url>.json` with its body in the `.body` file next to it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecordedResponse {
+    pub method: String,
+    pub url: String,
+    /// The url the response came from after redirects
+    pub final_url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+}
+
+impl RecordedResponse {
+    fn new(
+        method: &Method,
+        url: &Url,
+        final_url: &Url,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Self {
+        RecordedResponse {
+            method: method.to_string(),
+            url: url.to_string(),
+            final_url: final_url.to_string(),
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|(name, _)| !HOP_BY_HOP_HEADERS.contains(&name.as_str()))
+                .filter_map(|(name, value)| {
+                    Some((name.to_string(), value.to_str().ok()?.to_string()))
+                })
+                .collect(),
+        }
+    }
+    /// The status, url and headers of the response. None if a header is invalid
+    fn head(&self) -> Option<http::Response<()>> {
+        let mut builder = http::Response::builder().status(self.status);
+        if let Ok(url) = Url::parse(&self.final_url) {
+            builder = builder.url(url);
+        }
+        for (name, value) in &self.headers {
+            builder = builder.header(name, value);
+        }
+        builder.body(()).ok()
+    }
+    /// Builds the response with body. A response with invalid headers is a bad gateway
+    fn into_response(self, body: Vec<u8>) -> http::Response<Vec<u8>> {
+        match self.head() {
+            Some(head) => head.map(|_| body),
+            None => {
+                let mut response = http::Response::new(Vec::new());
+                *response.status_mut() = StatusCode::BAD_GATEWAY;
+                response
+            }
+        }
+    }
+}
+
+/// The recording and body files of a request in dir
+fn recording_paths(dir: &Path, method: &Method, url: &Url) -> (PathBuf, PathBuf) {
+    let mut sha = Sha256::new();
+    sha.update(format!("{method} {url}"));
+    let key = format!("{:x}", sha.finalize());
+    (
+        dir.join(format!("{key}.json")),
+        dir.join(format!("{key}.body")),
+    )
+}
+
+/// Saves the response to a request into dir
+///
+/// # Errors
+///
+/// Returns an error if the files cannot be written
+pub fn save_response(
+    dir: &Path,
+    method: &Method,
+    url: &Url,
+    response: &RecordedResponse,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    create_dir_all(dir).context(format!("Failed to create {:?}", dir))?;
+    let (json, body_path) = recording_paths(dir, method, url);
+    write(&body_path, body).context(format!("Failed to write {:?}", body_path))?;
+    let recording =
+        serde_json::to_string_pretty(response).context("Failed to serialize the response")?;
+    write(&json, recording).context(format!("Failed to write {:?}", json))
+}
+
+/// Loads the response to a request from dir. None if it was not recorded
+///
+/// # Errors
+///
+/// Returns an error if the recording cannot be read or parsed
+pub fn load_response(
+    dir: &Path,
+    method: &Method,
+    url: &Url,
+) -> anyhow::Result<Option<(RecordedResponse, Vec<u8>)>> {
+    let (json, body_path) = recording_paths(dir, method, url);
+    if !json.exists() {
+        return Ok(None);
+    }
+    let recording: RecordedResponse =
+        serde_json::from_str(&read_to_string(&json).context(format!("Failed to read {:?}", json))?)
+            .context(format!("Failed to parse {:?}", json))?;
+    let body = if body_path.exists() {
+        read(&body_path).context(format!("Failed to read {:?}", body_path))?
+    } else {
+        Vec::new()
+    };
+    Ok(Some((recording, body)))
+}
+
+/// The recorded response to a request, or a 404 marked with [`REPLAY_MISSING_HEADER`]
+/// so that a missing recording fails like a missing artifact
+fn replay(dir: &Path, method: &Method, url: &Url) -> http::Response<Vec<u8>> {
+    match load_response(dir, method, url) {
+        Ok(Some((recording, body))) => return recording.into_response(body),
+        Ok(None) => {
+            warn!(target: "http", "No recorded response for {} {} in {:?}", method, url, dir)
+        }
+        Err(err) => warn!(target: "http", "{:?}", err),
+    }
+    let mut response = http::Response::new(Vec::new());
+    *response.status_mut() = StatusCode::NOT_FOUND;
+    response.headers_mut().insert(
+        REPLAY_MISSING_HEADER,
+        http::HeaderValue::from_static("true"),
+    );
+    response
+}
+
+/// The chunks of a response body
+type BodyStream = Pin<Box<dyn Stream<Item = std::io::Result<Vec<u8>>> + Send>>;
+
+/// A response body that is copied into its `.body` file as the caller reads it.
+/// The recording is written once the whole body was read so that an interrupted
+/// download is not replayed as complete. A failure to save is only logged as the
+/// request itself succeeded
+struct RecordingBody {
+    /// Behind a mutex only so that the body is Sync, it is never contended
+    inner: Mutex<BodyStream>,
+    url: Url,
+    json: PathBuf,
+    body_path: PathBuf,
+    /// None once saving failed or finished
+    saving: Option<(File, RecordedResponse)>,
+}
+
+impl RecordingBody {
+    fn new(
+        dir: &Path,
+        method: &Method,
+        url: &Url,
+        recording: RecordedResponse,
+        inner: BodyStream,
+    ) -> Self {
+        let (json, body_path) = recording_paths(dir, method, url);
+        let file = create_dir_all(dir)
+            .context(format!("Failed to create {:?}", dir))
+            .and_then(|_| {
+                File::create(&body_path).context(format!("Failed to create {:?}", body_path))
+            });
+        let saving = match file {
+            Ok(file) => Some((file, recording)),
+            Err(err) => {
+                warn!(target: "http", "Failed to record the response of {}: {:?}", url, err);
+                None
+            }
+        };
+        RecordingBody {
+            inner: Mutex::new(inner),
+            url: url.clone(),
+            json,
+            body_path,
+            saving,
+        }
+    }
+    fn save_chunk(&mut self, chunk: &[u8]) {
+        let Some((file, _)) = &mut self.saving else {
+            return;
+        };
+        if let Err(err) = file.write_all(chunk) {
+            warn!(target: "http", "Failed to record the response of {}: {:?}", self.url, err);
+            self.saving = None;
+        }
+    }
+    fn finish(&mut self) {
+        let Some((_, recording)) = self.saving.take() else {
+            return;
+        };
+        let saved = serde_json::to_string_pretty(&recording)
+            .context("Failed to serialize the response")
+            .and_then(|json| {
+                write(&self.json, json).context(format!("Failed to write {:?}", self.json))
+            });
+        if let Err(err) = saved {
+            warn!(target: "http", "Failed to record the response of {}: {:?}", self.url, err);
+        }
+    }
+}
+
+impl Stream for RecordingBody {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let inner = this.inner.get_mut().unwrap_or_else(|err| err.into_inner());
+        let next = inner.as_mut().poll_next(cx);
+        match &next {
+            Poll::Ready(Some(Ok(chunk))) => this.save_chunk(chunk),
+            Poll::Ready(None) => this.finish(),
+            Poll::Ready(Some(Err(_))) => {
+                // an incomplete body is not kept
+                this.saving = None;
+                let _ = std::fs::remove_file(&this.body_path);
+            }
+            Poll::Pending => {}
+        }
+        next
+    }
+}
+
+/// Passes the body of a response through to the caller while saving it into dir
+fn record(
+    dir: &Path,
+    method: &Method,
+    url: &Url,
+    recording: RecordedResponse,
+    body: BodyStream,
+) -> http::Response<reqwest::Body> {
+    let Some(head) = recording.head() else {
+        return recording.into_response(Vec::new()).map(reqwest::Body::from);
+    };
+    let body = RecordingBody::new(dir, method, url, recording, body);
+    head.map(|_| reqwest::Body::wrap_stream(body))
+}
+
+/// The chunks read from a blocking response
+fn read_chunks(mut response: reqwest::blocking::Response) -> BodyStream {
+    let chunks = std::iter::from_fn(move || {
+        let mut chunk = vec![0; 64 * 1024];
+        match response.read(&mut chunk) {
+            Ok(0) => None,
+            Ok(read) => {
+                chunk.truncate(read);
+                Some(Ok(chunk))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    });
+    Box::pin(futures_util::stream::iter(chunks))
+}
+
+/// Sending requests through the http recording set with [`set_http_recording`].
+/// Without one the request is sent as usual
+pub trait SendRecorded {
+    type Response;
+    fn send_recorded(self) -> Self::Response;
+}
+
+impl SendRecorded for reqwest::blocking::RequestBuilder {
+    type Response = reqwest::Result<reqwest::blocking::Response>;
+
+    fn send_recorded(self) -> Self::Response {
+        match http_recording() {
+            None => self.send(),
+            Some(HttpRecording::Replay(dir)) => {
+                let request = self.build()?;
+                Ok(replay(&dir, request.method(), request.url()).into())
+            }
+            Some(HttpRecording::Record(dir)) => {
+                // requests with a streamed body cannot be cloned so they are not recorded
+                let Some(Ok(request)) = self.try_clone().map(|builder| builder.build()) else {
+                    return self.send();
+                };
+                let response = self.send()?;
+                let recording = RecordedResponse::new(
+                    request.method(),
+                    request.url(),
+                    response.url(),
+                    response.status(),
+                    response.headers(),
+                );
+                Ok(record(
+                    &dir,
+                    request.method(),
+                    request.url(),
+                    recording,
+                    read_chunks(response),
+                )
+                .into())
+            }
+        }
+    }
+}
+
+impl SendRecorded for reqwest::RequestBuilder {
+    type Response = std::pin::Pin<
+        Box<dyn std::future::Future<Output = reqwest::Result<reqwest::Response>> + Send>,
+    >;
+
+    fn send_recorded(self) -> Self::Response {
+        Box::pin(async move {
+            match http_recording() {
+                None => self.send().await,
+                Some(HttpRecording::Replay(dir)) => {
+                    let request = self.build()?;
+                    Ok(replay(&dir, request.method(), request.url()).into())
+                }
+                Some(HttpRecording::Record(dir)) => {
+                    let Some(Ok(request)) = self.try_clone().map(|builder| builder.build()) else {
+                        return self.send().await;
+                    };
+                    let response = self.send().await?;
+                    let recording = RecordedResponse::new(
+                        request.method(),
+                        request.url(),
+                        response.url(),
+                        response.status(),
+                        response.headers(),
+                    );
+                    let body = response.bytes_stream().map(|chunk| {
+                        chunk
+                            .map(|bytes| bytes.to_vec())
+                            .map_err(std::io::Error::other)
+                    });
+                    Ok(record(
+                        &dir,
+                        request.method(),
+                        request.url(),
+                        recording,
+                        Box::pin(body),
+                    )
+                    .into())
+                }
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn recorded_responses_replay_without_network() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("http-recording");
+    let url =
+        Url::parse("https://repo.example.com/maven2/com/example/lib/1.0/lib-1.0.pom").unwrap();
+    let redirected = Url::parse("https://cdn.example.com/lib-1.0.pom").unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert("etag", http::HeaderValue::from_static("\"5f2b\""));
+    headers.insert(
+        "transfer-encoding",
+        http::HeaderValue::from_static("chunked"),
+    );
+    let recording =
+        RecordedResponse::new(&Method::GET, &url, &redirected, StatusCode::OK, &headers);
+    assert_eq!(
+        recording.headers,
+        vec![(String::from("etag"), String::from("\"5f2b\""))]
+    );
+    save_response(&dir, &Method::GET, &url, &recording, b"<project/>").unwrap();
+    assert_eq!(
+        load_response(&dir, &Method::GET, &url).unwrap(),
+        Some((recording, b"<project/>".to_vec()))
+    );
+    // a HEAD is a different request
+    assert_eq!(load_response(&dir, &Method::HEAD, &url).unwrap(), None);
+
+    // the global recording is not set so that other tests keep using the network layer
+    let response = reqwest::blocking::Response::from(replay(&dir, &Method::GET, &url));
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.url(), &redirected);
+    assert_eq!(response.headers()["etag"], "\"5f2b\"");
+    assert_eq!(response.text().unwrap(), "<project/>");
+    let missing = reqwest::blocking::Response::from(replay(&dir, &Method::HEAD, &url));
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    assert!(missing.headers().contains_key(REPLAY_MISSING_HEADER));
+}
+
+#[test]
+fn recorded_bodies_are_streamed() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("http-recording");
+    let url = Url::parse("https://dl.example.com/platform-34.zip").unwrap();
+    let recording =
+        RecordedResponse::new(&Method::GET, &url, &url, StatusCode::OK, &HeaderMap::new());
+    let chunks: Vec<std::io::Result<Vec<u8>>> = vec![Ok(b"PK".to_vec()), Ok(b"\x03\x04".to_vec())];
+    let response = record(
+        &dir,
+        &Method::GET,
+        &url,
+        recording.clone(),
+        Box::pin(futures_util::stream::iter(chunks)),
+    );
+    // nothing is recorded until the caller has read the whole body
+    assert_eq!(load_response(&dir, &Method::GET, &url).unwrap(), None);
+    let response = reqwest::blocking::Response::from(response);
+    assert_eq!(response.bytes().unwrap().as_ref(), b"PK\x03\x04");
+    assert_eq!(
+        load_response(&dir, &Method::GET, &url).unwrap(),
+        Some((recording.clone(), b"PK\x03\x04".to_vec()))
+    );
+
+    // a failed download is not recorded
+    let failed = Url::parse("https://dl.example.com/platform-35.zip").unwrap();
+    let chunks: Vec<std::io::Result<Vec<u8>>> = vec![
+        Ok(b"PK".to_vec()),
+        Err(std::io::Error::other("connection reset")),
+    ];
+    let response = record(
+        &dir,
+        &Method::GET,
+        &failed,
+        recording,
+        Box::pin(futures_util::stream::iter(chunks)),
+    );
+    assert!(reqwest::blocking::Response::from(response).bytes().is_err());
+    assert_eq!(load_response(&dir, &Method::GET, &failed).unwrap(), None);
+    assert!(!recording_paths(&dir, &Method::GET, &failed).1.exists());
+}
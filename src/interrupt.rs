@@ -0,0 +1,171 @@
+use std::{
+    collections::HashMap,
+    fs::remove_file,
+    io::{Read, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use lazy_static::lazy_static;
+use log::warn;
+
+use crate::MULTI_PROGRESS_BAR;
+
+/// Exit code used when labt is stopped by Ctrl-C (128 + SIGINT)
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+lazy_static! {
+    /// Cleared once the user requests cancellation
+    static ref RUNNING: Arc<AtomicBool> = Arc::new(AtomicBool::new(true));
+    /// Files currently being written that should not survive an interruption
+    static ref PARTIAL_FILES: Mutex<HashMap<usize, PathBuf>> = Mutex::new(HashMap::new());
+}
+static NEXT_PARTIAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returned when an operation stops early because the user pressed Ctrl-C
+#[derive(thiserror::Error, Debug)]
+#[error("Interrupted")]
+pub struct Interrupted;
+
+/// Installs the process wide Ctrl-C handler.
+/// The first Ctrl-C requests cancellation and lets the running task stop
+/// at its next checkpoint. A second Ctrl-C removes partial files and exits immediately.
+///
+/// # Errors
+///
+/// Returns an error if a handler was already installed
+pub fn install_handler() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if RUNNING.swap(false, Ordering::SeqCst) {
+            warn!(target: "labt", "Interrupted, cleaning up. Press Ctrl-C again to force exit.");
+        } else {
+            remove_partial_files();
+            let _ = MULTI_PROGRESS_BAR.clear();
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })?;
+    Ok(())
+}
+
+/// Returns the shared running flag. It is set to false once an
+/// interruption is requested.
+pub fn running() -> Arc<AtomicBool> {
+    Arc::clone(&RUNNING)
+}
+
+/// Returns true if the user requested cancellation
+pub fn is_interrupted() -> bool {
+    !RUNNING.load(Ordering::SeqCst)
+}
+
+/// Cancellation checkpoint for long running loops
+///
+/// # Errors
+///
+/// Returns [`Interrupted`] if cancellation was requested
+pub fn check() -> anyhow::Result<()> {
+    if is_interrupted() {
+        return Err(Interrupted.into());
+    }
+    Ok(())
+}
+
+/// Removes every file still registered as partial
+pub fn remove_partial_files() {
+    let files = match PARTIAL_FILES.lock() {
+        Ok(mut files) => std::mem::take(&mut *files),
+        Err(_) => return,
+    };
+    for path in files.values() {
+        let _ = remove_file(path);
+    }
+}
+
+/// Copies reader into writer, checking for cancellation between chunks.
+///
+/// # Errors
+///
+/// Returns an IO error wrapping [`Interrupted`] if cancellation was requested
+pub fn copy<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> std::io::Result<u64> {
+    const BUFFER_LENGTH: usize = 8 * 1024;
+    let mut buf = [0u8; BUFFER_LENGTH];
+    let mut total: u64 = 0;
+    loop {
+        if is_interrupted() {
+            return Err(std::io::Error::other(Interrupted));
+        }
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        writer.write_all(&buf[..read])?;
+        total += read as u64;
+    }
+    Ok(total)
+}
+
+/// Tracks a file while it is being written. If the file is dropped before it is
+/// marked complete, because writing it failed or labt was interrupted, the file
+/// is removed so that no truncated file is left behind in the cache.
+#[derive(Debug)]
+pub struct PartialFile {
+    id: usize,
+    path: PathBuf,
+    complete: bool,
+}
+
+impl PartialFile {
+    pub fn new(path: PathBuf) -> Self {
+        let id = NEXT_PARTIAL_ID.fetch_add(1, Ordering::SeqCst);
+        if let Ok(mut files) = PARTIAL_FILES.lock() {
+            files.insert(id, path.clone());
+        }
+        PartialFile {
+            id,
+            path,
+            complete: false,
+        }
+    }
+    /// Marks the file as fully written
+    pub fn complete(&mut self) {
+        self.complete = true;
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if let Ok(mut files) = PARTIAL_FILES.lock() {
+            files.remove(&self.id);
+        }
+        if !self.complete {
+            let _ = remove_file(&self.path);
+        }
+    }
+}
+
+#[test]
+fn interrupt_copy_and_partial_file() {
+    let mut out = Vec::new();
+    assert_eq!(copy(&mut "labt".as_bytes(), &mut out).unwrap(), 4);
+    assert_eq!(out, b"labt");
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("partial");
+    std::fs::write(&path, b"partial").unwrap();
+    let partial = PartialFile::new(path.clone());
+    let id = partial.id;
+    assert!(PARTIAL_FILES.lock().unwrap().contains_key(&id));
+    // a file that failed to be written is removed
+    drop(partial);
+    assert!(!path.exists());
+    assert!(!PARTIAL_FILES.lock().unwrap().contains_key(&id));
+
+    std::fs::write(&path, b"complete").unwrap();
+    let mut partial = PartialFile::new(path.clone());
+    partial.complete();
+    drop(partial);
+    assert!(path.exists());
+}
@@ -0,0 +1,228 @@
+//! LABt as a library. The labt binary is a thin command line over these modules
+//! and other tools such as IDE plugins can embed the same functionality:
+//!
+//! - [`config`] reads and writes Labt.toml, Labt.lock and the user wide config.toml
+//! - [`submodules::resolve`] resolves dependency graphs and [`caching`] stores
+//!   artifacts in the labt cache
+//! - [`plugin`] loads and runs build plugins
+//!
+//! The labt home, the project manifest and the user wide config are discovered on
+//! each call unless they are selected with [`set_home`], [`set_manifest_path`] and
+//! [`config::global::set_global_config`], and they can be changed again at any time.
+//! They are kept in the current [`session::Session`] with offline mode and the http
+//! recording, so an embedder handling several projects at once runs each in its
+//! own session. The JDK of a build is only passed to the processes its plugins start.
+//!
+//! What labt prints is shared by the whole process: progress is drawn on
+//! [`MULTI_PROGRESS_BAR`] and logged with the `log` crate, so embedders install
+//! their own logger. The json output mode of [`output`], the [`events`] listeners,
+//! the [`metrics`] and the interrupt flag of [`interrupt`] are process wide too.
+
+use std::{
+    env::current_dir,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+};
+
+use anyhow::bail;
+use indicatif::MultiProgress;
+use lazy_static::lazy_static;
+use log::warn;
+use session::Session;
+
+pub mod archive;
+pub mod caching;
+pub mod config;
+pub mod events;
+pub mod http_recording;
+pub mod interrupt;
+pub mod metrics;
+pub mod output;
+pub mod paths;
+pub mod plugin;
+pub mod pom;
+pub mod session;
+pub mod submodules;
+pub(crate) mod tui;
+pub mod version;
+
+lazy_static! {
+    pub static ref MULTI_PROGRESS_BAR: Arc<MultiProgress> = Arc::new(MultiProgress::new());
+}
+
+pub const LABT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const USER_AGENT: &str = concat!("Labt/", env!("CARGO_PKG_VERSION"));
+pub const TARGET: &str = env!("TARGET");
+
+pub mod envs {
+    pub const LABT_HOME: &str = "LABT_HOME";
+    /// Enables the build cache in this directory
+    pub const LABT_BUILD_CACHE: &str = "LABT_BUILD_CACHE";
+    pub const HOME: &str = "HOME";
+    /// The android sdk user directory, ~/.android by default
+    pub const ANDROID_USER_HOME: &str = "ANDROID_USER_HOME";
+    pub const JAVA_HOME: &str = "JAVA_HOME";
+    pub const LOCALAPPDATA: &str = "LOCALAPPDATA";
+    pub const USERPROFILE: &str = "USERPROFILE";
+}
+
+/// Sets offline mode for the rest of the run. Offline, dependencies are only
+/// resolved from the labt cache and Labt.lock
+pub fn set_offline(offline: bool) {
+    Session::current().offline.store(offline, Ordering::SeqCst);
+}
+
+/// Returns true if labt should not access the network
+pub fn is_offline() -> bool {
+    Session::current().offline.load(Ordering::SeqCst)
+}
+
+/// Uses path as labt home instead of discovering it from LABT_HOME and the user
+/// home directory. None goes back to discovering it
+pub fn set_home(path: Option<PathBuf>) {
+    *Session::current()
+        .home
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = path;
+}
+
+/// Returns the location of Labt home, this is where Labt stores its
+/// configurations files, plugins and cache. Uses the one set by [`set_home`] or
+/// checks if LABT_HOME was set. If not set, falls back to $HOME/.labt on linux,
+/// or %LOCALAPPDATA%/.labt on windows.
+///
+/// # Errors
+///
+/// This function will return an error if no suitable path is found for labt home.
+pub fn get_home() -> anyhow::Result<PathBuf> {
+    if let Some(path) = Session::current()
+        .home
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+    {
+        return Ok(path.clone());
+    }
+
+    if let Ok(path) = std::env::var(envs::LABT_HOME) {
+        return Ok(PathBuf::from(path));
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    let (home, missing) = (envs::HOME, ".labt folder does not exist on $HOME");
+    #[cfg(target_os = "windows")]
+    let (home, missing) = (
+        envs::LOCALAPPDATA,
+        ".labt folder does not exist on %LOCALAPPDATA%",
+    );
+    if let Ok(path) = std::env::var(home) {
+        let path = PathBuf::from(path).join(".labt");
+        if path.exists() {
+            return Ok(path);
+        } else {
+            bail!(std::io::Error::new(std::io::ErrorKind::NotFound, missing));
+        }
+    }
+
+    bail!("No apropriate Labt home directory detected!");
+}
+
+/// Selects the manifest of the project instead of discovering it from the
+/// current working directory. The manifest can have any file name. None goes back
+/// to discovering the project. The properties of the previous project are forgotten
+pub fn set_manifest_path(path: Option<PathBuf>) {
+    let path = path.map(|path| std::path::absolute(&path).unwrap_or(path));
+    *Session::current()
+        .manifest_path
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = path;
+    config::properties::set_properties(None);
+}
+
+/// Returns the manifest of the project, the one set by [`set_manifest_path`] or
+/// the Labt.toml of the project discovered from the current working directory
+///
+/// # Errors
+///
+/// Returns an error if no project is found
+pub fn get_manifest_path() -> std::io::Result<PathBuf> {
+    if let Some(path) = Session::current()
+        .manifest_path
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .as_ref()
+    {
+        return Ok(path.clone());
+    }
+    let root = find_project_root(&current_dir()?)?;
+    Ok(root.join(config::LABT_TOML_FILE_NAME))
+}
+
+/// Returns the project root folder, the directory of the manifest set by
+/// [`set_manifest_path`] or the closest directory with a Labt.toml going up the
+/// directory tree from the current working directory
+///
+/// # Errors
+///
+/// Returns an error if the current working directory cannot be read or Labt.toml
+/// is never found
+pub fn get_project_root() -> std::io::Result<PathBuf> {
+    let manifest = get_manifest_path()?;
+    match manifest.parent() {
+        Some(root) => Ok(root.to_path_buf()),
+        None => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Manifest {:?} has no parent directory", manifest),
+        )),
+    }
+}
+
+/// Searches for the closest directory with a Labt.toml starting from start and
+/// going up the directory tree. Warns once if the project is nested in another
+/// project as commands run inside it no longer apply to the outer project
+///
+/// # Errors
+///
+/// Returns an error if Labt.toml is never found
+pub fn find_project_root(start: &Path) -> std::io::Result<PathBuf> {
+    let mut ancestors = start
+        .ancestors()
+        .filter(|dir| dir.join(config::LABT_TOML_FILE_NAME).is_file());
+    let Some(root) = ancestors.next() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "Failed to get project root",
+        ));
+    };
+    if let Some(outer) = ancestors.next() {
+        let session = Session::current();
+        let mut warned = session
+            .nested_warned
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if !warned.iter().any(|warned| warned == root) {
+            warned.push(root.to_path_buf());
+            warn!(target: "labt", "The project at {:?} is nested in the project at {:?}. Using {:?}, pass --manifest-path to select another project", root, outer, root);
+        }
+    }
+    Ok(root.to_path_buf())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn find_project_root_uses_closest_manifest() {
+    let tmp = tempfile::tempdir().unwrap();
+    let outer = tmp.path().join("nested");
+    let inner = outer.join("libs").join("core");
+    std::fs::create_dir_all(inner.join("src")).unwrap();
+    std::fs::write(outer.join(config::LABT_TOML_FILE_NAME), "").unwrap();
+    std::fs::write(inner.join(config::LABT_TOML_FILE_NAME), "").unwrap();
+
+    let from_inner = find_project_root(&inner.join("src"));
+    let from_outer = find_project_root(&outer.join("libs"));
+
+    assert_eq!(from_inner.unwrap(), inner);
+    assert_eq!(from_outer.unwrap(), outer);
+}
@@ -0,0 +1,159 @@
+//! Time spent in each phase of a command. Resolution and the build steps are
+//! timed from their [`Event`]s and the network phases are timed where their
+//! requests are sent. Phases are only timed between [`record_metrics`] and
+//! [`take_metrics`] so that embedders that do not read them pay nothing.
+
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::events::{subscribe, Event, Subscription};
+
+/// Resolution of the project dependencies, from [`Event::ResolutionFinished`]
+pub const RESOLVE: &str = "resolve";
+/// The requests for poms and maven-metadata.xml files
+pub const METADATA: &str = "metadata";
+/// The downloads of dependency artifacts
+pub const DOWNLOADS: &str = "downloads";
+/// The whole of labt build. Its steps are timed under their own names
+pub const BUILD: &str = "build";
+
+/// The total time spent in a phase. Spans of a phase that overlap, such as the
+/// downloads running on several threads at once, count the wall-clock time from
+/// the first start to the last end once instead of adding up every thread
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseMetric {
+    /// The number of times the phase was timed, e.g. one per request
+    pub count: u64,
+    pub elapsed_ms: u64,
+}
+
+/// The phases timed since [`record_metrics`]
+#[derive(Default)]
+struct Recording {
+    phases: BTreeMap<String, PhaseMetric>,
+    /// The number of open spans of each phase and when the first of them started
+    open: BTreeMap<String, (usize, Instant)>,
+}
+
+/// The phases timed since [`record_metrics`], None when not recording
+static METRICS: Mutex<Option<Recording>> = Mutex::new(None);
+
+/// Starts timing phases, dropping those of an earlier run. Resolution and the build
+/// steps are timed for as long as the returned subscription is kept
+pub fn record_metrics() -> Subscription {
+    *METRICS.lock().unwrap_or_else(|err| err.into_inner()) = Some(Recording::default());
+    subscribe(|event| match event {
+        Event::ResolutionFinished { elapsed_ms, .. } => {
+            record(RESOLVE, Duration::from_millis(*elapsed_ms))
+        }
+        Event::StepFinished {
+            step, elapsed_ms, ..
+        } => record(step, Duration::from_millis(*elapsed_ms)),
+        _ => {}
+    })
+}
+
+/// Adds elapsed to the phase. Does nothing when not recording. Only for phases
+/// that never run at once, the others are timed with [`span`]
+pub fn record(phase: &str, elapsed: Duration) {
+    let mut metrics = METRICS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(metrics) = metrics.as_mut() {
+        let metric = metrics.phases.entry(phase.to_string()).or_default();
+        metric.count += 1;
+        metric.elapsed_ms += elapsed.as_millis() as u64;
+    }
+}
+
+/// Times the phase until the returned span is dropped. While other spans of the
+/// phase are open, e.g. on the other download threads, the time is only added
+/// once the last of them ends, so that the phase gets the wall-clock time
+pub fn span(phase: &str) -> Span {
+    let mut metrics = METRICS.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(metrics) = metrics.as_mut() {
+        metrics.phases.entry(phase.to_string()).or_default().count += 1;
+        metrics
+            .open
+            .entry(phase.to_string())
+            .or_insert_with(|| (0, Instant::now()))
+            .0 += 1;
+    }
+    Span {
+        phase: phase.to_string(),
+    }
+}
+
+/// An open span of a phase, see [`span`]
+pub struct Span {
+    phase: String,
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let mut metrics = METRICS.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(metrics) = metrics.as_mut() else {
+            return;
+        };
+        // the span may have been opened before recording started
+        let Some((open, started)) = metrics.open.get_mut(&self.phase) else {
+            return;
+        };
+        *open -= 1;
+        if *open == 0 {
+            let elapsed = started.elapsed();
+            metrics.open.remove(&self.phase);
+            metrics
+                .phases
+                .entry(self.phase.clone())
+                .or_default()
+                .elapsed_ms += elapsed.as_millis() as u64;
+        }
+    }
+}
+
+/// Runs f within a [`span`] of the phase
+pub fn timed<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+    let _span = span(phase);
+    f()
+}
+
+/// Stops timing phases and returns those timed since [`record_metrics`]
+pub fn take_metrics() -> BTreeMap<String, PhaseMetric> {
+    METRICS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take()
+        .map(|recording| recording.phases)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn overlapping_spans_count_wall_clock_time() {
+    let _recording = record_metrics();
+    let started = Instant::now();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                timed("overlapping", || {
+                    std::thread::sleep(Duration::from_millis(100))
+                })
+            });
+        }
+    });
+    let wall = started.elapsed().as_millis() as u64;
+    let metrics = take_metrics();
+    let downloads = metrics.get("overlapping").unwrap();
+    assert_eq!(downloads.count, 4);
+    assert!(downloads.elapsed_ms >= 100, "{downloads:?}");
+    assert!(
+        downloads.elapsed_ms <= wall,
+        "{downloads:?} took longer than {wall}ms"
+    );
+}
@@ -0,0 +1,43 @@
+use std::{
+    io::{stdout, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// Set by the global --output flag
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// The format in which commands print their results
+#[derive(Clone, Copy, ValueEnum, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Text meant to be read in a terminal
+    #[default]
+    Human,
+    /// A single json document on stdout. Progress and logs are written to stderr
+    Json,
+}
+
+/// Sets the output format for the rest of the run
+pub fn set_output_format(format: OutputFormat) {
+    JSON_OUTPUT.store(format == OutputFormat::Json, Ordering::SeqCst);
+}
+
+/// Returns true if commands should print their results as json
+pub fn is_json_output() -> bool {
+    JSON_OUTPUT.load(Ordering::SeqCst)
+}
+
+/// Writes value to stdout as pretty printed json
+///
+/// # Errors
+///
+/// Returns an error if value cannot be serialized or stdout is closed
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    let json = serde_json::to_string_pretty(value).context("Failed to serialize output to json")?;
+    let mut out = stdout().lock();
+    writeln!(out, "{json}").context("Failed to write to stdout")?;
+    out.flush().context("Failed to write to stdout")
+}
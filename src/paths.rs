@@ -0,0 +1,139 @@
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+
+/// Characters windows does not allow in file names
+const INVALID_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names windows reserves in every directory, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// The longest path the win32 api accepts without the verbatim prefix
+pub const MAX_PATH: usize = 259;
+
+/// Prefix that turns off win32 path parsing and its MAX_PATH limit
+pub const VERBATIM_PREFIX: &str = r"\\?\";
+
+/// Makes name usable as a single path component on every platform. Versions and
+/// ids come from poms and repositories and may contain characters such as : or /
+/// that windows rejects or that would add directories. Invalid and control
+/// characters, and trailing dots and spaces are replaced with _ and reserved
+/// device names are prefixed with _. Names that are already valid are borrowed.
+pub fn sanitize_component(name: &str) -> Cow<'_, str> {
+    let trailing = name.len() - name.trim_end_matches(['.', ' ']).len();
+    let stem = name.split('.').next().unwrap_or_default();
+    let reserved = RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem.trim_end()));
+    let invalid = |c: char| INVALID_CHARS.contains(&c) || c.is_control();
+
+    if !name.is_empty() && trailing == 0 && !reserved && !name.contains(invalid) {
+        return Cow::Borrowed(name);
+    }
+
+    let mut sanitized = String::with_capacity(name.len() + 1);
+    if reserved {
+        sanitized.push('_');
+    }
+    let kept = name.len() - trailing;
+    for c in name[..kept].chars() {
+        sanitized.push(if invalid(c) { '_' } else { c });
+    }
+    sanitized.push_str(&"_".repeat(trailing));
+    if sanitized.is_empty() {
+        sanitized.push('_');
+    }
+    Cow::Owned(sanitized)
+}
+
+/// Returns the verbatim form of an absolute windows path longer than MAX_PATH
+/// e.g. C:\very\long\path becomes \\?\C:\very\long\path and \\server\share\path
+/// becomes \\?\UNC\server\share\path. Verbatim paths are not normalized by
+/// windows so / is replaced with \ and . and .. are resolved here.
+/// Returns None for short, relative and already verbatim paths.
+pub fn verbatim(path: &str) -> Option<String> {
+    if path.len() <= MAX_PATH || path.starts_with(VERBATIM_PREFIX) || path.starts_with(r"\\.\") {
+        return None;
+    }
+    let path = path.replace('/', "\\");
+    let (prefix, rest) = if let Some(unc) = path.strip_prefix(r"\\") {
+        ("UNC\\", unc)
+    } else {
+        let bytes = path.as_bytes();
+        if bytes.len() < 3 || !bytes[0].is_ascii_alphabetic() || &bytes[1..3] != b":\\" {
+            return None;
+        }
+        ("", path.as_str())
+    };
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in rest.split('\\') {
+        match component {
+            "" | "." => {}
+            // the drive or the server and share cannot be left
+            ".." if components.len() > if prefix.is_empty() { 1 } else { 2 } => {
+                components.pop();
+            }
+            ".." => {}
+            component => components.push(component),
+        }
+    }
+    let mut verbatim = format!("{VERBATIM_PREFIX}{prefix}{}", components.join("\\"));
+    if components.len() == 1 {
+        // the root of a drive
+        verbatim.push('\\');
+    }
+    Some(verbatim)
+}
+
+/// Returns path in a form that can be opened even when it is longer than
+/// MAX_PATH. The rust standard library does this itself but libgit2, the C
+/// runtime used by lua and external tools do not, so paths under labt home that
+/// are handed to them should go through this. Does nothing on other platforms.
+pub fn long_path(path: &Path) -> PathBuf {
+    #[cfg(target_os = "windows")]
+    if let Some(verbatim) = verbatim(&path.to_string_lossy()) {
+        return PathBuf::from(verbatim);
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn sanitize_component_replaces_windows_invalid_names() {
+    assert_eq!(sanitize_component("1.2.0"), "1.2.0");
+    assert!(matches!(sanitize_component("core-ktx"), Cow::Borrowed(_)));
+    assert_eq!(sanitize_component("1.0:beta"), "1.0_beta");
+    assert_eq!(sanitize_component("[1.0,2.0)"), "[1.0,2.0)");
+    assert_eq!(sanitize_component("../../etc"), ".._.._etc");
+    assert_eq!(sanitize_component("a\tb|c?"), "a_b_c_");
+    assert_eq!(sanitize_component("1.0."), "1.0_");
+    assert_eq!(sanitize_component(".."), "__");
+    assert_eq!(sanitize_component(""), "_");
+    assert_eq!(sanitize_component("con"), "_con");
+    assert_eq!(sanitize_component("Nul.txt"), "_Nul.txt");
+    assert_eq!(sanitize_component("console"), "console");
+}
+
+#[test]
+fn verbatim_prefixes_long_windows_paths() {
+    let long = "a".repeat(MAX_PATH);
+    assert_eq!(verbatim(r"C:\Users\labt\.labt\cache"), None);
+    assert_eq!(
+        verbatim(&format!(r"C:\Users\labt/.labt\.\cache\..\sdk\{long}")),
+        Some(format!(r"\\?\C:\Users\labt\.labt\sdk\{long}"))
+    );
+    assert_eq!(
+        verbatim(&format!(r"\\server\share\..\{long}")),
+        Some(format!(r"\\?\UNC\server\share\{long}"))
+    );
+    assert_eq!(verbatim(&format!(r"\\?\C:\{long}")), None);
+    assert_eq!(verbatim(&format!(r"relative\{long}")), None);
+    assert_eq!(verbatim(&format!("/home/labt/{long}")), None);
+}
@@ -0,0 +1,346 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    process::Command,
+    time::Duration,
+};
+
+use anyhow::{bail, Context};
+use labt_proc_macro::labt_lua;
+use log::{info, warn};
+use mlua::{Lua, LuaSerdeExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    get_project_root,
+    plugin::{
+        instrumentation::{InstrumentationParser, InstrumentationResults},
+        process,
+        report::{TestCase, TestCounts, TestStatus},
+    },
+    submodules::{
+        sdk::get_sdk_path,
+        toolchains::{find_in_dir, find_in_path},
+    },
+};
+
+use super::MluaAnyhowWrapper;
+
+/// The sdk package containing adb
+const PLATFORM_TOOLS: &str = "platform-tools";
+/// Directory in the build directory the test reports are written to
+const INSTRUMENT_RESULTS_DIR: &str = "test-results/instrument";
+
+/// A device as listed by adb devices
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub serial: String,
+    /// device, offline, unauthorized etc.
+    pub state: String,
+}
+
+/// Options of adb.instrument
+#[derive(Deserialize, Debug, Default)]
+struct InstrumentOptions {
+    /// The device to run on. Needed if more than one is connected
+    serial: Option<String>,
+    /// Passed to the runner as -e key value
+    #[serde(default)]
+    args: BTreeMap<String, String>,
+}
+
+/// Options of adb.test
+#[derive(Deserialize, Debug)]
+struct TestOptions {
+    /// The app apk. Not needed for self instrumenting test apks
+    app: Option<String>,
+    /// The test apk
+    test: String,
+    /// The instrumentation e.g. com.example.test/androidx.test.runner.AndroidJUnitRunner
+    runner: String,
+    #[serde(flatten)]
+    instrument: InstrumentOptions,
+}
+
+/// Summary of an instrumentation run returned to lua
+#[derive(Serialize, Debug)]
+struct InstrumentSummary {
+    #[serde(flatten)]
+    counts: TestCounts,
+    /// Set if the instrumentation did not run to completion
+    crash: Option<String>,
+    /// The JUnit XML report
+    report: PathBuf,
+    /// The HTML summary
+    html: PathBuf,
+    success: bool,
+}
+
+fn adb_names() -> Vec<String> {
+    if cfg!(target_os = "windows") {
+        vec![String::from("adb.exe")]
+    } else {
+        vec![String::from("adb")]
+    }
+}
+
+/// Locates adb in the labt sdk, then ANDROID_HOME or ANDROID_SDK_ROOT and then the PATH
+///
+/// # Errors
+///
+/// Returns an error if adb is not found
+pub fn locate_adb() -> anyhow::Result<PathBuf> {
+    let names = adb_names();
+    let mut sdks: Vec<PathBuf> = get_sdk_path().into_iter().collect();
+    sdks.extend(
+        ["ANDROID_HOME", "ANDROID_SDK_ROOT"]
+            .iter()
+            .filter_map(|variable| std::env::var_os(variable).map(PathBuf::from)),
+    );
+    sdks.iter()
+        .find_map(|sdk| find_in_dir(&sdk.join(PLATFORM_TOOLS), &names))
+        .or_else(|| find_in_path(&names))
+        .context("adb not found. Install platform-tools with labt sdk or add adb to the PATH")
+}
+
+/// Parses the output of adb devices
+pub fn parse_devices(output: &str) -> Vec<Device> {
+    output
+        .lines()
+        .skip_while(|line| !line.starts_with("List of devices"))
+        .skip(1)
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some(Device {
+                serial: parts.next()?.to_string(),
+                state: parts.next()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+fn adb_command(serial: Option<&str>) -> mlua::Result<Command> {
+    let mut cmd = Command::new(locate_adb().map_err(MluaAnyhowWrapper::external)?);
+    if let Some(serial) = serial {
+        cmd.arg("-s").arg(serial);
+    }
+    Ok(cmd)
+}
+
+fn install_apk(lua: &Lua, apk: &str, serial: Option<&str>) -> mlua::Result<()> {
+    let apk = match get_project_root() {
+        Ok(root) => root.join(apk),
+        Err(_) => PathBuf::from(apk),
+    };
+    if !apk.exists() {
+        return Err(MluaAnyhowWrapper::external(anyhow::anyhow!(
+            "Apk {:?} does not exist",
+            apk
+        )));
+    }
+    let mut cmd = adb_command(serial)?;
+    cmd.args(["install", "-r", "-t"]).arg(&apk);
+    let status = process::run(lua, &mut cmd)?;
+    if !status.success() {
+        return Err(MluaAnyhowWrapper::external(anyhow::anyhow!(
+            "adb failed to install {:?}",
+            apk
+        )));
+    }
+    Ok(())
+}
+
+/// Writes the reports of the run to build/test-results/instrument/<package>
+fn write_report(
+    results: &InstrumentationResults,
+    package: &str,
+) -> anyhow::Result<(PathBuf, PathBuf)> {
+    let dir = get_project_root()
+        .context("Failed to get project root directory")?
+        .join("build")
+        .join(INSTRUMENT_RESULTS_DIR)
+        .join(package);
+    let mut report = results.report(package);
+    if let Some(crash) = &results.crash {
+        // a crash outside a test would otherwise not fail the report
+        let reported = results
+            .cases
+            .iter()
+            .any(|case| case.message.as_ref() == Some(crash));
+        if !reported {
+            report.add(
+                package,
+                TestCase {
+                    class: String::new(),
+                    name: String::from("instrumentation"),
+                    status: TestStatus::Error,
+                    duration: Duration::ZERO,
+                    message: Some(crash.clone()),
+                },
+            );
+        }
+    }
+    report.write(&dir)
+}
+
+fn run_instrumentation(
+    lua: &Lua,
+    runner: &str,
+    options: &InstrumentOptions,
+) -> mlua::Result<InstrumentSummary> {
+    let Some((package, _)) = runner.split_once('/') else {
+        return Err(MluaAnyhowWrapper::external(anyhow::anyhow!(
+            "Invalid runner {runner}. Expected <test package>/<runner class>"
+        )));
+    };
+    let mut cmd = adb_command(options.serial.as_deref())?;
+    cmd.args(["shell", "am", "instrument", "-r", "-w"]);
+    for (key, value) in &options.args {
+        cmd.arg("-e").arg(key).arg(value);
+    }
+    cmd.arg(runner);
+
+    let mut parser = InstrumentationParser::new();
+    let status = process::run_streaming(lua, &mut cmd, |line| {
+        if let Some(case) = parser.line(line) {
+            match case.status {
+                TestStatus::Passed | TestStatus::Skipped => {
+                    info!(target: "adb", "{}#{} {}", case.class, case.name, case.status)
+                }
+                TestStatus::Failed | TestStatus::Error => {
+                    warn!(target: "adb", "{}#{} {}", case.class, case.name, case.status)
+                }
+            }
+        }
+        Ok(())
+    })?;
+    let mut results = parser.finish();
+    if !status.success() && results.crash.is_none() {
+        results.crash = Some(format!("adb exited with {status}"));
+    }
+    if let Some(crash) = &results.crash {
+        warn!(target: "adb", "{crash}");
+    }
+    let (report, html) = write_report(&results, package).map_err(MluaAnyhowWrapper::external)?;
+
+    let summary = InstrumentSummary {
+        counts: results.counts(),
+        crash: results.crash.clone(),
+        report,
+        html,
+        success: results.success(),
+    };
+    info!(target: "adb", "{}", summary.counts);
+    Ok(summary)
+}
+
+fn options<T: for<'de> Deserialize<'de> + Default>(
+    lua: &Lua,
+    value: Option<mlua::Value>,
+) -> mlua::Result<T> {
+    match value {
+        Some(value) => lua.from_value(value),
+        None => Ok(T::default()),
+    }
+}
+
+fn check_apk(path: &Path) -> anyhow::Result<()> {
+    if path.extension().is_none_or(|extension| extension != "apk") {
+        bail!("{:?} is not an apk", path);
+    }
+    Ok(())
+}
+
+/// Returns the path of adb
+#[labt_lua]
+fn path(_lua: &Lua) {
+    Ok(locate_adb()
+        .map_err(MluaAnyhowWrapper::external)?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Returns the connected devices as a list of tables with serial and state
+#[labt_lua]
+fn devices(lua: &Lua) {
+    let output = process::run_with_output(lua, adb_command(None)?.arg("devices"))?;
+    if !output.status.success() {
+        return Err(MluaAnyhowWrapper::external(anyhow::anyhow!(
+            "adb devices failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    lua.to_value(&parse_devices(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Installs the apk replacing an existing install. The device serial is optional
+#[labt_lua]
+fn install(lua: &Lua, (apk, serial): (String, Option<String>)) {
+    install_apk(lua, &apk, serial.as_deref())
+}
+
+/// Runs the instrumentation, logging each test as it finishes, and writes
+/// a JUnit report of the run. Returns the summary of the run
+#[labt_lua]
+fn instrument(lua: &Lua, (runner, opts): (String, Option<mlua::Value>)) {
+    let opts: InstrumentOptions = options(lua, opts)?;
+    lua.to_value(&run_instrumentation(lua, &runner, &opts)?)
+}
+
+/// Installs the app and test apks and runs the instrumentation
+#[labt_lua]
+fn test(lua: &Lua, opts: mlua::Value) {
+    let opts: TestOptions = lua.from_value(opts)?;
+    let serial = opts.instrument.serial.as_deref();
+    if let Some(app) = &opts.app {
+        check_apk(Path::new(app)).map_err(MluaAnyhowWrapper::external)?;
+        install_apk(lua, app, serial)?;
+    }
+    check_apk(Path::new(&opts.test)).map_err(MluaAnyhowWrapper::external)?;
+    install_apk(lua, &opts.test, serial)?;
+    lua.to_value(&run_instrumentation(lua, &opts.runner, &opts.instrument)?)
+}
+
+/// Generates adb table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to adb table fails
+/// or the underlying lua operations return errors.
+pub fn load_adb_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    path(lua, &table)?;
+    devices(lua, &table)?;
+    install(lua, &table)?;
+    instrument(lua, &table)?;
+    test(lua, &table)?;
+
+    lua.globals().set("adb", table)?;
+
+    Ok(())
+}
+
+#[test]
+fn adb_parse_devices() {
+    let output = "* daemon not running; starting now at tcp:5037
+* daemon started successfully
+List of devices attached
+emulator-5554\tdevice
+R58M123ABC\tunauthorized
+
+";
+    assert_eq!(
+        parse_devices(output),
+        vec![
+            Device {
+                serial: String::from("emulator-5554"),
+                state: String::from("device"),
+            },
+            Device {
+                serial: String::from("R58M123ABC"),
+                state: String::from("unauthorized"),
+            }
+        ]
+    );
+}
@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use labt_proc_macro::labt_lua;
+use log::{info, warn};
+use mlua::{Lua, LuaSerdeExt};
+use serde::Deserialize;
+
+use crate::{
+    config::{env::EnvValue, get_config, profile::ProfileName},
+    get_project_root,
+    submodules::{
+        apksign::{self, sign_apk, SignOptions, SigningConfig, SigningKey},
+        build::active_profile,
+    },
+};
+
+use super::MluaAnyhowWrapper;
+
+/// Options of apksign.sign. They override the `signing` table of the active profile
+#[derive(Deserialize, Debug, Default)]
+struct SignLuaOptions {
+    /// Relative to the project root
+    keystore: Option<PathBuf>,
+    alias: Option<String>,
+    store_password: Option<String>,
+    v1: Option<bool>,
+    v2: Option<bool>,
+    /// Overrides the minSdkVersion read from the apk
+    min_sdk: Option<u32>,
+}
+
+/// The key and signature schemes from the options, then the `signing` table of
+/// the active profile and then the debug keystore
+fn signing_key(opts: SignLuaOptions) -> anyhow::Result<(SigningKey, SignOptions)> {
+    let root = get_project_root()?;
+    let config = get_config()?;
+    let profile = active_profile();
+    let profile_signing = SigningConfig::from_profile(
+        config
+            .profile
+            .as_ref()
+            .and_then(|profiles| profiles.get(profile)),
+    )?;
+    let signing = match (opts.keystore, profile_signing) {
+        (Some(keystore), _) => Some(SigningConfig {
+            keystore,
+            alias: opts.alias,
+            store_password: opts.store_password.map(EnvValue::Value),
+            v1: true,
+            v2: true,
+        }),
+        (None, Some(mut signing)) => {
+            if opts.alias.is_some() {
+                signing.alias = opts.alias;
+            }
+            if let Some(password) = opts.store_password {
+                signing.store_password = Some(EnvValue::Value(password));
+            }
+            Some(signing)
+        }
+        (None, None) => None,
+    };
+
+    let mut options = signing
+        .as_ref()
+        .map(SigningConfig::options)
+        .unwrap_or_default();
+    options.v1 = opts.v1.unwrap_or(options.v1);
+    options.v2 = opts.v2.unwrap_or(options.v2);
+    options.min_sdk = opts.min_sdk.or(options.min_sdk);
+    let key = match signing {
+        Some(signing) => signing.load_key(&root)?,
+        None => {
+            if profile == ProfileName::Release {
+                warn!(target: "apksign", "Signing the release build with the debug keystore. Set signing in [profile.release] of Labt.toml to sign with your own key");
+            }
+            SigningKey::debug()?
+        }
+    };
+    Ok((key, options))
+}
+
+/// Signs the apk with the v1 and v2 signature schemes. The signed apk is written
+/// to output, or replaces the apk if output is not given. Returns the path of the
+/// signed apk
+#[labt_lua]
+fn sign(lua: &Lua, (apk, output, opts): (String, Option<String>, Option<mlua::Value>)) {
+    let opts: SignLuaOptions = match opts {
+        Some(opts) => lua.from_value(opts)?,
+        None => SignLuaOptions::default(),
+    };
+    let output = output.unwrap_or_else(|| apk.clone());
+    let (key, options) = signing_key(opts).map_err(MluaAnyhowWrapper::external)?;
+    sign_apk(apk.as_ref(), output.as_ref(), &key, options).map_err(MluaAnyhowWrapper::external)?;
+    info!(target: "apksign", "Signed {}", output);
+    Ok(output)
+}
+
+/// Returns the path of the debug keystore, the one of the android sdk if it
+/// exists, else the one in labt home, which is generated if it does not exist yet
+#[labt_lua]
+fn debug_keystore(_lua: &Lua) {
+    Ok(apksign::debug_keystore()
+        .map_err(MluaAnyhowWrapper::external)?
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Generates apksign table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to apksign table fails
+/// or the underlying lua operations return errors.
+pub fn load_apksign_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    sign(lua, &table)?;
+    debug_keystore(lua, &table)?;
+
+    lua.globals().set("apksign", table)?;
+
+    Ok(())
+}
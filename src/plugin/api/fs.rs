@@ -24,8 +24,7 @@ fn mkdir(_lua: &Lua, path: String) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(path);
         root
     } else {
@@ -90,8 +89,7 @@ fn copy(_lua: &Lua, (src, dest, recursive): (String, String, Option<bool>)) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(src_path);
         root
     } else {
@@ -102,8 +100,7 @@ fn copy(_lua: &Lua, (src, dest, recursive): (String, String, Option<bool>)) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(dest_path);
         root
     } else {
@@ -163,8 +160,7 @@ fn mv(_lua: &Lua, (src, dest): (String, String)) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(src_path);
         root
     } else {
@@ -175,8 +171,7 @@ fn mv(_lua: &Lua, (src, dest): (String, String)) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(dest_path);
         root
     } else {
@@ -214,8 +209,7 @@ fn rm(_lua: &Lua, (path, recursive): (String, Option<bool>)) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(path);
         root
     } else {
@@ -245,8 +239,7 @@ fn mkdir_all(_lua: &Lua, path: String) {
         // if path is relative, then build from project root
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(path);
         root
     } else {
@@ -287,8 +280,7 @@ fn glob(_lua: &Lua, pattern: String) {
     let pattern = if path.is_relative() {
         let mut root = crate::get_project_root()
             .context("Failed to get project root directory")
-            .map_err(MluaAnyhowWrapper::external)?
-            .clone();
+            .map_err(MluaAnyhowWrapper::external)?;
         root.push(path);
         if let Some(pattern) = root.to_str() {
             pattern.to_string()
@@ -340,7 +332,7 @@ fn is_newer(_lua: &Lua, (a, b): (String, String)) {
 ///
 /// This function will return an error if adding functions to fs table fails
 /// or the underlying lua operations return errors.
-pub fn load_fs_table(lua: &mut Lua) -> anyhow::Result<()> {
+pub fn load_fs_table(lua: &Lua) -> anyhow::Result<()> {
     let table = lua.create_table()?;
 
     mkdir(lua, &table)?;
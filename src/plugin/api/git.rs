@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use git2::{DescribeFormatOptions, DescribeOptions, Repository, StatusOptions};
+use labt_proc_macro::labt_lua;
+use mlua::{Lua, LuaSerdeExt};
+use serde::Serialize;
+
+use super::MluaAnyhowWrapper;
+
+/// Length of the abbreviated commit hash
+const SHORT_COMMIT_LENGTH: usize = 7;
+
+/// Git metadata of the project repository
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GitInfo {
+    /// The checked out branch. None if HEAD is detached
+    pub branch: Option<String>,
+    pub commit: String,
+    pub short_commit: String,
+    /// Like `git describe --tags --always --dirty`
+    pub describe: String,
+    /// True if tracked files have uncommitted changes
+    pub dirty: bool,
+}
+
+/// The project root, or the working directory when not in a project e.g. during init
+fn repository_path() -> anyhow::Result<PathBuf> {
+    match crate::get_project_root() {
+        Ok(root) => Ok(root),
+        Err(_) => std::env::current_dir().context("Failed to get current working directory"),
+    }
+}
+
+fn open_repository(path: &Path) -> anyhow::Result<Repository> {
+    Repository::discover(path).context(format!("No git repository found at {:?}", path))
+}
+
+fn head_branch(repo: &Repository) -> anyhow::Result<Option<String>> {
+    if repo.head_detached().unwrap_or(false) {
+        return Ok(None);
+    }
+    let head = repo.head().context("Failed to read git HEAD")?;
+    Ok(head.shorthand().map(|name| name.to_string()))
+}
+
+fn head_commit(repo: &Repository) -> anyhow::Result<String> {
+    let commit = repo
+        .head()
+        .context("Failed to read git HEAD")?
+        .peel_to_commit()
+        .context("HEAD does not point to a commit")?;
+    Ok(commit.id().to_string())
+}
+
+fn has_changes(repo: &Repository) -> anyhow::Result<bool> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(false).include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .context("Failed to read git status")?;
+    Ok(!statuses.is_empty())
+}
+
+fn describe_head(repo: &Repository) -> anyhow::Result<String> {
+    let mut options = DescribeOptions::new();
+    options.describe_tags().show_commit_oid_as_fallback(true);
+    let description = repo
+        .describe(&options)
+        .context("Failed to describe git HEAD")?;
+    let mut format = DescribeFormatOptions::new();
+    format.dirty_suffix("-dirty");
+    Ok(description.format(Some(&format))?)
+}
+
+/// Reads the git metadata of the repository containing path
+///
+/// # Errors
+///
+/// Returns an error if path is not in a git repository or it has no commits
+pub fn git_info(path: &Path) -> anyhow::Result<GitInfo> {
+    let repo = open_repository(path)?;
+    let commit = head_commit(&repo)?;
+    Ok(GitInfo {
+        branch: head_branch(&repo)?,
+        short_commit: commit.chars().take(SHORT_COMMIT_LENGTH).collect(),
+        commit,
+        describe: describe_head(&repo)?,
+        dirty: has_changes(&repo)?,
+    })
+}
+
+fn lua_repository() -> mlua::Result<Repository> {
+    repository_path()
+        .and_then(|path| open_repository(&path))
+        .map_err(MluaAnyhowWrapper::external)
+}
+
+/// Returns a description of HEAD like `git describe --tags --always --dirty`
+#[labt_lua]
+fn describe(_lua: &Lua) {
+    let repo = lua_repository()?;
+    describe_head(&repo).map_err(MluaAnyhowWrapper::external)
+}
+
+/// Returns the current branch name or nil if HEAD is detached
+#[labt_lua]
+fn branch(_lua: &Lua) {
+    let repo = lua_repository()?;
+    head_branch(&repo).map_err(MluaAnyhowWrapper::external)
+}
+
+/// Returns the hash of the HEAD commit. Abbreviated if short is true
+#[labt_lua]
+fn commit(_lua: &Lua, short: Option<bool>) {
+    let repo = lua_repository()?;
+    let commit = head_commit(&repo).map_err(MluaAnyhowWrapper::external)?;
+    if short.unwrap_or(false) {
+        return Ok(commit.chars().take(SHORT_COMMIT_LENGTH).collect::<String>());
+    }
+    Ok(commit)
+}
+
+/// Returns true if tracked files have uncommitted changes
+#[labt_lua]
+fn is_dirty(_lua: &Lua) {
+    let repo = lua_repository()?;
+    has_changes(&repo).map_err(MluaAnyhowWrapper::external)
+}
+
+/// Returns all git metadata as a table
+#[labt_lua]
+fn info(lua: &Lua) {
+    let info = repository_path()
+        .and_then(|path| git_info(&path))
+        .map_err(MluaAnyhowWrapper::external)?;
+    lua.to_value(&info)
+}
+
+/// Generates git table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to git table fails
+/// or the underlying lua operations return errors.
+pub fn load_git_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    describe(lua, &table)?;
+    branch(lua, &table)?;
+    commit(lua, &table)?;
+    is_dirty(lua, &table)?;
+    info(lua, &table)?;
+
+    lua.globals().set("git", table)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn git_info_describe_and_dirty() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("git-info");
+    std::fs::create_dir_all(&root).unwrap();
+    let repo = Repository::init(&root).unwrap();
+    std::fs::write(root.join("README"), "labt").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(Path::new("README")).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("labt", "labt@example.com").unwrap();
+    let oid = repo
+        .commit(Some("HEAD"), &signature, &signature, "first", &tree, &[])
+        .unwrap();
+    repo.tag_lightweight("v1.0.0", &repo.find_object(oid, None).unwrap(), false)
+        .unwrap();
+
+    let info = git_info(&root).unwrap();
+    assert_eq!(info.commit, oid.to_string());
+    assert_eq!(info.describe, "v1.0.0");
+    assert!(!info.dirty);
+
+    std::fs::write(root.join("README"), "changed").unwrap();
+    let info = git_info(&root);
+    let info = info.unwrap();
+    assert_eq!(info.describe, "v1.0.0-dirty");
+    assert!(info.dirty);
+}
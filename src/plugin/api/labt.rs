@@ -1,22 +1,53 @@
+use std::path::PathBuf;
+use std::rc::Weak;
+
 use anyhow::Context;
 use labt_proc_macro::labt_lua;
+use log::warn;
 use mlua::IntoLua;
 use mlua::Lua;
 use mlua::LuaSerdeExt;
+use mlua::Table;
 
 use crate::caching::Cache;
 use crate::config::get_config;
+use crate::config::get_resolve_config;
 use crate::config::get_resolvers_from_config;
 use crate::config::lock::load_labt_lock;
 use crate::config::lock::strings::ARTIFACT_ID;
 use crate::config::lock::strings::DEPENDENCIES;
 use crate::config::lock::strings::GROUP_ID;
 use crate::config::lock::strings::PACKAGING;
+use crate::config::lock::strings::SCOPE;
 use crate::config::lock::strings::VERSION;
+use crate::config::version_code::compute_version_code;
+use crate::config::LabToml;
+use crate::events::subscribe_local;
+use crate::events::Event;
+use crate::events::Subscription;
 use crate::plugin::api::MluaAnyhowWrapper;
+use crate::submodules::build::active_profile;
 use crate::submodules::build::Step;
 use crate::submodules::build::BUILD_STEP;
 use crate::submodules::resolve::ProjectDep;
+use crate::submodules::staging::StagingDirs;
+use crate::submodules::versions;
+
+/// Lua registry key holding the functions registered by `labt.on_cleanup`
+pub const CLEANUP_HOOKS: &str = "labt_cleanup_hooks";
+/// Lua registry key holding the functions registered by `labt.on_event` by event name
+pub const EVENT_HOOKS: &str = "labt_event_hooks";
+/// The event name of hooks called with every event
+const ALL_EVENTS: &str = "*";
+
+/// The lua state of a running plugin, kept in its app data so that event hooks can
+/// call back into it
+pub struct LuaHandle(pub Weak<Lua>);
+
+/// Keeps the event hooks of a plugin subscribed for as long as its lua state lives
+struct EventHooks {
+    _subscription: Subscription,
+}
 
 /// Returns the current build step the plugin was executed
 #[labt_lua]
@@ -25,28 +56,79 @@ fn get_build_step(_: &Lua) {
     Ok(build_step)
 }
 
+/// Returns the name of the profile the build runs with, debug or release
 #[labt_lua]
-fn get_project_config(lua: &Lua) {
+fn get_profile(_: &Lua) {
+    Ok(active_profile().to_string())
+}
+
+/// Returns the keys of the active `[profile.<name>]` table in Labt.toml. The
+/// table is empty if the project does not configure the profile
+#[labt_lua]
+fn get_profile_config(lua: &Lua) {
     let config = get_config().map_err(MluaAnyhowWrapper::external)?;
+    let profile = config
+        .profile
+        .as_ref()
+        .and_then(|profiles| profiles.get(active_profile()))
+        .cloned()
+        .unwrap_or_default();
+    lua.to_value(&profile)
+}
+
+/// Returns the project config. project.version_number holds the
+/// versionCode computed by the configured version code strategy, or the
+/// configured version_number if no versionCode can be derived, e.g. a ci
+/// strategy outside of ci
+#[labt_lua]
+fn get_project_config(lua: &Lua) {
+    let mut config = get_config().map_err(MluaAnyhowWrapper::external)?;
+    match project_version_code(lua, &config) {
+        Ok(code) => {
+            config.project.version_number = i32::try_from(code)
+                .context(format!("versionCode {code} does not fit in version_number"))
+                .map_err(MluaAnyhowWrapper::external)?;
+        }
+        Err(err) => {
+            warn!(target: "labt", "Using the version_number of Labt.toml as versionCode: {err}");
+        }
+    }
     lua.to_value(&config)
 }
 
+/// The versionCode computed once per plugin run, a strategy such as the
+/// timestamp would give a different code on every call
+struct VersionCode(i64);
+
+fn project_version_code(lua: &Lua, config: &LabToml) -> mlua::Result<i64> {
+    if let Some(code) = lua.app_data_ref::<VersionCode>() {
+        return Ok(code.0);
+    }
+    let root = crate::get_project_root()
+        .context("Failed to get project root directory")
+        .map_err(MluaAnyhowWrapper::external)?;
+    let code = compute_version_code(config, &root).map_err(MluaAnyhowWrapper::external)?;
+    lua.set_app_data(VersionCode(code));
+    Ok(code)
+}
+
+/// Returns the versionCode computed by the `[version_code]` strategy
+#[labt_lua]
+fn get_version_code(lua: &Lua) {
+    let config = get_config().map_err(MluaAnyhowWrapper::external)?;
+    project_version_code(lua, &config)
+}
+
 /// Returns the project root directory
 #[labt_lua]
 fn get_project_root(lua: &Lua) {
     let path = crate::get_project_root()
         .context("Failed to get project root directory")
-        .map_err(MluaAnyhowWrapper::external)?
-        .clone();
+        .map_err(MluaAnyhowWrapper::external)?;
     Ok(lua.to_value(&path))
 }
 
-#[labt_lua]
-fn get_lock_dependencies(lua: &Lua) {
-    // TODO cache this to reduce uneccessary reading of Labt.lock
-
-    let lock = load_labt_lock().map_err(MluaAnyhowWrapper::external)?;
-    let deps = lock.resolved;
+fn dependencies_table(lua: &Lua, deps: Vec<ProjectDep>) -> mlua::Result<Table<'_>> {
     let array = lua.create_table_with_capacity(deps.len(), 0)?;
 
     for dep in deps {
@@ -56,11 +138,36 @@ fn get_lock_dependencies(lua: &Lua) {
         dep_table.set(VERSION, dep.version)?;
         dep_table.set(DEPENDENCIES, dep.dependencies)?;
         dep_table.set(PACKAGING, dep.packaging)?;
+        dep_table.set(SCOPE, dep.scope.to_string())?;
         array.push(dep_table)?;
     }
 
     Ok(array)
 }
+
+#[labt_lua]
+fn get_lock_dependencies(lua: &Lua) {
+    // TODO cache this to reduce uneccessary reading of Labt.lock
+
+    let lock = load_labt_lock().map_err(MluaAnyhowWrapper::external)?;
+    dependencies_table(lua, lock.resolved)
+}
+
+/// Returns the resolved annotation processors and their dependencies from Labt.lock.
+/// These belong on the processor path and not on the app classpath
+#[labt_lua]
+fn get_annotation_processors(lua: &Lua) {
+    let lock = load_labt_lock().map_err(MluaAnyhowWrapper::external)?;
+    dependencies_table(lua, lock.processors)
+}
+
+/// Returns the dependencies only needed by tests from Labt.lock. These are in addition
+/// to the project dependencies returned by get_lock_dependencies
+#[labt_lua]
+fn get_test_dependencies(lua: &Lua) {
+    let lock = load_labt_lock().map_err(MluaAnyhowWrapper::external)?;
+    dependencies_table(lua, lock.test)
+}
 /// Returns the cache location for this dependency. This does not check if the path
 /// exists. It constructs a valid cache path according to the labt cache resolver.
 /// Returns an error if:
@@ -109,25 +216,173 @@ fn get_cache_path(
 /// - failed to read and configure resolvers from config
 #[labt_lua]
 fn resolve(_lua: &Lua) {
-    use crate::pom::Project;
-
-    let config = get_config()
+    let config = get_resolve_config()
         .context("Failed to get project configuration")
         .map_err(MluaAnyhowWrapper::external)?;
 
-    if let Some(deps) = &config.dependencies {
-        let dependencies: Vec<Project> = deps
-            .iter()
-            .map(|(artifact_id, table)| Project::new(&table.group_id, artifact_id, &table.version))
-            .collect();
+    if config.dependencies.is_some()
+        || config.annotation_processors.is_some()
+        || config.test_dependencies.is_some()
+    {
+        let dependencies = crate::submodules::resolve::get_config_dependencies(&config)
+            .context("Failed to read project dependencies")
+            .map_err(MluaAnyhowWrapper::external)?;
         let resolvers = get_resolvers_from_config(&config)
             .context("Failed to get resolvers")
             .map_err(MluaAnyhowWrapper::external)?;
 
-        crate::submodules::resolve::resolve(dependencies, resolvers)
-            .context("Failed to resolve projects dependencies")
+        crate::submodules::resolve::resolve(
+            dependencies,
+            resolvers,
+            crate::submodules::resolve::ResolveOptions::try_from(&config)
+                .map_err(MluaAnyhowWrapper::external)?,
+        )
+        .context("Failed to resolve projects dependencies")
+        .map_err(MluaAnyhowWrapper::external)?;
+    }
+    Ok(())
+}
+
+/// Lists the published versions of an artifact from the maven-metadata.xml of all
+/// configured repositories, newest first. Release dates are looked up if dates is true.
+/// Returns an error if no repository has metadata for the artifact
+#[labt_lua]
+fn get_available_versions(
+    lua: &Lua,
+    (group_id, artifact_id, limit, dates): (String, String, Option<usize>, Option<bool>),
+) {
+    let resolvers = versions::project_resolvers()
+        .context("Failed to get resolvers")
+        .map_err(MluaAnyhowWrapper::external)?;
+    let mut available = versions::available_versions(&group_id, &artifact_id, &resolvers)
+        .map_err(MluaAnyhowWrapper::external)?;
+    if let Some(limit) = limit {
+        available.truncate(limit);
+    }
+    if dates.unwrap_or(false) {
+        versions::fill_release_dates(&group_id, &artifact_id, &mut available, &resolvers)
             .map_err(MluaAnyhowWrapper::external)?;
     }
+    lua.to_value(&available)
+}
+
+/// Returns the directories the resolved dependencies are staged into before the build
+#[labt_lua]
+fn get_staging_dirs(lua: &Lua) {
+    let root = crate::get_project_root()
+        .context("Failed to get project root directory")
+        .map_err(MluaAnyhowWrapper::external)?;
+    lua.to_value(&StagingDirs::new(&root))
+}
+
+/// Returns the JAVA_HOME of the JDK configured in the `[jdk]` table, downloading it if needed.
+/// Falls back to the JAVA_HOME environment variable or nil if neither is set
+#[labt_lua]
+fn get_java_home(lua: &Lua) {
+    let jdk = get_config().ok().and_then(|config| config.jdk);
+    let java_home = match jdk {
+        Some(jdk) => {
+            Some(crate::submodules::jdk::provision_jdk(&jdk).map_err(MluaAnyhowWrapper::external)?)
+        }
+        None => std::env::var_os("JAVA_HOME").map(PathBuf::from),
+    };
+    lua.to_value(&java_home)
+}
+
+/// Generates the BuildConfig class from the `[build_config]` table in Labt.toml.
+/// Returns the path of the generated class or nil if the table is missing
+#[labt_lua]
+fn generate_build_config(lua: &Lua) {
+    let config = get_config()
+        .context("Failed to get project configuration")
+        .map_err(MluaAnyhowWrapper::external)?;
+    let root = crate::get_project_root()
+        .context("Failed to get project root directory")
+        .map_err(MluaAnyhowWrapper::external)?;
+    let path = crate::submodules::build::write_build_config(&config, &root)
+        .map_err(MluaAnyhowWrapper::external)?;
+    lua.to_value(&path)
+}
+
+/// Registers a function that is called if the build is interrupted while
+/// this plugin is running. Cleanup functions run in reverse order of registration.
+#[labt_lua]
+fn on_cleanup(lua: &Lua, callback: mlua::Function) {
+    let hooks: Option<mlua::Table> = lua.named_registry_value(CLEANUP_HOOKS)?;
+    let hooks = match hooks {
+        Some(hooks) => hooks,
+        None => {
+            let hooks = lua.create_table()?;
+            lua.set_named_registry_value(CLEANUP_HOOKS, hooks.clone())?;
+            hooks
+        }
+    };
+    hooks.push(callback)?;
+    Ok(())
+}
+
+/// Registers a function that is called with the events labt emits while this plugin
+/// runs, e.g. "resolution_finished" or "download_finished". "*" receives every event.
+#[labt_lua]
+fn on_event(lua: &Lua, (name, callback): (String, mlua::Function)) {
+    let hooks: Option<Table> = lua.named_registry_value(EVENT_HOOKS)?;
+    let hooks = match hooks {
+        Some(hooks) => hooks,
+        None => {
+            let hooks = lua.create_table()?;
+            lua.set_named_registry_value(EVENT_HOOKS, hooks.clone())?;
+            hooks
+        }
+    };
+    let callbacks: Option<Table> = hooks.get(name.as_str())?;
+    let callbacks = match callbacks {
+        Some(callbacks) => callbacks,
+        None => {
+            let callbacks = lua.create_table()?;
+            hooks.set(name, callbacks.clone())?;
+            callbacks
+        }
+    };
+    callbacks.push(callback)?;
+
+    if lua.app_data_ref::<EventHooks>().is_none() {
+        let handle = lua
+            .app_data_ref::<LuaHandle>()
+            .map(|handle| handle.0.clone())
+            .ok_or_else(|| mlua::Error::external("Event hooks are not available here"))?;
+        let subscription = subscribe_local(move |event| {
+            if let Some(lua) = handle.upgrade() {
+                if let Err(err) = call_event_hooks(&lua, event) {
+                    warn!(target: "plugin", "Event hook failed: {}", err);
+                }
+            }
+        });
+        lua.set_app_data(EventHooks {
+            _subscription: subscription,
+        });
+    }
+    Ok(())
+}
+
+/// Calls the hooks registered for the event and for all events
+fn call_event_hooks(lua: &Lua, event: &Event) -> mlua::Result<()> {
+    let hooks: Option<Table> = lua.named_registry_value(EVENT_HOOKS)?;
+    let Some(hooks) = hooks else {
+        return Ok(());
+    };
+    let value = lua.to_value(event)?;
+    let name: String = match &value {
+        mlua::Value::Table(table) => table.get("event")?,
+        _ => return Ok(()),
+    };
+    for key in [name.as_str(), ALL_EVENTS] {
+        let callbacks: Option<Table> = hooks.get(key)?;
+        if let Some(callbacks) = callbacks {
+            for callback in callbacks.sequence_values::<mlua::Function>() {
+                callback?.call::<_, ()>(value.clone())?;
+            }
+        }
+    }
     Ok(())
 }
 
@@ -142,6 +397,7 @@ impl<'lua> IntoLua<'lua> for Step {
             Self::COMPILE => Ok(mlua::Value::String(lua.create_string("COMPILE")?)),
             Self::DEX => Ok(mlua::Value::String(lua.create_string("DEX")?)),
             Self::BUNDLE => Ok(mlua::Value::String(lua.create_string("BUNDLE")?)),
+            Self::INSTRUMENT => Ok(mlua::Value::String(lua.create_string("INSTRUMENT")?)),
             Self::POST => Ok(mlua::Value::String(lua.create_string("POST")?)),
         }
     }
@@ -152,25 +408,91 @@ impl<'lua> IntoLua<'lua> for Step {
 ///
 /// This function will return an error if adding functions to labt function fails
 /// or the underlying lua operations return errors.
-pub fn load_labt_table(lua: &mut Lua) -> anyhow::Result<()> {
+pub fn load_labt_table(lua: &Lua) -> anyhow::Result<()> {
     let table = lua.create_table()?;
 
     // add get_stage, returns the current stage of the build
     get_build_step(lua, &table)?;
 
+    get_profile(lua, &table)?;
+    get_profile_config(lua, &table)?;
+
     // add get_project_config
     get_project_config(lua, &table)?;
+    get_version_code(lua, &table)?;
     // add get_project_root
     get_project_root(lua, &table)?;
 
     // add get_dependencies
     get_lock_dependencies(lua, &table)?;
 
+    get_annotation_processors(lua, &table)?;
+
+    get_test_dependencies(lua, &table)?;
+
     get_cache_path(lua, &table)?;
 
     resolve(lua, &table)?;
 
+    on_cleanup(lua, &table)?;
+
+    on_event(lua, &table)?;
+
+    generate_build_config(lua, &table)?;
+
+    get_available_versions(lua, &table)?;
+
+    get_staging_dirs(lua, &table)?;
+
+    get_java_home(lua, &table)?;
+
     lua.globals().set("labt", table)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn event_hooks_receive_emitted_events() {
+    use crate::events::emit;
+    use std::rc::Rc;
+
+    let lua = Rc::new(Lua::new());
+    lua.set_app_data(LuaHandle(Rc::downgrade(&lua)));
+    load_labt_table(&lua).unwrap();
+    lua.load(
+        r#"
+        received = {}
+        labt.on_event("step_started", function(event)
+            if event.step == "hooks-test" then
+                table.insert(received, event.event .. ":" .. event.step)
+            end
+        end)
+        labt.on_event("*", function(event)
+            if event.event == "step_finished" and event.step == "hooks-test" then
+                table.insert(received, tostring(event.success))
+            end
+        end)
+        "#,
+    )
+    .exec()
+    .unwrap();
+    emit(Event::StepStarted {
+        step: String::from("hooks-test"),
+    });
+    emit(Event::StepFinished {
+        step: String::from("hooks-test"),
+        success: true,
+        elapsed_ms: 5,
+    });
+    let received: Vec<String> = lua
+        .globals()
+        .get::<_, Table>("received")
+        .unwrap()
+        .sequence_values()
+        .collect::<mlua::Result<_>>()
+        .unwrap();
+    assert_eq!(received, vec!["step_started:hooks-test", "true"]);
+}
@@ -1,28 +1,48 @@
 use labt_proc_macro::labt_lua;
-use mlua::{Lua, Value};
+use mlua::{Function, Lua, Value, Variadic};
+
+use crate::plugin::process::mask_secrets;
+use crate::MULTI_PROGRESS_BAR;
 
 /// Logs a message at the info level
 #[labt_lua]
-fn info(_: &Lua, (target, message): (String, String)) {
+fn info(lua: &Lua, (target, message): (String, String)) {
+    let message = mask_secrets(lua, &message);
     log::info!(target: target.as_str(), "{}", message);
     Ok(())
 }
-/// Logs a message at the warn level
+/// Logs a message at the warn level. It is listed in the summary of a build
 #[labt_lua]
-fn warn(_: &Lua, (target, message): (String, String)) {
+fn warn(lua: &Lua, (target, message): (String, String)) {
+    let message = mask_secrets(lua, &message);
     log::warn!(target: target.as_str(), "{}", message);
     Ok(())
 }
 /// Logs a message at the error level
 #[labt_lua]
-fn error(_: &Lua, (target, message): (String, String)) {
+fn error(lua: &Lua, (target, message): (String, String)) {
+    let message = mask_secrets(lua, &message);
     log::error!(target: target.as_str(), "{}", message);
     Ok(())
 }
 /// Dumps a lua table for debugging
 #[labt_lua]
-fn dump(_lua: &Lua, table: Value) {
-    println!("{:#?}", table);
+fn dump(lua: &Lua, table: Value) {
+    let dump = format!("{:#?}", table);
+    println!("{}", mask_secrets(lua, &dump));
+    Ok(())
+}
+
+/// The lua print with the secrets of the plugin masked
+fn print(lua: &Lua, values: Variadic<Value>) -> mlua::Result<()> {
+    let tostring: Function = lua.globals().get("tostring")?;
+    let values = values
+        .into_iter()
+        .map(|value| tostring.call::<_, String>(value))
+        .collect::<mlua::Result<Vec<String>>>()?;
+    let line = values.join("\t");
+    let line = mask_secrets(lua, &line);
+    MULTI_PROGRESS_BAR.suspend(|| println!("{}", line));
     Ok(())
 }
 
@@ -32,7 +52,7 @@ fn dump(_lua: &Lua, table: Value) {
 ///
 /// This function will return an error if adding functions to log table fails
 /// or the underlying lua operations return errors.
-pub fn load_log_table(lua: &mut Lua) -> anyhow::Result<()> {
+pub fn load_log_table(lua: &Lua) -> anyhow::Result<()> {
     let table = lua.create_table()?;
 
     info(lua, &table)?;
@@ -41,6 +61,7 @@ pub fn load_log_table(lua: &mut Lua) -> anyhow::Result<()> {
     dump(lua, &table)?;
 
     lua.globals().set("log", table)?;
+    lua.globals().set("print", lua.create_function(print)?)?;
 
     Ok(())
 }
@@ -3,11 +3,18 @@ use std::{
     fmt::{write, Display},
 };
 
+pub mod adb;
+pub mod apksign;
 pub mod fs;
+pub mod git;
 pub mod labt;
 pub mod log;
 pub mod prompt;
+pub mod report;
+pub mod resources;
 pub mod sys;
+pub mod template;
+pub mod toolchains;
 pub mod zip;
 
 /// Wraps anyhow Error so as to allow useful anyhow error chain to be
@@ -49,4 +56,8 @@ impl MluaAnyhowWrapper {
     pub fn external(err: anyhow::Error) -> mlua::Error {
         mlua::Error::external(MluaAnyhowWrapper(err))
     }
+    /// The wrapped error, to walk its chain of causes
+    pub fn inner(&self) -> &anyhow::Error {
+        &self.0
+    }
 }
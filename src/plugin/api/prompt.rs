@@ -178,7 +178,7 @@ fn multi_select(_lua: &Lua, (prompt, options, default): (String, Vec<String>, Op
 ///
 /// This function will return an error if adding functions to prompt table fails
 /// or the underlying lua operations return errors.
-pub fn load_prompt_table(lua: &mut Lua) -> anyhow::Result<()> {
+pub fn load_prompt_table(lua: &Lua) -> anyhow::Result<()> {
     let table = lua.create_table()?;
     confirm(lua, &table)?;
     confirm_optional(lua, &table)?;
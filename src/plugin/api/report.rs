@@ -0,0 +1,146 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::Context;
+use labt_proc_macro::labt_lua;
+use mlua::{Lua, LuaSerdeExt, Table};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    get_project_root,
+    plugin::report::{TestCase, TestCounts, TestReport, TestStatus},
+};
+
+use super::MluaAnyhowWrapper;
+
+/// Directory in the build directory the reports are written to
+const TEST_RESULTS_DIR: &str = "test-results";
+
+/// A test case as added from lua
+#[derive(Deserialize, Debug)]
+struct LuaTestCase {
+    suite: String,
+    #[serde(default)]
+    class: String,
+    name: String,
+    #[serde(default)]
+    status: TestStatus,
+    /// In seconds
+    #[serde(default)]
+    duration: f64,
+    message: Option<String>,
+}
+
+impl From<LuaTestCase> for TestCase {
+    fn from(case: LuaTestCase) -> Self {
+        TestCase {
+            class: case.class,
+            name: case.name,
+            status: case.status,
+            duration: Duration::try_from_secs_f64(case.duration).unwrap_or_default(),
+            message: case.message,
+        }
+    }
+}
+
+/// Returned to lua by write
+#[derive(Serialize, Debug)]
+struct WrittenReport {
+    #[serde(flatten)]
+    counts: TestCounts,
+    junit: PathBuf,
+    html: PathBuf,
+    success: bool,
+}
+
+/// Adds a test case to the report. The case is a table with the fields suite, name,
+/// class, status, duration and message
+/// # Errors
+/// Returns an error if self is not a valid report object or the case is invalid
+#[labt_lua]
+fn add_case(lua: &Lua, (table_self, case): (Table, Table)) {
+    let cases: Table = table_self
+        .get("cases")
+        .context("Missing field \"cases\" on self table")
+        .map_err(MluaAnyhowWrapper::external)?;
+
+    // fail on the call adding an invalid case instead of on write
+    let _: LuaTestCase = lua.from_value(mlua::Value::Table(case.clone()))?;
+    cases
+        .push(case)
+        .context("Failed to add case to report cases")
+        .map_err(MluaAnyhowWrapper::external)?;
+
+    Ok(table_self)
+}
+
+/// Writes junit.xml and index.html into dir, relative to the project root.
+/// Defaults to build/test-results/<name>
+/// # Errors
+/// Returns an error if self is not a valid report object or writing fails
+#[labt_lua]
+fn write(lua: &Lua, (table_self, dir): (Table, Option<String>)) {
+    let name: String = table_self
+        .get("name")
+        .context("Missing field \"name\" on self table")
+        .map_err(MluaAnyhowWrapper::external)?;
+    let cases: Vec<mlua::Value> = table_self
+        .get("cases")
+        .context("Missing field \"cases\" on self table")
+        .map_err(MluaAnyhowWrapper::external)?;
+
+    let mut report = TestReport::new(&name);
+    for case in cases {
+        let case: LuaTestCase = lua.from_value(case)?;
+        let suite = case.suite.clone();
+        report.add(&suite, case.into());
+    }
+
+    let root = get_project_root()
+        .context("Failed to get project root directory")
+        .map_err(MluaAnyhowWrapper::external)?;
+    let dir = match dir {
+        Some(dir) => root.join(dir),
+        None => root.join("build").join(TEST_RESULTS_DIR).join(&name),
+    };
+    let (junit, html) = report.write(&dir).map_err(MluaAnyhowWrapper::external)?;
+
+    let counts = report.counts();
+    lua.to_value(&WrittenReport {
+        counts,
+        junit,
+        html,
+        success: counts.success(),
+    })
+}
+
+/// Creates a new test report
+#[labt_lua]
+fn new(lua: &Lua, name: String) {
+    let report = lua.create_table()?;
+
+    // fields
+    report.set("name", name)?;
+    report.set("cases", lua.create_table()?)?;
+
+    // functions
+    add_case(lua, &report)?;
+    write(lua, &report)?;
+
+    Ok(report)
+}
+
+/// Generates report table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to report table fails
+/// or the underlying lua operations return errors.
+pub fn load_report_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    new(lua, &table)?;
+
+    lua.globals().set("report", table)?;
+
+    Ok(())
+}
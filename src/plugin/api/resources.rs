@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use labt_proc_macro::labt_lua;
+use mlua::{Lua, LuaSerdeExt};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    get_project_root,
+    plugin::resources::{parse_r_txt, ResourceSymbol, ResourceUsage},
+};
+
+use super::MluaAnyhowWrapper;
+
+/// Options of resources.usage
+#[derive(Deserialize, Debug)]
+struct UsageOptions {
+    /// The R.txt listing the resources of the app
+    symbols: String,
+    /// The class, dex, jar or apk files, or directories of them, to scan
+    code: Vec<String>,
+}
+
+/// Returned to lua by usage
+#[derive(Serialize, Debug)]
+struct UsageSummary<'a> {
+    used: Vec<&'a ResourceSymbol>,
+    unused: Vec<&'a ResourceSymbol>,
+    /// The code looks up resources by name so none can be safely removed
+    dynamic_lookup: bool,
+}
+
+fn analyze(options: &UsageOptions) -> anyhow::Result<(Vec<ResourceSymbol>, ResourceUsage)> {
+    let root = get_project_root().context("Failed to get project root directory")?;
+    let resolve = |path: &str| -> PathBuf { root.join(path) };
+
+    let symbols_path = resolve(&options.symbols);
+    let text = std::fs::read_to_string(&symbols_path)
+        .context(format!("Failed to read {:?}", symbols_path))?;
+    let symbols = parse_r_txt(&text).context(format!("Failed to parse {:?}", symbols_path))?;
+
+    let mut usage = ResourceUsage::new();
+    for code in &options.code {
+        let path = resolve(code);
+        if !path.exists() {
+            anyhow::bail!("{:?} does not exist", path);
+        }
+        usage.scan_path(&path)?;
+    }
+    Ok((symbols, usage))
+}
+
+/// Finds which resources in R.txt are referenced from the compiled code. Takes a
+/// table with symbols, the path of R.txt, and code, a list of class, dex, jar or
+/// apk files or directories. Paths are relative to the project root.
+/// Returns a table with the used and unused resources
+/// # Errors
+/// Returns an error if a file cannot be read or parsed
+#[labt_lua]
+fn usage(lua: &Lua, options: mlua::Value) {
+    let options: UsageOptions = lua.from_value(options)?;
+    let (symbols, usage) = analyze(&options).map_err(MluaAnyhowWrapper::external)?;
+    let (used, unused) = usage.partition(&symbols);
+    lua.to_value(&UsageSummary {
+        used,
+        unused,
+        dynamic_lookup: usage.dynamic_lookup,
+    })
+}
+
+/// Generates resources table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to resources table fails
+/// or the underlying lua operations return errors.
+pub fn load_resources_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    usage(lua, &table)?;
+
+    lua.globals().set("resources", table)?;
+
+    Ok(())
+}
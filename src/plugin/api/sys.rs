@@ -4,6 +4,7 @@ use anyhow::Context;
 use mlua::{IntoLuaMulti, Lua, MultiValue, Table};
 
 use crate::get_project_root;
+use crate::plugin::process;
 
 use super::MluaAnyhowWrapper;
 
@@ -21,7 +22,7 @@ fn exec_command<'lua>(
     for arg in args {
         cmd.arg(arg.to_string()?);
     }
-    let status = cmd.status()?;
+    let status = process::run(lua, &mut cmd)?;
 
     (status.success(), status.code()).into_lua_multi(lua)
 }
@@ -40,7 +41,7 @@ fn exec_command_with_output<'lua>(
     for arg in args {
         cmd.arg(arg.to_string()?);
     }
-    let out = cmd.output()?;
+    let out = process::run_with_output(lua, &mut cmd)?;
     let stdout = String::from_utf8_lossy(&out.stdout).to_string();
     let stderr = String::from_utf8_lossy(&out.stderr).to_string();
 
@@ -53,7 +54,7 @@ fn exec_command_with_output<'lua>(
 ///
 /// This function will return an error if adding functions to sys table fails
 /// or the underlying lua operations return errors.
-pub fn load_sys_table(lua: &mut Lua) -> anyhow::Result<()> {
+pub fn load_sys_table(lua: &Lua) -> anyhow::Result<()> {
     let table = lua.create_table()?;
 
     // Metatables
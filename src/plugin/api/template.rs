@@ -0,0 +1,306 @@
+use std::{
+    collections::HashMap,
+    env::current_dir,
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use labt_proc_macro::labt_lua;
+use mlua::{Lua, Table};
+use tera::{Tera, Value};
+
+use crate::{config::LabToml, submodules::licenses::escape_html};
+
+use super::{git::git_info, MluaAnyhowWrapper};
+
+/// The templates of a plugin, relative to its root, if plugin.toml does not set init.templates
+pub const DEFAULT_TEMPLATES: &str = "templates/*";
+/// The template the manifest of a project is generated from, relative to its root
+pub const MANIFEST_TEMPLATE: &str = "app/AndroidManifest.xml.tera";
+/// The manifest generated from [`MANIFEST_TEMPLATE`], relative to the project root
+pub const MANIFEST_OUTPUT: &str = "app/AndroidManifest.xml";
+/// Name of the template compiled by render_string
+const STRING_TEMPLATE: &str = "__string__";
+
+/// The glob the templates of the running plugin are loaded from on first use
+pub struct TemplateGlob(pub String);
+
+/// Splits text into words at separators and lower to upper case changes
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut after_lower = false;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            after_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && after_lower {
+            words.push(std::mem::take(&mut current));
+        }
+        after_lower = c.is_lowercase() || c.is_numeric();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// `com.example.app` to `com/example/app`
+pub fn package_path(text: &str) -> String {
+    text.replace('.', "/")
+}
+
+/// `main activity` or `main_activity` to `MainActivity`
+pub fn pascal_case(text: &str) -> String {
+    words(text)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// `MainActivity` or `main activity` to `main_activity`
+pub fn snake_case(text: &str) -> String {
+    words(text)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<String>>()
+        .join("_")
+}
+
+/// A filter applying convert to a string value
+fn text_filter(name: &'static str, convert: fn(&str) -> String) -> impl tera::Filter {
+    move |value: &Value, _: &HashMap<String, Value>| {
+        let text = tera::try_get_value!(name, "value", String, value);
+        Ok(Value::String(convert(&text)))
+    }
+}
+
+/// Creates a template engine with the labt filters. The templates matching glob
+/// are loaded so that they can be rendered by name.
+///
+/// # Errors
+///
+/// Returns an error if a template fails to parse
+pub fn new_engine(glob: Option<&str>) -> anyhow::Result<Tera> {
+    let mut tera = match glob {
+        Some(glob) => Tera::new(glob).context(format!("Failed to load the templates in {glob}"))?,
+        None => Tera::default(),
+    };
+    tera.register_filter("package_path", text_filter("package_path", package_path));
+    tera.register_filter("pascal_case", text_filter("pascal_case", pascal_case));
+    tera.register_filter("snake_case", text_filter("snake_case", snake_case));
+    tera.register_filter("xml_escape", text_filter("xml_escape", escape_html));
+    Ok(tera)
+}
+
+/// Renders source as a one off template. It can include and extend the
+/// templates of engine
+///
+/// # Errors
+///
+/// Returns an error if the template fails to parse or render
+pub fn render_source(
+    engine: &Tera,
+    source: &str,
+    context: &tera::Context,
+) -> anyhow::Result<String> {
+    let mut tera = engine.clone();
+    tera.add_raw_template(STRING_TEMPLATE, source)
+        .context("Failed to parse template")?;
+    tera.render(STRING_TEMPLATE, context)
+        .context("Failed to render template")
+}
+
+/// Generates AndroidManifest.xml from the manifest template of the project at
+/// root if it has one. The template can use the `project` table of Labt.toml,
+/// `version_code` and `debug`. The file is only rewritten if its contents changed
+/// so that plugins do not see it as stale. Returns the path of the manifest.
+///
+/// # Errors
+///
+/// Returns an error if the template cannot be read or rendered or the manifest
+/// cannot be written
+pub fn generate_manifest(
+    config: &LabToml,
+    root: &Path,
+    version_code: i64,
+    debug: bool,
+) -> anyhow::Result<Option<PathBuf>> {
+    let template = root.join(MANIFEST_TEMPLATE);
+    if !template.exists() {
+        return Ok(None);
+    }
+    let source = read_to_string(&template).context(format!("Failed to read {:?}", template))?;
+    let mut context = tera::Context::new();
+    context.insert("project", &config.project);
+    context.insert("version_code", &version_code);
+    context.insert("debug", &debug);
+    let manifest = render_source(&new_engine(None)?, &source, &context)
+        .context(format!("Failed to render {:?}", template))?;
+
+    let path = root.join(MANIFEST_OUTPUT);
+    if read_to_string(&path).is_ok_and(|existing| existing == manifest) {
+        return Ok(Some(path));
+    }
+    write(&path, manifest).context(format!("Failed to write {:?}", path))?;
+    Ok(Some(path))
+}
+
+/// Returns the templates of the running plugin, loading them on first use
+fn plugin_engine(lua: &Lua) -> anyhow::Result<Tera> {
+    if let Some(tera) = lua.app_data_ref::<Tera>() {
+        return Ok(tera.clone());
+    }
+    let glob = lua
+        .app_data_ref::<TemplateGlob>()
+        .map(|glob| glob.0.clone());
+    let tera = new_engine(glob.as_deref())?;
+    lua.set_app_data(tera.clone());
+    Ok(tera)
+}
+
+fn template_context(context: Table) -> anyhow::Result<tera::Context> {
+    let mut context = tera::Context::from_serialize(context)
+        .context("Failed to serialize lua table to tera context")?;
+    // templates can use git metadata if the project is in a git repository
+    if !context.contains_key("git") {
+        if let Ok(info) = current_dir()
+            .map_err(anyhow::Error::from)
+            .and_then(|dir| git_info(&dir))
+        {
+            context.insert("git", &info);
+        }
+    }
+    Ok(context)
+}
+
+/// Renders a template of the plugin by name
+#[labt_lua]
+fn render(lua: &Lua, (name, context): (String, Table)) {
+    let tera = plugin_engine(lua).map_err(MluaAnyhowWrapper::external)?;
+    let context = template_context(context).map_err(MluaAnyhowWrapper::external)?;
+    let render = tera
+        .render(&name, &context)
+        .context(format!("Failed to render template {name}"))
+        .map_err(MluaAnyhowWrapper::external)?;
+    Ok(render)
+}
+
+/// Renders a template given as a string
+#[labt_lua]
+fn render_string(lua: &Lua, (source, context): (String, Table)) {
+    let tera = plugin_engine(lua).map_err(MluaAnyhowWrapper::external)?;
+    let context = template_context(context).map_err(MluaAnyhowWrapper::external)?;
+    render_source(&tera, &source, &context).map_err(MluaAnyhowWrapper::external)
+}
+
+/// Generates template table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to template table fails
+/// or the underlying lua operations return errors.
+pub fn load_template_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    render(lua, &table)?;
+    render_string(lua, &table)?;
+
+    lua.globals().set("template", table)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn templates_with_conditionals_loops_and_filters() {
+    assert_eq!(package_path("com.example.app"), "com/example/app");
+    assert_eq!(pascal_case("main activity"), "MainActivity");
+    assert_eq!(pascal_case("settings_screen2"), "SettingsScreen2");
+    assert_eq!(snake_case("MainActivity"), "main_activity");
+    assert_eq!(snake_case("HTTP client"), "http_client");
+    assert_eq!(
+        escape_html("Tom & Jerry's <App>"),
+        "Tom &amp; Jerry&#39;s &lt;App&gt;"
+    );
+
+    let engine = new_engine(None).unwrap();
+    let mut context = tera::Context::new();
+    context.insert("package", "com.example.app");
+    context.insert("name", "Tom & Jerry");
+    context.insert("activities", &["main", "settings screen"]);
+    context.insert("debuggable", &true);
+    let source = r#"<manifest package="{{ package }}">
+<application android:label="{{ name | xml_escape }}"{% if debuggable %} android:debuggable="true"{% endif %}>
+{%- for activity in activities %}
+<activity android:name=".{{ activity | pascal_case }}Activity"/>
+{%- endfor %}
+</application>
+</manifest>
+{{ package | package_path }}"#;
+    assert_eq!(
+        render_source(&engine, source, &context).unwrap(),
+        r#"<manifest package="com.example.app">
+<application android:label="Tom &amp; Jerry" android:debuggable="true">
+<activity android:name=".MainActivity"/>
+<activity android:name=".SettingsScreenActivity"/>
+</application>
+</manifest>
+com/example/app"#
+    );
+    assert!(render_source(&engine, "{% if %}", &context).is_err());
+}
+
+#[test]
+fn manifest_generated_from_project_template() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("manifest-template");
+    std::fs::create_dir_all(root.join("app")).unwrap();
+    let config: LabToml = toml::from_str(
+        r#"
+[project]
+name = "Tom & Jerry"
+description = ""
+version_number = 3
+version = "1.2"
+package = "com.example.app"
+"#,
+    )
+    .unwrap();
+    assert_eq!(generate_manifest(&config, &root, 3, true).unwrap(), None);
+
+    std::fs::write(
+        root.join(MANIFEST_TEMPLATE),
+        r#"<manifest package="{{ project.package }}" android:versionCode="{{ version_code }}" android:versionName="{{ project.version }}">
+<application android:label="{{ project.name | xml_escape }}"{% if debug %} android:debuggable="true"{% endif %}/>
+</manifest>"#,
+    )
+    .unwrap();
+    let path = generate_manifest(&config, &root, 7, false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(path, root.join(MANIFEST_OUTPUT));
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        r#"<manifest package="com.example.app" android:versionCode="7" android:versionName="1.2">
+<application android:label="Tom &amp; Jerry"/>
+</manifest>"#
+    );
+}
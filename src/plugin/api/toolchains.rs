@@ -0,0 +1,60 @@
+use labt_proc_macro::labt_lua;
+use mlua::{Lua, LuaSerdeExt};
+
+use crate::{
+    config::{get_config, LabToml},
+    submodules::toolchains::{resolve_toolchain, verify_toolchains, Tool, Toolchain},
+};
+
+use super::MluaAnyhowWrapper;
+
+/// The project config if running inside a project
+fn project_config() -> Option<LabToml> {
+    get_config().ok()
+}
+
+fn lua_toolchain(name: &str) -> mlua::Result<Toolchain> {
+    let tool: Tool = name.parse().map_err(MluaAnyhowWrapper::external)?;
+    resolve_toolchain(tool, project_config().as_ref()).map_err(MluaAnyhowWrapper::external)
+}
+
+/// Returns the located tool as a table with its name, path, version and source
+#[labt_lua]
+fn get(lua: &Lua, name: String) {
+    lua.to_value(&lua_toolchain(&name)?)
+}
+
+/// Returns the path of the located tool
+#[labt_lua]
+fn path(_lua: &Lua, name: String) {
+    Ok(lua_toolchain(&name)?.path.to_string_lossy().to_string())
+}
+
+/// Locates every tool with a version in the `[toolchains]` table and
+/// returns them. Errors on the first tool that does not match the config
+#[labt_lua]
+fn verify(lua: &Lua) {
+    let toolchains = match project_config() {
+        Some(config) => verify_toolchains(&config).map_err(MluaAnyhowWrapper::external)?,
+        None => Vec::new(),
+    };
+    lua.to_value(&toolchains)
+}
+
+/// Generates toolchains table and loads all its api functions
+///
+/// # Errors
+///
+/// This function will return an error if adding functions to toolchains table fails
+/// or the underlying lua operations return errors.
+pub fn load_toolchains_table(lua: &Lua) -> anyhow::Result<()> {
+    let table = lua.create_table()?;
+
+    get(lua, &table)?;
+    path(lua, &table)?;
+    verify(lua, &table)?;
+
+    lua.globals().set("toolchains", table)?;
+
+    Ok(())
+}
@@ -10,6 +10,7 @@ use labt_proc_macro::labt_lua;
 use mlua::{FromLua, Lua, Table};
 use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
 
+use crate::archive::{extract_zip, ArchiveProgress, BarProgress, ProgressWriter};
 use crate::plugin::api::MluaAnyhowWrapper;
 
 struct ZipEntry {
@@ -113,7 +114,20 @@ fn write(_lua: &Lua, table_self: Table) {
 
     let entries: Vec<ZipEntry> = table_self.get("entries")?;
 
+    let progress = BarProgress::new(format!(
+        "Archiving {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let bytes = entries
+        .iter()
+        .filter(|entry| !entry.is_dir)
+        .filter_map(|entry| entry.path.metadata().ok())
+        .map(|meta| meta.len())
+        .sum();
+    progress.start(Some(entries.len() as u64), Some(bytes));
+
     for entry in &entries {
+        progress.entry(&entry.name);
         let mut option = SimpleFileOptions::default();
 
         // set the alignment
@@ -149,9 +163,10 @@ fn write(_lua: &Lua, table_self: Table) {
                 ))
                 .map_err(MluaAnyhowWrapper::external)?;
 
-            io::copy(&mut file, &mut zip)?;
+            io::copy(&mut file, &mut ProgressWriter::new(&mut zip, &progress))?;
         }
     }
+    progress.finish();
 
     zip.finish()
         .context("Failed to correctly complete zip file ")
@@ -226,7 +241,7 @@ fn add_directory(lua: &Lua, (table_self, name): (Table, String)) {
     Ok(table_self)
 }
 
-fn new_zip_config(lua: &Lua, file: String, append: bool) -> mlua::Result<Table> {
+fn new_zip_config(lua: &Lua, file: String, append: bool) -> mlua::Result<Table<'_>> {
     let zipinfo: Table = lua.create_table()?;
     let entries: Table = lua.create_table()?;
 
@@ -275,8 +290,12 @@ fn extract(_lua: &Lua, (table_self, output, extract_all): (Table, String, Option
 
     let should_extract_all = extract_all.unwrap_or_default();
 
+    let progress = BarProgress::new(format!(
+        "Extracting {}",
+        path.file_name().unwrap_or_default().to_string_lossy()
+    ));
     if should_extract_all {
-        zip.extract(output_path)
+        extract_zip(&mut zip, output_path, &progress)
             .context(format!("Failed to extract zip archive to \"{}\" ", output))
             .map_err(MluaAnyhowWrapper::external)?;
         return Ok(());
@@ -287,7 +306,18 @@ fn extract(_lua: &Lua, (table_self, output, extract_all): (Table, String, Option
         .context("Missing field \"entries\" on self table")
         .map_err(MluaAnyhowWrapper::external)?;
 
+    let indexes: Vec<usize> = entries
+        .iter()
+        .filter_map(|entry| zip.index_for_name(&entry.name))
+        .collect();
+    let bytes = indexes
+        .into_iter()
+        .filter_map(|index| zip.by_index_raw(index).ok().map(|file| file.size()))
+        .sum();
+    progress.start(Some(entries.len() as u64), Some(bytes));
+
     for entry in &entries {
+        progress.entry(&entry.name);
         let mut zipfile = zip
             .by_name(&entry.name)
             .context(format!("Failed to locate \"{}\" in archive", entry.name))
@@ -338,11 +368,12 @@ fn extract(_lua: &Lua, (table_self, output, extract_all): (Table, String, Option
                 ))
                 .map_err(MluaAnyhowWrapper::external)?;
 
-            io::copy(&mut zipfile, &mut file)
+            io::copy(&mut zipfile, &mut ProgressWriter::new(&mut file, &progress))
                 .context("Failed to copy zip file entry bytes to output file")
                 .map_err(MluaAnyhowWrapper::external)?;
         }
     }
+    progress.finish();
 
     Ok(())
 }
@@ -375,13 +406,12 @@ fn open(lua: &Lua, file: String) {
 }
 
 /// Generates zip table and loads all its api functions
-
 ///
 /// # Errors
 ///
 /// This function will return an error if adding functions to fs table fails
 /// or the underlying lua operations return errors.
-pub fn load_zip_table(lua: &mut Lua) -> anyhow::Result<()> {
+pub fn load_zip_table(lua: &Lua) -> anyhow::Result<()> {
     let table = lua.create_table()?;
 
     new(lua, &table)?;
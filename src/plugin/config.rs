@@ -4,6 +4,7 @@ use std::{
     path::{Path, PathBuf},
     rc::Rc,
     str::FromStr,
+    time::Duration,
 };
 
 use anyhow::{bail, Context};
@@ -22,7 +23,7 @@ use crate::{
     },
 };
 
-use super::Plugin;
+use super::{api::template::DEFAULT_TEMPLATES, Plugin};
 
 pub(super) const NAME: &str = "name";
 pub(super) const VERSION: &str = "version";
@@ -38,6 +39,8 @@ pub(super) const REPO: &str = "repo";
 pub(super) const PATH: &str = "path";
 pub(super) const CHANNEL: &str = "channel";
 pub(super) const UNSAFE: &str = "unsafe";
+pub(super) const TIMEOUT: &str = "timeout";
+pub(super) const CHANGE_DETECTION: &str = "change_detection";
 pub(super) const INIT: &str = "init";
 pub(super) const TEMPLATES: &str = "templates";
 
@@ -46,6 +49,7 @@ const AAPT: &str = "aapt";
 const COMPILE: &str = "compile";
 const DEX: &str = "dex";
 const BUNDLE: &str = "bundle";
+const INSTRUMENT: &str = "instrument";
 const POST: &str = "post";
 
 /// The sdk entries that this plugin requires
@@ -139,6 +143,43 @@ pub struct PluginStage {
     /// Enable unsafe lua api
     #[serde(rename = "unsafe", default)]
     pub enable_unsafe: bool,
+    /// Maximum number of seconds this stage is allowed to run
+    pub timeout: Option<u64>,
+    /// How the inputs are checked for changes since the stage last ran
+    #[serde(default)]
+    pub change_detection: ChangeDetection,
+}
+
+/// How a build step decides whether the inputs of a plugin stage changed
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeDetection {
+    /// The stage runs if an input was modified after its outputs
+    #[default]
+    Mtime,
+    /// The stage runs if the contents of its inputs differ from the last
+    /// successful run. Touching or checking out a file does not rerun it
+    Hash,
+}
+
+impl FromStr for ChangeDetection {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mtime" => Ok(ChangeDetection::Mtime),
+            "hash" => Ok(ChangeDetection::Hash),
+            _ => bail!("Unknown change detection {s}. Expected mtime or hash"),
+        }
+    }
+}
+
+impl Display for ChangeDetection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeDetection::Mtime => write!(f, "mtime"),
+            ChangeDetection::Hash => write!(f, "hash"),
+        }
+    }
 }
 
 #[derive(Default, Debug, Deserialize, Serialize, PartialEq, Eq)]
@@ -150,6 +191,15 @@ pub struct PluginInit {
 }
 
 impl PluginToml {
+    /// The glob the templates of the plugin are loaded from
+    pub fn templates_glob(&self) -> String {
+        let templates = self
+            .init
+            .as_ref()
+            .and_then(|init| init.templates.as_deref())
+            .unwrap_or(DEFAULT_TEMPLATES);
+        self.path.join(templates).to_string_lossy().to_string()
+    }
     /// Maps PluginToml stages into their [`Plugin`] representation.
     pub fn get_steps(self) -> anyhow::Result<Vec<Plugin>> {
         let mut steps = vec![];
@@ -172,6 +222,9 @@ impl PluginToml {
                     plugin.sdk_dependencies = Rc::clone(&sdk_rc);
                     plugin.priority = s.priority;
                     plugin.unsafe_mode = self.enable_unsafe || s.enable_unsafe;
+                    plugin.timeout = s.timeout.map(Duration::from_secs);
+                    plugin.change_detection = s.change_detection;
+                    plugin.templates = Some(self.templates_glob());
                     plugin.package_paths = if let Some(package_paths) = &self.package_paths{
                             load_package_paths(package_paths, &self.path)
                         }else{
@@ -182,6 +235,7 @@ impl PluginToml {
                         // both have items, so add them to the output
                         plugin.dependents = Some((expand_globs(s.inputs.clone().unwrap()).context("Unable to expand global patterns specified by the inputs dependents")?,
                                 expand_globs(s.outputs.clone().unwrap()).context("Unable to expand global patterns specified by the outputs dependents")?));
+                        plugin.dependent_patterns = Some((s.inputs.clone().unwrap(), s.outputs.clone().unwrap()));
                     }
                     // add the plugin to the list of plugins
                     steps.push(plugin);
@@ -196,6 +250,7 @@ impl PluginToml {
             Step::COMPILE,
             Step::DEX,
             Step::BUNDLE,
+            Step::INSTRUMENT,
             Step::POST
         ];
 
@@ -212,6 +267,8 @@ enum PluginTomlErrorKind {
     ToStringErr(&'static str, Option<&'static str>, Option<usize>),
     /// Failed to convert a value to bool
     ToBoolErr(&'static str, Option<&'static str>, Option<usize>),
+    /// Failed to convert a value to an integer
+    ToIntegerErr(&'static str, Option<&'static str>),
     /// Invalid sdk string entry
     InvalidSdkKey(String, String),
     /// Invalid version string
@@ -272,6 +329,16 @@ impl Display for PluginTomlError {
             PluginTomlErrorKind::ToBoolErr(key, None, _) => {
                 write!(f, "Failed to convert {} value as boolean.", key)
             }
+            PluginTomlErrorKind::ToIntegerErr(key, Some(table)) => {
+                write!(
+                    f,
+                    "Failed to convert {} value as integer in the table \"{}\".",
+                    key, table
+                )
+            }
+            PluginTomlErrorKind::ToIntegerErr(key, None) => {
+                write!(f, "Failed to convert {} value as integer.", key)
+            }
             PluginTomlErrorKind::InvalidSdkVersionString(key) => {
                 write!(f, "Invalid version string for sdk dependency {}", key)
             }
@@ -323,6 +390,12 @@ impl Display for PluginToml {
                 if s.enable_unsafe {
                     table.insert(UNSAFE, value(true));
                 }
+                if let Some(timeout) = s.timeout {
+                    table.insert(TIMEOUT, value(timeout as i64));
+                }
+                if s.change_detection != ChangeDetection::default() {
+                    table.insert(CHANGE_DETECTION, value(s.change_detection.to_string()));
+                }
                 stages.insert(stage.to_string().as_str(), toml_edit::Item::Table(table));
             }
         };
@@ -331,6 +404,7 @@ impl Display for PluginToml {
         show_stage(Step::COMPILE);
         show_stage(Step::DEX);
         show_stage(Step::BUNDLE);
+        show_stage(Step::INSTRUMENT);
         show_stage(Step::POST);
 
         doc.insert(SDK, toml_edit::Item::Table(sdk_table));
@@ -463,12 +537,48 @@ impl FromStr for PluginToml {
                         false
                     };
 
+                    let timeout = if let Some(timeout) = stage.get(TIMEOUT) {
+                        let timeout = timeout.as_integer().ok_or_else(|| {
+                            PluginTomlError::new(PluginTomlErrorKind::ToIntegerErr(
+                                TIMEOUT,
+                                Some(STAGE),
+                            ))
+                        })?;
+                        Some(u64::try_from(timeout).context(format!(
+                            "{} for stage {} must not be negative",
+                            TIMEOUT, stage_name
+                        ))?)
+                    } else {
+                        None
+                    };
+
+                    let change_detection = if let Some(detection) = stage.get(CHANGE_DETECTION) {
+                        detection
+                            .as_str()
+                            .ok_or_else(|| {
+                                PluginTomlError::new(PluginTomlErrorKind::ToStringErr(
+                                    CHANGE_DETECTION,
+                                    Some(STAGE),
+                                    None,
+                                ))
+                            })?
+                            .parse::<ChangeDetection>()
+                            .context(format!(
+                                "Invalid {} for stage {}",
+                                CHANGE_DETECTION, stage_name
+                            ))?
+                    } else {
+                        ChangeDetection::default()
+                    };
+
                     Ok(Some(PluginStage {
                         file,
                         priority,
                         inputs,
                         outputs,
                         enable_unsafe: enabe_unsafe_stage,
+                        timeout,
+                        change_detection,
                     }))
                 } else {
                     Ok(None)
@@ -487,6 +597,7 @@ impl FromStr for PluginToml {
             map_stage(Step::COMPILE, COMPILE)?;
             map_stage(Step::DEX, DEX)?;
             map_stage(Step::BUNDLE, BUNDLE)?;
+            map_stage(Step::INSTRUMENT, INSTRUMENT)?;
             map_stage(Step::POST, POST)?;
         };
 
@@ -702,9 +813,7 @@ fn expand_globs(patterns: Vec<String>) -> anyhow::Result<Vec<PathBuf>> {
         let path = PathBuf::from(pattern);
         let path = if path.is_relative() {
             // if is a relative path, append project root instead
-            let mut root = get_project_root()
-                .context("Failed to get project root directory")?
-                .clone();
+            let mut root = get_project_root().context("Failed to get project root directory")?;
             root.push(path);
             root
         } else {
@@ -779,11 +888,13 @@ unsafe = true
 [stage.aapt]
 file="aapt.lua"
 priority=1
+change_detection="hash"
 
 # java compilation
 [stage.compile]
 file="compile.lua"
 priority=1
+timeout=600
 
 # dexing
 [stage.dex]
@@ -857,6 +968,8 @@ priority=1
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Hash,
         })
     );
     assert_eq!(
@@ -867,6 +980,8 @@ priority=1
             inputs: None,
             outputs: None,
             enable_unsafe: true,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         })
     );
     assert_eq!(
@@ -877,6 +992,8 @@ priority=1
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: Some(600),
+            change_detection: ChangeDetection::Mtime,
         })
     );
     assert_eq!(
@@ -887,6 +1004,8 @@ priority=1
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         })
     );
     assert_eq!(
@@ -897,6 +1016,8 @@ priority=1
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         })
     );
     assert_eq!(
@@ -907,6 +1028,8 @@ priority=1
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         })
     );
 }
@@ -956,6 +1079,8 @@ fn plugin_toml_to_string() {
             inputs: Some(vec![String::from("**/*.xml")]),
             outputs: Some(vec![String::from("build/res.apk")]),
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Hash,
         },
     );
 
@@ -967,6 +1092,8 @@ fn plugin_toml_to_string() {
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         },
     );
 
@@ -978,6 +1105,8 @@ fn plugin_toml_to_string() {
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: Some(600),
+            change_detection: ChangeDetection::Mtime,
         },
     );
 
@@ -989,6 +1118,8 @@ fn plugin_toml_to_string() {
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         },
     );
 
@@ -1000,6 +1131,8 @@ fn plugin_toml_to_string() {
             inputs: None,
             outputs: None,
             enable_unsafe: false,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         },
     );
 
@@ -1011,6 +1144,8 @@ fn plugin_toml_to_string() {
             inputs: None,
             outputs: None,
             enable_unsafe: true,
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
         },
     );
     let toml = r#"name = "example"
@@ -1032,10 +1167,12 @@ file = "aapt.lua"
 priority = 1
 inputs = ["**/*.xml"]
 outputs = ["build/res.apk"]
+change_detection = "hash"
 
 [stage.compile]
 file = "compile.lua"
 priority = 1
+timeout = 600
 
 [stage.dex]
 file = "dex.lua"
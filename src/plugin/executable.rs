@@ -2,25 +2,35 @@ use std::fs::read_to_string;
 use std::path::PathBuf;
 use std::process::Command;
 use std::rc::Rc;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
-use mlua::{Chunk, IntoLuaMulti, Lua, MultiValue, Table, Value};
+use mlua::{Chunk, Function, HookTriggers, IntoLuaMulti, Lua, MultiValue, Table, Value};
 
 use crate::get_project_root;
+use crate::paths::long_path;
 use crate::submodules::build::Step;
 use crate::submodules::sdk::toml_strings::REPOSITORY_NAME;
-use crate::submodules::sdk::{get_sdk_path, InstalledPackage};
+use crate::submodules::sdk::{package_dir, InstalledPackage};
 use crate::submodules::sdkmanager::ToId;
 
+use super::api::adb::load_adb_table;
+use super::api::apksign::load_apksign_table;
 use super::api::fs::load_fs_table;
-use super::api::labt::load_labt_table;
+use super::api::git::load_git_table;
+use super::api::labt::{load_labt_table, LuaHandle, CLEANUP_HOOKS};
 use super::api::log::load_log_table;
 use super::api::prompt::load_prompt_table;
+use super::api::report::load_report_table;
+use super::api::resources::load_resources_table;
 use super::api::sys::load_sys_table;
+use super::api::template::load_template_table;
+use super::api::toolchains::load_toolchains_table;
 use super::api::zip::load_zip_table;
 use super::api::MluaAnyhowWrapper;
 use super::config::{SdkEntry, CHANNEL, PATH, VERSION};
 use super::get_installed_list_hash;
+use super::process::{self, Deadline, ProcessEnv, ProcessLog};
 
 const PREFIX: &str = "sdk:";
 
@@ -34,7 +44,7 @@ impl Executable {
 
 pub struct ExecutableLua {
     build_step: Step,
-    lua: Lua,
+    lua: Rc<Lua>,
     path: PathBuf,
     package_paths: String,
     sdk: Rc<Vec<SdkEntry>>,
@@ -52,6 +62,14 @@ impl<'lua, 'a> ExecutableLua {
         } else {
             Lua::new()
         };
+        // abort running plugin code once the user presses Ctrl-C or the step times out
+        lua.set_hook(
+            HookTriggers::new().every_nth_instruction(1000),
+            |lua, _debug| process::check(lua),
+        );
+        let lua = Rc::new(lua);
+        // lets event hooks call back into the plugin
+        lua.set_app_data(LuaHandle(Rc::downgrade(&lua)));
         let paths: String = package_paths
             .iter()
             .filter_map(|p| p.to_str())
@@ -107,11 +125,10 @@ impl<'lua, 'a> ExecutableLua {
             return Ok(dir.clone());
         }
 
-        let mut sdk = get_sdk_path()?;
-        sdk.push(&package.repository_name);
-        let sdk = sdk.join(package.path.split(';').collect::<PathBuf>());
+        // the tools in deep sdk trees are run as external processes
+        let sdk = package_dir(&package.repository_name, &package.path)?;
 
-        Ok(sdk)
+        Ok(long_path(&sdk))
     }
     /// Fires the command and lets the output go where outputs go, stdout or stderr
     /// Only returns if success and exit code
@@ -130,7 +147,7 @@ impl<'lua, 'a> ExecutableLua {
         for arg in args {
             cmd.arg(arg.to_string()?);
         }
-        let status = cmd.status()?;
+        let status = process::run(lua, &mut cmd)?;
 
         (status.success(), status.code()).into_lua_multi(lua)
     }
@@ -150,7 +167,7 @@ impl<'lua, 'a> ExecutableLua {
         for arg in args {
             cmd.arg(arg.to_string()?);
         }
-        let out = cmd.output()?;
+        let out = process::run_with_output(lua, &mut cmd)?;
         let stdout = String::from_utf8_lossy(&out.stdout).to_string();
         let stderr = String::from_utf8_lossy(&out.stderr).to_string();
 
@@ -160,11 +177,10 @@ impl<'lua, 'a> ExecutableLua {
     fn build_sdk_module(
         lua: &'lua Lua,
         module: String,
-        id: String,
+        package: Rc<InstalledPackage>,
         sdk: Table<'lua>,
     ) -> mlua::Result<Table<'lua>> {
         let meta = lua.create_table()?;
-        let installed_list = get_installed_list_hash().map_err(MluaAnyhowWrapper::external)?;
         let module = if module.starts_with(PREFIX) {
             module.strip_prefix(PREFIX).unwrap()
         } else {
@@ -177,16 +193,8 @@ impl<'lua, 'a> ExecutableLua {
             .map(|s| s.trim_matches('.'))
             .collect();
 
-        let package = if let Some(package) = installed_list.get(&id) {
-            package
-        } else {
-            return Err(mlua::Error::external(format!(
-                "Trying to index a package \"{}\" that is not installed.",
-                id
-            )));
-        };
         // obtain directory of this package
-        let dir = Self::get_package_directory(package)
+        let dir = Self::get_package_directory(&package)
             .context(format!(
                 "Failed to obtain sdk install directory for package: {}",
                 package.to_id()
@@ -225,6 +233,7 @@ impl<'lua, 'a> ExecutableLua {
                 )));
             }
             let dir = dir.clone();
+            let package = Rc::clone(&package);
             lua.create_function(move |lua, args: MultiValue| {
                 let key = key.clone();
                 let mut dir = dir.clone();
@@ -241,9 +250,9 @@ impl<'lua, 'a> ExecutableLua {
                     return Err(mlua::Error::external("Command not found."));
                 }
                 if needs_output {
-                    Self::exec_sdk_command_with_output(lua, args, package, dir)
+                    Self::exec_sdk_command_with_output(lua, args, &package, dir)
                 } else {
-                    Self::exec_sdk_command(lua, args, package, dir)
+                    Self::exec_sdk_command(lua, args, &package, dir)
                 }
             })
         })?;
@@ -270,7 +279,7 @@ impl<'lua, 'a> ExecutableLua {
                     };
                     // only allow explicitly declared sdk modules in plugin.toml
                     if let Some(sdk) = sdk.iter().find(|s| s.name.eq(module)).cloned() {
-                        if let Some(package) = installed_list.get(&sdk.to_id()) {
+                        if let Some(package) = installed_list.get(&sdk.to_id()).cloned().map(Rc::new) {
                             Ok(
                                     Value::Function(lua.create_function(move |lua, module:String | {
                                         let table = lua.create_table()?;
@@ -280,7 +289,7 @@ impl<'lua, 'a> ExecutableLua {
                                         table.set(VERSION, package.version.to_string())?;
                                         table.set(PATH, package.path.clone())?;
                                         table.set(CHANNEL, package.channel.to_string())?;
-                                        Self::build_sdk_module(lua, module, package.to_id(), table)
+                                        Self::build_sdk_module(lua, module, Rc::clone(&package), table)
                                     })?),
                             )
                         } else {
@@ -318,12 +327,79 @@ impl<'lua, 'a> ExecutableLua {
     }
     pub fn add_function(&mut self) {}
     pub fn load_api_tables(&mut self) -> Result<()> {
-        load_labt_table(&mut self.lua).context("Failed to add labt table into lua context")?;
-        load_fs_table(&mut self.lua).context("Failed to add fs table into lua context")?;
-        load_log_table(&mut self.lua).context("Failed to add log table into lua context")?;
-        load_zip_table(&mut self.lua).context("Failed to add zip table into lua context")?;
-        load_sys_table(&mut self.lua).context("Failed to add sys table into lua context")?;
-        load_prompt_table(&mut self.lua).context("Failed to add prompt table into lua context")?;
+        load_labt_table(&self.lua).context("Failed to add labt table into lua context")?;
+        load_fs_table(&self.lua).context("Failed to add fs table into lua context")?;
+        load_log_table(&self.lua).context("Failed to add log table into lua context")?;
+        load_zip_table(&self.lua).context("Failed to add zip table into lua context")?;
+        load_sys_table(&self.lua).context("Failed to add sys table into lua context")?;
+        load_prompt_table(&self.lua).context("Failed to add prompt table into lua context")?;
+        load_git_table(&self.lua).context("Failed to add git table into lua context")?;
+        load_toolchains_table(&self.lua)
+            .context("Failed to add toolchains table into lua context")?;
+        load_adb_table(&self.lua).context("Failed to add adb table into lua context")?;
+        load_apksign_table(&self.lua).context("Failed to add apksign table into lua context")?;
+        load_report_table(&self.lua).context("Failed to add report table into lua context")?;
+        load_resources_table(&self.lua)
+            .context("Failed to add resources table into lua context")?;
+        load_template_table(&self.lua).context("Failed to add template table into lua context")?;
+        Ok(())
+    }
+    /// Limits how long the plugin code may run from now on. Lua code and child
+    /// processes spawned through the api are stopped once the timeout elapses.
+    pub fn set_timeout(&self, timeout: Option<Duration>) {
+        match timeout {
+            Some(timeout) => {
+                self.lua.set_app_data(Deadline::new(timeout));
+            }
+            None => {
+                self.lua.remove_app_data::<Deadline>();
+            }
+        }
+    }
+    /// Routes the output of processes spawned by the plugin through the given log
+    pub fn set_process_log(&self, log: ProcessLog) {
+        self.lua.set_app_data(log);
+    }
+    /// Sets the environment variables of the processes spawned by the plugin
+    pub fn set_process_env(&self, env: ProcessEnv) {
+        self.lua.set_app_data(env);
+    }
+    /// Returns true if the plugin ran past its timeout
+    pub fn timed_out(&self) -> bool {
+        self.lua
+            .app_data_ref::<Deadline>()
+            .is_some_and(|deadline| deadline.exceeded())
+    }
+    /// Runs the cleanup functions registered by the plugin through `labt.on_cleanup`
+    /// in reverse order of registration. All functions are called even if some fail.
+    pub fn run_cleanup_hooks(&self) -> Result<()> {
+        // the interrupt hook would abort the cleanup functions too
+        self.lua.remove_hook();
+        let hooks: Option<Table> = self
+            .lua
+            .named_registry_value(CLEANUP_HOOKS)
+            .context("Failed to get registered cleanup functions")?;
+        let hooks = match hooks {
+            Some(hooks) => hooks,
+            None => return Ok(()),
+        };
+
+        let functions: Vec<Function> = hooks
+            .sequence_values()
+            .collect::<mlua::Result<Vec<Function>>>()
+            .context("Failed to read registered cleanup functions")?;
+        let mut errors = Vec::new();
+        for function in functions.iter().rev() {
+            if let Err(err) = function.call::<_, ()>(()) {
+                errors.push(err.to_string());
+            }
+        }
+        if !errors.is_empty() {
+            return Err(anyhow!(
+                "Plugin cleanup functions failed:\n{}",
+                errors.join("\n")
+            ));
+        }
         Ok(())
     }
     pub fn get_lua(&self) -> &Lua {
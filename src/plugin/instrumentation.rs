@@ -0,0 +1,241 @@
+use std::time::{Duration, Instant};
+
+use super::report::{TestCase, TestCounts, TestReport, TestStatus};
+
+const STATUS: &str = "INSTRUMENTATION_STATUS: ";
+const STATUS_CODE: &str = "INSTRUMENTATION_STATUS_CODE: ";
+const RESULT: &str = "INSTRUMENTATION_RESULT: ";
+const CODE: &str = "INSTRUMENTATION_CODE: ";
+const FAILED: &str = "INSTRUMENTATION_FAILED: ";
+
+/// Status codes reported by `am instrument -r` for each test
+const CODE_START: i32 = 1;
+const CODE_OK: i32 = 0;
+const CODE_ERROR: i32 = -1;
+const CODE_FAILURE: i32 = -2;
+const CODE_IGNORED: i32 = -3;
+const CODE_ASSUMPTION_FAILURE: i32 = -4;
+
+/// The tests of an instrumentation run
+#[derive(Debug, Clone, Default)]
+pub struct InstrumentationResults {
+    pub cases: Vec<TestCase>,
+    /// Set if the instrumentation did not run to completion e.g. the process crashed
+    pub crash: Option<String>,
+}
+
+impl InstrumentationResults {
+    pub fn counts(&self) -> TestCounts {
+        TestCounts::of(self.cases.iter())
+    }
+    /// True if the run completed and no test failed
+    pub fn success(&self) -> bool {
+        self.crash.is_none() && self.counts().success()
+    }
+    /// Groups the tests into a report with a suite per test class
+    pub fn report(&self, name: &str) -> TestReport {
+        let mut report = TestReport::new(name);
+        for case in &self.cases {
+            report.add(&case.class, case.clone());
+        }
+        report
+    }
+}
+
+/// Parses the raw output of `am instrument -r` line by line
+#[derive(Debug, Default)]
+pub struct InstrumentationParser {
+    /// Key values of the status or result being read
+    values: Vec<(String, String)>,
+    /// The test started but not yet finished
+    running: Option<(String, String, Instant)>,
+    results: InstrumentationResults,
+}
+
+impl InstrumentationParser {
+    pub fn new() -> Self {
+        InstrumentationParser::default()
+    }
+    fn value(&self, key: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value.as_str())
+    }
+    fn push_value(&mut self, pair: &str) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        self.values.push((key.to_string(), value.to_string()));
+    }
+    /// Feeds a line of output. Returns the test case if the line finished one.
+    pub fn line(&mut self, line: &str) -> Option<&TestCase> {
+        let line = line.trim_end_matches('\r');
+        if let Some(pair) = line.strip_prefix(STATUS) {
+            self.push_value(pair);
+        } else if let Some(pair) = line.strip_prefix(RESULT) {
+            self.push_value(pair);
+        } else if let Some(code) = line.strip_prefix(STATUS_CODE) {
+            let code = code.trim().parse::<i32>().unwrap_or(CODE_ERROR);
+            return self.status(code);
+        } else if line.starts_with(CODE) {
+            // the run finished, the remaining values are the result bundle
+            if let Some(message) = self.value("shortMsg") {
+                self.results.crash = Some(message.to_string());
+            }
+            self.values.clear();
+        } else if let Some(reason) = line.strip_prefix(FAILED) {
+            self.results.crash = Some(format!("Instrumentation failed: {}", reason.trim()));
+        } else if let Some((_, value)) = self.values.last_mut() {
+            // continuation of a multi line value e.g. a stack trace
+            value.push('\n');
+            value.push_str(line);
+        }
+        None
+    }
+    fn status(&mut self, code: i32) -> Option<&TestCase> {
+        let class = self.value("class").unwrap_or_default().to_string();
+        let name = self.value("test").unwrap_or_default().to_string();
+        let stack = self
+            .value("stack")
+            .map(|stack| stack.trim_end().to_string());
+        self.values.clear();
+
+        if code == CODE_START {
+            self.running = Some((class, name, Instant::now()));
+            return None;
+        }
+        let duration = match self.running.take() {
+            Some((c, n, started)) if c == class && n == name => started.elapsed(),
+            _ => Duration::ZERO,
+        };
+        let status = match code {
+            CODE_OK => TestStatus::Passed,
+            CODE_FAILURE => TestStatus::Failed,
+            CODE_IGNORED | CODE_ASSUMPTION_FAILURE => TestStatus::Skipped,
+            _ => TestStatus::Error,
+        };
+        self.results.cases.push(TestCase {
+            class,
+            name,
+            status,
+            duration,
+            message: stack,
+        });
+        self.results.cases.last()
+    }
+    /// Finishes parsing. A test still running when the instrumentation
+    /// crashed is reported as an error.
+    pub fn finish(mut self) -> InstrumentationResults {
+        if let Some((class, name, started)) = self.running.take() {
+            let message = self
+                .results
+                .crash
+                .clone()
+                .unwrap_or_else(|| String::from("Test did not finish"));
+            self.results.cases.push(TestCase {
+                class,
+                name,
+                status: TestStatus::Error,
+                duration: started.elapsed(),
+                message: Some(message),
+            });
+        } else if self.results.crash.is_none() && !self.values.is_empty() {
+            // output ended before INSTRUMENTATION_CODE
+            self.results.crash = self.value("shortMsg").map(|message| message.to_string());
+        }
+        self.results
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn instrumentation_output_parsing() {
+    let output = "INSTRUMENTATION_STATUS: class=com.example.MainTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: id=AndroidJUnitRunner
+INSTRUMENTATION_STATUS: numtests=3
+INSTRUMENTATION_STATUS: stream=
+com.example.MainTest:
+INSTRUMENTATION_STATUS: test=opens
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.example.MainTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: id=AndroidJUnitRunner
+INSTRUMENTATION_STATUS: numtests=3
+INSTRUMENTATION_STATUS: stream=.
+INSTRUMENTATION_STATUS: test=opens
+INSTRUMENTATION_STATUS_CODE: 0
+INSTRUMENTATION_STATUS: class=com.example.MainTest
+INSTRUMENTATION_STATUS: test=adds
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.example.MainTest
+INSTRUMENTATION_STATUS: stack=java.lang.AssertionError: expected:<4> but was:<5>
+\tat org.junit.Assert.fail(Assert.java:89)
+
+INSTRUMENTATION_STATUS: stream=
+Error in adds(com.example.MainTest):
+INSTRUMENTATION_STATUS: test=adds
+INSTRUMENTATION_STATUS_CODE: -2
+INSTRUMENTATION_STATUS: class=com.example.MainTest
+INSTRUMENTATION_STATUS: test=ignored
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.example.MainTest
+INSTRUMENTATION_STATUS: test=ignored
+INSTRUMENTATION_STATUS_CODE: -3
+INSTRUMENTATION_RESULT: stream=
+
+Time: 0.12
+
+FAILURES!!!
+Tests run: 2,  Failures: 1
+
+INSTRUMENTATION_CODE: -1
+";
+    let mut parser = InstrumentationParser::new();
+    let mut finished = Vec::new();
+    for line in output.lines() {
+        if let Some(case) = parser.line(line) {
+            finished.push(format!("{}#{} {}", case.class, case.name, case.status));
+        }
+    }
+    assert_eq!(
+        finished,
+        vec![
+            "com.example.MainTest#opens passed",
+            "com.example.MainTest#adds failed",
+            "com.example.MainTest#ignored skipped"
+        ]
+    );
+    let results = parser.finish();
+    assert!(!results.success());
+    assert_eq!(results.crash, None);
+    assert_eq!(
+        results.cases[1].message.as_deref(),
+        Some("java.lang.AssertionError: expected:<4> but was:<5>\n\tat org.junit.Assert.fail(Assert.java:89)")
+    );
+
+    let report = results.report("com.example.test");
+    assert_eq!(report.suites.len(), 1);
+    assert_eq!(report.suites[0].name, "com.example.MainTest");
+    assert_eq!(report.counts().skipped, 1);
+
+    let mut parser = InstrumentationParser::new();
+    for line in [
+        "INSTRUMENTATION_STATUS: class=com.example.MainTest",
+        "INSTRUMENTATION_STATUS: test=crashes",
+        "INSTRUMENTATION_STATUS_CODE: 1",
+        "INSTRUMENTATION_RESULT: shortMsg=Process crashed.",
+        "INSTRUMENTATION_CODE: 0",
+    ] {
+        parser.line(line);
+    }
+    let results = parser.finish();
+    assert_eq!(results.crash.as_deref(), Some("Process crashed."));
+    assert_eq!(results.cases[0].status, TestStatus::Error);
+    assert_eq!(
+        results.cases[0].message.as_deref(),
+        Some("Process crashed.")
+    );
+}
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf, rc::Rc, sync::OnceLock};
+use std::{collections::HashMap, path::PathBuf, rc::Rc, time::Duration};
 
 use anyhow::Context;
 
@@ -14,47 +14,34 @@ use crate::{
 };
 
 use self::{
-    config::{PluginToml, SdkEntry},
+    api::template::TemplateGlob,
+    config::{ChangeDetection, PluginToml, SdkEntry},
     executable::ExecutableLua,
 };
 
 pub mod api;
 pub mod config;
 pub mod executable;
-
-/// A cached value of the InstalledList. It is initialized by get installed list
-static INSTALLED_LIST: OnceLock<InstalledList> = OnceLock::new();
-/// A cached value of the InstalledList in form of a hash map. It is initialized by get_installed_list_hash
-static INSTALLED_LIST_HASH: OnceLock<HashMap<String, &InstalledPackage>> = OnceLock::new();
-
-/// Returns the list of installed packages. Caches the return value for subsequent calls.
-/// Returns an error if we fail to parse installed list toml
-pub(super) fn get_installed_list() -> anyhow::Result<&'static InstalledList> {
-    if let Some(list) = INSTALLED_LIST.get() {
-        return Ok(list);
-    }
-    let list =
-        InstalledList::parse_from_sdk().context("Failed to parse Installed sdk packages list.")?;
-    Ok(INSTALLED_LIST.get_or_init(|| list))
-}
-
-/// Returns a hashmap of installed packages. Good for fast indexing.
-/// Returns an error if we fail to get underlying installed list from `get_installed_list`
-pub(super) fn get_installed_list_hash(
-) -> anyhow::Result<&'static HashMap<String, &'static InstalledPackage>> {
-    if let Some(list) = INSTALLED_LIST_HASH.get() {
-        return Ok(list);
-    }
-
+pub mod instrumentation;
+pub mod process;
+pub mod provenance;
+pub mod report;
+pub mod resources;
+pub mod trace;
+
+/// Returns a hashmap of installed packages by id. Good for fast indexing.
+/// The list is read from the sdk on every call as packages can be installed in
+/// between. Returns an error if we fail to parse installed list toml
+pub(super) fn get_installed_list_hash() -> anyhow::Result<HashMap<String, InstalledPackage>> {
     let installed_list =
-        get_installed_list().context("Failed to get installed sdk packages list.")?;
+        InstalledList::parse_from_sdk().context("Failed to parse Installed sdk packages list.")?;
 
     let mut list = HashMap::with_capacity(installed_list.packages.len());
-    for package in &installed_list.packages {
+    for package in installed_list.packages {
         list.insert(package.to_id(), package);
     }
 
-    Ok(INSTALLED_LIST_HASH.get_or_init(|| list))
+    Ok(list)
 }
 
 #[derive(Debug)]
@@ -66,12 +53,21 @@ pub struct Plugin {
     pub priority: i32,
     /// The files to check for changes during a build step
     pub dependents: Option<(Vec<PathBuf>, Vec<PathBuf>)>,
+    /// The input and output globs of dependents, expanded again by the build cache
+    /// as earlier steps create files that match them
+    pub dependent_patterns: Option<(Vec<String>, Vec<String>)>,
     /// package paths
     pub package_paths: Vec<PathBuf>,
     /// Unsafe mode enabled for this plugin
     pub unsafe_mode: bool,
     /// List of sdk modules to load
     pub sdk_dependencies: Rc<Vec<SdkEntry>>,
+    /// Maximum time this step is allowed to run
+    pub timeout: Option<Duration>,
+    /// How the dependents are checked for changes
+    pub change_detection: ChangeDetection,
+    /// The glob template.render loads the plugin templates from
+    pub templates: Option<String>,
 }
 
 impl Plugin {
@@ -83,9 +79,13 @@ impl Plugin {
             step,
             priority: 0,
             dependents: None,
+            dependent_patterns: None,
             package_paths: vec![],
             unsafe_mode: false,
             sdk_dependencies: Rc::new(Vec::default()),
+            timeout: None,
+            change_detection: ChangeDetection::Mtime,
+            templates: None,
         }
     }
     pub fn load(&self) -> anyhow::Result<ExecutableLua> {
@@ -100,6 +100,9 @@ impl Plugin {
             .context("Failed to inject LABt android sdk loader to lua require module.")?;
         exe.load_api_tables()
             .context("Error injecting api tables into lua context")?;
+        if let Some(templates) = &self.templates {
+            exe.get_lua().set_app_data(TemplateGlob(templates.clone()));
+        }
         Ok(exe)
     }
 }
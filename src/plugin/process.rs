@@ -0,0 +1,440 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    fs::{create_dir_all, File},
+    io::{BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Output, Stdio},
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use mlua::Lua;
+
+use crate::interrupt::{is_interrupted, Interrupted};
+
+/// How often a running child process is checked for completion, timeout or cancellation
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// The time by which the currently running plugin step must finish.
+/// Stored in the lua app data of the plugin being executed.
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    pub at: Instant,
+    pub timeout: Duration,
+}
+
+impl Deadline {
+    pub fn new(timeout: Duration) -> Self {
+        Deadline {
+            at: Instant::now() + timeout,
+            timeout,
+        }
+    }
+    pub fn exceeded(&self) -> bool {
+        Instant::now() >= self.at
+    }
+}
+
+/// Returned when a plugin step runs longer than its configured timeout
+#[derive(thiserror::Error, Debug)]
+#[error("Plugin step exceeded its timeout of {}s", .0.as_secs())]
+pub struct TimedOut(pub Duration);
+
+/// Environment variables set on the processes spawned by a plugin, from `[env]` in
+/// Labt.toml. Stored in the lua app data of the plugin being executed.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessEnv {
+    vars: BTreeMap<String, String>,
+    /// The values of the secret variables, longest first so that a secret
+    /// containing another is masked whole
+    secrets: Arc<Vec<String>>,
+    secret_names: BTreeSet<String>,
+}
+
+impl ProcessEnv {
+    pub fn set(&mut self, name: String, value: String) {
+        self.vars.insert(name, value);
+    }
+    /// Sets a variable whose value is masked in the logs
+    pub fn set_secret(&mut self, name: String, value: String) {
+        if !value.is_empty() {
+            let secrets = Arc::make_mut(&mut self.secrets);
+            secrets.push(value.clone());
+            secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+        }
+        self.secret_names.insert(name.clone());
+        self.vars.insert(name, value);
+    }
+    pub fn vars(&self) -> &BTreeMap<String, String> {
+        &self.vars
+    }
+    /// True if name was set with [`ProcessEnv::set_secret`]
+    pub fn is_secret(&self, name: &str) -> bool {
+        self.secret_names.contains(name)
+    }
+    /// Replaces the values of the secret variables in text with `***`
+    pub fn mask<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut masked = Cow::Borrowed(text);
+        for secret in self.secrets.iter() {
+            if masked.contains(secret.as_str()) {
+                masked = Cow::Owned(masked.replace(secret.as_str(), "***"));
+            }
+        }
+        masked
+    }
+    /// Sets the variables on cmd, except those the caller already set on it
+    fn apply(&self, cmd: &mut Command) {
+        let set: Vec<_> = cmd.get_envs().map(|(name, _)| name.to_owned()).collect();
+        for (name, value) in &self.vars {
+            if !set.iter().any(|set| set.as_os_str() == name.as_str()) {
+                cmd.env(name, value);
+            }
+        }
+    }
+}
+
+/// Replaces the secrets of the plugin [`ProcessEnv`] in text with `***`
+pub fn mask_secrets<'a>(lua: &Lua, text: &'a str) -> Cow<'a, str> {
+    match lua.app_data_ref::<ProcessEnv>() {
+        Some(env) => env.mask(text),
+        None => Cow::Borrowed(text),
+    }
+}
+
+/// Sets the variables of the plugin [`ProcessEnv`] on the command
+fn apply_env(lua: &Lua, cmd: &mut Command) {
+    if let Some(env) = lua.app_data_ref::<ProcessEnv>() {
+        env.apply(cmd);
+    }
+}
+
+/// Where the output of processes spawned by a plugin goes. Stored in the lua
+/// app data of the plugin being executed. Each output line is logged with the
+/// plugin prefix and written to the plugin log file.
+#[derive(Clone)]
+pub struct ProcessLog {
+    prefix: String,
+    file: Option<Arc<Mutex<File>>>,
+    /// Its secrets are masked in the logged lines
+    env: ProcessEnv,
+}
+
+impl ProcessLog {
+    /// Creates a process log, truncating the log file at path if given
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the log file cannot be created
+    pub fn new(prefix: String, path: Option<PathBuf>) -> std::io::Result<Self> {
+        let file = match path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    create_dir_all(parent)?;
+                }
+                Some(Arc::new(Mutex::new(File::create(path)?)))
+            }
+            None => None,
+        };
+        Ok(ProcessLog {
+            prefix,
+            file,
+            env: ProcessEnv::default(),
+        })
+    }
+    /// Masks the secrets of env in the logged lines
+    pub fn with_env(mut self, env: &ProcessEnv) -> Self {
+        self.env = env.clone();
+        self
+    }
+    fn write_file(&self, line: &str) {
+        if let Some(file) = &self.file {
+            if let Ok(mut file) = file.lock() {
+                let _ = writeln!(file, "{}", self.env.mask(line));
+            }
+        }
+    }
+    /// Logs a line of child output and persists it to the log file
+    fn line(&self, line: &str, stderr: bool) {
+        let line = self.env.mask(line);
+        if stderr {
+            warn!(target: &self.prefix, "{line}");
+        } else {
+            info!(target: &self.prefix, "{line}");
+        }
+        self.write_file(&line);
+    }
+    /// Persists captured output to the log file without logging it
+    fn persist(&self, output: &[u8]) {
+        for line in String::from_utf8_lossy(output).lines() {
+            self.write_file(line);
+        }
+    }
+}
+
+/// Reads the pipe line by line forwarding each line to the process log
+fn forward_lines<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    log: ProcessLog,
+    stderr: bool,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let pipe = match pipe {
+            Some(pipe) => pipe,
+            None => return,
+        };
+        let mut reader = BufReader::new(pipe);
+        let mut buf = Vec::new();
+        while let Ok(read) = reader.read_until(b'\n', &mut buf) {
+            if read == 0 {
+                break;
+            }
+            let line = String::from_utf8_lossy(&buf);
+            log.line(line.trim_end_matches(['\r', '\n']), stderr);
+            buf.clear();
+        }
+    })
+}
+
+/// Returns an error if the user pressed Ctrl-C or the plugin step deadline passed
+///
+/// # Errors
+///
+/// Returns [`Interrupted`] or [`TimedOut`] as a lua external error
+pub fn check(lua: &Lua) -> mlua::Result<()> {
+    if is_interrupted() {
+        return Err(mlua::Error::external(Interrupted));
+    }
+    if let Some(deadline) = lua.app_data_ref::<Deadline>() {
+        if deadline.exceeded() {
+            return Err(mlua::Error::external(TimedOut(deadline.timeout)));
+        }
+    }
+    Ok(())
+}
+
+/// Waits for the child to exit while watching for cancellation and the plugin step deadline.
+/// The child is killed if either happens.
+fn wait_child(lua: &Lua, child: &mut Child) -> mlua::Result<ExitStatus> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if let Err(err) = check(lua) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(err);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Spawns the command and waits for it to exit. If the plugin has a [`ProcessLog`] the output
+/// is routed through it, otherwise it goes to the inherited stdout/stderr.
+///
+/// # Errors
+///
+/// Returns an error if spawning fails, or if the step was cancelled or timed out
+pub fn run(lua: &Lua, cmd: &mut Command) -> mlua::Result<ExitStatus> {
+    apply_env(lua, cmd);
+    let log = lua.app_data_ref::<ProcessLog>().map(|log| log.clone());
+    let log = match log {
+        Some(log) => log,
+        None => {
+            let mut child = cmd.spawn()?;
+            return wait_child(lua, &mut child);
+        }
+    };
+
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let stdout = forward_lines(child.stdout.take(), log.clone(), false);
+    let stderr = forward_lines(child.stderr.take(), log, true);
+
+    let status = wait_child(lua, &mut child)?;
+    let _ = stdout.join();
+    let _ = stderr.join();
+    Ok(status)
+}
+
+/// Spawns the command capturing its stdout and stderr and waits for it to exit.
+///
+/// # Errors
+///
+/// Returns an error if spawning fails, or if the step was cancelled or timed out
+pub fn run_with_output(lua: &Lua, cmd: &mut Command) -> mlua::Result<Output> {
+    apply_env(lua, cmd);
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    // drain the pipes while waiting so that the child does not block on a full pipe
+    let read_pipe = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = read_pipe(
+        child
+            .stdout
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+    let stderr = read_pipe(
+        child
+            .stderr
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+
+    let status = wait_child(lua, &mut child)?;
+    let output = Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    };
+    if let Some(log) = lua.app_data_ref::<ProcessLog>() {
+        log.persist(&output.stdout);
+        log.persist(&output.stderr);
+    }
+    Ok(output)
+}
+
+/// Spawns the command and passes each line of its stdout to on_line as it is
+/// printed. The lines are persisted to the [`ProcessLog`] file if the plugin has one
+/// and stderr is routed through it. The child is killed if on_line returns an error.
+///
+/// # Errors
+///
+/// Returns an error if spawning fails, on_line fails, or if the step was cancelled or timed out
+pub fn run_streaming(
+    lua: &Lua,
+    cmd: &mut Command,
+    mut on_line: impl FnMut(&str) -> mlua::Result<()>,
+) -> mlua::Result<ExitStatus> {
+    apply_env(lua, cmd);
+    let log = lua.app_data_ref::<ProcessLog>().map(|log| log.clone());
+    let stderr_pipe = if log.is_some() {
+        Stdio::piped()
+    } else {
+        Stdio::inherit()
+    };
+    let mut child = cmd.stdout(Stdio::piped()).stderr(stderr_pipe).spawn()?;
+    let stderr = log
+        .clone()
+        .map(|log| forward_lines(child.stderr.take(), log, true));
+
+    let (sender, receiver) = mpsc::channel::<String>();
+    let stdout = child.stdout.take();
+    thread::spawn(move || {
+        let Some(pipe) = stdout else {
+            return;
+        };
+        let mut reader = BufReader::new(pipe);
+        let mut buf = Vec::new();
+        while let Ok(read) = reader.read_until(b'\n', &mut buf) {
+            if read == 0 {
+                break;
+            }
+            let line = String::from_utf8_lossy(&buf);
+            if sender
+                .send(line.trim_end_matches(['\r', '\n']).to_string())
+                .is_err()
+            {
+                break;
+            }
+            buf.clear();
+        }
+    });
+    let mut handle = |line: String| -> mlua::Result<()> {
+        if let Some(log) = &log {
+            log.write_file(&line);
+        }
+        on_line(&line)
+    };
+
+    let status = loop {
+        let received = match receiver.recv_timeout(POLL_INTERVAL) {
+            Ok(line) => handle(line),
+            Err(RecvTimeoutError::Timeout) => Ok(()),
+            Err(RecvTimeoutError::Disconnected) => {
+                thread::sleep(POLL_INTERVAL);
+                Ok(())
+            }
+        };
+        if let Err(err) = received.and_then(|_| check(lua)) {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(err);
+        }
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+    };
+    // the reader is not joined as a daemon started by the child may hold the pipe open
+    while let Ok(line) = receiver.recv_timeout(POLL_INTERVAL) {
+        handle(line)?;
+    }
+    if let Some(stderr) = stderr {
+        let _ = stderr.join();
+    }
+    Ok(status)
+}
+
+#[cfg(unix)]
+#[test]
+fn process_deadline_kills_child() {
+    let lua = Lua::new();
+    let output = run_with_output(&lua, Command::new("echo").arg("labt")).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "labt");
+
+    lua.set_app_data(Deadline::new(Duration::from_millis(100)));
+    let start = Instant::now();
+    let err = run(&lua, Command::new("sleep").arg("10")).unwrap_err();
+    assert!(start.elapsed() < Duration::from_secs(5));
+    assert!(err.to_string().contains("timeout"));
+}
+
+#[cfg(unix)]
+#[test]
+fn process_output_persisted_to_log() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("process-log").join("test.log");
+    let lua = Lua::new();
+    lua.set_app_data(ProcessLog::new(String::from("test"), Some(path.clone())).unwrap());
+
+    let status = run(
+        &lua,
+        Command::new("sh").args(["-c", "echo out; echo err >&2"]),
+    )
+    .unwrap();
+    assert!(status.success());
+    let output = run_with_output(&lua, Command::new("echo").arg("captured")).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "captured");
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let mut lines: Vec<&str> = log.lines().collect();
+    lines.sort();
+    assert_eq!(lines, vec!["captured", "err", "out"]);
+
+    let mut streamed = Vec::new();
+    let status = run_streaming(
+        &lua,
+        Command::new("sh").args(["-c", "echo one; echo two"]),
+        |line| {
+            streamed.push(line.to_string());
+            Ok(())
+        },
+    )
+    .unwrap();
+    assert!(status.success());
+    assert_eq!(streamed, vec!["one", "two"]);
+}
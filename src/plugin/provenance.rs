@@ -0,0 +1,150 @@
+use std::{
+    collections::BTreeMap,
+    fs::{read, read_to_string, write},
+    path::Path,
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+
+/// File in the plugin home directory recording where each installed version came from
+pub const PROVENANCE_FILE: &str = "provenance.toml";
+
+/// Where an installed plugin version was obtained from
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The url the plugin was fetched from
+    pub source: String,
+    /// The git tag that was checked out
+    pub reference: Option<String>,
+    /// The commit the reference resolved to at install time
+    pub commit: Option<String>,
+    /// sha1 digest of the installed plugin files
+    pub digest: String,
+    /// sha256 of the archive each sdk package of the plugin was installed from,
+    /// by package id
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub artifacts: BTreeMap<String, String>,
+}
+
+/// Provenance of all installed versions of a plugin
+/// ```toml
+/// [versions."v0.1.0"]
+/// source = "https://gitlab.com/lab-tool/plugins/labt-java"
+/// reference = "v0.1.0"
+/// commit = "1f0c..."
+/// digest = "9a3e..."
+///
+/// [versions."v0.1.0".artifacts]
+/// "google:build-tools;34.0.0:34.0.0:stable" = "5d1c..."
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ProvenanceFile {
+    #[serde(default)]
+    pub versions: BTreeMap<String, Provenance>,
+}
+
+fn hash_directory(root: &Path, dir: &Path, hasher: &mut Sha1) -> anyhow::Result<()> {
+    let mut entries: Vec<_> = dir
+        .read_dir()
+        .context(format!("Failed to list {:?}", dir))?
+        .flatten()
+        .map(|entry| entry.path())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        // the git metadata changes on every fetch so it is not part of the plugin contents
+        if path.file_name().is_some_and(|name| name == ".git") {
+            continue;
+        }
+        if path.is_dir() {
+            hash_directory(root, &path, hasher)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            hasher.update(relative.to_string_lossy().replace('\\', "/").as_bytes());
+            hasher.update(read(&path).context(format!("Failed to read {:?}", path))?);
+        }
+    }
+    Ok(())
+}
+
+/// Computes a sha1 digest over the relative paths and contents of all files
+/// in the plugin directory, ignoring git metadata.
+///
+/// # Errors
+///
+/// Returns an error if a file cannot be read
+pub fn directory_digest(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha1::new();
+    hash_directory(path, path, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Reads the provenance file from the plugin home directory. Returns
+/// an empty record if the file does not exist.
+///
+/// # Errors
+///
+/// Returns an error if the file exists but cannot be read or parsed
+pub fn read_provenance(plugin_home: &Path) -> anyhow::Result<ProvenanceFile> {
+    let path = plugin_home.join(PROVENANCE_FILE);
+    if !path.exists() {
+        return Ok(ProvenanceFile::default());
+    }
+    let toml_string = read_to_string(&path).context(format!("Failed to read {:?}", path))?;
+    toml::from_str(&toml_string).context(format!("Failed to parse {:?}", path))
+}
+
+/// Records the provenance of an installed plugin version
+///
+/// # Errors
+///
+/// Returns an error if reading or writing the provenance file fails
+pub fn record_provenance(
+    plugin_home: &Path,
+    version: &str,
+    provenance: Provenance,
+) -> anyhow::Result<()> {
+    let mut file = read_provenance(plugin_home)?;
+    file.versions.insert(version.to_string(), provenance);
+    let path = plugin_home.join(PROVENANCE_FILE);
+    let toml_string = toml::to_string(&file).context("Failed to serialize plugin provenance")?;
+    write(&path, toml_string).context(format!("Failed to write {:?}", path))
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn plugin_provenance_digest_and_record() {
+    let tmp = tempfile::tempdir().unwrap();
+    let home = tmp.path().join("provenance");
+    let plugin = home.join("versions").join("v0.1.0");
+    std::fs::create_dir_all(plugin.join("scripts")).unwrap();
+    std::fs::write(plugin.join("plugin.toml"), "name = \"test\"").unwrap();
+    std::fs::write(plugin.join("scripts").join("build.lua"), "print(1)").unwrap();
+    std::fs::write(plugin.join(".git"), "gitdir: elsewhere").unwrap();
+
+    let digest = directory_digest(&plugin).unwrap();
+    // git metadata does not affect the digest
+    std::fs::write(plugin.join(".git"), "gitdir: moved").unwrap();
+    assert_eq!(directory_digest(&plugin).unwrap(), digest);
+    std::fs::write(plugin.join("scripts").join("build.lua"), "print(2)").unwrap();
+    assert_ne!(directory_digest(&plugin).unwrap(), digest);
+
+    let provenance = Provenance {
+        source: String::from("https://example.com/plugin"),
+        reference: Some(String::from("v0.1.0")),
+        commit: Some(String::from("abc")),
+        digest,
+        artifacts: BTreeMap::from([(
+            String::from("google:platforms;android-34:3:stable"),
+            String::from("5d1c"),
+        )]),
+    };
+    record_provenance(&home, "v0.1.0", provenance.clone()).unwrap();
+    let file = read_provenance(&home).unwrap();
+    assert_eq!(file.versions.get("v0.1.0"), Some(&provenance));
+}
@@ -0,0 +1,334 @@
+use std::{
+    fmt::{Display, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+
+/// File name of the JUnit XML report
+pub const JUNIT_FILE: &str = "junit.xml";
+/// File name of the HTML summary
+pub const HTML_FILE: &str = "index.html";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    #[default]
+    Passed,
+    Failed,
+    Error,
+    Skipped,
+}
+
+impl Display for TestStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestStatus::Passed => write!(f, "passed"),
+            TestStatus::Failed => write!(f, "failed"),
+            TestStatus::Error => write!(f, "error"),
+            TestStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+/// The result of a single test
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TestCase {
+    /// The class of the test. Empty if the test is not in a class
+    pub class: String,
+    pub name: String,
+    pub status: TestStatus,
+    pub duration: Duration,
+    /// Why the test failed e.g. the stack trace
+    pub message: Option<String>,
+}
+
+/// A named group of tests, usually a test class
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct TestSuite {
+    pub name: String,
+    pub cases: Vec<TestCase>,
+}
+
+/// Counts of the test cases by status
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TestCounts {
+    pub tests: usize,
+    pub passed: usize,
+    pub failures: usize,
+    pub errors: usize,
+    pub skipped: usize,
+}
+
+impl TestCounts {
+    pub fn of<'a>(cases: impl Iterator<Item = &'a TestCase>) -> Self {
+        let mut counts = TestCounts::default();
+        for case in cases {
+            counts.tests += 1;
+            match case.status {
+                TestStatus::Passed => counts.passed += 1,
+                TestStatus::Failed => counts.failures += 1,
+                TestStatus::Error => counts.errors += 1,
+                TestStatus::Skipped => counts.skipped += 1,
+            }
+        }
+        counts
+    }
+    /// True if no test failed
+    pub fn success(&self) -> bool {
+        self.failures == 0 && self.errors == 0
+    }
+}
+
+impl Display for TestCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} tests, {} passed, {} failed, {} errors, {} skipped",
+            self.tests, self.passed, self.failures, self.errors, self.skipped
+        )
+    }
+}
+
+impl TestSuite {
+    pub fn counts(&self) -> TestCounts {
+        TestCounts::of(self.cases.iter())
+    }
+    pub fn duration(&self) -> Duration {
+        self.cases.iter().map(|case| case.duration).sum()
+    }
+}
+
+/// Collects test results into suites and renders them as a JUnit XML
+/// report and an HTML summary
+#[derive(Serialize, Debug, Clone, PartialEq, Default)]
+pub struct TestReport {
+    pub name: String,
+    pub suites: Vec<TestSuite>,
+}
+
+impl TestReport {
+    pub fn new(name: &str) -> Self {
+        TestReport {
+            name: name.to_string(),
+            suites: Vec::new(),
+        }
+    }
+    /// Adds the case to the suite, creating the suite if it is the first of its cases
+    pub fn add(&mut self, suite: &str, case: TestCase) {
+        match self.suites.iter_mut().find(|s| s.name == suite) {
+            Some(suite) => suite.cases.push(case),
+            None => self.suites.push(TestSuite {
+                name: suite.to_string(),
+                cases: vec![case],
+            }),
+        }
+    }
+    pub fn counts(&self) -> TestCounts {
+        TestCounts::of(self.suites.iter().flat_map(|suite| &suite.cases))
+    }
+    pub fn duration(&self) -> Duration {
+        self.suites.iter().map(|suite| suite.duration()).sum()
+    }
+    /// Renders the report in the JUnit XML format read by CI systems
+    pub fn junit_xml(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        let counts = self.counts();
+        let _ = writeln!(
+            xml,
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+            escape(&self.name),
+            counts.tests,
+            counts.failures,
+            counts.errors,
+            counts.skipped,
+            self.duration().as_secs_f64()
+        );
+        for suite in &self.suites {
+            let counts = suite.counts();
+            let _ = writeln!(
+                xml,
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">",
+                escape(&suite.name),
+                counts.tests,
+                counts.failures,
+                counts.errors,
+                counts.skipped,
+                suite.duration().as_secs_f64()
+            );
+            for case in &suite.cases {
+                let class = if case.class.is_empty() {
+                    &suite.name
+                } else {
+                    &case.class
+                };
+                let _ = write!(
+                    xml,
+                    "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+                    escape(class),
+                    escape(&case.name),
+                    case.duration.as_secs_f64()
+                );
+                let element = match case.status {
+                    TestStatus::Passed => {
+                        xml.push_str("/>\n");
+                        continue;
+                    }
+                    TestStatus::Failed => "failure",
+                    TestStatus::Error => "error",
+                    TestStatus::Skipped => "skipped",
+                };
+                xml.push_str(">\n");
+                match &case.message {
+                    Some(message) => {
+                        let summary = message.lines().next().unwrap_or_default();
+                        let _ = writeln!(
+                            xml,
+                            "      <{element} message=\"{}\">{}</{element}>",
+                            escape(summary),
+                            escape(message)
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(xml, "      <{element}/>");
+                    }
+                }
+                xml.push_str("    </testcase>\n");
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+    /// Renders a standalone HTML page summarizing the report
+    pub fn html(&self) -> String {
+        let counts = self.counts();
+        let mut html = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        let _ = writeln!(html, "<title>{}</title>", escape(&self.name));
+        html.push_str(
+            "<style>
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; vertical-align: top; }
+pre { margin: 0; white-space: pre-wrap; }
+.passed { color: #1a7f37; }
+.failed, .error { color: #cf222e; }
+.skipped { color: #9a6700; }
+</style>
+</head>
+<body>
+",
+        );
+        let _ = writeln!(html, "<h1>{}</h1>", escape(&self.name));
+        let _ = writeln!(
+            html,
+            "<p class=\"{}\">{} in {:.3}s</p>",
+            if counts.success() { "passed" } else { "failed" },
+            counts,
+            self.duration().as_secs_f64()
+        );
+        for suite in &self.suites {
+            let _ = writeln!(
+                html,
+                "<h2>{}</h2>\n<p>{}</p>",
+                escape(&suite.name),
+                suite.counts()
+            );
+            html.push_str("<table>\n<tr><th>Test</th><th>Status</th><th>Time</th></tr>\n");
+            for case in &suite.cases {
+                let _ = write!(html, "<tr><td>{}", escape(&case.name));
+                if let Some(message) = &case.message {
+                    let _ = write!(html, "<pre>{}</pre>", escape(message));
+                }
+                let _ = writeln!(
+                    html,
+                    "</td><td class=\"{status}\">{status}</td><td>{:.3}s</td></tr>",
+                    case.duration.as_secs_f64(),
+                    status = case.status
+                );
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+    /// Writes junit.xml and index.html into dir, returning their paths
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if dir cannot be created or a file cannot be written
+    pub fn write(&self, dir: &Path) -> anyhow::Result<(PathBuf, PathBuf)> {
+        std::fs::create_dir_all(dir).context(format!("Failed to create {:?}", dir))?;
+        let junit = dir.join(JUNIT_FILE);
+        std::fs::write(&junit, self.junit_xml())
+            .context(format!("Failed to write test report {:?}", junit))?;
+        let html = dir.join(HTML_FILE);
+        std::fs::write(&html, self.html())
+            .context(format!("Failed to write test report {:?}", html))?;
+        Ok((junit, html))
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn report_junit_and_html() {
+    let case = |name: &str, status: TestStatus, message: Option<&str>| TestCase {
+        class: String::new(),
+        name: name.to_string(),
+        status,
+        duration: Duration::from_millis(250),
+        message: message.map(|message| message.to_string()),
+    };
+    let mut report = TestReport::new("unit");
+    report.add("MathTest", case("adds", TestStatus::Passed, None));
+    report.add(
+        "MathTest",
+        case(
+            "divides",
+            TestStatus::Failed,
+            Some("expected <2> but was <3>\n\tat MathTest.divides"),
+        ),
+    );
+    report.add("IoTest", case("reads", TestStatus::Skipped, None));
+
+    assert_eq!(
+        report.counts(),
+        TestCounts {
+            tests: 3,
+            passed: 1,
+            failures: 1,
+            errors: 0,
+            skipped: 1
+        }
+    );
+    assert!(!report.counts().success());
+    assert_eq!(
+        report.junit_xml(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="unit" tests="3" failures="1" errors="0" skipped="1" time="0.750">
+  <testsuite name="MathTest" tests="2" failures="1" errors="0" skipped="0" time="0.500">
+    <testcase classname="MathTest" name="adds" time="0.250"/>
+    <testcase classname="MathTest" name="divides" time="0.250">
+      <failure message="expected &lt;2&gt; but was &lt;3&gt;">expected &lt;2&gt; but was &lt;3&gt;
+	at MathTest.divides</failure>
+    </testcase>
+  </testsuite>
+  <testsuite name="IoTest" tests="1" failures="0" errors="0" skipped="1" time="0.250">
+    <testcase classname="IoTest" name="reads" time="0.250">
+      <skipped/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+    );
+    let html = report.html();
+    assert!(html.contains(
+        "<p class=\"failed\">3 tests, 1 passed, 1 failed, 0 errors, 1 skipped in 0.750s</p>"
+    ));
+    assert!(html.contains("<tr><td>divides<pre>expected &lt;2&gt; but was &lt;3&gt;\n\tat MathTest.divides</pre></td><td class=\"failed\">failed</td><td>0.250s</td></tr>"));
+}
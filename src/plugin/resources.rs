@@ -0,0 +1,520 @@
+use std::{
+    collections::BTreeSet,
+    fs::{read_dir, File},
+    io::Read,
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use serde::Serialize;
+use zip::ZipArchive;
+
+use crate::submodules::bytecode::{read_entry, ClassFile, Constant};
+
+/// Package id of the resources of the app and its libraries
+const APP_PACKAGE_ID: u32 = 0x7f;
+/// Looks up a resource by name at runtime, hiding which resources are used
+const GET_IDENTIFIER: &str = "getIdentifier";
+
+const DEX_MAGIC: &[u8] = b"dex\n";
+/// Offsets of the size and offset pairs of the dex id tables
+const DEX_STRING_IDS: usize = 0x38;
+const DEX_TYPE_IDS: usize = 0x40;
+const DEX_FIELD_IDS: usize = 0x50;
+const DEX_CLASS_DEFS: usize = 0x60;
+/// Size of a class_def_item
+const DEX_CLASS_DEF_SIZE: usize = 32;
+/// Offset of insns_size in a code_item
+const DEX_INSNS_SIZE: usize = 12;
+/// const vAA, #+BBBBBBBB
+const DEX_CONST: u8 = 0x14;
+/// const/high16 vAA, #+BBBB0000
+const DEX_CONST_HIGH16: u8 = 0x15;
+/// sget through sget-short, reading a static field
+const DEX_SGET: std::ops::RangeInclusive<u8> = 0x60..=0x66;
+
+/// A resource declared in R.txt
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResourceSymbol {
+    /// The resource type e.g. drawable, string, layout
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub name: String,
+    pub id: u32,
+}
+
+/// Parses the R.txt written by `aapt2 link --output-text-symbols`. Styleables are
+/// skipped since they hold attribute indexes rather than resources.
+///
+/// # Errors
+///
+/// Returns an error if a resource id is not a hex number
+pub fn parse_r_txt(text: &str) -> anyhow::Result<Vec<ResourceSymbol>> {
+    let mut symbols = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let [java_type, kind, name, value] = parts[..] else {
+            continue;
+        };
+        if java_type != "int" || kind == "styleable" {
+            continue;
+        }
+        let id = u32::from_str_radix(value.trim_start_matches("0x"), 16).context(format!(
+            "Invalid resource id {} on line {} of R.txt",
+            value,
+            number + 1
+        ))?;
+        symbols.push(ResourceSymbol {
+            kind: kind.to_string(),
+            name: name.to_string(),
+            id,
+        });
+    }
+    Ok(symbols)
+}
+
+fn is_app_resource(value: u32) -> bool {
+    value >> 24 == APP_PACKAGE_ID
+}
+
+/// Returns the resource type if class is an R class e.g. com/example/R$drawable
+fn r_class_type(class: &str) -> Option<&str> {
+    let simple = class.rsplit('/').next().unwrap_or(class);
+    simple.strip_prefix("R$")
+}
+
+/// True for R and its nested classes, whose own fields hold every resource id
+fn is_r_class(class: &str) -> bool {
+    let simple = class.rsplit('/').next().unwrap_or(class);
+    simple == "R" || simple.starts_with("R$")
+}
+
+fn u16_at(data: &[u8], offset: usize) -> anyhow::Result<u16> {
+    match data.get(offset..offset.saturating_add(2)) {
+        Some(bytes) => Ok(u16::from_le_bytes([bytes[0], bytes[1]])),
+        None => bail!("Unexpected end of data at {offset}"),
+    }
+}
+
+fn u32_at(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    match data.get(offset..offset.saturating_add(4)) {
+        Some(bytes) => Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => bail!("Unexpected end of data at {offset}"),
+    }
+}
+
+/// The offset of item index of size bytes in a table at base, an error if it
+/// overflows
+fn table_offset(base: usize, index: usize, size: usize) -> anyhow::Result<usize> {
+    index
+        .checked_mul(size)
+        .and_then(|offset| offset.checked_add(base))
+        .context("Dex offset out of range")
+}
+
+fn uleb128(data: &[u8], offset: &mut usize) -> anyhow::Result<u32> {
+    let mut value = 0u32;
+    for shift in (0..35).step_by(7) {
+        let byte = *data.get(*offset).context("Unexpected end of data")?;
+        *offset += 1;
+        value |= u32::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(value)
+}
+
+/// The resources referenced from compiled code. The analysis errs on the side of
+/// keeping resources: a resource is only unused if nothing looks like a reference to it.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Resource ids inlined as constants, as done for the final R fields of the app
+    pub ids: BTreeSet<u32>,
+    /// R fields read by type and name, as done for the R fields of libraries
+    pub fields: BTreeSet<(String, String)>,
+    /// True if the code looks up resources by name, in which case any resource may be used
+    pub dynamic_lookup: bool,
+}
+
+impl ResourceUsage {
+    pub fn new() -> Self {
+        ResourceUsage::default()
+    }
+    fn field(&mut self, class: &str, name: &str) {
+        if let Some(kind) = r_class_type(class) {
+            self.fields.insert((kind.to_string(), name.to_string()));
+        }
+    }
+    /// True if the code references the resource
+    pub fn uses(&self, symbol: &ResourceSymbol) -> bool {
+        self.ids.contains(&symbol.id)
+            || self
+                .fields
+                .contains(&(symbol.kind.clone(), symbol.name.clone()))
+    }
+    /// Splits the symbols into the used and unused resources. Nothing is unused if
+    /// the code looks up resources by name.
+    pub fn partition<'a>(
+        &self,
+        symbols: &'a [ResourceSymbol],
+    ) -> (Vec<&'a ResourceSymbol>, Vec<&'a ResourceSymbol>) {
+        symbols
+            .iter()
+            .partition(|symbol| self.dynamic_lookup || self.uses(symbol))
+    }
+    /// Scans a java class file. R classes are skipped since they declare every resource.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not a valid class file
+    pub fn scan_class(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        let class = ClassFile::parse(data)?;
+        if is_r_class(class.name().unwrap_or_default()) {
+            return Ok(());
+        }
+
+        for constant in &class.pool {
+            match constant {
+                Constant::Integer(value) if is_app_resource(*value) => {
+                    self.ids.insert(*value);
+                }
+                Constant::Utf8(value) if value == GET_IDENTIFIER => self.dynamic_lookup = true,
+                Constant::FieldRef(owner, name_and_type) => {
+                    let name = match class.pool.get(*name_and_type as usize) {
+                        Some(Constant::NameAndType(name, _)) => class.utf8(*name),
+                        _ => None,
+                    };
+                    if let (Some(owner), Some(name)) = (class.class_name(*owner), name) {
+                        self.field(owner, name);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    /// Scans the code of a dex file. The code of R classes is skipped since it
+    /// initializes every resource field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data is not a valid dex file
+    pub fn scan_dex(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        if !data.starts_with(DEX_MAGIC) {
+            bail!("Invalid dex file magic");
+        }
+        let table = |offset: usize| -> anyhow::Result<(usize, usize)> {
+            Ok((
+                u32_at(data, offset)? as usize,
+                u32_at(data, table_offset(offset, 1, 4)?)? as usize,
+            ))
+        };
+        let (string_count, string_ids) = table(DEX_STRING_IDS)?;
+        let (type_count, type_ids) = table(DEX_TYPE_IDS)?;
+        let (_, field_ids) = table(DEX_FIELD_IDS)?;
+        let (class_count, class_defs) = table(DEX_CLASS_DEFS)?;
+
+        let string = |index: usize| -> anyhow::Result<String> {
+            if index >= string_count {
+                bail!("String index {index} out of range");
+            }
+            let mut offset = u32_at(data, table_offset(string_ids, index, 4)?)? as usize;
+            uleb128(data, &mut offset)?;
+            let end = data[offset.min(data.len())..]
+                .iter()
+                .position(|byte| *byte == 0)
+                .context("Unterminated dex string")?;
+            Ok(String::from_utf8_lossy(&data[offset..offset + end]).to_string())
+        };
+        // the class name in internal form e.g. com/example/R$drawable
+        let type_name = |index: usize| -> anyhow::Result<String> {
+            if index >= type_count {
+                bail!("Type index {index} out of range");
+            }
+            let descriptor = string(u32_at(data, table_offset(type_ids, index, 4)?)? as usize)?;
+            Ok(descriptor
+                .trim_start_matches('L')
+                .trim_end_matches(';')
+                .to_string())
+        };
+
+        for index in 0..string_count {
+            if string(index)? == GET_IDENTIFIER {
+                self.dynamic_lookup = true;
+                break;
+            }
+        }
+
+        let mut fields = BTreeSet::new();
+        for class in 0..class_count {
+            let def = table_offset(class_defs, class, DEX_CLASS_DEF_SIZE)?;
+            if is_r_class(&type_name(u32_at(data, def)? as usize)?) {
+                continue;
+            }
+            let class_data = u32_at(data, table_offset(def, 24, 1)?)? as usize;
+            if class_data == 0 {
+                continue;
+            }
+            let mut offset = class_data;
+            let static_fields = uleb128(data, &mut offset)?;
+            let instance_fields = uleb128(data, &mut offset)?;
+            let direct_methods = uleb128(data, &mut offset)?;
+            let virtual_methods = uleb128(data, &mut offset)?;
+            let fields_values = static_fields
+                .checked_add(instance_fields)
+                .and_then(|count| count.checked_mul(2))
+                .context("Invalid dex field count")?;
+            for _ in 0..fields_values {
+                uleb128(data, &mut offset)?;
+            }
+            let methods = direct_methods
+                .checked_add(virtual_methods)
+                .context("Invalid dex method count")?;
+            for _ in 0..methods {
+                // method index diff and access flags
+                uleb128(data, &mut offset)?;
+                uleb128(data, &mut offset)?;
+                let code = uleb128(data, &mut offset)? as usize;
+                if code != 0 {
+                    self.scan_insns(data, code, &mut fields)?;
+                }
+            }
+        }
+        for field in fields {
+            let field = table_offset(field_ids, field as usize, 8)?;
+            let class = type_name(u16_at(data, field)? as usize)?;
+            if r_class_type(&class).is_some() {
+                let name = string(u32_at(data, table_offset(field, 1, 4)?)? as usize)?;
+                self.field(&class, &name);
+            }
+        }
+        Ok(())
+    }
+    /// Collects the resource ids loaded as constants and the static fields read by the
+    /// instructions of a code_item. Every code unit is checked as a possible opcode, which
+    /// can only find extra references.
+    fn scan_insns(
+        &mut self,
+        data: &[u8],
+        code: usize,
+        fields: &mut BTreeSet<u16>,
+    ) -> anyhow::Result<()> {
+        let size = u32_at(data, table_offset(code, DEX_INSNS_SIZE, 1)?)? as usize;
+        let start = table_offset(code, DEX_INSNS_SIZE + 4, 1)?;
+        let insns = table_offset(start, size, 2)
+            .ok()
+            .and_then(|end| data.get(start..end))
+            .context("Dex code item out of range")?;
+        for unit in (0..insns.len()).step_by(2) {
+            let opcode = insns[unit];
+            if opcode == DEX_CONST {
+                if let Ok(value) = u32_at(insns, unit + 2) {
+                    if is_app_resource(value) {
+                        self.ids.insert(value);
+                    }
+                }
+            } else if opcode == DEX_CONST_HIGH16 {
+                if let Ok(value) = u16_at(insns, unit + 2) {
+                    let value = u32::from(value) << 16;
+                    if is_app_resource(value) {
+                        self.ids.insert(value);
+                    }
+                }
+            } else if DEX_SGET.contains(&opcode) {
+                if let Ok(field) = u16_at(insns, unit + 2) {
+                    fields.insert(field);
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Scans the class and dex files of a jar, apk or zip
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive or one of its class or dex files is invalid
+    pub fn scan_archive<R: Read + std::io::Seek>(&mut self, reader: R) -> anyhow::Result<()> {
+        let mut archive = ZipArchive::new(reader).context("Failed to read zip archive")?;
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            let name = entry.name().to_string();
+            let is_class = name.ends_with(".class");
+            if !is_class && !name.ends_with(".dex") {
+                continue;
+            }
+            let data = read_entry(entry)?;
+            if is_class {
+                self.scan_class(&data)
+            } else {
+                self.scan_dex(&data)
+            }
+            .context(format!("Failed to scan {}", name))?;
+        }
+        Ok(())
+    }
+    /// Scans a class, dex, jar, apk or zip file, or all of them in a directory
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a file cannot be read or is invalid
+    pub fn scan_path(&mut self, path: &Path) -> anyhow::Result<()> {
+        if path.is_dir() {
+            let mut entries: Vec<_> = read_dir(path)
+                .context(format!("Failed to read {:?}", path))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .collect();
+            entries.sort();
+            for entry in entries {
+                self.scan_path(&entry)?;
+            }
+            return Ok(());
+        }
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "class" => {
+                self.scan_class(&std::fs::read(path).context(format!("Failed to read {:?}", path))?)
+            }
+            "dex" => {
+                self.scan_dex(&std::fs::read(path).context(format!("Failed to read {:?}", path))?)
+            }
+            "jar" | "apk" | "zip" => {
+                self.scan_archive(File::open(path).context(format!("Failed to open {:?}", path))?)
+            }
+            _ => return Ok(()),
+        }
+        .context(format!("Failed to scan {:?}", path))
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn resource_usage_analysis() {
+    let symbols = parse_r_txt(
+        "int drawable icon 0x7f010000
+int drawable unused 0x7f010001
+int string app_name 0x7f020000
+int string lib_title 0x7f020001
+int[] styleable Button { 0x7f030000 }
+int styleable Button_color 0
+",
+    )
+    .unwrap();
+    assert_eq!(symbols.len(), 4);
+    assert_eq!(
+        symbols[0],
+        ResourceSymbol {
+            kind: String::from("drawable"),
+            name: String::from("icon"),
+            id: 0x7f01_0000,
+        }
+    );
+
+    // a class reading a library R field and loading an inlined id
+    let class = |this: &str, id: u32| {
+        let utf8 = |value: &str| {
+            let mut bytes = vec![1];
+            bytes.extend((value.len() as u16).to_be_bytes());
+            bytes.extend(value.as_bytes());
+            bytes
+        };
+        let mut data = crate::submodules::bytecode::CLASS_MAGIC.to_vec();
+        data.extend([0, 0, 0, 52]);
+        data.extend(10u16.to_be_bytes());
+        data.extend(utf8(this)); // 1
+        data.extend([7, 0, 1]); // 2 this class
+        data.extend(utf8("com/lib/R$string")); // 3
+        data.extend([7, 0, 3]); // 4
+        data.extend(utf8("lib_title")); // 5
+        data.extend(utf8("I")); // 6
+        data.extend([12, 0, 5, 0, 6]); // 7
+        data.extend([9, 0, 4, 0, 7]); // 8
+        data.push(3); // 9
+        data.extend(id.to_be_bytes());
+        data.extend([0, 0x21, 0, 2]);
+        data
+    };
+    let mut usage = ResourceUsage::new();
+    usage
+        .scan_class(&class("com/example/MainActivity", 0x7f02_0000))
+        .unwrap();
+    // R classes declare every resource
+    usage
+        .scan_class(&class("com/example/R$drawable", 0x7f01_0001))
+        .unwrap();
+
+    let (used, unused) = usage.partition(&symbols);
+    let names = |symbols: Vec<&ResourceSymbol>| -> Vec<String> {
+        symbols.iter().map(|symbol| symbol.name.clone()).collect()
+    };
+    assert_eq!(names(used), vec!["app_name", "lib_title"]);
+    assert_eq!(names(unused), vec!["icon", "unused"]);
+
+    usage.dynamic_lookup = true;
+    assert!(usage.partition(&symbols).1.is_empty());
+
+    // a dex with one method loading icon and reading a library R field
+    let mut dex = vec![0u8; 0x70];
+    dex[..8].copy_from_slice(b"dex\n035\0");
+    let mut set = |offset: usize, count: u32, at: u32| {
+        dex[offset..offset + 4].copy_from_slice(&count.to_le_bytes());
+        dex[offset + 4..offset + 8].copy_from_slice(&at.to_le_bytes());
+    };
+    set(DEX_STRING_IDS, 4, 0x70);
+    set(DEX_TYPE_IDS, 3, 0x80);
+    set(DEX_FIELD_IDS, 1, 0x8c);
+    set(DEX_CLASS_DEFS, 1, 0x94);
+    let strings = ["Lcom/example/Main;", "Lcom/lib/R$string;", "lib_title", "I"];
+    let mut string_data = Vec::new();
+    let mut offset = 0xdc;
+    for value in strings {
+        dex.extend((offset as u32).to_le_bytes());
+        string_data.push(value.len() as u8);
+        string_data.extend(value.as_bytes());
+        string_data.push(0);
+        offset = 0xdc + string_data.len();
+    }
+    dex.extend([0u32, 1, 3].iter().flat_map(|id| id.to_le_bytes()));
+    dex.extend([1, 0, 2, 0, 2, 0, 0, 0]);
+    let mut class_def = [0u8; DEX_CLASS_DEF_SIZE];
+    class_def[24..28].copy_from_slice(&0xb4u32.to_le_bytes());
+    dex.extend(class_def);
+    dex.extend([0, 0, 1, 0, 0, 1, 0xc0, 0x01, 0, 0, 0, 0]);
+    dex.extend([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0]);
+    dex.extend([0x15, 0, 0x01, 0x7f, 0x60, 0, 0, 0, 0x0e, 0, 0, 0]);
+    dex.extend(string_data);
+
+    let mut usage = ResourceUsage::new();
+    usage.scan_dex(&dex).unwrap();
+    let (used, _) = usage.partition(&symbols);
+    assert_eq!(names(used), vec!["icon", "lib_title"]);
+    assert!(!usage.dynamic_lookup);
+}
+
+#[test]
+fn scan_class_long_last_in_pool() {
+    let mut data = crate::submodules::bytecode::CLASS_MAGIC.to_vec();
+    data.extend([0, 0, 0, 52]);
+    data.extend(4u16.to_be_bytes());
+    data.extend([1, 0, 4]);
+    data.extend(b"Main"); // 1
+    data.extend([7, 0, 1]); // 2 this class
+    data.push(5); // 3 long, the pool count leaves no room for its second entry
+    data.extend(0x7f01_0000_0000_0000u64.to_be_bytes());
+    data.extend([0, 0x21, 0, 2]);
+    let mut usage = ResourceUsage::new();
+    usage.scan_class(&data).unwrap();
+    assert!(usage.ids.is_empty());
+
+    // table offsets that overflow are errors instead of panics
+    let mut dex = vec![0u8; 0x70];
+    dex[..8].copy_from_slice(b"dex\n035\0");
+    dex[DEX_CLASS_DEFS..DEX_CLASS_DEFS + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    dex[DEX_CLASS_DEFS + 4..DEX_CLASS_DEFS + 8].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert!(ResourceUsage::new().scan_dex(&dex).is_err());
+}
@@ -0,0 +1,378 @@
+use std::{
+    error::Error as StdError,
+    fmt::Display,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+
+use super::{api::MluaAnyhowWrapper, process::ProcessEnv};
+
+/// Lines of source shown on either side of the failing line
+const SOURCE_CONTEXT: usize = 2;
+const TRACEBACK_HEADER: &str = "stack traceback:";
+
+/// A plugin error with the Lua location and traceback pulled out of the
+/// mlua error so that it can be reported as a readable block
+#[derive(Debug, Default)]
+pub struct LuaFailure {
+    /// The plugin that failed e.g. labt-java:0.1.0
+    pub plugin: String,
+    /// The script that was executed
+    pub script: PathBuf,
+    /// The error message without the Lua location prefix
+    pub message: String,
+    /// The script and line the error was raised at
+    pub location: Option<(PathBuf, usize)>,
+    /// The source lines around location as (line number, line)
+    pub source: Vec<(usize, String)>,
+    /// The errors that caused message, outermost first
+    pub causes: Vec<String>,
+    /// The Lua stack traceback, one frame per line
+    pub traceback: Vec<String>,
+    /// The debug representation of the original error, for trace dumps
+    debug: String,
+}
+
+impl LuaFailure {
+    pub fn new(plugin: &str, script: &Path, err: &mlua::Error) -> Self {
+        let mut chain = Vec::new();
+        let mut traceback = Vec::new();
+        collect(err, &mut chain, &mut traceback);
+
+        let mut chain = chain.into_iter().filter(|m| !m.trim().is_empty());
+        let message = chain.next().unwrap_or_else(|| err.to_string());
+        let causes: Vec<String> = chain.collect();
+
+        // the error is raised at the first script line mentioned by the message or
+        // the traceback
+        let location = std::iter::once(message.as_str())
+            .chain(causes.iter().map(String::as_str))
+            .chain(traceback.iter().map(String::as_str))
+            .find_map(|text| find_location(text, script));
+        let source = location
+            .as_ref()
+            .and_then(|(path, line)| source_lines(path, *line))
+            .unwrap_or_default();
+
+        LuaFailure {
+            plugin: plugin.to_string(),
+            script: script.to_path_buf(),
+            message: strip_location(&message).to_string(),
+            location,
+            source,
+            causes,
+            traceback,
+            debug: format!("{:#?}", err),
+        }
+    }
+    /// Replaces the values of the secret variables of env everywhere in the
+    /// failure, including the raw error written by [`LuaFailure::dump`]
+    pub fn masked(mut self, env: &ProcessEnv) -> Self {
+        let mask = |text: &mut String| *text = env.mask(text).into_owned();
+        mask(&mut self.message);
+        self.causes.iter_mut().for_each(mask);
+        self.source.iter_mut().for_each(|(_, line)| mask(line));
+        self.traceback.iter_mut().for_each(mask);
+        mask(&mut self.debug);
+        self
+    }
+    /// Writes the failure with the full traceback and the raw error to path
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be written
+    pub fn dump(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .context(format!("Failed to create directory {:?}", parent))?;
+        }
+        let contents = format!("{}\nRaw error:\n{}\n", self, self.debug);
+        std::fs::write(path, contents).context(format!("Failed to write trace to {:?}", path))
+    }
+}
+
+impl Display for LuaFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Plugin {} failed", self.plugin)?;
+        writeln!(f, "  script: {}", self.script.display())?;
+        if let Some((path, line)) = &self.location {
+            writeln!(f, "  at:     {}:{}", path.display(), line)?;
+        }
+        writeln!(f, "  error:  {}", self.message)?;
+        if !self.source.is_empty() {
+            let at = self.location.as_ref().map(|(_, line)| *line);
+            let width = self.source.last().map(|(n, _)| n.to_string().len());
+            let width = width.unwrap_or(1);
+            writeln!(f)?;
+            for (number, line) in &self.source {
+                let marker = if Some(*number) == at { ">" } else { " " };
+                writeln!(f, "  {marker} {number:>width$} | {line}")?;
+            }
+        }
+        if !self.causes.is_empty() {
+            writeln!(f, "\nCaused by:")?;
+            for (i, cause) in self.causes.iter().enumerate() {
+                writeln!(f, "  {i}: {cause}")?;
+            }
+        }
+        if !self.traceback.is_empty() {
+            writeln!(f, "\nLua traceback:")?;
+            for frame in &self.traceback {
+                writeln!(f, "  {frame}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StdError for LuaFailure {}
+
+/// Flattens the mlua error into messages and traceback frames
+fn collect(err: &mlua::Error, chain: &mut Vec<String>, traceback: &mut Vec<String>) {
+    match err {
+        mlua::Error::CallbackError {
+            traceback: frames,
+            cause,
+        } => {
+            if traceback.is_empty() {
+                traceback.extend(traceback_frames(frames));
+            }
+            collect(cause, chain, traceback);
+        }
+        mlua::Error::WithContext { context, cause } => {
+            chain.push(context.clone());
+            collect(cause, chain, traceback);
+        }
+        mlua::Error::RuntimeError(message) | mlua::Error::SyntaxError { message, .. } => {
+            let (message, frames) = match message.split_once(TRACEBACK_HEADER) {
+                Some((message, frames)) => (message, frames),
+                None => (message.as_str(), ""),
+            };
+            if traceback.is_empty() {
+                traceback.extend(traceback_frames(frames));
+            }
+            chain.push(message.trim().to_string());
+        }
+        mlua::Error::ExternalError(external) => {
+            if let Some(wrapper) = external.downcast_ref::<MluaAnyhowWrapper>() {
+                chain.extend(wrapper.inner().chain().map(|cause| cause.to_string()));
+            } else {
+                chain.push(external.to_string());
+                let mut source = external.source();
+                while let Some(cause) = source {
+                    chain.push(cause.to_string());
+                    source = cause.source();
+                }
+            }
+        }
+        err => chain.push(err.to_string()),
+    }
+}
+
+fn traceback_frames(frames: &str) -> impl Iterator<Item = String> + '_ {
+    frames
+        .lines()
+        .map(str::trim)
+        .filter(|frame| !frame.is_empty() && *frame != TRACEBACK_HEADER)
+        .map(str::to_string)
+}
+
+/// Lua positions in text as (chunk name, line, start, end). Chunk names are
+/// either a file name or `[string "name"]`
+fn positions(text: &str) -> Vec<(&str, usize, usize, usize)> {
+    let mut found = Vec::new();
+    let bytes = text.as_bytes();
+    for (colon, _) in text.match_indices(':') {
+        let digits = bytes[colon + 1..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        let after = colon + 1 + digits;
+        if digits == 0 || bytes.get(after) != Some(&b':') {
+            continue;
+        }
+        let Ok(line) = text[colon + 1..after].parse::<usize>() else {
+            continue;
+        };
+        let before = &text[..colon];
+        let (chunk, start) = if let Some(quoted) = before.strip_suffix("\"]") {
+            match quoted.rfind("[string \"") {
+                Some(i) => (&quoted[i + "[string \"".len()..], i),
+                None => continue,
+            }
+        } else {
+            // the separator may be multi-byte whitespace such as a no-break space
+            let start = before
+                .char_indices()
+                .rfind(|(_, c)| c.is_whitespace() || *c == '\'' || *c == '"')
+                .map(|(i, c)| i + c.len_utf8())
+                .unwrap_or(0);
+            (&before[start..], start)
+        };
+        if !chunk.is_empty() && !chunk.starts_with('[') {
+            found.push((chunk, line, start, after + 1));
+        }
+    }
+    found
+}
+
+/// Finds the first `chunk:line:` in text that refers to a Lua file and maps it
+/// back to a path. Lua shortens long chunk names with `...` so those are matched
+/// against the script path.
+pub fn find_location(text: &str, script: &Path) -> Option<(PathBuf, usize)> {
+    positions(text)
+        .into_iter()
+        .find_map(|(chunk, line, _, _)| Some((resolve_chunk(chunk, script)?, line)))
+}
+
+fn resolve_chunk(chunk: &str, script: &Path) -> Option<PathBuf> {
+    let script_name = script.to_string_lossy();
+    if let Some(head) = chunk.strip_suffix("...") {
+        return script_name.starts_with(head).then(|| script.to_path_buf());
+    }
+    if let Some(tail) = chunk.strip_prefix("...") {
+        if script_name.ends_with(tail) {
+            return Some(script.to_path_buf());
+        }
+        let name = Path::new(tail).file_name()?;
+        return script
+            .parent()
+            .map(|dir| dir.join(name))
+            .filter(|path| path.exists());
+    }
+    if chunk == script_name {
+        return Some(script.to_path_buf());
+    }
+    if !chunk.ends_with(".lua") {
+        return None;
+    }
+    let path = PathBuf::from(chunk);
+    if path.exists() {
+        return Some(path);
+    }
+    script
+        .parent()
+        .map(|dir| dir.join(chunk))
+        .filter(|path| path.exists())
+}
+
+/// Removes the `chunk:line:` prefix Lua adds to error messages
+fn strip_location(message: &str) -> &str {
+    match positions(message).first() {
+        Some((_, _, 0, end)) => message[*end..].trim_start(),
+        _ => message,
+    }
+}
+fn source_lines(path: &Path, line: usize) -> Option<Vec<(usize, String)>> {
+    let source = read_to_string(path).ok()?;
+    let first = line.saturating_sub(SOURCE_CONTEXT).max(1);
+    let lines: Vec<(usize, String)> = source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| (i + 1, text.to_string()))
+        .skip(first - 1)
+        .take(line + SOURCE_CONTEXT + 1 - first)
+        .collect();
+    (!lines.is_empty()).then_some(lines)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn lua_failure_maps_errors_to_source() {
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("trace");
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("build.lua");
+    std::fs::write(
+        &script,
+        "local a = 1\nlocal b = 2\nfs.copy(\"x\", \"y\")\nlocal c = 3\nlocal d = 4\nlocal e = 5\n",
+    )
+    .unwrap();
+
+    let lua = mlua::Lua::new();
+    let copy = lua
+        .create_function(|_, ()| -> mlua::Result<()> {
+            Err(MluaAnyhowWrapper::external(
+                anyhow::anyhow!("No such file").context("Failed to copy x to y"),
+            ))
+        })
+        .unwrap();
+    let fs = lua.create_table().unwrap();
+    fs.set("copy", copy).unwrap();
+    lua.globals().set("fs", fs).unwrap();
+
+    let err = lua
+        .load(std::fs::read_to_string(&script).unwrap())
+        .set_name(script.to_string_lossy())
+        .exec()
+        .unwrap_err();
+    let failure = LuaFailure::new("demo:1.0", &script, &err);
+    assert_eq!(failure.message, "Failed to copy x to y");
+    assert_eq!(failure.causes, vec![String::from("No such file")]);
+    assert_eq!(failure.location, Some((script.clone(), 3)));
+    assert_eq!(
+        failure.source.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+        vec![1, 2, 3, 4, 5]
+    );
+    assert!(failure.traceback.iter().any(|frame| frame.contains(":3:")));
+    let block = failure.to_string();
+    assert!(block.contains("  > 3 | fs.copy(\"x\", \"y\")"));
+
+    // errors raised from lua keep their message without the location prefix
+    let err = lua
+        .load("local x = 1\nerror(\"bad config\")")
+        .set_name(script.to_string_lossy())
+        .exec()
+        .unwrap_err();
+    let failure = LuaFailure::new("demo:1.0", &script, &err);
+    assert_eq!(failure.message, "bad config");
+    assert_eq!(failure.location, Some((script.clone(), 2)));
+
+    // shortened chunk names map back to the script
+    let tail = format!("...{}:7: oops", &script.to_string_lossy()[4..]);
+    assert_eq!(find_location(&tail, &script), Some((script.clone(), 7)));
+    let head = format!("[string \"{}...\"]:9: oops", &script.to_string_lossy()[..8]);
+    assert_eq!(find_location(&head, &script), Some((script.clone(), 9)));
+    assert_eq!(find_location("[C]: in ?", &script), None);
+    // a location after multi-byte whitespace
+    assert_eq!(
+        positions("error\u{a0}build.lua:4: x"),
+        vec![("build.lua", 4, 7, 19)]
+    );
+    assert_eq!(
+        positions("at\u{3000}build.lua:5:"),
+        vec![("build.lua", 5, 5, 17)]
+    );
+}
+
+#[test]
+fn lua_failure_masks_secrets() {
+    let tmp = tempfile::tempdir().unwrap();
+    let script = tmp.path().join("secret.lua");
+    std::fs::write(
+        &script,
+        "local token = \"hunter2\"\nerror(\"bad token \" .. token)\n",
+    )
+    .unwrap();
+    let lua = mlua::Lua::new();
+    let err = lua
+        .load(std::fs::read_to_string(&script).unwrap())
+        .set_name(script.to_string_lossy())
+        .exec()
+        .unwrap_err();
+    let mut env = ProcessEnv::default();
+    env.set_secret(String::from("TOKEN"), String::from("hunter2"));
+    let failure = LuaFailure::new("demo:1.0", &script, &err).masked(&env);
+    assert_eq!(failure.message, "bad token ***");
+    let block = failure.to_string();
+    assert!(!block.contains("hunter2"));
+    assert!(block.contains("local token = \"***\""));
+
+    let trace = tmp.path().join("secret.trace");
+    failure.dump(&trace).unwrap();
+    assert!(!std::fs::read_to_string(&trace).unwrap().contains("hunter2"));
+}
@@ -1,14 +1,15 @@
+use crate::config::properties::get_properties;
+use crate::version::ComparableVersion;
 use anyhow::Context;
 use anyhow::Result;
 use quick_xml::{events::Event, Reader};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
 use std::io::BufReader;
 use std::io::Read;
 use std::str::FromStr;
 use tokio::io::AsyncRead;
-use version_compare::Version;
 
 /// constants for common tags
 mod tags {
@@ -32,6 +33,10 @@ mod tags {
     pub const RUNTIME: &[u8] = b"runtime";
     pub const PROPERTIES: &[u8] = b"properties";
     pub const PARENT: &[u8] = b"parent";
+    pub const LICENSES: &[u8] = b"licenses";
+    pub const LICENSE: &[u8] = b"license";
+    pub const NAME: &[u8] = b"name";
+    pub const URL: &[u8] = b"url";
 }
 
 #[derive(Clone, Default, Debug, PartialEq, Eq, Serialize)]
@@ -61,6 +66,17 @@ impl FromStr for Scope {
     }
 }
 
+impl Scope {
+    /// True if dependencies in this scope are needed to compile the project
+    pub fn on_compile_classpath(&self) -> bool {
+        matches!(self, Scope::COMPILE | Scope::SYSTEM | Scope::PROVIDED)
+    }
+    /// True if dependencies in this scope are packaged with the app
+    pub fn on_runtime_classpath(&self) -> bool {
+        matches!(self, Scope::COMPILE | Scope::SYSTEM | Scope::RUNTIME)
+    }
+}
+
 impl Display for Scope {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let scope = match self {
@@ -103,8 +119,8 @@ impl PartialOrd for VersionRange {
             | VersionRange::Eq(v) => v,
         };
 
-        let a = Version::from(version_a).unwrap();
-        let b = Version::from(version_b).unwrap();
+        let a = ComparableVersion::new(version_a);
+        let b = ComparableVersion::new(version_b);
 
         // Since we are working with a virtual number line here, inequality symbols should be taken into account.
         // > is greater that >= as it moves up by 1
@@ -154,7 +170,7 @@ impl FromStr for VersionRange {
         let mut current_state = VersionRangeState::Start;
         let mut start_index = 0;
 
-        for (i, c) in s.chars().enumerate() {
+        for (i, c) in s.char_indices() {
             match current_state {
                 VersionRangeState::Start => match c {
                     ' ' => {
@@ -384,7 +400,7 @@ impl FromStr for VersionRequirement {
         let mut current_state = VersionParserState::Start;
         let mut start_index = 0;
 
-        for (i, c) in s.chars().enumerate() {
+        for (i, c) in s.char_indices() {
             match current_state {
                 VersionParserState::Start => match c {
                     '(' => current_state = VersionParserState::InequalityStart,
@@ -416,7 +432,7 @@ impl FromStr for VersionRequirement {
                 VersionParserState::Eq => match c {
                     ',' => {
                         // peek on next char
-                        let chars = s.chars().skip(i + 1);
+                        let chars = s[i + 1..].chars();
 
                         for n in chars {
                             if n == ' ' {
@@ -489,7 +505,7 @@ impl FromStr for VersionRequirement {
                 VersionParserState::Gt => match c {
                     ',' => {
                         // peak on next character, but the next character might be space
-                        let chars = s.chars().skip(i + 1);
+                        let chars = s[i + 1..].chars();
                         for n in chars {
                             if n == ' ' {
                                 continue; // a whitespace
@@ -581,6 +597,17 @@ pub struct Project {
     optional: bool,
     /// Parent pom
     pub parent: Option<ParentPom>,
+    /// The licenses declared by this project
+    licenses: Vec<License>,
+}
+
+/// A license declared in the pom <licenses> section
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct License {
+    /// The full legal name of the license
+    pub name: String,
+    /// The url to the license text
+    pub url: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
@@ -617,6 +644,7 @@ impl Default for Project {
             properties: HashMap::new(),
             parent: None,
             optional: false,
+            licenses: vec![],
         }
     }
 }
@@ -706,6 +734,14 @@ impl Project {
         }
         self.scope = parent.scope.clone();
     }
+    /// Takes the version of a dependencyManagement entry if this project was declared
+    /// without a version
+    pub fn manage_version(&mut self, managed: &Project) {
+        if self.version == VersionRequirement::Unset {
+            self.version = managed.version.clone();
+            self.selected_version = managed.selected_version.clone();
+        }
+    }
     pub fn qualified_name(&self) -> anyhow::Result<String> {
         let version = self
             .selected_version
@@ -716,6 +752,10 @@ impl Project {
             self.group_id, self.artifact_id, version
         ))
     }
+    /// Returns the licenses declared in the pom
+    pub fn get_licenses(&self) -> &Vec<License> {
+        &self.licenses
+    }
     pub fn get_excludes(&self) -> &Vec<Exclusion> {
         &self.excludes
     }
@@ -725,6 +765,9 @@ impl Project {
     pub fn get_scope(&self) -> Scope {
         self.scope.clone()
     }
+    pub fn set_scope(&mut self, scope: Scope) {
+        self.scope = scope;
+    }
     pub fn get_packaging(&self) -> String {
         self.packaging.clone()
     }
@@ -740,6 +783,24 @@ impl Project {
         if value.is_some() {
             return value.cloned();
         }
+        // the pom's own project.* values come before the inherited properties
+        if let Some(("project", item)) = key.split_once(".") {
+            let value = match item {
+                "version" => Some(self.version.to_string()),
+                "artifactId" => Some(self.get_artifact_id()),
+                "groupId" => Some(self.get_group_id()),
+                "scope" => Some(self.get_scope().to_string()),
+                "packaging" => Some(self.get_packaging()),
+                _ => None,
+            };
+            if value.is_some() {
+                return value;
+            }
+        }
+        // then the ones inherited from config.toml and Labt.toml
+        if let Some(value) = get_properties().get(key) {
+            return Some(value.clone());
+        }
 
         let segments = key.split_once(".");
         segments?;
@@ -749,21 +810,7 @@ impl Project {
         match group {
             "env" => {
                 // do env stuff
-                match std::env::var(item) {
-                    Ok(v) => Some(v),
-                    _ => None,
-                }
-            }
-            "project" => {
-                // reply with project stuff
-                match item {
-                    "version" => Some(self.version.to_string()),
-                    "artifactId" => Some(self.get_artifact_id()),
-                    "groupId" => Some(self.get_group_id()),
-                    "scope" => Some(self.get_scope().to_string()),
-                    "packaging" => Some(self.get_packaging()),
-                    _ => None,
-                }
+                std::env::var(item).ok()
             }
             // TODO
             // "java" => {
@@ -776,57 +823,66 @@ impl Project {
         }
     }
     pub fn substitute_string(&self, data: &str) -> String {
-        // Parse the string for ${}
-        // Yet another state machine
-        let mut result = String::with_capacity(data.len());
-        #[derive(Debug)]
-        enum SubState {
-            Normal,
-            PlaceholderDollar,
-            PlaceholderBody,
-        }
+        substitute_properties(data, |key| self.get_property(key))
+    }
+}
+
+/// Replaces each ${property} placeholder in data with the value lookup returns
+/// for it. Properties lookup does not know are removed.
+pub fn substitute_properties<F>(data: &str, lookup: F) -> String
+where
+    F: Fn(&str) -> Option<String>,
+{
+    // Parse the string for ${}
+    // Yet another state machine
+    let mut result = String::with_capacity(data.len());
+    #[derive(Debug)]
+    enum SubState {
+        Normal,
+        PlaceholderDollar,
+        PlaceholderBody,
+    }
 
-        let mut state = SubState::Normal;
-        let mut current_placeholder_start = 0;
+    let mut state = SubState::Normal;
+    let mut current_placeholder_start = 0;
 
-        for (i, c) in data.chars().enumerate() {
-            match state {
-                SubState::Normal if c == '$' => {
-                    state = SubState::PlaceholderDollar;
-                    continue;
-                }
-                SubState::Normal => {
-                    result.push(c);
-                }
-                SubState::PlaceholderDollar if c == '{' => {
-                    state = SubState::PlaceholderBody;
-                    current_placeholder_start = i;
-                    continue;
-                }
-                SubState::PlaceholderDollar if c.is_whitespace() => {
-                    continue;
-                }
-                SubState::PlaceholderDollar => {
-                    result.push(c);
-                    state = SubState::Normal;
-                    continue;
-                }
-                SubState::PlaceholderBody if c == '}' => {
-                    // The end of our tag
-                    // obtain the tag
-                    let substring = &data[(current_placeholder_start + 1)..i].trim();
-                    if let Some(property) = self.get_property(substring) {
-                        result.push_str(&property);
-                    }
-                    state = SubState::Normal;
-                }
-                SubState::PlaceholderBody => {
-                    continue;
+    for (i, c) in data.char_indices() {
+        match state {
+            SubState::Normal if c == '$' => {
+                state = SubState::PlaceholderDollar;
+                continue;
+            }
+            SubState::Normal => {
+                result.push(c);
+            }
+            SubState::PlaceholderDollar if c == '{' => {
+                state = SubState::PlaceholderBody;
+                current_placeholder_start = i;
+                continue;
+            }
+            SubState::PlaceholderDollar if c.is_whitespace() => {
+                continue;
+            }
+            SubState::PlaceholderDollar => {
+                result.push(c);
+                state = SubState::Normal;
+                continue;
+            }
+            SubState::PlaceholderBody if c == '}' => {
+                // The end of our tag
+                // obtain the tag
+                let substring = &data[(current_placeholder_start + 1)..i].trim();
+                if let Some(property) = lookup(substring) {
+                    result.push_str(&property);
                 }
+                state = SubState::Normal;
+            }
+            SubState::PlaceholderBody => {
+                continue;
             }
         }
-        result
     }
+    result
 }
 
 /// Parser states, helps in keeping track of the current event
@@ -860,6 +916,9 @@ enum ParserState {
     /// The properties of this project
     /// <properties></properties>
     Properties(PropertiesState),
+    /// The licenses of this project
+    /// <licenses></licenses>
+    Licenses(LicensesState),
     /// Used to indicate that under project we are in a tag we dont care about
     /// The argument is the level of xml tree we are at. 0 is at project level.
     /// Increment if we go deeper (Start tag) and decrement when we go up (End tag)
@@ -922,6 +981,26 @@ enum PropertiesState {
     ReadEntry,
 }
 
+/// Keeps track of the licenses specific events
+#[derive(Clone, Debug)]
+enum LicensesState {
+    /// Root licenses tag
+    /// <licenses></licenses>
+    Licenses,
+    /// A single license
+    /// <license></license>
+    License(License),
+    /// The license name
+    /// <name></name>
+    ReadName(License),
+    /// The license url
+    /// <url></url>
+    ReadUrl(License),
+    /// A license tag we dont care about e.g. <distribution>
+    /// The argument is the depth below <license>
+    Other(License, usize),
+}
+
 /// Keeps track of the exclusions specific events
 #[derive(Clone, Debug)]
 enum ExclusionsState {
@@ -1214,6 +1293,59 @@ impl Parser {
 
         Ok(new_state)
     }
+    fn parse_licenses(&mut self, event: Event, state: LicensesState) -> Result<LicensesState> {
+        let new_state = match state {
+            // <licenses></licenses>
+            LicensesState::Licenses => match event {
+                Event::Start(start) if start.local_name().into_inner() == tags::LICENSE => {
+                    LicensesState::License(License::default())
+                }
+                _ => LicensesState::Licenses,
+            },
+            // <license></license>
+            LicensesState::License(license) => match event {
+                Event::End(end) if end.local_name().into_inner() == tags::LICENSE => {
+                    self.project.licenses.push(license);
+                    LicensesState::Licenses
+                }
+                Event::Start(start) => match start.local_name().into_inner() {
+                    tags::NAME => LicensesState::ReadName(license),
+                    tags::URL => LicensesState::ReadUrl(license),
+                    _ => LicensesState::Other(license, 1),
+                },
+                _ => LicensesState::License(license),
+            },
+            // <name></name>
+            LicensesState::ReadName(mut license) => match event {
+                Event::End(end) if end.local_name().into_inner() == tags::NAME => {
+                    LicensesState::License(license)
+                }
+                Event::Text(e) => {
+                    license.name.push_str(e.unescape()?.trim());
+                    LicensesState::ReadName(license)
+                }
+                _ => LicensesState::ReadName(license),
+            },
+            // <url></url>
+            LicensesState::ReadUrl(mut license) => match event {
+                Event::End(end) if end.local_name().into_inner() == tags::URL => {
+                    LicensesState::License(license)
+                }
+                Event::Text(e) => {
+                    license.url = Some(e.unescape()?.trim().to_string());
+                    LicensesState::ReadUrl(license)
+                }
+                _ => LicensesState::ReadUrl(license),
+            },
+            LicensesState::Other(license, level) => match event {
+                Event::Start(_) => LicensesState::Other(license, level + 1),
+                Event::End(_) if level == 1 => LicensesState::License(license),
+                Event::End(_) => LicensesState::Other(license, level - 1),
+                _ => LicensesState::Other(license, level),
+            },
+        };
+        Ok(new_state)
+    }
     fn parse_parent(&mut self, event: Event, state: ParentState) -> Result<ParentState> {
         let new_state = match state {
             // <parent></parent>
@@ -1299,6 +1431,7 @@ impl Parser {
                         ParserState::Parent(ParentState::Parent)
                     }
                     tags::PROPERTIES => ParserState::Properties(PropertiesState::Properties),
+                    tags::LICENSES => ParserState::Licenses(LicensesState::Licenses),
                     _ => ParserState::Other(1),
                 },
                 _ => ParserState::Project,
@@ -1381,6 +1514,13 @@ impl Parser {
                 }
                 event => ParserState::Properties(self.parse_props(event, prop_state)?),
             },
+            // <licenses></licenses>
+            ParserState::Licenses(licenses_state) => match event {
+                Event::End(end) if end.local_name().into_inner() == tags::LICENSES => {
+                    ParserState::Project
+                }
+                event => ParserState::Licenses(self.parse_licenses(event, licenses_state)?),
+            },
             // <parent></parent>
             ParserState::Parent(parent_state) => match event {
                 Event::End(end) if end.local_name().into_inner() == tags::PARENT => {
@@ -1399,7 +1539,7 @@ fn substitute_properties_vars(project: &mut Project) -> anyhow::Result<()> {
     // try to substitute properties.
     // some basic intelligence can be applied here since not all projects use variables
 
-    if !project.properties.is_empty() {
+    if !project.properties.is_empty() || !get_properties().is_empty() {
         project.group_id = project.substitute_string(project.group_id.as_str());
         project.artifact_id = project.substitute_string(project.artifact_id.as_str());
         if let Some(version) = &project.selected_version {
@@ -1418,6 +1558,19 @@ fn substitute_properties_vars(project: &mut Project) -> anyhow::Result<()> {
                 .context("Failed to select a suitable version for dependency")?;
         }
     }
+    let management = std::mem::take(&mut project.dependency_management);
+    for (_, mut dep) in management {
+        dep.artifact_id = project.substitute_string(&dep.artifact_id);
+        dep.group_id = project.substitute_string(&dep.group_id);
+        if let Some(v) = &dep.selected_version {
+            let v = project.substitute_string(v);
+            dep.version = v
+                .parse()
+                .context("Failed to select a suitable version for managed dependency")?;
+            dep.selected_version = Some(v);
+        }
+        project.add_to_dependency_management(dep);
+    }
 
     Ok(())
 }
@@ -1981,6 +2134,33 @@ fn version_range_from_string() {
     assert!("   ".parse::<VersionRange>().is_err(),);
 }
 #[test]
+fn version_parsing_with_multibyte_characters() {
+    assert_eq!(
+        ">=1.0-ä".parse::<VersionRange>().unwrap(),
+        VersionRange::Ge("1.0-ä".to_string())
+    );
+    assert_eq!(
+        "<β2".parse::<VersionRange>().unwrap(),
+        VersionRange::Lt("β2".to_string())
+    );
+    assert_eq!(
+        "[1.0-ü,2.0-β)".parse::<VersionRequirement>().unwrap(),
+        VersionRequirement::Hard(vec![
+            VersionRange::Ge("1.0-ü".to_string()),
+            VersionRange::Lt("2.0-β".to_string())
+        ])
+    );
+    assert_eq!(
+        "(,1.0-日本],[2.0-ä,)"
+            .parse::<VersionRequirement>()
+            .unwrap(),
+        VersionRequirement::Hard(vec![
+            VersionRange::Le("1.0-日本".to_string()),
+            VersionRange::Ge("2.0-ä".to_string())
+        ])
+    );
+}
+#[test]
 fn version_range_to_string() {
     assert_eq!(
         VersionRange::Eq("2.0".to_string()).to_string().as_str(),
@@ -2055,3 +2235,62 @@ fn version_requirement_from_constraint() {
     );
 }
 // su
+
+#[test]
+fn parse_pom_licenses() {
+    let pom = r#"
+<project>
+    <groupId>com.example</groupId>
+    <artifactId>licensed</artifactId>
+    <version>1.0.0</version>
+    <licenses>
+        <license>
+            <name>The Apache Software License, Version 2.0</name>
+            <url>https://www.apache.org/licenses/LICENSE-2.0.txt</url>
+            <distribution>repo</distribution>
+        </license>
+        <license>
+            <name>MIT</name>
+        </license>
+    </licenses>
+    <dependencies>
+        <dependency>
+            <groupId>com.example</groupId>
+            <artifactId>dep</artifactId>
+            <version>1.0.0</version>
+        </dependency>
+    </dependencies>
+</project>
+"#;
+    let project = parse_pom(
+        BufReader::new(pom.as_bytes()),
+        Project::new("com.example", "licensed", "1.0.0"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        project.get_licenses(),
+        &vec![
+            License {
+                name: String::from("The Apache Software License, Version 2.0"),
+                url: Some(String::from(
+                    "https://www.apache.org/licenses/LICENSE-2.0.txt"
+                )),
+            },
+            License {
+                name: String::from("MIT"),
+                url: None,
+            },
+        ]
+    );
+    assert_eq!(project.get_dependencies().len(), 1);
+}
+
+#[test]
+fn scope_classpaths() {
+    let classpaths = |scope: Scope| (scope.on_compile_classpath(), scope.on_runtime_classpath());
+    assert_eq!(classpaths(Scope::COMPILE), (true, true));
+    assert_eq!(classpaths(Scope::PROVIDED), (true, false));
+    assert_eq!(classpaths(Scope::RUNTIME), (false, true));
+    assert_eq!(classpaths(Scope::TEST), (false, false));
+}
@@ -0,0 +1,127 @@
+//! The state labt keeps between calls. It lives in a [`Session`] instead of in
+//! process wide statics so that an embedder can work with several projects, each
+//! with its own labt home and config, in one process.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    sync::{atomic::AtomicBool, Arc, Mutex, RwLock},
+    time::Instant,
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    config::{
+        global::{Credentials, GlobalConfig},
+        maven_settings::MavenSettings,
+    },
+    http_recording::HttpRecording,
+    submodules::toolchains::{Tool, Toolchain},
+};
+
+/// The project and labt home selected with [`crate::set_manifest_path`] and
+/// [`crate::set_home`], the user wide config with what is read from it and the
+/// state of the requests sent so far.
+///
+/// Every thread works with its current session. A thread that never entered one
+/// with [`Session::enter`] uses the default session, which is what the labt
+/// binary does. Labt passes the session on to the worker threads it starts.
+#[derive(Default)]
+pub struct Session {
+    /// The manifest set by --manifest-path or by labt init for the project it
+    /// creates. When it is not set the project is discovered from the current
+    /// working directory on every call
+    pub(crate) manifest_path: RwLock<Option<PathBuf>>,
+    /// The nested projects that were already warned about
+    pub(crate) nested_warned: Mutex<Vec<PathBuf>>,
+    /// Labt home set by [`crate::set_home`]. When it is not set it is discovered
+    /// from the environment on every call
+    pub(crate) home: RwLock<Option<PathBuf>>,
+    /// config.toml once it is read or the config set by
+    /// [`crate::config::global::set_global_config`]
+    pub(crate) global_config: RwLock<Option<Arc<GlobalConfig>>>,
+    /// Repository credentials by base url
+    pub(crate) credentials: Mutex<Vec<(String, Credentials)>>,
+    /// The earliest time the next request may be sent to each host
+    pub(crate) host_pacing: Mutex<BTreeMap<String, Instant>>,
+    /// The imported settings.xml once it is read. Some(None) if importing is disabled
+    pub(crate) maven_settings: RwLock<Option<Option<Arc<MavenSettings>>>>,
+    /// The properties once they are read or the ones set by
+    /// [`crate::config::properties::set_properties`]
+    pub(crate) properties: RwLock<Option<Arc<HashMap<String, String>>>>,
+    /// Toolchains already located. Detecting a version may start a jvm so it is done once
+    pub(crate) toolchains: Mutex<HashMap<Tool, Toolchain>>,
+    /// The git dependency repositories already cloned or fetched
+    pub(crate) git_fetched: Mutex<HashSet<PathBuf>>,
+    /// The warnings logged during the running build. None outside a build
+    pub(crate) build_warnings: Mutex<Option<Vec<String>>>,
+    /// Set by [`crate::set_offline`]
+    pub(crate) offline: AtomicBool,
+    /// Set by [`crate::http_recording::set_http_recording`]
+    pub(crate) http_recording: RwLock<Option<HttpRecording>>,
+}
+
+lazy_static! {
+    static ref DEFAULT_SESSION: Arc<Session> = Arc::new(Session::default());
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Arc<Session>>> = const { RefCell::new(None) };
+}
+
+/// Puts back the session that was current before [`Session::enter`], also when
+/// the closure panics
+struct Restore(Option<Arc<Session>>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        let previous = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = previous);
+    }
+}
+
+impl Session {
+    /// A session with nothing selected or read yet
+    pub fn new() -> Arc<Self> {
+        Arc::new(Session::default())
+    }
+    /// The session of the calling thread
+    pub fn current() -> Arc<Session> {
+        CURRENT
+            .with(|current| current.borrow().clone())
+            .unwrap_or_else(|| Arc::clone(&DEFAULT_SESSION))
+    }
+    /// Makes this the current session of the calling thread for the rest of its
+    /// life. For the worker threads of a tokio runtime, which run the tasks of
+    /// the thread that started it
+    pub(crate) fn attach(self: &Arc<Self>) {
+        CURRENT.with(|current| *current.borrow_mut() = Some(Arc::clone(self)));
+    }
+    /// Runs f with this session as the current session of the calling thread.
+    /// The previous session is current again once f returns
+    pub fn enter<T>(self: &Arc<Self>, f: impl FnOnce() -> T) -> T {
+        let previous = CURRENT.with(|current| current.borrow_mut().replace(Arc::clone(self)));
+        let _restore = Restore(previous);
+        f()
+    }
+}
+
+#[test]
+fn sessions_are_separate() {
+    let first = Session::new();
+    let second = Session::new();
+    first.enter(|| {
+        crate::set_home(Some(PathBuf::from("/first")));
+        crate::set_offline(true);
+        second.enter(|| {
+            crate::set_home(Some(PathBuf::from("/second")));
+            assert_eq!(crate::get_home().unwrap(), PathBuf::from("/second"));
+            assert!(!crate::is_offline());
+        });
+        assert_eq!(crate::get_home().unwrap(), PathBuf::from("/first"));
+        assert!(crate::is_offline());
+    });
+    assert!(!Arc::ptr_eq(&Session::current(), &first));
+}
@@ -1,11 +1,24 @@
+use std::io::IsTerminal;
+
 use crate::{
-    config::{add_dependency_to_config, get_resolvers},
+    config::{
+        add_dependency_to_config, get_resolve_config, get_resolvers_from_config,
+        global::client_builder,
+    },
     pom::Project,
+    version::ComparableVersion,
 };
 
-use super::{resolve::resolve, Submodule};
-use anyhow::{Context, Result};
+use super::{
+    index::{central_versions, find_artifacts, latest_indexed_version, IndexEntry, CENTRAL_INDEX},
+    resolve::{get_config_dependencies, resolve_added, ResolveOptions},
+    Submodule,
+};
+use anyhow::{bail, Context, Result};
 use clap::{Args, Command};
+use dialoguer::{theme::ColorfulTheme, Select};
+use lazy_static::lazy_static;
+use log::warn;
 use regex::Regex;
 
 #[derive(Clone, Args)]
@@ -16,14 +29,21 @@ pub struct AddArgs {
     /// dependency name
     #[arg(short)]
     pub artifact_id: Option<String>,
-    /// Version
+    /// Version. Defaults to the newest version in the repository index
     #[arg(short = 'V')]
     pub version: Option<String>,
     /// Dependency string in the form group_id:artifact_id:version
-    /// e.g. com.example:project1:1.0.0
+    /// e.g. com.example:project1:1.0.0. The version can be left out to use
+    /// the newest version in the repository index. An artifact name alone is
+    /// searched for and the matches are offered to pick from
     pub dependency: Option<String>,
 }
 
+lazy_static! {
+    /// A dependency given by its artifact name alone
+    static ref ARTIFACT_NAME: Regex = Regex::new(r"^[\w\.\-\d]+$").unwrap();
+}
+
 pub struct Add {
     pub args: AddArgs,
 }
@@ -32,7 +52,11 @@ impl Add {
     pub fn new(args: &AddArgs) -> Add {
         Add { args: args.clone() }
     }
-    fn parse_dependency(&mut self) -> clap::error::Result<(String, String, String)> {
+    /// Returns the group id, artifact id and version given. The group id is None
+    /// if only an artifact name was given
+    fn parse_dependency(
+        &mut self,
+    ) -> clap::error::Result<(Option<String>, String, Option<String>)> {
         use clap::error::ContextKind;
         use clap::error::ContextValue;
         use clap::error::ErrorKind;
@@ -41,19 +65,17 @@ impl Add {
 
         if let Some(dep) = &self.args.dependency {
             // if dependency positional argument was provided, try to parse it
-            let re = Regex::new(r"^([\w\.\-\d]+):([\w\.\-\d]+):([\w\.-]+)$")
+            let re = Regex::new(r"^([\w\.\-\d]+):([\w\.\-\d]+)(?::([\w\.-]+))?$")
                 .context("Invalid regex")
                 .unwrap();
             if let Some(group) = re.captures(dep) {
                 let group_id = &group[1];
                 let artifact_id = &group[2];
-                let version = &group[3];
+                let version = group.get(3).map(|version| version.as_str().to_string());
 
-                return Ok((
-                    group_id.to_string(),
-                    artifact_id.to_string(),
-                    version.to_string(),
-                ));
+                return Ok((Some(group_id.to_string()), artifact_id.to_string(), version));
+            } else if ARTIFACT_NAME.is_match(dep) {
+                return Ok((None, dep.to_string(), self.args.version.clone()));
             } else {
                 let mut err = Error::new(ErrorKind::InvalidValue).with_cmd(&cmd);
                 err.insert(ContextKind::InvalidArg, ContextValue::String("invalid dependency string format, allowed format is groupid:artifactid:version".to_string()));
@@ -66,19 +88,89 @@ impl Add {
             err.insert(ContextKind::Usage, ContextValue::String("-a".to_string()));
             err
         })?;
-        let group_id = self.args.group_id.clone().ok_or({
-            let mut err = Error::new(ErrorKind::MissingRequiredArgument).with_cmd(&cmd);
-            err.insert(ContextKind::Usage, ContextValue::String("-g".to_string()));
-            err
-        })?;
-        let version = self.args.version.clone().ok_or({
-            let mut err = Error::new(ErrorKind::MissingRequiredArgument).with_cmd(&cmd);
-            err.insert(ContextKind::Usage, ContextValue::String("-V".to_string()));
-            err
-        })?;
+        Ok((
+            self.args.group_id.clone(),
+            artifact_id,
+            self.args.version.clone(),
+        ))
+    }
+}
 
-        Ok((group_id, artifact_id, version))
+/// The versions of entry newest first. Artifacts found on Maven Central only
+/// list their newest version so the rest are looked up.
+fn entry_versions(entry: &IndexEntry) -> Vec<String> {
+    let mut versions = entry.versions.clone();
+    if entry.repositories.iter().any(|r| r == CENTRAL_INDEX) && !crate::is_offline() {
+        let found = client_builder()
+            .and_then(|builder| builder.build().context("Error creating download client"))
+            .and_then(|client| central_versions(&client, &entry.group_id, &entry.artifact_id));
+        match found {
+            Ok(found) => versions.extend(found),
+            Err(err) => {
+                warn!(target: "add", "Failed to list the versions of {}: {:?}", entry.coordinate(), err)
+            }
+        }
+    }
+    versions.sort_by_cached_key(|version| std::cmp::Reverse(ComparableVersion::new(version)));
+    versions.dedup();
+    versions
+}
+
+/// Searches for artifacts named like query and asks which one, and which
+/// version of it unless version is given, to add
+fn pick_artifact(query: &str, version: Option<String>) -> Result<(String, String, String)> {
+    let entries = find_artifacts(query)?;
+    if entries.is_empty() {
+        bail!("No artifact matching {query} was found. Run labt index to search the configured repositories or give the full group_id:artifact_id:version");
     }
+    if !std::io::stdin().is_terminal() {
+        let matches: Vec<String> = entries.iter().map(|e| e.coordinate()).collect();
+        bail!(
+            "{query} matches {}. Give one of them as group_id:artifact_id",
+            matches.join(", ")
+        );
+    }
+    let theme = ColorfulTheme::default();
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{} {} ({})",
+                entry.coordinate(),
+                entry.latest().unwrap_or_default(),
+                entry.repositories.join(", ")
+            )
+        })
+        .collect();
+    let picked = Select::with_theme(&theme)
+        .with_prompt(format!("Artifacts matching {query}"))
+        .items(&items)
+        .default(0)
+        .interact()
+        .context("Failed to show artifact selection prompt")?;
+    let entry = &entries[picked];
+    if let Some(version) = version {
+        return Ok((entry.group_id.clone(), entry.artifact_id.clone(), version));
+    }
+    let versions = entry_versions(entry);
+    if versions.is_empty() {
+        bail!(
+            "No versions of {} are known. Give one with -V",
+            entry.coordinate()
+        );
+    }
+    let picked = Select::with_theme(&theme)
+        .with_prompt(format!("Version of {}", entry.coordinate()))
+        .items(&versions)
+        .default(0)
+        .max_length(15)
+        .interact()
+        .context("Failed to show version selection prompt")?;
+    Ok((
+        entry.group_id.clone(),
+        entry.artifact_id.clone(),
+        versions[picked].clone(),
+    ))
 }
 
 impl Submodule for Add {
@@ -91,11 +183,33 @@ impl Submodule for Add {
                 return Ok(());
             }
         };
+        let (group_id, artifact_id, version) = match group_id {
+            Some(group_id) => (group_id, artifact_id, version),
+            None => {
+                let (group_id, artifact_id, picked) = pick_artifact(&artifact_id, version)?;
+                (group_id, artifact_id, Some(picked))
+            }
+        };
+        let version = match version {
+            Some(version) => version,
+            None => latest_indexed_version(&group_id, &artifact_id)?.context(format!(
+                "No version given and {group_id}:{artifact_id} is not in the repository index. Specify a version or run labt index"
+            ))?,
+        };
+        // the dependencies the lock file was resolved for
+        let previous = get_config_dependencies(&get_resolve_config()?)?;
         add_dependency_to_config(group_id.clone(), artifact_id.clone(), version.clone())?;
-        let resolvers = get_resolvers().context("Failed to get resolvers from Labt.toml config")?;
+        let config = get_resolve_config()?;
+        let resolvers = get_resolvers_from_config(&config)
+            .context("Failed to get resolvers from Labt.toml config")?;
         let mut project = Project::new(group_id.as_str(), artifact_id.as_str(), version.as_str());
         project.set_selected_version(Some(version.clone()));
-        resolve(vec![project], resolvers)?;
+        resolve_added(
+            previous,
+            project,
+            resolvers,
+            ResolveOptions::try_from(&config)?,
+        )?;
 
         // println!("{:?}", project);
 
@@ -0,0 +1,805 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Subcommand};
+use console::style;
+use indicatif::HumanBytes;
+use log::info;
+use serde::Serialize;
+use zip::ZipArchive;
+
+use super::Submodule;
+
+/// The magic string found at the end of an APK signing block
+pub(crate) const APK_SIG_BLOCK_MAGIC: &[u8; 16] = b"APK Sig Block 42";
+/// Signing block pair id for APK signature scheme v2
+pub(crate) const APK_SIGNATURE_SCHEME_V2_ID: u32 = 0x7109871a;
+/// Signing block pair id for APK signature scheme v3
+const APK_SIGNATURE_SCHEME_V3_ID: u32 = 0xf05368c0;
+/// Signing block pair id for APK signature scheme v3.1
+const APK_SIGNATURE_SCHEME_V31_ID: u32 = 0x1b93ad61;
+/// End of central directory record signature
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+/// Minimum size of the end of central directory record
+const EOCD_MIN_SIZE: u64 = 22;
+/// Offset of method_ids_size in the dex header
+const DEX_METHOD_IDS_SIZE_OFFSET: usize = 0x58;
+/// Chunk type of a binary xml file
+const RES_XML_TYPE: u16 = 0x0003;
+/// Chunk type of a string pool
+const RES_STRING_POOL_TYPE: u16 = 0x0001;
+/// Chunk type of the resource ids of the attribute names
+const RES_XML_RESOURCE_MAP_TYPE: u16 = 0x0180;
+/// Chunk type of a start tag
+const RES_XML_START_ELEMENT_TYPE: u16 = 0x0102;
+/// String pool flag of UTF-8 strings, otherwise they are UTF-16
+const STRING_POOL_UTF8_FLAG: u32 = 1 << 8;
+/// Resource id of the android:minSdkVersion attribute
+const MIN_SDK_VERSION_ATTR: u32 = 0x0101020c;
+/// Attribute value types
+const TYPE_STRING: u8 = 0x03;
+const TYPE_INT_DEC: u8 = 0x10;
+const TYPE_INT_HEX: u8 = 0x11;
+
+#[derive(Clone, Args)]
+pub struct ApkArgs {
+    #[command(subcommand)]
+    command: ApkSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum ApkSubcommands {
+    /// Prints a summary of the apk contents
+    Inspect(InspectArgs),
+}
+
+#[derive(Clone, Args)]
+pub struct InspectArgs {
+    /// Path to the apk file
+    path: PathBuf,
+    /// Export a json size treemap of the apk entries to this file
+    #[arg(long)]
+    treemap: Option<PathBuf>,
+}
+
+pub struct Apk {
+    args: ApkArgs,
+}
+
+impl Apk {
+    pub fn new(args: &ApkArgs) -> Self {
+        Apk { args: args.clone() }
+    }
+}
+
+/// A single dex file found in the apk
+#[derive(Debug, Default, Serialize)]
+pub struct DexInfo {
+    pub name: String,
+    pub size: u64,
+    pub methods: u32,
+}
+
+/// Apk signature schemes detected on the apk
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SignatureSchemes {
+    /// Jar signing
+    pub v1: bool,
+    pub v2: bool,
+    pub v3: bool,
+    pub v31: bool,
+}
+
+/// Aggregated size of a group of apk entries
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct EntrySize {
+    pub count: u64,
+    pub size: u64,
+    pub compressed_size: u64,
+}
+
+impl EntrySize {
+    fn add(&mut self, size: u64, compressed_size: u64) {
+        self.count += 1;
+        self.size += size;
+        self.compressed_size += compressed_size;
+    }
+}
+
+/// A node of the apk size treemap
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct TreemapNode {
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreemapNode>,
+}
+
+impl TreemapNode {
+    pub fn new(name: &str) -> Self {
+        TreemapNode {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+    /// Inserts a zip entry into the tree creating intermediate
+    /// directory nodes if needed. Sizes are accumulated up the tree.
+    pub fn insert(&mut self, path: &str, size: u64, compressed_size: u64) {
+        self.size += size;
+        self.compressed_size += compressed_size;
+
+        let mut segments = path.splitn(2, '/');
+        let head = segments.next().unwrap_or_default();
+        if head.is_empty() {
+            return;
+        }
+        let rest = segments.next().unwrap_or_default();
+
+        let child = if let Some(index) = self.children.iter().position(|c| c.name == head) {
+            &mut self.children[index]
+        } else {
+            self.children.push(TreemapNode::new(head));
+            self.children.last_mut().unwrap()
+        };
+
+        if rest.is_empty() {
+            child.size += size;
+            child.compressed_size += compressed_size;
+        } else {
+            child.insert(rest, size, compressed_size);
+        }
+    }
+}
+
+/// The inspection results of an apk
+#[derive(Debug, Default, Serialize)]
+pub struct ApkReport {
+    pub size: u64,
+    pub dex: Vec<DexInfo>,
+    /// resource sizes grouped by resource type e.g. drawable, layout
+    pub resources: BTreeMap<String, EntrySize>,
+    /// native libraries grouped by abi
+    pub native_libs: BTreeMap<String, Vec<(String, u64)>>,
+    pub assets: EntrySize,
+    pub other: EntrySize,
+    pub signatures: SignatureSchemes,
+    #[serde(skip)]
+    pub treemap: TreemapNode,
+}
+
+/// The type of file stored in the apk
+#[derive(Debug, PartialEq, Eq)]
+enum EntryKind {
+    Dex,
+    /// Compiled resources with their type e.g drawable
+    Resource(String),
+    /// The resource table
+    ResourceTable,
+    /// Native libraries with their abi
+    NativeLib(String),
+    Asset,
+    /// v1 signature files
+    Signature,
+    Other,
+}
+
+impl EntryKind {
+    fn from_name(name: &str) -> Self {
+        if name.ends_with(".dex") && !name.contains('/') {
+            return EntryKind::Dex;
+        }
+        if name == "resources.arsc" {
+            return EntryKind::ResourceTable;
+        }
+        if let Some(rest) = name.strip_prefix("res/") {
+            if let Some((dir, _)) = rest.split_once('/') {
+                // strip config qualifiers e.g. drawable-hdpi-v4 -> drawable
+                let res_type = dir.split('-').next().unwrap_or(dir);
+                return EntryKind::Resource(res_type.to_string());
+            }
+        }
+        if let Some(rest) = name.strip_prefix("lib/") {
+            if let Some((abi, _)) = rest.split_once('/') {
+                return EntryKind::NativeLib(abi.to_string());
+            }
+        }
+        if name.starts_with("assets/") {
+            return EntryKind::Asset;
+        }
+        if let Some(file) = name.strip_prefix("META-INF/") {
+            let upper = file.to_uppercase();
+            if upper.ends_with(".SF")
+                || upper.ends_with(".RSA")
+                || upper.ends_with(".DSA")
+                || upper.ends_with(".EC")
+            {
+                return EntryKind::Signature;
+            }
+        }
+        EntryKind::Other
+    }
+}
+
+/// Reads the number of method references from a dex file header
+/// # Errors
+/// Returns an error if the data is not a valid dex header
+fn dex_method_count(header: &[u8]) -> anyhow::Result<u32> {
+    if header.len() < DEX_METHOD_IDS_SIZE_OFFSET + 4 || !header.starts_with(b"dex\n") {
+        bail!("Invalid dex file header");
+    }
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&header[DEX_METHOD_IDS_SIZE_OFFSET..DEX_METHOD_IDS_SIZE_OFFSET + 4]);
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn le_u16(data: &[u8], offset: usize) -> anyhow::Result<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes(bytes.try_into().unwrap()))
+        .context("Truncated binary xml")
+}
+
+fn le_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+        .context("Truncated binary xml")
+}
+
+/// The string pool chunk of a binary xml file
+struct StringPool<'a> {
+    chunk: &'a [u8],
+    offsets: usize,
+    count: u32,
+    strings: usize,
+    utf8: bool,
+}
+
+impl<'a> StringPool<'a> {
+    fn new(chunk: &'a [u8], header_size: usize) -> anyhow::Result<Self> {
+        Ok(StringPool {
+            chunk,
+            offsets: header_size,
+            count: le_u32(chunk, 8)?,
+            utf8: le_u32(chunk, 16)? & STRING_POOL_UTF8_FLAG != 0,
+            strings: le_u32(chunk, 20)? as usize,
+        })
+    }
+    fn get(&self, index: u32) -> anyhow::Result<String> {
+        if index >= self.count {
+            bail!("Invalid string index {index} in binary xml");
+        }
+        let mut start =
+            self.strings + le_u32(self.chunk, self.offsets + index as usize * 4)? as usize;
+        if self.utf8 {
+            // the length in UTF-16 units and then in bytes, each one or two bytes long
+            let mut length = || -> anyhow::Result<usize> {
+                let first = *self.chunk.get(start).context("Truncated binary xml")? as usize;
+                start += 1;
+                if first & 0x80 == 0 {
+                    return Ok(first);
+                }
+                let second = *self.chunk.get(start).context("Truncated binary xml")? as usize;
+                start += 1;
+                Ok((first & 0x7f) << 8 | second)
+            };
+            length()?;
+            let len = length()?;
+            let bytes = self
+                .chunk
+                .get(start..start + len)
+                .context("Truncated binary xml")?;
+            Ok(String::from_utf8_lossy(bytes).to_string())
+        } else {
+            let mut len = le_u16(self.chunk, start)? as usize;
+            start += 2;
+            if len & 0x8000 != 0 {
+                len = (len & 0x7fff) << 16 | le_u16(self.chunk, start)? as usize;
+                start += 2;
+            }
+            let units = (0..len)
+                .map(|i| le_u16(self.chunk, start + i * 2))
+                .collect::<anyhow::Result<Vec<u16>>>()?;
+            Ok(String::from_utf16_lossy(&units))
+        }
+    }
+}
+
+/// Reads minSdkVersion from the uses-sdk tag of a compiled AndroidManifest.xml.
+/// Android treats a missing minSdkVersion as 1 and the codename of a preview
+/// platform as newer than every released one.
+/// # Errors
+/// Returns an error if the manifest is not valid binary xml
+pub(crate) fn min_sdk_version(manifest: &[u8]) -> anyhow::Result<u32> {
+    if le_u16(manifest, 0)? != RES_XML_TYPE {
+        bail!("The manifest is not binary xml");
+    }
+    let mut pool = None;
+    let mut resource_ids = Vec::new();
+    let mut offset = le_u16(manifest, 2)? as usize;
+    while offset < manifest.len() {
+        let kind = le_u16(manifest, offset)?;
+        let header_size = le_u16(manifest, offset + 2)? as usize;
+        let size = le_u32(manifest, offset + 4)? as usize;
+        let chunk = manifest
+            .get(offset..offset.saturating_add(size))
+            .filter(|_| size >= 8 && header_size <= size)
+            .context("Invalid binary xml chunk")?;
+        match kind {
+            RES_STRING_POOL_TYPE => pool = Some(StringPool::new(chunk, header_size)?),
+            RES_XML_RESOURCE_MAP_TYPE => {
+                resource_ids = (header_size..size)
+                    .step_by(4)
+                    .map(|id| le_u32(chunk, id))
+                    .collect::<anyhow::Result<_>>()?;
+            }
+            RES_XML_START_ELEMENT_TYPE => {
+                let pool = pool.as_ref().context("Binary xml without a string pool")?;
+                if pool.get(le_u32(chunk, header_size + 4)?)? != "uses-sdk" {
+                    offset += size;
+                    continue;
+                }
+                let start = header_size + le_u16(chunk, header_size + 8)? as usize;
+                let attribute_size = le_u16(chunk, header_size + 10)? as usize;
+                let count = le_u16(chunk, header_size + 12)? as usize;
+                for attribute in (0..count).map(|i| start + i * attribute_size) {
+                    let name = le_u32(chunk, attribute + 4)?;
+                    if resource_ids.get(name as usize) != Some(&MIN_SDK_VERSION_ATTR)
+                        && pool.get(name)? != "minSdkVersion"
+                    {
+                        continue;
+                    }
+                    let kind = *chunk.get(attribute + 15).context("Truncated binary xml")?;
+                    return match kind {
+                        TYPE_INT_DEC | TYPE_INT_HEX => le_u32(chunk, attribute + 16),
+                        TYPE_STRING => Ok(pool
+                            .get(le_u32(chunk, attribute + 8)?)?
+                            .parse()
+                            .unwrap_or(u32::MAX)),
+                        kind => bail!("minSdkVersion has the unexpected value type {kind:#x}"),
+                    };
+                }
+                return Ok(1);
+            }
+            _ => {}
+        }
+        offset += size;
+    }
+    Ok(1)
+}
+
+/// Returns the offset of the zip end of central directory record in data, searching
+/// the end of data as the record may be followed by a comment of up to u16::MAX bytes
+pub(crate) fn find_eocd(data: &[u8]) -> Option<usize> {
+    let last = data.len().checked_sub(EOCD_MIN_SIZE as usize)?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|i| u32::from_le_bytes(data[*i..*i + 4].try_into().unwrap()) == EOCD_SIGNATURE)
+}
+
+/// Locates the APK signing block that sits just before the zip central directory
+/// and returns the ids of all the pairs it contains.
+/// Returns an empty list if the apk has no signing block.
+/// # Errors
+/// Returns an error if an IO error occurs or the zip structure is malformed
+pub(crate) fn signing_block_ids<R: Read + Seek>(reader: &mut R) -> anyhow::Result<Vec<u32>> {
+    let file_size = reader.seek(SeekFrom::End(0))?;
+    if file_size < EOCD_MIN_SIZE {
+        bail!("File too small to be a zip archive");
+    }
+    // The EOCD may be followed by a comment of up to u16::MAX bytes
+    let search_size = file_size.min(EOCD_MIN_SIZE + u16::MAX as u64);
+    reader.seek(SeekFrom::Start(file_size - search_size))?;
+    let mut tail = vec![0u8; search_size as usize];
+    reader.read_exact(&mut tail)?;
+
+    let eocd = find_eocd(&tail).context("Unable to locate zip end of central directory record")?;
+    let cd_offset = u32::from_le_bytes(tail[eocd + 16..eocd + 20].try_into().unwrap()) as u64;
+
+    if cd_offset < 32 {
+        return Ok(Vec::new());
+    }
+
+    // size of block (u64) followed by the magic
+    let mut footer = [0u8; 24];
+    reader.seek(SeekFrom::Start(cd_offset - 24))?;
+    reader.read_exact(&mut footer)?;
+    if &footer[8..] != APK_SIG_BLOCK_MAGIC {
+        return Ok(Vec::new());
+    }
+    let block_size = u64::from_le_bytes(footer[..8].try_into().unwrap());
+    // the block size excludes the leading size field and covers at least the
+    // trailing size field and the magic
+    let block_start = block_size
+        .checked_add(8)
+        .filter(|_| block_size >= 24)
+        .and_then(|size| cd_offset.checked_sub(size))
+        .context("Invalid APK signing block size")?;
+
+    let mut pairs = vec![0u8; (block_size - 24) as usize];
+    reader.seek(SeekFrom::Start(block_start + 8))?;
+    reader.read_exact(&mut pairs)?;
+
+    let mut ids = Vec::new();
+    let mut offset = 0usize;
+    while offset + 12 <= pairs.len() {
+        let len = u64::from_le_bytes(pairs[offset..offset + 8].try_into().unwrap());
+        let id = u32::from_le_bytes(pairs[offset + 8..offset + 12].try_into().unwrap());
+        ids.push(id);
+        offset = usize::try_from(len)
+            .ok()
+            .and_then(|len| len.checked_add(8))
+            .and_then(|len| offset.checked_add(len))
+            .context("Invalid APK signing block pair length")?;
+    }
+
+    Ok(ids)
+}
+
+/// Inspects the apk at path collecting a summary of its contents
+/// # Errors
+/// Returns an error if the apk cannot be opened or read as a zip archive
+pub fn inspect_apk(path: &Path) -> anyhow::Result<ApkReport> {
+    let file = File::open(path).context(format!("Failed to open apk at {:?}", path))?;
+    let mut report = ApkReport {
+        size: file.metadata()?.len(),
+        treemap: TreemapNode::new(&path.file_name().unwrap_or_default().to_string_lossy()),
+        ..Default::default()
+    };
+
+    let mut archive = ZipArchive::new(file).context("Failed to read apk as a zip archive")?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let size = entry.size();
+        let compressed_size = entry.compressed_size();
+        report.treemap.insert(&name, size, compressed_size);
+
+        match EntryKind::from_name(&name) {
+            EntryKind::Dex => {
+                let mut header = Vec::with_capacity(DEX_METHOD_IDS_SIZE_OFFSET + 4);
+                (&mut entry)
+                    .take(DEX_METHOD_IDS_SIZE_OFFSET as u64 + 4)
+                    .read_to_end(&mut header)?;
+                let methods =
+                    dex_method_count(&header).context(format!("Failed to read {}", name))?;
+                report.dex.push(DexInfo {
+                    name,
+                    size,
+                    methods,
+                });
+            }
+            EntryKind::Resource(res_type) => {
+                report
+                    .resources
+                    .entry(res_type)
+                    .or_default()
+                    .add(size, compressed_size);
+            }
+            EntryKind::ResourceTable => {
+                report
+                    .resources
+                    .entry(String::from("resources.arsc"))
+                    .or_default()
+                    .add(size, compressed_size);
+            }
+            EntryKind::NativeLib(abi) => {
+                let lib = name.rsplit('/').next().unwrap_or(&name).to_string();
+                report.native_libs.entry(abi).or_default().push((lib, size));
+            }
+            EntryKind::Asset => report.assets.add(size, compressed_size),
+            EntryKind::Signature => {
+                report.signatures.v1 = true;
+                report.other.add(size, compressed_size);
+            }
+            EntryKind::Other => report.other.add(size, compressed_size),
+        }
+    }
+
+    let mut file = archive.into_inner();
+    for id in signing_block_ids(&mut file).context("Failed to read APK signing block")? {
+        match id {
+            APK_SIGNATURE_SCHEME_V2_ID => report.signatures.v2 = true,
+            APK_SIGNATURE_SCHEME_V3_ID => report.signatures.v3 = true,
+            APK_SIGNATURE_SCHEME_V31_ID => report.signatures.v31 = true,
+            _ => {}
+        }
+    }
+
+    Ok(report)
+}
+
+fn print_report(report: &ApkReport) {
+    println!("{} {}", style("APK size:").bold(), HumanBytes(report.size));
+
+    println!("\n{}", style("Dex files").bold().underlined());
+    let mut total_methods = 0;
+    for dex in &report.dex {
+        total_methods += dex.methods;
+        println!(
+            "  {:<20} {:>10} methods {:>12}",
+            dex.name,
+            dex.methods,
+            HumanBytes(dex.size).to_string()
+        );
+    }
+    println!("  {:<20} {:>10} methods", "total", total_methods);
+
+    println!("\n{}", style("Resources").bold().underlined());
+    for (res_type, size) in &report.resources {
+        println!(
+            "  {:<20} {:>6} files {:>12} ({} compressed)",
+            res_type,
+            size.count,
+            HumanBytes(size.size).to_string(),
+            HumanBytes(size.compressed_size)
+        );
+    }
+    println!(
+        "  {:<20} {:>6} files {:>12} ({} compressed)",
+        "assets",
+        report.assets.count,
+        HumanBytes(report.assets.size).to_string(),
+        HumanBytes(report.assets.compressed_size)
+    );
+
+    println!("\n{}", style("Native libraries").bold().underlined());
+    if report.native_libs.is_empty() {
+        println!("  none");
+    }
+    for (abi, libs) in &report.native_libs {
+        let total: u64 = libs.iter().map(|(_, size)| size).sum();
+        println!("  {} ({})", style(abi).cyan(), HumanBytes(total));
+        for (lib, size) in libs {
+            println!("    {:<30} {:>12}", lib, HumanBytes(*size).to_string());
+        }
+    }
+
+    println!("\n{}", style("Signature schemes").bold().underlined());
+    let yes_no = |v: bool| {
+        if v {
+            style("yes").green()
+        } else {
+            style("no").dim()
+        }
+    };
+    println!("  v1 (jar)  {}", yes_no(report.signatures.v1));
+    println!("  v2        {}", yes_no(report.signatures.v2));
+    println!("  v3        {}", yes_no(report.signatures.v3));
+    println!("  v3.1      {}", yes_no(report.signatures.v31));
+}
+
+impl Submodule for Apk {
+    fn run(&mut self) -> anyhow::Result<()> {
+        match &self.args.command {
+            ApkSubcommands::Inspect(args) => {
+                let report = inspect_apk(&args.path)
+                    .context(format!("Failed to inspect apk at {:?}", args.path))?;
+                print_report(&report);
+
+                if let Some(output) = &args.treemap {
+                    let file = File::create(output)
+                        .context(format!("Failed to create treemap file at {:?}", output))?;
+                    serde_json::to_writer_pretty(file, &report.treemap)
+                        .context("Failed to write treemap json")?;
+                    info!(target: "apk", "Exported size treemap to {:?}", output);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn apk_entry_kind_from_name() {
+    assert_eq!(EntryKind::from_name("classes.dex"), EntryKind::Dex);
+    assert_eq!(EntryKind::from_name("classes2.dex"), EntryKind::Dex);
+    assert_eq!(EntryKind::from_name("assets/data.dex"), EntryKind::Asset);
+    assert_eq!(
+        EntryKind::from_name("res/drawable-hdpi-v4/icon.png"),
+        EntryKind::Resource("drawable".to_string())
+    );
+    assert_eq!(
+        EntryKind::from_name("res/layout/main.xml"),
+        EntryKind::Resource("layout".to_string())
+    );
+    assert_eq!(
+        EntryKind::from_name("lib/arm64-v8a/libfoo.so"),
+        EntryKind::NativeLib("arm64-v8a".to_string())
+    );
+    assert_eq!(
+        EntryKind::from_name("resources.arsc"),
+        EntryKind::ResourceTable
+    );
+    assert_eq!(
+        EntryKind::from_name("META-INF/CERT.RSA"),
+        EntryKind::Signature
+    );
+    assert_eq!(
+        EntryKind::from_name("META-INF/MANIFEST.MF"),
+        EntryKind::Other
+    );
+}
+
+#[test]
+fn apk_dex_method_count() {
+    let mut header = vec![0u8; 0x70];
+    header[..8].copy_from_slice(b"dex\n035\0");
+    header[DEX_METHOD_IDS_SIZE_OFFSET..DEX_METHOD_IDS_SIZE_OFFSET + 4]
+        .copy_from_slice(&1234u32.to_le_bytes());
+    assert_eq!(dex_method_count(&header).unwrap(), 1234);
+
+    assert!(dex_method_count(b"not a dex").is_err());
+}
+
+/// A compiled AndroidManifest.xml with a uses-sdk tag with the given minSdkVersion
+#[cfg(test)]
+pub(crate) fn test_binary_manifest(min_sdk: Option<u32>) -> Vec<u8> {
+    let chunk = |kind: u16, header_size: u16, body: &[u8]| {
+        let mut chunk = kind.to_le_bytes().to_vec();
+        chunk.extend_from_slice(&header_size.to_le_bytes());
+        chunk.extend_from_slice(&(body.len() as u32 + 8).to_le_bytes());
+        chunk.extend_from_slice(body);
+        chunk
+    };
+
+    // UTF-16 strings: 0 minSdkVersion, 1 manifest, 2 uses-sdk
+    let names = ["minSdkVersion", "manifest", "uses-sdk"];
+    let mut offsets = Vec::new();
+    let mut strings = Vec::new();
+    for name in names {
+        offsets.extend_from_slice(&(strings.len() as u32).to_le_bytes());
+        strings.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        strings.extend(name.encode_utf16().flat_map(u16::to_le_bytes));
+        strings.extend_from_slice(&[0, 0]);
+    }
+    let mut pool = Vec::new();
+    for value in [names.len() as u32, 0, 0, 28 + offsets.len() as u32, 0] {
+        pool.extend_from_slice(&value.to_le_bytes());
+    }
+    pool.extend(offsets);
+    pool.extend(strings);
+
+    let element = |name: u32, attributes: &[(u32, u32)]| {
+        // line number, comment, namespace and name
+        let mut body = Vec::new();
+        for value in [1, u32::MAX, u32::MAX, name] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [20u16, 20, attributes.len() as u16, 0, 0, 0] {
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        for (name, value) in attributes {
+            for value in [u32::MAX, *name, u32::MAX] {
+                body.extend_from_slice(&value.to_le_bytes());
+            }
+            body.extend_from_slice(&[8, 0, 0, TYPE_INT_DEC]);
+            body.extend_from_slice(&value.to_le_bytes());
+        }
+        chunk(RES_XML_START_ELEMENT_TYPE, 16, &body)
+    };
+    let mut xml = chunk(RES_STRING_POOL_TYPE, 28, &pool);
+    xml.extend(chunk(
+        RES_XML_RESOURCE_MAP_TYPE,
+        8,
+        &MIN_SDK_VERSION_ATTR.to_le_bytes(),
+    ));
+    xml.extend(element(1, &[]));
+    let attributes: Vec<(u32, u32)> = min_sdk.into_iter().map(|min_sdk| (0, min_sdk)).collect();
+    xml.extend(element(2, &attributes));
+    chunk(RES_XML_TYPE, 8, &xml)
+}
+
+#[test]
+fn apk_min_sdk_version() {
+    assert_eq!(
+        min_sdk_version(&test_binary_manifest(Some(16))).unwrap(),
+        16
+    );
+    assert_eq!(
+        min_sdk_version(&test_binary_manifest(Some(24))).unwrap(),
+        24
+    );
+    assert_eq!(min_sdk_version(&test_binary_manifest(None)).unwrap(), 1);
+    assert!(min_sdk_version(b"<manifest/>").is_err());
+}
+
+#[test]
+fn apk_signing_block_ids() {
+    use std::io::Cursor;
+
+    let mut apk: Vec<u8> = b"local file entries".to_vec();
+
+    // signing block with a v2 and a v3 pair
+    let mut pairs = Vec::new();
+    for id in [APK_SIGNATURE_SCHEME_V2_ID, APK_SIGNATURE_SCHEME_V3_ID] {
+        pairs.extend_from_slice(&8u64.to_le_bytes());
+        pairs.extend_from_slice(&id.to_le_bytes());
+        pairs.extend_from_slice(&[0u8; 4]);
+    }
+    let block_size = (pairs.len() + 24) as u64;
+    apk.extend_from_slice(&block_size.to_le_bytes());
+    apk.extend_from_slice(&pairs);
+    apk.extend_from_slice(&block_size.to_le_bytes());
+    apk.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+
+    // empty central directory then the eocd record
+    let cd_offset = apk.len() as u32;
+    apk.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+    apk.extend_from_slice(&[0u8; 12]);
+    apk.extend_from_slice(&cd_offset.to_le_bytes());
+    apk.extend_from_slice(&[0u8; 2]);
+
+    let ids = signing_block_ids(&mut Cursor::new(apk)).unwrap();
+    assert_eq!(
+        ids,
+        vec![APK_SIGNATURE_SCHEME_V2_ID, APK_SIGNATURE_SCHEME_V3_ID]
+    );
+}
+
+#[test]
+fn apk_signing_block_invalid_size() {
+    use std::io::Cursor;
+
+    for block_size in [0u64, 23, u64::MAX - 4, 1 << 40] {
+        let mut apk: Vec<u8> = b"local file entries".to_vec();
+        apk.extend_from_slice(&block_size.to_le_bytes());
+        apk.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+        let cd_offset = apk.len() as u32;
+        apk.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+        apk.extend_from_slice(&[0u8; 12]);
+        apk.extend_from_slice(&cd_offset.to_le_bytes());
+        apk.extend_from_slice(&[0u8; 2]);
+        assert!(signing_block_ids(&mut Cursor::new(apk)).is_err());
+    }
+}
+
+#[test]
+fn apk_signing_block_invalid_pair_length() {
+    use std::io::Cursor;
+
+    let mut pairs = Vec::new();
+    pairs.extend_from_slice(&u64::MAX.to_le_bytes());
+    pairs.extend_from_slice(&APK_SIGNATURE_SCHEME_V2_ID.to_le_bytes());
+    pairs.extend_from_slice(&[0u8; 4]);
+    let block_size = (pairs.len() + 24) as u64;
+    let mut apk: Vec<u8> = b"local file entries".to_vec();
+    apk.extend_from_slice(&block_size.to_le_bytes());
+    apk.extend_from_slice(&pairs);
+    apk.extend_from_slice(&block_size.to_le_bytes());
+    apk.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+    let cd_offset = apk.len() as u32;
+    apk.extend_from_slice(&EOCD_SIGNATURE.to_le_bytes());
+    apk.extend_from_slice(&[0u8; 12]);
+    apk.extend_from_slice(&cd_offset.to_le_bytes());
+    apk.extend_from_slice(&[0u8; 2]);
+    assert!(signing_block_ids(&mut Cursor::new(apk)).is_err());
+}
+
+#[test]
+fn apk_treemap_insert() {
+    let mut root = TreemapNode::new("app.apk");
+    root.insert("res/layout/main.xml", 100, 50);
+    root.insert("res/layout/other.xml", 20, 10);
+    root.insert("classes.dex", 1000, 400);
+
+    assert_eq!(root.size, 1120);
+    assert_eq!(root.compressed_size, 460);
+    assert_eq!(root.children.len(), 2);
+    let res = &root.children[0];
+    assert_eq!(res.name, "res");
+    assert_eq!(res.size, 120);
+    assert_eq!(res.children[0].children.len(), 2);
+    assert_eq!(root.children[1].size, 1000);
+}
@@ -0,0 +1,1236 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use log::{info, warn};
+use openssl::{
+    asn1::Asn1Time,
+    bn::{BigNum, MsbOption},
+    hash::{Hasher, MessageDigest},
+    nid::Nid,
+    pkcs12::Pkcs12,
+    pkey::{Id, PKey, Private},
+    rsa::Rsa,
+    sign::Signer,
+    x509::{X509NameBuilder, X509},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+use crate::{
+    config::{env::EnvValue, profile::ProfileTable},
+    envs, get_home,
+};
+
+use super::{
+    apk::{find_eocd, min_sdk_version, APK_SIGNATURE_SCHEME_V2_ID, APK_SIG_BLOCK_MAGIC},
+    bytecode::read_entry,
+    resolvers::file::user_home,
+};
+
+/// The keystore that apks are signed with if the profile has no signing table. The
+/// one of the android sdk in ~/.android is used if it exists, else the one in labt home
+pub const DEBUG_KEYSTORE: &str = "debug.keystore";
+/// The password of the debug keystore, the same as that of the android sdk debug keystore
+pub const DEBUG_KEYSTORE_PASSWORD: &str = "android";
+/// The alias of the key in the debug keystore
+pub const DEBUG_KEY_ALIAS: &str = "androiddebugkey";
+/// Written to the jar manifest and signature file
+const CREATED_BY: &str = "labt";
+/// The v2 signature digests the apk in chunks of this size
+const V2_CHUNK_SIZE: usize = 1024 * 1024;
+/// RSASSA-PKCS1-v1_5 with SHA2-256 in the v2 signature scheme
+const SIGNATURE_RSA_PKCS1_V1_5_WITH_SHA256: u32 = 0x0103;
+/// ECDSA with SHA2-256 in the v2 signature scheme
+const SIGNATURE_ECDSA_WITH_SHA256: u32 = 0x0201;
+/// Zip64 end of central directory locator signature
+const ZIP64_EOCD_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+/// Jar manifest lines longer than this many bytes continue on the next line
+const MANIFEST_LINE_LENGTH: usize = 72;
+/// The first android version that verifies SHA-256 v1 signatures, android 4.3
+const V1_SHA256_MIN_SDK: u32 = 18;
+
+/// DER tags
+const DER_INTEGER: u8 = 0x02;
+const DER_OCTET_STRING: u8 = 0x04;
+const DER_NULL: u8 = 0x05;
+const DER_OID: u8 = 0x06;
+const DER_BMP_STRING: u8 = 0x1e;
+const DER_SEQUENCE: u8 = 0x30;
+const DER_SET: u8 = 0x31;
+const DER_CONTEXT_0: u8 = 0xa0;
+
+/// Encoded object identifiers. 1.2.840.113549.1.7.1 pkcs7 data
+const OID_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x01];
+/// 1.2.840.113549.1.7.2 pkcs7 signedData
+const OID_SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+/// 1.3.14.3.2.26 SHA-1
+const OID_SHA1: &[u8] = &[0x2b, 0x0e, 0x03, 0x02, 0x1a];
+/// 2.16.840.1.101.3.4.2.1 SHA-256
+const OID_SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+/// 1.2.840.113549.1.1.1 rsaEncryption
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+/// 1.2.840.10045.4.1 ecdsa-with-SHA1
+const OID_ECDSA_WITH_SHA1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x01];
+/// 1.2.840.10045.4.3.2 ecdsa-with-SHA256
+const OID_ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+/// 1.2.840.113549.1.12.10.1.1 PKCS12 keyBag
+const OID_KEY_BAG: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x01,
+];
+/// 1.2.840.113549.1.12.10.1.2 PKCS12 pkcs8ShroudedKeyBag
+const OID_SHROUDED_KEY_BAG: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x02,
+];
+/// 1.2.840.113549.1.9.20 friendlyName, the alias of a PKCS12 bag
+const OID_FRIENDLY_NAME: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x14];
+
+/// The `signing` table of a profile. The keystore must be a PKCS12 keystore, the
+/// default of keytool since java 9, and is relative to the project root.
+/// ```toml
+/// [profile.release]
+/// signing = { keystore = "release.jks", alias = "upload", store_password = { secret = "env:KEYSTORE_PASSWORD" } }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SigningConfig {
+    pub keystore: PathBuf,
+    /// The alias of the key. The first key of the keystore is used if unset
+    pub alias: Option<String>,
+    /// A value or secret. Keystores without a password leave it unset
+    pub store_password: Option<EnvValue>,
+    /// Sign with the v1 (jar) signature scheme
+    #[serde(default = "enabled")]
+    pub v1: bool,
+    /// Sign with the v2 signature scheme
+    #[serde(default = "enabled")]
+    pub v2: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl SigningConfig {
+    /// The signing table of the profile, None if it has none
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the signing table is invalid
+    pub fn from_profile(profile: Option<&ProfileTable>) -> anyhow::Result<Option<Self>> {
+        let Some(signing) = profile.and_then(|profile| profile.values.get("signing")) else {
+            return Ok(None);
+        };
+        signing
+            .clone()
+            .try_into()
+            .map(Some)
+            .context("Invalid signing table in the profile")
+    }
+    pub fn options(&self) -> SignOptions {
+        SignOptions {
+            v1: self.v1,
+            v2: self.v2,
+            min_sdk: None,
+        }
+    }
+    /// Opens the keystore, reading the password if it is a secret
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the password cannot be read or the keystore cannot be opened
+    pub fn load_key(&self, project_root: &Path) -> anyhow::Result<SigningKey> {
+        let password = match &self.store_password {
+            Some(password) => password
+                .resolve(project_root)
+                .context("Failed to read the keystore password")?,
+            None => String::new(),
+        };
+        SigningKey::from_keystore(
+            &project_root.join(&self.keystore),
+            &password,
+            self.alias.as_deref(),
+        )
+    }
+}
+
+/// The signature schemes an apk is signed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignOptions {
+    pub v1: bool,
+    pub v2: bool,
+    /// The minSdkVersion the v1 signature digest is chosen for. Read from the
+    /// AndroidManifest.xml of the apk if unset
+    pub min_sdk: Option<u32>,
+}
+
+impl Default for SignOptions {
+    fn default() -> Self {
+        SignOptions {
+            v1: true,
+            v2: true,
+            min_sdk: None,
+        }
+    }
+}
+
+/// The digest of a v1 signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum V1Digest {
+    Sha1,
+    Sha256,
+}
+
+impl V1Digest {
+    /// SHA-256 if every android version the apk installs on verifies it, else
+    /// SHA-1, like apksigner
+    fn for_min_sdk(min_sdk: u32) -> Self {
+        if min_sdk < V1_SHA256_MIN_SDK {
+            V1Digest::Sha1
+        } else {
+            V1Digest::Sha256
+        }
+    }
+    /// The prefix of the digest attributes of the jar manifest and signature file
+    fn attribute(self) -> &'static str {
+        match self {
+            V1Digest::Sha1 => "SHA1",
+            V1Digest::Sha256 => "SHA-256",
+        }
+    }
+    fn message_digest(self) -> MessageDigest {
+        match self {
+            V1Digest::Sha1 => MessageDigest::sha1(),
+            V1Digest::Sha256 => MessageDigest::sha256(),
+        }
+    }
+    fn oid(self) -> &'static [u8] {
+        match self {
+            V1Digest::Sha1 => OID_SHA1,
+            V1Digest::Sha256 => OID_SHA256,
+        }
+    }
+    fn ecdsa_oid(self) -> &'static [u8] {
+        match self {
+            V1Digest::Sha1 => OID_ECDSA_WITH_SHA1,
+            V1Digest::Sha256 => OID_ECDSA_WITH_SHA256,
+        }
+    }
+    /// The base64 digest of data
+    fn base64(self, data: &[u8]) -> anyhow::Result<String> {
+        Ok(BASE64.encode(openssl::hash::hash(self.message_digest(), data)?))
+    }
+}
+
+/// A private key and its certificate chain
+pub struct SigningKey {
+    key: PKey<Private>,
+    /// The certificate of the key followed by the rest of its chain
+    certs: Vec<X509>,
+}
+
+impl SigningKey {
+    /// Opens a PKCS12 keystore, selecting the key with alias if set. Aliases are
+    /// compared ignoring case as keytool does
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the keystore cannot be read, is not PKCS12, the password
+    /// is wrong, it has no key with the alias or the key is not an RSA or EC key
+    pub fn from_keystore(path: &Path, password: &str, alias: Option<&str>) -> anyhow::Result<Self> {
+        let der = std::fs::read(path).context(format!("Failed to read the keystore {:?}", path))?;
+        let keystore = Pkcs12::from_der(&der).context(format!(
+            "{:?} is not a PKCS12 keystore. Convert a JKS keystore with keytool -importkeystore -srckeystore {} -destkeystore release.p12 -deststoretype pkcs12",
+            path,
+            path.to_string_lossy()
+        ))?;
+        let parsed = keystore
+            .parse2(password)
+            .context(format!("Wrong password for the keystore {:?}", path))?;
+        let is_alias = |cert: &X509, alias: &str| {
+            cert.alias()
+                .is_some_and(|found| String::from_utf8_lossy(found).eq_ignore_ascii_case(alias))
+        };
+        // openssl only returns the first key, the others are looked up in the keystore
+        let key = match alias {
+            Some(alias)
+                if parsed
+                    .cert
+                    .as_ref()
+                    .is_some_and(|cert| cert.alias().is_some() && !is_alias(cert, alias)) =>
+            {
+                pkcs12_key(&der, password, alias)
+                    .context(format!("Failed to read the keystore {:?}", path))?
+            }
+            _ => parsed.pkey,
+        };
+        let mut certs: Vec<X509> = parsed
+            .cert
+            .into_iter()
+            .chain(parsed.ca.into_iter().flatten())
+            .collect();
+        let Some(key) = key else {
+            let aliases: Vec<String> = certs
+                .iter()
+                .filter_map(|cert| cert.alias())
+                .map(|alias| format!("{:?}", String::from_utf8_lossy(alias)))
+                .collect();
+            match alias {
+                Some(alias) => bail!(
+                    "The keystore {:?} has no key {:?}. Its aliases are {}",
+                    path,
+                    alias,
+                    aliases.join(", ")
+                ),
+                None => bail!("The keystore {:?} has no private key", path),
+            }
+        };
+        let cert = certs
+            .iter()
+            .position(|cert| cert.public_key().is_ok_and(|public| public.public_eq(&key)))
+            .map(|index| certs.remove(index))
+            .context(format!(
+                "The keystore {:?} has no certificate for the key",
+                path
+            ))?;
+        // the certificates of the other keys are not part of the chain
+        certs.retain(|cert| cert.alias().is_none());
+        certs.insert(0, cert);
+        let key = SigningKey { key, certs };
+        key.v2_algorithm()
+            .context(format!("Unsupported key in the keystore {:?}", path))?;
+        Ok(key)
+    }
+    /// The key of the debug keystore, see [`debug_keystore`]
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the debug keystore cannot be generated or opened
+    pub fn debug() -> anyhow::Result<Self> {
+        open_debug_keystore().map(|(_, key)| key)
+    }
+    /// A new RSA key with a self signed certificate valid for 30 years, like the
+    /// debug key of the android sdk
+    fn generate() -> anyhow::Result<Self> {
+        let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_nid(Nid::COUNTRYNAME, "US")?;
+        name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "Android")?;
+        name.append_entry_by_nid(Nid::COMMONNAME, "Android Debug")?;
+        let name = name.build();
+        let mut serial = BigNum::new()?;
+        serial.rand(64, MsbOption::MAYBE_ZERO, false)?;
+        let serial = serial.to_asn1_integer()?;
+        let not_before = Asn1Time::days_from_now(0)?;
+        let not_after = Asn1Time::days_from_now(30 * 365)?;
+
+        let mut cert = X509::builder()?;
+        cert.set_version(2)?;
+        cert.set_serial_number(&serial)?;
+        cert.set_subject_name(&name)?;
+        cert.set_issuer_name(&name)?;
+        cert.set_pubkey(&key)?;
+        cert.set_not_before(&not_before)?;
+        cert.set_not_after(&not_after)?;
+        cert.sign(&key, MessageDigest::sha256())?;
+        Ok(SigningKey {
+            key,
+            certs: vec![cert.build()],
+        })
+    }
+    /// Saves the key and its certificate to a PKCS12 keystore
+    fn write_keystore(&self, path: &Path, password: &str, alias: &str) -> anyhow::Result<()> {
+        let mut builder = Pkcs12::builder();
+        builder.name(alias).pkey(&self.key).cert(&self.certs[0]);
+        let der = builder.build2(password)?.to_der()?;
+        std::fs::write(path, der).context(format!("Failed to write the keystore {:?}", path))
+    }
+    /// The id of the v2 signature algorithm for the key
+    fn v2_algorithm(&self) -> anyhow::Result<u32> {
+        match self.key.id() {
+            Id::RSA => Ok(SIGNATURE_RSA_PKCS1_V1_5_WITH_SHA256),
+            Id::EC => Ok(SIGNATURE_ECDSA_WITH_SHA256),
+            id => bail!("{:?} keys cannot sign apks, use an RSA or EC key", id),
+        }
+    }
+    /// The name of the v1 signature block file for the key
+    fn v1_block_name(&self) -> &'static str {
+        if self.key.id() == Id::EC {
+            "META-INF/CERT.EC"
+        } else {
+            "META-INF/CERT.RSA"
+        }
+    }
+}
+
+/// The debug keystore of the android sdk, in $ANDROID_USER_HOME or ~/.android.
+/// Apks signed with it can be installed over those built by gradle
+fn android_debug_keystore() -> Option<PathBuf> {
+    let android_home = match std::env::var_os(envs::ANDROID_USER_HOME) {
+        Some(home) => PathBuf::from(home),
+        None => user_home().ok()?.join(".android"),
+    };
+    Some(android_home.join(DEBUG_KEYSTORE)).filter(|path| path.exists())
+}
+
+/// Opens the debug keystore of the android sdk if it exists, else the one in labt home
+fn open_debug_keystore() -> anyhow::Result<(PathBuf, SigningKey)> {
+    if let Some(path) = android_debug_keystore() {
+        match SigningKey::from_keystore(&path, DEBUG_KEYSTORE_PASSWORD, Some(DEBUG_KEY_ALIAS)) {
+            Ok(key) => return Ok((path, key)),
+            Err(err) => {
+                warn!(target: "apksign", "Unable to use the android sdk debug keystore, using the one in labt home instead: {:?}", err)
+            }
+        }
+    }
+    let path = labt_debug_keystore()?;
+    let key = SigningKey::from_keystore(&path, DEBUG_KEYSTORE_PASSWORD, Some(DEBUG_KEY_ALIAS))?;
+    Ok((path, key))
+}
+
+/// Returns the path of the debug keystore. This is the debug keystore of the
+/// android sdk if it exists and can be opened, else the one in labt home, which
+/// is generated if labt home does not have one
+///
+/// # Errors
+///
+/// Returns an error if labt home is not found or the keystore cannot be written
+/// or opened
+pub fn debug_keystore() -> anyhow::Result<PathBuf> {
+    open_debug_keystore().map(|(path, _)| path)
+}
+
+/// Returns the path of the debug keystore in labt home, generating it if labt
+/// home does not have one
+fn labt_debug_keystore() -> anyhow::Result<PathBuf> {
+    let path = get_home()?.join(DEBUG_KEYSTORE);
+    if path.exists() {
+        return Ok(path);
+    }
+    // written next to the keystore and renamed so that a concurrent build never
+    // opens half a keystore
+    let partial = path.with_extension(format!("tmp-{}", std::process::id()));
+    SigningKey::generate()
+        .and_then(|key| key.write_keystore(&partial, DEBUG_KEYSTORE_PASSWORD, DEBUG_KEY_ALIAS))
+        .context("Failed to generate the debug keystore")?;
+    if path.exists() {
+        // another build generated it first, keep theirs as apks may be signed with it
+        let _ = std::fs::remove_file(&partial);
+    } else {
+        std::fs::rename(&partial, &path)
+            .context(format!("Failed to move the debug keystore to {:?}", path))?;
+        info!(target: "apksign", "Generated the debug keystore {:?}", path);
+    }
+    Ok(path)
+}
+
+/// Signs the apk at input and writes the signed apk to output, which may be the
+/// same path. Existing v1 signatures are replaced and stored entries are aligned
+/// like zipalign does, as the apk cannot be aligned once it has a v2 signature.
+///
+/// # Errors
+///
+/// Returns an error if the apk cannot be read or written, or signing fails
+pub fn sign_apk(
+    input: &Path,
+    output: &Path,
+    key: &SigningKey,
+    options: SignOptions,
+) -> anyhow::Result<()> {
+    if !options.v1 && !options.v2 {
+        bail!("Enable at least one of the v1 and v2 signature schemes");
+    }
+    let file = File::open(input).context(format!("Failed to open the apk {:?}", input))?;
+    let mut archive =
+        ZipArchive::new(file).context(format!("Failed to read {:?} as a zip archive", input))?;
+    let mut apk = write_signed_zip(&mut archive, key, options)
+        .context(format!("Failed to sign {:?}", input))?;
+    drop(archive);
+    if options.v2 {
+        apk = add_v2_signature(apk, key)
+            .context(format!("Failed to add the v2 signature to {:?}", input))?;
+    }
+    std::fs::write(output, apk).context(format!("Failed to write the signed apk {:?}", output))
+}
+
+/// True for the files of a v1 signature, which are replaced when signing
+fn is_signature_file(name: &str) -> bool {
+    let Some(file) = name.strip_prefix("META-INF/") else {
+        return false;
+    };
+    let upper = file.to_uppercase();
+    !file.contains('/')
+        && (upper == "MANIFEST.MF"
+            || upper.starts_with("SIG-")
+            || [".SF", ".RSA", ".DSA", ".EC"]
+                .iter()
+                .any(|extension| upper.ends_with(extension)))
+}
+
+/// The alignment of uncompressed entries. Native libraries are page aligned so
+/// that they can be loaded straight from the apk
+fn alignment(name: &str) -> u16 {
+    if name.ends_with(".so") {
+        16384
+    } else {
+        4
+    }
+}
+
+/// Copies the entries of the archive without the old signature files, adding
+/// the v1 signature if enabled
+fn write_signed_zip<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    key: &SigningKey,
+    options: SignOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    if options.v1 {
+        let min_sdk = match options.min_sdk {
+            Some(min_sdk) => min_sdk,
+            None => apk_min_sdk(archive)?,
+        };
+        let digest = V1Digest::for_min_sdk(min_sdk);
+        let mut digests = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            if entry.is_dir() || is_signature_file(entry.name()) {
+                continue;
+            }
+            let mut hasher = Hasher::new(digest.message_digest())?;
+            std::io::copy(&mut entry, &mut hasher)
+                .context(format!("Failed to read {}", entry.name()))?;
+            digests.push((entry.name().to_string(), BASE64.encode(hasher.finish()?)));
+        }
+        // a fixed time so that signing the same apk gives the same bytes
+        let meta_options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(DateTime::default());
+        for (name, contents) in jar_signature(&digests, key, options.v2, digest)? {
+            writer.start_file(name, meta_options)?;
+            writer.write_all(&contents)?;
+        }
+    }
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index_raw(i)?;
+        if is_signature_file(entry.name()) {
+            continue;
+        }
+        if entry.is_dir() || entry.compression() != CompressionMethod::Stored {
+            writer.raw_copy_file(entry)?;
+            continue;
+        }
+        let mut entry_options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .with_alignment(alignment(entry.name()));
+        if let Some(time) = entry.last_modified() {
+            entry_options = entry_options.last_modified_time(time);
+        }
+        if let Some(mode) = entry.unix_mode() {
+            entry_options = entry_options.unix_permissions(mode);
+        }
+        writer.start_file(entry.name().to_string(), entry_options)?;
+        std::io::copy(&mut entry, &mut writer)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+/// The minSdkVersion in the AndroidManifest.xml of the apk
+fn apk_min_sdk<R: Read + Seek>(archive: &mut ZipArchive<R>) -> anyhow::Result<u32> {
+    let entry = archive
+        .by_name("AndroidManifest.xml")
+        .context("The apk has no AndroidManifest.xml to read minSdkVersion from")?;
+    let manifest = read_entry(entry)?;
+    min_sdk_version(&manifest).context("Failed to read minSdkVersion from AndroidManifest.xml")
+}
+
+/// Appends a manifest attribute, continuing lines over [`MANIFEST_LINE_LENGTH`]
+/// bytes on lines starting with a space
+fn manifest_attribute(section: &mut Vec<u8>, name: &str, value: &str) {
+    let line = format!("{name}: {value}");
+    let mut rest = line.as_str();
+    let mut limit = MANIFEST_LINE_LENGTH;
+    while rest.len() > limit {
+        let mut end = limit;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        section.extend_from_slice(&rest.as_bytes()[..end]);
+        section.extend_from_slice(b"\r\n ");
+        rest = &rest[end..];
+        limit = MANIFEST_LINE_LENGTH - 1;
+    }
+    section.extend_from_slice(rest.as_bytes());
+    section.extend_from_slice(b"\r\n");
+}
+
+fn manifest_section(attributes: &[(&str, &str)]) -> Vec<u8> {
+    let mut section = Vec::new();
+    for (name, value) in attributes {
+        manifest_attribute(&mut section, name, value);
+    }
+    section.extend_from_slice(b"\r\n");
+    section
+}
+
+/// The MANIFEST.MF, CERT.SF and signature block of a v1 signature of the entries
+/// with the given base64 digests
+fn jar_signature(
+    digests: &[(String, String)],
+    key: &SigningKey,
+    v2: bool,
+    digest: V1Digest,
+) -> anyhow::Result<[(&'static str, Vec<u8>); 3]> {
+    let digest_attribute = format!("{}-Digest", digest.attribute());
+    let mut manifest = manifest_section(&[("Manifest-Version", "1.0"), ("Created-By", CREATED_BY)]);
+    let mut entry_sections = Vec::new();
+    for (name, entry_digest) in digests {
+        let section = manifest_section(&[("Name", name), (&digest_attribute, entry_digest)]);
+        entry_sections.extend(manifest_section(&[
+            ("Name", name),
+            (&digest_attribute, &digest.base64(&section)?),
+        ]));
+        manifest.extend(section);
+    }
+
+    let manifest_attribute = format!("{}-Digest-Manifest", digest.attribute());
+    let manifest_digest = digest.base64(&manifest)?;
+    let mut attributes = vec![
+        ("Signature-Version", "1.0"),
+        ("Created-By", CREATED_BY),
+        (&manifest_attribute, manifest_digest.as_str()),
+    ];
+    // tells verifiers that understand v2 to reject the apk if its v2 signature was stripped
+    if v2 {
+        attributes.push(("X-Android-APK-Signed", "2"));
+    }
+    let mut signature_file = manifest_section(&attributes);
+    signature_file.extend(entry_sections);
+
+    let block = signature_block(&signature_file, key, digest)?;
+
+    Ok([
+        ("META-INF/MANIFEST.MF", manifest),
+        ("META-INF/CERT.SF", signature_file),
+        (key.v1_block_name(), block),
+    ])
+}
+
+/// A DER element with the tag and contents
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    if contents.len() < 0x80 {
+        element.push(contents.len() as u8);
+    } else {
+        let length = contents.len().to_be_bytes();
+        let skip = length.iter().take_while(|byte| **byte == 0).count();
+        element.push(0x80 | (length.len() - skip) as u8);
+        element.extend_from_slice(&length[skip..]);
+    }
+    element.extend_from_slice(contents);
+    element
+}
+
+/// A DER INTEGER of the unsigned big endian number
+fn der_unsigned(number: &[u8]) -> Vec<u8> {
+    let skip = number.iter().take_while(|byte| **byte == 0).count();
+    let mut contents = number[skip..].to_vec();
+    if contents.first().is_none_or(|byte| byte & 0x80 != 0) {
+        contents.insert(0, 0);
+    }
+    der(DER_INTEGER, &contents)
+}
+
+/// Splits the next DER element off the front of data, returning its tag and contents
+fn der_next<'a>(data: &mut &'a [u8]) -> anyhow::Result<(u8, &'a [u8], &'a [u8])> {
+    let element = *data;
+    let (&tag, rest) = element.split_first().context("Truncated DER")?;
+    let (&first, rest) = rest.split_first().context("Truncated DER")?;
+    let (length, header) = if first < 0x80 {
+        (first as usize, 2)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            bail!("Unsupported DER length");
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |length, byte| length << 8 | *byte as usize);
+        (length, 2 + count)
+    };
+    let end = header
+        .checked_add(length)
+        .filter(|end| *end <= element.len())
+        .context("Truncated DER")?;
+    *data = &element[end..];
+    Ok((tag, &element[header..end], &element[..end]))
+}
+
+/// Splits the next DER element off the front of data, returning its contents.
+/// The element must have the tag
+fn der_expect<'a>(data: &mut &'a [u8], tag: u8) -> anyhow::Result<&'a [u8]> {
+    let (found, contents, _) = der_next(data)?;
+    if found != tag {
+        bail!("Expected DER tag {:#x}, found {:#x}", tag, found);
+    }
+    Ok(contents)
+}
+
+/// The PKCS#7 SignedData with the detached signature of data by the key, the v1
+/// signature block. Built here as openssl cannot sign with a chosen digest
+fn signature_block(data: &[u8], key: &SigningKey, digest: V1Digest) -> anyhow::Result<Vec<u8>> {
+    let mut signer = Signer::new(digest.message_digest(), &key.key)?;
+    signer.update(data)?;
+    let signature = signer.sign_to_vec()?;
+
+    let digest_algorithm = der(
+        DER_SEQUENCE,
+        &[der(DER_OID, digest.oid()), der(DER_NULL, &[])].concat(),
+    );
+    let signature_algorithm = if key.key.id() == Id::EC {
+        der(DER_SEQUENCE, &der(DER_OID, digest.ecdsa_oid()))
+    } else {
+        der(
+            DER_SEQUENCE,
+            &[der(DER_OID, OID_RSA_ENCRYPTION), der(DER_NULL, &[])].concat(),
+        )
+    };
+    let cert = &key.certs[0];
+    let issuer_and_serial = der(
+        DER_SEQUENCE,
+        &[
+            cert.issuer_name().to_der()?,
+            der_unsigned(&cert.serial_number().to_bn()?.to_vec()),
+        ]
+        .concat(),
+    );
+    let signer_info = der(
+        DER_SEQUENCE,
+        &[
+            der(DER_INTEGER, &[1]),
+            issuer_and_serial,
+            digest_algorithm.clone(),
+            signature_algorithm,
+            der(DER_OCTET_STRING, &signature),
+        ]
+        .concat(),
+    );
+    let mut certs = Vec::new();
+    for cert in &key.certs {
+        certs.extend(cert.to_der()?);
+    }
+    let signed_data = der(
+        DER_SEQUENCE,
+        &[
+            der(DER_INTEGER, &[1]),
+            der(DER_SET, &digest_algorithm),
+            der(DER_SEQUENCE, &der(DER_OID, OID_DATA)),
+            der(DER_CONTEXT_0, &certs),
+            der(DER_SET, &signer_info),
+        ]
+        .concat(),
+    );
+    Ok(der(
+        DER_SEQUENCE,
+        &[
+            der(DER_OID, OID_SIGNED_DATA),
+            der(DER_CONTEXT_0, &signed_data),
+        ]
+        .concat(),
+    ))
+}
+
+/// The friendlyName in the attributes of a PKCS12 bag
+fn friendly_name(mut attributes: &[u8]) -> anyhow::Result<Option<String>> {
+    if attributes.is_empty() {
+        return Ok(None);
+    }
+    let mut attributes = der_expect(&mut attributes, DER_SET)?;
+    while !attributes.is_empty() {
+        let mut attribute = der_expect(&mut attributes, DER_SEQUENCE)?;
+        if der_expect(&mut attribute, DER_OID)? != OID_FRIENDLY_NAME {
+            continue;
+        }
+        let name = der_expect(&mut der_expect(&mut attribute, DER_SET)?, DER_BMP_STRING)?;
+        let units: Vec<u16> = name
+            .chunks_exact(2)
+            .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+            .collect();
+        return Ok(Some(String::from_utf16_lossy(&units)));
+    }
+    Ok(None)
+}
+
+/// The private key with the alias in a PKCS12 keystore. Only the unencrypted
+/// safe contents are searched, which is where keytool and openssl put the keys,
+/// each encrypted on its own
+fn pkcs12_key(
+    keystore: &[u8],
+    password: &str,
+    alias: &str,
+) -> anyhow::Result<Option<PKey<Private>>> {
+    let mut pfx = der_expect(&mut &keystore[..], DER_SEQUENCE)?;
+    der_expect(&mut pfx, DER_INTEGER)?;
+    let mut auth_safe = der_expect(&mut pfx, DER_SEQUENCE)?;
+    if der_expect(&mut auth_safe, DER_OID)? != OID_DATA {
+        bail!("Keystores with public key integrity are not supported");
+    }
+    let mut content = der_expect(
+        &mut der_expect(&mut auth_safe, DER_CONTEXT_0)?,
+        DER_OCTET_STRING,
+    )?;
+    let mut safes = der_expect(&mut content, DER_SEQUENCE)?;
+    while !safes.is_empty() {
+        let mut info = der_expect(&mut safes, DER_SEQUENCE)?;
+        if der_expect(&mut info, DER_OID)? != OID_DATA {
+            continue;
+        }
+        let mut contents =
+            der_expect(&mut der_expect(&mut info, DER_CONTEXT_0)?, DER_OCTET_STRING)?;
+        let mut bags = der_expect(&mut contents, DER_SEQUENCE)?;
+        while !bags.is_empty() {
+            let mut bag = der_expect(&mut bags, DER_SEQUENCE)?;
+            let kind = der_expect(&mut bag, DER_OID)?;
+            let (_, _, value) = der_next(&mut der_expect(&mut bag, DER_CONTEXT_0)?)?;
+            if !friendly_name(bag)?.is_some_and(|name| name.eq_ignore_ascii_case(alias)) {
+                continue;
+            }
+            if kind == OID_KEY_BAG {
+                return Ok(Some(PKey::private_key_from_pkcs8(value)?));
+            }
+            if kind == OID_SHROUDED_KEY_BAG {
+                return Ok(Some(PKey::private_key_from_pkcs8_passphrase(
+                    value,
+                    password.as_bytes(),
+                )?));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// data prefixed with its u32 little endian length
+fn length_prefixed(data: &[u8]) -> Vec<u8> {
+    let mut prefixed = (data.len() as u32).to_le_bytes().to_vec();
+    prefixed.extend_from_slice(data);
+    prefixed
+}
+
+/// The v2 digest of the zip sections: the entries, the central directory and
+/// the end of central directory record
+fn v2_digest(sections: [&[u8]; 3]) -> Vec<u8> {
+    let chunks: Vec<&[u8]> = sections
+        .iter()
+        .flat_map(|section| section.chunks(V2_CHUNK_SIZE))
+        .collect();
+    let mut digest = Sha256::new();
+    digest.update([0x5a]);
+    digest.update((chunks.len() as u32).to_le_bytes());
+    for chunk in chunks {
+        let mut chunk_digest = Sha256::new();
+        chunk_digest.update([0xa5]);
+        chunk_digest.update((chunk.len() as u32).to_le_bytes());
+        chunk_digest.update(chunk);
+        digest.update(chunk_digest.finalize());
+    }
+    digest.finalize().to_vec()
+}
+
+/// The APK signing block with a single v2 signer of the digest
+fn v2_signing_block(digest: &[u8], key: &SigningKey) -> anyhow::Result<Vec<u8>> {
+    let algorithm = key.v2_algorithm()?;
+
+    let mut digest_record = algorithm.to_le_bytes().to_vec();
+    digest_record.extend(length_prefixed(digest));
+    let mut certs = Vec::new();
+    for cert in &key.certs {
+        certs.extend(length_prefixed(&cert.to_der()?));
+    }
+    let mut signed_data = length_prefixed(&length_prefixed(&digest_record));
+    signed_data.extend(length_prefixed(&certs));
+    // no additional attributes
+    signed_data.extend(length_prefixed(&[]));
+
+    let mut signer = Signer::new(MessageDigest::sha256(), &key.key)?;
+    signer.update(&signed_data)?;
+    let mut signature = algorithm.to_le_bytes().to_vec();
+    signature.extend(length_prefixed(&signer.sign_to_vec()?));
+
+    let mut signer = length_prefixed(&signed_data);
+    signer.extend(length_prefixed(&length_prefixed(&signature)));
+    signer.extend(length_prefixed(&key.key.public_key_to_der()?));
+    let value = length_prefixed(&length_prefixed(&signer));
+
+    // the size excludes the size field before the pairs but includes the one after them
+    let pair_size = 4 + value.len() as u64;
+    let block_size = 8 + pair_size + 8 + APK_SIG_BLOCK_MAGIC.len() as u64;
+    let mut block = block_size.to_le_bytes().to_vec();
+    block.extend_from_slice(&pair_size.to_le_bytes());
+    block.extend_from_slice(&APK_SIGNATURE_SCHEME_V2_ID.to_le_bytes());
+    block.extend(value);
+    block.extend_from_slice(&block_size.to_le_bytes());
+    block.extend_from_slice(APK_SIG_BLOCK_MAGIC);
+    Ok(block)
+}
+
+/// Inserts an APK signing block with the v2 signature of the apk before its
+/// central directory
+fn add_v2_signature(mut apk: Vec<u8>, key: &SigningKey) -> anyhow::Result<Vec<u8>> {
+    let read_u32 =
+        |apk: &[u8], offset: usize| u32::from_le_bytes(apk[offset..offset + 4].try_into().unwrap());
+    let eocd = find_eocd(&apk).context("Unable to locate zip end of central directory record")?;
+    if eocd >= 20 && read_u32(&apk, eocd - 20) == ZIP64_EOCD_LOCATOR_SIGNATURE {
+        bail!("Zip64 apks cannot be signed");
+    }
+    let cd_size = read_u32(&apk, eocd + 12) as usize;
+    let cd_offset = read_u32(&apk, eocd + 16) as usize;
+    if cd_offset.checked_add(cd_size) != Some(eocd) {
+        bail!("The zip central directory does not end at the end of central directory record");
+    }
+
+    let digest = v2_digest([&apk[..cd_offset], &apk[cd_offset..eocd], &apk[eocd..]]);
+    let block = v2_signing_block(&digest, key)?;
+    let moved_cd_offset = u32::try_from(cd_offset + block.len())
+        .context("The signed apk is too large for a zip without zip64")?;
+    apk.splice(cd_offset..cd_offset, block.iter().copied());
+    let eocd = eocd + block.len();
+    apk[eocd + 16..eocd + 20].copy_from_slice(&moved_cd_offset.to_le_bytes());
+    Ok(apk)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn apksign_signs_with_v1_and_v2() {
+    use super::apk::{signing_block_ids, test_binary_manifest};
+    use openssl::{
+        pkcs7::{Pkcs7, Pkcs7Flags},
+        sign::Verifier,
+        stack::Stack,
+        x509::store::X509StoreBuilder,
+    };
+
+    /// Splits a u32 length prefixed value off the front of data
+    fn take<'a>(data: &mut &'a [u8]) -> &'a [u8] {
+        let len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+        let (value, rest) = data[4..].split_at(len);
+        *data = rest;
+        value
+    }
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("apksign");
+    std::fs::create_dir_all(&root).unwrap();
+    let input = root.join("app-unsigned.apk");
+    let long_name = format!("res/raw/{}.txt", "long_resource_name_".repeat(5));
+    let mut writer = ZipWriter::new(File::create(&input).unwrap());
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, options) in [
+        ("AndroidManifest.xml", SimpleFileOptions::default()),
+        ("META-INF/OLD.SF", SimpleFileOptions::default()),
+        ("resources.arsc", stored),
+        ("lib/arm64-v8a/libnative.so", stored),
+        (long_name.as_str(), SimpleFileOptions::default()),
+    ] {
+        writer.start_file(name, options).unwrap();
+        if name == "AndroidManifest.xml" {
+            writer.write_all(&test_binary_manifest(Some(21))).unwrap();
+        } else {
+            writer.write_all(name.repeat(100).as_bytes()).unwrap();
+        }
+    }
+    writer.finish().unwrap();
+
+    let key = SigningKey::generate().unwrap();
+    let output = root.join("app.apk");
+    sign_apk(&input, &output, &key, SignOptions::default()).unwrap();
+    let signed = std::fs::read(&output).unwrap();
+
+    let mut archive = ZipArchive::new(Cursor::new(&signed)).unwrap();
+    let names: Vec<&str> = archive.file_names().collect();
+    assert!(!names.contains(&"META-INF/OLD.SF"));
+    for name in [
+        "META-INF/MANIFEST.MF",
+        "META-INF/CERT.SF",
+        "META-INF/CERT.RSA",
+    ] {
+        assert!(names.contains(&name), "{name} is missing");
+    }
+    assert_eq!(
+        archive.by_name("resources.arsc").unwrap().data_start() % 4,
+        0
+    );
+    assert_eq!(
+        archive
+            .by_name("lib/arm64-v8a/libnative.so")
+            .unwrap()
+            .data_start()
+            % 16384,
+        0
+    );
+
+    // v1: the signature block signs CERT.SF, which digests the manifest
+    let mut read = |name: &str| {
+        let mut contents = Vec::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    };
+    let manifest = read("META-INF/MANIFEST.MF");
+    let signature_file = read("META-INF/CERT.SF");
+    let block = read("META-INF/CERT.RSA");
+    let manifest_text = String::from_utf8_lossy(&manifest);
+    assert!(manifest_text.contains("Name: resources.arsc\r\n"));
+    assert!(manifest_text
+        .split("\r\n")
+        .all(|line| line.len() <= MANIFEST_LINE_LENGTH));
+    let signature_text = String::from_utf8_lossy(&signature_file);
+    assert!(signature_text.contains(&format!(
+        "SHA-256-Digest-Manifest: {}\r\n",
+        BASE64.encode(Sha256::digest(&manifest))
+    )));
+    assert!(signature_text.contains("X-Android-APK-Signed: 2\r\n"));
+    let certs = Stack::new().unwrap();
+    let store = X509StoreBuilder::new().unwrap().build();
+    Pkcs7::from_der(&block)
+        .unwrap()
+        .verify(
+            &certs,
+            &store,
+            Some(&signature_file),
+            None,
+            Pkcs7Flags::NOVERIFY,
+        )
+        .unwrap();
+
+    // v2: the signer signs the digest of the apk without the signing block
+    assert_eq!(
+        signing_block_ids(&mut Cursor::new(&signed)).unwrap(),
+        vec![APK_SIGNATURE_SCHEME_V2_ID]
+    );
+    let eocd = find_eocd(&signed).unwrap();
+    let cd_offset = u32::from_le_bytes(signed[eocd + 16..eocd + 20].try_into().unwrap()) as usize;
+    let block_size =
+        u64::from_le_bytes(signed[cd_offset - 24..cd_offset - 16].try_into().unwrap()) as usize;
+    let block_start = cd_offset - block_size - 8;
+    let mut value = &signed[block_start + 20..cd_offset - 24];
+    let mut signers = take(&mut value);
+    let mut signer = take(&mut signers);
+    let signed_data = take(&mut signer);
+    let mut signatures = take(&mut signer);
+    let public_key = PKey::public_key_from_der(take(&mut signer)).unwrap();
+
+    let mut signature = take(&mut signatures);
+    assert_eq!(
+        u32::from_le_bytes(signature[..4].try_into().unwrap()),
+        SIGNATURE_RSA_PKCS1_V1_5_WITH_SHA256
+    );
+    signature = &signature[4..];
+    let mut verifier = Verifier::new(MessageDigest::sha256(), &public_key).unwrap();
+    verifier.update(signed_data).unwrap();
+    assert!(verifier.verify(take(&mut signature)).unwrap());
+
+    let mut signed_data = signed_data;
+    let mut digests = take(&mut signed_data);
+    let mut digest = &take(&mut digests)[4..];
+    let mut unsigned_eocd = signed[eocd..].to_vec();
+    unsigned_eocd[16..20].copy_from_slice(&(block_start as u32).to_le_bytes());
+    assert_eq!(
+        take(&mut digest),
+        v2_digest([
+            &signed[..block_start],
+            &signed[cd_offset..eocd],
+            &unsigned_eocd
+        ])
+    );
+
+    // signing again replaces the signatures instead of adding to them
+    sign_apk(
+        &output,
+        &output,
+        &key,
+        SignOptions {
+            v1: false,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let resigned = std::fs::read(&output).unwrap();
+    assert_eq!(
+        signing_block_ids(&mut Cursor::new(&resigned)).unwrap(),
+        vec![APK_SIGNATURE_SCHEME_V2_ID]
+    );
+    let archive = ZipArchive::new(Cursor::new(&resigned)).unwrap();
+    assert!(archive.file_names().all(|name| !is_signature_file(name)));
+}
+
+#[test]
+fn apksign_v1_uses_sha1_below_api_18() {
+    use openssl::{
+        pkcs7::{Pkcs7, Pkcs7Flags},
+        stack::Stack,
+        x509::store::X509StoreBuilder,
+    };
+
+    let tmp = tempfile::tempdir().unwrap();
+    let input = tmp.path().join("app-unsigned.apk");
+    let mut writer = ZipWriter::new(File::create(&input).unwrap());
+    writer
+        .start_file("AndroidManifest.xml", SimpleFileOptions::default())
+        .unwrap();
+    writer
+        .write_all(&super::apk::test_binary_manifest(Some(16)))
+        .unwrap();
+    writer.finish().unwrap();
+
+    let key = SigningKey::generate().unwrap();
+    let output = tmp.path().join("app.apk");
+    sign_apk(&input, &output, &key, SignOptions::default()).unwrap();
+    let mut archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+    let mut read = |name: &str| {
+        let mut contents = Vec::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    };
+    let manifest = read("META-INF/MANIFEST.MF");
+    let signature_file = read("META-INF/CERT.SF");
+    let block = read("META-INF/CERT.RSA");
+    assert!(String::from_utf8_lossy(&manifest).contains("SHA1-Digest: "));
+    assert!(String::from_utf8_lossy(&signature_file).contains(&format!(
+        "SHA1-Digest-Manifest: {}\r\n",
+        BASE64.encode(sha1::Sha1::digest(&manifest))
+    )));
+    let store = X509StoreBuilder::new().unwrap().build();
+    Pkcs7::from_der(&block)
+        .unwrap()
+        .verify(
+            &Stack::new().unwrap(),
+            &store,
+            Some(&signature_file),
+            None,
+            Pkcs7Flags::NOVERIFY,
+        )
+        .unwrap();
+
+    // an explicit minSdkVersion overrides the one of the apk
+    let options = SignOptions {
+        min_sdk: Some(24),
+        ..Default::default()
+    };
+    sign_apk(&input, &output, &key, options).unwrap();
+    let mut archive = ZipArchive::new(File::open(&output).unwrap()).unwrap();
+    let mut manifest = String::new();
+    archive
+        .by_name("META-INF/MANIFEST.MF")
+        .unwrap()
+        .read_to_string(&mut manifest)
+        .unwrap();
+    assert!(manifest.contains("SHA-256-Digest: "));
+}
+
+#[test]
+fn apksign_keystore_selects_key_by_alias() {
+    let tmp = tempfile::tempdir().unwrap();
+    let first = SigningKey::generate().unwrap();
+    let second = SigningKey::generate().unwrap();
+
+    // a keystore with two keys like keytool writes, each key and certificate
+    // bag with the alias as its friendly name
+    let bag = |kind: &[u8], value: Vec<u8>, alias: &str| {
+        let name: Vec<u8> = alias.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let attribute = der(
+            DER_SEQUENCE,
+            &[
+                der(DER_OID, OID_FRIENDLY_NAME),
+                der(DER_SET, &der(DER_BMP_STRING, &name)),
+            ]
+            .concat(),
+        );
+        der(
+            DER_SEQUENCE,
+            &[
+                der(DER_OID, kind),
+                der(DER_CONTEXT_0, &value),
+                der(DER_SET, &attribute),
+            ]
+            .concat(),
+        )
+    };
+    // 1.2.840.113549.1.12.10.1.3 certBag and 1.2.840.113549.1.9.22.1 x509Certificate
+    let cert_bag_oid = [
+        0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x0a, 0x01, 0x03,
+    ];
+    let x509_oid = [0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x16, 0x01];
+    let mut bags = Vec::new();
+    for (key, alias) in [(&first, "first"), (&second, "second")] {
+        let shrouded = key
+            .key
+            .private_key_to_pkcs8_passphrase(openssl::symm::Cipher::aes_256_cbc(), b"secret")
+            .unwrap();
+        bags.extend(bag(OID_SHROUDED_KEY_BAG, shrouded, alias));
+        let cert = der(
+            DER_SEQUENCE,
+            &[
+                der(DER_OID, &x509_oid),
+                der(
+                    DER_CONTEXT_0,
+                    &der(DER_OCTET_STRING, &key.certs[0].to_der().unwrap()),
+                ),
+            ]
+            .concat(),
+        );
+        bags.extend(bag(&cert_bag_oid, cert, alias));
+    }
+    let data = |contents: &[u8]| {
+        der(
+            DER_SEQUENCE,
+            &[
+                der(DER_OID, OID_DATA),
+                der(DER_CONTEXT_0, &der(DER_OCTET_STRING, contents)),
+            ]
+            .concat(),
+        )
+    };
+    let safes = der(DER_SEQUENCE, &data(&der(DER_SEQUENCE, &bags)));
+    let keystore = der(
+        DER_SEQUENCE,
+        &[der(DER_INTEGER, &[3]), data(&safes)].concat(),
+    );
+
+    assert_eq!(
+        pkcs12_key(&keystore, "secret", "SECOND")
+            .unwrap()
+            .unwrap()
+            .public_key_to_der()
+            .unwrap(),
+        second.key.public_key_to_der().unwrap()
+    );
+    assert!(pkcs12_key(&keystore, "secret", "third").unwrap().is_none());
+
+    // openssl writes keystores with a single key, the error lists its alias
+    let path = tmp.path().join("release.p12");
+    let mut builder = Pkcs12::builder();
+    builder.name("first").pkey(&first.key).cert(&first.certs[0]);
+    std::fs::write(&path, builder.build2("secret").unwrap().to_der().unwrap()).unwrap();
+    let selected = SigningKey::from_keystore(&path, "secret", Some("first")).unwrap();
+    assert!(selected.certs[0]
+        .public_key()
+        .unwrap()
+        .public_eq(&first.key));
+    let err = SigningKey::from_keystore(&path, "secret", Some("second"))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("\"first\""), "{err}");
+}
@@ -0,0 +1,141 @@
+use std::{
+    fs::{read_dir, remove_dir_all, rename, File},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::warn;
+use zip::ZipArchive;
+
+use crate::{
+    archive::{extract_zip, BarProgress},
+    caching::Cache,
+    pom::Scope,
+};
+
+use super::resolve::ProjectDep;
+
+/// Splits the entries into the compile and runtime classpaths by their scope.
+/// Provided dependencies are only needed at compile time and runtime
+/// dependencies only at runtime.
+pub fn split_classpaths<'a, T: Clone + 'a>(
+    entries: impl IntoIterator<Item = (&'a Scope, T)>,
+) -> (Vec<T>, Vec<T>) {
+    let mut compile = Vec::new();
+    let mut runtime = Vec::new();
+    for (scope, entry) in entries {
+        if scope.on_compile_classpath() {
+            compile.push(entry.clone());
+        }
+        if scope.on_runtime_classpath() {
+            runtime.push(entry);
+        }
+    }
+    (compile, runtime)
+}
+
+/// Returns the cached artifact of a resolved dependency. Returns None for pom only
+/// dependencies and warns about those that have not been downloaded yet. target
+/// is the log target of the caller.
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available
+pub fn cached_artifact(dep: &ProjectDep, target: &str) -> anyhow::Result<Option<PathBuf>> {
+    // pom only dependencies have no artifacts
+    if dep.packaging == "pom" {
+        return Ok(None);
+    }
+    let mut cache = Cache::from(dep);
+    cache.use_labt_home()?;
+    let path = cache.get_path()?;
+    if !path.exists() {
+        warn!(target: target, "{}:{}:{} has not been downloaded. Run labt resolve", dep.group_id, dep.artifact_id, dep.version);
+        return Ok(None);
+    }
+    Ok(Some(path))
+}
+
+/// Extracts the AAR into output once. The archive is first extracted next to output
+/// and then moved so that an interrupted extraction is never reused.
+pub fn extract_aar(aar: &Path, output: &Path) -> anyhow::Result<()> {
+    if output.exists() {
+        return Ok(());
+    }
+    let mut partial = output.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    if partial.exists() {
+        remove_dir_all(&partial).context(format!("Failed to remove {:?}", partial))?;
+    }
+    let file = File::open(aar).context(format!("Failed to open {:?}", aar))?;
+    let mut archive = ZipArchive::new(file).context(format!("{:?} is not a valid AAR", aar))?;
+    let name = aar.file_name().unwrap_or_default().to_string_lossy();
+    extract_zip(
+        &mut archive,
+        &partial,
+        &BarProgress::new(format!("Extracting {name}")),
+    )
+    .context(format!("Failed to extract {:?}", aar))?;
+    rename(&partial, output).context(format!("Failed to move {:?} to {:?}", partial, output))?;
+    Ok(())
+}
+
+/// Jars in the extracted AAR. classes.jar and the bundled libs/*.jar
+pub fn aar_jars(extracted: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut jars = Vec::new();
+    let classes = extracted.join("classes.jar");
+    if classes.exists() {
+        jars.push(classes);
+    }
+    let libs = extracted.join("libs");
+    if libs.is_dir() {
+        let mut bundled: Vec<PathBuf> = read_dir(&libs)
+            .context(format!("Failed to read {:?}", libs))?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "jar"))
+            .collect();
+        bundled.sort();
+        jars.extend(bundled);
+    }
+    Ok(jars)
+}
+
+/// Jars of the artifact at path. IDEs and compilers cannot read AARs so they are
+/// extracted under extract_dir first. Also returns the extracted AAR.
+///
+/// # Errors
+///
+/// Returns an error if extracting an AAR fails
+pub fn artifact_jars(
+    dep: &ProjectDep,
+    path: &Path,
+    extract_dir: &Path,
+) -> anyhow::Result<(Vec<PathBuf>, Option<PathBuf>)> {
+    if dep.packaging == "aar" {
+        let extracted = extract_dir
+            .join(&dep.group_id)
+            .join(format!("{}-{}", dep.artifact_id, dep.version));
+        extract_aar(path, &extracted)?;
+        Ok((aar_jars(&extracted)?, Some(extracted)))
+    } else {
+        Ok((vec![path.to_path_buf()], None))
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn split_classpaths_by_scope() {
+    let entries = [
+        (Scope::COMPILE, "compile"),
+        (Scope::PROVIDED, "provided"),
+        (Scope::RUNTIME, "runtime"),
+        (Scope::TEST, "test"),
+    ];
+    let (compile, runtime) = split_classpaths(entries.iter().map(|(scope, name)| (scope, *name)));
+    assert_eq!(compile, vec!["compile", "provided"]);
+    assert_eq!(runtime, vec!["compile", "runtime"]);
+}
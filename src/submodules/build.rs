@@ -1,34 +1,105 @@
 use std::{
     cell::RefCell,
+    collections::HashMap,
     fmt::Display,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
-use anyhow::Context;
+use anyhow::{bail, Context};
 use clap::{Args, ValueEnum};
+use log::{info, warn};
 use reqwest::Url;
 
 use crate::{
-    config::get_config,
-    get_home, get_project_root,
-    plugin::{load_plugins, load_plugins_from_paths},
+    config::{
+        build_config::{generate_build_config, BuildConfigValues},
+        env::process_env,
+        get_config,
+        hooks::{hook_name, validate_hooks, HookTime, HooksTable},
+        lock::{load_labt_lock, lock_file_in},
+        profile::ProfileName,
+        version_code::compute_version_code,
+        LabToml,
+    },
+    events::{self, emit, Event},
+    get_home, get_manifest_path, get_project_root,
+    interrupt::{self, Interrupted},
+    metrics,
+    paths::sanitize_component,
+    plugin::{
+        api::template::{generate_manifest, MANIFEST_TEMPLATE},
+        config::{load_package_paths, ChangeDetection},
+        load_plugins, load_plugins_from_paths,
+        process::{ProcessEnv, ProcessLog, TimedOut},
+        trace::LuaFailure,
+        Plugin,
+    },
 };
 
-use super::Submodule;
+use super::{
+    build_cache::{cache_key, code_digest, config_digest, expand_files, BuildCache},
+    build_summary::{
+        record_warnings, take_warnings, write_summary, BuildSummary, PluginSummary, RunStatus,
+        StepSummary,
+    },
+    fingerprint::{stage_key, FingerprintDb},
+    jdk::{export_java_home, provision_jdk},
+    plugin::plugin_dir,
+    reproducible::{
+        check_reference, compare_outputs, output_files, remove_outputs, render_differences,
+        save_outputs, REPRODUCIBLE_DIR,
+    },
+    sdkmanager::requirements::ensure_sdk_packages,
+    staging::stage_dependencies,
+    toolchains::verify_toolchains,
+    Submodule,
+};
 
 // temporary, will remove if a cleaner way of passing the current step
 // to plugins is achieved
 thread_local! {
     pub static BUILD_STEP: RefCell<Step> = const { RefCell::new(Step::PRE) };
+    pub static BUILD_RELEASE: RefCell<bool> = const { RefCell::new(false) };
 }
 
 #[derive(Clone, Args)]
 pub struct BuildArgs {
     pub step: Option<Step>,
+    /// Build with the release profile. Sets BuildConfig.DEBUG to false
+    #[arg(long)]
+    pub release: bool,
+    /// Build twice from clean outputs and compare the outputs byte by byte
+    #[arg(long)]
+    pub check_reproducible: bool,
+    /// Compare the outputs with this earlier build directory instead of building twice
+    #[arg(long, value_name = "DIR", requires = "check_reproducible")]
+    pub reference: Option<PathBuf>,
+    /// Write the full Lua traceback of a failed plugin to build/logs/<step>-<plugin>.trace
+    #[arg(long)]
+    pub lua_trace: bool,
+    /// Do not check the sdk for the platform and build-tools the dependencies require
+    #[arg(long)]
+    pub skip_sdk_check: bool,
+    /// Accept the licenses of the sdk packages the dependencies require instead of
+    /// asking. Also installs them when there is no terminal to ask on
+    #[arg(long)]
+    pub accept_licenses: bool,
+    /// Run every stage instead of restoring its outputs from the build cache
+    #[arg(long)]
+    pub no_build_cache: bool,
 }
 
 pub struct Build {
     pub args: BuildArgs,
+    /// The environment of the processes plugins spawn, read from `[env]` when the build starts
+    env: ProcessEnv,
+    /// None if the build cache is disabled
+    build_cache: Option<BuildCache>,
+    /// The digest of the profile and environment in the cache keys
+    cache_config: String,
+    /// The output globs of the plugins of the steps to run, for the build summary
+    output_patterns: Vec<String>,
 }
 
 #[derive(Clone, Copy, ValueEnum, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -45,6 +116,9 @@ pub enum Step {
     DEX,
     /// Bundles compiled resources into apk file and aligning, signing etc.
     BUNDLE,
+    /// Runs the instrumented tests on a connected device. Only runs when
+    /// requested with labt build instrument
+    INSTRUMENT,
     /// POST compilation step. Run, create a release file, return results to
     /// CI/CD pipeline etc.
     POST,
@@ -57,6 +131,7 @@ impl Display for Step {
             Step::COMPILE => write!(f, "compile"),
             Step::DEX => write!(f, "dex"),
             Step::BUNDLE => write!(f, "bundle"),
+            Step::INSTRUMENT => write!(f, "instrument"),
             Step::POST => write!(f, "post"),
         }
     }
@@ -64,12 +139,129 @@ impl Display for Step {
 
 impl Build {
     pub fn new(args: &BuildArgs) -> Self {
-        Self { args: args.clone() }
+        Self {
+            args: args.clone(),
+            env: ProcessEnv::default(),
+            build_cache: None,
+            cache_config: String::new(),
+            output_patterns: Vec::new(),
+        }
     }
 }
 
+/// The directories of the plugins a project uses. Plugins in Labt.toml are
+/// loaded from their local path or their install in labt home, and every
+/// directory in the project plugins folder is a plugin too.
+///
+/// # Errors
+///
+/// Returns an error if labt home, the project root or its plugins folder cannot be read
+pub fn project_plugin_paths(config: &LabToml) -> anyhow::Result<Vec<PathBuf>> {
+    let home = get_home().context("Failed to load plugin home")?;
+    // array of plugin locations to be loaded
+    let mut paths: Vec<PathBuf> = vec![];
+    if let Some(plugins) = &config.plugins {
+        paths.extend(plugins.values().filter_map(|plugin| {
+            // check if plugin has location string
+
+            if let Some(location) = &plugin.location {
+                // if location is a valid url, load from labt home plugins
+                if let Ok(url) = Url::parse(location.as_str()) {
+                    let mut h = plugin_dir(home.clone(), &url);
+                    h.push("versions");
+                    h.push(sanitize_component(&format!("v{}", &plugin.version)).as_ref());
+                    Some(h)
+                } else {
+                    // else use the defined location
+                    Some(PathBuf::from(location))
+                }
+            } else {
+                // Dont load plugin that we dont know where it is located
+                None
+            }
+        }));
+    }
+
+    // include the paths of plugins in the project folder
+    let mut root = get_project_root().context("Failed to read the project root folder")?;
+    root.push("plugins");
+    if root.exists() {
+        for path in (root.read_dir()?).flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
 impl Submodule for Build {
     fn run(&mut self) -> anyhow::Result<()> {
+        if self.args.check_reproducible {
+            return self.check_reproducible();
+        }
+        self.build()
+    }
+}
+
+impl Build {
+    /// Builds from clean outputs, either twice or once against a reference build,
+    /// and reports the outputs that differ
+    fn check_reproducible(&mut self) -> anyhow::Result<()> {
+        let build_dir = get_project_root()
+            .context("Failed to read the project root folder")?
+            .join("build");
+        let reference = match &self.args.reference {
+            Some(reference) => {
+                check_reference(&build_dir, reference)?;
+                reference.clone()
+            }
+            None => {
+                remove_outputs(&build_dir)?;
+                self.build().context("The first build failed")?;
+                let first = build_dir.join(REPRODUCIBLE_DIR);
+                save_outputs(&build_dir, &first)?;
+                // zip timestamps have a 2 second resolution, give them a chance to differ
+                std::thread::sleep(Duration::from_secs(2));
+                first
+            }
+        };
+        remove_outputs(&build_dir)?;
+        self.build().context("The build to compare failed")?;
+
+        let differences = compare_outputs(&reference, &build_dir)?;
+        if differences.is_empty() {
+            let count = output_files(&build_dir)?.len();
+            info!(target: "build", "Build is reproducible, {count} outputs are identical to {:?}", reference);
+            return Ok(());
+        }
+        print!("{}", render_differences(&differences));
+        bail!(
+            "Build is not reproducible: {} outputs differ from {:?}",
+            differences.len(),
+            reference
+        );
+    }
+    /// Runs the build and writes build/build-summary.json, also when the build fails
+    fn build(&mut self) -> anyhow::Result<()> {
+        let project_root = get_project_root().context("Failed to read the project root folder")?;
+        let started = Instant::now();
+        let mut summary = BuildSummary::new(self.args.release);
+        record_warnings();
+        let result = self.run_build(&project_root, &mut summary);
+        summary.success = result.is_ok();
+        summary.elapsed_ms = started.elapsed().as_millis() as u64;
+        metrics::record(metrics::BUILD, started.elapsed());
+        summary.warnings = take_warnings();
+        let written = expand_files(&project_root, &self.output_patterns)
+            .and_then(|outputs| write_summary(&project_root.join("build"), &outputs, &mut summary));
+        match written {
+            Ok(path) => info!(target: "build", "Wrote build summary to {:?}", path),
+            Err(err) => warn!(target: "build", "Failed to write the build summary: {:?}", err),
+        }
+        result
+    }
+    fn run_build(&mut self, project_root: &Path, summary: &mut BuildSummary) -> anyhow::Result<()> {
         // The order by which to run the plugin build step
         let order: Vec<Step> = if let Some(step) = self.args.step {
             // if the build step was added explicitly, then just run that one
@@ -77,7 +269,8 @@ impl Submodule for Build {
             vec![step]
         } else {
             // TODO add a more intelligent filter to run only the
-            // required steps instead of just running everything
+            // required steps instead of just running everything.
+            // INSTRUMENT needs a device so it only runs when asked for
             vec![
                 Step::PRE,
                 Step::AAPT,
@@ -87,114 +280,451 @@ impl Submodule for Build {
                 Step::POST,
             ]
         };
-        let mut home = get_home().context("Failed to load plugin home")?;
-        home.push("plugins");
         // try loading plugin from config
         let config = get_config().context("Failed to load plugins list from config")?;
-        // array of plugin locations to be loaded
-        let mut paths: Vec<PathBuf> = vec![];
-        if let Some(plugins) = config.plugins {
-            paths.extend(plugins.iter().filter_map(|(_name, plugin)| {
-                // check if plugin has location string
-
-                if let Some(location) = &plugin.location {
-                    // if location is a valid url, load from labt home plugins
-                    if let Ok(url) = Url::parse(location.as_str()) {
-                        let mut h = home.clone();
-                        if let Some(domain) = url.domain() {
-                            h.push(domain);
-                        } else {
-                            h.push("example.com"); // keep this
-                        }
+        let timeouts = config.timeouts.clone().unwrap_or_default();
+        let hooks = config.hooks.clone().unwrap_or_default();
+        validate_hooks(&hooks)?;
+        let paths = project_plugin_paths(&config)?;
 
-                        let url_path = url.path();
-                        let url_path = if let Some(p) = url_path.strip_suffix(".git") {
-                            p
-                        } else {
-                            url_path
-                        };
-                        h.extend(url_path.split('/'));
-                        h.push("versions");
-                        h.push(format!("v{}", &plugin.version));
-                        Some(h)
-                    } else {
-                        // else use the defined location
-                        Some(PathBuf::from(location))
-                    }
-                } else {
-                    // Dont load plugin that we dont know where it is located
-                    None
-                }
-            }));
-        }
-
-        {
-            // include the paths of plugins in the project folder
-            let mut root = get_project_root()
-                .context("Failed to read the project root folder")?
-                .clone();
-            root.push("plugins");
-            if root.exists() {
-                for path in (root.read_dir()?).flatten().map(|entry| entry.path()) {
-                    if path.is_dir() {
-                        paths.push(path);
-                    }
-                }
+        BUILD_RELEASE.with(|release| {
+            *release.borrow_mut() = self.args.release;
+        });
+        info!(target: "build", "Building with the {} profile", active_profile());
+        let profile = config
+            .profile
+            .as_ref()
+            .and_then(|profiles| profiles.get(active_profile()));
+        self.env = process_env(config.env.as_ref(), profile, project_root)?;
+        self.build_cache = if self.args.no_build_cache {
+            None
+        } else {
+            BuildCache::from_config().context("Failed to configure the build cache")?
+        };
+        if let Some(path) = write_build_config(&config, project_root)? {
+            info!(target: "build", "Generated {:?}", path);
+        }
+        if let Some(path) = write_manifest(&config, project_root)? {
+            info!(target: "build", "Generated {:?}", path);
+        }
+        if lock_file_in(project_root).exists() {
+            let lock = load_labt_lock().context("Failed to load lock file")?;
+            stage_dependencies(project_root, &lock.resolved, &lock.processors)
+                .context("Failed to stage dependencies")?;
+            if !self.args.skip_sdk_check {
+                ensure_sdk_packages(&lock.resolved, self.args.accept_licenses)
+                    .context("Failed to check the sdk packages required by the dependencies")?;
             }
         }
+        if let Some(jdk) = &config.jdk {
+            let java_home = provision_jdk(jdk)?;
+            info!(target: "build", "Using JDK at {:?}", java_home);
+            export_java_home(&mut self.env, &java_home)?;
+        }
+        let toolchains = verify_toolchains(&config)?;
+        for toolchain in &toolchains {
+            info!(target: "build", "Using {} {} at {:?}", toolchain.name, toolchain.version.as_deref().unwrap_or_default(), toolchain.path);
+        }
+        self.cache_config = config_digest(
+            active_profile(),
+            profile,
+            &self.env,
+            &toolchains,
+            &get_manifest_path()?,
+            &lock_file_in(project_root),
+        )?;
 
         let plugin_list = load_plugins_from_paths(paths).context("Failed to load plugins")?;
         let mut map = load_plugins(plugin_list).context("Error loading plugin configurations")?;
+        self.output_patterns = order
+            .iter()
+            .filter_map(|step| map.get(step))
+            .flatten()
+            .filter_map(|plugin| plugin.dependent_patterns.as_ref())
+            .flat_map(|(_, outputs)| outputs.iter().cloned())
+            .collect();
+        let mut fingerprints = FingerprintDb::load(project_root);
 
+        self.run_hook(
+            &hooks,
+            HookTime::Pre,
+            None,
+            &timeouts,
+            project_root,
+            &mut summary.hooks,
+        )?;
         for step in order {
             // update build step if already provided
             BUILD_STEP.with(|s| {
                 *s.borrow_mut() = step;
             });
+            let mut step_events = StepEvents::start(step);
+            let mut step_summary = StepSummary::new(step);
+            let result = self
+                .run_hook(
+                    &hooks,
+                    HookTime::Pre,
+                    Some(step),
+                    &timeouts,
+                    project_root,
+                    &mut step_summary.plugins,
+                )
+                .and_then(|()| {
+                    self.run_step(
+                        map.get_mut(&step),
+                        &timeouts,
+                        project_root,
+                        &mut fingerprints,
+                        &mut step_summary,
+                    )
+                })
+                .and_then(|()| {
+                    self.run_hook(
+                        &hooks,
+                        HookTime::Post,
+                        Some(step),
+                        &timeouts,
+                        project_root,
+                        &mut step_summary.plugins,
+                    )
+                });
+            step_summary.finish(result.is_ok(), step_events.started.elapsed());
+            summary.steps.push(step_summary);
+            result?;
+            step_events.success = true;
+        }
+        self.run_hook(
+            &hooks,
+            HookTime::Post,
+            None,
+            &timeouts,
+            project_root,
+            &mut summary.hooks,
+        )?;
+
+        Ok(())
+    }
+    /// Runs the [hooks] script around a step, or around the whole build if step is
+    /// None, like a plugin of that step. The run is added to summary. Hook scripts
+    /// can require the lua files next to them
+    fn run_hook(
+        &self,
+        hooks: &HooksTable,
+        time: HookTime,
+        step: Option<Step>,
+        timeouts: &HashMap<String, u64>,
+        project_root: &Path,
+        summary: &mut Vec<PluginSummary>,
+    ) -> anyhow::Result<()> {
+        let name = hook_name(time, step);
+        let Some(script) = hooks.get(&name) else {
+            return Ok(());
+        };
+        interrupt::check()?;
+        let script = project_root.join(script);
+        if !script.exists() {
+            bail!("The {name} hook {:?} does not exist", script);
+        }
+        let step = step.unwrap_or(match time {
+            HookTime::Pre => Step::PRE,
+            HookTime::Post => Step::POST,
+        });
+        BUILD_STEP.with(|s| {
+            *s.borrow_mut() = step;
+        });
+        let mut hook = Plugin::new(name.clone(), String::new(), script.clone(), step);
+        hook.package_paths = load_package_paths(&[], script.parent().unwrap_or(project_root));
 
-            if let Some(plugins) = map.get_mut(&step) {
-                // sort plugins by priority
-                plugins.sort_by(|a, b| b.priority.partial_cmp(&a.priority).unwrap());
-                '_loop: for plugin in plugins {
-                    // filter for only required plugins
-                    if let Some((inputs, outputs)) = &plugin.dependents {
-                        // iterate on plugin dependents,
-                        // if the output list is empty, then it will skip the iteration and assume first run
-                        for output in outputs {
-                            // for each output, compare to see if to run this plugin stage
-                            // only run plugins which have their dependents changed should be run
-                            let is_stale = inputs
-                                .iter()
-                                .any(|input| is_file_newer(input, output).unwrap_or(false));
-
-                            if !is_stale {
-                                // the plugin outputs are newer so skip it
-                                continue '_loop;
-                            }
+        info!(target: "build", "Running the {name} hook {:?}", script);
+        let started = Instant::now();
+        let result = self.run_plugin(&hook, timeouts, project_root);
+        let status = if result.is_ok() {
+            RunStatus::Ran
+        } else {
+            RunStatus::Failed
+        };
+        summary.push(PluginSummary::new(&hook, status, started.elapsed()));
+        result.context(format!("The {name} hook failed"))
+    }
+    /// Runs the plugins of a step by priority. Plugins whose outputs are newer
+    /// than their inputs, or whose input contents are unchanged with hash change
+    /// detection, are skipped
+    fn run_step(
+        &self,
+        plugins: Option<&mut Vec<Plugin>>,
+        timeouts: &HashMap<String, u64>,
+        project_root: &Path,
+        fingerprints: &mut FingerprintDb,
+        summary: &mut StepSummary,
+    ) -> anyhow::Result<()> {
+        let Some(plugins) = plugins else {
+            return Ok(());
+        };
+        // sort plugins by priority
+        plugins.sort_by(|a, b| b.priority.partial_cmp(&a.priority).unwrap());
+        '_loop: for plugin in plugins.iter() {
+            interrupt::check()?;
+            let key = stage_key(
+                plugin.step,
+                &plugin.name,
+                &plugin.version,
+                &code_digest(plugin)?,
+            );
+            let hashed_inputs = match &plugin.dependents {
+                Some((inputs, outputs)) if plugin.change_detection == ChangeDetection::Hash => {
+                    let current = fingerprints.fingerprint(&key, project_root, inputs)?;
+                    // outputs that do not exist were not matched by their globs
+                    if !outputs.is_empty()
+                        && outputs.iter().all(|output| output.exists())
+                        && fingerprints.is_unchanged(&key, &current)
+                    {
+                        summary.plugins.push(PluginSummary::new(
+                            plugin,
+                            RunStatus::Skipped,
+                            Duration::ZERO,
+                        ));
+                        continue '_loop;
+                    }
+                    Some(inputs)
+                }
+                _ => None,
+            };
+            // stages with hash change detection are restored from the build cache if an
+            // earlier build ran them with the same inputs
+            let cached = match (&self.build_cache, &plugin.dependent_patterns) {
+                (Some(cache), Some((input_patterns, output_patterns)))
+                    if hashed_inputs.is_some() =>
+                {
+                    // inputs created by earlier steps were not there when the plugin loaded
+                    let inputs = expand_files(project_root, input_patterns)?;
+                    let current = fingerprints.fingerprint(&key, project_root, &inputs)?;
+                    let cache_key = cache_key(plugin, &self.cache_config, &current)?;
+                    if cache.restore(&cache_key, project_root)? {
+                        info!(target: "build", "Restored the outputs of {}:{} from the build cache", plugin.name, plugin.step);
+                        summary.plugins.push(PluginSummary::new(
+                            plugin,
+                            RunStatus::Cached,
+                            Duration::ZERO,
+                        ));
+                        if let Some(inputs) = hashed_inputs {
+                            let current = fingerprints.fingerprint(&key, project_root, inputs)?;
+                            fingerprints.record(key, current);
+                            fingerprints.save(project_root)?;
                         }
+                        continue '_loop;
                     }
-                    // loop through each plugin executing each
-                    let exe = plugin.load().context(format!(
-                        "Error loading plugin: {}:{} at build step {:?}",
-                        plugin.name, plugin.version, plugin.step
-                    ))?;
-
-                    let chunk = exe.load().context(format!(
-                        "Error loading lua code for {}:{} at build step {:?}",
-                        plugin.name, plugin.version, plugin.step
-                    ))?;
-
-                    chunk.exec().context(format!(
-                        "Failed to execute plugin code {:?} for plugin {}:{} at build step {:?}",
-                        plugin.path, plugin.name, plugin.version, plugin.step
-                    ))?;
+                    Some((cache, cache_key, output_patterns))
+                }
+                _ => None,
+            };
+            // filter for only required plugins
+            if let Some((inputs, outputs)) = plugin
+                .dependents
+                .as_ref()
+                .filter(|_| plugin.change_detection == ChangeDetection::Mtime)
+            {
+                // iterate on plugin dependents,
+                // if the output list is empty, then it will skip the iteration and assume first run
+                for output in outputs {
+                    // for each output, compare to see if to run this plugin stage
+                    // only run plugins which have their dependents changed should be run
+                    let is_stale = inputs
+                        .iter()
+                        .any(|input| is_file_newer(input, output).unwrap_or(false));
+
+                    if !is_stale {
+                        // the plugin outputs are newer so skip it
+                        summary.plugins.push(PluginSummary::new(
+                            plugin,
+                            RunStatus::Skipped,
+                            Duration::ZERO,
+                        ));
+                        continue '_loop;
+                    }
+                }
+            }
+            let started = Instant::now();
+            let result = self.run_plugin(plugin, timeouts, project_root);
+            let status = if result.is_ok() {
+                RunStatus::Ran
+            } else {
+                RunStatus::Failed
+            };
+            summary
+                .plugins
+                .push(PluginSummary::new(plugin, status, started.elapsed()));
+            result?;
+            if let Some(inputs) = hashed_inputs {
+                // the plugin may have rewritten its inputs e.g. a formatter
+                let current = fingerprints.fingerprint(&key, project_root, inputs)?;
+                fingerprints.record(key, current);
+                fingerprints.save(project_root)?;
+            }
+            if let Some((cache, cache_key, output_patterns)) = cached {
+                let outputs = expand_files(project_root, output_patterns)?;
+                if let Err(err) = cache.store(&cache_key, project_root, &outputs) {
+                    warn!(target: "build", "Failed to cache the outputs of {}:{}: {:?}", plugin.name, plugin.step, err);
                 }
             }
         }
+        Ok(())
+    }
+    /// Loads and runs the lua code of a plugin with its timeout and process log
+    fn run_plugin(
+        &self,
+        plugin: &Plugin,
+        timeouts: &HashMap<String, u64>,
+        project_root: &Path,
+    ) -> anyhow::Result<()> {
+        let step = plugin.step;
+        let exe = plugin.load().context(format!(
+            "Error loading plugin: {}:{} at build step {:?}",
+            plugin.name, plugin.version, plugin.step
+        ))?;
+
+        let chunk = exe.load().context(format!(
+            "Error loading lua code for {}:{} at build step {:?}",
+            plugin.name, plugin.version, plugin.step
+        ))?;
+
+        // project configured timeouts take precedence over the plugin defaults
+        let timeout = timeouts
+            .get(&step.to_string())
+            .or(timeouts.get("default"))
+            .map(|secs| Duration::from_secs(*secs))
+            .or(plugin.timeout);
+        exe.set_timeout(timeout);
 
+        // keep subprocess output from corrupting the progress bars
+        let log_path = project_root
+            .join("build")
+            .join("logs")
+            .join(format!("{}-{}.log", step, plugin.name));
+        let log = ProcessLog::new(format!("{}:{}", plugin.name, step), Some(log_path))
+            .context("Failed to create plugin log file")?
+            .with_env(&self.env);
+        exe.set_process_log(log);
+        exe.set_process_env(self.env.clone());
+
+        let result = chunk.exec();
+        // events from other threads for the hooks of the plugin
+        events::deliver_pending();
+        if exe.timed_out() {
+            if let Err(err) = exe.run_cleanup_hooks() {
+                warn!(target: "build", "Cleanup for plugin {}:{} failed: {:?}", plugin.name, plugin.version, err);
+            }
+            return Err(TimedOut(timeout.unwrap_or_default())).context(format!(
+                "Plugin {}:{} timed out at build step {:?}",
+                plugin.name, plugin.version, plugin.step
+            ));
+        }
+        if interrupt::is_interrupted() {
+            // give the plugin a chance to undo partial work
+            if let Err(err) = exe.run_cleanup_hooks() {
+                warn!(target: "build", "Cleanup for plugin {}:{} failed: {:?}", plugin.name, plugin.version, err);
+            }
+            return Err(Interrupted).context(format!(
+                "Build interrupted while running plugin {}:{} at build step {:?}",
+                plugin.name, plugin.version, plugin.step
+            ));
+        }
+        if let Err(err) = result {
+            let failure = LuaFailure::new(
+                &format!("{}:{}", plugin.name, plugin.version),
+                &plugin.path,
+                &err,
+            )
+            .masked(&self.env);
+            if self.args.lua_trace {
+                let trace = project_root
+                    .join("build")
+                    .join("logs")
+                    .join(format!("{}-{}.trace", step, plugin.name));
+                match failure.dump(&trace) {
+                    Ok(()) => info!(target: "build", "Wrote Lua trace to {:?}", trace),
+                    Err(err) => warn!(target: "build", "{:?}", err),
+                }
+            }
+            return Err(failure).context(format!(
+                "Failed to execute plugin code {:?} for plugin {}:{} at build step {:?}",
+                plugin.path, plugin.name, plugin.version, plugin.step
+            ));
+        }
         Ok(())
     }
 }
+
+/// Emits [`Event::StepStarted`] and [`Event::StepFinished`] once dropped, so that
+/// the step is reported as finished on every early return
+struct StepEvents {
+    step: Step,
+    started: Instant,
+    success: bool,
+}
+
+impl StepEvents {
+    fn start(step: Step) -> Self {
+        emit(Event::StepStarted {
+            step: step.to_string(),
+        });
+        StepEvents {
+            step,
+            started: Instant::now(),
+            success: false,
+        }
+    }
+}
+
+impl Drop for StepEvents {
+    fn drop(&mut self) {
+        emit(Event::StepFinished {
+            step: self.step.to_string(),
+            success: self.success,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+        });
+    }
+}
+
+/// The profile of the running build, release if it was started with --release
+pub fn active_profile() -> ProfileName {
+    ProfileName::from_release(BUILD_RELEASE.with(|release| *release.borrow()))
+}
+
+/// Generates the BuildConfig class if the project has a `[build_config]` table.
+/// Returns the path of the generated class.
+///
+/// # Errors
+///
+/// Returns an error if generating the class fails
+pub fn write_build_config(config: &LabToml, root: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let table = match &config.build_config {
+        Some(table) => table,
+        None => return Ok(None),
+    };
+    let release = BUILD_RELEASE.with(|release| *release.borrow());
+    let version_code = compute_version_code(config, root)?;
+    let values = BuildConfigValues::from_config(config, table, version_code, !release);
+    let path = generate_build_config(root, table, &values)
+        .context("Failed to generate BuildConfig class")?;
+    Ok(Some(path))
+}
+
+/// Generates AndroidManifest.xml if the project has a manifest template, see
+/// [`generate_manifest`]. Returns the path of the generated manifest.
+///
+/// # Errors
+///
+/// Returns an error if the version code cannot be computed or generating fails
+pub fn write_manifest(config: &LabToml, root: &Path) -> anyhow::Result<Option<PathBuf>> {
+    if !root.join(MANIFEST_TEMPLATE).exists() {
+        return Ok(None);
+    }
+    let release = BUILD_RELEASE.with(|release| *release.borrow());
+    let version_code = compute_version_code(config, root)?;
+    generate_manifest(config, root, version_code, !release)
+        .context("Failed to generate AndroidManifest.xml")
+}
+
 /// Returns true if file a is newer than file b
 /// If file b does not exist, returns true
 /// if file a does not exist returns false
@@ -210,24 +740,11 @@ pub fn is_file_newer(a: &Path, b: &Path) -> std::io::Result<bool> {
         return Ok(false);
     }
     // try to obtain the metadata for comparison
-    let metadata_a = match a.metadata() {
-        Ok(metadata) => metadata,
-        Err(err) => return Err(err),
-    };
-
-    let metadata_b = match b.metadata() {
-        Ok(metadata) => metadata,
-        Err(err) => return Err(err),
-    };
+    let metadata_a = a.metadata()?;
+    let metadata_b = b.metadata()?;
 
-    let modification_a = match metadata_a.modified() {
-        Ok(modified) => modified,
-        Err(err) => return Err(err),
-    };
-    let modification_b = match metadata_b.modified() {
-        Ok(modified) => modified,
-        Err(err) => return Err(err),
-    };
+    let modification_a = metadata_a.modified()?;
+    let modification_b = metadata_b.modified()?;
 
     Ok(modification_a > modification_b)
 }
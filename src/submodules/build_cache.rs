@@ -0,0 +1,451 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    caching::file_sha256,
+    config::{
+        global::get_global_config,
+        profile::{ProfileName, ProfileTable},
+    },
+    envs::{JAVA_HOME, LABT_BUILD_CACHE},
+    get_home,
+    plugin::{process::ProcessEnv, Plugin},
+};
+
+use super::{fingerprint::FileFingerprint, toolchains::Toolchain};
+
+/// The directory in labt home where stage outputs are cached if config.toml does not set one
+pub const BUILD_CACHE_DIR: &str = "build-cache";
+/// The list of outputs in a cache entry
+const ENTRY_MANIFEST: &str = "outputs.json";
+/// The directory of a cache entry holding the output files
+const ENTRY_FILES: &str = "files";
+
+/// An output of a stage in a cache entry
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CachedOutput {
+    /// Relative to the project root
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// The outputs of plugin stages from earlier builds, keyed by everything that
+/// affects what a stage writes. A stage whose key is in the cache has its
+/// outputs copied back instead of running
+pub struct BuildCache {
+    dir: PathBuf,
+}
+
+impl BuildCache {
+    pub fn new(dir: PathBuf) -> Self {
+        BuildCache { dir }
+    }
+    /// The build cache configured by `[build_cache]` in config.toml, None if it is
+    /// disabled. `LABT_BUILD_CACHE` enables it in that directory, e.g. a directory
+    /// that CI saves between jobs
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if config.toml cannot be read or labt home is not found
+    pub fn from_config() -> anyhow::Result<Option<Self>> {
+        if let Some(dir) = std::env::var_os(LABT_BUILD_CACHE).filter(|dir| !dir.is_empty()) {
+            return Ok(Some(BuildCache::new(PathBuf::from(dir))));
+        }
+        let config = get_global_config()?;
+        let Some(table) = config.build_cache.as_ref().filter(|table| table.enabled) else {
+            return Ok(None);
+        };
+        let dir = match &table.dir {
+            Some(dir) => PathBuf::from(dir),
+            None => get_home()?.join(BUILD_CACHE_DIR),
+        };
+        Ok(Some(BuildCache::new(dir)))
+    }
+    fn entry(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(key)
+    }
+    /// Copies the outputs cached under key into the project at root. Returns false
+    /// if there is no entry. A corrupt entry is removed and reported as missing
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an output cannot be written to the project
+    pub fn restore(&self, key: &str, root: &Path) -> anyhow::Result<bool> {
+        let entry = self.entry(key);
+        let manifest = entry.join(ENTRY_MANIFEST);
+        if !manifest.exists() {
+            return Ok(false);
+        }
+        let outputs = match read_manifest(&manifest).and_then(|outputs| {
+            verify_entry(&entry, &outputs)?;
+            Ok(outputs)
+        }) {
+            Ok(outputs) => outputs,
+            Err(err) => {
+                warn!(target: "build", "Removing the corrupt build cache entry {:?}: {:?}", entry, err);
+                let _ = std::fs::remove_dir_all(&entry);
+                return Ok(false);
+            }
+        };
+        for output in outputs {
+            let dest = root.join(&output.path);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)
+                    .context(format!("Failed to create {:?}", parent))?;
+            }
+            std::fs::copy(entry.join(ENTRY_FILES).join(&output.path), &dest)
+                .context(format!("Failed to restore {:?} from the build cache", dest))?;
+        }
+        Ok(true)
+    }
+    /// Caches the files in outputs, relative to root, under key. A stage with
+    /// outputs outside the project is not cached as restoring only part of its
+    /// outputs would leave the build incomplete
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the entry cannot be written
+    pub fn store(&self, key: &str, root: &Path, outputs: &[PathBuf]) -> anyhow::Result<()> {
+        let entry = self.entry(key);
+        if entry.join(ENTRY_MANIFEST).exists() {
+            return Ok(());
+        }
+        if let Some(outside) = outputs
+            .iter()
+            .find(|output| !output.strip_prefix(root).is_ok_and(is_relative_inside))
+        {
+            info!(target: "build", "Not caching the stage as its output {:?} is outside the project", outside);
+            return Ok(());
+        }
+        // written next to the entry and renamed so that a concurrent build never
+        // restores half an entry
+        let partial = entry.with_extension(format!("tmp-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&partial);
+        let result = write_entry(&partial, root, outputs).and_then(|()| {
+            std::fs::rename(&partial, &entry)
+                .or_else(|err| {
+                    // another build stored the same entry first
+                    if entry.join(ENTRY_MANIFEST).exists() {
+                        Ok(())
+                    } else {
+                        Err(err)
+                    }
+                })
+                .context(format!(
+                    "Failed to move the build cache entry to {:?}",
+                    entry
+                ))
+        });
+        let _ = std::fs::remove_dir_all(&partial);
+        result
+    }
+}
+
+fn read_manifest(path: &Path) -> anyhow::Result<Vec<CachedOutput>> {
+    let json = std::fs::read_to_string(path).context(format!("Failed to read {:?}", path))?;
+    serde_json::from_str(&json).context(format!("Failed to parse {:?}", path))
+}
+
+/// Checks every file of an entry against its sha256
+fn verify_entry(entry: &Path, outputs: &[CachedOutput]) -> anyhow::Result<()> {
+    for output in outputs {
+        if !is_relative_inside(&output.path) {
+            anyhow::bail!("{:?} is outside the project", output.path);
+        }
+        let path = entry.join(ENTRY_FILES).join(&output.path);
+        let sha256 = file_sha256(&path).context(format!("Failed to hash {:?}", path))?;
+        if sha256 != output.sha256 {
+            anyhow::bail!("{:?} does not match its sha256", path);
+        }
+    }
+    Ok(())
+}
+
+fn write_entry(entry: &Path, root: &Path, outputs: &[PathBuf]) -> anyhow::Result<()> {
+    let mut cached = Vec::new();
+    for output in outputs {
+        let relative = output
+            .strip_prefix(root)
+            .ok()
+            .filter(|relative| is_relative_inside(relative))
+            .context(format!("{:?} is outside the project", output))?;
+        let dest = entry.join(ENTRY_FILES).join(relative);
+        if let Some(parent) = dest.parent() {
+            std::fs::create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+        }
+        std::fs::copy(output, &dest).context(format!("Failed to cache {:?}", output))?;
+        cached.push(CachedOutput {
+            path: relative.to_path_buf(),
+            sha256: file_sha256(&dest).context(format!("Failed to hash {:?}", dest))?,
+        });
+    }
+    let json = serde_json::to_string_pretty(&cached).context("Failed to serialize the outputs")?;
+    let manifest = entry.join(ENTRY_MANIFEST);
+    std::fs::create_dir_all(entry).context(format!("Failed to create {:?}", entry))?;
+    std::fs::write(&manifest, json).context(format!("Failed to write {:?}", manifest))
+}
+
+/// True for a relative path that does not leave the directory it is joined to
+fn is_relative_inside(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+/// The files matched by the globs, relative globs are relative to root. Matched
+/// directories are replaced by the files in them
+///
+/// # Errors
+///
+/// Returns an error if a pattern is not a valid glob or a directory cannot be read
+pub fn expand_files(root: &Path, patterns: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    fn collect(path: PathBuf, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+        if path.is_dir() {
+            for entry in std::fs::read_dir(&path).context(format!("Failed to read {:?}", path))? {
+                collect(entry?.path(), files)?;
+            }
+        } else if path.is_file() {
+            files.push(path);
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    for pattern in patterns {
+        let pattern = root.join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())
+            .context(format!("Invalid glob pattern {:?}", pattern))?;
+        for path in paths.flatten() {
+            collect(path, &mut files)?;
+        }
+    }
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// The cache key of a plugin stage. It covers the plugin, the code of the stage
+/// and the lua modules it can require, the sdk packages it loads, its input and
+/// output patterns, the contents of its inputs and config, see [`config_digest`]
+///
+/// # Errors
+///
+/// Returns an error if the stage code or its modules cannot be read
+pub fn cache_key(
+    plugin: &Plugin,
+    config: &str,
+    inputs: &BTreeMap<PathBuf, FileFingerprint>,
+) -> anyhow::Result<String> {
+    let mut sha = Sha256::new();
+    let code = code_digest(plugin)?;
+    for part in [
+        plugin.name.as_str(),
+        plugin.version.as_str(),
+        &plugin.step.to_string(),
+        &code,
+        config,
+    ] {
+        sha.update(part.as_bytes());
+        sha.update([0]);
+    }
+    for sdk in plugin.sdk_dependencies.iter() {
+        for part in [sdk.repo.as_str(), &sdk.path, &sdk.version.to_string()] {
+            sha.update(part.as_bytes());
+            sha.update([0]);
+        }
+    }
+    if let Some((input_patterns, output_patterns)) = &plugin.dependent_patterns {
+        for pattern in input_patterns
+            .iter()
+            .chain([&String::new()])
+            .chain(output_patterns)
+        {
+            sha.update(pattern.as_bytes());
+            sha.update([0]);
+        }
+    }
+    for (path, fingerprint) in inputs {
+        sha.update(path.to_string_lossy().as_bytes());
+        sha.update([0]);
+        sha.update(fingerprint.sha256.as_bytes());
+        sha.update([0]);
+    }
+    Ok(format!("{:x}", sha.finalize()))
+}
+
+/// The sha256 of the code of a plugin stage and the lua modules it can require
+///
+/// # Errors
+///
+/// Returns an error if the stage code or its modules cannot be read
+pub fn code_digest(plugin: &Plugin) -> anyhow::Result<String> {
+    let mut sha = Sha256::new();
+    let code = file_sha256(&plugin.path).context(format!("Failed to hash {:?}", plugin.path))?;
+    sha.update(code.as_bytes());
+    sha.update([0]);
+    for (path, sha256) in lua_modules(plugin)? {
+        sha.update(path.to_string_lossy().as_bytes());
+        sha.update([0]);
+        sha.update(sha256.as_bytes());
+        sha.update([0]);
+    }
+    Ok(format!("{:x}", sha.finalize()))
+}
+
+/// The lua files in the directories of the package paths of a plugin with their
+/// sha256. The paths are relative to their package directory so that the key does
+/// not change with where the plugin is installed
+fn lua_modules(plugin: &Plugin) -> anyhow::Result<Vec<(PathBuf, String)>> {
+    let mut seen = HashSet::new();
+    let mut modules = Vec::new();
+    for package_path in &plugin.package_paths {
+        // the directory before the ? of e.g. lib/?.lua or lib/?/init.lua
+        let dir: PathBuf = package_path
+            .components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains('?'))
+            .collect();
+        if !dir.is_dir() {
+            continue;
+        }
+        for file in expand_files(&dir, &[String::from("**/*.lua")])? {
+            if !seen.insert(file.clone()) {
+                continue;
+            }
+            let sha256 = file_sha256(&file).context(format!("Failed to hash {:?}", file))?;
+            let relative = file.strip_prefix(&dir).unwrap_or(&file).to_path_buf();
+            modules.push((relative, sha256));
+        }
+    }
+    Ok(modules)
+}
+
+/// The digest of the build settings that reach every stage: the active profile,
+/// its table in Labt.toml, the environment of plugin processes, the manifest, the
+/// lock file with the resolved dependencies, the JDK and the versions of the
+/// toolchains. Secret variables are
+/// included by name only so that their values are not derivable from the key
+///
+/// # Errors
+///
+/// Returns an error if the manifest or an existing lock file cannot be read
+pub fn config_digest(
+    profile: ProfileName,
+    table: Option<&ProfileTable>,
+    env: &ProcessEnv,
+    toolchains: &[Toolchain],
+    manifest: &Path,
+    lock: &Path,
+) -> anyhow::Result<String> {
+    let vars: BTreeMap<&str, &str> = env
+        .vars()
+        .iter()
+        .map(|(name, value)| {
+            if env.is_secret(name) {
+                (name.as_str(), "***")
+            } else {
+                (name.as_str(), value.as_str())
+            }
+        })
+        .collect();
+    let manifest = file_sha256(manifest).context(format!("Failed to hash {:?}", manifest))?;
+    let lock = if lock.exists() {
+        Some(file_sha256(lock).context(format!("Failed to hash {:?}", lock))?)
+    } else {
+        None
+    };
+    let settings = serde_json::json!({
+        "profile": profile,
+        "table": table,
+        "env": vars,
+        "manifest": manifest,
+        "lock": lock,
+        "java_home": std::env::var(JAVA_HOME).ok(),
+        "toolchains": toolchains
+            .iter()
+            .map(|toolchain| (toolchain.name.to_string(), toolchain.version.as_deref()))
+            .collect::<Vec<_>>(),
+    });
+    Ok(format!(
+        "{:x}",
+        Sha256::digest(settings.to_string().as_bytes())
+    ))
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn build_cache_restores_outputs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("build-cache");
+    let project = root.join("project");
+    std::fs::create_dir_all(project.join("build/classes/org")).unwrap();
+    std::fs::write(project.join("build/classes/org/Main.class"), "main").unwrap();
+    std::fs::write(project.join("build/app.jar"), "jar").unwrap();
+    let patterns = [String::from("build/*.jar"), String::from("build/classes")];
+    let outputs = expand_files(&project, &patterns).unwrap();
+    assert_eq!(
+        outputs,
+        vec![
+            project.join("build/app.jar"),
+            project.join("build/classes/org/Main.class")
+        ]
+    );
+
+    let cache = BuildCache::new(root.join("cache"));
+    let key = "ab".repeat(32);
+    assert!(!cache.restore(&key, &project).unwrap());
+    cache.store(&key, &project, &outputs).unwrap();
+
+    std::fs::remove_dir_all(project.join("build")).unwrap();
+    assert!(cache.restore(&key, &project).unwrap());
+    assert_eq!(
+        std::fs::read_to_string(project.join("build/classes/org/Main.class")).unwrap(),
+        "main"
+    );
+
+    // a tampered entry is dropped instead of restored
+    std::fs::write(cache.entry(&key).join("files/build/app.jar"), "other").unwrap();
+    assert!(!cache.restore(&key, &project).unwrap());
+    assert!(!cache.entry(&key).exists());
+}
+
+#[test]
+fn build_cache_key_inputs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let project = tmp.path().join("project");
+    std::fs::create_dir_all(project.join("build")).unwrap();
+    let manifest = project.join("Labt.toml");
+    let lock = project.join("Labt.lock");
+    std::fs::write(&manifest, "[project]").unwrap();
+
+    let digest = |env: &ProcessEnv| {
+        config_digest(ProfileName::Debug, None, env, &[], &manifest, &lock).unwrap()
+    };
+    let mut env = ProcessEnv::default();
+    env.set_secret(String::from("STORE_PASSWORD"), String::from("hunter2"));
+    let first = digest(&env);
+    // only the name of a secret is part of the key
+    env.set_secret(String::from("STORE_PASSWORD"), String::from("hunter3"));
+    assert_eq!(digest(&env), first);
+    // a new lock file changes the resolved dependencies
+    std::fs::write(&lock, "[[dependencies]]").unwrap();
+    assert_ne!(digest(&env), first);
+
+    // a stage writing outside the project is not cached
+    std::fs::write(project.join("build/app.jar"), "jar").unwrap();
+    std::fs::write(tmp.path().join("outside.jar"), "jar").unwrap();
+    let cache = BuildCache::new(tmp.path().join("cache"));
+    let key = "cd".repeat(32);
+    let outputs = [
+        project.join("build/app.jar"),
+        tmp.path().join("outside.jar"),
+    ];
+    cache.store(&key, &project, &outputs).unwrap();
+    assert!(!cache.restore(&key, &project).unwrap());
+}
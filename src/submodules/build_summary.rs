@@ -0,0 +1,379 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Context;
+use log::{Level, Log, Metadata, Record};
+use serde::Serialize;
+
+use crate::{caching::file_sha256, plugin::Plugin, session::Session};
+
+use super::{build::Step, reproducible::output_files};
+
+/// The summary written to the build directory at the end of labt build
+pub const BUILD_SUMMARY_FILE: &str = "build-summary.json";
+
+/// The extensions of the packages a build produces. They are listed as artifacts
+/// even if no plugin declares them as outputs
+const PACKAGE_EXTENSIONS: [&str; 2] = ["apk", "aab"];
+
+/// Whether a step or a plugin ran
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// Ran to completion
+    Ran,
+    /// Not run since its outputs were up to date or a step had no plugins to run
+    Skipped,
+    /// Not run since its outputs were restored from the build cache
+    Cached,
+    /// Failed, timed out or was interrupted
+    Failed,
+}
+
+/// A machine readable report of a build for CI to archive
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct BuildSummary {
+    pub success: bool,
+    pub release: bool,
+    pub elapsed_ms: u64,
+    /// The steps in the order they ran. Steps after a failed one are left out
+    pub steps: Vec<StepSummary>,
+    /// The pre_build and post_build hooks that ran
+    pub hooks: Vec<PluginSummary>,
+    /// The declared plugin outputs and the packages in the build directory
+    pub artifacts: Vec<Artifact>,
+    /// The warnings logged during the build, by labt and by plugins
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StepSummary {
+    pub step: String,
+    pub status: RunStatus,
+    pub elapsed_ms: u64,
+    pub plugins: Vec<PluginSummary>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct PluginSummary {
+    pub name: String,
+    pub version: String,
+    pub status: RunStatus,
+    pub elapsed_ms: u64,
+}
+
+/// A build output
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    /// The path relative to the build directory
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl BuildSummary {
+    pub fn new(release: bool) -> Self {
+        BuildSummary {
+            success: false,
+            release,
+            elapsed_ms: 0,
+            steps: Vec::new(),
+            hooks: Vec::new(),
+            artifacts: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl StepSummary {
+    pub fn new(step: Step) -> Self {
+        StepSummary {
+            step: step.to_string(),
+            status: RunStatus::Skipped,
+            elapsed_ms: 0,
+            plugins: Vec::new(),
+        }
+    }
+    /// Sets the status of the step from whether it succeeded and the plugins that ran
+    pub fn finish(&mut self, success: bool, elapsed: Duration) {
+        self.elapsed_ms = elapsed.as_millis() as u64;
+        self.status = if !success {
+            RunStatus::Failed
+        } else if self
+            .plugins
+            .iter()
+            .any(|plugin| plugin.status == RunStatus::Ran)
+        {
+            RunStatus::Ran
+        } else {
+            RunStatus::Skipped
+        };
+    }
+}
+
+impl PluginSummary {
+    pub fn new(plugin: &Plugin, status: RunStatus, elapsed: Duration) -> Self {
+        PluginSummary {
+            name: plugin.name.clone(),
+            version: plugin.version.clone(),
+            status,
+            elapsed_ms: elapsed.as_millis() as u64,
+        }
+    }
+}
+
+/// Starts collecting the warnings logged during a build, dropping those of an earlier build
+pub fn record_warnings() {
+    *Session::current().build_warnings.lock().unwrap() = Some(Vec::new());
+}
+
+/// Adds a warning to the summary of the running build. Does nothing outside a build
+pub fn add_warning(message: String) {
+    if let Some(warnings) = Session::current().build_warnings.lock().unwrap().as_mut() {
+        warnings.push(message);
+    }
+}
+
+/// Stops collecting warnings and returns those logged since [`record_warnings`]
+pub fn take_warnings() -> Vec<String> {
+    Session::current()
+        .build_warnings
+        .lock()
+        .unwrap()
+        .take()
+        .unwrap_or_default()
+}
+
+/// Wraps the logger of labt to add the warnings logged during a build to its
+/// summary. Warnings are always passed on so they are recorded even if the
+/// wrapped logger filters them out
+pub struct WarningLogger<L: Log> {
+    log: L,
+}
+
+impl<L: Log> WarningLogger<L> {
+    pub fn new(log: L) -> Self {
+        WarningLogger { log }
+    }
+}
+
+impl<L: Log> Log for WarningLogger<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn || self.log.enabled(metadata)
+    }
+    fn log(&self, record: &Record) {
+        if record.level() == Level::Warn {
+            add_warning(format!("{}: {}", record.target(), record.args()));
+        }
+        if self.log.enabled(record.metadata()) {
+            self.log.log(record);
+        }
+    }
+    fn flush(&self) {
+        self.log.flush();
+    }
+}
+
+/// The size and sha256 of the build outputs. These are the declared outputs in
+/// the build directory and the packages in it. Intermediates are left out
+///
+/// # Errors
+///
+/// Returns an error if the build directory or an output cannot be read
+pub fn build_artifacts(build_dir: &Path, outputs: &[PathBuf]) -> anyhow::Result<Vec<Artifact>> {
+    let mut files: BTreeSet<PathBuf> = outputs
+        .iter()
+        .filter_map(|output| output.strip_prefix(build_dir).ok())
+        .map(Path::to_path_buf)
+        .collect();
+    files.extend(output_files(build_dir)?.into_iter().filter(|file| {
+        file.extension()
+            .is_some_and(|ext| PACKAGE_EXTENSIONS.iter().any(|package| ext == *package))
+    }));
+    let mut artifacts = Vec::new();
+    for file in files {
+        if file == Path::new(BUILD_SUMMARY_FILE) {
+            continue;
+        }
+        let path = build_dir.join(&file);
+        if !path.is_file() {
+            continue;
+        }
+        let size = path
+            .metadata()
+            .context(format!("Failed to read the metadata of {:?}", path))?
+            .len();
+        let sha256 = file_sha256(&path).context(format!("Failed to hash {:?}", path))?;
+        artifacts.push(Artifact {
+            path: file,
+            size,
+            sha256,
+        });
+    }
+    Ok(artifacts)
+}
+
+/// Lists the artifacts of the build and writes the summary to
+/// build/build-summary.json. outputs are the files matched by the output globs
+/// of the plugins. Returns the path written
+///
+/// # Errors
+///
+/// Returns an error if the artifacts cannot be read or the file cannot be written
+pub fn write_summary(
+    build_dir: &Path,
+    outputs: &[PathBuf],
+    summary: &mut BuildSummary,
+) -> anyhow::Result<PathBuf> {
+    summary.artifacts = build_artifacts(build_dir, outputs)?;
+    std::fs::create_dir_all(build_dir).context(format!("Failed to create {:?}", build_dir))?;
+    let path = build_dir.join(BUILD_SUMMARY_FILE);
+    let json =
+        serde_json::to_string_pretty(summary).context("Failed to serialize the build summary")?;
+    std::fs::write(&path, json).context(format!("Failed to write {:?}", path))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn build_summary_lists_steps_and_artifacts() {
+    let tmp = tempfile::tempdir().unwrap();
+    let build_dir = tmp.path().join("build-summary");
+    std::fs::create_dir_all(build_dir.join("logs")).unwrap();
+    std::fs::create_dir_all(build_dir.join("classes/com")).unwrap();
+    std::fs::create_dir_all(build_dir.join("mapping")).unwrap();
+    std::fs::write(build_dir.join("app.apk"), "apk").unwrap();
+    std::fs::write(build_dir.join("logs/compile-javac.log"), "log").unwrap();
+    std::fs::write(build_dir.join("classes/com/Main.class"), "class").unwrap();
+    std::fs::write(build_dir.join("mapping/mapping.txt"), "map").unwrap();
+    std::fs::write(build_dir.join(BUILD_SUMMARY_FILE), "{}").unwrap();
+
+    let plugin = Plugin::new(
+        String::from("javac"),
+        String::from("1.0.0"),
+        PathBuf::from("compile.lua"),
+        Step::COMPILE,
+    );
+    let mut compile = StepSummary::new(Step::COMPILE);
+    compile.plugins.push(PluginSummary::new(
+        &plugin,
+        RunStatus::Ran,
+        Duration::from_millis(1200),
+    ));
+    compile.finish(true, Duration::from_millis(1250));
+    assert_eq!(compile.status, RunStatus::Ran);
+    let mut dex = StepSummary::new(Step::DEX);
+    dex.plugins.push(PluginSummary::new(
+        &plugin,
+        RunStatus::Skipped,
+        Duration::ZERO,
+    ));
+    dex.finish(true, Duration::from_millis(3));
+    assert_eq!(dex.status, RunStatus::Skipped);
+    let mut failed = StepSummary::new(Step::BUNDLE);
+    failed.finish(false, Duration::ZERO);
+    assert_eq!(failed.status, RunStatus::Failed);
+
+    let warnings = Session::new().enter(|| {
+        add_warning(String::from("ignored outside a build"));
+        record_warnings();
+        add_warning(String::from("javac: deprecated api"));
+        let warnings = take_warnings();
+        assert_eq!(take_warnings(), Vec::<String>::new());
+        warnings
+    });
+    let mut summary = BuildSummary::new(false);
+    summary.steps = vec![compile, dex];
+    summary.hooks.push(PluginSummary::new(
+        &Plugin::new(
+            String::from("pre_build"),
+            String::new(),
+            PathBuf::from("scripts/gen.lua"),
+            Step::PRE,
+        ),
+        RunStatus::Ran,
+        Duration::from_millis(40),
+    ));
+    summary.success = true;
+    summary.warnings = warnings;
+
+    // only the declared outputs and the packages are listed, not intermediates
+    let outputs = [
+        build_dir.join("mapping/mapping.txt"),
+        tmp.path().join("app/src/R.java"),
+    ];
+    let path = write_summary(&build_dir, &outputs, &mut summary).unwrap();
+    assert_eq!(
+        summary.artifacts,
+        vec![
+            Artifact {
+                path: PathBuf::from("app.apk"),
+                size: 3,
+                sha256: String::from(
+                    "dd37c2d7274f7ea982cb83390c36918fee9ce8889073c44b68cdc00bdb8c3e04"
+                ),
+            },
+            Artifact {
+                path: PathBuf::from("mapping/mapping.txt"),
+                size: 3,
+                sha256: file_sha256(&build_dir.join("mapping/mapping.txt")).unwrap(),
+            }
+        ]
+    );
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(json["steps"][0]["step"], "compile");
+    assert_eq!(json["steps"][0]["plugins"][0]["elapsed_ms"], 1200);
+    assert_eq!(json["steps"][1]["status"], "skipped");
+    assert_eq!(json["hooks"][0]["name"], "pre_build");
+    assert_eq!(json["artifacts"][0]["path"], "app.apk");
+    assert_eq!(json["warnings"][0], "javac: deprecated api");
+}
+
+#[test]
+fn warning_logger_records_filtered_warnings() {
+    struct ErrorsOnly;
+    impl Log for ErrorsOnly {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.level() <= Level::Error
+        }
+        fn log(&self, _record: &Record) {}
+        fn flush(&self) {}
+    }
+    let logger = WarningLogger::new(ErrorsOnly);
+    assert!(logger.enabled(&Metadata::builder().level(Level::Warn).build()));
+    assert!(!logger.enabled(&Metadata::builder().level(Level::Info).build()));
+    let warnings = Session::new().enter(|| {
+        let warn = |message| {
+            logger.log(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .target("staging")
+                    .args(format_args!("{}", message))
+                    .build(),
+            )
+        };
+        warn("ignored outside a build");
+        record_warnings();
+        warn("junit:junit:4.13 has not been downloaded");
+        logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target("build")
+                .args(format_args!("not a warning"))
+                .build(),
+        );
+        take_warnings()
+    });
+    assert_eq!(
+        warnings,
+        vec![String::from(
+            "staging: junit:junit:4.13 has not been downloaded"
+        )]
+    );
+}
@@ -0,0 +1,284 @@
+use std::{
+    collections::HashSet,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use log::{info, warn};
+use reqwest::Url;
+
+use crate::{
+    caching::{cached_pom_ancestors, Cache, CacheType},
+    config::{
+        get_config,
+        global::{get_global_config, GlobalConfig, GLOBAL_CONFIG_FILE},
+        lock::load_labt_lock,
+    },
+    get_home,
+    paths::sanitize_component,
+    plugin::{api::fs::copy_recursively, config::PluginToml},
+};
+
+use super::{
+    jdk::jdk_install_dir,
+    plugin::plugin_dir,
+    prefetch::{Prefetch, PrefetchArgs},
+    resolve::ProjectDep,
+    sdk::{get_sdk_path, package_dir_in},
+    sdkmanager::{
+        installed_list::{InstalledList, INSTALLED_LIST},
+        requirements::{required_compile_sdk, satisfying_packages},
+        InstalledPackage, ToIdLong,
+    },
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct BundleOfflineArgs {
+    /// The directory to write the bundle to. It must be empty or not exist
+    dir: PathBuf,
+    #[command(flatten)]
+    prefetch: PrefetchArgs,
+}
+
+pub struct BundleOffline {
+    args: BundleOfflineArgs,
+}
+
+impl BundleOffline {
+    pub fn new(args: &BundleOfflineArgs) -> Self {
+        BundleOffline { args: args.clone() }
+    }
+}
+
+/// Returns where path, which is inside home, goes in the bundle
+pub fn rebase(path: &Path, home: &Path, bundle: &Path) -> Option<PathBuf> {
+    path.strip_prefix(home)
+        .ok()
+        .map(|relative| bundle.join(relative))
+}
+
+/// The config.toml of a bundle. It keeps the properties of the current
+/// config.toml and switches labt to offline mode
+pub fn bundle_config(global: &GlobalConfig) -> GlobalConfig {
+    GlobalConfig {
+        properties: global.properties.clone(),
+        offline: Some(true),
+        ..Default::default()
+    }
+}
+
+/// Copies the file or directory at path in home to the same place in bundle.
+/// Returns false if it does not exist
+fn copy_into(path: &Path, home: &Path, bundle: &Path) -> anyhow::Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let dest =
+        rebase(path, home, bundle).context(format!("{:?} is not in labt home {:?}", path, home))?;
+    if path.is_dir() {
+        copy_recursively(path, &dest).context(format!("Failed to copy {:?}", path))?;
+    } else {
+        if let Some(parent) = dest.parent() {
+            create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+        }
+        std::fs::copy(path, &dest).context(format!("Failed to copy {:?}", path))?;
+    }
+    Ok(true)
+}
+
+impl Submodule for BundleOffline {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let bundle = &self.args.dir;
+        if bundle.exists()
+            && bundle
+                .read_dir()
+                .context(format!("Failed to read {:?}", bundle))?
+                .next()
+                .is_some()
+        {
+            bail!("{:?} is not empty", bundle);
+        }
+        if crate::is_offline() {
+            info!(target: "bundle", "Bundling what is already in labt home since --offline was passed");
+        } else {
+            Prefetch::new(&self.args.prefetch)
+                .run()
+                .context("Failed to fetch what the build needs")?;
+        }
+        let home = get_home().context("Failed to get labt home")?;
+        let config = get_config().context("Failed reading project configuration")?;
+        let lock =
+            load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+        create_dir_all(bundle).context(format!("Failed to create {:?}", bundle))?;
+
+        // dependencies, with the parent poms and maven-metadata.xml resolution reads
+        let deps: Vec<&ProjectDep> = lock
+            .resolved
+            .iter()
+            .chain(&lock.test)
+            .chain(&lock.processors)
+            .collect();
+        let mut copied = HashSet::new();
+        let mut missing = 0;
+        for dep in &deps {
+            crate::interrupt::check()?;
+            let coordinate = (
+                dep.group_id.clone(),
+                dep.artifact_id.clone(),
+                dep.version.clone(),
+            );
+            // resolution reads the parents and the imported boms again when Labt.lock changes
+            let ancestors = cached_pom_ancestors(&coordinate)?;
+            let coordinates = std::iter::once(coordinate).chain(ancestors);
+            for (group_id, artifact_id, version) in coordinates {
+                if !copied.insert((group_id.clone(), artifact_id.clone(), version.clone())) {
+                    continue;
+                }
+                let mut pom = Cache::new(
+                    group_id.clone(),
+                    artifact_id.clone(),
+                    version.clone(),
+                    CacheType::POM,
+                );
+                pom.use_labt_home()?;
+                let pom = pom.get_path()?;
+                let Some(version_dir) = pom.parent() else {
+                    continue;
+                };
+                if !copy_into(version_dir, &home, bundle)? {
+                    warn!(target: "bundle", "{}:{}:{} is not in the labt cache", group_id, artifact_id, version);
+                    missing += 1;
+                    continue;
+                }
+                if let Some(metadata) = version_dir.parent().map(|d| d.join("maven-metadata.xml")) {
+                    copy_into(&metadata, &home, bundle)?;
+                }
+            }
+        }
+
+        // plugins and the sdk packages they load
+        let mut sdk_packages: Vec<InstalledPackage> = Vec::new();
+        let installed = InstalledList::parse_from_sdk()
+            .context("Failed to parse installed sdk packages list")?;
+        let installed_map = installed.get_hash_map_long();
+        let mut plugins = 0;
+        for (name, plugin) in config.plugins.iter().flatten() {
+            let Some(url) = plugin.location.as_deref().and_then(|l| Url::parse(l).ok()) else {
+                info!(target: "bundle", "Skipping plugin {} as it is not installed in labt home", name);
+                continue;
+            };
+            let mut dir = plugin_dir(home.clone(), &url);
+            dir.push("versions");
+            dir.push(sanitize_component(&format!("v{}", &plugin.version)).as_ref());
+            if !copy_into(&dir, &home, bundle)? {
+                bail!(
+                    "Plugin {}@{} is not installed. Run labt prefetch",
+                    name,
+                    plugin.version
+                );
+            }
+            plugins += 1;
+            if self.args.prefetch.no_sdk {
+                continue;
+            }
+            let plugin_toml: PluginToml = std::fs::read_to_string(dir.join("plugin.toml"))
+                .context(format!("Failed to read the plugin.toml of {}", name))?
+                .parse()
+                .context(format!("Failed to parse the plugin.toml of {}", name))?;
+            for sdk in &plugin_toml.sdk {
+                match installed_map.get(&sdk.to_id_long()) {
+                    Some(package) => sdk_packages.push((*package).clone()),
+                    None => {
+                        warn!(target: "bundle", "Sdk package {} required by {} is not installed", sdk.path, name);
+                    }
+                }
+            }
+        }
+        if !self.args.prefetch.no_sdk {
+            if let Some(requirement) = required_compile_sdk(&lock.resolved)? {
+                sdk_packages.extend(
+                    satisfying_packages(&requirement, &installed)
+                        .into_iter()
+                        .cloned(),
+                );
+            }
+        }
+        sdk_packages.sort_by_key(|p| p.to_id_long());
+        sdk_packages.dedup_by_key(|p| p.to_id_long());
+
+        let sdk = get_sdk_path()?;
+        let bundle_sdk = rebase(&sdk, &home, bundle)
+            .context(format!("{:?} is not in labt home {:?}", sdk, home))?;
+        for package in &mut sdk_packages {
+            crate::interrupt::check()?;
+            let source = package.directory.clone().unwrap_or_else(|| {
+                package_dir_in(sdk.clone(), &package.repository_name, &package.path)
+            });
+            let dest = package_dir_in(bundle_sdk.clone(), &package.repository_name, &package.path);
+            copy_recursively(&source, &dest)
+                .context(format!("Failed to copy sdk package {}", package.path))?;
+            // the bundle is moved, so the default package directory is used
+            package.directory = None;
+        }
+        let list = InstalledList {
+            packages: sdk_packages,
+            repositories: Default::default(),
+        };
+        create_dir_all(&bundle_sdk).context(format!("Failed to create {:?}", bundle_sdk))?;
+        std::fs::write(bundle_sdk.join(INSTALLED_LIST), list.to_string())
+            .context("Failed to write the installed sdk packages of the bundle")?;
+
+        if let Some(jdk) = &config.jdk {
+            let dir = jdk_install_dir(jdk)?;
+            if !copy_into(&dir, &home, bundle)? {
+                warn!(target: "bundle", "JDK {} is not installed", jdk.version);
+            }
+        }
+
+        let global = get_global_config().context("Failed to read config.toml")?;
+        let path = bundle.join(GLOBAL_CONFIG_FILE);
+        let contents = toml::to_string(&bundle_config(&global))
+            .context("Failed to serialize the bundle config.toml")?;
+        std::fs::write(&path, contents).context(format!("Failed to write {:?}", path))?;
+
+        if missing > 0 {
+            warn!(target: "bundle", "{} dependencies are missing from the bundle", missing);
+        }
+        info!(target: "bundle", "Bundled {} dependencies, {} plugins and {} sdk packages to {:?}. Build with LABT_HOME={:?} on the isolated machine",
+            deps.len(), plugins, list.packages.len(), bundle, bundle);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn bundle_is_an_offline_labt_home() {
+    use std::collections::HashMap;
+
+    let home = Path::new("/home/user/.labt");
+    let bundle = Path::new("/media/usb/bundle");
+    assert_eq!(
+        rebase(&home.join("cache").join("androidx.core"), home, bundle),
+        Some(bundle.join("cache").join("androidx.core"))
+    );
+    assert_eq!(rebase(Path::new("/opt/sdk"), home, bundle), None);
+
+    let global = GlobalConfig {
+        properties: Some(HashMap::from([(
+            String::from("kotlin.version"),
+            String::from("1.9.24"),
+        )])),
+        offline: None,
+        ..Default::default()
+    };
+    let contents = toml::to_string(&bundle_config(&global)).unwrap();
+    let parsed: GlobalConfig = toml::from_str(&contents).unwrap();
+    assert_eq!(parsed.offline, Some(true));
+    assert_eq!(parsed.properties, global.properties);
+    assert_eq!(parsed.network, None);
+}
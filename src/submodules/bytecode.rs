@@ -0,0 +1,349 @@
+use std::{
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use log::warn;
+use zip::{read::ZipFile, ZipArchive};
+
+use crate::caching::Cache;
+
+use super::resolve::ProjectDep;
+
+/// Java releases are class file major versions offset by 44 e.g. Java 17 is 61
+pub const MAJOR_VERSION_OFFSET: u16 = 44;
+pub const CLASS_MAGIC: [u8; 4] = [0xCA, 0xFE, 0xBA, 0xBE];
+/// The largest archive entry read into memory. The size in the zip header is not
+/// trusted beyond it
+const MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+
+/// The Java release a class file major version targets
+pub fn java_release(major: u16) -> u16 {
+    major.saturating_sub(MAJOR_VERSION_OFFSET)
+}
+
+/// Reads the major version from the first 8 bytes of a class file. None if it is
+/// not a class file
+pub fn class_major_version(header: &[u8]) -> Option<u16> {
+    if header.len() < 8 || header[..4] != CLASS_MAGIC {
+        return None;
+    }
+    Some(u16::from_be_bytes([header[6], header[7]]))
+}
+
+/// Reads the big endian values of a class file
+struct ClassReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ClassReader<'a> {
+    fn take(&mut self, len: usize) -> anyhow::Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.offset..self.offset.saturating_add(len))
+            .context("Unexpected end of class file")?;
+        self.offset += len;
+        Ok(bytes)
+    }
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> anyhow::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// A constant pool entry. Constants no scan looks into are [`Constant::Other`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constant {
+    Utf8(String),
+    Integer(u32),
+    /// The index of the class name
+    Class(u16),
+    /// The index of the string literal
+    String(u16),
+    /// The indexes of the class and the name and type
+    FieldRef(u16, u16),
+    /// The indexes of the name and the descriptor
+    NameAndType(u16, u16),
+    Other,
+}
+
+/// The constant pool of a class file and the class it declares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassFile {
+    /// Indexed by constant pool index. Entry 0 and the second entries of longs and
+    /// doubles are [`Constant::Other`]
+    pub pool: Vec<Constant>,
+    /// The index of the class constant of this class
+    pub this_class: u16,
+}
+
+impl ClassFile {
+    /// Reads the constant pool and this class of a class file
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if data is not a class file or it is truncated
+    pub fn parse(data: &[u8]) -> anyhow::Result<ClassFile> {
+        let mut reader = ClassReader { data, offset: 0 };
+        if reader.take(4)? != CLASS_MAGIC {
+            bail!("Invalid class file magic");
+        }
+        // minor and major version
+        reader.take(4)?;
+        let count = reader.u16()? as usize;
+        let mut pool = vec![Constant::Other; count];
+        let mut index = 1;
+        while index < count {
+            let tag = reader.u8()?;
+            let constant = match tag {
+                1 => {
+                    let len = reader.u16()? as usize;
+                    Constant::Utf8(String::from_utf8_lossy(reader.take(len)?).to_string())
+                }
+                3 => Constant::Integer(reader.u32()?),
+                4 | 10 | 11 | 17 | 18 => {
+                    reader.take(4)?;
+                    Constant::Other
+                }
+                5 | 6 => {
+                    reader.take(8)?;
+                    Constant::Other
+                }
+                7 => Constant::Class(reader.u16()?),
+                8 => Constant::String(reader.u16()?),
+                16 | 19 | 20 => {
+                    reader.take(2)?;
+                    Constant::Other
+                }
+                9 => Constant::FieldRef(reader.u16()?, reader.u16()?),
+                12 => Constant::NameAndType(reader.u16()?, reader.u16()?),
+                15 => {
+                    reader.take(3)?;
+                    Constant::Other
+                }
+                _ => bail!("Unknown constant pool tag {tag} at {index}"),
+            };
+            pool[index] = constant;
+            // long and double take two entries
+            index += if matches!(tag, 5 | 6) { 2 } else { 1 };
+        }
+        // access flags
+        reader.take(2)?;
+        let this_class = reader.u16()?;
+        Ok(ClassFile { pool, this_class })
+    }
+    /// The utf8 constant at index
+    pub fn utf8(&self, index: u16) -> Option<&str> {
+        match self.pool.get(index as usize) {
+            Some(Constant::Utf8(value)) => Some(value.as_str()),
+            _ => None,
+        }
+    }
+    /// The name of the class constant at index e.g. `com/example/Outer$Inner`
+    pub fn class_name(&self, index: u16) -> Option<&str> {
+        match self.pool.get(index as usize) {
+            Some(Constant::Class(name)) => self.utf8(*name),
+            _ => None,
+        }
+    }
+    /// The name of the class declared by the class file
+    pub fn name(&self) -> Option<&str> {
+        self.class_name(self.this_class)
+    }
+}
+
+/// Reads an archive entry into memory
+///
+/// # Errors
+///
+/// Returns an error if the entry cannot be read or is larger than [`MAX_ENTRY_SIZE`]
+pub fn read_entry(mut entry: ZipFile) -> anyhow::Result<Vec<u8>> {
+    let name = entry.name().to_string();
+    if entry.size() > MAX_ENTRY_SIZE {
+        bail!("{name} is larger than {MAX_ENTRY_SIZE} bytes");
+    }
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    (&mut entry)
+        .take(MAX_ENTRY_SIZE + 1)
+        .read_to_end(&mut data)
+        .context(format!("Failed to read {}", name))?;
+    if data.len() as u64 > MAX_ENTRY_SIZE {
+        bail!("{name} is larger than {MAX_ENTRY_SIZE} bytes");
+    }
+    Ok(data)
+}
+
+/// Classes dexing never sees. Multi-release jars keep classes for newer Java
+/// releases under META-INF/versions and module-info is dropped by d8
+fn is_ignored(name: &str) -> bool {
+    name.starts_with("META-INF/versions/") || name.ends_with("module-info.class")
+}
+
+/// Returns the highest class file major version in a jar. The jars an AAR
+/// bundles, classes.jar and libs/*.jar, are scanned too. Bundled jars that
+/// cannot be read are skipped with a warning. None if there are no classes.
+///
+/// # Errors
+///
+/// Returns an error if an entry cannot be read
+pub fn max_major_version<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> anyhow::Result<Option<u16>> {
+    let mut max = None;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        let major = if name.ends_with(".class") && !is_ignored(&name) {
+            let mut header = [0; 8];
+            match entry.read_exact(&mut header) {
+                Ok(()) => class_major_version(&header),
+                Err(_) => None,
+            }
+        } else if name.ends_with(".jar") {
+            let nested = read_entry(entry).and_then(|jar| {
+                let mut nested = ZipArchive::new(Cursor::new(jar))
+                    .context(format!("{} is not a valid jar", name))?;
+                max_major_version(&mut nested)
+            });
+            match nested {
+                Ok(major) => major,
+                Err(err) => {
+                    warn!(target: "bytecode", "Skipping {name}: {:?}", err);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        max = max.max(major);
+    }
+    Ok(max)
+}
+
+/// Returns the highest class file major version in the jar or AAR at path
+///
+/// # Errors
+///
+/// Returns an error if the file is not a valid archive
+pub fn artifact_major_version(path: &Path) -> anyhow::Result<Option<u16>> {
+    let file = File::open(path).context(format!("Failed to open {:?}", path))?;
+    let mut archive =
+        ZipArchive::new(file).context(format!("{:?} is not a valid archive", path))?;
+    max_major_version(&mut archive).context(format!("Failed to scan {:?}", path))
+}
+
+/// A dependency with classes for a newer Java release than allowed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytecodeViolation {
+    pub coordinate: String,
+    /// The highest class file major version in the artifact
+    pub major: u16,
+}
+
+/// Scans the cached artifacts of deps for classes targeting a Java release
+/// newer than max_release. Dependencies without artifacts or not yet
+/// downloaded are skipped, as are artifacts that cannot be read with a warning.
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available
+pub fn check_bytecode(
+    deps: &[ProjectDep],
+    max_release: u16,
+) -> anyhow::Result<Vec<BytecodeViolation>> {
+    let mut violations = Vec::new();
+    for dep in deps {
+        crate::interrupt::check()?;
+        if dep.packaging != "jar" && dep.packaging != "aar" {
+            continue;
+        }
+        let mut cache = Cache::from(dep);
+        cache.use_labt_home()?;
+        let path = cache.get_path()?;
+        if !path.exists() {
+            continue;
+        }
+        let coordinate = format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version);
+        let major = match artifact_major_version(&path) {
+            Ok(major) => major,
+            Err(err) => {
+                warn!(target: "bytecode", "Skipping the bytecode check of {coordinate}: {:?}", err);
+                continue;
+            }
+        };
+        if let Some(major) = major {
+            if java_release(major) > max_release {
+                violations.push(BytecodeViolation { coordinate, major });
+            }
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn bytecode_major_version_of_jars_and_aars() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let class = |major: u16| {
+        let mut header = CLASS_MAGIC.to_vec();
+        header.extend([0, 0]);
+        header.extend(major.to_be_bytes());
+        header.extend([0; 16]);
+        header
+    };
+    let zip = |entries: &[(&str, Vec<u8>)]| {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    };
+
+    let jar = zip(&[
+        ("com/example/Lib.class", class(52)),
+        ("com/example/Util.class", class(55)),
+        ("module-info.class", class(53)),
+        ("META-INF/versions/21/com/example/Util.class", class(65)),
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0".to_vec()),
+    ]);
+    let aar = zip(&[
+        ("AndroidManifest.xml", b"<manifest/>".to_vec()),
+        ("classes.jar", zip(&[("com/example/Ui.class", class(50))])),
+        (
+            "libs/extra.jar",
+            zip(&[("com/example/Extra.class", class(61))]),
+        ),
+    ]);
+    let resources = zip(&[("res/values/values.xml", b"<resources/>".to_vec())]);
+    // a bundled jar that cannot be read does not hide the other classes
+    let corrupt = zip(&[
+        ("classes.jar", zip(&[("com/example/Ui.class", class(52))])),
+        ("libs/broken.jar", b"not a jar".to_vec()),
+    ]);
+
+    let scan =
+        |bytes: Vec<u8>| max_major_version(&mut ZipArchive::new(Cursor::new(bytes)).unwrap());
+    assert_eq!(scan(jar).unwrap(), Some(55));
+    assert_eq!(scan(aar).unwrap(), Some(61));
+    assert_eq!(scan(resources).unwrap(), None);
+    assert_eq!(scan(corrupt).unwrap(), Some(52));
+    assert_eq!(java_release(61), 17);
+    assert_eq!(class_major_version(b"PK\x03\x04\0\0\0\0"), None);
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashSet,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Subcommand};
+use indicatif::HumanBytes;
+use log::{info, warn};
+
+use crate::{
+    caching::cached_pom_ancestors,
+    config::lock::{known_locks, lock_file_in, strings::LOCK_FILE, write_known_locks, LabtLock},
+    get_home, get_project_root,
+};
+
+use super::{licenses::LICENSES_CACHE_DIR, Submodule};
+
+const METADATA_FILE: &str = "maven-metadata.xml";
+
+#[derive(Clone, Args)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum CacheSubcommands {
+    /// Deletes cached artifacts that no known Labt.lock uses
+    Gc(GcArgs),
+}
+
+#[derive(Clone, Args)]
+pub struct GcArgs {
+    /// Keep artifacts downloaded within this many days even if no lock file uses them
+    #[arg(long, value_name = "DAYS", default_value_t = 30)]
+    max_age: u64,
+    /// Also keep the artifacts of this lock file. Can be given more than once
+    #[arg(long = "lock", value_name = "PATH")]
+    locks: Vec<PathBuf>,
+    /// List what would be deleted without deleting it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub struct Cache {
+    args: CacheArgs,
+}
+
+impl Cache {
+    pub fn new(args: &CacheArgs) -> Self {
+        Cache { args: args.clone() }
+    }
+}
+
+/// A version directory of an artifact in the labt cache
+#[derive(Debug, Clone)]
+pub struct CachedVersion {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub path: PathBuf,
+    /// Total size of the files in the directory
+    pub size: u64,
+    /// The latest modification time of the files in the directory
+    pub modified: SystemTime,
+}
+
+impl CachedVersion {
+    pub fn coordinate(&self) -> String {
+        format!("{}:{}:{}", self.group_id, self.artifact_id, self.version)
+    }
+}
+
+fn sub_dirs(path: &Path) -> Vec<(String, PathBuf)> {
+    let mut dirs: Vec<(String, PathBuf)> = read_dir(path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| {
+            (
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            )
+        })
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Lists the artifact versions in the cache directory, laid out as
+/// group_id/artifact_id/version
+///
+/// # Errors
+///
+/// Returns an error if a version directory cannot be read
+pub fn scan_cache(cache: &Path) -> anyhow::Result<Vec<CachedVersion>> {
+    let mut versions = Vec::new();
+    for (group_id, group) in sub_dirs(cache) {
+        if group_id == LICENSES_CACHE_DIR {
+            continue;
+        }
+        for (artifact_id, artifact) in sub_dirs(&group) {
+            for (version, path) in sub_dirs(&artifact) {
+                let mut size = 0;
+                let mut modified = SystemTime::UNIX_EPOCH;
+                for entry in read_dir(&path).context(format!("Failed to read {:?}", path))? {
+                    let metadata = entry?.metadata()?;
+                    size += metadata.len();
+                    if let Ok(time) = metadata.modified() {
+                        modified = modified.max(time);
+                    }
+                }
+                versions.push(CachedVersion {
+                    group_id: group_id.clone(),
+                    artifact_id: artifact_id.clone(),
+                    version,
+                    path,
+                    size,
+                    modified,
+                });
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// The cached versions that are neither used by a lock, given as group_id:artifact_id:version
+/// in keep, nor modified after the cutoff
+pub fn select_garbage<'a>(
+    cached: &'a [CachedVersion],
+    keep: &HashSet<String>,
+    cutoff: SystemTime,
+) -> Vec<&'a CachedVersion> {
+    cached
+        .iter()
+        .filter(|version| version.modified < cutoff && !keep.contains(&version.coordinate()))
+        .collect()
+}
+
+/// The coordinates of every artifact in the lock, with their parent and imported
+/// bom poms that resolution reads again when offline
+fn locked_coordinates(lock: &LabtLock, keep: &mut HashSet<String>) -> anyhow::Result<()> {
+    for dep in lock
+        .resolved
+        .iter()
+        .chain(&lock.processors)
+        .chain(&lock.test)
+    {
+        let coordinate = (
+            dep.group_id.clone(),
+            dep.artifact_id.clone(),
+            dep.version.clone(),
+        );
+        let ancestors = cached_pom_ancestors(&coordinate)?;
+        for (group_id, artifact_id, version) in std::iter::once(coordinate).chain(ancestors) {
+            keep.insert(format!("{group_id}:{artifact_id}:{version}"));
+        }
+    }
+    Ok(())
+}
+
+/// Removes the metadata and the directories of an artifact without cached versions
+fn remove_empty_artifact(artifact: &Path, cutoff: SystemTime) -> std::io::Result<u64> {
+    if !sub_dirs(artifact).is_empty() {
+        return Ok(0);
+    }
+    let mut reclaimed = 0;
+    let metadata = artifact.join(METADATA_FILE);
+    if let Ok(info) = metadata.metadata() {
+        if info.modified().is_ok_and(|time| time >= cutoff) {
+            return Ok(0);
+        }
+        reclaimed = info.len();
+        std::fs::remove_file(&metadata)?;
+    }
+    // only removed if nothing else is left in it
+    if std::fs::remove_dir(artifact).is_ok() {
+        if let Some(group) = artifact.parent() {
+            let _ = std::fs::remove_dir(group);
+        }
+    }
+    Ok(reclaimed)
+}
+
+impl Cache {
+    fn gc(&self, args: &GcArgs) -> anyhow::Result<()> {
+        let cache = get_home()?.join("cache");
+        if !cache.is_dir() {
+            info!(target: "cache", "The labt cache is empty");
+            return Ok(());
+        }
+
+        // lock files that no longer exist are dropped from the list
+        let mut locks = known_locks()?;
+        locks.retain(|lock| lock.exists());
+        if !args.dry_run {
+            write_known_locks(&locks)?;
+        }
+        if let Ok(root) = get_project_root() {
+            // the dependencies Labt.dev.toml overrides are kept too
+            locks.push(root.join(LOCK_FILE));
+            locks.push(lock_file_in(&root));
+        }
+        for lock in &args.locks {
+            if !lock.is_file() {
+                bail!("Lock file {:?} does not exist", lock);
+            }
+            locks.push(lock.clone());
+        }
+        let mut locks: Vec<PathBuf> = locks
+            .into_iter()
+            .filter(|lock| lock.exists())
+            .map(|lock| lock.canonicalize().unwrap_or(lock))
+            .collect();
+        locks.sort();
+        locks.dedup();
+
+        let mut keep = HashSet::new();
+        for path in &locks {
+            let lock = std::fs::read_to_string(path)
+                .context(format!("Failed to read {:?}", path))?
+                .parse::<LabtLock>()
+                .context(format!("Failed to parse {:?}", path))?;
+            locked_coordinates(&lock, &mut keep)?;
+        }
+        info!(target: "cache", "Keeping the artifacts of {} lock files and those newer than {} days", locks.len(), args.max_age);
+        if locks.is_empty() {
+            warn!(target: "cache", "No lock files are known. Run labt resolve in your projects or pass them with --lock");
+        }
+
+        let cutoff = SystemTime::now()
+            .checked_sub(Duration::from_secs(args.max_age * 24 * 60 * 60))
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let cached = scan_cache(&cache)?;
+        let garbage = select_garbage(&cached, &keep, cutoff);
+
+        let mut reclaimed = 0;
+        let mut artifacts = Vec::new();
+        for version in &garbage {
+            if args.dry_run {
+                info!(target: "cache", "Would delete {} {}", version.coordinate(), HumanBytes(version.size));
+            } else {
+                std::fs::remove_dir_all(&version.path)
+                    .context(format!("Failed to delete {:?}", version.path))?;
+                if let Some(artifact) = version.path.parent() {
+                    artifacts.push(artifact.to_path_buf());
+                }
+            }
+            reclaimed += version.size;
+        }
+        artifacts.dedup();
+        for artifact in artifacts {
+            reclaimed += remove_empty_artifact(&artifact, cutoff)
+                .context(format!("Failed to clean up {:?}", artifact))?;
+        }
+
+        if args.dry_run {
+            info!(target: "cache", "Would delete {} of {} cached versions, reclaiming {}", garbage.len(), cached.len(), HumanBytes(reclaimed));
+        } else {
+            info!(target: "cache", "Deleted {} of {} cached versions, reclaimed {}", garbage.len(), cached.len(), HumanBytes(reclaimed));
+        }
+        Ok(())
+    }
+}
+
+impl Submodule for Cache {
+    fn run(&mut self) -> anyhow::Result<()> {
+        match &self.args.command {
+            CacheSubcommands::Gc(args) => self.gc(args),
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn cache_gc_selects_unused_versions() {
+    use std::fs::{create_dir_all, File};
+
+    let tmp = tempfile::tempdir().unwrap();
+    let cache = tmp.path().join("cache-gc");
+    let old = SystemTime::now() - Duration::from_secs(90 * 24 * 60 * 60);
+    for (version, modified) in [("1.0", old), ("1.1", old), ("2.0", SystemTime::now())] {
+        let dir = cache.join("com.example").join("core").join(version);
+        create_dir_all(&dir).unwrap();
+        let file = File::create(dir.join(format!("core-{version}.jar"))).unwrap();
+        file.set_len(100).unwrap();
+        file.set_modified(modified).unwrap();
+    }
+    create_dir_all(cache.join(LICENSES_CACHE_DIR)).unwrap();
+
+    let cached = scan_cache(&cache).unwrap();
+    assert_eq!(cached.len(), 3);
+    assert_eq!(cached[0].size, 100);
+
+    let mut keep = HashSet::new();
+    keep.insert(String::from("com.example:core:1.1"));
+    let cutoff = SystemTime::now() - Duration::from_secs(30 * 24 * 60 * 60);
+    let garbage: Vec<String> = select_garbage(&cached, &keep, cutoff)
+        .iter()
+        .map(|version| version.coordinate())
+        .collect();
+    assert_eq!(garbage, vec![String::from("com.example:core:1.0")]);
+}
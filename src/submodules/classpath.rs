@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Args;
+use log::warn;
+use serde::Serialize;
+
+use crate::{config::lock::load_labt_lock, get_project_root, output::is_json_output, pom::Scope};
+
+use super::{
+    artifacts::split_classpaths,
+    ide::{android_jar, dependency_jar, extracted_libs_dir},
+    resolve::ProjectDep,
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct ExportClasspathArgs {
+    /// Print the classpath entries as json instead of a classpath string
+    #[arg(long)]
+    json: bool,
+    /// Print the runtime classpath instead of the compile classpath. Ignored with --json
+    #[arg(long)]
+    runtime: bool,
+    /// The android platform to compile against e.g. android-34.
+    /// Defaults to the latest installed platform
+    #[arg(short, long)]
+    platform: Option<String>,
+}
+
+pub struct ExportClasspath {
+    args: ExportClasspathArgs,
+}
+
+impl ExportClasspath {
+    pub fn new(args: &ExportClasspathArgs) -> Self {
+        ExportClasspath { args: args.clone() }
+    }
+}
+
+/// A resolved dependency on the classpath
+#[derive(Serialize, Debug, PartialEq, Eq)]
+pub struct ClasspathEntry {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub scope: String,
+    pub packaging: String,
+    /// The jar to put on the classpath. For AARs this is the extracted classes.jar
+    pub path: PathBuf,
+}
+
+/// The project classpath for external tooling
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct Classpath {
+    /// android.jar of the platform compiled against
+    pub platform: Option<PathBuf>,
+    /// Jars needed to compile the project including android.jar
+    pub compile: Vec<PathBuf>,
+    /// Jars that need to be packaged with the app
+    pub runtime: Vec<PathBuf>,
+    pub dependencies: Vec<ClasspathEntry>,
+}
+
+impl Classpath {
+    /// Splits the entries into the compile and runtime classpaths.
+    /// Provided dependencies are only needed at compile time and
+    /// runtime dependencies only at runtime.
+    pub fn new(platform: Option<PathBuf>, dependencies: Vec<ClasspathEntry>) -> Self {
+        let scopes: Vec<Scope> = dependencies
+            .iter()
+            .map(|dep| dep.scope.parse().unwrap_or_default())
+            .collect();
+        let (jars, runtime) = split_classpaths(
+            scopes
+                .iter()
+                .zip(&dependencies)
+                .map(|(scope, dep)| (scope, dep.path.clone())),
+        );
+        let mut compile: Vec<PathBuf> = platform.iter().cloned().collect();
+        compile.extend(jars);
+        Classpath {
+            platform,
+            compile,
+            runtime,
+            dependencies,
+        }
+    }
+}
+
+fn classpath_entry(dep: &ProjectDep, path: PathBuf) -> ClasspathEntry {
+    ClasspathEntry {
+        group_id: dep.group_id.clone(),
+        artifact_id: dep.artifact_id.clone(),
+        version: dep.version.clone(),
+        scope: dep.scope.to_string(),
+        packaging: dep.packaging.clone(),
+        path,
+    }
+}
+
+impl Submodule for ExportClasspath {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let root = get_project_root().context("Failed to get the project root")?;
+        let lock =
+            load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+
+        let libs_dir = extracted_libs_dir(&root);
+        let mut entries = Vec::with_capacity(lock.resolved.len());
+        for dep in &lock.resolved {
+            if let Some(path) = dependency_jar(dep, &libs_dir)? {
+                entries.push(classpath_entry(dep, path));
+            }
+        }
+
+        let platform = android_jar(self.args.platform.as_deref())?;
+        if platform.is_none() {
+            warn!(target: "classpath", "No android platform found in the sdk. android.jar is not on the classpath.");
+        }
+        let classpath = Classpath::new(platform, entries);
+
+        if self.args.json || is_json_output() {
+            let json = serde_json::to_string_pretty(&classpath)
+                .context("Failed to serialize classpath to json")?;
+            println!("{json}");
+            return Ok(());
+        }
+
+        let paths = if self.args.runtime {
+            &classpath.runtime
+        } else {
+            &classpath.compile
+        };
+        let joined = std::env::join_paths(paths).context("Invalid path on the classpath")?;
+        println!("{}", joined.to_string_lossy());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn classpath_splits_compile_and_runtime() {
+    let entry = |artifact_id: &str, scope: &str| ClasspathEntry {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: String::from("1.0"),
+        scope: scope.to_string(),
+        packaging: String::from("jar"),
+        path: PathBuf::from(format!("{artifact_id}.jar")),
+    };
+    let classpath = Classpath::new(
+        Some(PathBuf::from("android.jar")),
+        vec![
+            entry("lib", "compile"),
+            entry("annotations", "provided"),
+            entry("driver", "runtime"),
+        ],
+    );
+    assert_eq!(
+        classpath.compile,
+        vec![
+            PathBuf::from("android.jar"),
+            PathBuf::from("lib.jar"),
+            PathBuf::from("annotations.jar")
+        ]
+    );
+    assert_eq!(
+        classpath.runtime,
+        vec![PathBuf::from("lib.jar"), PathBuf::from("driver.jar")]
+    );
+}
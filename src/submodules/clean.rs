@@ -0,0 +1,223 @@
+use std::{
+    collections::BTreeSet,
+    fs::{remove_dir_all, remove_file},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Args;
+use log::{info, warn};
+
+use crate::{
+    caching::Cache,
+    config::{
+        dev_overrides::{DEV_LOCK_FILE, DEV_OVERRIDES_FILE},
+        get_config,
+        lock::{load_labt_lock, lock_file_in, strings::LOCK_FILE},
+        LABT_TOML_FILE_NAME,
+    },
+    get_project_root,
+    plugin::{config::PluginToml, load_plugins_from_paths},
+};
+
+use super::{build::project_plugin_paths, resolvers::path::PATH_REPO_STR, Submodule};
+
+/// The build outputs directory of a project
+pub const BUILD_DIR: &str = "build";
+/// Project files that are never deleted even if an output pattern matches them
+const PROJECT_FILES: [&str; 4] = [
+    LABT_TOML_FILE_NAME,
+    LOCK_FILE,
+    DEV_OVERRIDES_FILE,
+    DEV_LOCK_FILE,
+];
+
+#[derive(Clone, Args)]
+pub struct CleanArgs {
+    /// Also delete the artifacts of the path dependencies that were copied into the labt cache
+    #[arg(long)]
+    cache: bool,
+    /// List what would be deleted without deleting it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub struct Clean {
+    args: CleanArgs,
+}
+
+impl Clean {
+    pub fn new(args: &CleanArgs) -> Self {
+        Clean { args: args.clone() }
+    }
+}
+
+/// Returns the paths clean removes from the project at root: the build directory
+/// and the existing matches of the plugin output patterns. Outputs outside the
+/// project, the project root and its Labt.toml and lock files, and the paths
+/// inside another removed directory are left out.
+///
+/// # Errors
+///
+/// Returns an error if an output pattern is not a valid glob
+pub fn clean_targets(root: &Path, outputs: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut targets = BTreeSet::new();
+    let build = root.join(BUILD_DIR);
+    if build.exists() {
+        targets.insert(build);
+    }
+    for pattern in outputs {
+        // starts_with compares components so root/../other would pass
+        if Path::new(pattern)
+            .components()
+            .any(|component| component == Component::ParentDir)
+        {
+            continue;
+        }
+        let pattern = root.join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())
+            .context(format!("Invalid output pattern {:?}", pattern))?;
+        targets.extend(
+            paths
+                .flatten()
+                .filter(|path| path.starts_with(root) && path != root)
+                .filter(|path| !PROJECT_FILES.iter().any(|file| *path == root.join(file))),
+        );
+    }
+    // the set is sorted so a directory comes before the paths inside it
+    let mut kept: Vec<PathBuf> = Vec::new();
+    for target in targets {
+        if !kept.iter().any(|dir| target.starts_with(dir)) {
+            kept.push(target);
+        }
+    }
+    Ok(kept)
+}
+
+/// The output patterns of the stages of plugins
+pub fn plugin_outputs(plugins: &[PluginToml]) -> Vec<String> {
+    let mut outputs: Vec<String> = plugins
+        .iter()
+        .flat_map(|plugin| plugin.stages.values())
+        .flat_map(|stage| stage.outputs.iter().flatten())
+        .cloned()
+        .collect();
+    outputs.sort();
+    outputs.dedup();
+    outputs
+}
+
+/// The cache directories of the path dependencies in the project lock file
+fn path_dependency_caches(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    if !lock_file_in(root).exists() {
+        return Ok(Vec::new());
+    }
+    let lock = load_labt_lock().context("Failed to load lock file")?;
+    let mut dirs = Vec::new();
+    for dep in lock
+        .resolved
+        .iter()
+        .chain(&lock.test)
+        .chain(&lock.processors)
+    {
+        if dep.resolver.as_deref() != Some(PATH_REPO_STR) {
+            continue;
+        }
+        let mut cache = Cache::from(dep);
+        cache.use_labt_home()?;
+        if let Some(dir) = cache.get_path()?.parent() {
+            if dir.exists() {
+                dirs.push(dir.to_path_buf());
+            }
+        }
+    }
+    dirs.sort();
+    dirs.dedup();
+    Ok(dirs)
+}
+
+fn remove(path: &Path) -> anyhow::Result<()> {
+    if path.is_dir() {
+        remove_dir_all(path).context(format!("Failed to delete {:?}", path))
+    } else {
+        remove_file(path).context(format!("Failed to delete {:?}", path))
+    }
+}
+
+impl Submodule for Clean {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let root = get_project_root().context("Failed to get the project root")?;
+        let config = get_config()?;
+
+        // plugins that are not installed have nothing to clean up
+        let mut plugins = Vec::new();
+        for path in project_plugin_paths(&config)? {
+            if !path.exists() {
+                continue;
+            }
+            match load_plugins_from_paths(vec![path.clone()]) {
+                Ok(loaded) => plugins.extend(loaded),
+                Err(err) => {
+                    warn!(target: "clean", "Skipping the outputs of the plugin at {:?}: {:?}", path, err)
+                }
+            }
+        }
+
+        let mut targets = clean_targets(&root, &plugin_outputs(&plugins))?;
+        if self.args.cache {
+            targets.extend(path_dependency_caches(&root)?);
+        }
+        if targets.is_empty() {
+            info!(target: "clean", "Nothing to clean");
+            return Ok(());
+        }
+        for target in &targets {
+            crate::interrupt::check()?;
+            let name = target.strip_prefix(&root).unwrap_or(target);
+            info!(target: "clean", "Deleting {}", name.display());
+            if !self.args.dry_run {
+                remove(target)?;
+            }
+        }
+        if !self.args.dry_run {
+            info!(target: "clean", "Deleted {} paths", targets.len());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn clean_build_dir_and_plugin_outputs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("clean");
+    for file in [
+        "build/res.apk",
+        "app/gen/R.java",
+        "app/src/Main.java",
+        "out.dex",
+        "Labt.toml",
+    ] {
+        let path = root.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    let outputs = vec![
+        String::from("build/res.apk"),
+        String::from("app/gen"),
+        String::from("*.dex"),
+        String::from("missing/*"),
+        String::from("../*"),
+        String::from("Labt.*"),
+    ];
+    assert_eq!(
+        clean_targets(&root, &outputs).unwrap(),
+        vec![
+            root.join("app/gen"),
+            root.join("build"),
+            root.join("out.dex")
+        ]
+    );
+}
@@ -0,0 +1,491 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fs::File,
+    io::{Cursor, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Subcommand};
+use log::{info, warn};
+use regex::Regex;
+use serde::Serialize;
+use zip::ZipArchive;
+
+use crate::{
+    caching::Cache,
+    config::{get_config, lock::load_labt_lock},
+    get_project_root,
+    output::{is_json_output, print_json},
+};
+
+use super::{
+    bytecode::{read_entry, ClassFile, Constant},
+    resolve::{get_config_dependencies, ProjectDep},
+    Submodule,
+};
+
+/// The most referenced classes listed for each used but undeclared dependency
+const MAX_LISTED_CLASSES: usize = 5;
+
+#[derive(Clone, Args)]
+pub struct DepsArgs {
+    #[command(subcommand)]
+    command: DepsSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum DepsSubcommands {
+    /// Reports declared dependencies the project does not use and transitive
+    /// dependencies it uses without declaring them
+    Analyze(AnalyzeArgs),
+}
+
+#[derive(Clone, Args)]
+pub struct AnalyzeArgs {
+    /// Glob patterns relative to the project root of the compiled classes to scan
+    #[arg(long, default_values = ["build/**/*.class"])]
+    classes: Vec<String>,
+    /// Glob patterns relative to the project root of the sources whose imports are
+    /// scanned if there are no compiled classes
+    #[arg(long, default_values = ["app/**/*.java", "app/**/*.kt"])]
+    sources: Vec<String>,
+    /// Scan the source imports even if there are compiled classes
+    #[arg(long)]
+    imports: bool,
+    /// Fail if there are unused or undeclared dependencies e.g. on CI
+    #[arg(long)]
+    strict: bool,
+}
+
+pub struct Deps {
+    args: DepsArgs,
+}
+
+impl Deps {
+    pub fn new(args: &DepsArgs) -> Self {
+        Deps { args: args.clone() }
+    }
+}
+
+/// The classes of a resolved dependency as dotted names with nested classes
+/// separated by dots too e.g. `com.example.Outer.Inner`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyClasses {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    /// Declared in Labt.toml
+    pub declared: bool,
+    pub classes: BTreeSet<String>,
+}
+
+impl DependencyClasses {
+    fn coordinate(&self) -> String {
+        format!("{}:{}:{}", self.group_id, self.artifact_id, self.version)
+    }
+}
+
+/// A transitive dependency the project uses directly
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct UndeclaredDependency {
+    pub dependency: String,
+    /// Some of the classes the project uses from it
+    pub classes: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyReport {
+    /// Declared dependencies none of whose classes are used
+    pub unused: Vec<String>,
+    /// Transitive dependencies whose classes are used
+    pub undeclared: Vec<UndeclaredDependency>,
+}
+
+impl DependencyReport {
+    pub fn is_empty(&self) -> bool {
+        self.unused.is_empty() && self.undeclared.is_empty()
+    }
+}
+
+/// Converts a class file name e.g. `com/example/Outer$Inner` to `com.example.Outer.Inner`
+fn dotted(name: &str) -> String {
+    name.replace(['/', '$'], ".")
+}
+
+/// Reads the constant pool of a class file and returns the classes it references,
+/// both as class constants and in the type descriptors and signatures of its
+/// fields, methods and annotations. String literals are left out.
+///
+/// # Errors
+///
+/// Returns an error if bytes is not a class file or the constant pool is truncated
+pub fn class_references(bytes: &[u8]) -> anyhow::Result<BTreeSet<String>> {
+    let descriptor = Regex::new(r"L([A-Za-z_$][\w$]*(?:/[\w$]+)+)[;<]")
+        .expect("descriptor pattern is a valid regex");
+    let class = ClassFile::parse(bytes)?;
+    let literals: HashSet<u16> = class
+        .pool
+        .iter()
+        .filter_map(|constant| match constant {
+            Constant::String(index) => Some(*index),
+            _ => None,
+        })
+        .collect();
+
+    let mut references = BTreeSet::new();
+    for (index, constant) in class.pool.iter().enumerate() {
+        match constant {
+            Constant::Class(_) => {
+                if let Some(name) = class.class_name(index as u16) {
+                    if !name.starts_with('[') {
+                        references.insert(dotted(name));
+                    }
+                }
+            }
+            Constant::Utf8(value) if !literals.contains(&(index as u16)) => {
+                for caps in descriptor.captures_iter(value) {
+                    references.insert(dotted(&caps[1]));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(references)
+}
+
+/// Returns the imports of a java or kotlin source as dotted names. Wildcard
+/// imports keep the trailing `*` and static imports keep the member name
+pub fn source_imports(source: &str) -> BTreeSet<String> {
+    let import = Regex::new(r"(?m)^\s*import\s+(?:static\s+)?([\w.]*\w(?:\.\*)?)")
+        .expect("import pattern is a valid regex");
+    import
+        .captures_iter(source)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+/// Adds the classes in a jar or AAR to classes. The jars an AAR bundles,
+/// classes.jar and libs/*.jar, are read too
+fn archive_classes<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    classes: &mut BTreeSet<String>,
+) -> anyhow::Result<()> {
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+        if let Some(class) = name.strip_suffix(".class") {
+            if !name.starts_with("META-INF/") && !class.ends_with("module-info") {
+                classes.insert(dotted(class));
+            }
+        } else if name.ends_with(".jar") {
+            let jar = read_entry(entry)?;
+            let mut nested = ZipArchive::new(Cursor::new(jar))
+                .context(format!("{} is not a valid jar", name))?;
+            archive_classes(&mut nested, classes)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the classes in the jar or AAR at path
+///
+/// # Errors
+///
+/// Returns an error if the file is not a valid archive
+pub fn artifact_classes(path: &Path) -> anyhow::Result<BTreeSet<String>> {
+    let file = File::open(path).context(format!("Failed to open {:?}", path))?;
+    let mut archive =
+        ZipArchive::new(file).context(format!("{:?} is not a valid archive", path))?;
+    let mut classes = BTreeSet::new();
+    archive_classes(&mut archive, &mut classes).context(format!("Failed to scan {:?}", path))?;
+    Ok(classes)
+}
+
+/// Finds the dependency providing a reference. Class references match a class
+/// exactly, imports of members or nested classes match their enclosing class and
+/// wildcard imports match any class in the package.
+fn providers(dependencies: &[DependencyClasses], reference: &str) -> Vec<usize> {
+    if let Some(package) = reference.strip_suffix('*') {
+        return dependencies
+            .iter()
+            .enumerate()
+            .filter(|(_, dep)| {
+                dep.classes
+                    .range(package.to_string()..)
+                    .next()
+                    .is_some_and(|class| class.starts_with(package))
+            })
+            .map(|(i, _)| i)
+            .collect();
+    }
+    let mut name = reference;
+    loop {
+        let found: Vec<usize> = dependencies
+            .iter()
+            .enumerate()
+            .filter(|(_, dep)| dep.classes.contains(name))
+            .map(|(i, _)| i)
+            .collect();
+        if !found.is_empty() {
+            return found;
+        }
+        match name.rsplit_once('.') {
+            // a single segment is a package, not a class
+            Some((outer, _)) if outer.contains('.') => name = outer,
+            _ => return Vec::new(),
+        }
+    }
+}
+
+/// Compares the classes the project references with the classes of its
+/// dependencies. A declared dependency is unused if none of its classes are
+/// referenced. Declared dependencies without classes, e.g. boms, are left out.
+pub fn analyze_dependencies(
+    dependencies: &[DependencyClasses],
+    references: &BTreeSet<String>,
+) -> DependencyReport {
+    let mut used: Vec<BTreeSet<String>> = vec![BTreeSet::new(); dependencies.len()];
+    for reference in references {
+        for i in providers(dependencies, reference) {
+            used[i].insert(reference.clone());
+        }
+    }
+    let mut report = DependencyReport::default();
+    for (dep, used) in dependencies.iter().zip(used) {
+        if dep.declared {
+            if used.is_empty() && !dep.classes.is_empty() {
+                report.unused.push(dep.coordinate());
+            }
+        } else if !used.is_empty() {
+            report.undeclared.push(UndeclaredDependency {
+                dependency: dep.coordinate(),
+                classes: used.into_iter().take(MAX_LISTED_CLASSES).collect(),
+            });
+        }
+    }
+    report.unused.sort();
+    report
+        .undeclared
+        .sort_by(|a, b| a.dependency.cmp(&b.dependency));
+    report
+}
+
+/// The files matching the glob patterns relative to root
+fn matching_files(root: &Path, patterns: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = BTreeSet::new();
+    for pattern in patterns {
+        let pattern = root.join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())
+            .context(format!("Invalid pattern {:?}", pattern))?;
+        files.extend(paths.flatten().filter(|path| path.is_file()));
+    }
+    Ok(files.into_iter().collect())
+}
+
+/// Reads the classes of the resolved project dependencies from the labt cache.
+/// Dependencies that have not been downloaded are skipped with a warning
+fn dependency_classes(
+    resolved: &[ProjectDep],
+    declared: &HashSet<(String, String)>,
+) -> anyhow::Result<Vec<DependencyClasses>> {
+    let mut dependencies = Vec::with_capacity(resolved.len());
+    for dep in resolved {
+        crate::interrupt::check()?;
+        let key = (dep.group_id.clone(), dep.artifact_id.clone());
+        // runtime dependencies are not meant to be referenced
+        let declared = declared.contains(&key) && dep.scope.on_compile_classpath();
+        let classes = if dep.packaging == "jar" || dep.packaging == "aar" {
+            let mut cache = Cache::from(dep);
+            cache.use_labt_home()?;
+            let path = cache.get_path()?;
+            if !path.exists() {
+                warn!(target: "deps", "{}:{}:{} has not been downloaded. Run labt resolve", dep.group_id, dep.artifact_id, dep.version);
+                continue;
+            }
+            artifact_classes(&path)?
+        } else {
+            BTreeSet::new()
+        };
+        dependencies.push(DependencyClasses {
+            group_id: dep.group_id.clone(),
+            artifact_id: dep.artifact_id.clone(),
+            version: dep.version.clone(),
+            declared,
+            classes,
+        });
+    }
+    Ok(dependencies)
+}
+
+fn analyze(args: &AnalyzeArgs) -> anyhow::Result<()> {
+    let root = get_project_root().context("Failed to get the project root")?;
+    let config = get_config()?;
+    let lock = load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+    let declared: HashSet<(String, String)> = get_config_dependencies(&config)?
+        .iter()
+        .map(|dep| (dep.get_group_id(), dep.get_artifact_id()))
+        .collect();
+
+    let mut references = BTreeSet::new();
+    let class_files = if args.imports {
+        Vec::new()
+    } else {
+        matching_files(&root, &args.classes)?
+    };
+    if class_files.is_empty() {
+        let sources = matching_files(&root, &args.sources)?;
+        if sources.is_empty() {
+            bail!("There are no compiled classes or sources to analyze. Run labt build or pass --classes or --sources");
+        }
+        info!(target: "deps", "Scanning the imports of {} sources", sources.len());
+        for source in sources {
+            let text =
+                std::fs::read_to_string(&source).context(format!("Failed to read {:?}", source))?;
+            references.extend(source_imports(&text));
+        }
+    } else {
+        info!(target: "deps", "Scanning {} class files", class_files.len());
+        for class in class_files {
+            let bytes = std::fs::read(&class).context(format!("Failed to read {:?}", class))?;
+            references
+                .extend(class_references(&bytes).context(format!("Failed to read {:?}", class))?);
+        }
+    }
+
+    let dependencies = dependency_classes(&lock.resolved, &declared)?;
+    let report = analyze_dependencies(&dependencies, &references);
+    if is_json_output() {
+        print_json(&report)?;
+    } else if report.is_empty() {
+        println!("All declared dependencies are used and no undeclared dependencies are used");
+    } else {
+        if !report.unused.is_empty() {
+            println!("Unused declared dependencies:");
+            for dep in &report.unused {
+                println!("  {dep}");
+            }
+        }
+        if !report.undeclared.is_empty() {
+            println!("Used undeclared dependencies:");
+            for dep in &report.undeclared {
+                println!("  {} ({})", dep.dependency, dep.classes.join(", "));
+            }
+        }
+    }
+    if args.strict && !report.is_empty() {
+        bail!(
+            "{} unused and {} undeclared dependencies",
+            report.unused.len(),
+            report.undeclared.len()
+        );
+    }
+    Ok(())
+}
+
+impl Submodule for Deps {
+    fn run(&mut self) -> anyhow::Result<()> {
+        match &self.args.command {
+            DepsSubcommands::Analyze(args) => analyze(args),
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn deps_analyze_reports_unused_and_undeclared() {
+    // a class file referencing com/example/http/Client and, in a method
+    // descriptor, com/example/json/Json$Reader. The string literal is ignored
+    let mut pool: Vec<Vec<u8>> = Vec::new();
+    let utf8 = |text: &str| {
+        let mut entry = vec![1];
+        entry.extend((text.len() as u16).to_be_bytes());
+        entry.extend(text.as_bytes());
+        entry
+    };
+    pool.push(utf8("com/example/app/Main")); // 1
+    pool.push(vec![7, 0, 1]); // 2
+    pool.push(utf8("com/example/http/Client")); // 3
+    pool.push(vec![7, 0, 3]); // 4
+    pool.push(utf8("(Lcom/example/json/Json$Reader;)V")); // 5
+    pool.push(utf8("Lcom/example/unused/Literal;")); // 6
+    pool.push(vec![8, 0, 6]); // 7
+    pool.push(vec![5, 0, 0, 0, 0, 0, 0, 0, 1]); // 8 and 9
+    pool.push(vec![7, 0, 1]); // 10
+    let mut class = super::bytecode::CLASS_MAGIC.to_vec();
+    class.extend([0, 0, 0, 61]);
+    class.extend(11u16.to_be_bytes());
+    for entry in pool {
+        class.extend(entry);
+    }
+    class.extend([0, 0x21, 0, 2]);
+    let references = class_references(&class).unwrap();
+    assert_eq!(
+        references,
+        BTreeSet::from([
+            String::from("com.example.app.Main"),
+            String::from("com.example.http.Client"),
+            String::from("com.example.json.Json.Reader"),
+        ])
+    );
+    assert!(class_references(b"not a class").is_err());
+
+    let imports = source_imports(
+        "package com.example.app;\n\nimport com.example.http.Client;\nimport static com.example.json.Json.parse;\nimport com.example.logging.*\nimport com.example.kotlin.Ext as E\n",
+    );
+    assert_eq!(
+        imports,
+        BTreeSet::from([
+            String::from("com.example.http.Client"),
+            String::from("com.example.json.Json.parse"),
+            String::from("com.example.kotlin.Ext"),
+            String::from("com.example.logging.*"),
+        ])
+    );
+
+    let dep = |artifact_id: &str, declared: bool, classes: &[&str]| DependencyClasses {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: String::from("1.0"),
+        declared,
+        classes: classes.iter().map(|class| class.to_string()).collect(),
+    };
+    let dependencies = vec![
+        dep("http", true, &["com.example.http.Client"]),
+        dep(
+            "json",
+            false,
+            &["com.example.json.Json", "com.example.json.Json.Reader"],
+        ),
+        dep("unused", true, &["com.example.unused.Literal"]),
+        dep("bom", true, &[]),
+        dep("logging", false, &["com.example.logging.Log"]),
+        dep("io", false, &["com.example.io.Files"]),
+    ];
+    assert_eq!(
+        analyze_dependencies(&dependencies, &references),
+        DependencyReport {
+            unused: vec![String::from("com.example:unused:1.0")],
+            undeclared: vec![UndeclaredDependency {
+                dependency: String::from("com.example:json:1.0"),
+                classes: vec![String::from("com.example.json.Json.Reader")],
+            }],
+        }
+    );
+    let report = analyze_dependencies(&dependencies, &imports);
+    assert_eq!(
+        report.undeclared,
+        vec![
+            UndeclaredDependency {
+                dependency: String::from("com.example:json:1.0"),
+                classes: vec![String::from("com.example.json.Json.parse")],
+            },
+            UndeclaredDependency {
+                dependency: String::from("com.example:logging:1.0"),
+                classes: vec![String::from("com.example.logging.*")],
+            },
+        ]
+    );
+}
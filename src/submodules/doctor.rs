@@ -0,0 +1,562 @@
+use std::{
+    fmt::Display,
+    fs::{remove_file, write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::bail;
+use clap::Args;
+use console::style;
+use serde::Serialize;
+
+use crate::{
+    config::{
+        get_config, get_resolvers_from_config,
+        global::{authenticate, client_builder, get_global_config, GLOBAL_CONFIG_FILE},
+        lock::{load_labt_lock, lock_file_in},
+        LabToml, LABT_TOML_FILE_NAME,
+    },
+    get_home, get_manifest_path, get_project_root,
+    http_recording::SendRecorded,
+    is_offline,
+    output::{is_json_output, print_json},
+    plugin::{load_plugins, load_plugins_from_paths},
+};
+
+use super::{
+    build::project_plugin_paths,
+    resolvers::get_default_resolvers,
+    sdk::{get_sdk_path, package_dir},
+    sdkmanager::{
+        installed_list::{installed_revision, InstalledList, INSTALLED_LIST, PACKAGE_XML},
+        InstalledPackage,
+    },
+    Submodule,
+};
+
+/// The directories labt creates in its home on the first run
+const HOME_DIRS: [&str; 2] = ["cache", "plugins"];
+
+#[derive(Clone, Args)]
+pub struct DoctorArgs {
+    /// Seconds to wait for each resolver to respond
+    #[arg(long, default_value_t = 10)]
+    timeout: u64,
+}
+
+pub struct Doctor {
+    args: DoctorArgs,
+}
+
+impl Doctor {
+    pub fn new(args: &DoctorArgs) -> Self {
+        Doctor { args: args.clone() }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// Works but is likely to cause problems
+    Warning,
+    Failed,
+    /// Not applicable e.g. the resolvers while offline
+    Skipped,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, "ok"),
+            CheckStatus::Warning => write!(f, "warn"),
+            CheckStatus::Failed => write!(f, "fail"),
+            CheckStatus::Skipped => write!(f, "skip"),
+        }
+    }
+}
+
+/// The result of a single check with a fix if it did not pass
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// What was checked e.g. labt home or resolver google
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What to do about a failure or warning
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+    fn skipped(name: impl Into<String>, message: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Skipped,
+            message: message.into(),
+            fix: None,
+        }
+    }
+    fn failed(name: impl Into<String>, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Failed,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+    fn warning(
+        name: impl Into<String>,
+        message: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Check {
+            name: name.into(),
+            status: CheckStatus::Warning,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Checks the labt home at home has the directories labt uses, is writable and
+/// has a valid config.toml
+pub fn check_home(home: &Path) -> Vec<Check> {
+    const NAME: &str = "labt home";
+    if !home.is_dir() {
+        return vec![Check::failed(
+            NAME,
+            format!("{:?} does not exist", home),
+            format!(
+                "Create it with `mkdir -p {}` or point LABT_HOME to an existing directory",
+                home.display()
+            ),
+        )];
+    }
+    let mut checks = Vec::new();
+    let probe = home.join(format!(".labt-doctor-{}", std::process::id()));
+    match write(&probe, "") {
+        Ok(()) => {
+            let _ = remove_file(&probe);
+            checks.push(Check::ok(NAME, format!("{:?}", home)));
+        }
+        Err(err) => checks.push(Check::failed(
+            NAME,
+            format!("{:?} is not writable: {}", home, err),
+            "Fix the permissions of labt home or point LABT_HOME to a writable directory",
+        )),
+    }
+    for dir in HOME_DIRS {
+        let path = home.join(dir);
+        if !path.is_dir() {
+            checks.push(Check::failed(
+                format!("labt home {dir}"),
+                format!("{:?} does not exist", path),
+                format!("Create it with `mkdir -p {}`", path.display()),
+            ));
+        }
+    }
+    let config = home.join(GLOBAL_CONFIG_FILE);
+    if config.exists() {
+        match get_global_config() {
+            Ok(_) => checks.push(Check::ok(GLOBAL_CONFIG_FILE, format!("{:?}", config))),
+            Err(err) => checks.push(Check::failed(
+                GLOBAL_CONFIG_FILE,
+                format!("{:#}", err),
+                format!("Fix or remove {}", config.display()),
+            )),
+        }
+    }
+    checks
+}
+
+/// Checks that Labt.toml and Labt.lock of the current project parse. Returns the
+/// config for the other checks
+fn check_project() -> (Vec<Check>, Option<LabToml>) {
+    let in_project = get_manifest_path().is_ok_and(|manifest| manifest.exists());
+    if !in_project {
+        return (
+            vec![Check::skipped(LABT_TOML_FILE_NAME, "Not in a labt project")],
+            None,
+        );
+    }
+    let mut checks = Vec::new();
+    let config = match get_config() {
+        Ok(config) => {
+            checks.push(Check::ok(LABT_TOML_FILE_NAME, config.project.name.clone()));
+            Some(config)
+        }
+        Err(err) => {
+            checks.push(Check::failed(
+                LABT_TOML_FILE_NAME,
+                format!("{:#}", err),
+                format!(
+                    "Fix the error in {}. `labt migrate` updates keys of older labt versions",
+                    LABT_TOML_FILE_NAME
+                ),
+            ));
+            None
+        }
+    };
+    if let Ok(root) = get_project_root() {
+        let lock = lock_file_in(&root);
+        if lock.exists() {
+            let name = lock
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            match load_labt_lock() {
+                Ok(_) => checks.push(Check::ok(name, format!("{:?}", lock))),
+                Err(err) => checks.push(Check::failed(
+                    name,
+                    format!("{:#}", err),
+                    "Delete the lock file and run `labt resolve` to write it again",
+                )),
+            }
+        }
+    }
+    (checks, config)
+}
+
+/// The check of a resolver that answered with status
+pub fn resolver_check(name: &str, url: &str, status: reqwest::StatusCode) -> Check {
+    let name = format!("resolver {name}");
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        Check::warning(
+            name,
+            format!("{url} responded with {status}"),
+            "Check the credentials of the repository in maven settings.xml",
+        )
+    } else if status.is_server_error() {
+        Check::warning(
+            name,
+            format!("{url} responded with {status}"),
+            "The repository is having problems, try again later or configure a mirror",
+        )
+    } else {
+        // a repository root can be a 404 and still serve artifacts
+        Check::ok(name, format!("{url} is reachable"))
+    }
+}
+
+/// Sends a request to the base url of every network resolver
+fn check_resolvers(config: Option<&LabToml>, timeout: Duration) -> Vec<Check> {
+    if is_offline() {
+        return vec![Check::skipped("resolvers", "Offline")];
+    }
+    let resolvers = match config {
+        Some(config) => get_resolvers_from_config(config),
+        None => get_default_resolvers(),
+    };
+    let resolvers = match resolvers {
+        Ok(resolvers) => resolvers,
+        Err(err) => {
+            return vec![Check::failed(
+                "resolvers",
+                format!("{:#}", err),
+                "Fix the [resolvers] of Labt.toml or the mirrors of maven settings.xml",
+            )]
+        }
+    };
+    let client = match client_builder().and_then(|builder| Ok(builder.timeout(timeout).build()?)) {
+        Ok(client) => client,
+        Err(err) => {
+            return vec![Check::failed(
+                "resolvers",
+                format!("{:#}", err),
+                format!("Fix the network settings of labt home {GLOBAL_CONFIG_FILE}"),
+            )]
+        }
+    };
+    let mut checks = Vec::new();
+    for resolver in &resolvers {
+        let Some(url) = resolver.get_base_url() else {
+            continue;
+        };
+        // private repositories answer 401 without the credentials the resolvers send
+        match authenticate(client.head(url), url).send_recorded() {
+            Ok(response) => checks.push(resolver_check(
+                resolver.get_name(),
+                url,
+                response.status(),
+            )),
+            Err(err) => checks.push(Check::failed(
+                format!("resolver {}", resolver.get_name()),
+                format!("{url} is unreachable: {err}"),
+                format!(
+                    "Check the network connection and the proxy in {GLOBAL_CONFIG_FILE}, or pass --offline to build from the cache"
+                ),
+            )),
+        }
+    }
+    checks
+}
+
+/// Checks that the packages in the installed list of the sdk are on disk with
+/// the revisions the list records
+fn check_sdk() -> Vec<Check> {
+    let sdk = match get_sdk_path() {
+        Ok(sdk) => sdk,
+        Err(err) => {
+            return vec![Check::failed(
+                "sdk",
+                format!("{:#}", err),
+                "Fix labt home so that the sdk directory can be created",
+            )]
+        }
+    };
+    let list = match InstalledList::from_file(&sdk.join(INSTALLED_LIST)) {
+        Ok(list) => list,
+        Err(err) => {
+            return vec![Check::failed(
+                "sdk",
+                format!("{:#}", err),
+                format!(
+                    "Fix {} or delete it and install the packages again",
+                    sdk.join(INSTALLED_LIST).display()
+                ),
+            )]
+        }
+    };
+    if list.packages.is_empty() {
+        return vec![Check::skipped("sdk", "No sdk packages installed")];
+    }
+    list.packages
+        .iter()
+        .map(|package| {
+            let dir = match &package.directory {
+                Some(dir) => Ok(dir.clone()),
+                None => package_dir(&package.repository_name, &package.path),
+            };
+            sdk_package_check(package, dir)
+        })
+        .collect()
+}
+
+/// Checks that an installed package is in dir with the revision the installed
+/// list records, as declared by the package.xml or source.properties in it
+pub fn sdk_package_check(package: &InstalledPackage, dir: anyhow::Result<PathBuf>) -> Check {
+    let name = format!("sdk {}:{}", package.repository_name, package.path);
+    let problem = match &dir {
+        Ok(dir) if !dir.is_dir() => format!("v{} is missing at {:?}", package.version, dir),
+        Ok(dir) => match installed_revision(dir) {
+            Ok(Some(revision)) if revision == package.version => {
+                return Check::ok(name, format!("v{}", package.version))
+            }
+            Ok(Some(revision)) => format!(
+                "{:?} has v{} instead of v{}",
+                dir, revision, package.version
+            ),
+            Ok(None) => format!(
+                "v{} at {:?} has no {} or source.properties, the install is incomplete",
+                package.version, dir, PACKAGE_XML
+            ),
+            Err(err) => format!("{:#}", err),
+        },
+        Err(err) => format!("{:#}", err),
+    };
+    Check::failed(
+        name,
+        problem,
+        format!(
+            "Reinstall it with `labt sdk install {} --path \"{}\" --version {} --channel {}`",
+            package.repository_name, package.path, package.version, package.channel
+        ),
+    )
+}
+
+/// Loads the plugins of the project and compiles the lua of each stage without running it
+fn check_plugins(config: &LabToml) -> Vec<Check> {
+    let paths = match project_plugin_paths(config) {
+        Ok(paths) => paths,
+        Err(err) => {
+            return vec![Check::failed(
+                "plugins",
+                format!("{:#}", err),
+                "Fix the [plugins] of Labt.toml",
+            )]
+        }
+    };
+    let mut checks = Vec::new();
+    for path in paths {
+        let name = format!("plugin {}", path.display());
+        if !path.exists() {
+            checks.push(Check::failed(
+                name,
+                format!("{:?} does not exist", path),
+                "Install the plugins of the project with `labt plugin fetch`",
+            ));
+            continue;
+        }
+        let plugins = match load_plugins_from_paths(vec![path.clone()]).and_then(load_plugins) {
+            Ok(plugins) => plugins,
+            Err(err) => {
+                checks.push(Check::failed(
+                    name,
+                    format!("{:#}", err),
+                    "Fix plugin.toml of the plugin or install it again",
+                ));
+                continue;
+            }
+        };
+        let mut stages: Vec<_> = plugins.values().flatten().collect();
+        stages.sort_by_key(|plugin| plugin.step);
+        for plugin in stages {
+            let name = format!("plugin {}:{} {}", plugin.name, plugin.version, plugin.step);
+            let compiled = plugin.load().and_then(|exe| {
+                exe.load()?.into_function()?;
+                Ok(())
+            });
+            checks.push(match compiled {
+                Ok(()) => Check::ok(name, format!("{:?}", plugin.path)),
+                Err(err) => Check::failed(
+                    name,
+                    format!("{:#}", err),
+                    format!("Fix the lua code at {}", plugin.path.display()),
+                ),
+            });
+        }
+    }
+    if checks.is_empty() {
+        checks.push(Check::skipped("plugins", "The project uses no plugins"));
+    }
+    checks
+}
+
+fn print_check(check: &Check) {
+    let status = match check.status {
+        CheckStatus::Ok => style(check.status.to_string()).green(),
+        CheckStatus::Warning => style(check.status.to_string()).yellow(),
+        CheckStatus::Failed => style(check.status.to_string()).red(),
+        CheckStatus::Skipped => style(check.status.to_string()).dim(),
+    };
+    println!(
+        "[{:>4}] {}: {}",
+        status,
+        style(&check.name).bold(),
+        check.message
+    );
+    if let Some(fix) = &check.fix {
+        println!("       fix: {}", fix);
+    }
+}
+
+impl Submodule for Doctor {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let home = get_home();
+        let mut checks = match &home {
+            Ok(home) => check_home(home),
+            Err(err) => vec![Check::failed(
+                "labt home",
+                format!("{:#}", err),
+                "Set LABT_HOME to a writable directory or create .labt in your home directory",
+            )],
+        };
+        let home_exists = home.is_ok_and(|home| home.is_dir());
+        let (project, config) = check_project();
+        checks.extend(project);
+        checks.extend(check_resolvers(
+            config.as_ref(),
+            Duration::from_secs(self.args.timeout),
+        ));
+        // the sdk and installed plugins live in labt home
+        if home_exists {
+            checks.extend(check_sdk());
+            if let Some(config) = &config {
+                checks.extend(check_plugins(config));
+            }
+        }
+
+        if is_json_output() {
+            print_json(&checks)?;
+        } else {
+            checks.iter().for_each(print_check);
+        }
+        let failed = checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Failed)
+            .count();
+        if failed > 0 {
+            bail!("{failed} checks failed");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn doctor_checks_home_and_resolver_responses() {
+    let tmp = tempfile::tempdir().unwrap();
+    let home = tmp.path().join("doctor");
+    let checks = check_home(&home);
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].status, CheckStatus::Failed);
+
+    std::fs::create_dir_all(home.join("cache")).unwrap();
+    let checks = check_home(&home);
+    assert_eq!(
+        checks
+            .iter()
+            .map(|check| (check.name.as_str(), check.status))
+            .collect::<Vec<_>>(),
+        vec![
+            ("labt home", CheckStatus::Ok),
+            ("labt home plugins", CheckStatus::Failed)
+        ]
+    );
+    assert_eq!(
+        checks[1].fix,
+        Some(format!(
+            "Create it with `mkdir -p {}`",
+            home.join("plugins").display()
+        ))
+    );
+    // the write probe is cleaned up
+    assert_eq!(std::fs::read_dir(&home).unwrap().count(), 1);
+
+    let url = "https://repo.example.com/maven2/";
+    let check = resolver_check("example", url, reqwest::StatusCode::NOT_FOUND);
+    assert_eq!(check.status, CheckStatus::Ok);
+    assert_eq!(check.fix, None);
+    let check = resolver_check("example", url, reqwest::StatusCode::UNAUTHORIZED);
+    assert_eq!(check.status, CheckStatus::Warning);
+    assert_eq!(check.name, "resolver example");
+    let check = resolver_check("example", url, reqwest::StatusCode::BAD_GATEWAY);
+    assert_eq!(check.status, CheckStatus::Warning);
+
+    // installed packages must declare the revision the installed list records
+    let package = InstalledPackage::new(
+        String::from("platforms;android-34"),
+        "3.0.0".parse().unwrap(),
+        Default::default(),
+        String::from("google"),
+    );
+    let dir = tmp.path().join("android-34");
+    assert_eq!(
+        sdk_package_check(&package, Ok(dir.clone())).status,
+        CheckStatus::Failed
+    );
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("android.jar"), "").unwrap();
+    assert_eq!(
+        sdk_package_check(&package, Ok(dir.clone())).status,
+        CheckStatus::Failed
+    );
+    std::fs::write(dir.join("source.properties"), "Pkg.Revision=2\n").unwrap();
+    let check = sdk_package_check(&package, Ok(dir.clone()));
+    assert_eq!(check.status, CheckStatus::Failed);
+    assert!(check.message.contains("v2.0.0.0 instead of v3.0.0.0"));
+    std::fs::write(dir.join("source.properties"), "Pkg.Revision=3\n").unwrap();
+    assert_eq!(sdk_package_check(&package, Ok(dir)).status, CheckStatus::Ok);
+}
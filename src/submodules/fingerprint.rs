@@ -0,0 +1,205 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Context;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::caching::file_sha256;
+
+/// The directory in the project where labt keeps its build state
+pub const PROJECT_STATE_DIR: &str = ".labt";
+/// The input fingerprints of the plugin stages that detect changes by hash
+pub const FINGERPRINTS_FILE: &str = "fingerprints.json";
+
+/// The content hash of an input file. The size and modification time are kept
+/// so that files that were not touched are not hashed again
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileFingerprint {
+    pub size: u64,
+    /// Nanoseconds since the unix epoch
+    pub modified: u128,
+    pub sha256: String,
+}
+
+/// The input fingerprints of each plugin stage at its last successful run,
+/// persisted in `.labt/fingerprints.json`
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct FingerprintDb {
+    /// Keyed by [`stage_key`], then by the input path relative to the project
+    pub stages: BTreeMap<String, BTreeMap<PathBuf, FileFingerprint>>,
+}
+
+/// The key of a plugin stage in the database, `<step>:<plugin name>:<plugin
+/// version>:<code digest>`. An upgraded or edited plugin gets a new key so that
+/// the stage runs again with unchanged inputs
+pub fn stage_key(step: impl std::fmt::Display, plugin: &str, version: &str, code: &str) -> String {
+    format!("{step}:{plugin}:{version}:{code}")
+}
+
+/// The `<step>:<plugin name>` of a [`stage_key`]
+fn stage_of(key: &str) -> &str {
+    key.rsplitn(3, ':').nth(2).unwrap_or(key)
+}
+
+impl FingerprintDb {
+    fn path(root: &Path) -> PathBuf {
+        root.join(PROJECT_STATE_DIR).join(FINGERPRINTS_FILE)
+    }
+    /// Loads the database of the project at root. A missing or unreadable
+    /// database is empty, so every stage runs once to fill it again
+    pub fn load(root: &Path) -> Self {
+        let path = Self::path(root);
+        if !path.exists() {
+            return FingerprintDb::default();
+        }
+        match std::fs::read_to_string(&path)
+            .context(format!("Failed to read {:?}", path))
+            .and_then(|json| {
+                serde_json::from_str(&json).context(format!("Failed to parse {:?}", path))
+            }) {
+            Ok(db) => db,
+            Err(err) => {
+                warn!(target: "build", "Ignoring the input fingerprints: {:?}", err);
+                FingerprintDb::default()
+            }
+        }
+    }
+    /// Writes the database to the `.labt` directory of the project at root
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory or file cannot be written
+    pub fn save(&self, root: &Path) -> anyhow::Result<()> {
+        let path = Self::path(root);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).context(format!("Failed to create {:?}", dir))?;
+        }
+        let json =
+            serde_json::to_string_pretty(self).context("Failed to serialize the fingerprints")?;
+        std::fs::write(&path, json).context(format!("Failed to write {:?}", path))
+    }
+    /// Fingerprints the input files of a stage. Files whose size and modification
+    /// time match the last run keep their recorded hash. Directories and inputs
+    /// that were deleted since their globs were expanded are skipped
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if an input cannot be read
+    pub fn fingerprint(
+        &self,
+        key: &str,
+        root: &Path,
+        inputs: &[PathBuf],
+    ) -> anyhow::Result<BTreeMap<PathBuf, FileFingerprint>> {
+        let recorded = self.stages.get(key);
+        let mut fingerprints = BTreeMap::new();
+        for input in inputs {
+            let Ok(meta) = std::fs::metadata(input) else {
+                continue;
+            };
+            if !meta.is_file() {
+                continue;
+            }
+            let size = meta.len();
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|time| time.as_nanos())
+                .unwrap_or_default();
+            let name = input.strip_prefix(root).unwrap_or(input).to_path_buf();
+            let unchanged = recorded
+                .and_then(|recorded| recorded.get(&name))
+                .filter(|old| old.size == size && old.modified == modified);
+            let sha256 = match unchanged {
+                Some(old) => old.sha256.clone(),
+                None => file_sha256(input).context(format!("Failed to hash {:?}", input))?,
+            };
+            fingerprints.insert(
+                name,
+                FileFingerprint {
+                    size,
+                    modified,
+                    sha256,
+                },
+            );
+        }
+        Ok(fingerprints)
+    }
+    /// True if the inputs have the same files and contents as at the last
+    /// successful run of the stage
+    pub fn is_unchanged(&self, key: &str, current: &BTreeMap<PathBuf, FileFingerprint>) -> bool {
+        self.stages.get(key).is_some_and(|recorded| {
+            recorded.len() == current.len()
+                && recorded
+                    .iter()
+                    .zip(current)
+                    .all(|((a, old), (b, new))| a == b && old.sha256 == new.sha256)
+        })
+    }
+    /// Records the inputs of a stage that ran successfully, replacing those of
+    /// the other versions of the stage
+    pub fn record(&mut self, key: String, current: BTreeMap<PathBuf, FileFingerprint>) {
+        let stage = stage_of(&key);
+        self.stages.retain(|other, _| stage_of(other) != stage);
+        self.stages.insert(key, current);
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn fingerprints_ignore_touched_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("fingerprint");
+    std::fs::create_dir_all(root.join("res")).unwrap();
+    let layout = root.join("res/layout.xml");
+    let strings = root.join("res/strings.xml");
+    std::fs::write(&layout, "<LinearLayout/>").unwrap();
+    std::fs::write(&strings, "<resources/>").unwrap();
+    let inputs = vec![layout.clone(), strings.clone(), root.join("res")];
+    let key = stage_key("aapt", "labt-java", "0.1.0", "c0de");
+
+    let mut db = FingerprintDb::load(&root);
+    let first = db.fingerprint(&key, &root, &inputs).unwrap();
+    assert_eq!(first.len(), 2);
+    assert!(first.contains_key(Path::new("res/layout.xml")));
+    assert!(!db.is_unchanged(&key, &first));
+    db.record(key.clone(), first);
+    db.save(&root).unwrap();
+
+    // a touched file with the same contents is hashed again and still matches
+    let db = FingerprintDb::load(&root);
+    let file = std::fs::File::options().write(true).open(&layout).unwrap();
+    file.set_modified(UNIX_EPOCH + std::time::Duration::from_secs(86400))
+        .unwrap();
+    let touched = db.fingerprint(&key, &root, &inputs).unwrap();
+    assert_eq!(
+        touched[Path::new("res/layout.xml")].modified,
+        86400 * 1_000_000_000
+    );
+    assert!(db.is_unchanged(&key, &touched));
+
+    std::fs::write(&strings, "<resources><string/></resources>").unwrap();
+    let edited = db.fingerprint(&key, &root, &inputs).unwrap();
+    assert!(!db.is_unchanged(&key, &edited));
+    // a removed input is a change too
+    let removed = db.fingerprint(&key, &root, &inputs[..1]).unwrap();
+    assert!(!db.is_unchanged(&key, &removed));
+    assert!(!db.is_unchanged(&stage_key("aapt", "other", "0.1.0", "c0de"), &touched));
+    // an upgraded or edited plugin runs again
+    let upgraded = stage_key("aapt", "labt-java", "0.2.0", "c0de");
+    assert!(!db.is_unchanged(&upgraded, &touched));
+    assert!(!db.is_unchanged(&stage_key("aapt", "labt-java", "0.1.0", "edited"), &touched));
+    let mut db = db;
+    db.record(upgraded.clone(), touched.clone());
+    assert_eq!(db.stages.keys().collect::<Vec<_>>(), vec![&upgraded]);
+
+    std::fs::write(root.join(PROJECT_STATE_DIR).join(FINGERPRINTS_FILE), "{").unwrap();
+    assert_eq!(FingerprintDb::load(&root), FingerprintDb::default());
+}
@@ -0,0 +1,222 @@
+use std::fs::write;
+
+use anyhow::{bail, Context};
+use clap::Args;
+use log::info;
+use toml_edit::{Decor, Document, InlineTable, Item, Table, Value};
+
+use crate::{
+    config::{get_config_string, LABT_TOML_FILE_NAME},
+    get_manifest_path,
+};
+
+use super::Submodule;
+
+/// The order of keys inside a dependency inline table. Version comes first
+/// so that aligning the keys also aligns the versions.
+const DEPENDENCY_KEY_ORDER: [&str; 13] = [
+    "version",
+    "group_id",
+    "artifact_id",
+    "dep_type",
+    "scope",
+    "resolver",
+    "pin_tree",
+    "path",
+    "artifact",
+    "git",
+    "tag",
+    "branch",
+    "rev",
+];
+/// The order of keys inside a plugin inline table
+const PLUGIN_KEY_ORDER: [&str; 2] = ["version", "location"];
+
+#[derive(Clone, Args)]
+pub struct FmtArgs {
+    /// Only check if Labt.toml is formatted. Fails if formatting would change it
+    #[arg(long)]
+    check: bool,
+}
+
+pub struct Fmt {
+    args: FmtArgs,
+}
+
+impl Fmt {
+    pub fn new(args: &FmtArgs) -> Self {
+        Fmt { args: args.clone() }
+    }
+}
+
+impl Submodule for Fmt {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let original = get_config_string()?;
+        let formatted = format_config(&original)?;
+
+        if formatted == original {
+            info!(target: "fmt", "{} is already formatted", LABT_TOML_FILE_NAME);
+            return Ok(());
+        }
+        if self.args.check {
+            bail!(
+                "{} is not formatted. Run labt fmt to format it",
+                LABT_TOML_FILE_NAME
+            );
+        }
+
+        let path = get_manifest_path().context("Failed to get the project manifest")?;
+        write(&path, formatted).context(format!("Failed to write {:?}", path))?;
+        info!(target: "fmt", "Formatted {}", LABT_TOML_FILE_NAME);
+        Ok(())
+    }
+}
+
+/// Formats the contents of Labt.toml. Dependencies and plugins are sorted
+/// alphabetically, their inline tables normalized and their keys aligned.
+/// Comments are preserved.
+///
+/// # Errors
+///
+/// Returns an error if the toml cannot be parsed
+pub fn format_config(toml: &str) -> anyhow::Result<String> {
+    let mut doc = toml
+        .parse::<Document>()
+        .context(format!("Failed parsing {}", LABT_TOML_FILE_NAME))?;
+
+    if let Some(table) = doc.get_mut("dependencies").and_then(Item::as_table_mut) {
+        format_table(table, &DEPENDENCY_KEY_ORDER);
+    }
+    if let Some(table) = doc.get_mut("plugins").and_then(Item::as_table_mut) {
+        format_table(table, &PLUGIN_KEY_ORDER);
+    }
+
+    Ok(doc.to_string())
+}
+
+fn is_blank(decor: &Decor) -> bool {
+    let blank = |raw: Option<&toml_edit::RawString>| {
+        raw.and_then(|raw| raw.as_str())
+            .is_none_or(|s| s.trim().is_empty())
+    };
+    blank(decor.prefix()) && blank(decor.suffix())
+}
+
+/// Standard and dotted sub tables are converted to inline tables unless
+/// they contain comments, since an inline table cannot hold them.
+fn to_inline_table(table: &Table) -> Option<InlineTable> {
+    for (key, item) in table.iter() {
+        let value = item.as_value()?;
+        if !table.key_decor(key).is_none_or(is_blank) || !is_blank(value.decor()) {
+            return None;
+        }
+    }
+    Some(table.clone().into_inline_table())
+}
+
+fn key_rank(order: &[&str], key: &str) -> usize {
+    order.iter().position(|k| *k == key).unwrap_or(order.len())
+}
+
+fn format_table(table: &mut Table, order: &[&str]) {
+    let keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+
+    for key in &keys {
+        let item = match table.get_mut(key) {
+            Some(item) => item,
+            None => continue,
+        };
+        if let Some(sub_table) = item.as_table() {
+            let header = sub_table
+                .decor()
+                .prefix()
+                .filter(|header| header.as_str().is_some_and(|s| !s.trim().is_empty()))
+                .cloned();
+            if let Some(inline) = to_inline_table(sub_table) {
+                *item = Item::Value(Value::InlineTable(inline));
+                // keep the comments that were above the table header
+                if let (Some(header), Some(decor)) = (header, table.key_decor_mut(key)) {
+                    decor.set_prefix(header);
+                }
+            }
+        }
+    }
+    if table.iter().any(|(_, item)| item.is_value()) {
+        table.set_implicit(false);
+    }
+
+    for (_, item) in table.iter_mut() {
+        if let Some(inline) = item.as_inline_table_mut() {
+            inline.sort_values_by(|k1, _, k2, _| {
+                key_rank(order, k1.get())
+                    .cmp(&key_rank(order, k2.get()))
+                    .then_with(|| k1.get().cmp(k2.get()))
+            });
+            inline.fmt();
+        }
+        if let Some(value) = item.as_value_mut() {
+            value.decor_mut().set_prefix(" ");
+        }
+    }
+
+    table.sort_values();
+
+    align_keys(table);
+}
+
+/// Pads the keys of the table so that their values line up
+fn align_keys(table: &mut Table) {
+    let widths: Vec<(String, usize)> = table
+        .iter()
+        .filter(|(_, item)| item.is_value())
+        .filter_map(|(key, _)| {
+            let (k, _) = table.get_key_value(key)?;
+            Some((key.to_string(), k.display_repr().chars().count()))
+        })
+        .collect();
+    let max = widths.iter().map(|(_, width)| *width).max().unwrap_or(0);
+
+    for (key, width) in widths {
+        if let Some(decor) = table.key_decor_mut(&key) {
+            decor.set_suffix(" ".repeat(max - width + 1));
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn fmt_sorts_aligns_and_keeps_comments() {
+    let toml = r#"[project]
+name = "app"
+
+[dependencies]
+# ui components
+material={group_id="com.google.android.material",version="1.11.0"}
+appcompat = { version = "1.6.1",   group_id = "androidx.appcompat" } # needed by material
+
+[dependencies.core]
+version = "1.12.0"
+group_id = "androidx.core"
+
+[plugins]
+java = { location = "https://gitlab.com/lab-tool/plugins/labt-java", version = "v0.1.0" }
+"#;
+    let expected = r#"[project]
+name = "app"
+
+[dependencies]
+appcompat = { version = "1.6.1", group_id = "androidx.appcompat" } # needed by material
+core      = { version = "1.12.0", group_id = "androidx.core" }
+# ui components
+material  = { version = "1.11.0", group_id = "com.google.android.material" }
+
+[plugins]
+java = { version = "v0.1.0", location = "https://gitlab.com/lab-tool/plugins/labt-java" }
+"#;
+    let formatted = format_config(toml).unwrap();
+    assert_eq!(formatted, expected);
+    // formatting is stable
+    assert_eq!(format_config(&formatted).unwrap(), formatted);
+}
@@ -0,0 +1,213 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use console::style;
+use log::info;
+
+use crate::{
+    config::{get_config, LABT_TOML_FILE_NAME},
+    get_project_root, MULTI_PROGRESS_BAR,
+};
+
+use super::{resolvers::path::load_path_libraries, Submodule};
+
+#[derive(Clone, Args)]
+pub struct ForeachArgs {
+    /// Run the command with the system shell instead of as a labt subcommand
+    #[arg(long)]
+    exec: bool,
+    /// Continue with the remaining projects after one fails
+    #[arg(long)]
+    keep_going: bool,
+    /// The labt subcommand and its arguments, e.g. labt foreach -- build --release
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+pub struct Foreach {
+    args: ForeachArgs,
+}
+
+impl Foreach {
+    pub fn new(args: &ForeachArgs) -> Self {
+        Foreach { args: args.clone() }
+    }
+}
+
+/// The outcome of running the command in one project
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemberStatus {
+    Ok(Duration),
+    Failed(Duration),
+    /// Not run since an earlier project failed
+    Skipped,
+}
+
+/// The projects the command ran in, in the order they ran
+#[derive(Debug, Default)]
+pub struct ForeachReport {
+    pub members: Vec<(String, MemberStatus)>,
+}
+
+impl ForeachReport {
+    pub fn failed(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|(_, status)| matches!(status, MemberStatus::Failed(_)))
+            .count()
+    }
+}
+
+impl Display for ForeachReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .members
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        for (name, status) in &self.members {
+            match status {
+                MemberStatus::Ok(elapsed) => {
+                    writeln!(f, "  ok       {name:width$}  {:.1}s", elapsed.as_secs_f32())?
+                }
+                MemberStatus::Failed(elapsed) => {
+                    writeln!(f, "  failed   {name:width$}  {:.1}s", elapsed.as_secs_f32())?
+                }
+                MemberStatus::Skipped => writeln!(f, "  skipped  {name}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Foreach {
+    /// The command to run in a project directory
+    fn command(&self, dir: &Path) -> anyhow::Result<Command> {
+        if self.args.exec {
+            let script = self.args.command.join(" ");
+            #[cfg(not(target_os = "windows"))]
+            let mut command = Command::new("sh");
+            #[cfg(not(target_os = "windows"))]
+            command.arg("-c").arg(script);
+            #[cfg(target_os = "windows")]
+            let mut command = Command::new("cmd");
+            #[cfg(target_os = "windows")]
+            command.arg("/C").arg(script);
+            return Ok(command);
+        }
+        let exe = std::env::current_exe().context("Failed to get the labt executable path")?;
+        let mut command = Command::new(exe);
+        // selects the member even when it is nested in the project
+        command
+            .arg("--manifest-path")
+            .arg(dir.join(LABT_TOML_FILE_NAME))
+            .args(&self.args.command);
+        Ok(command)
+    }
+
+    /// Runs the command in each project in order. The projects after a failure are
+    /// skipped unless --keep-going is set
+    fn run_members(&self, members: Vec<(String, PathBuf)>) -> anyhow::Result<ForeachReport> {
+        let mut report = ForeachReport::default();
+        for (name, dir) in members {
+            crate::interrupt::check()?;
+            if report.failed() > 0 && !self.args.keep_going {
+                report.members.push((name, MemberStatus::Skipped));
+                continue;
+            }
+            info!(target: "foreach", "{} in {:?}", style(&name).bold(), dir);
+            let start = Instant::now();
+            let mut command = self.command(&dir)?;
+            let status = MULTI_PROGRESS_BAR.suspend(|| command.current_dir(&dir).status());
+            let status = status.context(format!("Failed to run the command in {:?}", dir))?;
+            let elapsed = start.elapsed();
+            let status = if status.success() {
+                MemberStatus::Ok(elapsed)
+            } else {
+                MemberStatus::Failed(elapsed)
+            };
+            report.members.push((name, status));
+        }
+        Ok(report)
+    }
+}
+
+impl Submodule for Foreach {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let root = get_project_root().context("Failed to get the project root")?;
+        let config = get_config()?;
+
+        // path dependencies are listed before their dependents, the project comes last
+        let mut members: Vec<(String, PathBuf)> = load_path_libraries(&root, &config)
+            .context("Failed to load path dependencies")?
+            .into_iter()
+            .filter(|library| library.build)
+            .map(|library| (library.artifact_id, library.root))
+            .collect();
+        members.push((config.project.name.clone(), root));
+
+        let report = self.run_members(members)?;
+        println!("\n{report}");
+        let failed = report.failed();
+        if failed > 0 {
+            bail!(
+                "{} failed in {} of {} projects",
+                self.args.command.join(" "),
+                failed,
+                report.members.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn foreach_report_lists_member_status() {
+    let report = ForeachReport {
+        members: vec![
+            (
+                String::from("core"),
+                MemberStatus::Ok(Duration::from_millis(400)),
+            ),
+            (
+                String::from("widgets"),
+                MemberStatus::Failed(Duration::from_millis(1250)),
+            ),
+            (String::from("app"), MemberStatus::Skipped),
+        ],
+    };
+    assert_eq!(report.failed(), 1);
+    assert_eq!(
+        report.to_string(),
+        "  ok       core     0.4s\n  failed   widgets  1.2s\n  skipped  app\n"
+    );
+}
+
+#[test]
+fn foreach_failing_command_marks_member_failed() {
+    let tmp = tempfile::tempdir().unwrap();
+    let foreach = Foreach::new(&ForeachArgs {
+        exec: true,
+        keep_going: false,
+        command: vec![String::from("exit 3")],
+    });
+    let report = foreach
+        .run_members(vec![
+            (String::from("core"), tmp.path().to_path_buf()),
+            (String::from("app"), tmp.path().to_path_buf()),
+        ])
+        .unwrap();
+    assert_eq!(report.failed(), 1);
+    assert!(matches!(report.members[0], (_, MemberStatus::Failed(_))));
+    assert_eq!(report.members[1].1, MemberStatus::Skipped);
+}
@@ -0,0 +1,330 @@
+use std::{
+    fs::{create_dir_all, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use log::{info, warn};
+
+use crate::{
+    config::{get_config, lock::load_labt_lock},
+    get_project_root,
+};
+
+use super::{
+    artifacts::{artifact_jars, cached_artifact, split_classpaths},
+    licenses::escape_html,
+    resolve::ProjectDep,
+    sdkmanager::installed_list::{InstalledList, InstalledPackage},
+    Submodule,
+};
+
+/// Directory under the project build folder where the ide support files are kept
+pub const IDE_BUILD_DIR: &str = "ide";
+/// The sdk package path prefix for android platforms
+const PLATFORM_PREFIX: &str = "platforms;android-";
+
+#[derive(Clone, Args)]
+pub struct IdeArgs {
+    /// The IDE project files to generate
+    #[arg(short, long, value_enum, default_value_t = IdeFormat::All)]
+    format: IdeFormat,
+    /// Java source directories relative to the project root
+    #[arg(short, long, default_values = ["app/java"])]
+    source: Vec<PathBuf>,
+    /// The android platform to compile against e.g. android-34.
+    /// Defaults to the latest installed platform
+    #[arg(short, long)]
+    platform: Option<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum IdeFormat {
+    /// Eclipse .classpath and .project used by VS Code Java (jdt.ls)
+    Vscode,
+    /// IntelliJ IDEA / Android Studio module files
+    Intellij,
+    All,
+}
+
+pub struct Ide {
+    args: IdeArgs,
+}
+
+impl Ide {
+    pub fn new(args: &IdeArgs) -> Self {
+        Ide { args: args.clone() }
+    }
+}
+
+impl Submodule for Ide {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let root = get_project_root().context("Failed to get the project root")?;
+        let config = get_config()?;
+        let lock =
+            load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+
+        // runtime only dependencies cannot be compiled against
+        let (compile, _) =
+            split_classpaths(lock.resolved.iter().map(|dep| (&dep.scope, dep.clone())));
+        let mut jars = dependency_jars(&compile, &extracted_libs_dir(&root))?;
+
+        match android_jar(self.args.platform.as_deref())? {
+            Some(jar) => jars.insert(0, jar),
+            None => {
+                warn!(target: "ide", "No android platform found in the sdk. Install one with labt sdk to get android completions.");
+            }
+        }
+
+        let classpath = IdeClasspath {
+            name: config.project.name.clone(),
+            sources: self.args.source.clone(),
+            jars,
+        };
+
+        if matches!(self.args.format, IdeFormat::Vscode | IdeFormat::All) {
+            write(root.join(".classpath"), classpath.eclipse_classpath())
+                .context("Failed to write .classpath")?;
+            let project = root.join(".project");
+            // do not overwrite a project file the user may have customised
+            if !project.exists() {
+                write(&project, classpath.eclipse_project()).context("Failed to write .project")?;
+            }
+            info!(target: "ide", "Generated .classpath for VS Code Java");
+        }
+        if matches!(self.args.format, IdeFormat::Intellij | IdeFormat::All) {
+            let iml = format!("{}.iml", classpath.name);
+            write(root.join(&iml), classpath.intellij_module())
+                .context(format!("Failed to write {iml}"))?;
+            let idea = root.join(".idea");
+            let modules = idea.join("modules.xml");
+            if !modules.exists() {
+                create_dir_all(&idea).context("Failed to create .idea directory")?;
+                write(&modules, classpath.intellij_modules())
+                    .context("Failed to write .idea/modules.xml")?;
+            }
+            info!(target: "ide", "Generated {iml} for IntelliJ IDEA and Android Studio");
+        }
+
+        Ok(())
+    }
+}
+
+/// The project classpath as seen by an IDE
+pub struct IdeClasspath {
+    pub name: String,
+    /// Source directories relative to the project root
+    pub sources: Vec<PathBuf>,
+    /// Absolute paths of the jars on the compile classpath
+    pub jars: Vec<PathBuf>,
+}
+
+fn path_string(path: &Path) -> String {
+    escape_html(&path.to_string_lossy().replace('\\', "/"))
+}
+
+impl IdeClasspath {
+    /// Eclipse classpath file read by the VS Code Java language server
+    pub fn eclipse_classpath(&self) -> String {
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<classpath>\n");
+        for source in &self.sources {
+            xml.push_str(&format!(
+                "\t<classpathentry kind=\"src\" path=\"{}\"/>\n",
+                path_string(source)
+            ));
+        }
+        xml.push_str(
+            "\t<classpathentry kind=\"con\" path=\"org.eclipse.jdt.launching.JRE_CONTAINER\"/>\n",
+        );
+        for jar in &self.jars {
+            xml.push_str(&format!(
+                "\t<classpathentry kind=\"lib\" path=\"{}\"/>\n",
+                path_string(jar)
+            ));
+        }
+        xml.push_str("\t<classpathentry kind=\"output\" path=\"build/ide/classes\"/>\n");
+        xml.push_str("</classpath>\n");
+        xml
+    }
+    /// Minimal eclipse project file marking the project as a java project
+    pub fn eclipse_project(&self) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<projectDescription>
+	<name>{}</name>
+	<comment></comment>
+	<projects></projects>
+	<buildSpec>
+		<buildCommand>
+			<name>org.eclipse.jdt.core.javabuilder</name>
+			<arguments></arguments>
+		</buildCommand>
+	</buildSpec>
+	<natures>
+		<nature>org.eclipse.jdt.core.javanature</nature>
+	</natures>
+</projectDescription>
+"#,
+            escape_html(&self.name)
+        )
+    }
+    /// IntelliJ module file with the sources and every jar as a module library
+    pub fn intellij_module(&self) -> String {
+        let mut xml = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<module type="JAVA_MODULE" version="4">
+  <component name="NewModuleRootManager" inherit-compiler-output="false">
+    <output url="file://$MODULE_DIR$/build/ide/classes" />
+    <exclude-output />
+    <content url="file://$MODULE_DIR$">
+"#,
+        );
+        for source in &self.sources {
+            xml.push_str(&format!(
+                "      <sourceFolder url=\"file://$MODULE_DIR$/{}\" isTestSource=\"false\" />\n",
+                path_string(source)
+            ));
+        }
+        xml.push_str("      <excludeFolder url=\"file://$MODULE_DIR$/build\" />\n");
+        xml.push_str("    </content>\n");
+        xml.push_str("    <orderEntry type=\"inheritedJdk\" />\n");
+        xml.push_str("    <orderEntry type=\"sourceFolder\" forTests=\"false\" />\n");
+        for jar in &self.jars {
+            xml.push_str(&format!(
+                r#"    <orderEntry type="module-library">
+      <library>
+        <CLASSES>
+          <root url="jar://{}!/" />
+        </CLASSES>
+      </library>
+    </orderEntry>
+"#,
+                path_string(jar)
+            ));
+        }
+        xml.push_str("  </component>\n</module>\n");
+        xml
+    }
+    /// IntelliJ project module list pointing at the generated module file
+    pub fn intellij_modules(&self) -> String {
+        let iml = escape_html(&format!("{}.iml", self.name));
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<project version="4">
+  <component name="ProjectModuleManager">
+    <modules>
+      <module fileurl="file://$PROJECT_DIR$/{iml}" filepath="$PROJECT_DIR$/{iml}" />
+    </modules>
+  </component>
+</project>
+"#
+        )
+    }
+}
+
+/// Returns the jar of a resolved dependency. IDEs and compilers cannot read AARs so
+/// they are extracted into extract_dir and their classes.jar is returned. Returns None for dependencies
+/// without classes and for those that have not been downloaded yet.
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available or extracting an AAR fails
+pub fn dependency_jar(dep: &ProjectDep, extract_dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let Some(path) = cached_artifact(dep, "ide")? else {
+        return Ok(None);
+    };
+    let (jars, _) = artifact_jars(dep, &path, extract_dir)
+        .context(format!("Failed to extract classes.jar from {:?}", path))?;
+    Ok(jars.into_iter().next())
+}
+
+/// Returns the jars of the resolved dependencies, including the jars bundled in
+/// AARs. See [`dependency_jar`]
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available or extracting an AAR fails
+pub fn dependency_jars(deps: &[ProjectDep], extract_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut jars = Vec::with_capacity(deps.len());
+    for dep in deps {
+        if let Some(path) = cached_artifact(dep, "ide")? {
+            jars.extend(artifact_jars(dep, &path, extract_dir)?.0);
+        }
+    }
+    Ok(jars)
+}
+
+/// Directory in the project where extracted AAR classes are kept
+pub fn extracted_libs_dir(root: &Path) -> PathBuf {
+    root.join("build").join(IDE_BUILD_DIR).join("libs")
+}
+
+/// Platform api level from an sdk package path e.g. 34 from platforms;android-34
+fn platform_level(package: &InstalledPackage) -> Option<u32> {
+    package.path.strip_prefix(PLATFORM_PREFIX)?.parse().ok()
+}
+
+/// Returns android.jar of the requested platform or the latest installed platform
+///
+/// # Errors
+///
+/// Returns an error if the installed sdk list cannot be read or the requested platform is not installed
+pub fn android_jar(platform: Option<&str>) -> anyhow::Result<Option<PathBuf>> {
+    let list = InstalledList::parse_from_sdk().context("Failed to read installed sdk packages")?;
+    let package = match platform {
+        Some(platform) => {
+            let path = format!("platforms;{platform}");
+            Some(
+                list.contains_path(&path)
+                    .context(format!("Sdk package {path} is not installed"))?,
+            )
+        }
+        None => list
+            .packages
+            .iter()
+            .filter(|p| platform_level(p).is_some())
+            .max_by_key(|p| platform_level(p)),
+    };
+    let package = match package {
+        Some(package) => package,
+        None => return Ok(None),
+    };
+    let dir = match &package.directory {
+        Some(dir) => dir.clone(),
+        None => super::sdk::package_dir(&package.repository_name, &package.path)?,
+    };
+    Ok(Some(dir.join("android.jar")))
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn ide_classpath_files() {
+    let classpath = IdeClasspath {
+        name: String::from("app"),
+        sources: vec![PathBuf::from("app/java")],
+        jars: vec![
+            PathBuf::from("/sdk/android.jar"),
+            PathBuf::from("/cache/a&b.jar"),
+        ],
+    };
+    assert_eq!(
+        classpath.eclipse_classpath(),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<classpath>
+	<classpathentry kind="src" path="app/java"/>
+	<classpathentry kind="con" path="org.eclipse.jdt.launching.JRE_CONTAINER"/>
+	<classpathentry kind="lib" path="/sdk/android.jar"/>
+	<classpathentry kind="lib" path="/cache/a&amp;b.jar"/>
+	<classpathentry kind="output" path="build/ide/classes"/>
+</classpath>
+"#
+    );
+    let iml = classpath.intellij_module();
+    assert!(
+        iml.contains(r#"<sourceFolder url="file://$MODULE_DIR$/app/java" isTestSource="false" />"#)
+    );
+    assert!(iml.contains(r#"<root url="jar:///sdk/android.jar!/" />"#));
+}
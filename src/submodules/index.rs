@@ -0,0 +1,695 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, read_dir, File},
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use fuzzy_matcher::clangd::fuzzy_match;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{info, warn};
+use quick_xml::{events::Event, Reader};
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{
+    config::{
+        get_config, get_resolvers_from_config,
+        global::{authenticate, client_builder, pace},
+        LabToml,
+    },
+    get_home,
+    http_recording::SendRecorded,
+    interrupt::PartialFile,
+    session::Session,
+    version::ComparableVersion,
+    MULTI_PROGRESS_BAR,
+};
+
+use super::Submodule;
+
+/// Directory under labt home where the repository indexes are stored
+const INDEX_DIR: &str = "index";
+/// The list of groups published by repositories with a google maven style index
+const MASTER_INDEX: &str = "master-index.xml";
+/// The artifacts and versions of a group in a google maven style index
+const GROUP_INDEX: &str = "group-index.xml";
+const VERSIONS: &[u8] = b"versions";
+/// Number of group indexes downloaded at the same time
+const FETCH_THREADS: usize = 8;
+/// Name given to entries found in the labt cache
+const CACHE_INDEX: &str = "cache";
+/// The Maven Central search API
+const CENTRAL_SEARCH: &str = "https://search.maven.org/solrsearch/select";
+/// Name given to entries found with the Maven Central search API
+pub const CENTRAL_INDEX: &str = "central";
+/// Number of artifacts asked from the Maven Central search API if not given
+pub const SEARCH_ROWS: usize = 20;
+/// Number of versions of an artifact asked from the Maven Central search API
+const VERSION_ROWS: usize = 50;
+
+#[derive(Clone, Args)]
+pub struct IndexArgs {
+    /// Only update the index of this resolver
+    #[arg(short, long)]
+    pub resolver: Option<String>,
+}
+
+pub struct Index {
+    args: IndexArgs,
+}
+
+impl Index {
+    pub fn new(args: &IndexArgs) -> Self {
+        Index { args: args.clone() }
+    }
+}
+
+/// An artifact listed in a repository index
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub versions: Vec<String>,
+    /// Names of the resolvers whose index lists this artifact
+    pub repositories: Vec<String>,
+}
+
+impl IndexEntry {
+    /// The newest known version of the artifact
+    pub fn latest(&self) -> Option<&str> {
+        self.versions
+            .iter()
+            .max_by_key(|version| ComparableVersion::new(version))
+            .map(|version| version.as_str())
+    }
+    pub fn coordinate(&self) -> String {
+        format!("{}:{}", self.group_id, self.artifact_id)
+    }
+}
+
+/// Parses the groups listed in a master-index.xml
+///
+/// # Errors
+///
+/// Returns an error if the xml is invalid
+pub fn parse_master_index(xml: &str) -> anyhow::Result<Vec<String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut groups = Vec::new();
+    let mut depth = 0;
+    loop {
+        match reader
+            .read_event()
+            .context("Failed to parse master index")?
+        {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            // groups are the empty children of the root element
+            Event::Empty(tag) if depth == 1 => {
+                groups.push(String::from_utf8_lossy(tag.name().as_ref()).to_string());
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(groups)
+}
+
+/// Parses the artifacts and their versions listed in a group-index.xml
+///
+/// # Errors
+///
+/// Returns an error if the xml is invalid
+pub fn parse_group_index(xml: &str, repository: &str) -> anyhow::Result<Vec<IndexEntry>> {
+    let mut reader = Reader::from_str(xml);
+    let mut group_id = None;
+    let mut entries = Vec::new();
+    loop {
+        match reader.read_event().context("Failed to parse group index")? {
+            Event::Start(tag) if group_id.is_none() => {
+                group_id = Some(String::from_utf8_lossy(tag.name().as_ref()).to_string());
+            }
+            Event::Empty(tag) | Event::Start(tag) => {
+                let Some(group_id) = &group_id else {
+                    continue;
+                };
+                let mut versions = Vec::new();
+                for attribute in tag.attributes().flatten() {
+                    if attribute.key.as_ref() == VERSIONS {
+                        versions = String::from_utf8_lossy(&attribute.value)
+                            .split(',')
+                            .map(|version| version.trim().to_string())
+                            .filter(|version| !version.is_empty())
+                            .collect();
+                    }
+                }
+                entries.push(IndexEntry {
+                    group_id: group_id.clone(),
+                    artifact_id: String::from_utf8_lossy(tag.name().as_ref()).to_string(),
+                    versions,
+                    repositories: vec![repository.to_string()],
+                });
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(entries)
+}
+
+/// Parses a plain text coordinate list. Each line is group_id:artifact_id with
+/// optional comma separated versions e.g. `androidx.core:core:1.12.0,1.13.0`.
+/// Empty lines and lines starting with # are skipped. This is also the format the
+/// indexes are stored in.
+pub fn parse_coordinate_list(text: &str, repository: &str) -> Vec<IndexEntry> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ':');
+            let group_id = parts.next()?.trim();
+            let artifact_id = parts.next()?.trim();
+            if group_id.is_empty() || artifact_id.is_empty() {
+                return None;
+            }
+            let versions = parts
+                .next()
+                .map(|versions| {
+                    versions
+                        .split(',')
+                        .map(|version| version.trim().to_string())
+                        .filter(|version| !version.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(IndexEntry {
+                group_id: group_id.to_string(),
+                artifact_id: artifact_id.to_string(),
+                versions,
+                repositories: vec![repository.to_string()],
+            })
+        })
+        .collect()
+}
+
+fn index_dir() -> anyhow::Result<PathBuf> {
+    Ok(get_home()?.join(INDEX_DIR))
+}
+
+fn write_index(repository: &str, entries: &[IndexEntry]) -> anyhow::Result<PathBuf> {
+    let dir = index_dir()?;
+    create_dir_all(&dir).context(format!("Failed to create {:?}", dir))?;
+    let path = dir.join(format!("{repository}.txt"));
+    let mut partial = PartialFile::new(path.clone());
+    let mut file = File::create(&path).context(format!("Failed to create {:?}", path))?;
+    writeln!(file, "# labt index of the {repository} repository")?;
+    for entry in entries {
+        writeln!(
+            file,
+            "{}:{}:{}",
+            entry.group_id,
+            entry.artifact_id,
+            entry.versions.join(",")
+        )?;
+    }
+    partial.complete();
+    Ok(path)
+}
+
+/// Downloads the index of a repository that publishes a google maven style
+/// master-index.xml. Returns None if the repository has no such index.
+fn fetch_group_indexes(
+    client: &reqwest::blocking::Client,
+    repository: &str,
+    base_url: &str,
+) -> anyhow::Result<Option<Vec<IndexEntry>>> {
+    let base_url = base_url.trim_end_matches('/');
+    let url = format!("{base_url}/{MASTER_INDEX}");
+    let res = authenticate(client.get(&url), &url)
+        .send_recorded()
+        .context(format!("Failed to reach the {repository} repository"))?;
+    if !res.status().is_success() {
+        return Ok(None);
+    }
+    let groups = parse_master_index(&res.text()?)?;
+
+    let bar = MULTI_PROGRESS_BAR.add(ProgressBar::new(groups.len() as u64));
+    bar.set_style(ProgressStyle::with_template("{spinner} Indexing {msg} {pos}/{len}").unwrap());
+    bar.set_message(repository.to_string());
+    let queue = Mutex::new(groups.iter());
+    let entries = Mutex::new(Vec::new());
+    let session = Session::current();
+    std::thread::scope(|scope| {
+        for _ in 0..FETCH_THREADS {
+            scope.spawn(|| {
+                session.enter(|| loop {
+                    if crate::interrupt::is_interrupted() {
+                        return;
+                    }
+                    let Some(group) = queue.lock().unwrap().next() else {
+                        return;
+                    };
+                    let url = format!("{base_url}/{}/{GROUP_INDEX}", group.replace('.', "/"));
+                    if !pace(&url) {
+                        return;
+                    }
+                    let parsed = authenticate(client.get(&url), &url)
+                        .send_recorded()
+                        .and_then(|res| res.error_for_status())
+                        .and_then(|res| res.text())
+                        .map_err(anyhow::Error::from)
+                        .and_then(|xml| parse_group_index(&xml, repository));
+                    match parsed {
+                        Ok(group_entries) => entries.lock().unwrap().extend(group_entries),
+                        Err(err) => warn!(target: "index", "Skipping group {group}: {err}"),
+                    }
+                    bar.inc(1);
+                })
+            });
+        }
+    });
+    bar.finish_and_clear();
+    crate::interrupt::check()?;
+
+    let mut entries = entries.into_inner().unwrap();
+    entries.sort_by_key(|entry| entry.coordinate());
+    Ok(Some(entries))
+}
+
+/// Downloads the indexes of the configured repositories into labt home. Repositories
+/// with an `index` url in their resolver table use that coordinate list, the rest are
+/// indexed if they publish a master-index.xml. Returns the number of artifacts indexed
+/// per repository.
+///
+/// # Errors
+///
+/// Returns an error if labt is offline, a repository cannot be reached or the index
+/// cannot be written
+pub fn update_index(config: &LabToml, only: Option<&str>) -> anyhow::Result<Vec<(String, usize)>> {
+    if crate::is_offline() {
+        bail!("The repository index cannot be updated in offline mode");
+    }
+    let client = client_builder()?
+        .build()
+        .context("Error creating download client")?;
+    let resolvers = get_resolvers_from_config(config).context("Failed to get resolvers")?;
+
+    let mut updated = Vec::new();
+    for resolver in &resolvers {
+        // local repositories are searched in place
+        let Some(base_url) = resolver
+            .get_base_url()
+            .filter(|url| !url.starts_with("file:"))
+        else {
+            continue;
+        };
+        let name = resolver.get_name().to_string();
+        if only.is_some_and(|only| only != name) {
+            continue;
+        }
+        let index_url = config
+            .resolvers
+            .as_ref()
+            .and_then(|resolvers| resolvers.get(&name))
+            .and_then(|table| table.index.clone());
+        let entries = match index_url {
+            Some(url) => {
+                let text = authenticate(client.get(&url), &url)
+                    .send_recorded()
+                    .and_then(|res| res.error_for_status())
+                    .and_then(|res| res.text())
+                    .context(format!("Failed to download the index of {name} from {url}"))?;
+                parse_coordinate_list(&text, &name)
+            }
+            None => match fetch_group_indexes(&client, &name, base_url)? {
+                Some(entries) => entries,
+                None => {
+                    warn!(target: "index", "The {name} repository does not publish an index. Set index in its resolver table to a coordinate list");
+                    continue;
+                }
+            },
+        };
+        let path = write_index(&name, &entries)?;
+        info!(target: "index", "Indexed {} artifacts of {name} into {:?}", entries.len(), path);
+        updated.push((name, entries.len()));
+    }
+    if let Some(only) = only {
+        if updated.is_empty() {
+            bail!("No repository named {only} could be indexed");
+        }
+    }
+    Ok(updated)
+}
+
+/// Lists the artifacts already in the labt cache
+fn cached_entries() -> anyhow::Result<Vec<IndexEntry>> {
+    let cache = get_home()?.join("cache");
+    let mut entries = Vec::new();
+    if !cache.is_dir() {
+        return Ok(entries);
+    }
+    let dirs = |path: &PathBuf| -> Vec<(String, PathBuf)> {
+        read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| {
+                (
+                    entry.file_name().to_string_lossy().to_string(),
+                    entry.path(),
+                )
+            })
+            .collect()
+    };
+    for (group_id, group) in dirs(&cache) {
+        for (artifact_id, artifact) in dirs(&group) {
+            let versions: Vec<String> = dirs(&artifact).into_iter().map(|(v, _)| v).collect();
+            if versions.is_empty() {
+                continue;
+            }
+            entries.push(IndexEntry {
+                group_id: group_id.clone(),
+                artifact_id,
+                versions,
+                repositories: vec![CACHE_INDEX.to_string()],
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Loads the downloaded repository indexes together with the artifacts in the labt
+/// cache. Artifacts listed by several repositories are merged into one entry. This
+/// does not access the network.
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available or an index cannot be read
+pub fn load_index() -> anyhow::Result<Vec<IndexEntry>> {
+    let mut all = cached_entries()?;
+    let dir = index_dir()?;
+    if dir.is_dir() {
+        for file in read_dir(&dir).context(format!("Failed to read {:?}", dir))? {
+            let path = file?.path();
+            if path.extension().is_none_or(|ext| ext != "txt") {
+                continue;
+            }
+            let Some(repository) = path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            let mut text = String::new();
+            for line in BufReader::new(File::open(&path)?).lines() {
+                text.push_str(&line?);
+                text.push('\n');
+            }
+            all.extend(parse_coordinate_list(&text, &repository));
+        }
+    }
+
+    let mut merged: BTreeMap<String, IndexEntry> = BTreeMap::new();
+    for entry in all {
+        match merged.get_mut(&entry.coordinate()) {
+            Some(existing) => {
+                for version in entry.versions {
+                    if !existing.versions.contains(&version) {
+                        existing.versions.push(version);
+                    }
+                }
+                existing.repositories.extend(entry.repositories);
+            }
+            None => {
+                merged.insert(entry.coordinate(), entry);
+            }
+        }
+    }
+    Ok(merged.into_values().collect())
+}
+
+/// Finds the artifacts matching the query, best matches first. Exact artifact id
+/// matches come first followed by fuzzy matches on group_id:artifact_id.
+pub fn search_index<'a>(entries: &'a [IndexEntry], query: &str) -> Vec<&'a IndexEntry> {
+    let query = query.trim();
+    let mut matches: Vec<(bool, i64, &IndexEntry)> = entries
+        .iter()
+        .filter_map(|entry| {
+            let exact = entry.artifact_id == query || entry.coordinate() == query;
+            fuzzy_match(&entry.coordinate(), query)
+                .or(exact.then_some(0))
+                .map(|rank| (exact, rank, entry))
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.0.cmp(&a.0)
+            .then(b.1.cmp(&a.1))
+            .then_with(|| a.2.coordinate().cmp(&b.2.coordinate()))
+    });
+    matches.into_iter().map(|(_, _, entry)| entry).collect()
+}
+
+#[derive(Deserialize)]
+struct SolrResult {
+    response: SolrResponse,
+}
+
+#[derive(Deserialize)]
+struct SolrResponse {
+    docs: Vec<SolrDoc>,
+}
+
+/// An artifact, or a single version of it when the gav core is searched
+#[derive(Deserialize)]
+struct SolrDoc {
+    g: String,
+    a: String,
+    #[serde(rename = "latestVersion")]
+    latest_version: Option<String>,
+    v: Option<String>,
+}
+
+/// Parses a Maven Central search API response. Artifacts keep the order they
+/// were ranked in and the versions of an artifact are merged into one entry.
+///
+/// # Errors
+///
+/// Returns an error if the json is invalid
+pub fn parse_central_search(json: &str) -> anyhow::Result<Vec<IndexEntry>> {
+    let result: SolrResult =
+        serde_json::from_str(json).context("Failed to parse the Maven Central search result")?;
+    let mut entries: Vec<IndexEntry> = Vec::new();
+    for doc in result.response.docs {
+        let version = doc.v.or(doc.latest_version);
+        match entries
+            .iter_mut()
+            .find(|entry| entry.group_id == doc.g && entry.artifact_id == doc.a)
+        {
+            Some(entry) => entry.versions.extend(version),
+            None => entries.push(IndexEntry {
+                group_id: doc.g,
+                artifact_id: doc.a,
+                versions: version.into_iter().collect(),
+                repositories: vec![CENTRAL_INDEX.to_string()],
+            }),
+        }
+    }
+    Ok(entries)
+}
+
+fn query_central(
+    client: &reqwest::blocking::Client,
+    params: &[(&str, &str)],
+) -> anyhow::Result<Vec<IndexEntry>> {
+    let url = Url::parse_with_params(CENTRAL_SEARCH, params.iter().chain(&[("wt", "json")]))
+        .context("Invalid Maven Central search url")?;
+    let json = client
+        .get(url)
+        .send_recorded()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.text())
+        .context("Failed to query the Maven Central search API")?;
+    parse_central_search(&json)
+}
+
+/// Searches Maven Central for artifacts matching the query. Each entry only
+/// lists the newest version, see [`central_versions`] for the rest.
+///
+/// # Errors
+///
+/// Returns an error if the search API cannot be reached or answers with
+/// something else than a search result
+pub fn search_central(
+    client: &reqwest::blocking::Client,
+    query: &str,
+    rows: usize,
+) -> anyhow::Result<Vec<IndexEntry>> {
+    query_central(client, &[("q", query.trim()), ("rows", &rows.to_string())])
+}
+
+/// Lists the versions of group_id:artifact_id published to Maven Central
+///
+/// # Errors
+///
+/// Returns an error if the search API cannot be reached or answers with
+/// something else than a search result
+pub fn central_versions(
+    client: &reqwest::blocking::Client,
+    group_id: &str,
+    artifact_id: &str,
+) -> anyhow::Result<Vec<String>> {
+    let query = format!("g:\"{group_id}\" AND a:\"{artifact_id}\"");
+    let entries = query_central(
+        client,
+        &[
+            ("q", &query),
+            ("core", "gav"),
+            ("rows", &VERSION_ROWS.to_string()),
+        ],
+    )?;
+    Ok(entries
+        .into_iter()
+        .flat_map(|entry| entry.versions)
+        .collect())
+}
+
+/// Finds the artifacts matching the query in the downloaded repository indexes,
+/// the labt cache and, unless labt is offline, the Maven Central search API.
+/// Indexed artifacts come first since they are matched locally.
+///
+/// # Errors
+///
+/// Returns an error if the indexes cannot be read
+pub fn find_artifacts(query: &str) -> anyhow::Result<Vec<IndexEntry>> {
+    let indexed = load_index()?;
+    let mut found: Vec<IndexEntry> = search_index(&indexed, query)
+        .into_iter()
+        .take(SEARCH_ROWS)
+        .cloned()
+        .collect();
+    if crate::is_offline() {
+        return Ok(found);
+    }
+    let client = client_builder()?
+        .build()
+        .context("Error creating download client")?;
+    match search_central(&client, query, SEARCH_ROWS) {
+        Ok(central) => merge_entries(&mut found, central),
+        Err(err) => warn!(target: "index", "Skipping Maven Central: {:?}", err),
+    }
+    Ok(found)
+}
+
+/// Adds more to found keeping the order. The versions and repositories of an
+/// artifact already found are merged into its entry
+pub fn merge_entries(found: &mut Vec<IndexEntry>, more: Vec<IndexEntry>) {
+    for entry in more {
+        match found
+            .iter_mut()
+            .find(|f| f.coordinate() == entry.coordinate())
+        {
+            Some(existing) => {
+                for version in entry.versions {
+                    if !existing.versions.contains(&version) {
+                        existing.versions.push(version);
+                    }
+                }
+                existing.repositories.extend(entry.repositories);
+            }
+            None => found.push(entry),
+        }
+    }
+}
+
+/// Looks up the newest indexed version of group_id:artifact_id
+pub fn latest_indexed_version(group_id: &str, artifact_id: &str) -> anyhow::Result<Option<String>> {
+    Ok(load_index()?
+        .into_iter()
+        .find(|entry| entry.group_id == group_id && entry.artifact_id == artifact_id)
+        .and_then(|entry| entry.latest().map(|v| v.to_string())))
+}
+
+impl Submodule for Index {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let config = get_config()?;
+        let updated = update_index(&config, self.args.resolver.as_deref())?;
+        for (repository, count) in updated {
+            println!("{repository}: {count} artifacts");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn repository_index_parsing_and_search() {
+    let master = r#"<?xml version='1.0' encoding='UTF-8'?>
+<metadata>
+  <androidx.core/>
+  <androidx.room/>
+</metadata>"#;
+    assert_eq!(
+        parse_master_index(master).unwrap(),
+        vec!["androidx.core", "androidx.room"]
+    );
+
+    let group = r#"<?xml version='1.0' encoding='UTF-8'?>
+<androidx.room>
+  <room-runtime versions="2.5.0,2.6.0,2.6.1"/>
+  <room-compiler versions="2.6.1"/>
+</androidx.room>"#;
+    let mut entries = parse_group_index(group, "google").unwrap();
+    assert_eq!(entries[0].coordinate(), "androidx.room:room-runtime");
+    assert_eq!(entries[0].latest(), Some("2.6.1"));
+    assert_eq!(entries[1].versions, vec!["2.6.1"]);
+
+    entries.extend(parse_coordinate_list(
+        "# central\njunit:junit:4.12,4.13.2\n\ncom.squareup.okhttp3:okhttp\n",
+        "central",
+    ));
+    assert_eq!(entries[2].latest(), Some("4.13.2"));
+    assert!(entries[3].versions.is_empty());
+
+    let found: Vec<String> = search_index(&entries, "room")
+        .iter()
+        .map(|entry| entry.coordinate())
+        .collect();
+    assert_eq!(
+        found,
+        vec!["androidx.room:room-compiler", "androidx.room:room-runtime"]
+    );
+    // an exact artifact id is ranked first
+    assert_eq!(
+        search_index(&entries, "junit")[0].coordinate(),
+        "junit:junit"
+    );
+}
+
+#[test]
+fn central_search_results() {
+    let json = r#"{
+  "responseHeader": {"status": 0, "QTime": 2},
+  "response": {"numFound": 3, "start": 0, "docs": [
+    {"id": "com.squareup.okhttp3:okhttp", "g": "com.squareup.okhttp3", "a": "okhttp", "latestVersion": "4.12.0", "p": "jar", "versionCount": 52},
+    {"id": "com.squareup.okhttp:okhttp", "g": "com.squareup.okhttp", "a": "okhttp", "latestVersion": "2.7.5", "p": "jar", "versionCount": 30}
+  ]}
+}"#;
+    let entries = parse_central_search(json).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].coordinate(), "com.squareup.okhttp3:okhttp");
+    assert_eq!(entries[0].latest(), Some("4.12.0"));
+    assert_eq!(entries[1].repositories, vec![CENTRAL_INDEX]);
+
+    // the gav core lists every version as its own document
+    let json = r#"{"response": {"numFound": 2, "docs": [
+    {"id": "junit:junit:4.13.2", "g": "junit", "a": "junit", "v": "4.13.2", "p": "jar"},
+    {"id": "junit:junit:4.12", "g": "junit", "a": "junit", "v": "4.12", "p": "jar"}
+  ]}}"#;
+    let entries = parse_central_search(json).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].versions, vec!["4.13.2", "4.12"]);
+    assert!(parse_central_search("<html>rate limited</html>").is_err());
+}
@@ -1,16 +1,20 @@
 use crate::{
-    config::LabToml,
-    plugin::{api::MluaAnyhowWrapper, config::load_package_paths, executable::ExecutableLua},
-    PROJECT_ROOT,
+    config::{LabToml, LABT_TOML_FILE_NAME},
+    plugin::{
+        api::template::{new_engine, DEFAULT_TEMPLATES},
+        config::load_package_paths,
+        executable::ExecutableLua,
+        trace::LuaFailure,
+    },
+    set_manifest_path,
 };
 use anyhow::{bail, Context};
 use clap::Args;
-use labt_proc_macro::labt_lua;
-use mlua::{Lua, LuaSerdeExt, Table};
-use std::{env::current_dir, fs::File, io::Write, path::PathBuf, rc::Rc, sync::OnceLock};
-use tera::Tera;
+use log::{info, warn};
+use mlua::{LuaSerdeExt, Table};
+use std::{env::current_dir, fs::File, io::Write, path::PathBuf, rc::Rc};
 
-use super::{plugin::fetch_plugin, Submodule};
+use super::{build::write_manifest, plugin::fetch_plugin, Submodule};
 
 #[derive(Args, Clone)]
 pub struct InitArgs {
@@ -40,34 +44,6 @@ impl Init {
     }
 }
 
-static TERA: OnceLock<Tera> = OnceLock::new();
-
-#[labt_lua]
-fn render(_lua: &Lua, (name, context): (String, Table)) {
-    let t = TERA
-        .get()
-        .context("Tera template not initialized yet.")
-        .map_err(MluaAnyhowWrapper::external)?;
-    let render = t
-        .render(
-            &name,
-            &tera::Context::from_serialize(context)
-                .context("Failed to serialize lua table to tera context")
-                .map_err(MluaAnyhowWrapper::external)?,
-        )
-        .context("Failed to render template")
-        .map_err(MluaAnyhowWrapper::external)?;
-    Ok(render)
-}
-
-fn load_template_table(lua: &Lua) -> anyhow::Result<()> {
-    let table = lua.create_table()?;
-    render(lua, &table)?;
-
-    lua.globals().set("template", table)?;
-    Ok(())
-}
-
 impl Submodule for Init {
     /*
         ============Entry point for this module =================
@@ -100,10 +76,12 @@ impl Submodule for Init {
 
         let init = config.init.unwrap();
         let init_file = path.join(&init.file);
+        let templates = path.join(init.templates.as_deref().unwrap_or(DEFAULT_TEMPLATES));
+        let plugin = format!("{}:{}", config.name, config.version);
 
-        // override the project path with the current working dir
+        // the project is created in the current working dir
         let cwd = current_dir()?;
-        let _ = PROJECT_ROOT.set(cwd);
+        set_manifest_path(Some(cwd.join(LABT_TOML_FILE_NAME)));
 
         let package_paths = if let Some(package_paths) = &config.package_paths {
             load_package_paths(package_paths, &path)
@@ -111,29 +89,28 @@ impl Submodule for Init {
             load_package_paths(&[], &path)
         };
 
-        let mut exec = ExecutableLua::new(init_file, &package_paths, Rc::new(Vec::new()), false);
+        let mut exec = ExecutableLua::new(
+            init_file.clone(),
+            &package_paths,
+            Rc::new(Vec::new()),
+            false,
+        );
         exec.load_api_tables()
             .context("Error injecting api tables into lua context")?;
         let lua = exec.get_lua();
         lua.globals().set("PLUGIN_VERSION", config.version)?;
-        load_template_table(lua)?;
 
         let chunk = exec.load().context("Failed to load project init script")?;
 
         let output = self.args.path.as_ref().unwrap();
         chunk
             .exec()
+            .map_err(|err| LuaFailure::new(&plugin, &init_file, &err))
             .context("Failed to execute project init code.")?;
 
         // now call the templater init function with path
-        let template_path = if let Some(template) = init.templates {
-            path.join(template)
-        } else {
-            path.join("templates/*")
-        };
-
-        let t = Tera::new(template_path.to_string_lossy().as_ref())?;
-        TERA.get_or_init(|| t);
+        let t = new_engine(Some(templates.to_string_lossy().as_ref()))?;
+        lua.set_app_data(t);
 
         let init_function: mlua::Function = lua
             .globals()
@@ -142,6 +119,7 @@ impl Submodule for Init {
 
         let (project_table, override_path) = init_function
             .call::<String, (Table, Option<String>)>(output.to_string_lossy().to_string())
+            .map_err(|err| LuaFailure::new(&plugin, &init_file, &err))
             .context("Failed to execute plugin init function")?;
 
         let project: LabToml = lua.from_value(mlua::Value::Table(project_table))?;
@@ -153,6 +131,7 @@ impl Submodule for Init {
             self.args.path.as_ref().unwrap().clone()
         };
 
+        let root = output.clone();
         output.push("Labt.toml");
 
         // create file target to write toml file
@@ -166,6 +145,15 @@ impl Submodule for Init {
             "Writing LabtToml string to toml file at {}",
             path.to_str().unwrap_or("[unknown]")
         ))?;
+
+        // templates can leave the manifest to be generated from the project table
+        match write_manifest(&project, &root) {
+            Ok(Some(path)) => info!(target: "init", "Generated {:?}", path),
+            Ok(None) => {}
+            Err(err) => {
+                warn!(target: "init", "{:?}. It is generated again by labt build", err)
+            }
+        }
         Ok(())
     }
 }
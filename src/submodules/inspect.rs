@@ -0,0 +1,323 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use indicatif::HumanBytes;
+use log::warn;
+use zip::ZipArchive;
+
+use crate::{
+    caching::{Cache, CacheType},
+    config::lock::{load_labt_lock, lock_file_in, strings::LOCK_FILE},
+    get_project_root,
+    pom::{parse_pom, Project},
+};
+
+use super::{licenses::read_cached_licenses, resolve::ProjectDep, Submodule};
+
+/// The manifest of an android library archive
+const AAR_MANIFEST: &str = "AndroidManifest.xml";
+
+#[derive(Clone, Args)]
+pub struct InspectArgs {
+    /// The artifact as group_id:artifact_id:version. The version locked in
+    /// Labt.lock is used if it is left out
+    pub coordinate: String,
+    /// Do not list the files inside the jar or aar
+    #[arg(long)]
+    pub no_files: bool,
+}
+
+pub struct Inspect {
+    args: InspectArgs,
+}
+
+impl Inspect {
+    pub fn new(args: &InspectArgs) -> Self {
+        Inspect { args: args.clone() }
+    }
+}
+
+/// What the labt cache knows about an artifact
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Inspection {
+    /// group_id:artifact_id:version
+    pub coordinate: String,
+    pub packaging: String,
+    /// group_id:artifact_id:version of the parent pom
+    pub parent: Option<String>,
+    pub licenses: Vec<String>,
+    /// Where the artifact is locked e.g. "project, compile scope from google"
+    pub locked: Option<String>,
+    /// The dependencies declared in the pom with their version requirement and scope
+    pub dependencies: Vec<String>,
+    /// The files inside the archive with their uncompressed size. None if the
+    /// artifact has no archive or it is not cached
+    pub files: Option<Vec<(String, u64)>>,
+    /// The AndroidManifest.xml of an aar
+    pub manifest: Option<String>,
+}
+
+impl Inspection {
+    /// Fills the summary from the parsed pom of the artifact
+    pub fn from_pom(project: &Project, version: &str) -> Self {
+        let dependencies = project
+            .get_dependencies()
+            .iter()
+            .map(|dep| {
+                let version = match dep.get_selected_version() {
+                    Some(version) => version.clone(),
+                    None => dep.get_version().to_string(),
+                };
+                let mut line = format!("{}:{}", dep.get_group_id(), dep.get_artifact_id());
+                if !version.is_empty() {
+                    line.push_str(&format!(":{version}"));
+                }
+                line.push_str(&format!(" ({}", dep.get_scope()));
+                if dep.is_optional() {
+                    line.push_str(", optional");
+                }
+                line.push(')');
+                line
+            })
+            .collect();
+        Inspection {
+            coordinate: format!(
+                "{}:{}:{}",
+                project.get_group_id(),
+                project.get_artifact_id(),
+                version
+            ),
+            packaging: project.get_packaging(),
+            parent: project.parent.as_ref().map(|parent| {
+                format!(
+                    "{}:{}:{}",
+                    parent.group_id, parent.artifact_id, parent.version
+                )
+            }),
+            dependencies,
+            ..Default::default()
+        }
+    }
+    /// Lists the archive and reads the manifest if it is an aar
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the archive is not a valid zip
+    pub fn read_archive<R: Read + Seek>(&mut self, reader: R) -> anyhow::Result<()> {
+        let mut archive = ZipArchive::new(reader).context("Not a valid jar or aar")?;
+        let mut files = Vec::with_capacity(archive.len());
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i)?;
+            if !entry.is_dir() {
+                files.push((entry.name().to_string(), entry.size()));
+            }
+        }
+        files.sort();
+        if let Ok(mut manifest) = archive.by_name(AAR_MANIFEST) {
+            let mut contents = String::new();
+            if manifest.read_to_string(&mut contents).is_ok() {
+                self.manifest = Some(contents);
+            }
+        }
+        self.files = Some(files);
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Inspection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.coordinate)?;
+        writeln!(f, "  packaging: {}", self.packaging)?;
+        if let Some(parent) = &self.parent {
+            writeln!(f, "  parent:    {parent}")?;
+        }
+        if !self.licenses.is_empty() {
+            writeln!(f, "  licenses:  {}", self.licenses.join(", "))?;
+        }
+        if let Some(locked) = &self.locked {
+            writeln!(f, "  locked:    {locked}")?;
+        }
+
+        writeln!(f, "\nDependencies ({}):", self.dependencies.len())?;
+        for dep in &self.dependencies {
+            writeln!(f, "  {dep}")?;
+        }
+
+        if let Some(files) = &self.files {
+            let total: u64 = files.iter().map(|(_, size)| size).sum();
+            let width = files.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            writeln!(f, "\nFiles ({}, {}):", files.len(), HumanBytes(total))?;
+            for (name, size) in files {
+                writeln!(f, "  {name:width$}  {}", HumanBytes(*size))?;
+            }
+        }
+        if let Some(manifest) = &self.manifest {
+            writeln!(f, "\n{AAR_MANIFEST}:")?;
+            writeln!(f, "{}", manifest.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+/// The locked entry of group_id:artifact_id and the Labt.lock section it is in
+fn find_locked(group_id: &str, artifact_id: &str) -> Option<(ProjectDep, &'static str)> {
+    let root = get_project_root().ok()?;
+    if !lock_file_in(&root).exists() {
+        return None;
+    }
+    let lock = load_labt_lock().ok()?;
+    [
+        (lock.resolved, "project"),
+        (lock.processors, "annotation processor"),
+        (lock.test, "test"),
+    ]
+    .into_iter()
+    .find_map(|(deps, section)| {
+        deps.into_iter()
+            .find(|dep| dep.group_id == group_id && dep.artifact_id == artifact_id)
+            .map(|dep| (dep, section))
+    })
+}
+
+impl Submodule for Inspect {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let parts: Vec<&str> = self.args.coordinate.split(':').collect();
+        let (group_id, artifact_id, version) = match parts.as_slice() {
+            [group_id, artifact_id] => (*group_id, *artifact_id, None),
+            [group_id, artifact_id, version] => (*group_id, *artifact_id, Some(*version)),
+            _ => bail!(
+                "Invalid coordinate {}. Expected group_id:artifact_id[:version]",
+                self.args.coordinate
+            ),
+        };
+        let locked = find_locked(group_id, artifact_id);
+        let version = match (version, &locked) {
+            (Some(version), _) => version.to_string(),
+            (None, Some((dep, _))) => dep.version.clone(),
+            (None, None) => bail!(
+                "{group_id}:{artifact_id} is not in {LOCK_FILE}. Give the version as group_id:artifact_id:version"
+            ),
+        };
+
+        let mut pom = Cache::new(
+            group_id.to_string(),
+            artifact_id.to_string(),
+            version.clone(),
+            CacheType::POM,
+        );
+        pom.use_labt_home()?;
+        let file = pom.open().context(format!(
+            "{group_id}:{artifact_id}:{version} is not in the labt cache. Add it to the project and run labt resolve"
+        ))?;
+        let project = parse_pom(
+            BufReader::new(file),
+            Project::new(group_id, artifact_id, &version),
+        )
+        .context(format!(
+            "Failed to parse pom for {group_id}:{artifact_id}:{version}"
+        ))?;
+
+        let mut inspection = Inspection::from_pom(&project, &version);
+        inspection.licenses = read_cached_licenses(group_id, artifact_id, &version)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|license| license.name)
+            .collect();
+        if let Some((dep, section)) = locked.filter(|(dep, _)| dep.version == version) {
+            let mut locked = format!("{section}, {} scope", dep.scope);
+            if let Some(resolver) = &dep.resolver {
+                locked.push_str(&format!(" from {resolver}"));
+            }
+            inspection.locked = Some(locked);
+            // the lock knows the packaging the artifact was downloaded as
+            inspection.packaging = dep.packaging;
+        }
+
+        if !self.args.no_files && inspection.packaging != "pom" {
+            let mut artifact = Cache::new(
+                group_id.to_string(),
+                artifact_id.to_string(),
+                version.clone(),
+                CacheType::from(inspection.packaging.clone()),
+            );
+            artifact.use_labt_home()?;
+            let path = artifact.get_path()?;
+            if path.exists() {
+                let file = File::open(&path).context(format!("Failed to open {:?}", path))?;
+                inspection
+                    .read_archive(file)
+                    .context(format!("Failed to read {:?}", path))?;
+            } else {
+                warn!(target: "inspect", "The {} of {} is not in the labt cache. Run labt resolve to download it", inspection.packaging, inspection.coordinate);
+            }
+        }
+
+        print!("{inspection}");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn inspect_pom_and_aar_contents() {
+    use std::io::{Cursor, Write};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let pom = r#"<project>
+  <groupId>com.example</groupId>
+  <artifactId>widgets</artifactId>
+  <version>1.2.0</version>
+  <packaging>aar</packaging>
+  <dependencies>
+    <dependency>
+      <groupId>androidx.core</groupId>
+      <artifactId>core</artifactId>
+      <version>1.9.0</version>
+    </dependency>
+    <dependency>
+      <groupId>com.example</groupId>
+      <artifactId>extras</artifactId>
+      <version>[1.0,2.0)</version>
+      <scope>runtime</scope>
+      <optional>true</optional>
+    </dependency>
+  </dependencies>
+</project>"#;
+    let project = parse_pom(
+        BufReader::new(pom.as_bytes()),
+        Project::new("com.example", "widgets", "1.2.0"),
+    )
+    .unwrap();
+    let mut inspection = Inspection::from_pom(&project, "1.2.0");
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in [
+        ("classes.jar", "0123456789"),
+        (
+            AAR_MANIFEST,
+            "<manifest package=\"com.example.widgets\" />\n",
+        ),
+        ("res/values/values.xml", "<resources />"),
+    ] {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    let archive = writer.finish().unwrap();
+    inspection.read_archive(archive).unwrap();
+
+    assert_eq!(
+        inspection.to_string(),
+        "com.example:widgets:1.2.0\n  packaging: aar\n\n\
+         Dependencies (2):\n  androidx.core:core:1.9.0 (compile)\n  com.example:extras:[1.0,2.0) (runtime, optional)\n\n\
+         Files (3, 66 B):\n  AndroidManifest.xml    43 B\n  classes.jar            10 B\n  res/values/values.xml  13 B\n\n\
+         AndroidManifest.xml:\n<manifest package=\"com.example.widgets\" />\n"
+    );
+}
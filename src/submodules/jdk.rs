@@ -0,0 +1,700 @@
+use std::{
+    fs::{create_dir_all, read_dir, remove_dir_all, remove_file, rename, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use flate2::read::GzDecoder;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::info;
+use serde::Deserialize;
+use zip::ZipArchive;
+
+use crate::{
+    archive::{extract_zip, ArchiveProgress, BarProgress, ProgressWriter},
+    caching::file_sha256,
+    config::{
+        global::client_builder,
+        jdk::{JdkDistribution, JdkTable},
+    },
+    envs::JAVA_HOME,
+    events::ProgressReader,
+    get_home,
+    http_recording::SendRecorded,
+    interrupt::PartialFile,
+    plugin::process::ProcessEnv,
+    MULTI_PROGRESS_BAR,
+};
+
+/// Directory under labt home where JDKs are installed
+const JDKS_DIR: &str = "jdks";
+const ADOPTIUM_API: &str = "https://api.adoptium.net/v3/assets/latest";
+const AZUL_API: &str = "https://api.azul.com/metadata/v1/zulu/packages/";
+/// How deep in the extracted archive to look for bin/java
+const JAVA_HOME_SEARCH_DEPTH: usize = 4;
+
+#[cfg(target_os = "windows")]
+const JAVA_EXECUTABLE: &str = "java.exe";
+#[cfg(not(target_os = "windows"))]
+const JAVA_EXECUTABLE: &str = "java";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveType {
+    Zip,
+    TarGz,
+}
+
+impl ArchiveType {
+    fn extension(&self) -> &'static str {
+        match self {
+            ArchiveType::Zip => "zip",
+            ArchiveType::TarGz => "tar.gz",
+        }
+    }
+}
+
+/// The operating system and architecture names used by the distribution download apis
+fn platform(distribution: JdkDistribution) -> anyhow::Result<(&'static str, &'static str)> {
+    let os = match (std::env::consts::OS, distribution) {
+        ("linux", _) => "linux",
+        ("windows", _) => "windows",
+        ("macos", JdkDistribution::Temurin) => "mac",
+        ("macos", JdkDistribution::Zulu) => "macos",
+        (os, _) => bail!("No {distribution} JDK builds are available for {os}"),
+    };
+    let arch = match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "aarch64" => "aarch64",
+        "x86" if distribution == JdkDistribution::Temurin => "x32",
+        "x86" => "i686",
+        "arm" => "arm",
+        arch => bail!("No {distribution} JDK builds are available for {arch}"),
+    };
+    Ok((os, arch))
+}
+
+fn archive_type() -> ArchiveType {
+    if cfg!(target_os = "windows") {
+        ArchiveType::Zip
+    } else {
+        ArchiveType::TarGz
+    }
+}
+
+/// The directory the JDK is installed into. JDKs are kept per target triple so
+/// that a shared labt home works across machines.
+pub fn jdk_install_dir(table: &JdkTable) -> anyhow::Result<PathBuf> {
+    let mut path = get_home().context("Failed to get labt home")?;
+    path.push(JDKS_DIR);
+    path.push(format!(
+        "{}-{}-{}",
+        table.distribution,
+        table.version,
+        crate::TARGET
+    ));
+    Ok(path)
+}
+
+/// Looks for the directory containing bin/java. Archives usually have a single top
+/// level directory and macOS builds nest the JDK under Contents/Home.
+pub fn find_java_home(dir: &Path) -> Option<PathBuf> {
+    let mut search = vec![(dir.to_path_buf(), 0)];
+    while let Some((dir, depth)) = search.pop() {
+        if dir.join("bin").join(JAVA_EXECUTABLE).is_file() {
+            return Some(dir);
+        }
+        if depth >= JAVA_HOME_SEARCH_DEPTH {
+            continue;
+        }
+        if let Ok(entries) = read_dir(&dir) {
+            let mut children: Vec<PathBuf> = entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .collect();
+            children.sort();
+            search.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
+    }
+    None
+}
+
+/// A JDK build to download with the checksum its distribution publishes
+#[derive(Debug, PartialEq, Eq)]
+struct JdkPackage {
+    url: String,
+    sha256: String,
+}
+
+#[derive(Deserialize)]
+struct AzulPackage {
+    package_uuid: String,
+}
+
+#[derive(Deserialize)]
+struct AzulPackageDetails {
+    download_url: String,
+    sha256_hash: String,
+}
+
+#[derive(Deserialize)]
+struct AdoptiumRelease {
+    binary: AdoptiumBinary,
+}
+
+#[derive(Deserialize)]
+struct AdoptiumBinary {
+    package: AdoptiumPackage,
+}
+
+#[derive(Deserialize)]
+struct AdoptiumPackage {
+    link: String,
+    checksum: String,
+}
+
+fn get_json<T: for<'de> Deserialize<'de>>(
+    client: &reqwest::blocking::Client,
+    url: &str,
+) -> anyhow::Result<T> {
+    let body = client
+        .get(url)
+        .send_recorded()
+        .and_then(|res| res.error_for_status())
+        .and_then(|res| res.text())
+        .context(format!("Failed to query {url}"))?;
+    serde_json::from_str(&body).context(format!("Failed to parse the response of {url}"))
+}
+
+/// Returns the latest generally available build of the JDK
+fn latest_package(
+    client: &reqwest::blocking::Client,
+    table: &JdkTable,
+) -> anyhow::Result<JdkPackage> {
+    let (os, arch) = platform(table.distribution)?;
+    let missing = || {
+        format!(
+            "No {} JDK {} build found for {os} {arch}",
+            table.distribution, table.version
+        )
+    };
+    match table.distribution {
+        JdkDistribution::Temurin => {
+            let url = format!(
+                "{ADOPTIUM_API}/{}/hotspot?os={os}&architecture={arch}&image_type=jdk&vendor=eclipse",
+                table.version
+            );
+            let releases: Vec<AdoptiumRelease> = get_json(client, &url)?;
+            let package = releases
+                .into_iter()
+                .next()
+                .context(missing())?
+                .binary
+                .package;
+            Ok(JdkPackage {
+                url: package.link,
+                sha256: package.checksum,
+            })
+        }
+        JdkDistribution::Zulu => {
+            let url = format!(
+                "{AZUL_API}?java_version={}&os={os}&arch={arch}&archive_type={}&java_package_type=jdk&javafx_bundled=false&release_status=ga&latest=true",
+                table.version,
+                archive_type().extension()
+            );
+            let packages: Vec<AzulPackage> = get_json(client, &url)?;
+            let package = packages.into_iter().next().context(missing())?;
+            // the list does not include checksums, the package details do
+            let details: AzulPackageDetails =
+                get_json(client, &format!("{AZUL_API}{}", package.package_uuid))?;
+            Ok(JdkPackage {
+                url: details.download_url,
+                sha256: details.sha256_hash,
+            })
+        }
+    }
+}
+
+/// Reads an octal number from a tar header field
+fn tar_octal(field: &[u8]) -> anyhow::Result<u64> {
+    let text = std::str::from_utf8(field)
+        .context("Invalid tar header number")?
+        .trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8).context(format!("Invalid tar header number {text}"))
+}
+
+fn tar_string(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Joins an archive entry name to output, rejecting names that escape it
+fn enclosed_path(output: &Path, name: &str) -> Option<PathBuf> {
+    let mut path = output.to_path_buf();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+/// True if a link at path pointing to target stays inside output. The target is
+/// relative to the directory of the link and is followed through the links
+/// extracted before it, so the parent of path must exist
+fn link_inside(output: &Path, path: &Path, target: &str) -> anyhow::Result<bool> {
+    let root = output
+        .canonicalize()
+        .context(format!("Failed to resolve {:?}", output))?;
+    let parent = path.parent().context("Invalid tar link path")?;
+    let mut resolved = parent
+        .canonicalize()
+        .context(format!("Failed to resolve {:?}", parent))?;
+    for component in Path::new(target).components() {
+        match component {
+            Component::Normal(part) => {
+                resolved.push(part);
+                if resolved.is_symlink() {
+                    // a link pointing at a missing file cannot lead anywhere else
+                    match resolved.canonicalize() {
+                        Ok(real) => resolved = real,
+                        Err(_) => return Ok(false),
+                    }
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            _ => return Ok(false),
+        }
+        if !resolved.starts_with(&root) {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Creates the directory and checks that it did not resolve outside output
+/// through a link extracted earlier
+fn create_enclosed_dir(output: &Path, dir: &Path) -> anyhow::Result<()> {
+    create_dir_all(dir).context(format!("Failed to create {:?}", dir))?;
+    let root = output
+        .canonicalize()
+        .context(format!("Failed to resolve {:?}", output))?;
+    let resolved = dir
+        .canonicalize()
+        .context(format!("Failed to resolve {:?}", dir))?;
+    if !resolved.starts_with(&root) {
+        bail!(
+            "Tar entry {:?} is written outside the archive through a link",
+            dir
+        );
+    }
+    Ok(())
+}
+
+/// Creates the parent directory of an entry, see [`create_enclosed_dir`], and
+/// removes a link already at path so that writing the entry does not follow it
+fn create_parent(output: &Path, path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        create_enclosed_dir(output, parent)?;
+    }
+    if path.is_symlink() {
+        remove_file(path).context(format!("Failed to remove the link {:?}", path))?;
+    }
+    Ok(())
+}
+
+/// Extracts a tar stream into output reporting its progress. Supports the ustar,
+/// gnu long name and pax path extensions used by JDK archives.
+///
+/// # Errors
+///
+/// Returns an error if the archive is malformed or the files cannot be written
+pub fn extract_tar<R: Read>(
+    mut reader: R,
+    output: &Path,
+    progress: &dyn ArchiveProgress,
+) -> anyhow::Result<()> {
+    // a stream has no index of its entries
+    progress.start(None, None);
+    let mut header = [0u8; 512];
+    let mut long_name: Option<String> = None;
+    let mut long_link: Option<String> = None;
+    loop {
+        crate::interrupt::check()?;
+        if let Err(err) = reader.read_exact(&mut header) {
+            if err.kind() == std::io::ErrorKind::UnexpectedEof {
+                break;
+            }
+            return Err(err).context("Failed to read tar header");
+        }
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let size = tar_octal(&header[124..136])?;
+        let mode = tar_octal(&header[100..108])? as u32;
+        let kind = header[156];
+        let mut name = tar_string(&header[0..100]);
+        let prefix = tar_string(&header[345..500]);
+        if &header[257..262] == b"ustar" && !prefix.is_empty() {
+            name = format!("{prefix}/{name}");
+        }
+        let link = long_link.take().unwrap_or(tar_string(&header[157..257]));
+        let name = long_name.take().unwrap_or(name);
+        let padding = (512 - size % 512) % 512;
+
+        match kind {
+            // gnu long name or long link name of the next entry
+            b'L' | b'K' => {
+                let mut data = Vec::new();
+                (&mut reader).take(size).read_to_end(&mut data)?;
+                let value = tar_string(&data);
+                if kind == b'L' {
+                    long_name = Some(value);
+                } else {
+                    long_link = Some(value);
+                }
+            }
+            // pax extended header of the next entry
+            b'x' => {
+                let mut data = Vec::new();
+                (&mut reader).take(size).read_to_end(&mut data)?;
+                for record in String::from_utf8_lossy(&data).lines() {
+                    let record = record.split_once(' ').map(|(_, r)| r).unwrap_or(record);
+                    match record.split_once('=') {
+                        Some(("path", value)) => long_name = Some(value.to_string()),
+                        Some(("linkpath", value)) => long_link = Some(value.to_string()),
+                        _ => {}
+                    }
+                }
+            }
+            b'5' => {
+                if let Some(path) = enclosed_path(output, &name) {
+                    create_enclosed_dir(output, &path)?;
+                }
+                std::io::copy(&mut (&mut reader).take(size), &mut std::io::sink())?;
+            }
+            b'0' | b'\0' | b'7' => {
+                let path = enclosed_path(output, &name)
+                    .context(format!("Tar entry {name} is outside the archive"))?;
+                create_parent(output, &path)?;
+                progress.entry(&name);
+                let mut file = BufWriter::new(ProgressWriter::new(
+                    File::create(&path).context(format!("Failed to create {:?}", path))?,
+                    progress,
+                ));
+                std::io::copy(&mut (&mut reader).take(size), &mut file)
+                    .context(format!("Failed to write {:?}", path))?;
+                file.flush()?;
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+                }
+                #[cfg(not(unix))]
+                let _ = mode;
+            }
+            b'1' | b'2' => {
+                let path = enclosed_path(output, &name)
+                    .context(format!("Tar entry {name} is outside the archive"))?;
+                create_parent(output, &path)?;
+                if kind == b'1' {
+                    let target = enclosed_path(output, &link)
+                        .context(format!("Tar link {link} is outside the archive"))?;
+                    std::fs::copy(&target, &path)
+                        .context(format!("Failed to link {:?} to {:?}", path, target))?;
+                } else {
+                    if !link_inside(output, &path, &link)? {
+                        bail!("Tar link {name} points outside the archive to {link}");
+                    }
+                    #[cfg(unix)]
+                    std::os::unix::fs::symlink(&link, &path)
+                        .context(format!("Failed to link {:?} to {link}", path))?;
+                }
+                std::io::copy(&mut (&mut reader).take(size), &mut std::io::sink())?;
+            }
+            _ => {
+                std::io::copy(&mut (&mut reader).take(size), &mut std::io::sink())?;
+            }
+        }
+        std::io::copy(&mut (&mut reader).take(padding), &mut std::io::sink())?;
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// Downloads the JDK archive to path showing its progress. Returns the archive type
+/// taken from the final url after redirects.
+fn download_archive(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    path: &Path,
+) -> anyhow::Result<ArchiveType> {
+    let res = client
+        .get(url)
+        .send_recorded()
+        .and_then(|res| res.error_for_status())
+        .context(format!("Failed to download {url}"))?;
+    let kind = if res.url().path().ends_with(".zip") {
+        ArchiveType::Zip
+    } else {
+        ArchiveType::TarGz
+    };
+    let total = res.content_length();
+    let bar = MULTI_PROGRESS_BAR.add(
+        ProgressBar::new(total.unwrap_or(0))
+            .with_style(
+                ProgressStyle::with_template(
+                    "  {bar:30} {bytes}/{total_bytes} {bytes_per_sec} {wide_msg}",
+                )
+                .unwrap(),
+            )
+            .with_message("Downloading JDK"),
+    );
+    let mut reader = BufReader::new(
+        ProgressReader::new(res, String::from("jdk"), total).with_progress_bar(bar.clone()),
+    );
+    let mut partial = PartialFile::new(path.to_path_buf());
+    let mut writer =
+        BufWriter::new(File::create(path).context(format!("Failed to create {:?}", path))?);
+    let result = crate::interrupt::copy(&mut reader, &mut writer).and_then(|_| writer.flush());
+    bar.finish_and_clear();
+    MULTI_PROGRESS_BAR.remove(&bar);
+    result.context(format!("Failed to download {url}"))?;
+    partial.complete();
+    Ok(kind)
+}
+
+/// Downloads and extracts the JDK into its install directory
+fn install_jdk(table: &JdkTable, install: &Path) -> anyhow::Result<()> {
+    if crate::is_offline() {
+        bail!(
+            "Offline mode: {} JDK {} is not installed and needs network access. Run again without --offline to download it",
+            table.distribution,
+            table.version
+        );
+    }
+    let client = client_builder()?
+        .build()
+        .context("Error creating http client")?;
+    let package = latest_package(&client, table)?;
+    let url = &package.url;
+    info!(target: "jdk", "Downloading {} JDK {} from {url}", table.distribution, table.version);
+
+    let parent = install.parent().context("Invalid JDK install directory")?;
+    create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+    let mut archive = install.as_os_str().to_owned();
+    archive.push(".download");
+    let archive = PathBuf::from(archive);
+    let mut partial = install.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    if partial.exists() {
+        remove_dir_all(&partial).context(format!("Failed to remove {:?}", partial))?;
+    }
+
+    let kind = download_archive(&client, url, &archive)?;
+    let sha256 = file_sha256(&archive).context(format!("Failed to hash {:?}", archive))?;
+    if !sha256.eq_ignore_ascii_case(package.sha256.trim()) {
+        let _ = remove_file(&archive);
+        bail!(
+            "The JDK archive from {url} has the sha256 {sha256}, but {} was published for it",
+            package.sha256
+        );
+    }
+    let result = match kind {
+        ArchiveType::Zip => {
+            let file = File::open(&archive).context(format!("Failed to open {:?}", archive))?;
+            let mut zip =
+                ZipArchive::new(file).context(format!("{:?} is not a valid zip", archive))?;
+            extract_zip(&mut zip, &partial, &BarProgress::new("Extracting JDK"))
+        }
+        ArchiveType::TarGz => {
+            let file = File::open(&archive).context(format!("Failed to open {:?}", archive))?;
+            extract_tar(
+                GzDecoder::new(BufReader::new(file)),
+                &partial,
+                &BarProgress::new("Extracting JDK"),
+            )
+        }
+    };
+    let _ = remove_file(&archive);
+    result.context("Failed to extract the JDK archive")?;
+
+    if find_java_home(&partial).is_none() {
+        bail!("The downloaded JDK archive does not contain bin/{JAVA_EXECUTABLE}");
+    }
+    rename(&partial, install).context(format!("Failed to move {:?} to {:?}", partial, install))?;
+    Ok(())
+}
+
+/// Returns the JAVA_HOME of the JDK configured in the `[jdk]` table, downloading it
+/// into labt home if it is not installed yet.
+///
+/// # Errors
+///
+/// Returns an error if the JDK is not available for this platform or the download fails
+pub fn provision_jdk(table: &JdkTable) -> anyhow::Result<PathBuf> {
+    let install = jdk_install_dir(table)?;
+    if !install.exists() {
+        install_jdk(table, &install).context(format!(
+            "Failed to install {} JDK {}",
+            table.distribution, table.version
+        ))?;
+    }
+    find_java_home(&install).context(format!(
+        "No bin/{JAVA_EXECUTABLE} found in {:?}. Remove the directory to download the JDK again",
+        install
+    ))
+}
+
+/// Points JAVA_HOME at the JDK and puts its bin directory first on PATH in the
+/// environment of the processes plugins start, so that they use it.
+pub fn export_java_home(env: &mut ProcessEnv, java_home: &Path) -> anyhow::Result<()> {
+    let bin = java_home.join("bin");
+    let path = match env.vars().get("PATH") {
+        Some(path) => path.into(),
+        None => std::env::var_os("PATH").unwrap_or_default(),
+    };
+    let paths = std::iter::once(bin).chain(std::env::split_paths(&path));
+    let path = std::env::join_paths(paths).context("Failed to add the JDK to PATH")?;
+    env.set(
+        JAVA_HOME.to_string(),
+        java_home.to_string_lossy().to_string(),
+    );
+    env.set("PATH".to_string(), path.to_string_lossy().to_string());
+    Ok(())
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn jdk_tar_extraction() {
+    fn header(name: &str, kind: u8, size: usize, link: &str) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000755");
+        header[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header
+    }
+    fn data(contents: &[u8]) -> Vec<u8> {
+        let mut data = contents.to_vec();
+        data.resize(contents.len().div_ceil(512) * 512, 0);
+        data
+    }
+    let long_name = format!("jdk-17/{}/release", "a".repeat(120));
+    let pax = format!("{} path={long_name}\n", long_name.len() + 7);
+
+    let mut tar = Vec::new();
+    tar.extend(header("jdk-17/", b'5', 0, ""));
+    tar.extend(header("jdk-17/bin/java", b'0', 4, ""));
+    tar.extend(data(b"java"));
+    tar.extend(header("jdk-17/bin/javac", b'2', 0, "java"));
+    tar.extend(header("PaxHeader", b'x', pax.len(), ""));
+    tar.extend(data(pax.as_bytes()));
+    tar.extend(header("truncated", b'0', 2, ""));
+    tar.extend(data(b"17"));
+    tar.extend(header("../escape", b'0', 1, ""));
+    tar.extend(data(b"x"));
+    tar.extend(vec![0u8; 1024]);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("jdk");
+    let result = extract_tar(tar.as_slice(), &root, &crate::archive::NoProgress);
+    let java_home = find_java_home(&root);
+    let release = std::fs::read_to_string(root.join(&long_name));
+
+    // the entry escaping the output directory is rejected
+    assert!(result.is_err());
+    assert_eq!(java_home, Some(root.join("jdk-17")));
+    assert_eq!(release.unwrap(), "17");
+}
+
+#[test]
+fn jdk_links_stay_inside_the_archive() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("jdk");
+    for dir in ["jdk-17/bin", "jdk-17/legal"] {
+        create_dir_all(root.join(dir)).unwrap();
+    }
+    let inside = |name: &str, target: &str| link_inside(&root, &root.join(name), target).unwrap();
+    assert!(inside("jdk-17/bin/javac", "java"));
+    assert!(inside("jdk-17/bin/javac", "../lib/javac"));
+    assert!(inside("jdk-17/legal/java.base", "../../jdk-17/legal"));
+    assert!(!inside("jdk-17/bin/javac", "../../.."));
+    assert!(!inside("jdk-17/lib", "/etc"));
+    assert!(!inside("lib", "../lib"));
+
+    // a link through an earlier link is followed to where it really points
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(".", root.join("a")).unwrap();
+        assert!(inside("a/b", "jdk-17"));
+        assert!(!inside("a/b", "../x"));
+        assert!(!inside("jdk-17/b", "../a/../../x"));
+    }
+}
+
+#[test]
+fn jdk_java_home_is_exported_to_plugin_processes() {
+    let mut env = ProcessEnv::default();
+    env.set("PATH".to_string(), "/usr/bin".to_string());
+    let java_home = Path::new("/opt/jdk-17");
+    export_java_home(&mut env, java_home).unwrap();
+    assert_eq!(env.vars()[JAVA_HOME], "/opt/jdk-17");
+    let path = std::env::join_paths([java_home.join("bin"), PathBuf::from("/usr/bin")]).unwrap();
+    assert_eq!(env.vars()["PATH"], path.to_string_lossy());
+}
+
+#[cfg(unix)]
+#[test]
+fn jdk_tar_entries_do_not_write_through_links() {
+    fn header(name: &str, kind: u8, size: usize, link: &str) -> Vec<u8> {
+        let mut header = vec![0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..107].copy_from_slice(b"0000644");
+        header[124..135].copy_from_slice(format!("{size:011o}").as_bytes());
+        header[156] = kind;
+        header[157..157 + link.len()].copy_from_slice(link.as_bytes());
+        header
+    }
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("jdk");
+
+    // a link that only escapes through the link before it
+    let mut tar = Vec::new();
+    tar.extend(header("a", b'2', 0, "."));
+    tar.extend(header("a/b", b'2', 0, "../x"));
+    tar.extend(vec![0u8; 1024]);
+    assert!(extract_tar(tar.as_slice(), &root, &crate::archive::NoProgress).is_err());
+    assert!(!tmp.path().join("x").exists());
+
+    // a file replaces a link at its path instead of writing to the link target
+    let mut tar = Vec::new();
+    tar.extend(header("target", b'0', 0, ""));
+    tar.extend(header("link", b'2', 0, "target"));
+    tar.extend(header("link", b'0', 5, ""));
+    let mut data = b"hello".to_vec();
+    data.resize(512, 0);
+    tar.extend(data);
+    // a size no allocation can hold
+    let mut long = header("long", b'L', 0, "");
+    long[124..135].copy_from_slice(b"77777777777");
+    tar.extend(long);
+    extract_tar(tar.as_slice(), &root, &crate::archive::NoProgress).unwrap();
+    assert_eq!(std::fs::read_to_string(root.join("target")).unwrap(), "");
+    assert_eq!(std::fs::read_to_string(root.join("link")).unwrap(), "hello");
+    assert!(!root.join("link").is_symlink());
+}
@@ -0,0 +1,723 @@
+use std::{
+    collections::BTreeMap,
+    fs::{create_dir_all, File},
+    io::{BufReader, Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Subcommand, ValueEnum};
+use indicatif::ProgressBar;
+use log::{info, warn};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use zip::ZipArchive;
+
+use crate::{
+    caching::{
+        graph::{load_graph, GraphSection},
+        Cache, CacheType,
+    },
+    config::{global::client_builder, lock::load_labt_lock},
+    get_home, get_project_root,
+    http_recording::SendRecorded,
+    pom::{parse_pom, License, Project},
+    MULTI_PROGRESS_BAR,
+};
+
+use super::{artifacts::split_classpaths, resolve::ProjectDep, Submodule};
+
+/// Directory under the labt cache where downloaded license texts are stored
+pub const LICENSES_CACHE_DIR: &str = "licenses";
+/// How far up the parent poms to look for inherited licenses
+pub const MAX_PARENT_DEPTH: usize = 10;
+
+#[derive(Clone, Args)]
+pub struct LicensesArgs {
+    #[command(subcommand)]
+    command: LicensesSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum LicensesSubcommands {
+    /// Downloads dependency licenses and notices and assembles a third-party notices file
+    Fetch(FetchArgs),
+    /// Prints the license of each locked dependency as a table
+    Report(ReportArgs),
+}
+
+#[derive(Clone, Args)]
+pub struct FetchArgs {
+    /// The notices file to generate. Relative paths are resolved from the project root
+    #[arg(short, long, default_value = "open_source_licenses.html")]
+    output: PathBuf,
+    /// The format of the generated notices file
+    #[arg(short, long, value_enum, default_value_t = NoticesFormat::Html)]
+    format: NoticesFormat,
+}
+
+#[derive(Clone, Args)]
+pub struct ReportArgs {
+    /// The format of the report
+    #[arg(short, long, value_enum, default_value_t = ReportFormat::Table)]
+    format: ReportFormat,
+    /// Write the report to this file instead of stdout. Relative paths are resolved
+    /// from the project root
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Exit with a non-zero status if a dependency does not declare a license
+    #[arg(long)]
+    fail_on_unknown: bool,
+    /// Include test dependencies and annotation processors
+    #[arg(long)]
+    all: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum ReportFormat {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum NoticesFormat {
+    Html,
+    Text,
+}
+
+pub struct Licenses {
+    args: LicensesArgs,
+}
+
+impl Licenses {
+    pub fn new(args: &LicensesArgs) -> Self {
+        Licenses { args: args.clone() }
+    }
+}
+
+/// The license and notice information collected for a single dependency
+#[derive(Debug, Default)]
+pub struct DependencyNotice {
+    /// group_id:artifact_id:version
+    pub coordinate: String,
+    pub licenses: Vec<License>,
+    /// Notice files found in the artifact as (file name, contents)
+    pub notices: Vec<(String, String)>,
+}
+
+/// A row of the license report
+#[derive(Debug, Default, Serialize)]
+pub struct LicenseEntry {
+    /// group_id:artifact_id:version
+    pub coordinate: String,
+    pub scope: String,
+    pub licenses: Vec<License>,
+}
+
+impl LicenseEntry {
+    /// The license names, using the SPDX id where the license is recognized
+    fn license_names(&self) -> Vec<String> {
+        self.licenses
+            .iter()
+            .map(|license| match spdx_license_id(license) {
+                Some(id) => id.to_string(),
+                None => license.name.clone(),
+            })
+            .collect()
+    }
+    /// The dependency pom does not declare any license
+    pub fn is_unknown(&self) -> bool {
+        self.licenses.is_empty()
+    }
+}
+
+/// Reads the licenses declared in the cached pom of this dependency. If the pom
+/// does not declare any, its parent poms are checked.
+///
+/// # Errors
+///
+/// Returns an error if the pom is not cached or fails to parse
+pub fn read_cached_licenses(
+    group_id: &str,
+    artifact_id: &str,
+    version: &str,
+) -> anyhow::Result<Vec<License>> {
+    let mut coordinate = (
+        group_id.to_string(),
+        artifact_id.to_string(),
+        version.to_string(),
+    );
+
+    for _ in 0..MAX_PARENT_DEPTH {
+        let (group_id, artifact_id, version) = coordinate;
+        let mut cache = Cache::new(
+            group_id.clone(),
+            artifact_id.clone(),
+            version.clone(),
+            CacheType::POM,
+        );
+        cache.use_labt_home()?;
+        let file = cache.open().context(format!(
+            "Failed to open cached pom for {}:{}:{}. Try running labt resolve",
+            group_id, artifact_id, version
+        ))?;
+        let project = parse_pom(
+            BufReader::new(file),
+            Project::new(&group_id, &artifact_id, &version),
+        )
+        .context(format!(
+            "Failed to parse pom for {}:{}:{}",
+            group_id, artifact_id, version
+        ))?;
+
+        if !project.get_licenses().is_empty() {
+            return Ok(project.get_licenses().clone());
+        }
+
+        match &project.parent {
+            Some(parent) => {
+                coordinate = (
+                    parent.group_id.clone(),
+                    parent.artifact_id.clone(),
+                    parent.version.clone(),
+                )
+            }
+            None => break,
+        }
+    }
+
+    Ok(Vec::new())
+}
+
+/// Maps a license declared in a pom to its SPDX identifier. Poms use free form
+/// names so the common spellings and license urls are matched.
+/// Returns None if the license is not recognized
+pub fn spdx_license_id(license: &License) -> Option<&'static str> {
+    let name = license.name.to_lowercase();
+    let url = license.url.as_deref().unwrap_or_default().to_lowercase();
+    let has = |patterns: &[&str]| {
+        patterns
+            .iter()
+            .any(|pattern| name.contains(pattern) || url.contains(pattern))
+    };
+
+    if has(&[
+        "apache-2.0",
+        "apache 2",
+        "apache license, version 2",
+        "license-2.0",
+    ]) || name == "apache license 2.0"
+    {
+        Some("Apache-2.0")
+    } else if has(&["mit license", "opensource.org/licenses/mit"]) || name == "mit" {
+        Some("MIT")
+    } else if has(&["bsd-3-clause", "bsd 3-clause", "new bsd", "revised bsd"]) {
+        Some("BSD-3-Clause")
+    } else if has(&["bsd-2-clause", "bsd 2-clause", "simplified bsd"]) {
+        Some("BSD-2-Clause")
+    } else if has(&[
+        "epl-2.0",
+        "eclipse public license - v 2.0",
+        "eclipse public license 2.0",
+    ]) {
+        Some("EPL-2.0")
+    } else if has(&[
+        "epl-v10",
+        "eclipse public license - v 1.0",
+        "eclipse public license 1.0",
+    ]) {
+        Some("EPL-1.0")
+    } else if has(&[
+        "lgpl-2.1",
+        "lgpl, version 2.1",
+        "lesser general public license, version 2.1",
+    ]) {
+        Some("LGPL-2.1-only")
+    } else if has(&[
+        "mpl-2.0",
+        "mozilla public license 2.0",
+        "mozilla public license, version 2.0",
+    ]) {
+        Some("MPL-2.0")
+    } else if has(&["cddl"]) {
+        Some("CDDL-1.0")
+    } else if has(&["creativecommons.org/publicdomain/zero", "cc0"]) {
+        Some("CC0-1.0")
+    } else if has(&["unlicense"]) {
+        Some("Unlicense")
+    } else {
+        None
+    }
+}
+
+/// Returns true if the archive entry is a license or notice file
+/// e.g. META-INF/NOTICE.txt or LICENSE
+fn is_notice_file(name: &str) -> bool {
+    let (dir, file) = match name.rsplit_once('/') {
+        Some((dir, file)) => (dir, file),
+        None => ("", name),
+    };
+    if !dir.is_empty() && !dir.eq_ignore_ascii_case("META-INF") {
+        return false;
+    }
+    let file = file.to_uppercase();
+    file.starts_with("NOTICE") || file.starts_with("LICENSE")
+}
+
+/// Extracts license and notice files bundled in the cached artifact archive
+fn read_notices(dep: &ProjectDep) -> anyhow::Result<Vec<(String, String)>> {
+    let mut cache = Cache::from(dep);
+    cache.use_labt_home()?;
+    if !cache.exists() {
+        return Ok(Vec::new());
+    }
+    let path = cache.get_path()?;
+    let file = File::open(&path).context(format!("Failed to open {:?}", path))?;
+    let mut archive = match ZipArchive::new(file) {
+        Ok(archive) => archive,
+        // not all packaging types are archives
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut notices = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        if !entry.is_file() || !is_notice_file(entry.name()) {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let mut contents = String::new();
+        if entry.read_to_string(&mut contents).is_ok() {
+            notices.push((name, contents));
+        }
+    }
+    Ok(notices)
+}
+
+/// Returns the license text at this url. Texts are cached in the labt home
+/// so subsequent runs do not hit the network.
+/// Returns None if the url does not serve plain text e.g. an html page.
+fn fetch_license_text(
+    client: &reqwest::blocking::Client,
+    url: &str,
+) -> anyhow::Result<Option<String>> {
+    let mut path = get_home().context("Unable to get home dir for caching")?;
+    path.push("cache");
+    path.push(LICENSES_CACHE_DIR);
+    create_dir_all(&path).context(format!("Failed to create directory {:?}", path))?;
+
+    let mut sha = Sha1::new();
+    sha.update(url.as_bytes());
+    path.push(format!("{:x}.txt", sha.finalize()));
+
+    if path.exists() {
+        let mut text = String::new();
+        File::open(&path)?.read_to_string(&mut text)?;
+        return Ok(Some(text));
+    }
+    if crate::is_offline() {
+        bail!("Offline mode: the license at {url} is not in the labt cache and needs network access. Run again without --offline to download it");
+    }
+
+    let res = client
+        .get(url)
+        .send_recorded()
+        .context(format!("Failed to download license at {url}"))?
+        .error_for_status()
+        .context(format!("Failed to download license at {url}"))?;
+
+    let is_html = res
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("html"));
+    if is_html {
+        return Ok(None);
+    }
+
+    let text = res.text()?;
+    File::create(&path)
+        .context(format!("Failed to create license cache file {:?}", path))?
+        .write_all(text.as_bytes())?;
+
+    Ok(Some(text))
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders the notices as a single html page
+pub fn render_html(notices: &[DependencyNotice], texts: &BTreeMap<String, String>) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Open source licenses</title>\n</head>\n<body>\n<h1>Open source licenses</h1>\n",
+    );
+
+    for notice in notices {
+        html.push_str(&format!(
+            "<h2>{}</h2>\n<ul>\n",
+            escape_html(&notice.coordinate)
+        ));
+        for license in &notice.licenses {
+            match &license.url {
+                Some(url) => html.push_str(&format!(
+                    "<li><a href=\"{}\">{}</a></li>\n",
+                    escape_html(url),
+                    escape_html(&license.name)
+                )),
+                None => html.push_str(&format!("<li>{}</li>\n", escape_html(&license.name))),
+            }
+        }
+        if notice.licenses.is_empty() {
+            html.push_str("<li>Unknown license</li>\n");
+        }
+        html.push_str("</ul>\n");
+        for (name, contents) in &notice.notices {
+            html.push_str(&format!(
+                "<h3>{}</h3>\n<pre>{}</pre>\n",
+                escape_html(name),
+                escape_html(contents)
+            ));
+        }
+    }
+
+    if !texts.is_empty() {
+        html.push_str("<h1>License texts</h1>\n");
+        for (url, text) in texts {
+            html.push_str(&format!(
+                "<h2 id=\"{0}\">{0}</h2>\n<pre>{1}</pre>\n",
+                escape_html(url),
+                escape_html(text)
+            ));
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Renders the notices as plain text
+pub fn render_text(notices: &[DependencyNotice], texts: &BTreeMap<String, String>) -> String {
+    let separator = "=".repeat(80);
+    let mut out = String::new();
+
+    for notice in notices {
+        out.push_str(&format!("{}\n{separator}\n", notice.coordinate));
+        for license in &notice.licenses {
+            match &license.url {
+                Some(url) => out.push_str(&format!("  {} ({})\n", license.name, url)),
+                None => out.push_str(&format!("  {}\n", license.name)),
+            }
+        }
+        if notice.licenses.is_empty() {
+            out.push_str("  Unknown license\n");
+        }
+        for (name, contents) in &notice.notices {
+            out.push_str(&format!("\n--- {name} ---\n{contents}\n"));
+        }
+        out.push('\n');
+    }
+
+    for (url, text) in texts {
+        out.push_str(&format!("{separator}\n{url}\n{separator}\n{text}\n\n"));
+    }
+    out
+}
+
+/// Quotes a csv field if it contains a separator, quote or newline
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+pub fn render_report(entries: &[LicenseEntry], format: ReportFormat) -> anyhow::Result<String> {
+    let mut out = String::new();
+    match format {
+        ReportFormat::Table => {
+            let width = entries
+                .iter()
+                .map(|entry| entry.coordinate.len())
+                .max()
+                .unwrap_or(0)
+                .max("DEPENDENCY".len());
+            out.push_str(&format!(
+                "{:width$}  {:8}  LICENSE\n",
+                "DEPENDENCY", "SCOPE"
+            ));
+            for entry in entries {
+                let names = entry.license_names();
+                let names = if names.is_empty() {
+                    String::from("UNKNOWN")
+                } else {
+                    names.join(", ")
+                };
+                out.push_str(&format!(
+                    "{:width$}  {:8}  {}\n",
+                    entry.coordinate, entry.scope, names
+                ));
+            }
+        }
+        ReportFormat::Csv => {
+            out.push_str("dependency,scope,license,url\n");
+            for entry in entries {
+                let urls: Vec<&str> = entry
+                    .licenses
+                    .iter()
+                    .filter_map(|license| license.url.as_deref())
+                    .collect();
+                out.push_str(&format!(
+                    "{},{},{},{}\n",
+                    csv_field(&entry.coordinate),
+                    csv_field(&entry.scope),
+                    csv_field(&entry.license_names().join("; ")),
+                    csv_field(&urls.join("; "))
+                ));
+            }
+        }
+        ReportFormat::Json => {
+            out = serde_json::to_string_pretty(entries)
+                .context("Failed to serialize license report")?;
+            out.push('\n');
+        }
+    }
+    Ok(out)
+}
+
+fn report(args: &ReportArgs) -> anyhow::Result<()> {
+    let root = get_project_root().context("Failed to get project root directory")?;
+    // the graph cache has the licenses of the cached poms and their parents
+    let graph = load_graph(&root)?;
+
+    let entries: Vec<LicenseEntry> = graph
+        .nodes
+        .into_iter()
+        .filter(|node| args.all || node.section == GraphSection::Project)
+        .map(|node| LicenseEntry {
+            coordinate: node.coordinate(),
+            scope: node.scope,
+            licenses: node.licenses,
+        })
+        .collect();
+
+    let contents = render_report(&entries, args.format)?;
+    match &args.output {
+        Some(output) => {
+            let output = root.join(output);
+            std::fs::write(&output, contents)
+                .context(format!("Failed to write license report {:?}", output))?;
+            info!(target: "licenses", "Wrote license report of {} dependencies to {:?}", entries.len(), output);
+        }
+        None => print!("{contents}"),
+    }
+
+    check_unknown(&entries, args.fail_on_unknown)
+}
+
+/// Warns about the dependencies without a license, or fails with fail_on_unknown
+/// so that labt exits with an error status
+fn check_unknown(entries: &[LicenseEntry], fail_on_unknown: bool) -> anyhow::Result<()> {
+    let unknown: Vec<&str> = entries
+        .iter()
+        .filter(|entry| entry.is_unknown())
+        .map(|entry| entry.coordinate.as_str())
+        .collect();
+    if !unknown.is_empty() {
+        if fail_on_unknown {
+            bail!(
+                "{} dependencies do not declare a license: {}",
+                unknown.len(),
+                unknown.join(", ")
+            );
+        }
+        warn!(target: "licenses", "{} dependencies do not declare a license", unknown.len());
+    }
+    Ok(())
+}
+
+fn fetch(args: &FetchArgs) -> anyhow::Result<()> {
+    let lock = load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+    let client = client_builder()?
+        .build()
+        .context("Failed to initialize license download client")?;
+
+    // only the dependencies packaged into the apk, like staging does
+    let (_, packaged) = split_classpaths(lock.resolved.iter().map(|dep| (&dep.scope, dep)));
+    let progress = MULTI_PROGRESS_BAR.add(ProgressBar::new(packaged.len() as u64));
+
+    let mut notices = Vec::with_capacity(packaged.len());
+    let mut texts: BTreeMap<String, String> = BTreeMap::new();
+
+    for dep in packaged {
+        progress.inc(1);
+        let coordinate = format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version);
+        let licenses = read_cached_licenses(&dep.group_id, &dep.artifact_id, &dep.version)
+            .unwrap_or_else(|err| {
+                warn!(target: "licenses", "{:?}", err);
+                Vec::new()
+            });
+
+        for url in licenses.iter().filter_map(|license| license.url.as_ref()) {
+            if texts.contains_key(url) {
+                continue;
+            }
+            match fetch_license_text(&client, url) {
+                Ok(Some(text)) => {
+                    texts.insert(url.clone(), text);
+                }
+                Ok(None) => {}
+                Err(err) => warn!(target: "licenses", "{:?}", err),
+            }
+        }
+
+        let dep_notices = read_notices(dep).context(format!(
+            "Failed to read notice files from {} archive",
+            coordinate
+        ))?;
+
+        notices.push(DependencyNotice {
+            coordinate,
+            licenses,
+            notices: dep_notices,
+        });
+    }
+    progress.finish_and_clear();
+
+    let contents = match args.format {
+        NoticesFormat::Html => render_html(&notices, &texts),
+        NoticesFormat::Text => render_text(&notices, &texts),
+    };
+
+    let output = if args.output.is_absolute() {
+        args.output.clone()
+    } else {
+        get_project_root()
+            .context("Failed to get project root directory")?
+            .join(&args.output)
+    };
+    if let Some(parent) = output.parent() {
+        create_dir_all(parent).context(format!("Failed to create directory {:?}", parent))?;
+    }
+    File::create(&output)
+        .context(format!("Failed to create notices file {:?}", output))?
+        .write_all(contents.as_bytes())
+        .context(format!("Failed to write notices file {:?}", output))?;
+
+    info!(target: "licenses", "Generated notices for {} dependencies at {:?}", notices.len(), output);
+    Ok(())
+}
+
+impl Submodule for Licenses {
+    fn run(&mut self) -> anyhow::Result<()> {
+        match &self.args.command {
+            LicensesSubcommands::Fetch(args) => fetch(args),
+            LicensesSubcommands::Report(args) => report(args),
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn licenses_notice_file_names() {
+    assert!(is_notice_file("META-INF/NOTICE"));
+    assert!(is_notice_file("META-INF/LICENSE.txt"));
+    assert!(is_notice_file("meta-inf/notice.md"));
+    assert!(is_notice_file("LICENSE"));
+    assert!(!is_notice_file("res/raw/license.txt"));
+    assert!(!is_notice_file("META-INF/MANIFEST.MF"));
+}
+
+#[test]
+fn licenses_render_html_escapes() {
+    let notices = vec![DependencyNotice {
+        coordinate: String::from("com.example:lib:1.0"),
+        licenses: vec![License {
+            name: String::from("Apache <2.0>"),
+            url: Some(String::from("https://example.com/LICENSE?a=1&b=2")),
+        }],
+        notices: vec![(String::from("META-INF/NOTICE"), String::from("(c) A & B"))],
+    }];
+    let html = render_html(&notices, &BTreeMap::new());
+    assert!(html.contains(
+        "<li><a href=\"https://example.com/LICENSE?a=1&amp;b=2\">Apache &lt;2.0&gt;</a></li>"
+    ));
+    assert!(html.contains("<pre>(c) A &amp; B</pre>"));
+
+    let text = render_text(&notices, &BTreeMap::new());
+    assert_eq!(text.lines().next(), Some("com.example:lib:1.0"));
+}
+
+#[test]
+fn licenses_report_formats() {
+    let entries = vec![
+        LicenseEntry {
+            coordinate: String::from("androidx.core:core:1.12.0"),
+            scope: String::from("compile"),
+            licenses: vec![License {
+                name: String::from("The Apache Software License, Version 2.0"),
+                url: Some(String::from(
+                    "http://www.apache.org/licenses/LICENSE-2.0.txt",
+                )),
+            }],
+        },
+        LicenseEntry {
+            coordinate: String::from("com.example:lib:1.0"),
+            scope: String::from("runtime"),
+            licenses: vec![License {
+                name: String::from("Example, \"custom\" license"),
+                url: None,
+            }],
+        },
+        LicenseEntry {
+            coordinate: String::from("com.example:other:2.0"),
+            scope: String::from("compile"),
+            licenses: Vec::new(),
+        },
+    ];
+    assert!(!entries[0].is_unknown());
+    assert!(entries[2].is_unknown());
+    assert!(check_unknown(&entries[..2], true).is_ok());
+    assert!(check_unknown(&entries, false).is_ok());
+    let err = check_unknown(&entries, true).unwrap_err();
+    assert!(err.to_string().contains("com.example:other:2.0"));
+
+    let table = render_report(&entries, ReportFormat::Table).unwrap();
+    assert_eq!(
+        table.lines().collect::<Vec<_>>(),
+        vec![
+            "DEPENDENCY                 SCOPE     LICENSE",
+            "androidx.core:core:1.12.0  compile   Apache-2.0",
+            "com.example:lib:1.0        runtime   Example, \"custom\" license",
+            "com.example:other:2.0      compile   UNKNOWN",
+        ]
+    );
+
+    let csv = render_report(&entries, ReportFormat::Csv).unwrap();
+    assert_eq!(
+        csv.lines().nth(2),
+        Some("com.example:lib:1.0,runtime,\"Example, \"\"custom\"\" license\",")
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&render_report(&entries, ReportFormat::Json).unwrap()).unwrap();
+    assert_eq!(
+        json[0]["licenses"][0]["url"],
+        "http://www.apache.org/licenses/LICENSE-2.0.txt"
+    );
+}
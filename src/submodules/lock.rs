@@ -0,0 +1,651 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    fs::create_dir_all,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::{Args, Subcommand};
+use git2::Repository;
+use indicatif::HumanBytes;
+use log::info;
+
+use crate::{
+    caching::{download::artifact_size, Cache},
+    config::{
+        get_config, get_resolvers_from_config,
+        global::client_builder,
+        lock::{lock_file_in, merge_locks, replace_lock, strings::LOCK_FILE, LabtLock, LockMerge},
+    },
+    get_project_root,
+    paths::sanitize_component,
+    version::ComparableVersion,
+};
+
+use super::{
+    fingerprint::PROJECT_STATE_DIR,
+    resolve::{resolve_merge_conflicts, ProjectDep, ResolveOptions},
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct LockArgs {
+    #[command(subcommand)]
+    command: LockSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum LockSubcommands {
+    /// Summarizes the artifacts added, removed and changed between two lock files
+    Diff(DiffArgs),
+    /// Merges two lock files, resolving again only the entries both sides changed.
+    /// Can be used as a git merge driver
+    Merge(MergeArgs),
+    /// Saves and restores named copies of Labt.lock e.g. while bisecting a dependency regression
+    Snapshot(SnapshotArgs),
+}
+
+#[derive(Clone, Args)]
+pub struct DiffArgs {
+    /// The old lock file, or a git revision to read Labt.lock from. Defaults to HEAD
+    old: Option<String>,
+    /// The new lock file, or a git revision to read Labt.lock from.
+    /// Defaults to Labt.lock in the project
+    new: Option<String>,
+    /// Write the summary as a markdown list e.g. for a changelog
+    #[arg(long)]
+    markdown: bool,
+    /// Do not look up the sizes of artifacts that are not in the labt cache
+    #[arg(long)]
+    no_sizes: bool,
+}
+
+/// To use as a git merge driver add `Labt.lock merge=labt` to .gitattributes and run
+/// `git config merge.labt.driver "labt lock merge %O %A %B"`
+#[derive(Clone, Args)]
+pub struct MergeArgs {
+    /// The lock file of the common ancestor (%O)
+    base: PathBuf,
+    /// Our lock file (%A). The merged lock is written here unless --output is given
+    ours: PathBuf,
+    /// Their lock file (%B)
+    theirs: PathBuf,
+    /// Write the merged lock file to this path instead
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Keep the higher version of conflicting entries instead of resolving them again
+    #[arg(long)]
+    no_resolve: bool,
+}
+
+#[derive(Clone, Args)]
+pub struct SnapshotArgs {
+    #[command(subcommand)]
+    command: SnapshotSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum SnapshotSubcommands {
+    /// Copies the current lock file to a named snapshot
+    Save(SnapshotSaveArgs),
+    /// Replaces the lock file with a named snapshot
+    Restore(SnapshotRestoreArgs),
+    /// Lists the saved snapshots
+    List,
+}
+
+#[derive(Clone, Args)]
+pub struct SnapshotSaveArgs {
+    /// The snapshot name
+    name: String,
+    /// Replace a snapshot with the same name
+    #[arg(short, long)]
+    force: bool,
+}
+
+#[derive(Clone, Args)]
+pub struct SnapshotRestoreArgs {
+    /// The snapshot name
+    name: String,
+}
+
+pub struct Lock {
+    args: LockArgs,
+}
+
+impl Lock {
+    pub fn new(args: &LockArgs) -> Self {
+        Lock { args: args.clone() }
+    }
+}
+
+/// How a locked artifact changed between two lock files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Upgraded { from: String },
+    Downgraded { from: String },
+}
+
+impl ChangeKind {
+    fn title(&self) -> &'static str {
+        match self {
+            ChangeKind::Added => "Added",
+            ChangeKind::Removed => "Removed",
+            ChangeKind::Upgraded { .. } => "Upgraded",
+            ChangeKind::Downgraded { .. } => "Downgraded",
+        }
+    }
+}
+
+/// An artifact that changed between two lock files
+#[derive(Debug, Clone)]
+pub struct LockChange {
+    /// The lock file section e.g. project, test or processor
+    pub section: &'static str,
+    pub kind: ChangeKind,
+    /// The artifact in the new lock, or the old one if removed
+    pub dep: ProjectDep,
+}
+
+/// Compares two lock files by group_id:artifact_id within each section
+pub fn diff_locks(old: &LabtLock, new: &LabtLock) -> Vec<LockChange> {
+    let mut changes = Vec::new();
+    for ((section, old), (_, new)) in old.sections().into_iter().zip(new.sections()) {
+        let key = |dep: &ProjectDep| format!("{}:{}", dep.group_id, dep.artifact_id);
+        let old: BTreeMap<String, &ProjectDep> = old.iter().map(|dep| (key(dep), dep)).collect();
+        let new: BTreeMap<String, &ProjectDep> = new.iter().map(|dep| (key(dep), dep)).collect();
+
+        for (coordinate, dep) in &new {
+            let kind = match old.get(coordinate) {
+                None => ChangeKind::Added,
+                Some(previous) if previous.version == dep.version => continue,
+                Some(previous) => {
+                    let from = previous.version.clone();
+                    if ComparableVersion::new(&dep.version) > ComparableVersion::new(&from) {
+                        ChangeKind::Upgraded { from }
+                    } else {
+                        ChangeKind::Downgraded { from }
+                    }
+                }
+            };
+            changes.push(LockChange {
+                section,
+                kind,
+                dep: (*dep).clone(),
+            });
+        }
+        for (coordinate, dep) in &old {
+            if !new.contains_key(coordinate) {
+                changes.push(LockChange {
+                    section,
+                    kind: ChangeKind::Removed,
+                    dep: (*dep).clone(),
+                });
+            }
+        }
+    }
+    changes
+}
+
+fn merge(args: &MergeArgs) -> anyhow::Result<()> {
+    let read = |path: &PathBuf| -> anyhow::Result<LabtLock> {
+        std::fs::read_to_string(path)
+            .context(format!("Failed to read {:?}", path))?
+            .parse::<LabtLock>()
+            .context(format!("Failed to parse the lock file {:?}", path))
+    };
+    let base = read(&args.base)?;
+    let ours = read(&args.ours)?;
+    let theirs = read(&args.theirs)?;
+
+    let LockMerge {
+        mut lock,
+        conflicts,
+    } = merge_locks(&base, &ours, &theirs);
+    if !conflicts.is_empty() {
+        let names: Vec<String> = conflicts
+            .sections()
+            .iter()
+            .flat_map(|(_, deps)| deps.iter())
+            .map(|dep| format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version))
+            .collect();
+        if args.no_resolve {
+            info!(target: "lock", "Keeping the higher version of conflicting entries: {}", names.join(", "));
+            lock.resolved.extend(conflicts.resolved);
+            lock.test.extend(conflicts.test);
+            lock.processors.extend(conflicts.processors);
+        } else {
+            info!(target: "lock", "Resolving conflicting entries: {}", names.join(", "));
+            let config = get_config()?;
+            let resolvers =
+                get_resolvers_from_config(&config).context("Failed to get resolvers")?;
+            resolve_merge_conflicts(
+                &mut lock,
+                &conflicts,
+                resolvers,
+                ResolveOptions::try_from(&config)?,
+            )
+            .context("Failed to resolve the conflicting lock entries")?;
+        }
+    }
+
+    let output = args.output.as_ref().unwrap_or(&args.ours);
+    replace_lock(output, &lock)
+}
+
+/// Looks up the artifact size in the labt cache, falling back to the repository
+fn dep_size(dep: &ProjectDep, client: Option<&reqwest::blocking::Client>) -> Option<u64> {
+    if dep.packaging == "pom" {
+        return Some(0);
+    }
+    let mut cache = Cache::from(dep);
+    if cache.use_labt_home().is_ok() {
+        if let Some(size) = cache
+            .get_path()
+            .ok()
+            .and_then(|path| path.metadata().ok())
+            .map(|metadata| metadata.len())
+        {
+            return Some(size);
+        }
+    }
+    client.and_then(|client| artifact_size(client, dep))
+}
+
+fn signed_bytes(delta: i64) -> String {
+    let sign = if delta < 0 { "-" } else { "+" };
+    format!("{sign}{}", HumanBytes(delta.unsigned_abs()))
+}
+
+/// Renders the changes grouped by kind. sizes returns the artifact size of a
+/// dependency if it is known.
+pub fn render_diff(
+    changes: &[LockChange],
+    markdown: bool,
+    sizes: impl Fn(&ProjectDep) -> Option<u64>,
+) -> String {
+    let mut out = String::new();
+    if changes.is_empty() {
+        out.push_str("No dependency changes\n");
+        return out;
+    }
+    let mut total: i64 = 0;
+    let mut unknown = false;
+    for title in ["Added", "Removed", "Upgraded", "Downgraded"] {
+        let group: Vec<&LockChange> = changes.iter().filter(|c| c.kind.title() == title).collect();
+        if group.is_empty() {
+            continue;
+        }
+        if markdown {
+            let _ = writeln!(out, "### {title}");
+        } else {
+            let _ = writeln!(out, "{title}:");
+        }
+        for change in group {
+            let dep = &change.dep;
+            let (version, delta) = match &change.kind {
+                ChangeKind::Added => (dep.version.clone(), sizes(dep).map(|s| s as i64)),
+                ChangeKind::Removed => (dep.version.clone(), sizes(dep).map(|s| -(s as i64))),
+                ChangeKind::Upgraded { from } | ChangeKind::Downgraded { from } => {
+                    let old = ProjectDep {
+                        version: from.clone(),
+                        ..dep.clone()
+                    };
+                    let delta = sizes(dep)
+                        .zip(sizes(&old))
+                        .map(|(new, old)| new as i64 - old as i64);
+                    (format!("{from} -> {}", dep.version), delta)
+                }
+            };
+            match delta {
+                Some(delta) => total += delta,
+                None => unknown = true,
+            }
+            let size = delta
+                .map(signed_bytes)
+                .unwrap_or(String::from("size unknown"));
+            let section = if change.section == "project" {
+                String::new()
+            } else {
+                format!(" [{}]", change.section)
+            };
+            let coordinate = format!("{}:{}", dep.group_id, dep.artifact_id);
+            if markdown {
+                let _ = writeln!(out, "- `{coordinate}` {version} ({size}){section}");
+            } else {
+                let _ = writeln!(out, "  {coordinate} {version} ({size}){section}");
+            }
+        }
+        if markdown {
+            out.push('\n');
+        }
+    }
+    let _ = writeln!(
+        out,
+        "Total size change: {}{}",
+        signed_bytes(total),
+        if unknown { " (some sizes unknown)" } else { "" }
+    );
+    out
+}
+
+/// The directory in the project state directory where lock snapshots are kept.
+/// They are outside build/ so that labt clean leaves them for a rollback
+pub const SNAPSHOT_DIR: &str = "lock-snapshots";
+const SNAPSHOT_EXTENSION: &str = "lock";
+
+fn snapshot_dir(root: &Path) -> PathBuf {
+    root.join(PROJECT_STATE_DIR).join(SNAPSHOT_DIR)
+}
+
+/// The file a named snapshot of the project at root is stored in
+pub fn snapshot_path(root: &Path, name: &str) -> PathBuf {
+    snapshot_dir(root).join(format!("{}.{SNAPSHOT_EXTENSION}", sanitize_component(name)))
+}
+
+/// Returns the names of the snapshots of the project at root, sorted
+///
+/// # Errors
+///
+/// Returns an error if the snapshot directory cannot be read
+pub fn list_snapshots(root: &Path) -> anyhow::Result<Vec<String>> {
+    let dir = snapshot_dir(root);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = dir
+        .read_dir()
+        .context(format!("Failed to read {:?}", dir))?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext == SNAPSHOT_EXTENSION)
+        })
+        .filter_map(|path| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+/// Copies the lock file of the project at root to a named snapshot. Returns the
+/// snapshot path
+///
+/// # Errors
+///
+/// Returns an error if there is no lock file or the snapshot exists and force is false
+pub fn save_snapshot(root: &Path, name: &str, force: bool) -> anyhow::Result<PathBuf> {
+    let lock = lock_file_in(root);
+    if !lock.exists() {
+        bail!("There is no {:?} to snapshot. Run labt resolve first", lock);
+    }
+    let path = snapshot_path(root, name);
+    if path.exists() && !force {
+        bail!("Snapshot {name} already exists. Use --force to replace it");
+    }
+    let dir = snapshot_dir(root);
+    create_dir_all(&dir).context(format!("Failed to create {:?}", dir))?;
+    std::fs::copy(&lock, &path).context(format!("Failed to copy {:?} to {:?}", lock, path))?;
+    Ok(path)
+}
+
+/// Replaces the lock file of the project at root with a named snapshot. Returns
+/// the changes from the replaced lock file
+///
+/// # Errors
+///
+/// Returns an error if the snapshot does not exist or is not a valid lock file
+pub fn restore_snapshot(root: &Path, name: &str) -> anyhow::Result<Vec<LockChange>> {
+    let path = snapshot_path(root, name);
+    if !path.exists() {
+        let available = list_snapshots(root)?;
+        if available.is_empty() {
+            bail!("Snapshot {name} does not exist. There are no snapshots");
+        }
+        bail!(
+            "Snapshot {name} does not exist. Available snapshots: {}",
+            available.join(", ")
+        );
+    }
+    let snapshot = read_lock(&path.to_string_lossy(), root)?;
+    let lock = lock_file_in(root);
+    let current = std::fs::read_to_string(&lock)
+        .ok()
+        .and_then(|contents| contents.parse::<LabtLock>().ok())
+        .unwrap_or_default();
+    replace_lock(&lock, &snapshot)?;
+    Ok(diff_locks(&current, &snapshot))
+}
+
+fn snapshot(args: &SnapshotArgs) -> anyhow::Result<()> {
+    let root = get_project_root()?;
+    match &args.command {
+        SnapshotSubcommands::Save(args) => {
+            let path = save_snapshot(&root, &args.name, args.force)?;
+            info!(target: "lock", "Saved snapshot {} to {:?}", args.name, path);
+        }
+        SnapshotSubcommands::Restore(args) => {
+            let changes = restore_snapshot(&root, &args.name)?;
+            for change in &changes {
+                let dep = &change.dep;
+                let from = match &change.kind {
+                    ChangeKind::Upgraded { from } | ChangeKind::Downgraded { from } => {
+                        format!("{from} -> ")
+                    }
+                    _ => String::new(),
+                };
+                info!(target: "lock", "{} {}:{} {}{}", change.kind.title(), dep.group_id, dep.artifact_id, from, dep.version);
+            }
+            info!(target: "lock", "Restored snapshot {}, {} artifacts changed", args.name, changes.len());
+        }
+        SnapshotSubcommands::List => {
+            for name in list_snapshots(&root)? {
+                println!("{name}");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a lock file from a path or from a git revision of the project repository
+fn read_lock(source: &str, root: &Path) -> anyhow::Result<LabtLock> {
+    let path = PathBuf::from(source);
+    let contents = if path.is_file() {
+        std::fs::read_to_string(&path).context(format!("Failed to read {:?}", path))?
+    } else {
+        read_lock_at_revision(root, source)?
+    };
+    contents
+        .parse::<LabtLock>()
+        .context(format!("Failed to parse the lock file from {source}"))
+}
+
+fn read_lock_at_revision(root: &Path, revision: &str) -> anyhow::Result<String> {
+    let repo = Repository::discover(root).context(format!(
+        "{revision} is not a file and no git repository was found"
+    ))?;
+    let workdir = repo
+        .workdir()
+        .context("The git repository has no working directory")?;
+    // Labt.dev.lock is local, only Labt.lock is committed
+    let lock = root.join(LOCK_FILE);
+    let relative = match lock.strip_prefix(workdir) {
+        Ok(relative) => relative.to_path_buf(),
+        // the project root may be reached through a symbolic link
+        Err(_) => root
+            .canonicalize()?
+            .join(LOCK_FILE)
+            .strip_prefix(workdir.canonicalize()?)
+            .context("Labt.lock is outside the git repository")?
+            .to_path_buf(),
+    };
+    let tree = repo
+        .revparse_single(revision)
+        .context(format!("{revision} is not a file or a git revision"))?
+        .peel_to_tree()?;
+    let entry = tree
+        .get_path(&relative)
+        .context(format!("{LOCK_FILE} does not exist at {revision}"))?;
+    let blob = entry.to_object(&repo)?.peel_to_blob()?;
+    match std::str::from_utf8(blob.content()) {
+        Ok(contents) => Ok(contents.to_string()),
+        Err(_) => bail!("{LOCK_FILE} at {revision} is not valid utf-8"),
+    }
+}
+
+impl Submodule for Lock {
+    fn run(&mut self) -> anyhow::Result<()> {
+        match &self.args.command {
+            LockSubcommands::Diff(args) => {
+                let root = get_project_root()?;
+                let old = read_lock(args.old.as_deref().unwrap_or("HEAD"), &root)?;
+                let new = match &args.new {
+                    Some(new) => read_lock(new, &root)?,
+                    None => read_lock(&lock_file_in(&root).to_string_lossy(), &root)?,
+                };
+                let changes = diff_locks(&old, &new);
+                let client = if args.no_sizes || crate::is_offline() {
+                    None
+                } else {
+                    client_builder()?.build().ok()
+                };
+                print!(
+                    "{}",
+                    render_diff(&changes, args.markdown, |dep| dep_size(
+                        dep,
+                        client.as_ref()
+                    ))
+                );
+                Ok(())
+            }
+            // git only keeps the file conflicted if the merge driver fails, which it
+            // sees from labt exiting with an error
+            LockSubcommands::Merge(args) => merge(args),
+            LockSubcommands::Snapshot(args) => snapshot(args),
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn lock_diff_summary() {
+    let dep = |artifact_id: &str, version: &str| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: version.to_string(),
+        packaging: String::from("jar"),
+        ..Default::default()
+    };
+    let old = LabtLock {
+        resolved: vec![
+            dep("kept", "1.0"),
+            dep("removed", "1.0"),
+            dep("core", "1.0"),
+        ],
+        test: vec![dep("junit", "4.13")],
+        ..Default::default()
+    };
+    let new = LabtLock {
+        resolved: vec![dep("kept", "1.0"), dep("core", "1.2"), dep("added", "2.0")],
+        test: vec![dep("junit", "4.12")],
+        ..Default::default()
+    };
+    let changes = diff_locks(&old, &new);
+    let kinds: Vec<(&str, ChangeKind)> = changes
+        .iter()
+        .map(|c| (c.dep.artifact_id.as_str(), c.kind.clone()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ("added", ChangeKind::Added),
+            (
+                "core",
+                ChangeKind::Upgraded {
+                    from: String::from("1.0")
+                }
+            ),
+            ("removed", ChangeKind::Removed),
+            (
+                "junit",
+                ChangeKind::Downgraded {
+                    from: String::from("4.13")
+                }
+            ),
+        ]
+    );
+
+    let sizes = |dep: &ProjectDep| match (dep.artifact_id.as_str(), dep.version.as_str()) {
+        ("added", _) => Some(2048),
+        ("removed", _) => Some(1024),
+        ("core", "1.0") => Some(1000),
+        ("core", "1.2") => Some(1500),
+        _ => None,
+    };
+    assert_eq!(
+        render_diff(&changes, false, sizes),
+        "Added:
+  com.example:added 2.0 (+2.00 KiB)
+Removed:
+  com.example:removed 1.0 (-1.00 KiB)
+Upgraded:
+  com.example:core 1.0 -> 1.2 (+500 B)
+Downgraded:
+  com.example:junit 4.13 -> 4.12 (size unknown) [test]
+Total size change: +1.49 KiB (some sizes unknown)
+"
+    );
+    assert_eq!(render_diff(&[], false, sizes), "No dependency changes\n");
+}
+
+#[test]
+fn lock_snapshots_save_and_restore() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("lock-snapshots");
+    create_dir_all(&root).unwrap();
+    let dep = |version: &str| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: String::from("core"),
+        version: version.to_string(),
+        packaging: String::from("jar"),
+        ..Default::default()
+    };
+    let write = |version: &str| {
+        let lock = LabtLock {
+            resolved: vec![dep(version)],
+            ..Default::default()
+        };
+        std::fs::write(root.join(LOCK_FILE), lock.to_string()).unwrap();
+    };
+
+    assert!(save_snapshot(&root, "good", false).is_err());
+    write("1.0");
+    assert_eq!(
+        save_snapshot(&root, "good", false).unwrap(),
+        root.join(PROJECT_STATE_DIR)
+            .join(SNAPSHOT_DIR)
+            .join("good.lock")
+    );
+    write("1.2");
+    save_snapshot(&root, "bad", false).unwrap();
+    assert!(save_snapshot(&root, "bad", false).is_err());
+    assert_eq!(list_snapshots(&root).unwrap(), vec!["bad", "good"]);
+
+    let changes = restore_snapshot(&root, "good").unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(
+        changes[0].kind,
+        ChangeKind::Downgraded {
+            from: String::from("1.2")
+        }
+    );
+    let restored = read_lock(&root.join(LOCK_FILE).to_string_lossy(), &root).unwrap();
+    assert_eq!(restored.resolved[0].version, "1.0");
+    assert!(!root.join(format!("{LOCK_FILE}.lck")).exists());
+    assert!(restore_snapshot(&root, "missing").is_err());
+}
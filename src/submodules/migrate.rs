@@ -0,0 +1,313 @@
+use std::{
+    collections::BTreeSet,
+    fs::{read_to_string, write},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use clap::{Args, Subcommand};
+use log::{info, warn};
+use toml_edit::{Document, Item, TableLike, Value};
+
+use crate::{
+    config::{
+        androidx::ClassMapping,
+        deprecations::{Deprecation, Deprecations},
+        get_config, get_config_string, LABT_TOML_FILE_NAME,
+    },
+    get_manifest_path, get_project_root,
+};
+
+use super::Submodule;
+
+/// The Labt.toml tables that hold maven dependencies
+pub(super) const DEPENDENCY_TABLES: [&str; 3] =
+    ["dependencies", "test_dependencies", "annotation_processors"];
+
+#[derive(Clone, Args)]
+pub struct MigrateArgs {
+    #[command(subcommand)]
+    command: MigrateSubcommands,
+}
+
+#[derive(Clone, Subcommand)]
+pub enum MigrateSubcommands {
+    /// Replaces the support library dependencies in Labt.toml with their androidx artifacts
+    /// using the official androidx artifact mapping
+    Androidx(MigrateAndroidxArgs),
+}
+
+#[derive(Clone, Args)]
+pub struct MigrateAndroidxArgs {
+    /// Only print the changes without writing Labt.toml or the sources
+    #[arg(long)]
+    dry_run: bool,
+    /// Also rewrite the support library imports and class names in the sources
+    #[arg(long)]
+    rewrite_imports: bool,
+    /// Glob patterns relative to the project root of the sources to rewrite
+    #[arg(long, default_values = ["app/**/*.java", "app/**/*.kt", "app/**/*.xml"])]
+    sources: Vec<String>,
+    /// The official androidx-class-mapping.csv to use instead of the classes bundled with labt
+    #[arg(long)]
+    class_mapping: Option<PathBuf>,
+}
+
+pub struct Migrate {
+    args: MigrateArgs,
+}
+
+impl Migrate {
+    pub fn new(args: &MigrateArgs) -> Self {
+        Migrate { args: args.clone() }
+    }
+}
+
+/// A dependency of Labt.toml that was rewritten
+#[derive(Debug, PartialEq, Eq)]
+pub struct Migration {
+    /// The table and key of the dependency e.g. dependencies.appcompat
+    pub entry: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// Sets a string value keeping the formatting around the old value
+pub(super) fn set_string(table: &mut dyn TableLike, key: &str, value: &str) {
+    match table.get_mut(key).and_then(Item::as_value_mut) {
+        Some(old) => {
+            let decor = old.decor().clone();
+            *old = Value::from(value);
+            *old.decor_mut() = decor;
+        }
+        None => {
+            table.insert(key, toml_edit::value(value));
+            // space the new key like the rest of an inline table
+            table.fmt();
+        }
+    }
+}
+
+pub(super) fn get_string<'a>(table: &'a dyn TableLike, key: &str) -> Option<&'a str> {
+    table.get(key).and_then(Item::as_str)
+}
+
+/// Rewrites the dependencies of the toml that have a deprecation accepted by
+/// migrate to their replacements. Formatting and comments are preserved.
+///
+/// # Errors
+///
+/// Returns an error if the toml cannot be parsed
+pub fn migrate_config(
+    toml: &str,
+    deprecations: &Deprecations,
+    migrate: impl Fn(&Deprecation) -> bool,
+) -> anyhow::Result<(String, Vec<Migration>)> {
+    let mut doc = toml
+        .parse::<Document>()
+        .context(format!("Failed parsing {}", LABT_TOML_FILE_NAME))?;
+
+    let mut migrations = Vec::new();
+    for name in DEPENDENCY_TABLES {
+        let Some(table) = doc.get_mut(name).and_then(Item::as_table_like_mut) else {
+            continue;
+        };
+        let keys: Vec<String> = table.iter().map(|(key, _)| key.to_string()).collect();
+        for key in keys {
+            let Some(dep) = table.get_mut(&key).and_then(Item::as_table_like_mut) else {
+                continue;
+            };
+            let Some(group_id) = get_string(dep, "group_id").map(String::from) else {
+                continue;
+            };
+            let artifact_id = get_string(dep, "artifact_id").unwrap_or(&key).to_string();
+            let Some(replacement) = deprecations
+                .find(&group_id, &artifact_id)
+                .filter(|deprecation| migrate(deprecation))
+                .and_then(|deprecation| deprecation.replacement.as_ref())
+            else {
+                continue;
+            };
+            let version = get_string(dep, "version").unwrap_or_default().to_string();
+
+            set_string(dep, "group_id", &replacement.group_id);
+            if replacement.artifact_id != key || dep.contains_key("artifact_id") {
+                set_string(dep, "artifact_id", &replacement.artifact_id);
+            }
+            // versions managed by a bom stay unset
+            let new_version = match &replacement.version {
+                Some(new_version) if !version.is_empty() => {
+                    set_string(dep, "version", new_version);
+                    new_version.clone()
+                }
+                _ => version.clone(),
+            };
+            migrations.push(Migration {
+                entry: format!("{name}.{key}"),
+                from: format!("{group_id}:{artifact_id}:{version}"),
+                to: format!(
+                    "{}:{}:{}",
+                    replacement.group_id, replacement.artifact_id, new_version
+                ),
+            });
+        }
+    }
+    Ok((doc.to_string(), migrations))
+}
+
+/// Rewrites the support library dependencies of Labt.toml
+fn migrate_dependencies(args: &MigrateAndroidxArgs) -> anyhow::Result<()> {
+    let config = get_config()?;
+    let deprecations = Deprecations::new(config.deprecations.as_ref());
+    let original = get_config_string()?;
+    let (migrated, migrations) =
+        migrate_config(&original, &deprecations, Deprecation::is_androidx_migration)?;
+    if migrations.is_empty() {
+        info!(target: "migrate", "No support library dependencies to migrate");
+        return Ok(());
+    }
+    for migration in &migrations {
+        info!(target: "migrate", "{}: {} -> {}", migration.entry, migration.from, migration.to);
+    }
+    if args.dry_run {
+        return Ok(());
+    }
+    let path = get_manifest_path().context("Failed to get the project manifest")?;
+    write(&path, migrated).context(format!("Failed to write {:?}", path))?;
+    info!(target: "migrate", "Migrated {} dependencies. Run labt resolve to update the lock file", migrations.len());
+    Ok(())
+}
+
+/// Rewrites the support library names in the sources matched by the glob patterns
+fn migrate_sources(args: &MigrateAndroidxArgs) -> anyhow::Result<()> {
+    let mapping = match &args.class_mapping {
+        Some(path) => ClassMapping::parse(
+            &read_to_string(path).context(format!("Failed to read class mapping {:?}", path))?,
+        ),
+        None => ClassMapping::builtin(),
+    };
+    let root = get_project_root().context("Failed to get the project root")?;
+
+    let mut files = BTreeSet::new();
+    for pattern in &args.sources {
+        let pattern = root.join(pattern);
+        let paths = glob::glob(&pattern.to_string_lossy())
+            .context(format!("Invalid glob pattern {:?}", pattern))?;
+        files.extend(paths.flatten().filter(|path| path.is_file()));
+    }
+
+    let mut changed = 0;
+    let mut unmapped = BTreeSet::new();
+    for path in files {
+        crate::interrupt::check()?;
+        // skip files that are not text
+        let Ok(source) = read_to_string(&path) else {
+            continue;
+        };
+        let rewrite = mapping.rewrite(&source);
+        let name = path
+            .strip_prefix(&root)
+            .unwrap_or(&path)
+            .display()
+            .to_string();
+        unmapped.extend(
+            rewrite
+                .unmapped
+                .into_iter()
+                .map(|class| format!("{class} in {name}")),
+        );
+        if rewrite.replaced == 0 {
+            continue;
+        }
+        info!(target: "migrate", "{}: {} support library names", name, rewrite.replaced);
+        if !args.dry_run {
+            write(&path, rewrite.text).context(format!("Failed to write {:?}", path))?;
+        }
+        changed += 1;
+    }
+    for class in &unmapped {
+        warn!(target: "migrate", "No androidx mapping for {}", class);
+    }
+    if !unmapped.is_empty() && args.class_mapping.is_none() {
+        warn!(target: "migrate", "Labt only bundles the common classes. Pass the official androidx-class-mapping.csv with --class-mapping to map the rest");
+    }
+    if !args.dry_run {
+        info!(target: "migrate", "Rewrote {} source files", changed);
+    }
+    Ok(())
+}
+
+impl Submodule for Migrate {
+    fn run(&mut self) -> anyhow::Result<()> {
+        match &self.args.command {
+            MigrateSubcommands::Androidx(args) => {
+                migrate_dependencies(args)?;
+                if args.rewrite_imports {
+                    migrate_sources(args)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn migrate_support_libraries_to_androidx() {
+    let toml = r#"[project]
+name = "app"
+
+[dependencies]
+# the action bar
+appcompat-v7 = { group_id = "com.android.support", version = "28.0.0" }
+design = { version = "28.0.0", group_id = "com.android.support" } # material widgets
+gson = { group_id = "com.google.code.gson", version = "2.10.1" }
+
+[dependencies.old-http]
+group_id = "com.mycorp"
+version = "1.2"
+
+[test_dependencies]
+runner = { group_id = "com.android.support.test", version = "1.0.2" }
+"#;
+    let configured = std::collections::HashMap::from([(
+        String::from("com.mycorp:old-http"),
+        crate::config::deprecations::DeprecationEntry {
+            replacement: Some(String::from("com.mycorp:http:2.0.0")),
+            message: None,
+        },
+    )]);
+    let deprecations = Deprecations::new(Some(&configured));
+    let (migrated, migrations) =
+        migrate_config(toml, &deprecations, Deprecation::is_androidx_migration).unwrap();
+    assert_eq!(
+        migrated,
+        r#"[project]
+name = "app"
+
+[dependencies]
+# the action bar
+appcompat-v7 = { group_id = "androidx.appcompat", version = "1.0.0", artifact_id = "appcompat" }
+design = { version = "1.0.0-rc01", group_id = "com.google.android.material", artifact_id = "material" } # material widgets
+gson = { group_id = "com.google.code.gson", version = "2.10.1" }
+
+[dependencies.old-http]
+group_id = "com.mycorp"
+version = "1.2"
+
+[test_dependencies]
+runner = { group_id = "androidx.test", version = "1.1.0" }
+"#
+    );
+    assert_eq!(
+        migrations[0],
+        Migration {
+            entry: String::from("dependencies.appcompat-v7"),
+            from: String::from("com.android.support:appcompat-v7:28.0.0"),
+            to: String::from("androidx.appcompat:appcompat:1.0.0"),
+        }
+    );
+    assert_eq!(migrations.len(), 3);
+}
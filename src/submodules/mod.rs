@@ -7,10 +7,45 @@ pub trait Submodule {
 }
 
 pub mod add;
+pub mod apk;
+pub mod apksign;
+pub mod artifacts;
 pub mod build;
+pub mod build_cache;
+pub mod build_summary;
+pub mod bundle_offline;
+pub mod bytecode;
+pub mod cache;
+pub mod classpath;
+pub mod clean;
+pub mod deps;
+pub mod doctor;
+pub mod fingerprint;
+pub mod fmt;
+pub mod foreach;
+pub mod ide;
+pub mod index;
 pub mod init;
+pub mod inspect;
+pub mod jdk;
+pub mod licenses;
+pub mod lock;
+pub mod migrate;
+pub mod outdated;
 pub mod plugin;
+pub mod prefetch;
+pub mod rename;
+pub mod reproducible;
 pub mod resolve;
 pub mod resolvers;
+pub mod sbom;
 pub mod sdk;
 pub mod sdkmanager;
+pub mod search;
+pub mod staging;
+pub mod toolchains;
+pub mod tree;
+pub mod update;
+pub mod verify;
+pub mod versions;
+pub mod why;
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use anyhow::Context;
+use clap::Args;
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    config::{get_config, get_resolvers_from_config, maven_metadata::MavenMetadata, Dependency},
+    output::is_json_output,
+    version::{is_pre_release, ComparableVersion},
+};
+
+use super::{resolvers::Resolver, versions::fetch_metadata_sources, Submodule};
+
+#[derive(Clone, Args)]
+pub struct OutdatedArgs {
+    /// Print the report as json for CI pipelines
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub struct Outdated {
+    args: OutdatedArgs,
+}
+
+impl Outdated {
+    pub fn new(args: &OutdatedArgs) -> Self {
+        Outdated { args: args.clone() }
+    }
+}
+
+/// The versions available for a dependency declared in Labt.toml
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutdatedEntry {
+    /// The Labt.toml table the dependency is declared in
+    pub section: String,
+    pub group_id: String,
+    pub artifact_id: String,
+    /// The version in Labt.toml. None if a bom manages it
+    pub current: Option<String>,
+    /// The newest published version without a pre-release qualifier
+    pub release: Option<String>,
+    /// The newest published version, including pre-releases
+    pub latest: Option<String>,
+    /// A newer release than current is available
+    pub outdated: bool,
+    /// Why the versions could not be looked up
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+fn newest<'a>(versions: impl Iterator<Item = &'a String>) -> Option<String> {
+    versions
+        .max_by_key(|version| ComparableVersion::new(version))
+        .cloned()
+}
+
+impl OutdatedEntry {
+    /// Fills release and latest from the metadata of each repository. Release skips
+    /// alpha, beta, rc, milestone, dev and snapshot versions
+    pub fn set_versions(&mut self, sources: &[(String, MavenMetadata)]) {
+        let versions = || sources.iter().flat_map(|(_, metadata)| &metadata.versions);
+        // repositories that do not set <release> or <latest> fall back to their versions
+        self.latest = newest(
+            sources
+                .iter()
+                .filter_map(|(_, metadata)| metadata.latest.as_ref())
+                .chain(versions()),
+        );
+        // the <release> of google maven is often a pre-release of androidx
+        self.release = newest(
+            sources
+                .iter()
+                .filter_map(|(_, metadata)| metadata.release.as_ref())
+                .chain(versions())
+                .filter(|version| !is_pre_release(version)),
+        );
+        self.outdated = match (&self.current, &self.release) {
+            (Some(current), Some(release)) => {
+                ComparableVersion::new(release) > ComparableVersion::new(current)
+            }
+            _ => false,
+        };
+    }
+}
+
+/// The maven dependencies of a Labt.toml table with the resolver they are pinned
+/// to. Path and git dependencies have no published versions to compare against
+fn declared<'a>(
+    section: &str,
+    dependencies: &'a Option<HashMap<String, Dependency>>,
+) -> Vec<(OutdatedEntry, Option<&'a str>)> {
+    let Some(dependencies) = dependencies else {
+        return Vec::new();
+    };
+    let mut entries: Vec<(OutdatedEntry, Option<&str>)> = dependencies
+        .iter()
+        .filter(|(_, dep)| dep.path.is_none() && dep.git.is_none())
+        .map(|(key, dep)| {
+            let entry = OutdatedEntry {
+                section: section.to_string(),
+                group_id: dep.group_id.clone(),
+                artifact_id: dep.artifact_id.clone().unwrap_or(key.clone()),
+                current: (!dep.version.is_empty()).then(|| dep.version.clone()),
+                ..Default::default()
+            };
+            (entry, dep.resolver.as_deref())
+        })
+        .collect();
+    entries.sort_by(|(a, _), (b, _)| {
+        (&a.group_id, &a.artifact_id).cmp(&(&b.group_id, &b.artifact_id))
+    });
+    entries
+}
+
+pub fn render_table(entries: &[OutdatedEntry]) -> String {
+    let names: Vec<String> = entries
+        .iter()
+        .map(|entry| format!("{}:{}", entry.group_id, entry.artifact_id))
+        .collect();
+    let width = names
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or(0)
+        .max("DEPENDENCY".len());
+    let column = |version: &Option<String>| version.clone().unwrap_or(String::from("-"));
+    let version_width = entries
+        .iter()
+        .flat_map(|entry| [&entry.current, &entry.release, &entry.latest])
+        .map(|version| column(version).len())
+        .max()
+        .unwrap_or(0)
+        .max("RELEASE".len());
+
+    let mut out = format!(
+        "{:width$}  {:version_width$}  {:version_width$}  LATEST\n",
+        "DEPENDENCY", "CURRENT", "RELEASE"
+    );
+    for (name, entry) in names.iter().zip(entries) {
+        let marker = if entry.outdated { "*" } else { "" };
+        let line = match &entry.error {
+            Some(_) => format!(
+                "{:width$}  {:version_width$}  ?",
+                name,
+                column(&entry.current)
+            ),
+            None => format!(
+                "{:width$}  {:version_width$}  {:version_width$}  {:version_width$}{}",
+                name,
+                column(&entry.current),
+                column(&entry.release),
+                column(&entry.latest),
+                marker
+            ),
+        };
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+impl Submodule for Outdated {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let config = get_config()?;
+        let resolvers = get_resolvers_from_config(&config)?;
+
+        let mut declared_deps = declared("dependencies", &config.dependencies);
+        declared_deps.extend(declared("test_dependencies", &config.test_dependencies));
+        declared_deps.extend(declared(
+            "annotation_processors",
+            &config.annotation_processors,
+        ));
+
+        let mut entries = Vec::with_capacity(declared_deps.len());
+        for (mut entry, resolver) in declared_deps {
+            crate::interrupt::check()?;
+            // a dependency pinned to a resolver is only looked up there
+            let pinned: Vec<Box<dyn Resolver>>;
+            let lookup = match resolver {
+                Some(name) => {
+                    pinned = get_resolvers_from_config(&config)?
+                        .into_iter()
+                        .filter(|r| r.get_name() == name)
+                        .collect();
+                    &pinned
+                }
+                None => &resolvers,
+            };
+            match fetch_metadata_sources(&entry.group_id, &entry.artifact_id, lookup) {
+                Ok(sources) => entry.set_versions(&sources),
+                Err(err) => {
+                    warn!(target: "outdated", "{:?}", err);
+                    entry.error = Some(err.to_string());
+                }
+            }
+            entries.push(entry);
+        }
+
+        if self.args.json || is_json_output() {
+            let json = serde_json::to_string_pretty(&entries)
+                .context("Failed to serialize outdated dependencies")?;
+            println!("{json}");
+        } else {
+            print!("{}", render_table(&entries));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn outdated_versions_from_metadata() {
+    let metadata = |versions: &[&str], release: Option<&str>, latest: Option<&str>| MavenMetadata {
+        versions: versions.iter().map(|v| v.to_string()).collect(),
+        release: release.map(String::from),
+        latest: latest.map(String::from),
+        ..Default::default()
+    };
+    let sources = vec![
+        (
+            String::from("google"),
+            metadata(
+                &["1.10.0", "1.12.0", "1.13.0-alpha01"],
+                Some("1.12.0"),
+                Some("1.13.0-alpha01"),
+            ),
+        ),
+        (
+            String::from("central"),
+            metadata(&["1.11.0", "1.14.0-SNAPSHOT"], None, None),
+        ),
+    ];
+    let mut entry = OutdatedEntry {
+        group_id: String::from("androidx.core"),
+        artifact_id: String::from("core"),
+        current: Some(String::from("1.10.0")),
+        ..Default::default()
+    };
+    entry.set_versions(&sources);
+    assert_eq!(entry.release.as_deref(), Some("1.12.0"));
+    assert_eq!(entry.latest.as_deref(), Some("1.14.0-SNAPSHOT"));
+    assert!(entry.outdated);
+
+    // without <release> the newest non snapshot is used
+    let mut up_to_date = OutdatedEntry {
+        group_id: String::from("junit"),
+        artifact_id: String::from("junit"),
+        current: Some(String::from("4.13.2")),
+        ..Default::default()
+    };
+    up_to_date.set_versions(&[(
+        String::from("central"),
+        metadata(&["4.12", "4.13.2", "5.0-SNAPSHOT"], None, None),
+    )]);
+    assert_eq!(up_to_date.release.as_deref(), Some("4.13.2"));
+    assert!(!up_to_date.outdated);
+
+    let failed = OutdatedEntry {
+        group_id: String::from("com.example"),
+        artifact_id: String::from("lib"),
+        error: Some(String::from("not found")),
+        ..Default::default()
+    };
+    assert_eq!(
+        render_table(&[entry, up_to_date, failed]),
+        "DEPENDENCY          CURRENT          RELEASE          LATEST\n\
+         androidx.core:core  1.10.0           1.12.0           1.14.0-SNAPSHOT*\n\
+         junit:junit         4.13.2           4.13.2           5.0-SNAPSHOT\n\
+         com.example:lib     -                ?\n"
+    );
+}
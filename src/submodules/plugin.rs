@@ -1,34 +1,44 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     env::current_dir,
     fs::{create_dir_all, read_to_string, File},
     io::Write,
-    path::PathBuf,
-    sync::{atomic::AtomicBool, Arc},
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Duration,
 };
 
 use anyhow::{bail, Context};
 use clap::{Args, Subcommand};
+use console::style;
 use dialoguer::Confirm;
 use git2::{DescribeFormatOptions, DescribeOptions, Repository, WorktreeAddOptions};
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{info, trace, warn};
 use reqwest::Url;
+use serde::Serialize;
+use version_compare::Cmp;
 
 use crate::{
     config::{
         add_plugin_to_config, get_config, remove_plugin_from_config, repository::RepositoryXml,
+        PluginTable,
     },
     get_home,
-    plugin::config::{PluginToml, SdkEntry},
+    output::{is_json_output, print_json},
+    paths::{long_path, sanitize_component},
+    plugin::{
+        config::{PluginToml, SdkEntry},
+        provenance::{directory_digest, read_provenance, record_provenance, Provenance},
+    },
     pom::VersionRange,
     submodules::{
+        build::Step,
         resolvers::GOOGLE_REPO_URL,
         sdk::{
-            get_sdk_path, parse_repository_toml, toml_strings, Installer, InstallerTarget, Sdk,
-            DEFAULT_RESOURCES_URL, DEFAULT_URL, FAILED_TO_PARSE_SDK_STR, GOOGLE_REPO_NAME_STR,
-            SDKMANAGER_TARGET,
+            package_dir, parse_repository_toml, toml_strings, InstalledPackage, Installer,
+            InstallerTarget, Sdk, DEFAULT_RESOURCES_URL, DEFAULT_URL, FAILED_TO_PARSE_SDK_STR,
+            GOOGLE_REPO_NAME_STR, SDKMANAGER_TARGET,
         },
         sdkmanager::{installed_list::InstalledList, ToIdLong},
     },
@@ -62,6 +72,8 @@ pub enum PluginSubcommands {
     Remove(RemoveArgs),
     /// Install missing plugins defined in Project config
     Fetch,
+    /// List the plugins used by this project with their status
+    List,
 }
 
 #[derive(Clone, Args)]
@@ -131,6 +143,10 @@ impl<'a> Submodule for Plugin<'a> {
                         .context("Failed to fetch plugins")?;
                     return Ok(());
                 }
+                PluginSubcommands::List => {
+                    list_plugins().context("Failed to list plugins")?;
+                    return Ok(());
+                }
             }
         }
 
@@ -216,7 +232,7 @@ pub fn build_repo(location: &str, git_path: PathBuf) -> anyhow::Result<Repositor
         ))?;
         // TODO Do a basic cleanup if cloning fails
         spinner.set_message(format!("Clonning {}", location));
-        Repository::clone(location, git_path)
+        Repository::clone(location, long_path(&git_path))
             .context("Failed to clone plugin to local directory")?
     } else {
         // The git path exists, so the repository definately exists
@@ -230,30 +246,7 @@ pub fn build_repo(location: &str, git_path: PathBuf) -> anyhow::Result<Repositor
         // the repository could have changed alot since labt interacted with it.
         // So try to fetch the latest updates from origin
         spinner.set_message(format!("Fetching updates from {}", location));
-        let mut remote = repo
-            .find_remote("origin")
-            .context("Unable to get the repository \"origin\"")?;
-
-        // fetch all these lads
-        if let Err(err) = remote.fetch(
-            &[
-                "refs/heads/*:refs/remotes/origin/*",
-                "refs/tags/*:refs/tags/*",
-            ],
-            None,
-            None,
-        ) {
-            match (err.code(), err.class()) {
-                (git2::ErrorCode::GenericError, git2::ErrorClass::Net) => {
-                    warn!(target: "plugin", "A network request failed. We are unable to update the plugin git repo. We will proceed in offline mode but latest versions will be missing or incorrect.")
-                }
-                _ => {
-                    bail!(err);
-                }
-            }
-        }
-        // drop it so that the borrow checker does not try to crusify us.
-        drop(remote);
+        fetch_origin(&repo)?;
 
         repo
     };
@@ -262,6 +255,68 @@ pub fn build_repo(location: &str, git_path: PathBuf) -> anyhow::Result<Repositor
     Ok(repo)
 }
 
+/// Fetches the branches and tags of origin. A failed network request is only
+/// warned about so that the commits already fetched can still be used
+pub fn fetch_origin(repo: &Repository) -> anyhow::Result<()> {
+    let mut remote = repo
+        .find_remote("origin")
+        .context("Unable to get the repository \"origin\"")?;
+
+    // fetch all these lads
+    if let Err(err) = remote.fetch(
+        &[
+            "refs/heads/*:refs/remotes/origin/*",
+            "refs/tags/*:refs/tags/*",
+        ],
+        None,
+        None,
+    ) {
+        match (err.code(), err.class()) {
+            (git2::ErrorCode::GenericError, git2::ErrorClass::Net) => {
+                warn!(target: "plugin", "A network request failed. We are unable to update the plugin git repo. We will proceed in offline mode but latest versions will be missing or incorrect.")
+            }
+            _ => {
+                bail!(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the directory in labt home where a plugin fetched from url is stored
+/// e.g. LABT_HOME/plugins/gitlab.com/lab-tool/plugins/labt-java
+pub fn plugin_home(url: &Url) -> anyhow::Result<PathBuf> {
+    let home = get_home().context("Failed to get Labt Home")?;
+    Ok(plugin_dir(home, url))
+}
+
+/// The directory under home where a plugin fetched from url is stored. The url
+/// path segments are sanitized so that they are valid directory names
+pub fn plugin_dir(home: PathBuf, url: &Url) -> PathBuf {
+    let mut path = home;
+    path.push("plugins");
+    if let Some(domain) = url.domain() {
+        path.push(sanitize_component(domain).as_ref());
+    } else {
+        path.push("example.com"); // keep this
+    }
+
+    let url_path = url.path();
+    let url_path = if let Some(p) = url_path.strip_suffix(".git") {
+        p
+    } else {
+        url_path
+    };
+    path.extend(
+        url_path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(sanitize_component)
+            .map(|segment| segment.into_owned()),
+    );
+    path
+}
+
 /// Do a clone if the location is a http url
 /// else if the path exists on os file system, add it to the config
 /// Returns an error if the underlying io/parsing operations fail.
@@ -276,23 +331,11 @@ pub fn fetch_plugin(
     let version = version.unwrap_or(LATEST);
 
     let mut already_installed: bool = false;
+    // recorded once the sdk packages of the plugin are installed
+    let mut provenance = None;
 
     let path = if let Ok(url) = Url::parse(location) {
-        let mut path = get_home().context("Failed to get Labt Home")?;
-        path.push("plugins");
-        if let Some(domain) = url.domain() {
-            path.push(domain);
-        } else {
-            path.push("example.com"); // keep this
-        }
-
-        let url_path = url.path();
-        let url_path = if let Some(p) = url_path.strip_suffix(".git") {
-            p
-        } else {
-            url_path
-        };
-        path.extend(url_path.split('/'));
+        let path = plugin_home(&url)?;
 
         let mut git_path = path.clone();
         git_path.push("git");
@@ -304,9 +347,9 @@ pub fn fetch_plugin(
         // check if we already checked out this particular version to avoid unecessary fetching
         if version != LATEST {
             if version.starts_with("v") {
-                worktrees_version_path.push(version);
+                worktrees_version_path.push(sanitize_component(version).as_ref());
             } else {
-                worktrees_version_path.push(format!("v{}", version));
+                worktrees_version_path.push(sanitize_component(&format!("v{}", version)).as_ref());
             }
 
             if worktrees_version_path.exists() {
@@ -342,7 +385,9 @@ pub fn fetch_plugin(
                 .context("Failed to resolve version from plugin repo")?;
 
             // obtain the tag name
-            worktrees_path.push(&version);
+            // tags such as release/1.0 are not valid directory names
+            let worktree_name = sanitize_component(&version).into_owned();
+            worktrees_path.push(&worktree_name);
             if !worktrees_path.exists() && reference.is_tag() {
                 let id = reference
                     .target()
@@ -366,8 +411,25 @@ pub fn fetch_plugin(
                 let mut worktree_options = WorktreeAddOptions::new();
                 worktree_options.reference(Some(branch.get()));
 
-                repo.worktree(&version, &worktrees_path, Some(&worktree_options))?;
+                repo.worktree(
+                    &worktree_name,
+                    &long_path(&worktrees_path),
+                    Some(&worktree_options),
+                )?;
             }
+
+            let commit = reference
+                .peel_to_commit()
+                .context("Unable to obtain the commit of the selected version")?;
+            let record = Provenance {
+                source: location.to_string(),
+                reference: Some(version.clone()),
+                commit: Some(commit.id().to_string()),
+                digest: directory_digest(&worktrees_path)
+                    .context("Failed to compute plugin digest")?,
+                artifacts: BTreeMap::new(),
+            };
+            provenance = Some((path, version, record));
             worktrees_path
         } else {
             worktrees_version_path
@@ -445,191 +507,232 @@ pub fn fetch_plugin(
         }
     }
 
-    if !plugin_toml.sdk.is_empty() && install_sdk {
-        let mut installed_list = InstalledList::parse_from_sdk()?;
-        const PLUGIN_SDK: &str = "plugin sdk";
+    let sdk_installed =
+        plugin_toml.sdk.is_empty() || !install_sdk || install_plugin_sdk(&mut plugin_toml)?;
+    if let Some((home, version, mut record)) = provenance {
+        record.artifacts = sdk_artifacts(&plugin_toml);
+        record_provenance(&home, &version, record).context("Failed to record plugin provenance")?;
+    }
+    if !sdk_installed {
+        return Ok(None);
+    }
+    // check if its a fs path
+    if update_config {
+        // TODO check which is best to use. plugin_toml.version or version passed by user.
+        add_plugin_to_config(
+            plugin_toml.name.clone(),
+            plugin_toml.version.clone(),
+            location.to_string(),
+        )
+        .context("Failed to add plugin to project config")?;
+    }
 
-        // detect the google repo and include it in the installation of repos if it was not specified
-        if !installed_list
-            .repositories
-            .contains_key(GOOGLE_REPO_NAME_STR)
-            && !plugin_toml.sdk_repo.contains_key(GOOGLE_REPO_NAME_STR)
-        {
-            // loop though all the requested sdk modules and look for the use of default google
-            if plugin_toml
-                .sdk
-                .iter()
-                .any(|sdk| sdk.repo == GOOGLE_REPO_NAME_STR)
-            {
-                plugin_toml.sdk_repo.insert(
-                    GOOGLE_REPO_NAME_STR.to_string(),
-                    crate::submodules::sdkmanager::installed_list::RepositoryInfo {
-                        url: DEFAULT_RESOURCES_URL.to_string(),
-                        accepted_licenses: HashSet::new(),
-                        path: PathBuf::new(),
-                    },
-                );
-            }
-        }
+    if !already_installed {
+        info!(target: "plugin", "Installed plugin: {}@{}", plugin_toml.name, plugin_toml.version);
+    }
 
-        // add all the repositories specified by the plugin.
-        for (name, repo) in plugin_toml.sdk_repo.iter() {
-            // TODO possiblitity of repository name collisions.
-            info!(target: PLUGIN_SDK, "Installing {} sdk repo for plugin {}@{}.", name, plugin_toml.name, plugin_toml.version);
-            Sdk::add_repository(name, &repo.url, &mut installed_list).context(format!(
-                "Failed to install {} sdk repo requested by plugin {}@{}.",
-                name, plugin_toml.name, plugin_toml.version
-            ))?;
+    Ok(Some((plugin_toml, path)))
+}
+
+/// The sha256 of the archives the installed sdk packages of the plugin were
+/// installed from, by package id. Packages installed before the digest was
+/// recorded are left out
+fn sdk_artifacts(plugin_toml: &PluginToml) -> BTreeMap<String, String> {
+    if plugin_toml.sdk.is_empty() {
+        return BTreeMap::new();
+    }
+    let list = match InstalledList::parse_from_sdk() {
+        Ok(list) => list,
+        Err(err) => {
+            warn!(target: "plugin", "Unable to read the installed sdk packages to record their digests: {:?}", err);
+            return BTreeMap::new();
         }
+    };
+    let digests: HashMap<String, &String> = list
+        .packages
+        .iter()
+        .filter_map(|package| Some((package.to_id_long(), package.sha256.as_ref()?)))
+        .collect();
+    plugin_toml
+        .sdk
+        .iter()
+        .filter_map(|sdk| {
+            let id = sdk.to_id_long();
+            let digest = digests.get(&id)?.to_string();
+            Some((id, digest))
+        })
+        .collect()
+}
 
-        let (host_os, bits) = Sdk::get_host_os_and_bits(None)?;
-
-        let running = Arc::new(AtomicBool::new(true));
-        let mut installer = Installer::new(
-            Url::parse(DEFAULT_URL)?,
-            bits,
-            host_os.clone(),
-            false,
-            running,
-        );
-
-        // filter all repositories already installed
-        let installed_list_map = installed_list.get_hash_map_long();
-        // this should prevent re installation of already available packages or "uninstallation" of things we did not install
-        let sdk_list: Vec<&SdkEntry> = plugin_toml
+/// Installs the sdk packages and repositories required by a plugin that are not
+/// installed yet. Returns false if some of the packages failed to install.
+/// Returns an error if the underlying io/parsing operations fail.
+pub fn install_plugin_sdk(plugin_toml: &mut PluginToml) -> anyhow::Result<bool> {
+    let mut installed_list = InstalledList::parse_from_sdk()?;
+    const PLUGIN_SDK: &str = "plugin sdk";
+
+    // detect the google repo and include it in the installation of repos if it was not specified
+    if !installed_list
+        .repositories
+        .contains_key(GOOGLE_REPO_NAME_STR)
+        && !plugin_toml.sdk_repo.contains_key(GOOGLE_REPO_NAME_STR)
+    {
+        // loop though all the requested sdk modules and look for the use of default google
+        if plugin_toml
             .sdk
             .iter()
-            .filter(|sdk| !installed_list_map.contains_key(&sdk.to_id_long()))
-            .collect();
-
-        // A very rough caching for the repository lists
-        let mut repositories: HashMap<String, RepositoryXml> = HashMap::new();
-
-        // the plugin requested for an sdk, so try to check for their existance an install if necessary
-        for sdk in sdk_list {
-            // =================== INSTALL PLAN ===================
-            // - We assume at this point all the repositories are ready
-            // - The installer needs the repository "RemotePackage"
-            // - We need to select the correct sdk repository
-            //    + open the matching sdk repository config from file or cache memory
-            //    + in the repository, find the package path/id. Error if not found, otherwise return the "RemotePackage".
-            //    + pass the "RemotePackage" to the installer and its details
-            //    + repeat for the next plugin sdk packages
-
-            // Load this repository
-            let repo = if let Some(repo) = &repositories.get(&sdk.repo) {
-                repo
+            .any(|sdk| sdk.repo == GOOGLE_REPO_NAME_STR)
+        {
+            plugin_toml.sdk_repo.insert(
+                GOOGLE_REPO_NAME_STR.to_string(),
+                crate::submodules::sdkmanager::installed_list::RepositoryInfo {
+                    url: DEFAULT_RESOURCES_URL.to_string(),
+                    accepted_licenses: HashSet::new(),
+                    path: PathBuf::new(),
+                },
+            );
+        }
+    }
+
+    // add all the repositories specified by the plugin.
+    for (name, repo) in plugin_toml.sdk_repo.iter() {
+        // TODO possiblitity of repository name collisions.
+        info!(target: PLUGIN_SDK, "Installing {} sdk repo for plugin {}@{}.", name, plugin_toml.name, plugin_toml.version);
+        Sdk::add_repository(name, &repo.url, &mut installed_list).context(format!(
+            "Failed to install {} sdk repo requested by plugin {}@{}.",
+            name, plugin_toml.name, plugin_toml.version
+        ))?;
+    }
+
+    let (host_os, bits) = Sdk::get_host_os_and_bits(None)?;
+
+    let running = crate::interrupt::running();
+    let mut installer = Installer::new(
+        Url::parse(DEFAULT_URL)?,
+        bits,
+        host_os.clone(),
+        false,
+        running,
+    );
+
+    // filter all repositories already installed
+    let installed_list_map = installed_list.get_hash_map_long();
+    // this should prevent re installation of already available packages or "uninstallation" of things we did not install
+    let sdk_list: Vec<&SdkEntry> = plugin_toml
+        .sdk
+        .iter()
+        .filter(|sdk| !installed_list_map.contains_key(&sdk.to_id_long()))
+        .collect();
+
+    // A very rough caching for the repository lists
+    let mut repositories: HashMap<String, RepositoryXml> = HashMap::new();
+
+    // the plugin requested for an sdk, so try to check for their existance an install if necessary
+    for sdk in sdk_list {
+        // =================== INSTALL PLAN ===================
+        // - We assume at this point all the repositories are ready
+        // - The installer needs the repository "RemotePackage"
+        // - We need to select the correct sdk repository
+        //    + open the matching sdk repository config from file or cache memory
+        //    + in the repository, find the package path/id. Error if not found, otherwise return the "RemotePackage".
+        //    + pass the "RemotePackage" to the installer and its details
+        //    + repeat for the next plugin sdk packages
+
+        // Load this repository
+        let repo = if let Some(repo) = &repositories.get(&sdk.repo) {
+            repo
+        } else {
+            // not cached so load the repository
+            if let Some(repo_entry) = installed_list.repositories.get(&sdk.repo) {
+                let mut path = repo_entry.path.clone();
+                path.push(toml_strings::CONFIG_FILE);
+                let repo = parse_repository_toml(&path).context(FAILED_TO_PARSE_SDK_STR)?;
+                repositories.insert(sdk.repo.to_string(), repo);
+                repositories.get(&sdk.repo).unwrap()
             } else {
-                // not cached so load the repository
-                if let Some(repo_entry) = installed_list.repositories.get(&sdk.repo) {
-                    let mut path = repo_entry.path.clone();
-                    path.push(toml_strings::CONFIG_FILE);
-                    let repo = parse_repository_toml(&path).context(FAILED_TO_PARSE_SDK_STR)?;
-                    repositories.insert(sdk.repo.to_string(), repo);
-                    repositories.get(&sdk.repo).unwrap()
-                } else {
-                    // you fambled the repository name
-                    bail!("The plugin config tried to install an sdk package from a repository name it did not specify in its config! ");
-                }
-            };
+                // you fambled the repository name
+                bail!("The plugin config tried to install an sdk package from a repository name it did not specify in its config! ");
+            }
+        };
 
-            // since we have obtained the correct repo. Now find the package
-            let package = repo
-                .get_remote_packages()
-                .iter()
-                .find(|p| {
-                    if !&sdk.path.eq(p.get_path()) {
-                        return false;
-                    }
+        // since we have obtained the correct repo. Now find the package
+        let package = repo
+            .get_remote_packages()
+            .iter()
+            .find(|p| {
+                if !&sdk.path.eq(p.get_path()) {
+                    return false;
+                }
 
-                    if !sdk.version.eq(p.get_revision()) {
-                        return false;
-                    }
+                if !sdk.version.eq(p.get_revision()) {
+                    return false;
+                }
 
-                    if &sdk.channel != p.get_channel() {
-                        return false;
-                    }
+                if &sdk.channel != p.get_channel() {
+                    return false;
+                }
 
-                    true
-                })
-                .context(format!(
-                    "Package {} v{}-{} does not exist on \"{}\" sdk repo.",
-                    sdk.path, sdk.version, sdk.channel, sdk.channel
-                ))?;
+                true
+            })
+            .context(format!(
+                "Package {} v{}-{} does not exist on \"{}\" sdk repo.",
+                sdk.path, sdk.version, sdk.channel, sdk.channel
+            ))?;
 
-            if sdk.repo == GOOGLE_REPO_NAME_STR {
-                installer.add_package(GOOGLE_REPO_NAME_STR, package.clone())?;
+        if sdk.repo == GOOGLE_REPO_NAME_STR {
+            installer.add_package(GOOGLE_REPO_NAME_STR, package.clone())?;
+        } else {
+            // the repo devs should specify the base url for this package.
+            let base_url = if let Some(url) = package.get_base_url() {
+                Url::parse(url)?
             } else {
-                // the repo devs should specify the base url for this package.
-                let base_url = if let Some(url) = package.get_base_url() {
-                    Url::parse(url)?
-                } else {
-                    trace!(target: "sdkmanager", "Repository did not specify its base URL. Setting google repo url as a place holder hoping that they did for whatever archive we are installing. ");
-                    Url::parse(GOOGLE_REPO_URL)?
-                };
-
-                let path: PathBuf = package.get_path().split(';').collect();
-                let mut sdk_path = get_sdk_path()?;
-                sdk_path.push(&sdk.repo);
-                let target = InstallerTarget {
-                    bits,
-                    host_os: host_os.clone(),
-                    target_path: sdk_path.join(path),
-                    package: package.clone(),
-                    download_url: Arc::new(base_url),
-                    repository_name: sdk.repo.to_string(),
-                };
+                trace!(target: "sdkmanager", "Repository did not specify its base URL. Setting google repo url as a place holder hoping that they did for whatever archive we are installing. ");
+                Url::parse(GOOGLE_REPO_URL)?
+            };
 
-                installer.add_target(target);
-            }
-            if !package.get_uses_license().is_empty() {
-                // auto accept license
-                let mut license_path = installed_list
-                    .repositories
-                    .get(&sdk.repo)
-                    .unwrap()
-                    .path
-                    .clone();
-                license_path.push("licenses");
-                license_path.push(package.get_uses_license());
-                log::info!(target: SDKMANAGER_TARGET, "Auto accepting license for {}. You can review it at {:#?}.", package.get_path(), license_path);
-                installed_list.accept_license(&sdk.repo, package.get_uses_license().to_string());
-            }
-        }
-        drop(repositories);
-        installer.install()?;
-        let install_target_count = installer.install_targets.len();
-        let installed_count = installer.complete_tasks.len();
+            let target = InstallerTarget {
+                bits,
+                host_os: host_os.clone(),
+                target_path: package_dir(&sdk.repo, package.get_path())?,
+                package: package.clone(),
+                download_url: Arc::new(base_url),
+                repository_name: sdk.repo.to_string(),
+            };
 
-        for package in installer.complete_tasks {
-            installed_list.add_installed_package(package);
+            installer.add_target(target);
         }
-        installed_list
-            .save_to_file()
-            .context("Failed to update installed package list with installed packages")?;
-
-        // now complain about the failed installs
-        if install_target_count != installed_count {
-            log::error!(target: "plugin", "Failed to install all sdk packages required by {}@{} plugin. Canceling the installation. If this was due to a network error, please re-run the install command, and we will attempt to install the failed packages.", plugin_toml.name, plugin_toml.version);
-            return Ok(None);
+        if !package.get_uses_license().is_empty() {
+            // auto accept license
+            let mut license_path = installed_list
+                .repositories
+                .get(&sdk.repo)
+                .unwrap()
+                .path
+                .clone();
+            license_path.push("licenses");
+            license_path.push(package.get_uses_license());
+            log::info!(target: SDKMANAGER_TARGET, "Auto accepting license for {}. You can review it at {:#?}.", package.get_path(), license_path);
+            installed_list.accept_license(&sdk.repo, package.get_uses_license().to_string());
         }
     }
-    // check if its a fs path
-    if update_config {
-        // TODO check which is best to use. plugin_toml.version or version passed by user.
-        add_plugin_to_config(
-            plugin_toml.name.clone(),
-            plugin_toml.version.clone(),
-            location.to_string(),
-        )
-        .context("Failed to add plugin to project config")?;
-    }
+    drop(repositories);
+    installer.install()?;
+    let install_target_count = installer.install_targets.len();
+    let installed_count = installer.complete_tasks.len();
 
-    if !already_installed {
-        info!(target: "plugin", "Installed plugin: {}@{}", plugin_toml.name, plugin_toml.version);
+    for package in installer.complete_tasks {
+        installed_list.add_installed_package(package);
     }
-
-    Ok(Some((plugin_toml, path)))
+    installed_list
+        .save_to_file()
+        .context("Failed to update installed package list with installed packages")?;
+
+    // now complain about the failed installs
+    if install_target_count != installed_count {
+        log::error!(target: "plugin", "Failed to install all sdk packages required by {}@{} plugin. Canceling the installation. If this was due to a network error, please re-run the install command, and we will attempt to install the failed packages.", plugin_toml.name, plugin_toml.version);
+        return Ok(false);
+    }
+    Ok(true)
 }
 
 /// Fetches all plugin listed on the project config
@@ -661,6 +764,289 @@ pub fn fetch_plugins_from_config(iknow_what_iam_doing: bool) -> anyhow::Result<(
     Ok(())
 }
 
+/// Returns the newest `v*` tag in the local plugin repository that is newer than version.
+/// Only tags fetched during previous installs are considered.
+fn newer_plugin_version(git_path: &Path, version: &str) -> Option<String> {
+    let repo = Repository::open(git_path).ok()?;
+    let tags = repo.tag_names(Some("v*")).ok()?;
+    let current = version.trim_start_matches('v');
+    tags.iter()
+        .flatten()
+        .filter(|tag| {
+            version_compare::compare_to(tag.trim_start_matches('v'), current, Cmp::Gt)
+                .unwrap_or(false)
+        })
+        .max_by(|a, b| {
+            match version_compare::compare(a.trim_start_matches('v'), b.trim_start_matches('v')) {
+                Ok(Cmp::Gt) => std::cmp::Ordering::Greater,
+                Ok(Cmp::Lt) => std::cmp::Ordering::Less,
+                _ => std::cmp::Ordering::Equal,
+            }
+        })
+        .map(|tag| tag.to_string())
+}
+
+/// Whether a plugin configured in Labt.toml is available
+#[derive(Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PluginState {
+    #[default]
+    Installed,
+    /// A remote plugin that was not fetched yet
+    NotInstalled,
+    /// A local plugin path that does not exist
+    NotFound,
+}
+
+/// An sdk package required by a plugin
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginSdk {
+    pub repo: String,
+    pub path: String,
+    pub version: String,
+    pub channel: String,
+    /// installed, missing or unknown if the installed packages cannot be read
+    pub status: String,
+}
+
+/// The install record of a remote plugin compared with its files on disk
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginProvenance {
+    pub commit: Option<String>,
+    pub reference: Option<String>,
+    /// sha1 digest of the installed plugin files
+    pub digest: String,
+    /// The digest recorded at install time
+    pub recorded_digest: String,
+    /// The installed files still match the recorded digest
+    pub verified: bool,
+    /// sha256 of the archive each sdk package was installed from, by package id
+    pub artifacts: BTreeMap<String, String>,
+}
+
+/// A plugin configured in Labt.toml as shown by labt plugin list
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct PluginStatus {
+    pub name: String,
+    pub version: String,
+    pub source: String,
+    /// The source is a local path instead of a url
+    pub local: bool,
+    pub state: PluginState,
+    /// The build steps the plugin hooks
+    pub steps: Vec<String>,
+    pub sdk: Vec<PluginSdk>,
+    /// None for local plugins and remote plugins installed without a record
+    pub provenance: Option<PluginProvenance>,
+    /// A newer tag in the local plugin repository
+    pub update: Option<String>,
+}
+
+/// Reads the build steps an installed plugin hooks and its sdk dependencies
+/// with their install status
+fn plugin_details(
+    installed: &Path,
+    installed_sdk: &Option<HashMap<String, &InstalledPackage>>,
+    status: &mut PluginStatus,
+) -> anyhow::Result<()> {
+    let plugin_toml_path = installed.join("plugin.toml");
+    let plugin_toml = read_to_string(&plugin_toml_path)
+        .context(format!("Failed to read {:?}", plugin_toml_path))?
+        .parse::<PluginToml>()
+        .context(format!("Failed to parse {:?}", plugin_toml_path))?;
+
+    let mut steps: Vec<&Step> = plugin_toml.stages.keys().collect();
+    steps.sort();
+    status.steps = steps.iter().map(|step| step.to_string()).collect();
+    status.sdk = plugin_toml
+        .sdk
+        .iter()
+        .map(|sdk| PluginSdk {
+            repo: sdk.repo.clone(),
+            path: sdk.path.clone(),
+            version: sdk.version.to_string(),
+            channel: sdk.channel.to_string(),
+            status: match installed_sdk {
+                Some(map) if map.contains_key(&sdk.to_id_long()) => "installed",
+                Some(_) => "missing",
+                None => "unknown",
+            }
+            .to_string(),
+        })
+        .collect();
+    Ok(())
+}
+
+/// Collects the source, build steps, sdk dependencies, provenance and available
+/// update of a configured plugin
+fn plugin_status(
+    name: String,
+    plugin: PluginTable,
+    installed_sdk: &Option<HashMap<String, &InstalledPackage>>,
+) -> anyhow::Result<PluginStatus> {
+    let location = plugin.location.unwrap_or_default();
+    let mut status = PluginStatus {
+        name,
+        version: plugin.version,
+        source: location.clone(),
+        ..Default::default()
+    };
+    let url = match Url::parse(&location) {
+        Ok(url) => url,
+        Err(_) => {
+            status.local = true;
+            let installed = PathBuf::from(&location);
+            if installed.exists() {
+                plugin_details(&installed, installed_sdk, &mut status)?;
+            } else {
+                status.state = PluginState::NotFound;
+            }
+            return Ok(status);
+        }
+    };
+
+    let home = plugin_home(&url)?;
+    let version = if status.version.starts_with('v') {
+        status.version.clone()
+    } else {
+        format!("v{}", status.version)
+    };
+    let installed = home
+        .join("versions")
+        .join(sanitize_component(&version).as_ref());
+    if !installed.exists() {
+        status.state = PluginState::NotInstalled;
+        return Ok(status);
+    }
+    plugin_details(&installed, installed_sdk, &mut status)?;
+
+    let provenance = read_provenance(&home)?;
+    if let Some(provenance) = provenance.versions.get(&version) {
+        let digest = directory_digest(&installed)?;
+        status.provenance = Some(PluginProvenance {
+            commit: provenance.commit.clone(),
+            reference: provenance.reference.clone(),
+            verified: digest == provenance.digest,
+            digest,
+            recorded_digest: provenance.digest.clone(),
+            artifacts: provenance.artifacts.clone(),
+        });
+    }
+    status.update = newer_plugin_version(&home.join("git"), &version);
+    Ok(status)
+}
+
+/// Prints a plugin status for labt plugin list
+fn print_plugin_status(status: &PluginStatus) {
+    println!("{}@{}", style(&status.name).bold(), status.version);
+    if status.local {
+        println!("  source: local path {}", status.source);
+    } else {
+        println!("  source: {}", status.source);
+    }
+    match status.state {
+        PluginState::NotFound => {
+            println!("  status: {}", style("not found").red());
+            return;
+        }
+        PluginState::NotInstalled => {
+            println!("  status: {}", style("not installed").yellow());
+            return;
+        }
+        PluginState::Installed => {}
+    }
+
+    println!("  steps: {}", status.steps.join(", "));
+    if !status.sdk.is_empty() {
+        println!("  sdk:");
+        for sdk in &status.sdk {
+            let state = match sdk.status.as_str() {
+                "installed" => style(sdk.status.as_str()).green(),
+                "missing" => style(sdk.status.as_str()).red(),
+                _ => style(sdk.status.as_str()).yellow(),
+            };
+            println!(
+                "    {}:{} v{} ({}) {}",
+                sdk.repo, sdk.path, sdk.version, sdk.channel, state
+            );
+        }
+    }
+    if status.local {
+        return;
+    }
+
+    match &status.provenance {
+        Some(provenance) => {
+            if let Some(commit) = &provenance.commit {
+                println!(
+                    "  commit: {} ({})",
+                    commit,
+                    provenance.reference.as_deref().unwrap_or("")
+                );
+            }
+            if provenance.verified {
+                println!(
+                    "  digest: {} {}",
+                    provenance.digest,
+                    style("verified").green()
+                );
+            } else {
+                println!(
+                    "  digest: {} {} (recorded {})",
+                    provenance.digest,
+                    style("modified").red(),
+                    provenance.recorded_digest
+                );
+            }
+            for (package, sha256) in &provenance.artifacts {
+                println!("  archive: {} sha256 {}", package, sha256);
+            }
+        }
+        None => println!("  provenance: {}", style("not recorded").yellow()),
+    }
+
+    match &status.update {
+        Some(newer) => println!("  update: {} available", style(newer).cyan()),
+        None => println!("  update: up to date"),
+    }
+}
+
+/// Prints the plugins configured in Labt.toml with their source, the build steps they hook,
+/// their sdk dependencies, provenance and whether a newer version is available.
+/// Returns an error if the project config or plugin provenance records cannot be read
+pub fn list_plugins() -> anyhow::Result<()> {
+    let config = get_config().context("Failed reading project configuration")?;
+    let plugins = match config.plugins {
+        Some(plugins) if !plugins.is_empty() => plugins,
+        _ => {
+            if is_json_output() {
+                return print_json(&Vec::<PluginStatus>::new());
+            }
+            info!(target: "plugin", "No plugins configured in this project");
+            return Ok(());
+        }
+    };
+    let mut plugins: Vec<_> = plugins.into_iter().collect();
+    plugins.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let installed_list = InstalledList::parse_from_sdk().ok();
+    let installed_sdk = installed_list.as_ref().map(|list| list.get_hash_map_long());
+
+    let mut statuses = Vec::new();
+    for (name, plugin) in plugins {
+        let status = plugin_status(name, plugin, &installed_sdk)?;
+        if is_json_output() {
+            statuses.push(status);
+        } else {
+            print_plugin_status(&status);
+        }
+    }
+    if is_json_output() {
+        print_json(&statuses)?;
+    }
+    Ok(())
+}
+
 /// Creates a new plugin on the provided path, if local_plugin is true, the
 /// plugin is created on current directory
 /// UNSTABLE
@@ -688,7 +1074,7 @@ pub fn create_new_plugin(
     let mut path = if local_plugin {
         let mut cwd = current_dir().context("Failed to get current working directory.")?;
         cwd.push("plugins");
-        cwd.push(format!("{}-{}", name, version));
+        cwd.push(sanitize_component(&format!("{}-{}", name, version)).as_ref());
         create_dir_all(&cwd).context("Failed creating plugin directory on project folder")?;
         cwd
     } else {
@@ -700,7 +1086,7 @@ pub fn create_new_plugin(
         } else {
             let mut path = get_home().context("Failed to get Labt Home")?;
             path.push("plugins");
-            path.push(format!("{}-{}", name, version));
+            path.push(sanitize_component(&format!("{}-{}", name, version)).as_ref());
             path
         }
     };
@@ -716,3 +1102,52 @@ pub fn create_new_plugin(
 
     Ok(())
 }
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn newer_plugin_version_from_tags() {
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("plugin-tags");
+    let repo = Repository::init(&path).unwrap();
+    let signature = git2::Signature::now("labt", "labt@example.com").unwrap();
+    let tree_id = repo.index().unwrap().write_tree().unwrap();
+    let tree = repo.find_tree(tree_id).unwrap();
+    let commit = repo
+        .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+        .unwrap();
+    let commit = repo.find_object(commit, None).unwrap();
+    for tag in ["v0.1.0", "v0.2.0", "v0.10.0", "other"] {
+        repo.tag_lightweight(tag, &commit, false).unwrap();
+    }
+
+    assert_eq!(
+        newer_plugin_version(&path, "v0.1.0"),
+        Some(String::from("v0.10.0"))
+    );
+    assert_eq!(newer_plugin_version(&path, "0.10.0"), None);
+}
+
+#[test]
+fn plugin_dir_from_url() {
+    let home = PathBuf::from("home");
+    let url = Url::parse("https://gitlab.com/lab-tool/plugins/labt-java.git").unwrap();
+    assert_eq!(
+        plugin_dir(home.clone(), &url),
+        home.join("plugins")
+            .join("gitlab.com")
+            .join("lab-tool")
+            .join("plugins")
+            .join("labt-java")
+    );
+    let url = Url::parse("https://example.org/team/aux/plugin:v2").unwrap();
+    assert_eq!(
+        plugin_dir(home.clone(), &url),
+        home.join("plugins")
+            .join("example.org")
+            .join("team")
+            .join("_aux")
+            .join("plugin_v2")
+    );
+}
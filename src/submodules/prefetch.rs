@@ -0,0 +1,149 @@
+use anyhow::{bail, Context};
+use clap::Args;
+use log::info;
+
+use crate::{
+    config::{get_config, resolution::INCLUDABLE_SCOPES},
+    get_home,
+    plugin::config::PluginToml,
+    submodules::build::Step,
+};
+
+use super::{
+    jdk::provision_jdk,
+    plugin::{fetch_plugin, install_plugin_sdk},
+    resolve::{Resolve, ResolveArgs},
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct PrefetchArgs {
+    /// Only install the sdk packages of plugins that run at these build steps.
+    /// Defaults to every step
+    #[arg(short, long, value_enum, value_delimiter = ',')]
+    pub step: Vec<Step>,
+    /// Do not install the sdk packages required by plugins
+    #[arg(long)]
+    pub no_sdk: bool,
+    /// Also prefetch the dependencies of this scope. Can be provided, runtime or test
+    #[arg(long = "include-scope", value_name = "SCOPE", value_delimiter = ',', value_parser = INCLUDABLE_SCOPES)]
+    pub include_scopes: Vec<String>,
+    /// Trust the installation of the plugin(s), as they have the ability to execute arbitrary code.
+    #[arg(long)]
+    pub trust: bool,
+}
+
+pub struct Prefetch {
+    args: PrefetchArgs,
+}
+
+impl Prefetch {
+    pub fn new(args: &PrefetchArgs) -> Self {
+        Prefetch { args: args.clone() }
+    }
+}
+
+/// Returns true if the plugin has a stage at one of the steps. No steps means
+/// every step
+pub fn plugin_runs_at(plugin: &PluginToml, steps: &[Step]) -> bool {
+    steps.is_empty() || plugin.stages.keys().any(|step| steps.contains(step))
+}
+
+impl Submodule for Prefetch {
+    fn run(&mut self) -> anyhow::Result<()> {
+        if crate::is_offline() {
+            bail!("labt prefetch downloads what offline builds need and cannot run with --offline");
+        }
+        let mut config = get_config().context("Failed reading project configuration")?;
+
+        Resolve::new(&ResolveArgs {
+            refresh: false,
+            include_scopes: self.args.include_scopes.clone(),
+            include_optional: false,
+            jobs: None,
+        })
+        .run()
+        .context("Failed to prefetch the project dependencies")?;
+
+        let mut plugins: Vec<_> = config
+            .plugins
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        plugins.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let plugin_count = plugins.len();
+        let mut iknow_what_iam_doing = self.args.trust;
+        let mut sdk_plugins = 0;
+        for (name, plugin) in plugins {
+            crate::interrupt::check()?;
+            let location = match plugin.location {
+                Some(location) => location,
+                // try using labt home if not specified
+                None => get_home()
+                    .context("Failed to get Labt home")?
+                    .to_string_lossy()
+                    .to_string(),
+            };
+            let Some((mut plugin_toml, _)) = fetch_plugin(
+                &location,
+                Some(plugin.version.as_str()),
+                false,
+                false,
+                &mut iknow_what_iam_doing,
+            )
+            .context(format!(
+                "Failed to fetch plugin: {}@{}",
+                name, plugin.version
+            ))?
+            else {
+                bail!("Plugin {}@{} was not installed", name, plugin.version);
+            };
+
+            if self.args.no_sdk
+                || plugin_toml.sdk.is_empty()
+                || !plugin_runs_at(&plugin_toml, &self.args.step)
+            {
+                continue;
+            }
+            if !install_plugin_sdk(&mut plugin_toml)
+                .context(format!("Failed to install the sdk packages of {}", name))?
+            {
+                bail!("Some sdk packages required by {} failed to install", name);
+            }
+            sdk_plugins += 1;
+        }
+
+        if let Some(jdk) = &config.jdk {
+            let home = provision_jdk(jdk)?;
+            info!(target: "prefetch", "JDK {} is installed at {:?}", jdk.version, home);
+        }
+
+        info!(target: "prefetch", "Prefetched dependencies, {} plugins and the sdk packages of {} plugins. The project can now be built with --offline",
+            plugin_count, sdk_plugins);
+        Ok(())
+    }
+}
+
+#[test]
+fn prefetch_sdk_only_for_plugins_at_steps() {
+    let plugin: PluginToml = r#"
+name="example"
+version="0.1.0"
+
+[stage.compile]
+file="compile.lua"
+priority=1
+
+[stage.instrument]
+file="instrument.lua"
+priority=1
+"#
+    .parse()
+    .unwrap();
+
+    assert!(plugin_runs_at(&plugin, &[]));
+    assert!(plugin_runs_at(&plugin, &[Step::PRE, Step::COMPILE]));
+    assert!(plugin_runs_at(&plugin, &[Step::INSTRUMENT]));
+    assert!(!plugin_runs_at(&plugin, &[Step::AAPT, Step::BUNDLE]));
+}
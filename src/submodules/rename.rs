@@ -0,0 +1,603 @@
+use std::{
+    collections::BTreeSet,
+    fs::{create_dir_all, read_to_string, remove_dir, rename, write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use log::{info, warn};
+use regex::Regex;
+use toml_edit::{Document, Item};
+
+use crate::{
+    config::{get_config, get_config_string, LABT_TOML_FILE_NAME},
+    get_manifest_path, get_project_root,
+};
+
+use super::{
+    migrate::{get_string, set_string},
+    Submodule,
+};
+
+/// Sources whose directories follow the package
+const SOURCE_EXTENSIONS: [&str; 2] = ["java", "kt"];
+
+#[derive(Clone, Args)]
+pub struct RenameArgs {
+    /// The new application package e.g. com.example.app
+    #[arg(long)]
+    package: String,
+    /// Only print the changes without writing Labt.toml or the sources
+    #[arg(long)]
+    dry_run: bool,
+    /// Glob patterns relative to the project root of the sources, manifests and resources to rewrite
+    #[arg(long, default_values = ["app/**/*.java", "app/**/*.kt", "app/**/*.xml"])]
+    sources: Vec<String>,
+}
+
+pub struct Rename {
+    args: RenameArgs,
+}
+
+impl Rename {
+    pub fn new(args: &RenameArgs) -> Self {
+        Rename { args: args.clone() }
+    }
+}
+
+/// Returns true if name is a valid application package. It needs at least two
+/// segments of letters, digits and underscores that start with a letter
+pub fn is_package_name(name: &str) -> bool {
+    let segments: Vec<&str> = name.split('.').collect();
+    segments.len() >= 2
+        && segments.iter().all(|segment| {
+            segment.starts_with(|c: char| c.is_ascii_alphabetic())
+                && segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// Replaces the package and the names qualified with it. The package must not
+/// be part of a longer name e.g. org.com.example.app or com.example.application.
+/// Returns the new text and the number of replacements
+fn rename_qualified(text: &str, old: &str, new: &str) -> (String, usize) {
+    let pattern = Regex::new(&format!(r"(^|[^\w.]){}\b", regex::escape(old)))
+        .expect("escaped package is a valid regex");
+    let mut replaced = 0;
+    let text = pattern.replace_all(text, |caps: &regex::Captures| {
+        replaced += 1;
+        format!("{}{}", &caps[1], new)
+    });
+    (text.into_owned(), replaced)
+}
+
+/// The byte ranges of a java or kotlin source outside comments and string and
+/// char literals
+fn code_spans(source: &str) -> Vec<(usize, usize)> {
+    let bytes = source.as_bytes();
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        // all the delimiters are ascii so i is on a char boundary when one matches
+        if !matches!(bytes[i], b'/' | b'"' | b'\'') {
+            i += 1;
+            continue;
+        }
+        let rest = &source[i..];
+        let end = if rest.starts_with("//") {
+            rest.find('\n').map_or(source.len(), |end| i + end)
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            comment
+                .find("*/")
+                .map_or(source.len(), |end| i + 2 + end + 2)
+        } else if let Some(raw) = rest.strip_prefix("\"\"\"") {
+            raw.find("\"\"\"")
+                .map_or(source.len(), |end| i + 3 + end + 3)
+        } else if bytes[i] == b'/' {
+            i += 1;
+            continue;
+        } else {
+            let quote = bytes[i];
+            let mut end = i + 1;
+            while end < bytes.len() && bytes[end] != quote && bytes[end] != b'\n' {
+                end += if bytes[end] == b'\\' { 2 } else { 1 };
+            }
+            let mut end = (end + 1).min(source.len());
+            while !source.is_char_boundary(end) {
+                end += 1;
+            }
+            end
+        };
+        spans.push((start, i));
+        start = end;
+        i = end;
+    }
+    spans.push((start, source.len()));
+    spans
+}
+
+/// The byte ranges of the tags of an xml source, which hold the qualified names
+/// of views and the attribute values of the manifest. Comments, CDATA and text
+/// are left out
+fn xml_tag_spans(source: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    while let Some(open) = source[i..].find('<').map(|open| i + open) {
+        let rest = &source[open..];
+        if rest.starts_with("<!--") {
+            i = rest.find("-->").map_or(source.len(), |end| open + end + 3);
+        } else if rest.starts_with("<![CDATA[") {
+            i = rest.find("]]>").map_or(source.len(), |end| open + end + 3);
+        } else {
+            let end = rest.find('>').map_or(source.len(), |end| open + end + 1);
+            spans.push((open, end));
+            i = end;
+        }
+    }
+    spans
+}
+
+/// Replaces the package and the names qualified with it in a java, kotlin or
+/// xml source, see [`rename_qualified`]. Only code is rewritten in java and
+/// kotlin, comments and string literals are left as they are. In xml only the
+/// tags are rewritten. Returns the new text and the number of replacements
+pub fn rename_source(source: &str, old: &str, new: &str, xml: bool) -> (String, usize) {
+    let spans = if xml {
+        xml_tag_spans(source)
+    } else {
+        code_spans(source)
+    };
+    let mut text = String::with_capacity(source.len());
+    let mut replaced = 0;
+    let mut copied = 0;
+    for (start, end) in spans {
+        text.push_str(&source[copied..start]);
+        let (renamed, count) = rename_qualified(&source[start..end], old, new);
+        text.push_str(&renamed);
+        replaced += count;
+        copied = end;
+    }
+    text.push_str(&source[copied..]);
+    (text, replaced)
+}
+
+/// Sets the project package of Labt.toml to new. A BuildConfig package in the
+/// old package follows it. Formatting and comments are preserved.
+///
+/// # Errors
+///
+/// Returns an error if the toml cannot be parsed or has no [project] table
+pub fn rename_config(toml: &str, old: &str, new: &str) -> anyhow::Result<String> {
+    let mut doc = toml
+        .parse::<Document>()
+        .context(format!("Failed parsing {}", LABT_TOML_FILE_NAME))?;
+    let project = doc
+        .get_mut("project")
+        .and_then(Item::as_table_like_mut)
+        .context(format!("{} has no [project] table", LABT_TOML_FILE_NAME))?;
+    set_string(project, "package", new);
+    if let Some(build_config) = doc
+        .get_mut("build_config")
+        .and_then(Item::as_table_like_mut)
+    {
+        if let Some(package) = get_string(build_config, "package") {
+            let (package, replaced) = rename_qualified(package, old, new);
+            if replaced > 0 {
+                set_string(build_config, "package", &package);
+            }
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Returns the package directories of the sources, the closest ancestor of each
+/// source whose path ends with the package
+pub fn package_dirs(files: &[PathBuf], package: &str) -> BTreeSet<PathBuf> {
+    let package_path: PathBuf = package.split('.').collect();
+    files
+        .iter()
+        .filter(|file| {
+            file.extension()
+                .is_some_and(|ext| SOURCE_EXTENSIONS.iter().any(|e| ext == *e))
+        })
+        .filter_map(|file| {
+            file.ancestors()
+                .skip(1)
+                .find(|dir| dir.ends_with(&package_path))
+                .map(Path::to_path_buf)
+        })
+        .collect()
+}
+
+/// Where a package directory moves to. The new package goes in the same source root
+fn renamed_dir(dir: &Path, old: &str, new: &str) -> PathBuf {
+    let mut root = dir.to_path_buf();
+    for _ in old.split('.') {
+        root.pop();
+    }
+    root.join(new.split('.').collect::<PathBuf>())
+}
+
+/// Removes dir and its parents up to root while they are empty
+fn remove_empty_dirs(dir: &Path, root: &Path) {
+    for dir in dir.ancestors() {
+        if dir == root || !dir.starts_with(root) || remove_dir(dir).is_err() {
+            break;
+        }
+    }
+}
+
+/// Returns true if moving dir to the package directory to would merge it with
+/// other files. A parent package is free if it only holds the directories leading to dir
+fn is_blocked(dir: &Path, to: &Path) -> bool {
+    if !to.exists() {
+        return false;
+    }
+    let Ok(rest) = dir.strip_prefix(to) else {
+        return true;
+    };
+    let mut current = to.to_path_buf();
+    for component in rest.components() {
+        if current
+            .read_dir()
+            .map_or(true, |entries| entries.count() != 1)
+        {
+            return true;
+        }
+        current.push(component);
+    }
+    false
+}
+
+/// Moves a package directory with its subpackages to the new package
+///
+/// # Errors
+///
+/// Returns an error if the directories cannot be moved or the new package
+/// directory already has files
+pub fn move_package_dir(dir: &Path, old: &str, new: &str) -> anyhow::Result<PathBuf> {
+    let to = renamed_dir(dir, old, new);
+    if is_blocked(dir, &to) {
+        bail!("Cannot move {:?} since {:?} already exists", dir, to);
+    }
+    let mut root = dir.to_path_buf();
+    for _ in old.split('.') {
+        root.pop();
+    }
+    let moving = root.join(format!(".labt-rename-{}", std::process::id()));
+    rename(dir, &moving).context(format!("Failed to move {:?}", dir))?;
+    if let Some(parent) = dir.parent() {
+        remove_empty_dirs(parent, &root);
+    }
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent).context(format!("Failed to create {:?}", parent))?;
+    }
+    rename(&moving, &to).context(format!("Failed to move {:?} to {:?}", dir, to))?;
+    Ok(to)
+}
+
+impl Submodule for Rename {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let new = self.args.package.as_str();
+        if !is_package_name(new) {
+            bail!(
+                "{new} is not a valid package name. Use at least two segments like com.example.app"
+            );
+        }
+        let config = get_config()?;
+        let old = config.project.package.clone();
+        if old == new {
+            info!(target: "rename", "The project package is already {}", new);
+            return Ok(());
+        }
+        let root = get_project_root().context("Failed to get the project root")?;
+
+        let mut files = BTreeSet::new();
+        for pattern in &self.args.sources {
+            let pattern = root.join(pattern);
+            let paths = glob::glob(&pattern.to_string_lossy())
+                .context(format!("Invalid glob pattern {:?}", pattern))?;
+            files.extend(paths.flatten().filter(|path| path.is_file()));
+        }
+        let files: Vec<PathBuf> = files.into_iter().collect();
+
+        // check the moves before anything is written
+        let dirs = package_dirs(&files, &old);
+        for dir in &dirs {
+            let to = renamed_dir(dir, &old, new);
+            if is_blocked(dir, &to) {
+                bail!("Cannot move {:?} since {:?} already exists", dir, to);
+            }
+        }
+        let name = |path: &Path| {
+            path.strip_prefix(&root)
+                .unwrap_or(path)
+                .display()
+                .to_string()
+        };
+
+        let manifest = get_manifest_path().context("Failed to get the project manifest")?;
+        let renamed = rename_config(&get_config_string()?, &old, new)?;
+        info!(target: "rename", "{}: project.package {} -> {}", LABT_TOML_FILE_NAME, old, new);
+
+        // every edit is planned before anything is changed
+        let mut edits = Vec::new();
+        for path in &files {
+            crate::interrupt::check()?;
+            // skip files that are not text
+            let Ok(source) = read_to_string(path) else {
+                continue;
+            };
+            let xml = path.extension().is_some_and(|ext| ext == "xml");
+            let (text, replaced) = rename_source(&source, &old, new, xml);
+            if replaced == 0 {
+                continue;
+            }
+            info!(target: "rename", "{}: {} references", name(path), replaced);
+            edits.push((path.clone(), source, text));
+        }
+        let moves: Vec<(PathBuf, PathBuf)> = dirs
+            .iter()
+            .map(|dir| (dir.clone(), renamed_dir(dir, &old, new)))
+            .collect();
+        for (dir, to) in &moves {
+            info!(target: "rename", "{} -> {}", name(dir), name(to));
+        }
+        if self.args.dry_run {
+            return Ok(());
+        }
+
+        let mut done = Applied::default();
+        if let Err(err) = apply(&mut done, &moves, &edits, (&manifest, &renamed), &old, new) {
+            done.roll_back(&old, new);
+            return Err(err.context(format!(
+                "Failed to rename {old} to {new}. The project was left unchanged"
+            )));
+        }
+        info!(target: "rename", "Renamed {} to {} in {} files and moved {} package directories", old, new, edits.len(), dirs.len());
+        Ok(())
+    }
+}
+
+/// The changes made so far by a rename, undone if a later one fails
+#[derive(Default)]
+struct Applied {
+    /// The package directories moved with where they moved to
+    moved: Vec<(PathBuf, PathBuf)>,
+    /// The files written with their previous contents
+    written: Vec<(PathBuf, String)>,
+}
+
+impl Applied {
+    /// Restores the written files and moves the package directories back. What
+    /// cannot be undone is logged
+    fn roll_back(&self, old: &str, new: &str) {
+        for (path, contents) in self.written.iter().rev() {
+            if let Err(err) = write(path, contents) {
+                warn!(target: "rename", "Failed to restore {:?}: {}", path, err);
+            }
+        }
+        for (dir, to) in self.moved.iter().rev() {
+            if let Err(err) = move_package_dir(to, new, old) {
+                warn!(target: "rename", "Failed to move {:?} back to {:?}: {:?}", to, dir, err);
+            }
+        }
+    }
+}
+
+/// Where a source is once the package directories moved
+fn moved_path(path: &Path, moves: &[(PathBuf, PathBuf)]) -> PathBuf {
+    moves
+        .iter()
+        .find_map(|(dir, to)| path.strip_prefix(dir).ok().map(|rest| to.join(rest)))
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Moves the package directories, then writes the sources and Labt.toml,
+/// recording each change in done
+fn apply(
+    done: &mut Applied,
+    moves: &[(PathBuf, PathBuf)],
+    edits: &[(PathBuf, String, String)],
+    (manifest, renamed): (&Path, &str),
+    old: &str,
+    new: &str,
+) -> anyhow::Result<()> {
+    for (dir, _) in moves {
+        let to = move_package_dir(dir, old, new)?;
+        done.moved.push((dir.clone(), to));
+    }
+    for (path, source, text) in edits {
+        let path = moved_path(path, moves);
+        // recorded first as a failed write may have truncated the file
+        done.written.push((path.clone(), source.clone()));
+        write(&path, text).context(format!("Failed to write {:?}", path))?;
+    }
+    let previous = read_to_string(manifest).context(format!("Failed to read {:?}", manifest))?;
+    done.written.push((manifest.to_path_buf(), previous));
+    write(manifest, renamed).context(format!("Failed to write {:?}", manifest))
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn rename_package_in_sources_and_directories() {
+    assert!(is_package_name("com.example.app"));
+    assert!(!is_package_name("app"));
+    assert!(!is_package_name("com.1example"));
+    assert!(!is_package_name("com..app"));
+
+    let source = r#"package com.example.app.ui;
+
+import com.example.app.R;
+import com.example.application.Helper;
+import org.com.example.app.Other;
+
+class Main { com.example.app.data.Store store; }
+"#;
+    let (text, replaced) = rename_source(source, "com.example.app", "org.acme.shop", false);
+    assert_eq!(replaced, 3);
+    assert_eq!(
+        text,
+        r#"package org.acme.shop.ui;
+
+import org.acme.shop.R;
+import com.example.application.Helper;
+import org.com.example.app.Other;
+
+class Main { org.acme.shop.data.Store store; }
+"#
+    );
+    let (manifest, _) = rename_source(
+        r#"<manifest package="com.example.app"><activity android:name="com.example.app.Main"/></manifest>"#,
+        "com.example.app",
+        "org.acme.shop",
+        true,
+    );
+    assert_eq!(
+        manifest,
+        r#"<manifest package="org.acme.shop"><activity android:name="org.acme.shop.Main"/></manifest>"#
+    );
+
+    let toml = r#"[project]
+name = "app"
+package = "com.example.app" # the application id
+
+[build_config]
+package = "com.example.app.config"
+"#;
+    assert_eq!(
+        rename_config(toml, "com.example.app", "org.acme.shop").unwrap(),
+        r#"[project]
+name = "app"
+package = "org.acme.shop" # the application id
+
+[build_config]
+package = "org.acme.shop.config"
+"#
+    );
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("rename");
+    let java = root.join("app/java");
+    let main = java.join("com/example/app/Main.java");
+    let ui = java.join("com/example/app/ui/Screen.java");
+    for file in [&main, &ui] {
+        create_dir_all(file.parent().unwrap()).unwrap();
+        write(file, "").unwrap();
+    }
+    let files = vec![
+        main.clone(),
+        ui.clone(),
+        root.join("app/AndroidManifest.xml"),
+    ];
+    let dirs = package_dirs(&files, "com.example.app");
+    assert_eq!(dirs, BTreeSet::from([java.join("com/example/app")]));
+
+    // the new package is a subpackage of the old one
+    let to = move_package_dir(
+        &java.join("com/example/app"),
+        "com.example.app",
+        "com.example.app.shop",
+    )
+    .unwrap();
+    assert_eq!(to, java.join("com/example/app/shop"));
+    assert!(to.join("ui/Screen.java").exists());
+    let to = move_package_dir(&to, "com.example.app.shop", "org.acme.shop").unwrap();
+    assert!(to.join("Main.java").exists());
+    assert!(!java.join("com").exists());
+    // a parent package with other files is not merged
+    write(java.join("org/acme/Util.java"), "").unwrap();
+    assert!(move_package_dir(&to, "org.acme.shop", "org.acme").is_err());
+    assert!(to.join("Main.java").exists());
+    std::fs::remove_file(java.join("org/acme/Util.java")).unwrap();
+    let to = move_package_dir(&to, "org.acme.shop", "org.acme").unwrap();
+    assert_eq!(to, java.join("org/acme"));
+    assert!(to.join("ui/Screen.java").exists());
+}
+
+#[test]
+fn rename_skips_literals_and_comments() {
+    let source = r#"package com.example.app;
+
+// moved from com.example.app.legacy
+/* see com.example.app.Main */
+class Main {
+    String tag = "com.example.app.Main";
+    char c = '"';
+    com.example.app.Store store;
+    String block = """
+        com.example.app
+        """;
+    String name = "é"; com.example.app.Other other;
+}
+"#;
+    let (text, replaced) = rename_source(source, "com.example.app", "org.acme.shop", false);
+    assert_eq!(replaced, 3);
+    assert!(text.starts_with("package org.acme.shop;"));
+    assert!(text.contains("// moved from com.example.app.legacy"));
+    assert!(text.contains("/* see com.example.app.Main */"));
+    assert!(text.contains(r#""com.example.app.Main""#));
+    assert!(text.contains("org.acme.shop.Store store"));
+    assert!(text.contains("\n        com.example.app\n"));
+    assert!(text.contains("org.acme.shop.Other other"));
+
+    let layout = r#"<!-- com.example.app.ui.Chart -->
+<com.example.app.ui.Chart android:tag="com.example.app">
+    <TextView android:text="@string/app"/><![CDATA[com.example.app]]>com.example.app
+</com.example.app.ui.Chart>"#;
+    let (text, replaced) = rename_source(layout, "com.example.app", "org.acme.shop", true);
+    assert_eq!(replaced, 3);
+    assert_eq!(
+        text,
+        r#"<!-- com.example.app.ui.Chart -->
+<org.acme.shop.ui.Chart android:tag="org.acme.shop">
+    <TextView android:text="@string/app"/><![CDATA[com.example.app]]>com.example.app
+</org.acme.shop.ui.Chart>"#
+    );
+}
+
+#[test]
+fn rename_rolls_back_on_failure() {
+    let tmp = tempfile::tempdir().unwrap();
+    let java = tmp.path().join("app/java");
+    let main = java.join("com/example/app/Main.java");
+    create_dir_all(main.parent().unwrap()).unwrap();
+    write(&main, "package com.example.app;").unwrap();
+    let manifest = tmp.path().join(LABT_TOML_FILE_NAME);
+    write(&manifest, "[project]").unwrap();
+
+    let dir = java.join("com/example/app");
+    let moves = vec![(
+        dir.clone(),
+        renamed_dir(&dir, "com.example.app", "org.acme.shop"),
+    )];
+    let edits = vec![
+        (
+            main.clone(),
+            String::from("package com.example.app;"),
+            String::from("package org.acme.shop;"),
+        ),
+        // a directory cannot be written
+        (java.clone(), String::new(), String::new()),
+    ];
+    let mut done = Applied::default();
+    let result = apply(
+        &mut done,
+        &moves,
+        &edits,
+        (&manifest, "[project]\npackage = \"org.acme.shop\""),
+        "com.example.app",
+        "org.acme.shop",
+    );
+    assert!(result.is_err());
+    assert!(java.join("org/acme/shop/Main.java").exists());
+    done.roll_back("com.example.app", "org.acme.shop");
+    assert_eq!(read_to_string(&main).unwrap(), "package com.example.app;");
+    assert!(!java.join("org").exists());
+    assert_eq!(read_to_string(&manifest).unwrap(), "[project]");
+}
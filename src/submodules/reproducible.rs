@@ -0,0 +1,401 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use zip::ZipArchive;
+
+use super::{build_summary::BUILD_SUMMARY_FILE, ide::IDE_BUILD_DIR, staging::STAGING_DIR};
+
+/// Directory in the build directory holding the outputs of the first build
+pub const REPRODUCIBLE_DIR: &str = "reproducible";
+/// Build directory entries that are not build outputs
+const IGNORED_DIRS: [&str; 5] = [
+    "logs",
+    STAGING_DIR,
+    IDE_BUILD_DIR,
+    REPRODUCIBLE_DIR,
+    BUILD_SUMMARY_FILE,
+];
+/// Examples of differing archive entries listed per kind of difference
+const MAX_EXAMPLES: usize = 3;
+
+/// A build output that differs from the reference build
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputDifference {
+    /// The path relative to the build directory
+    pub path: PathBuf,
+    /// What differs, and where known why
+    pub reasons: Vec<String>,
+}
+
+fn is_output(relative: &Path) -> bool {
+    relative
+        .components()
+        .next()
+        .is_some_and(|first| !IGNORED_DIRS.iter().any(|dir| first.as_os_str() == *dir))
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut BTreeSet<PathBuf>) -> anyhow::Result<()> {
+    for entry in std::fs::read_dir(dir).context(format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let relative = path.strip_prefix(root)?.to_path_buf();
+        if !is_output(&relative) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            files.insert(relative);
+        }
+    }
+    Ok(())
+}
+
+/// The output files in the build directory relative to it
+///
+/// # Errors
+///
+/// Returns an error if the directory cannot be read
+pub fn output_files(build_dir: &Path) -> anyhow::Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    if build_dir.exists() {
+        collect_files(build_dir, build_dir, &mut files)?;
+    }
+    Ok(files)
+}
+
+/// Removes the build outputs so that every plugin runs again. Logs and staged
+/// dependencies are kept.
+///
+/// # Errors
+///
+/// Returns an error if an output cannot be removed
+pub fn remove_outputs(build_dir: &Path) -> anyhow::Result<()> {
+    if !build_dir.exists() {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(build_dir).context(format!("Failed to read {:?}", build_dir))? {
+        let path = entry?.path();
+        if !is_output(path.strip_prefix(build_dir)?) {
+            continue;
+        }
+        if path.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        }
+        .context(format!("Failed to remove {:?}", path))?;
+    }
+    Ok(())
+}
+
+/// Checks that the reference build exists and is not among the outputs of the
+/// build directory, where [`remove_outputs`] would delete it before it is compared.
+///
+/// # Errors
+///
+/// Returns an error if the reference does not exist or is a build output
+pub fn check_reference(build_dir: &Path, reference: &Path) -> anyhow::Result<()> {
+    let reference = reference
+        .canonicalize()
+        .context(format!("Reference build {:?} not found", reference))?;
+    let Ok(build_dir) = build_dir.canonicalize() else {
+        return Ok(());
+    };
+    if let Ok(relative) = reference.strip_prefix(&build_dir) {
+        if relative.as_os_str().is_empty() || is_output(relative) {
+            bail!(
+                "Reference build {:?} is inside the build directory and would be removed before the build. Move it out of {:?}",
+                reference,
+                build_dir
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Copies the build outputs into dest, replacing what it had
+///
+/// # Errors
+///
+/// Returns an error if copying fails
+pub fn save_outputs(build_dir: &Path, dest: &Path) -> anyhow::Result<()> {
+    if dest.exists() {
+        std::fs::remove_dir_all(dest).context(format!("Failed to clear {:?}", dest))?;
+    }
+    for file in output_files(build_dir)? {
+        let target = dest.join(&file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(build_dir.join(&file), &target)
+            .context(format!("Failed to copy {:?}", file))?;
+    }
+    Ok(())
+}
+
+/// Compares the build outputs with those of the reference build byte by byte
+///
+/// # Errors
+///
+/// Returns an error if an output cannot be read
+pub fn compare_outputs(
+    reference_dir: &Path,
+    build_dir: &Path,
+) -> anyhow::Result<Vec<OutputDifference>> {
+    let reference = output_files(reference_dir)?;
+    let current = output_files(build_dir)?;
+    let mut differences = Vec::new();
+    for path in reference.union(&current) {
+        let reasons = match (reference.contains(path), current.contains(path)) {
+            (true, false) => vec![String::from("only produced by the reference build")],
+            (false, true) => vec![String::from("not produced by the reference build")],
+            _ => {
+                let a = std::fs::read(reference_dir.join(path))
+                    .context(format!("Failed to read reference {:?}", path))?;
+                let b = std::fs::read(build_dir.join(path))
+                    .context(format!("Failed to read {:?}", path))?;
+                compare_bytes(&a, &b)
+            }
+        };
+        if !reasons.is_empty() {
+            differences.push(OutputDifference {
+                path: path.clone(),
+                reasons,
+            });
+        }
+    }
+    Ok(differences)
+}
+
+/// Explains why two outputs differ. Empty if they are identical.
+fn compare_bytes(a: &[u8], b: &[u8]) -> Vec<String> {
+    if a == b {
+        return Vec::new();
+    }
+    if let Some(reasons) = compare_archives(a, b, "") {
+        return reasons;
+    }
+    let offset = a.iter().zip(b).position(|(a, b)| a != b);
+    match offset {
+        Some(offset) => vec![format!("content differs from byte {offset}")],
+        None => vec![format!("size differs ({} vs {} bytes)", a.len(), b.len())],
+    }
+}
+
+#[derive(Default)]
+struct ArchiveEntry {
+    index: usize,
+    modified: Option<String>,
+    mode: Option<u32>,
+    compression: String,
+    data: Vec<u8>,
+}
+
+fn read_archive(bytes: &[u8]) -> Option<BTreeMap<String, ArchiveEntry>> {
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).ok()?;
+    let mut entries = BTreeMap::new();
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index).ok()?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).ok()?;
+        entries.insert(
+            file.name().to_string(),
+            ArchiveEntry {
+                index,
+                modified: file.last_modified().map(|time| time.to_string()),
+                mode: file.unix_mode(),
+                compression: file.compression().to_string(),
+                data,
+            },
+        );
+    }
+    Some(entries)
+}
+
+fn examples(names: &[&String]) -> String {
+    let mut list = names
+        .iter()
+        .take(MAX_EXAMPLES)
+        .map(|name| name.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+    if names.len() > MAX_EXAMPLES {
+        let _ = write!(list, " and {} more", names.len() - MAX_EXAMPLES);
+    }
+    list
+}
+
+/// Compares two zip based archives e.g. apk, jar or aar entry by entry. Nested
+/// archives are compared too with their entries prefixed by the archive name.
+/// Returns None if either is not an archive.
+fn compare_archives(a: &[u8], b: &[u8], prefix: &str) -> Option<Vec<String>> {
+    let a = read_archive(a)?;
+    let b = read_archive(b)?;
+    let mut reasons = Vec::new();
+
+    let only_a: Vec<&String> = a.keys().filter(|name| !b.contains_key(*name)).collect();
+    let only_b: Vec<&String> = b.keys().filter(|name| !a.contains_key(*name)).collect();
+    if !only_a.is_empty() {
+        reasons.push(format!(
+            "{prefix}entries only in the reference build: {}",
+            examples(&only_a)
+        ));
+    }
+    if !only_b.is_empty() {
+        reasons.push(format!(
+            "{prefix}entries not in the reference build: {}",
+            examples(&only_b)
+        ));
+    }
+
+    let shared: Vec<(&String, &ArchiveEntry, &ArchiveEntry)> = a
+        .iter()
+        .filter_map(|(name, entry)| b.get(name).map(|other| (name, entry, other)))
+        .collect();
+    let mut order_a: Vec<&(&String, &ArchiveEntry, &ArchiveEntry)> = shared.iter().collect();
+    let mut order_b = order_a.clone();
+    order_a.sort_by_key(|(_, entry, _)| entry.index);
+    order_b.sort_by_key(|(_, _, entry)| entry.index);
+    if order_a
+        .iter()
+        .zip(&order_b)
+        .any(|((a, _, _), (b, _, _))| a != b)
+    {
+        reasons.push(format!(
+            "{prefix}entries are written in a different order, e.g. from iterating a directory or hash map"
+        ));
+    }
+
+    let differing = |check: &dyn Fn(&ArchiveEntry, &ArchiveEntry) -> bool| -> Vec<&String> {
+        shared
+            .iter()
+            .filter(|(_, a, b)| check(a, b))
+            .map(|(name, _, _)| *name)
+            .collect()
+    };
+    let timestamps = differing(&|a, b| a.modified != b.modified);
+    if !timestamps.is_empty() {
+        reasons.push(format!(
+            "{prefix}timestamps differ for {} entries: {}",
+            timestamps.len(),
+            examples(&timestamps)
+        ));
+    }
+    let modes = differing(&|a, b| a.mode != b.mode);
+    if !modes.is_empty() {
+        reasons.push(format!(
+            "{prefix}file permissions differ for {} entries: {}",
+            modes.len(),
+            examples(&modes)
+        ));
+    }
+    let compression = differing(&|a, b| a.compression != b.compression);
+    if !compression.is_empty() {
+        reasons.push(format!(
+            "{prefix}compression differs for {} entries: {}",
+            compression.len(),
+            examples(&compression)
+        ));
+    }
+    let mut contents = Vec::new();
+    for (name, a, b) in &shared {
+        if a.data == b.data {
+            continue;
+        }
+        match compare_archives(&a.data, &b.data, &format!("{prefix}{name}: ")) {
+            Some(nested) => reasons.extend(nested),
+            None => contents.push(*name),
+        }
+    }
+    if !contents.is_empty() {
+        reasons.push(format!(
+            "{prefix}content differs for {} entries: {}",
+            contents.len(),
+            examples(&contents)
+        ));
+    }
+    if reasons.is_empty() {
+        // same entries, the difference is in the archive layout itself
+        reasons.push(format!(
+            "{prefix}archive entries match but the archive bytes differ, e.g. alignment, comments or the signing block"
+        ));
+    }
+    Some(reasons)
+}
+
+/// Renders the differences as a report
+pub fn render_differences(differences: &[OutputDifference]) -> String {
+    let mut out = String::new();
+    for difference in differences {
+        let _ = writeln!(out, "{}", difference.path.to_string_lossy());
+        for reason in &difference.reasons {
+            let _ = writeln!(out, "  {reason}");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn reproducibility_reference_outside_outputs() {
+    let tmp = tempfile::tempdir().unwrap();
+    let build_dir = tmp.path().join("build");
+    std::fs::create_dir_all(build_dir.join("baseline")).unwrap();
+    std::fs::create_dir_all(build_dir.join(REPRODUCIBLE_DIR)).unwrap();
+    std::fs::create_dir_all(tmp.path().join("baseline")).unwrap();
+
+    assert!(check_reference(&build_dir, &build_dir.join("baseline")).is_err());
+    assert!(check_reference(&build_dir, &build_dir).is_err());
+    assert!(check_reference(&build_dir, &build_dir.join("missing")).is_err());
+    // the outputs saved by an earlier check are never removed
+    assert!(check_reference(&build_dir, &build_dir.join(REPRODUCIBLE_DIR)).is_ok());
+    assert!(check_reference(&build_dir, &tmp.path().join("baseline")).is_ok());
+}
+
+#[test]
+fn reproducibility_archive_differences() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, DateTime, ZipWriter};
+
+    let archive = |entries: &[(&str, &str)], year: u16| -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .last_modified_time(DateTime::from_date_and_time(year, 1, 1, 0, 0, 0).unwrap());
+        for (name, content) in entries {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(content.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    };
+
+    let reference = archive(&[("classes.dex", "dex"), ("res/a.xml", "a")], 2020);
+    assert_eq!(compare_bytes(&reference, &reference), Vec::<String>::new());
+    assert_eq!(
+        compare_bytes(
+            &reference,
+            &archive(&[("classes.dex", "dex"), ("res/a.xml", "a")], 2021)
+        ),
+        vec!["timestamps differ for 2 entries: classes.dex, res/a.xml"]
+    );
+    assert_eq!(
+        compare_bytes(
+            &reference,
+            &archive(&[("res/a.xml", "a"), ("classes.dex", "dex2")], 2020)
+        ),
+        vec![
+            "entries are written in a different order, e.g. from iterating a directory or hash map",
+            "content differs for 1 entries: classes.dex"
+        ]
+    );
+    assert_eq!(
+        compare_bytes(b"build 1", b"build 2"),
+        vec!["content differs from byte 6"]
+    );
+}
@@ -1,22 +1,41 @@
 use std::cell::RefCell;
 use std::cmp::Ordering;
+use std::collections::{HashMap, VecDeque};
 use std::fmt::Display;
 use std::fs::File;
-use std::path::PathBuf;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::time::Duration;
-
-use crate::caching::save_dependencies;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::caching::graph::refresh_graph;
+use crate::caching::snapshot::{snapshot_is_stale, DEFAULT_SNAPSHOT_REFRESH};
+use crate::caching::{file_sha256, save_dependencies};
+use crate::caching::{Cache, CacheType};
+use crate::config::deprecations::Deprecations;
+use crate::config::dev_overrides::{load_dev_overrides_in, DEV_LOCK_FILE, DEV_OVERRIDES_FILE};
 use crate::config::lock::strings::LOCK_FILE;
-use crate::config::lock::write_lock;
-use crate::config::lock::{load_labt_lock, LabtLock};
-use crate::config::{get_config, get_resolvers_from_config};
+use crate::config::lock::update_lock;
+use crate::config::lock::{
+    load_labt_lock, load_lock_dependencies_with, lock_file_in, record_known_lock, LabtLock,
+};
+use crate::config::maven_metadata::is_snapshot;
+use crate::config::policy::{group_matches_prefix, PolicyTable};
+use crate::config::properties::get_properties;
+use crate::config::resolution::INCLUDABLE_SCOPES;
+use crate::config::{get_resolve_config, get_resolvers_from_config, Dependency, LabToml};
+use crate::events::{self, emit, subscribe, Event};
+use crate::output::{is_json_output, print_json};
 use crate::pom::{self, Project, VersionRange};
 use crate::pom::{Scope, VersionRequirement};
 use crate::{get_project_root, MULTI_PROGRESS_BAR};
 
-use super::resolvers::ResolverErrorKind;
+use super::bytecode::{check_bytecode, java_release};
+use super::resolvers::path::{get_scoped_dependencies, is_local, PATH_REPO_STR};
+use super::resolvers::prefetch::{PomPrefetcher, DEFAULT_JOBS};
 use super::resolvers::{Resolver, CACHE_REPO_STR};
+use super::resolvers::{ResolverError, ResolverErrorKind};
 use super::Submodule;
 use anyhow::anyhow;
 use anyhow::bail;
@@ -25,11 +44,24 @@ use anyhow::Result;
 use clap::Args;
 use indicatif::ProgressBar;
 use indicatif::ProgressStyle;
-use log::info;
+use log::{info, warn};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
 
 #[derive(Args, Clone)]
 pub struct ResolveArgs {
-    // TODO add arguments
+    /// Ignore the cached resolution in Labt.lock and walk the dependency tree again
+    #[arg(long)]
+    pub refresh: bool,
+    /// Also resolve dependencies of this scope. Can be provided, runtime or test
+    #[arg(long = "include-scope", value_name = "SCOPE", value_delimiter = ',', value_parser = INCLUDABLE_SCOPES)]
+    pub include_scopes: Vec<String>,
+    /// Also resolve optional dependencies
+    #[arg(long)]
+    pub include_optional: bool,
+    /// Number of poms downloaded at the same time. 1 fetches them one by one
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
 }
 
 pub struct Resolve {
@@ -41,30 +73,316 @@ impl Resolve {
         Resolve { args: args.clone() }
     }
 }
+
+/// A dependency in the json output of labt resolve
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolvedDependency {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub scope: String,
+    pub packaging: String,
+    /// The resolver whose repository supplied the artifact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// group_id:artifact_id:version of the dependencies it requested
+    pub dependencies: Vec<String>,
+}
+
+impl From<&ProjectDep> for ResolvedDependency {
+    fn from(dep: &ProjectDep) -> Self {
+        ResolvedDependency {
+            group_id: dep.group_id.clone(),
+            artifact_id: dep.artifact_id.clone(),
+            version: dep.version.clone(),
+            scope: dep.scope.to_string(),
+            packaging: dep.packaging.clone(),
+            repository: dep.resolver.clone(),
+            sha256: dep.sha256.clone(),
+            dependencies: dep.dependencies.clone(),
+        }
+    }
+}
+
+/// The json output of labt resolve, the sections of Labt.lock
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolveOutput {
+    pub resolved: Vec<ResolvedDependency>,
+    pub test: Vec<ResolvedDependency>,
+    pub processors: Vec<ResolvedDependency>,
+}
+
+impl From<&LabtLock> for ResolveOutput {
+    fn from(lock: &LabtLock) -> Self {
+        let convert = |deps: &[ProjectDep]| deps.iter().map(ResolvedDependency::from).collect();
+        ResolveOutput {
+            resolved: convert(&lock.resolved),
+            test: convert(&lock.test),
+            processors: convert(&lock.processors),
+        }
+    }
+}
 // =================
 // Entry point
 // =================
 impl Submodule for Resolve {
     fn run(&mut self) -> Result<()> {
         // try reading toml file
-        let config = get_config()?;
-        if let Some(deps) = &config.dependencies {
-            let dependencies: Vec<Project> = deps
-                .iter()
-                .map(|(artifact_id, table)| {
-                    let mut p = Project::new(&table.group_id, artifact_id, &table.version);
-                    p.set_selected_version(Some(table.version.clone()));
-                    p
-                })
-                .collect();
+        let config = get_resolve_config()?;
+        if config.dependencies.is_some()
+            || config.annotation_processors.is_some()
+            || config.test_dependencies.is_some()
+        {
+            let dependencies = get_config_dependencies(&config)?;
             let resolvers =
                 get_resolvers_from_config(&config).context("Failed to get resolvers")?;
 
-            resolve(dependencies, resolvers)?;
+            let mut options = ResolveOptions::try_from(&config)?;
+            options.refresh = self.args.refresh;
+            options.include_scopes(&self.args.include_scopes)?;
+            options.include_optional |= self.args.include_optional;
+            if let Some(jobs) = self.args.jobs {
+                options.jobs = jobs;
+            }
+
+            resolve(dependencies, resolvers, options)?;
+            if is_json_output() {
+                let lock = load_labt_lock().context("Failed to load the resolved Labt.lock")?;
+                print_json(&ResolveOutput::from(&lock))?;
+            }
+        } else if is_json_output() {
+            print_json(&ResolveOutput::default())?;
         }
         Ok(())
     }
 }
+/// Returns the dependencies declared in Labt.toml ready for resolution.
+/// Path dependencies take their coordinates from the library Labt.toml.
+///
+/// # Errors
+///
+/// Returns an error if the project root or a path dependency cannot be read
+pub fn get_config_dependencies(config: &LabToml) -> anyhow::Result<Vec<Project>> {
+    let root = get_project_root().context("Failed to get project root directory")?;
+    let dependencies = get_scoped_dependencies(&root, config)?
+        .into_iter()
+        .map(|((group_id, artifact_id, version), scope)| {
+            let mut p = Project::new(&group_id, &artifact_id, &version);
+            // dependencies without a version take it from a bom
+            if !version.is_empty() {
+                p.set_selected_version(Some(version));
+            }
+            p.set_scope(scope);
+            p
+        })
+        .collect();
+    Ok(dependencies)
+}
+
+/// Converts a dependency table that can only be fetched from maven repositories.
+/// Path and git entries are skipped.
+fn get_maven_projects(table: &Option<HashMap<String, Dependency>>, kind: &str) -> Vec<Project> {
+    let Some(table) = table else {
+        return Vec::new();
+    };
+    let mut projects: Vec<Project> = table
+        .iter()
+        .filter_map(|(name, dep)| {
+            if is_local(dep) {
+                warn!(target: "resolve", "{kind} {name} is not a maven artifact. Skipping");
+                return None;
+            }
+            let artifact_id = dep.artifact_id.as_deref().unwrap_or(name);
+            let mut p = Project::new(&dep.group_id, artifact_id, &dep.version);
+            p.set_selected_version(Some(dep.version.clone()));
+            Some(p)
+        })
+        .collect();
+    // the table order is not preserved, keep resolution deterministic
+    projects.sort_by_key(|p| p.qualified_name().unwrap_or_default());
+    projects
+}
+
+/// Returns the boms declared in Labt.toml
+pub fn get_config_boms(config: &LabToml) -> Vec<Project> {
+    let Some(dependencies) = &config.dependencies else {
+        return Vec::new();
+    };
+    let mut boms: Vec<Project> = dependencies
+        .iter()
+        .filter(|(_, dep)| dep.is_bom())
+        .map(|(name, dep)| {
+            let artifact_id = dep.artifact_id.as_deref().unwrap_or(name);
+            let mut p = Project::new(&dep.group_id, artifact_id, &dep.version);
+            p.set_selected_version(Some(dep.version.clone()));
+            p
+        })
+        .collect();
+    boms.sort_by_key(|p| p.qualified_name().unwrap_or_default());
+    boms
+}
+
+/// Returns the annotation processors declared in Labt.toml
+pub fn get_config_processors(config: &LabToml) -> Vec<Project> {
+    get_maven_projects(&config.annotation_processors, "Annotation processor")
+}
+
+/// Returns the test dependencies declared in Labt.toml
+pub fn get_config_test_dependencies(config: &LabToml) -> Vec<Project> {
+    get_maven_projects(&config.test_dependencies, "Test dependency")
+}
+
+/// The resolver a dependency entry is pinned to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyPin {
+    pub resolver: String,
+    /// The transitive dependencies are pinned to the resolver as well
+    pub tree: bool,
+}
+
+/// Returns the pins of the dependency entries that set a resolver keyed by
+/// group_id:artifact_id. Path and git dependencies are not fetched from resolvers
+pub fn get_config_dependency_pins(config: &LabToml) -> HashMap<String, DependencyPin> {
+    [
+        &config.dependencies,
+        &config.test_dependencies,
+        &config.annotation_processors,
+    ]
+    .into_iter()
+    .flatten()
+    .flatten()
+    .filter(|(_, dep)| !is_local(dep))
+    .filter_map(|(name, dep)| {
+        let resolver = dep.resolver.clone()?;
+        let artifact_id = dep.artifact_id.as_deref().unwrap_or(name);
+        Some((
+            format!("{}:{}", dep.group_id, artifact_id),
+            DependencyPin {
+                resolver,
+                tree: dep.pin_tree.unwrap_or(false),
+            },
+        ))
+    })
+    .collect()
+}
+
+/// Project wide settings that affect how dependencies are resolved
+#[derive(Debug, Default, Clone)]
+pub struct ResolveOptions {
+    /// Artifact policy evaluated on the resolved dependencies
+    pub policy: PolicyTable,
+    /// Group id prefixes pinned to a single resolver
+    pub pins: HashMap<String, String>,
+    /// Dependency entries pinned to a single resolver keyed by group_id:artifact_id
+    pub dependency_pins: HashMap<String, DependencyPin>,
+    /// Walk the dependency tree even if the resolution inputs did not change
+    pub refresh: bool,
+    /// Transitive dependency scopes resolved in addition to compile
+    pub scopes: Vec<Scope>,
+    /// Resolve dependencies marked as optional
+    pub include_optional: bool,
+    /// Number of poms downloaded at the same time. 0 uses the default
+    pub jobs: usize,
+    /// Annotation processors resolved onto the processor path
+    pub processors: Vec<Project>,
+    /// Dependencies resolved into the test section of Labt.lock
+    pub test_dependencies: Vec<Project>,
+    /// Only resolve from the labt cache and Labt.lock
+    pub offline: bool,
+    /// How long locked -SNAPSHOT versions are used before checking for a newer build.
+    /// None uses the default refresh interval
+    pub snapshot_refresh: Option<Duration>,
+    /// Platform poms whose dependencyManagement supplies the versions of dependencies
+    /// declared without one
+    pub boms: Vec<Project>,
+    /// Deprecated artifacts to warn about
+    pub deprecations: Deprecations,
+    /// Warn about dependencies with classes for a newer Java release than this
+    pub max_java_release: Option<u16>,
+}
+
+impl TryFrom<&LabToml> for ResolveOptions {
+    type Error = anyhow::Error;
+    fn try_from(config: &LabToml) -> anyhow::Result<Self> {
+        let resolution = config.resolution.clone().unwrap_or_default();
+        let mut options = ResolveOptions {
+            policy: config.policy.clone().unwrap_or_default(),
+            pins: config.pins.clone().unwrap_or_default(),
+            dependency_pins: get_config_dependency_pins(config),
+            refresh: false,
+            scopes: Vec::new(),
+            include_optional: resolution.optional,
+            jobs: resolution.jobs.unwrap_or_default(),
+            processors: get_config_processors(config),
+            test_dependencies: get_config_test_dependencies(config),
+            offline: crate::is_offline(),
+            snapshot_refresh: resolution
+                .snapshot_refresh
+                .map(|minutes| Duration::from_secs(minutes * 60)),
+            boms: get_config_boms(config),
+            deprecations: Deprecations::new(config.deprecations.as_ref()),
+            max_java_release: resolution.max_java_release,
+        };
+        options
+            .include_scopes(&resolution.scopes)
+            .context("Invalid scopes in [resolution] of Labt.toml")?;
+        Ok(options)
+    }
+}
+
+impl ResolveOptions {
+    /// Returns the resolver this group is pinned to. The longest matching prefix wins.
+    pub fn pinned_resolver(&self, group_id: &str) -> Option<&str> {
+        self.pins
+            .iter()
+            .filter(|(prefix, _)| group_matches_prefix(group_id, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, resolver)| resolver.as_str())
+    }
+    /// Returns the pin of the dependency entry of this artifact
+    pub fn dependency_pin(&self, group_id: &str, artifact_id: &str) -> Option<&DependencyPin> {
+        self.dependency_pins
+            .get(&format!("{group_id}:{artifact_id}"))
+    }
+    /// Adds scopes to be resolved in addition to compile
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a scope is not one of [`INCLUDABLE_SCOPES`]
+    pub fn include_scopes(&mut self, scopes: &[String]) -> anyhow::Result<()> {
+        for scope in scopes {
+            if !INCLUDABLE_SCOPES.contains(&scope.as_str()) {
+                bail!(
+                    "Unsupported resolution scope \"{scope}\". Expected one of {}",
+                    INCLUDABLE_SCOPES.join(", ")
+                );
+            }
+            let scope = scope.parse::<Scope>()?;
+            if !self.scopes.contains(&scope) {
+                self.scopes.push(scope);
+            }
+        }
+        Ok(())
+    }
+    /// Number of poms to download at the same time
+    pub fn jobs(&self) -> usize {
+        if self.jobs == 0 {
+            DEFAULT_JOBS
+        } else {
+            self.jobs
+        }
+    }
+    /// Returns true if a dependency with this scope and optional flag should be resolved
+    pub fn includes(&self, scope: &Scope, optional: bool) -> bool {
+        if optional && !self.include_optional {
+            return false;
+        }
+        *scope == Scope::COMPILE || self.scopes.contains(scope)
+    }
+}
+
 #[derive(Debug, Default, Clone)]
 pub struct ProjectDep {
     pub artifact_id: String,
@@ -76,8 +394,125 @@ pub struct ProjectDep {
     pub packaging: String,
     pub cache_hit: bool,
     pub constraints: Option<Constraint>,
+    /// The dependency paths that requested this artifact during resolution.
+    /// Only used to report version conflicts and not saved to Labt.lock
+    pub requirements: Vec<Requirement>,
+    /// Name of the resolver whose repository supplied the artifact
+    pub resolver: Option<String>,
+    /// When this version was first resolved, in UTC e.g. 2024-05-01T10:15:30Z
+    pub resolved_at: Option<String>,
+    /// Sha256 of the downloaded artifact. Cached artifacts that no longer match it
+    /// are rejected. Snapshots and local artifacts change over time and have none
+    pub sha256: Option<String>,
 }
 
+/// A version of an artifact requested by a project in the dependency tree
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Requirement {
+    /// group_id:artifact_id of the projects from the direct dependency
+    /// down to the project that requested the artifact
+    pub path: Vec<String>,
+    /// The requested version
+    pub version: String,
+}
+
+impl Display for Requirement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Labt.toml")?;
+        for project in &self.path {
+            write!(f, " -> {project}")?;
+        }
+        write!(f, " requires {}", self.version)
+    }
+}
+
+/// A version conflict that could not be resolved, with the dependency paths
+/// that led to it and the overrides that would fix it
+#[derive(Debug)]
+pub struct ConflictReport {
+    pub group_id: String,
+    pub artifact_id: String,
+    /// The version resolved before the conflict was detected
+    pub selected: String,
+    pub constraint: Option<Constraint>,
+    /// The requirements that produced the selected version
+    pub existing: Vec<Requirement>,
+    /// The requirement that could not be satisfied
+    pub incoming: Requirement,
+    /// The version that would have been selected for the incoming requirement
+    pub candidate: String,
+}
+
+impl ConflictReport {
+    /// Builds a report for the last requirement added to the resolved dependency
+    fn new(dep: &ProjectDep, candidate: &str) -> Self {
+        let mut existing = dep.requirements.clone();
+        let incoming = existing.pop().unwrap_or_default();
+        ConflictReport {
+            group_id: dep.group_id.clone(),
+            artifact_id: dep.artifact_id.clone(),
+            selected: dep.version.clone(),
+            constraint: dep.constraints.clone(),
+            existing,
+            incoming,
+            candidate: candidate.to_string(),
+        }
+    }
+}
+
+impl Display for ConflictReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Dependency version conflict on {}:{}",
+            self.group_id, self.artifact_id
+        )?;
+        writeln!(f, "  {} was selected earlier for:", self.selected)?;
+        if self.existing.is_empty() {
+            writeln!(f, "    {}", LOCK_FILE)?;
+        }
+        for requirement in &self.existing {
+            writeln!(f, "    {requirement}")?;
+        }
+        if let Some(constraint) = &self.constraint {
+            writeln!(f, "  with the constraint {constraint}")?;
+        }
+        writeln!(f, "  which does not satisfy:")?;
+        writeln!(f, "    {}", self.incoming)?;
+        writeln!(
+            f,
+            "Direct dependencies take precedence. To force a version, add one of these to [dependencies] in Labt.toml:"
+        )?;
+        let mut versions = vec![&self.selected];
+        if self.candidate != self.selected {
+            versions.push(&self.candidate);
+        }
+        for version in versions {
+            writeln!(
+                f,
+                "    {} = {{ group_id = \"{}\", version = \"{version}\" }}",
+                self.artifact_id, self.group_id
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConflictReport {}
+
+/// A dependency that is not in the labt cache while resolving offline. The walk skips
+/// it and carries on so that all the artifacts that need network access are reported.
+#[derive(Debug)]
+struct NotCached(String);
+
+impl Display for NotCached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is not in the labt cache", self.0)
+    }
+}
+
+impl std::error::Error for NotCached {}
+
 /// This is a summary of all dependency constraints that we need to
 /// prevent conflicts and other crazy stuff
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -158,7 +593,7 @@ impl PartialOrd for ProjectDep {
         if self.artifact_id != other.artifact_id {
             return None;
         }
-        match version_compare::compare(&self.version, &other.version)
+        match crate::version::compare(&self.version, &other.version)
             .unwrap_or(version_compare::Cmp::Ne)
         {
             version_compare::Cmp::Lt => Some(Ordering::Less),
@@ -262,19 +697,19 @@ impl Constraint {
             VersionRequirement::Soft(version) => {
                 // if an exact version is specified, lock on it
                 if let Some(exact) = &self.exact {
-                    return version_compare::compare_to(version, exact, version_compare::Cmp::Eq)
+                    return crate::version::compare_to(version, exact, version_compare::Cmp::Eq)
                         .map_err(|_| version_parse_error(version, exact));
                 }
                 // Min
                 if let Some((inclusive, min)) = &self.min {
                     // reject any version below this number
                     if *inclusive {
-                        if !version_compare::compare_to(version, min, version_compare::Cmp::Ge)
+                        if !crate::version::compare_to(version, min, version_compare::Cmp::Ge)
                             .map_err(|_| version_parse_error(version, min))?
                         {
                             return Ok(false);
                         }
-                    } else if !version_compare::compare_to(version, min, version_compare::Cmp::Gt)
+                    } else if !crate::version::compare_to(version, min, version_compare::Cmp::Gt)
                         .map_err(|_| version_parse_error(version, min))?
                     {
                         return Ok(false);
@@ -284,12 +719,12 @@ impl Constraint {
                 if let Some((inclusive, max)) = &self.max {
                     // reject any versions above this version
                     if *inclusive {
-                        if !version_compare::compare_to(version, max, version_compare::Cmp::Le)
+                        if !crate::version::compare_to(version, max, version_compare::Cmp::Le)
                             .map_err(|_| version_parse_error(version, max))?
                         {
                             return Ok(false);
                         }
-                    } else if !version_compare::compare_to(version, max, version_compare::Cmp::Lt)
+                    } else if !crate::version::compare_to(version, max, version_compare::Cmp::Lt)
                         .map_err(|_| version_parse_error(version, max))?
                     {
                         return Ok(false);
@@ -313,7 +748,7 @@ impl Constraint {
                         pom::VersionRange::Ge(version) => {
                             // if an exact version is specified, lock on it
                             if let Some(exact) = &self.exact {
-                                return version_compare::compare_to(
+                                return crate::version::compare_to(
                                     version,
                                     exact,
                                     version_compare::Cmp::Eq,
@@ -322,7 +757,7 @@ impl Constraint {
                             }
                             if let Some((inclusive, max)) = &self.max {
                                 if *inclusive {
-                                    if !version_compare::compare_to(
+                                    if !crate::version::compare_to(
                                         version,
                                         max,
                                         version_compare::Cmp::Le,
@@ -331,7 +766,7 @@ impl Constraint {
                                     {
                                         return Ok(false);
                                     }
-                                } else if !version_compare::compare_to(
+                                } else if !crate::version::compare_to(
                                     version,
                                     max,
                                     version_compare::Cmp::Lt,
@@ -345,7 +780,7 @@ impl Constraint {
                         pom::VersionRange::Gt(version) => {
                             // if an exact version is specified, lock on it
                             if let Some(exact) = &self.exact {
-                                return version_compare::compare_to(
+                                return crate::version::compare_to(
                                     version,
                                     exact,
                                     version_compare::Cmp::Eq,
@@ -353,7 +788,7 @@ impl Constraint {
                                 .map_err(|_| version_parse_error(version, exact));
                             }
                             if let Some((_, max)) = &self.max {
-                                if !version_compare::compare_to(
+                                if !crate::version::compare_to(
                                     version,
                                     max,
                                     version_compare::Cmp::Lt,
@@ -368,7 +803,7 @@ impl Constraint {
                         pom::VersionRange::Lt(version) => {
                             // if an exact version is specified, lock on it
                             if let Some(exact) = &self.exact {
-                                return version_compare::compare_to(
+                                return crate::version::compare_to(
                                     version,
                                     exact,
                                     version_compare::Cmp::Eq,
@@ -376,7 +811,7 @@ impl Constraint {
                                 .map_err(|_| version_parse_error(version, exact));
                             }
                             if let Some((_, min)) = &self.min {
-                                if !version_compare::compare_to(
+                                if !crate::version::compare_to(
                                     version,
                                     min,
                                     version_compare::Cmp::Gt,
@@ -390,7 +825,7 @@ impl Constraint {
                         pom::VersionRange::Le(version) => {
                             // if an exact version is specified, lock on it
                             if let Some(exact) = &self.exact {
-                                return version_compare::compare_to(
+                                return crate::version::compare_to(
                                     version,
                                     exact,
                                     version_compare::Cmp::Eq,
@@ -399,7 +834,7 @@ impl Constraint {
                             }
                             if let Some((inclusive, min)) = &self.min {
                                 if *inclusive {
-                                    if !version_compare::compare_to(
+                                    if !crate::version::compare_to(
                                         version,
                                         min,
                                         version_compare::Cmp::Ge,
@@ -408,7 +843,7 @@ impl Constraint {
                                     {
                                         return Ok(false);
                                     }
-                                } else if !version_compare::compare_to(
+                                } else if !crate::version::compare_to(
                                     version,
                                     min,
                                     version_compare::Cmp::Gt,
@@ -423,7 +858,7 @@ impl Constraint {
                         pom::VersionRange::Eq(version) => {
                             // if an exact version is specified, lock on it
                             if let Some(exact) = &self.exact {
-                                return version_compare::compare_to(
+                                return crate::version::compare_to(
                                     version,
                                     exact,
                                     version_compare::Cmp::Eq,
@@ -435,7 +870,7 @@ impl Constraint {
                             if let Some((inclusive, min)) = &self.min {
                                 // reject any version below this number
                                 if *inclusive {
-                                    if !version_compare::compare_to(
+                                    if !crate::version::compare_to(
                                         version,
                                         min,
                                         version_compare::Cmp::Ge,
@@ -444,7 +879,7 @@ impl Constraint {
                                     {
                                         return Ok(false);
                                     }
-                                } else if !version_compare::compare_to(
+                                } else if !crate::version::compare_to(
                                     version,
                                     min,
                                     version_compare::Cmp::Gt,
@@ -458,7 +893,7 @@ impl Constraint {
                             if let Some((inclusive, max)) = &self.max {
                                 // reject any versions above this version
                                 if *inclusive {
-                                    if !version_compare::compare_to(
+                                    if !crate::version::compare_to(
                                         version,
                                         max,
                                         version_compare::Cmp::Le,
@@ -467,7 +902,7 @@ impl Constraint {
                                     {
                                         return Ok(false);
                                     }
-                                } else if !version_compare::compare_to(
+                                } else if !crate::version::compare_to(
                                     version,
                                     max,
                                     version_compare::Cmp::Lt,
@@ -493,34 +928,34 @@ impl Constraint {
             }
         }
     }
-    pub fn within_range(target: &String, start: &VersionRange, end: &VersionRange) -> bool {
+    pub fn within_range(target: &str, start: &VersionRange, end: &VersionRange) -> bool {
         // if a version is on the left of the start, then it is out of range
         // if version is on right of the end, then it is out of range
         //
         match start {
             VersionRange::Gt(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Gt).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Gt).unwrap() {
                     return false;
                 }
             }
             VersionRange::Ge(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Ge).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Ge).unwrap() {
                     return false;
                 }
             }
             // The value is within this v and -ve Infinity
             VersionRange::Lt(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Lt).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Lt).unwrap() {
                     return false;
                 }
             }
             VersionRange::Le(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Le).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Le).unwrap() {
                     return false;
                 }
             }
             VersionRange::Eq(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Eq).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Eq).unwrap() {
                     return false;
                 }
             }
@@ -528,28 +963,28 @@ impl Constraint {
 
         match end {
             VersionRange::Lt(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Lt).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Lt).unwrap() {
                     return false;
                 }
             }
             VersionRange::Le(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Le).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Le).unwrap() {
                     return false;
                 }
             }
             // The value is within this v and +ve infinity
             VersionRange::Gt(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Gt).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Gt).unwrap() {
                     return false;
                 }
             }
             VersionRange::Ge(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Ge).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Ge).unwrap() {
                     return false;
                 }
             }
             VersionRange::Eq(v) => {
-                if !version_compare::compare_to(target, v, version_compare::Cmp::Eq).unwrap() {
+                if !crate::version::compare_to(target, v, version_compare::Cmp::Eq).unwrap() {
                     return false;
                 }
             }
@@ -992,8 +1427,7 @@ impl Constraint {
                     // check if we already have an exact set.
                     if let Some(exact) = &self.exact {
                         // We have an exact, they must be equal before we proceed
-                        if !version_compare::compare_to(&c, exact, version_compare::Cmp::Eq)
-                            .unwrap()
+                        if !crate::version::compare_to(&c, exact, version_compare::Cmp::Eq).unwrap()
                         {
                             // This is an error. We are conflicting very hard
                             bail!("Conflicting exact versions set with existing ={exact} and incomming ={c}");
@@ -1051,7 +1485,15 @@ impl Constraint {
 pub struct ProjectWrapper {
     project: Project,
     resolvers: Rc<RefCell<Vec<Box<dyn Resolver>>>>,
-    progress: Option<Rc<RefCell<ProgressBar>>>,
+    options: Option<Rc<ResolveOptions>>,
+    prefetcher: Option<Arc<PomPrefetcher>>,
+    /// Collects the dependencies missing from the cache when resolving offline
+    missing: Option<Rc<RefCell<Vec<String>>>>,
+    /// The dependencyManagement imported from the project boms. Supplies the
+    /// versions of dependencies declared without one anywhere in the tree
+    management: Option<Rc<HashMap<String, Project>>>,
+    /// The resolver of an ancestor pinned with pin_tree
+    tree_pin: Option<String>,
 }
 
 impl ProjectWrapper {
@@ -1059,11 +1501,84 @@ impl ProjectWrapper {
         ProjectWrapper {
             project,
             resolvers,
-            progress: None,
+            options: None,
+            prefetcher: None,
+            missing: None,
+            management: None,
+            tree_pin: None,
         }
     }
-    pub fn set_progress_bar(&mut self, progress: Option<Rc<RefCell<ProgressBar>>>) {
-        self.progress = progress;
+    /// Sets the resolution options to apply on this project and its dependencies
+    pub fn set_options(&mut self, options: Option<Rc<ResolveOptions>>) {
+        self.options = options;
+    }
+    /// Sets the pool that downloads the poms of dependencies ahead of the walk
+    pub fn set_prefetcher(&mut self, prefetcher: Option<Arc<PomPrefetcher>>) {
+        self.prefetcher = prefetcher;
+    }
+    /// Sets where dependencies missing from the cache are collected instead of failing
+    /// the walk. Used when resolving offline
+    pub fn set_missing(&mut self, missing: Option<Rc<RefCell<Vec<String>>>>) {
+        self.missing = missing;
+    }
+    /// Sets the dependencyManagement imported from the project boms
+    pub fn set_management(&mut self, management: Option<Rc<HashMap<String, Project>>>) {
+        self.management = management;
+    }
+    /// Creates the wrapper of a dependency or parent sharing this wrapper's settings
+    fn child(&self, project: Project) -> ProjectWrapper {
+        let mut wrapper = ProjectWrapper::new(project, self.resolvers.clone());
+        wrapper.set_options(self.options.clone());
+        wrapper.set_prefetcher(self.prefetcher.clone());
+        wrapper.set_missing(self.missing.clone());
+        wrapper.set_management(self.management.clone());
+        wrapper.tree_pin = self.child_tree_pin();
+        wrapper
+    }
+    /// Returns the pin the dependencies of this project inherit
+    fn child_tree_pin(&self) -> Option<String> {
+        self.options
+            .as_ref()
+            .and_then(|options| {
+                options.dependency_pin(
+                    &self.project.get_group_id(),
+                    &self.project.get_artifact_id(),
+                )
+            })
+            .filter(|pin| pin.tree)
+            .map(|pin| pin.resolver.clone())
+            .or_else(|| self.tree_pin.clone())
+    }
+    /// Returns the resolver the project is pinned to if any. The pin of its dependency
+    /// entry comes first, then the group pins and then the pin inherited from an
+    /// ancestor pinned with pin_tree
+    fn pinned_resolver(
+        options: Option<&ResolveOptions>,
+        project: &Project,
+        tree_pin: Option<&str>,
+    ) -> Option<String> {
+        let group_id = project.get_group_id();
+        options
+            .and_then(|options| {
+                options
+                    .dependency_pin(&group_id, &project.get_artifact_id())
+                    .map(|pin| pin.resolver.as_str())
+                    .or_else(|| options.pinned_resolver(&group_id))
+            })
+            .or(tree_pin)
+            .map(String::from)
+    }
+    /// Checks if the resolver can be queried for a project given its pinned resolver.
+    /// The cache is always allowed, its origin is checked separately.
+    fn resolver_allowed(resolver: &dyn Resolver, pinned: Option<&str>) -> bool {
+        match pinned {
+            Some(pin) => {
+                resolver.get_name() == pin
+                    || resolver.get_name() == CACHE_REPO_STR
+                    || resolver.get_name() == PATH_REPO_STR
+            }
+            None => true,
+        }
     }
     #[allow(unused)]
     pub fn add_resolver(&mut self, resolver: Box<dyn Resolver>) {
@@ -1074,10 +1589,54 @@ impl ProjectWrapper {
         let mut url = String::new();
         let mut cache_hit = false;
 
+        let pinned = Self::pinned_resolver(
+            self.options.as_deref(),
+            &self.project,
+            self.tree_pin.as_deref(),
+        );
+        // the url of the pinned resolver, used to confirm the origin of cached artifacts
+        let pinned_url = match &pinned {
+            // the pinned repository cannot be queried offline so the cache is trusted
+            Some(_) if self.options.as_ref().is_some_and(|o| o.offline) => None,
+            Some(pin) => Some(
+                self.resolvers
+                    .borrow()
+                    .iter()
+                    .find(|r| r.get_name() == pin)
+                    .and_then(|r| r.get_base_url().map(|url| url.to_string()))
+                    .context(format!(
+                        "{}:{} is pinned to resolver \"{}\" which is not configured",
+                        self.project.get_group_id(),
+                        self.project.get_artifact_id(),
+                        pin
+                    ))?,
+            ),
+            None => None,
+        };
+
+        // a repository specific failure, reported if no other resolver has the dependency
+        let mut failure: Option<(String, ResolverError)> = None;
         for resolver in self.resolvers.borrow_mut().iter() {
+            if !Self::resolver_allowed(resolver.as_ref(), pinned.as_deref()) {
+                continue;
+            }
+            // keep a copy in case the cache entry came from a different repo than the pinned one
+            let snapshot = pinned_url.as_ref().map(|_| self.project.clone());
             match resolver.fetch(&mut self.project) {
                 Err(err) => match err.kind() {
                     ResolverErrorKind::NotFound => continue,
+                    ResolverErrorKind::ParseError if resolver.get_name() == CACHE_REPO_STR => {
+                        // the entry is written again once the dependency is fetched from a repository
+                        warn!(target: "fetch", "Ignoring corrupt cache entry for {}: {}. Fetching it again.",
+                            self.project.qualified_name().unwrap_or_default(),
+                            err.to_string().trim_end());
+                        continue;
+                    }
+                    kind if kind.try_next_resolver() => {
+                        log::trace!(target: "fetch", "Trying the next resolver after {} failed: {}", resolver.get_name(), err.to_string().trim_end());
+                        failure = Some((resolver.get_name().to_string(), err));
+                        continue;
+                    }
                     _ => {
                         return Err(anyhow!(err).context(format!(
                             "Error while trying to resolve dependency on {}",
@@ -1086,6 +1645,13 @@ impl ProjectWrapper {
                     }
                 },
                 Ok(base_url) => {
+                    if let (Some(pinned_url), Some(snapshot)) = (&pinned_url, snapshot) {
+                        if base_url.trim_end_matches('/') != pinned_url.trim_end_matches('/') {
+                            // cached from another repo, fetch it again from the pinned resolver
+                            self.project = snapshot;
+                            continue;
+                        }
+                    }
                     url = base_url;
                     found = true;
                     cache_hit = resolver.get_name() == CACHE_REPO_STR;
@@ -1096,6 +1662,25 @@ impl ProjectWrapper {
 
         // we failed to fetch dependency across all configured resolvers
         if !found {
+            if let Some(missing) = &self.missing {
+                let name = self.project.qualified_name()?;
+                missing.borrow_mut().push(name.clone());
+                return Err(anyhow!(NotCached(name)));
+            }
+            if let Some((name, err)) = failure {
+                return Err(anyhow!(err).context(format!(
+                    "Failed to resolve dependency \"{}\" on {}",
+                    self.project.qualified_name()?,
+                    name
+                )));
+            }
+            if let Some(pin) = pinned {
+                bail!(
+                    "Dependency \"{}\" not found on its pinned resolver \"{}\"",
+                    self.project.qualified_name()?,
+                    pin
+                );
+            }
             bail!(
                 "Dependency \"{}\" not found on all configured resolvers",
                 self.project.qualified_name()?
@@ -1104,18 +1689,124 @@ impl ProjectWrapper {
 
         Ok((url, cache_hit))
     }
+    /// Fetches the full pom of the project. The cache resolver only keeps the resolved
+    /// dependencies so a cached pom is parsed directly to keep its dependencyManagement.
+    fn fetch_pom(&mut self) -> anyhow::Result<()> {
+        if let Some(version) = self.project.get_selected_version().clone() {
+            let mut cache = Cache::new(
+                self.project.get_group_id(),
+                self.project.get_artifact_id(),
+                version,
+                CacheType::POM,
+            );
+            if cache.use_labt_home().is_ok() {
+                if let Ok(file) = cache.open() {
+                    if let Ok(project) = pom::parse_pom(BufReader::new(file), self.project.clone())
+                    {
+                        self.project = project;
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        self.fetch()?;
+        Ok(())
+    }
+    /// Fetches the pom of a bom and returns its dependencyManagement including the
+    /// entries inherited from its parents and imported from other boms
+    fn import_bom(
+        &mut self,
+        importing: &mut Vec<String>,
+    ) -> anyhow::Result<HashMap<String, Project>> {
+        let key = format!(
+            "{}:{}",
+            self.project.get_group_id(),
+            self.project.get_artifact_id()
+        );
+        if importing.contains(&key) {
+            bail!(
+                "Cyclic bom import of {} through {}",
+                key,
+                importing.join(" -> ")
+            );
+        }
+        importing.push(key);
+        self.fetch_pom().context(format!(
+            "Failed to fetch bom {}",
+            self.project.qualified_name().unwrap_or_default()
+        ))?;
+
+        let mut management = self.project.get_dependency_management().clone();
+        if let Some(parent) = self.project.parent.clone() {
+            let inherited = self
+                .child(Project::new(
+                    &parent.group_id,
+                    &parent.artifact_id,
+                    &parent.version,
+                ))
+                .import_bom(importing)?;
+            for (key, dep) in inherited {
+                management.entry(key).or_insert(dep);
+            }
+        }
+        let management = self.managed_dependencies(&management, importing)?;
+        importing.pop();
+        Ok(management)
+    }
+    /// Replaces the import scoped entries of a dependencyManagement section with the
+    /// entries of the boms they reference. Declared entries take precedence over
+    /// imported ones and earlier imports over later ones.
+    fn managed_dependencies(
+        &self,
+        management: &HashMap<String, Project>,
+        importing: &mut Vec<String>,
+    ) -> anyhow::Result<HashMap<String, Project>> {
+        let mut managed = HashMap::new();
+        let mut imports = Vec::new();
+        for (key, dep) in management {
+            if dep.get_scope() == Scope::IMPORT {
+                imports.push(dep.clone());
+            } else {
+                managed.insert(key.clone(), dep.clone());
+            }
+        }
+        imports.sort_by_key(|dep| dep.qualified_name().unwrap_or_default());
+        for import in imports {
+            for (key, dep) in self.child(import).import_bom(importing)? {
+                managed.entry(key).or_insert(dep);
+            }
+        }
+        Ok(managed)
+    }
 
     fn compute_version(
         resolvers: Rc<RefCell<Vec<Box<dyn Resolver>>>>,
         dep: &Project,
+        pinned: Option<&str>,
     ) -> anyhow::Result<String> {
         let mut found = false;
         let mut version = String::new();
+        let mut failure: Option<(String, ResolverError)> = None;
 
         for resolver in resolvers.borrow_mut().iter() {
+            if !Self::resolver_allowed(resolver.as_ref(), pinned) {
+                continue;
+            }
             match resolver.calculate_version(dep) {
                 Err(err) => match err.kind() {
                     ResolverErrorKind::NotFound => continue,
+                    ResolverErrorKind::ParseError if resolver.get_name() == CACHE_REPO_STR => {
+                        // the network resolvers overwrite the cached metadata
+                        warn!(target: "fetch", "Ignoring corrupt cached maven-metadata.xml for {}:{}. Fetching it again.",
+                            dep.get_group_id(),
+                            dep.get_artifact_id());
+                        continue;
+                    }
+                    kind if kind.try_next_resolver() => {
+                        log::trace!(target: "fetch", "Trying the next resolver after {} failed: {}", resolver.get_name(), err.to_string().trim_end());
+                        failure = Some((resolver.get_name().to_string(), err));
+                        continue;
+                    }
                     ResolverErrorKind::NoSelectedVersion => {
                         // metadata was found but no correct version was found
                         if resolver.get_name() == CACHE_REPO_STR {
@@ -1148,6 +1839,14 @@ impl ProjectWrapper {
         }
         // we failed to fetch dependency across all configured resolvers
         if !found {
+            if let Some((name, err)) = failure {
+                return Err(anyhow!(err).context(format!(
+                    "Failed to compute the version of \"{}:{}\" on {} resolver",
+                    dep.get_group_id(),
+                    dep.get_artifact_id(),
+                    name
+                )));
+            }
             bail!(
                 "No correct version could be selected for \"{}:{}\" on all configured resolvers",
                 dep.get_group_id(),
@@ -1174,6 +1873,7 @@ impl BuildTree for ProjectWrapper {
         resolved: &mut Vec<ProjectDep>,
         unresolved: &mut Vec<String>,
     ) -> anyhow::Result<()> {
+        crate::interrupt::check()?;
         let selected_version_err = |group_id, artifact_id| {
             anyhow!(
                 "No selected version set for package {}:{}",
@@ -1194,6 +1894,18 @@ impl BuildTree for ProjectWrapper {
                 self.project.get_artifact_id(),
             ))?;
 
+        // reject blocked coordinates before doing any network requests. Minimum
+        // versions are enforced on the versions that win conflict resolution
+        if let Some(options) = &self.options {
+            let violations = options.policy.check_blocked(
+                &self.project.get_group_id(),
+                &self.project.get_artifact_id(),
+            );
+            if let Some(violation) = violations.first() {
+                bail!("Dependency policy violation: {violation}");
+            }
+        }
+
         // push this project to unresolved
         // Nearest Defination Wins
         // So we only compare group_id and artifact_id.
@@ -1203,19 +1915,21 @@ impl BuildTree for ProjectWrapper {
             self.project.get_group_id(),
             self.project.get_artifact_id()
         ));
+        let requirement = Requirement {
+            path: unresolved[..unresolved.len() - 1].to_vec(),
+            version: self.project.get_version().to_string(),
+        };
 
         // Version was resolved earlier and this is just a version conflict
         let mut resolved_earlier = false;
 
-        if let Some(prog) = &self.progress {
-            let prog = prog.borrow();
-            prog.set_message(format!(" {} ", qualified_name));
-            prog.set_prefix("Fetching");
-        }
         info!(target: "fetch", "{} scope {:?}",
             qualified_name,
             self.project.get_scope(),
         );
+        emit(Event::DependencyStarted {
+            coordinate: qualified_name.clone(),
+        });
         // before we even proceed to do this "expensive" fetch just confirm this isn't a
         // potential version conflict and return instead
         if let Some((index, res)) = resolved.iter_mut().enumerate().find(|(_, res)| {
@@ -1224,9 +1938,10 @@ impl BuildTree for ProjectWrapper {
         }) {
             // We have already seen this package with same group and artifact id.
             // but are the versions the same?
+            res.requirements.push(requirement.clone());
 
             // now check version for possible conflicts
-            match version_compare::compare(&res.version, &version) {
+            match crate::version::compare(&res.version, &version) {
                 Ok(v) => match v {
                     version_compare::Cmp::Eq => {
                         // the versions are same, so skip resolving as it has already been resolved
@@ -1247,7 +1962,7 @@ impl BuildTree for ProjectWrapper {
                                     // A soft version has been suggested. We can override it at will.
                                     // always prefer a more later version
                                     if constraints.within(c).unwrap() {
-                                        if version_compare::compare_to(
+                                        if crate::version::compare_to(
                                             &version,
                                             &res.version,
                                             version_compare::Cmp::Gt,
@@ -1262,26 +1977,48 @@ impl BuildTree for ProjectWrapper {
                                             resolved_earlier = true;
                                         } else {
                                             // we don't need this tree direction, its older
+                                            events::conflict_resolved(
+                                                &res.group_id,
+                                                &res.artifact_id,
+                                                &version,
+                                                &res.version,
+                                            );
                                             unresolved.pop();
                                             return Ok(());
                                         }
                                     } else {
                                         // It is a softie and out of range; we dont need you
+                                        events::conflict_resolved(
+                                            &res.group_id,
+                                            &res.artifact_id,
+                                            &version,
+                                            &res.version,
+                                        );
                                         unresolved.pop();
                                         return Ok(());
                                     }
                                 }
                                 VersionRequirement::Unset => {
                                     // Someone did not bother choosing a version, so any can do. In this case just use the already resolved one.
+                                    events::conflict_resolved(
+                                        &res.group_id,
+                                        &res.artifact_id,
+                                        &version,
+                                        &res.version,
+                                    );
                                     unresolved.pop();
                                     return Ok(());
                                 }
                                 VersionRequirement::Hard(v) => {
                                     // These are commandments. If we cannot fit within, then this is not recoverable at all.
-                                    if constraints.within(c).context(format!(
-                                        "Failed to contain {:?} within {constraints}",
-                                        v,
-                                    ))? {
+                                    if constraints
+                                        .within(c)
+                                        .context(format!(
+                                            "Failed to contain {:?} within {constraints}",
+                                            v,
+                                        ))
+                                        .with_context(|| ConflictReport::new(res, &version))?
+                                    {
                                         // we can fit within, so make the version more strict than ever
                                         let containment = constraints.contain(c).unwrap();
                                         // If containment has an exact value set, update it and resolve
@@ -1297,10 +2034,18 @@ impl BuildTree for ProjectWrapper {
                                             let vr = VersionRequirement::from(&containment);
 
                                             // step 2: Calculate the suitable version
+                                            let mut project =
+                                                Project::new(&res.group_id, &res.artifact_id, "");
+                                            project.set_version(vr);
+                                            let pinned = Self::pinned_resolver(
+                                                self.options.as_deref(),
+                                                &project,
+                                                self.tree_pin.as_deref(),
+                                            );
                                             let version = Self::compute_version(
                                                 Rc::clone(&self.resolvers),
-                                                Project::new(&res.group_id, &res.artifact_id, "")
-                                                    .set_version(vr),
+                                                &project,
+                                                pinned.as_deref(),
                                             )
                                             .context(format!("Failed to calculate a version for dependency {}:{}.", res.group_id, res.artifact_id))
                                             .context(format!("No appropriate version could be selected that could sastify {} on this version conflict.", containment))?;
@@ -1313,12 +2058,7 @@ impl BuildTree for ProjectWrapper {
                                         resolved_earlier = true;
                                     } else {
                                         // the constraint cannot fit in this. This is fatal.
-                                        bail!(
-                                            "Dependency version conflict. {}:{} has a hard set version requirements as {} which does not fit within previously set constraint of {constraints}. Canceling the resolution.",
-                                            self.project.get_group_id(),
-                                            self.project.get_artifact_id(),
-                                            v.iter().map(|k| k.to_string()).collect::<Vec<String>>().join(", ")
-                                        );
+                                        return Err(anyhow!(ConflictReport::new(res, &version)));
                                     }
                                 }
                             }
@@ -1331,12 +2071,18 @@ impl BuildTree for ProjectWrapper {
                             match c {
                                 VersionRequirement::Unset => {
                                     // No version set, so just use already resolved
+                                    events::conflict_resolved(
+                                        &res.group_id,
+                                        &res.artifact_id,
+                                        &version,
+                                        &res.version,
+                                    );
                                     unresolved.pop();
                                     return Ok(());
                                 }
                                 VersionRequirement::Soft(v) => {
                                     // two softies, prefer the latest
-                                    if let Ok(cmp) = version_compare::compare(v, &res.version) {
+                                    if let Ok(cmp) = crate::version::compare(v, &res.version) {
                                         match cmp {
                                             version_compare::Cmp::Ge | version_compare::Cmp::Gt => {
                                                 // resolve this, it is bigger
@@ -1347,6 +2093,12 @@ impl BuildTree for ProjectWrapper {
                                             version_compare::Cmp::Lt
                                             | version_compare::Cmp::Le
                                             | version_compare::Cmp::Eq => {
+                                                events::conflict_resolved(
+                                                    &res.group_id,
+                                                    &res.artifact_id,
+                                                    &version,
+                                                    &res.version,
+                                                );
                                                 unresolved.pop();
                                                 return Ok(());
                                             }
@@ -1360,7 +2112,9 @@ impl BuildTree for ProjectWrapper {
                                     // no constraint was specified on the already resolved version.
                                     // so just override everything
                                     let mut new_constraint = Constraint::default();
-                                    new_constraint.contain_mut(c)?;
+                                    new_constraint.contain_mut(c).with_context(|| {
+                                        ConflictReport::new(&resolved[index], &version)
+                                    })?;
                                     resolved[index].version = version.clone();
                                     resolved[index].constraints = Some(new_constraint);
                                     resolved_earlier = true;
@@ -1376,26 +2130,43 @@ impl BuildTree for ProjectWrapper {
                     )));
                 }
             }
+            if resolved_earlier {
+                events::conflict_resolved(
+                    &resolved[index].group_id,
+                    &resolved[index].artifact_id,
+                    &version,
+                    &resolved[index].version,
+                );
+            }
         }
         // fetch the dependencies of this project
-        let (url, cache_hit) = self.fetch().context(format!(
-            "Error fetching {} scope {:?}",
-            qualified_name,
-            self.project.get_scope(),
-        ))?;
+        let (url, cache_hit) = match self.fetch() {
+            Ok(fetched) => fetched,
+            Err(err) if err.is::<NotCached>() => {
+                unresolved.pop();
+                return Ok(());
+            }
+            Err(err) => {
+                return Err(err.context(format!(
+                    "Error fetching {} scope {:?}",
+                    qualified_name,
+                    self.project.get_scope(),
+                )))
+            }
+        };
 
+        // boms imported by the parent dependencyManagement
+        let mut parent_imports = HashMap::new();
         if let Some(parent) = &self.project.parent {
             // if we are given a parent, try to fetch the parent common dependencies
             // obtain the returned dependencies and merge it with our chain.
 
             // this should just bubble up the parent tree
-            let mut wrapper = ProjectWrapper::new(
-                Project::new(&parent.group_id, &parent.artifact_id, &parent.version),
-                self.resolvers.clone(),
-            );
-            if let Some(progress) = &self.progress {
-                wrapper.set_progress_bar(Some(progress.clone()));
-            }
+            let mut wrapper = self.child(Project::new(
+                &parent.group_id,
+                &parent.artifact_id,
+                &parent.version,
+            ));
             log::trace!(target: "fetch", "Fetching parent {}:{}:{} for {}:{}", 
                 parent.group_id,
                 parent.artifact_id,
@@ -1405,6 +2176,12 @@ impl BuildTree for ProjectWrapper {
 
             wrapper.build_tree(resolved, unresolved)?;
             let management = wrapper.project.get_dependency_management();
+            parent_imports.extend(
+                management
+                    .iter()
+                    .filter(|(_, dep)| dep.get_scope() == Scope::IMPORT)
+                    .map(|(key, dep)| (key.clone(), dep.clone())),
+            );
             for dep in self.project.get_dependencies_mut() {
                 let key = format!("{}:{}", dep.get_group_id(), dep.get_artifact_id());
                 if let Some(parent_dep) = management.get(&key) {
@@ -1415,13 +2192,31 @@ impl BuildTree for ProjectWrapper {
             self.project.get_dependencies_mut().extend(deps);
         }
 
-        let excludes = Rc::new(self.project.get_excludes().clone());
-        self.project.get_dependencies_mut().retain(|dep| {
-            if dep.get_scope().ne(&pom::Scope::COMPILE) {
-                return false;
+        // dependencies declared without a version take it from the project boms, then from
+        // this pom's dependencyManagement and the boms it or its parent import
+        let unversioned = |dep: &Project| dep.get_version() == &VersionRequirement::Unset;
+        if self.project.get_dependencies().iter().any(unversioned) {
+            let mut management = self.project.get_dependency_management().clone();
+            for (key, dep) in parent_imports {
+                management.entry(key).or_insert(dep);
             }
+            let local = self.managed_dependencies(&management, &mut Vec::new())?;
+            let root = self.management.clone();
+            for dep in self.project.get_dependencies_mut() {
+                let key = format!("{}:{}", dep.get_group_id(), dep.get_artifact_id());
+                if let Some(managed) = root.as_ref().and_then(|root| root.get(&key)) {
+                    dep.manage_version(managed);
+                } else if let Some(managed) = local.get(&key) {
+                    dep.manage_version(managed);
+                }
+            }
+        }
 
-            if dep.is_optional() {
+        let excludes = Rc::new(self.project.get_excludes().clone());
+        let default_options = ResolveOptions::default();
+        let filter = self.options.as_deref().unwrap_or(&default_options);
+        self.project.get_dependencies_mut().retain(|dep| {
+            if !filter.includes(&dep.get_scope(), dep.is_optional()) {
                 return false;
             }
 
@@ -1461,28 +2256,32 @@ impl BuildTree for ProjectWrapper {
             }
             true // this particular guy survived, such a waster of clock cycles, good for it
         });
+        let options = self.options.clone();
+        let tree_pin = self.child_tree_pin();
         for dep in self.project.get_dependencies_mut() {
+            let pinned = Self::pinned_resolver(options.as_deref(), dep, tree_pin.as_deref());
             // use version resolvers to compute the version of this dependency if needed
-            let version =
-                Self::compute_version(Rc::clone(&self.resolvers), dep).context(format!(
+            let version = Self::compute_version(Rc::clone(&self.resolvers), dep, pinned.as_deref())
+                .context(format!(
                     "Failed to calculate a version for dependency {}:{}.", // the artifact might even not exist
                     dep.get_group_id(),
                     dep.get_artifact_id()
                 ))?;
             // from here now on we have a version for even the recursive calls, therefore there should be no complaints
             dep.set_selected_version(Some(version.clone()));
-
+            // start downloading the siblings while the first subtree is walked
+            if let Some(prefetcher) = &self.prefetcher {
+                prefetcher.submit(&dep.get_group_id(), &dep.get_artifact_id(), &version);
+            }
+        }
+        for dep in self.project.get_dependencies() {
             if unresolved.contains(&format!("{}:{}", dep.get_group_id(), dep.get_artifact_id())) {
                 // Circular dep, if encountered,
                 // TODO check config for ignore, warn, or Error
                 log::trace!(target: "fetch", "Circular dependency detected for {}:{}, Using \"Nearest defination wins\". ", dep.get_group_id(), dep.get_artifact_id());
                 continue;
             }
-            let mut wrapper = ProjectWrapper::new(dep.clone(), self.resolvers.clone());
-            if let Some(progress) = &self.progress {
-                wrapper.set_progress_bar(Some(progress.clone()));
-            }
-            wrapper.build_tree(resolved, unresolved)?;
+            self.child(dep.clone()).build_tree(resolved, unresolved)?;
         }
 
         // remove this project from unresolved
@@ -1493,8 +2292,14 @@ impl BuildTree for ProjectWrapper {
             self.project.get_group_id(),
             self.project.get_artifact_id(),
         ))?;
+        emit(Event::DependencyFinished {
+            coordinate: qualified_name,
+            repository: url.clone(),
+            cache_hit,
+        });
         project.base_url = url;
         project.cache_hit = cache_hit;
+        project.requirements.push(requirement);
 
         if !resolved_earlier {
             resolved.push(project);
@@ -1503,6 +2308,313 @@ impl BuildTree for ProjectWrapper {
     }
 }
 
+/// Computes a hash of all the inputs that affect the resolution result. These are
+/// the dependencies, the resolvers and the resolution options. If the hash matches the
+/// one stored in Labt.lock, then the lock is still a valid resolution for the project.
+pub fn resolution_fingerprint(
+    dependencies: &[Project],
+    resolvers: &[Box<dyn Resolver>],
+    options: &ResolveOptions,
+) -> String {
+    let mut lines = vec![format!("labt {}", crate::LABT_VERSION)];
+
+    let mut deps: Vec<String> = dependencies
+        .iter()
+        .map(|dep| {
+            let mut line = format!(
+                "dependency {}:{}:{}:{}",
+                dep.get_group_id(),
+                dep.get_artifact_id(),
+                dep.get_version(),
+                dep.get_selected_version().clone().unwrap_or_default()
+            );
+            if dep.get_scope() != Scope::COMPILE {
+                line.push_str(&format!(" {}", dep.get_scope()));
+            }
+            line
+        })
+        .collect();
+    deps.sort();
+    lines.extend(deps);
+    lines.extend(options.processors.iter().map(|processor| {
+        format!(
+            "processor {}:{}:{}",
+            processor.get_group_id(),
+            processor.get_artifact_id(),
+            processor.get_version()
+        )
+    }));
+    lines.extend(options.test_dependencies.iter().map(|dep| {
+        format!(
+            "test {}:{}:{}",
+            dep.get_group_id(),
+            dep.get_artifact_id(),
+            dep.get_version()
+        )
+    }));
+    lines.extend(options.boms.iter().map(|bom| {
+        format!(
+            "bom {}:{}:{}",
+            bom.get_group_id(),
+            bom.get_artifact_id(),
+            bom.get_version()
+        )
+    }));
+
+    // resolver order matters so it is not sorted
+    lines.extend(resolvers.iter().map(|resolver| {
+        format!(
+            "resolver {} {} {}",
+            resolver.get_name(),
+            resolver.get_base_url().unwrap_or_default(),
+            resolver.get_priority()
+        )
+    }));
+
+    let mut rules: Vec<String> = options
+        .pins
+        .iter()
+        .map(|(prefix, resolver)| format!("pin {prefix} {resolver}"))
+        .chain(options.dependency_pins.iter().map(|(coordinate, pin)| {
+            format!("dependency-pin {coordinate} {} {}", pin.resolver, pin.tree)
+        }))
+        .chain(
+            options
+                .policy
+                .blocked
+                .iter()
+                .map(|blocked| format!("blocked {blocked}")),
+        )
+        .chain(
+            options
+                .policy
+                .repositories
+                .iter()
+                .map(|(prefix, allowed)| format!("repository {prefix} {}", allowed.join(","))),
+        )
+        .chain(
+            options
+                .policy
+                .minimum_versions
+                .iter()
+                .map(|(coordinate, version)| format!("minimum {coordinate} {version}")),
+        )
+        .collect();
+    rules.extend(options.scopes.iter().map(|scope| format!("scope {scope}")));
+    // the inherited properties can change the versions the poms ask for
+    rules.extend(
+        get_properties()
+            .iter()
+            .map(|(key, value)| format!("property {key}={value}")),
+    );
+    if options.include_optional {
+        rules.push(String::from("optional"));
+    }
+    rules.sort();
+    lines.extend(rules);
+
+    let mut sha = Sha1::new();
+    for line in lines {
+        sha.update(line.as_bytes());
+        sha.update(b"\n");
+    }
+    format!("{:x}", sha.finalize())
+}
+
+/// Formats seconds since the unix epoch as a UTC timestamp e.g. 2024-05-01T10:15:30Z
+pub fn format_utc(secs: u64) -> String {
+    // days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let days = (secs / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let doe = days - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    let time = secs % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// The resolution timestamps of the locked versions by group_id:artifact_id:version
+fn resolved_times(lock: &LabtLock) -> HashMap<String, String> {
+    lock.resolved
+        .iter()
+        .chain(&lock.processors)
+        .chain(&lock.test)
+        .filter_map(|dep| {
+            dep.resolved_at.as_ref().map(|at| {
+                (
+                    format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version),
+                    at.clone(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// The sha256 of the locked artifacts by group_id:artifact_id:version
+fn locked_hashes(lock: &LabtLock) -> HashMap<String, String> {
+    lock.resolved
+        .iter()
+        .chain(&lock.processors)
+        .chain(&lock.test)
+        .filter_map(|dep| {
+            dep.sha256.as_ref().map(|sha256| {
+                (
+                    format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version),
+                    sha256.clone(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// True if the artifact never changes once published so that its hash can be locked.
+/// Snapshots are rebuilt by their repository and local artifacts by the project
+fn is_hashable(dep: &ProjectDep) -> bool {
+    !is_snapshot(&dep.version)
+        && !dep.base_url.starts_with("file:")
+        && dep.resolver.as_deref() != Some(PATH_REPO_STR)
+}
+
+/// Gives the versions that were already locked their previous hash so that the
+/// downloads are checked against it
+fn restore_hashes(lock: &mut LabtLock, previous: &HashMap<String, String>) {
+    let deps = lock
+        .resolved
+        .iter_mut()
+        .chain(lock.processors.iter_mut())
+        .chain(lock.test.iter_mut());
+    for dep in deps {
+        let coordinate = format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version);
+        dep.sha256 = previous
+            .get(&coordinate)
+            .filter(|_| is_hashable(dep))
+            .cloned();
+    }
+}
+
+/// Hashes the cached artifacts of the locked dependencies that have no hash yet.
+/// Returns true if any hash was added
+///
+/// # Errors
+///
+/// Returns an error if a cached artifact cannot be read
+fn record_hashes(lock: &mut LabtLock) -> anyhow::Result<bool> {
+    let mut added = false;
+    let deps = lock
+        .resolved
+        .iter_mut()
+        .chain(lock.processors.iter_mut())
+        .chain(lock.test.iter_mut());
+    for dep in deps.filter(|dep| dep.sha256.is_none() && is_hashable(dep)) {
+        let mut cache = Cache::from(&*dep);
+        cache.use_labt_home()?;
+        if !cache.exists() {
+            continue;
+        }
+        let path = cache.get_path()?;
+        dep.sha256 = Some(file_sha256(&path).context(format!("Failed to hash {:?}", path))?);
+        added = true;
+    }
+    Ok(added)
+}
+
+/// Records which resolver supplied each locked dependency and when its version was
+/// first resolved. Versions that were already locked keep their timestamp so that only
+/// the entries that changed show up in Labt.lock diffs.
+fn record_provenance(
+    lock: &mut LabtLock,
+    previous: &HashMap<String, String>,
+    resolvers: &[Box<dyn Resolver>],
+) {
+    let now = format_utc(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default(),
+    );
+    let deps = lock
+        .resolved
+        .iter_mut()
+        .chain(lock.processors.iter_mut())
+        .chain(lock.test.iter_mut());
+    for dep in deps {
+        let base_url = dep.base_url.trim_end_matches('/');
+        let resolver = resolvers
+            .iter()
+            .find(|r| r.get_base_url().map(|url| url.trim_end_matches('/')) == Some(base_url))
+            .map(|r| r.get_name().to_string());
+        if resolver.is_some() {
+            dep.resolver = resolver;
+        } else if dep.base_url.starts_with("file:") {
+            dep.resolver = Some(PATH_REPO_STR.to_string());
+        }
+        let coordinate = format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version);
+        dep.resolved_at = Some(previous.get(&coordinate).cloned().unwrap_or(now.clone()));
+    }
+}
+
+/// Returns true if the dependency is a -SNAPSHOT version whose cached metadata is due
+/// for a refresh. Never true offline since the repositories cannot be checked.
+fn snapshot_needs_refresh(dep: &ProjectDep, options: &ResolveOptions) -> bool {
+    !options.offline
+        && is_snapshot(&dep.version)
+        && snapshot_is_stale(
+            &dep.group_id,
+            &dep.artifact_id,
+            &dep.version,
+            options.snapshot_refresh.unwrap_or(DEFAULT_SNAPSHOT_REFRESH),
+        )
+}
+
+/// True if any locked dependency is a snapshot due for a refresh
+fn has_stale_snapshots(lock: &LabtLock, options: &ResolveOptions) -> bool {
+    lock.resolved
+        .iter()
+        .chain(&lock.processors)
+        .chain(&lock.test)
+        .any(|dep| snapshot_needs_refresh(dep, options))
+}
+
+/// Emits [`Event::ResolutionStarted`] and, once dropped, [`Event::ResolutionFinished`]
+/// so that listeners hear about the end of a resolution that failed or panicked
+struct ResolutionEvents {
+    start: Instant,
+    /// The number of resolved dependencies once resolution succeeded
+    resolved: Option<usize>,
+}
+
+impl ResolutionEvents {
+    fn start(dependencies: usize) -> Self {
+        emit(Event::ResolutionStarted { dependencies });
+        ResolutionEvents {
+            start: Instant::now(),
+            resolved: None,
+        }
+    }
+    fn succeeded(&mut self, resolved: usize) {
+        self.resolved = Some(resolved);
+    }
+}
+
+impl Drop for ResolutionEvents {
+    fn drop(&mut self) {
+        emit(Event::ResolutionFinished {
+            resolved: self.resolved.unwrap_or(0),
+            success: self.resolved.is_some(),
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+        });
+    }
+}
+
 /// Starts the resolution algorithm. Reads any existing Labt.lock and it includes
 /// its resolution in the algorithm. After complete resolution it writes the result to
 /// Labt.lock
@@ -1517,53 +2629,566 @@ impl BuildTree for ProjectWrapper {
 pub fn resolve(
     dependencies: Vec<Project>,
     resolvers: Vec<Box<dyn Resolver>>,
+    options: ResolveOptions,
 ) -> anyhow::Result<Vec<Project>> {
     // load labt.lock file directory
-    let mut path: PathBuf = get_project_root()
-        .context("Failed to get project root directory")?
-        .clone();
-    path.push(LOCK_FILE);
+    let root: PathBuf = get_project_root().context("Failed to get project root directory")?;
+    let path = lock_file_in(&root);
+    warn_dev_overrides(&root);
+    let mut events = ResolutionEvents::start(dependencies.len());
 
     // list of resolvers by their order of priority
     let resolvers = Rc::new(RefCell::new(resolvers));
+    let options = Rc::new(options);
 
     // load resolved dependencies from lock file
     let mut lock: LabtLock = if path.exists() {
         load_labt_lock()?
     } else {
-        LabtLock::default()
+        committed_lock(&root)?
+    };
+    // what was read, so that changes other processes write in the meantime are merged
+    let base = lock.clone();
+    let fingerprint = resolution_fingerprint(&dependencies, &resolvers.borrow(), &options);
+    // path dependencies may have changed on disk so they always need a rebuild
+    let has_path_deps = resolvers
+        .borrow()
+        .iter()
+        .any(|r| r.get_name() == PATH_REPO_STR);
+    let stale_snapshots = has_stale_snapshots(&lock, &options);
+    if !options.refresh
+        && !has_path_deps
+        && !stale_snapshots
+        && lock.fingerprint.as_ref() == Some(&fingerprint)
+    {
+        // nothing changed since the last resolution, just confirm the artifacts are available
+        info!(target: "resolve", "Dependencies unchanged since last resolution. Using {:?}", path.file_name().unwrap_or_default());
+        save_dependencies(&lock.resolved).context("Failed downloading saved dependencies")?;
+        save_dependencies(&lock.processors)
+            .context("Failed downloading saved annotation processors")?;
+        save_dependencies(&lock.test).context("Failed downloading saved test dependencies")?;
+        // locks written before hashes were recorded get them on the next resolution
+        let base = lock.clone();
+        if record_hashes(&mut lock)? {
+            update_lock(&path, &base, &lock)?;
+        }
+        warn_deprecated(&lock, &options);
+        warn_bytecode(&lock, &options);
+        events.succeeded(lock.resolved.len());
+        return Ok(dependencies);
+    }
+    lock.fingerprint = Some(fingerprint);
+    let previous = resolved_times(&lock);
+    let hashes = locked_hashes(&lock);
+    if stale_snapshots {
+        // locked versions are not fetched again, so drop the snapshots to check for a newer build
+        lock.resolved
+            .retain(|dep| !snapshot_needs_refresh(dep, &options));
+    }
+
+    let declared_scopes: Vec<(String, Scope)> = dependencies
+        .iter()
+        .map(|dep| {
+            (
+                format!("{}:{}", dep.get_group_id(), dep.get_artifact_id()),
+                dep.get_scope(),
+            )
+        })
+        .collect();
+    let resolved_projects =
+        walk_dependencies(dependencies, &mut lock.resolved, &resolvers, &options)?;
+    apply_scopes(&mut lock.resolved, &declared_scopes);
+    // processors are not on the app classpath so they do not conflict with its dependencies
+    lock.processors.clear();
+    walk_dependencies(
+        options.processors.clone(),
+        &mut lock.processors,
+        &resolvers,
+        &options,
+    )
+    .context("Failed to resolve annotation processors")?;
+    lock.test = walk_test_dependencies(&lock.resolved, &resolvers, &options)?;
+    record_provenance(&mut lock, &previous, &resolvers.borrow());
+    restore_hashes(&mut lock, &hashes);
+
+    // the lock is written after the downloads so that it records the artifact hashes
+    save_dependencies(&lock.resolved).context("Failed downloading saved dependencies")?;
+    save_dependencies(&lock.processors)
+        .context("Failed downloading saved annotation processors")?;
+    save_dependencies(&lock.test).context("Failed downloading saved test dependencies")?;
+    record_hashes(&mut lock)?;
+    let lock = update_lock(&path, &base, &lock)?;
+    if let Err(err) = record_known_lock(&path) {
+        warn!(target: "resolve", "Failed to record {:?} in labt home: {:?}", path, err);
+    }
+    // the query commands read the graph instead of parsing the cached poms again
+    if let Some(root) = path.parent() {
+        if let Err(err) = refresh_graph(root, &lock) {
+            warn!(target: "resolve", "Failed to cache the dependency graph: {:?}", err);
+        }
+    }
+    warn_deprecated(&lock, &options);
+    warn_bytecode(&lock, &options);
+    events.succeeded(lock.resolved.len());
+    Ok(resolved_projects)
+}
+
+/// The scope of a dependency declared with child scope by a dependency in parent scope
+fn transitive_scope(parent: &Scope, child: &Scope) -> Scope {
+    match (parent, child) {
+        (Scope::COMPILE, child) => child.clone(),
+        (parent @ (Scope::RUNTIME | Scope::PROVIDED | Scope::TEST), Scope::COMPILE) => {
+            parent.clone()
+        }
+        (Scope::PROVIDED | Scope::TEST, Scope::RUNTIME) => parent.clone(),
+        (_, child) => child.clone(),
+    }
+}
+
+/// The scope of an artifact needed in both scopes. Test is the narrowest and compile
+/// the widest. Provided and runtime together make compile
+fn widest_scope(a: &Scope, b: &Scope) -> Scope {
+    match (a, b) {
+        (a, b) if a == b => a.clone(),
+        (Scope::TEST, other) | (other, Scope::TEST) => other.clone(),
+        _ => Scope::COMPILE,
+    }
+}
+
+/// Sets the scope of the resolved dependencies from the scopes their Labt.toml entries
+/// declare. Scopes are passed down the tree like maven does and an artifact reached
+/// through several paths takes the widest scope. Entries not reached keep their scope.
+pub fn apply_scopes(resolved: &mut [ProjectDep], declared: &[(String, Scope)]) {
+    let index: HashMap<String, usize> = resolved
+        .iter()
+        .enumerate()
+        .map(|(i, dep)| (format!("{}:{}", dep.group_id, dep.artifact_id), i))
+        .collect();
+    // the scope the poms declared the artifact with
+    let pom_scopes: Vec<Scope> = resolved.iter().map(|dep| dep.scope.clone()).collect();
+    let mut scopes: Vec<Option<Scope>> = vec![None; resolved.len()];
+    let mut queue = VecDeque::new();
+
+    // requeues the artifact only when its scope got wider
+    fn widen(scopes: &mut [Option<Scope>], queue: &mut VecDeque<usize>, i: usize, scope: Scope) {
+        let widened = match &scopes[i] {
+            Some(current) => widest_scope(current, &scope),
+            None => scope,
+        };
+        if scopes[i].as_ref() != Some(&widened) {
+            scopes[i] = Some(widened);
+            queue.push_back(i);
+        }
+    }
+    for (key, scope) in declared {
+        if let Some(&i) = index.get(key) {
+            widen(&mut scopes, &mut queue, i, scope.clone());
+        }
+    }
+    while let Some(i) = queue.pop_front() {
+        let parent = scopes[i].clone().unwrap_or_default();
+        for child in &resolved[i].dependencies {
+            // entries are group_id:artifact_id:version
+            let key = child
+                .rsplit_once(':')
+                .map_or(child.as_str(), |(key, _)| key);
+            if let Some(&c) = index.get(key) {
+                widen(
+                    &mut scopes,
+                    &mut queue,
+                    c,
+                    transitive_scope(&parent, &pom_scopes[c]),
+                );
+            }
+        }
+    }
+    for (dep, scope) in resolved.iter_mut().zip(scopes) {
+        if let Some(scope) = scope {
+            dep.scope = scope;
+        }
+    }
+}
+
+/// The committed Labt.lock of the project at root. A Labt.dev.lock starts from
+/// it so that the dependencies that are not overridden keep their versions
+fn committed_lock(root: &Path) -> anyhow::Result<LabtLock> {
+    let path = root.join(LOCK_FILE);
+    if !path.exists() {
+        return Ok(LabtLock::default());
+    }
+    let mut file = File::open(&path).context(format!("Unable to open {:?}", path))?;
+    load_lock_dependencies_with(&mut file)
+}
+
+/// Flags the dependencies replaced by Labt.dev.toml, whose resolution is kept
+/// out of Labt.lock
+fn warn_dev_overrides(root: &Path) {
+    let Ok(Some(overrides)) = load_dev_overrides_in(root) else {
+        return;
+    };
+    for (coordinate, dev) in &overrides {
+        warn!(target: "resolve", "{coordinate} is overridden with {} by {DEV_OVERRIDES_FILE}", dev.describe());
+    }
+    warn!(target: "resolve", "Resolving into {DEV_LOCK_FILE}. {LOCK_FILE} is left unchanged until {DEV_OVERRIDES_FILE} is removed");
+}
+
+/// Warns about the deprecated artifacts in the lock and suggests their replacements
+fn warn_deprecated(lock: &LabtLock, options: &ResolveOptions) {
+    for section in [&lock.resolved, &lock.processors, &lock.test] {
+        for hint in options.deprecations.hints(section) {
+            warn!(target: "resolve", "{hint}");
+        }
+    }
+}
+
+/// Warns about the dependencies compiled for a newer Java release than max_java_release
+/// allows, which dexing would reject
+fn warn_bytecode(lock: &LabtLock, options: &ResolveOptions) {
+    let Some(max_release) = options.max_java_release else {
+        return;
     };
+    match check_bytecode(&lock.resolved, max_release) {
+        Ok(violations) => {
+            for violation in violations {
+                warn!(target: "resolve", "{} contains classes for Java {} (class file version {}) but max_java_release is {}",
+                    violation.coordinate,
+                    java_release(violation.major),
+                    violation.major,
+                    max_release);
+            }
+        }
+        Err(err) => {
+            warn!(target: "resolve", "Failed to check the bytecode of the dependencies: {:?}", err)
+        }
+    }
+}
+
+/// Starts downloading the poms of the dependencies in parallel and hands the pool to
+/// the network resolvers. Returns None if poms should be fetched one by one.
+fn start_prefetcher(
+    dependencies: &[Project],
+    resolvers: &mut [Box<dyn Resolver>],
+    options: &ResolveOptions,
+) -> Option<Arc<PomPrefetcher>> {
+    let jobs = options.jobs();
+    if jobs <= 1 {
+        return None;
+    }
+    let repositories: Vec<(String, String)> = resolvers
+        .iter()
+        .filter_map(|resolver| {
+            resolver
+                .get_base_url()
+                .map(|url| (resolver.get_name().to_string(), url.to_string()))
+        })
+        .collect();
+    // offline there are no repositories to fetch from
+    if repositories.is_empty() {
+        return None;
+    }
+    let prefetcher = Arc::new(PomPrefetcher::new(repositories, options.clone(), jobs));
+    for resolver in resolvers.iter_mut() {
+        resolver.set_prefetcher(Some(Arc::clone(&prefetcher)));
+    }
+    for dep in dependencies {
+        if let Some(version) = dep.get_selected_version() {
+            prefetcher.submit(&dep.get_group_id(), &dep.get_artifact_id(), version);
+        }
+    }
+    Some(prefetcher)
+}
+
+/// Walks the dependency trees of the projects adding them to the lock. Dependencies already
+/// in the lock are not fetched again but their constraints are checked for conflicts.
+fn walk_dependencies(
+    dependencies: Vec<Project>,
+    resolved: &mut Vec<ProjectDep>,
+    resolvers: &Rc<RefCell<Vec<Box<dyn Resolver>>>>,
+    options: &Rc<ResolveOptions>,
+) -> anyhow::Result<Vec<Project>> {
     let mut unresolved = vec![];
 
     // start a new spinner progress bar and add it to the global multi progress bar
-    let spinner = Rc::new(RefCell::new(
-        MULTI_PROGRESS_BAR.add(ProgressBar::new_spinner()),
-    ));
+    let spinner = MULTI_PROGRESS_BAR.add(ProgressBar::new_spinner());
+    spinner.enable_steady_tick(Duration::from_millis(100));
     spinner
-        .borrow()
-        .enable_steady_tick(Duration::from_millis(100));
-    spinner
-        .borrow()
         .set_style(ProgressStyle::with_template("\n{spinner} {prefix:.blue} {wide_msg}").unwrap());
+    // the spinner follows the walk through its events
+    let bar = spinner.clone();
+    let _progress = subscribe(move |event| {
+        if let Event::DependencyStarted { coordinate } = event {
+            bar.set_message(format!(" {} ", coordinate));
+            bar.set_prefix("Fetching");
+        }
+    });
+
+    let prefetcher = start_prefetcher(&dependencies, &mut resolvers.borrow_mut(), options);
+    let missing = options.offline.then(|| Rc::new(RefCell::new(Vec::new())));
+    let mut resolved_projects: Vec<Project> = Vec::new();
+    let new_wrapper = |project: Project| {
+        let mut wrapper = ProjectWrapper::new(project, Rc::clone(resolvers));
+        wrapper.set_options(Some(Rc::clone(options)));
+        wrapper.set_prefetcher(prefetcher.clone());
+        wrapper.set_missing(missing.clone());
+        wrapper
+    };
+
+    let mut management = None;
+    let result = options.boms.iter().try_for_each(|bom| {
+        let imported = new_wrapper(bom.clone()).import_bom(&mut Vec::new())?;
+        let management = management.get_or_insert_with(HashMap::new);
+        for (key, dep) in imported {
+            management.entry(key).or_insert(dep);
+        }
+        anyhow::Ok(())
+    });
+    let management = management.map(Rc::new);
+
+    let result = result.and_then(|_| {
+        dependencies.into_iter().try_for_each(|mut project| {
+            if project.get_version() == &VersionRequirement::Unset {
+                let key = format!("{}:{}", project.get_group_id(), project.get_artifact_id());
+                let managed = management
+                    .as_ref()
+                    .and_then(|management| management.get(&key))
+                    .context(format!(
+                        "Dependency {key} has no version and is not managed by a bom"
+                    ))?;
+                project.manage_version(managed);
+            }
+            // create a new project wrapper for dependency resolution
+            let mut wrapper = new_wrapper(project);
+            wrapper.set_management(management.clone());
+
+            // walk the dependency tree
+            wrapper.build_tree(resolved, &mut unresolved)?;
+            resolved_projects.push(wrapper.project);
+            anyhow::Ok(())
+        })
+    });
+    if prefetcher.is_some() {
+        for resolver in resolvers.borrow_mut().iter_mut() {
+            resolver.set_prefetcher(None);
+        }
+    }
+    // clear progressbar
+    spinner.finish_and_clear();
+    result?;
+    if let Some(missing) = missing {
+        let mut missing = missing.take();
+        if !missing.is_empty() {
+            missing.sort();
+            missing.dedup();
+            bail!(
+                "Offline mode: these dependencies are not in the labt cache and need network access:\n  {}\nRun again without --offline to download them",
+                missing.join("\n  ")
+            );
+        }
+    }
+
+    // versions may have shifted during conflict resolution and older lock entries
+    // were never walked, so evaluate the policy on the final result
+    options.policy.enforce(resolved, &resolvers.borrow())?;
+
+    Ok(resolved_projects)
+}
+
+/// Walks the test dependencies on top of the resolved project dependencies and returns
+/// only what the tests add. Test dependencies see the project versions but are not
+/// allowed to change them, so adding a test library never changes what gets packaged.
+///
+/// # Errors
+///
+/// Returns an error if resolution fails or a test dependency needs a different version
+/// of a project dependency
+fn walk_test_dependencies(
+    main: &[ProjectDep],
+    resolvers: &Rc<RefCell<Vec<Box<dyn Resolver>>>>,
+    options: &Rc<ResolveOptions>,
+) -> anyhow::Result<Vec<ProjectDep>> {
+    if options.test_dependencies.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut graph = main.to_vec();
+    walk_dependencies(
+        options.test_dependencies.clone(),
+        &mut graph,
+        resolvers,
+        options,
+    )
+    .context("Failed to resolve test dependencies")?;
+
+    let mut test = Vec::new();
+    let mut changed = Vec::new();
+    for dep in graph {
+        match main
+            .iter()
+            .find(|m| m.group_id == dep.group_id && m.artifact_id == dep.artifact_id)
+        {
+            Some(m) if m.version != dep.version => changed.push(format!(
+                "{}:{} {} -> {}",
+                dep.group_id, dep.artifact_id, m.version, dep.version
+            )),
+            Some(_) => {}
+            None => test.push(dep),
+        }
+    }
+    if !changed.is_empty() {
+        bail!(
+            "Test dependencies would change the versions of project dependencies:\n  {}\nAdd the required versions to [dependencies] or use a compatible test dependency",
+            changed.join("\n  ")
+        );
+    }
+    Ok(test)
+}
+
+/// Resolves the entries that both sides of a lock file merge changed differently.
+/// Only the conflicting artifacts are walked, at the versions given in conflicts,
+/// against the rest of the merged lock so the other entries stay as merged.
+///
+/// # Errors
+///
+/// Returns an error if a conflicting artifact cannot be resolved
+pub fn resolve_merge_conflicts(
+    lock: &mut LabtLock,
+    conflicts: &LabtLock,
+    resolvers: Vec<Box<dyn Resolver>>,
+    options: ResolveOptions,
+) -> anyhow::Result<()> {
+    let projects = |deps: &[ProjectDep]| -> Vec<Project> {
+        deps.iter()
+            .map(|dep| {
+                let mut p = Project::new(&dep.group_id, &dep.artifact_id, &dep.version);
+                p.set_selected_version(Some(dep.version.clone()));
+                p
+            })
+            .collect()
+    };
+    let resolvers = Rc::new(RefCell::new(resolvers));
+    let options = Rc::new(options);
+    let previous = resolved_times(lock);
+    let mut hashes = locked_hashes(lock);
+    hashes.extend(locked_hashes(conflicts));
+
+    if !conflicts.resolved.is_empty() {
+        walk_dependencies(
+            projects(&conflicts.resolved),
+            &mut lock.resolved,
+            &resolvers,
+            &options,
+        )?;
+    }
+    if !conflicts.processors.is_empty() {
+        walk_dependencies(
+            projects(&conflicts.processors),
+            &mut lock.processors,
+            &resolvers,
+            &options,
+        )
+        .context("Failed to resolve annotation processors")?;
+    }
+    if !conflicts.test.is_empty() {
+        // test entries are walked on top of the project dependencies they share
+        let mut graph: Vec<ProjectDep> = lock.resolved.iter().chain(&lock.test).cloned().collect();
+        walk_dependencies(projects(&conflicts.test), &mut graph, &resolvers, &options)
+            .context("Failed to resolve test dependencies")?;
+        lock.test = graph
+            .into_iter()
+            .filter(|dep| {
+                !lock
+                    .resolved
+                    .iter()
+                    .any(|m| m.group_id == dep.group_id && m.artifact_id == dep.artifact_id)
+            })
+            .collect();
+    }
+    record_provenance(lock, &previous, &resolvers.borrow());
+    restore_hashes(lock, &hashes);
+    Ok(())
+}
+
+/// Returns true if only the subtree of the added dependency needs to be walked. This is
+/// the case if Labt.lock is the resolution of the previous dependencies and the added
+/// dependency is new rather than a version change of an existing one.
+pub fn can_resolve_incrementally(
+    lock: &LabtLock,
+    previous: &[Project],
+    added: &Project,
+    resolvers: &[Box<dyn Resolver>],
+    options: &ResolveOptions,
+) -> bool {
+    if options.refresh
+        || resolvers.iter().any(|r| r.get_name() == PATH_REPO_STR)
+        || has_stale_snapshots(lock, options)
+    {
+        return false;
+    }
+    let replaced = previous.iter().any(|dep| {
+        dep.get_group_id() == added.get_group_id()
+            && dep.get_artifact_id() == added.get_artifact_id()
+    });
+    !replaced
+        && lock.fingerprint.as_ref() == Some(&resolution_fingerprint(previous, resolvers, options))
+}
+
+/// Resolves a dependency added to Labt.toml. If Labt.lock is up to date with the previous
+/// dependencies, only the added dependency subtree is walked and checked against the
+/// locked constraints. Otherwise all the dependencies are resolved again.
+///
+/// # Errors
+///
+/// This function will return an error if resolution fails or Labt.lock cannot be written
+pub fn resolve_added(
+    previous: Vec<Project>,
+    added: Project,
+    resolvers: Vec<Box<dyn Resolver>>,
+    options: ResolveOptions,
+) -> anyhow::Result<Vec<Project>> {
+    let root: PathBuf = get_project_root().context("Failed to get project root directory")?;
+    let path = lock_file_in(&root);
 
-    let mut resolved_projects: Vec<Project> = Vec::new();
+    let mut lock: LabtLock = if path.exists() {
+        load_labt_lock()?
+    } else {
+        committed_lock(&root)?
+    };
+    let base = lock.clone();
+
+    let incremental = can_resolve_incrementally(&lock, &previous, &added, &resolvers, &options);
+    let mut dependencies: Vec<Project> = previous
+        .into_iter()
+        .filter(|dep| {
+            dep.get_group_id() != added.get_group_id()
+                || dep.get_artifact_id() != added.get_artifact_id()
+        })
+        .collect();
+    dependencies.push(added.clone());
+    if !incremental {
+        return resolve(dependencies, resolvers, options);
+    }
 
-    for project in dependencies {
-        // create a new project wrapper for dependency resolution
-        let mut wrapper = ProjectWrapper::new(project.clone(), Rc::clone(&resolvers));
-        wrapper.set_progress_bar(Some(spinner.clone()));
+    warn_dev_overrides(&root);
+    info!(target: "resolve", "Resolving {}:{} against {:?}", added.get_group_id(), added.get_artifact_id(), path.file_name().unwrap_or_default());
+    lock.fingerprint = Some(resolution_fingerprint(&dependencies, &resolvers, &options));
 
-        // walk the dependency tree
-        wrapper.build_tree(&mut lock.resolved, &mut unresolved)?;
-        resolved_projects.push(wrapper.project);
-    }
-    // clear progressbar
-    spinner.borrow().finish_and_clear();
+    let resolvers = Rc::new(RefCell::new(resolvers));
+    let options = Rc::new(options);
+    let previous = resolved_times(&lock);
+    let hashes = locked_hashes(&lock);
+    walk_dependencies(vec![added], &mut lock.resolved, &resolvers, &options)?;
+    // the added dependency may now be shared with the tests
+    lock.test = walk_test_dependencies(&lock.resolved, &resolvers, &options)?;
+    record_provenance(&mut lock, &previous, &resolvers.borrow());
+    restore_hashes(&mut lock, &hashes);
 
-    let mut file = File::create(path).context("Unable to open lock file")?;
-    write_lock(&mut file, &lock)?;
     save_dependencies(&lock.resolved).context("Failed downloading saved dependencies")?;
-    Ok(resolved_projects)
+    save_dependencies(&lock.test).context("Failed downloading saved test dependencies")?;
+    record_hashes(&mut lock)?;
+    update_lock(&path, &base, &lock)?;
+    if let Err(err) = record_known_lock(&path) {
+        warn!(target: "resolve", "Failed to record {:?} in labt home: {:?}", path, err);
+    }
+    Ok(dependencies)
 }
 #[cfg(test)]
 use pretty_assertions::assert_eq;
@@ -2229,6 +3854,10 @@ mod pom_faker {
             self.optional = value;
             self
         }
+        pub fn set_scope(mut self, scope: Scope) -> Self {
+            self.scope = scope;
+            self
+        }
     }
 
     static REPO: std::sync::LazyLock<
@@ -2730,6 +4359,109 @@ mod pom_faker {
     }
 }
 
+#[test]
+fn lock_hashes_follow_locked_versions() {
+    let dep = |artifact_id: &str, version: &str, base_url: &str| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: version.to_string(),
+        base_url: base_url.to_string(),
+        scope: Scope::COMPILE,
+        packaging: String::from("jar"),
+        ..Default::default()
+    };
+    let repo = "https://repo.example.com/maven2/";
+    let mut previous = LabtLock {
+        resolved: vec![
+            dep("kept", "1.0.0", repo),
+            dep("bumped", "1.0.0", repo),
+            dep("nightly", "2.0-SNAPSHOT", repo),
+        ],
+        ..Default::default()
+    };
+    for (i, dep) in previous.resolved.iter_mut().enumerate() {
+        dep.sha256 = Some(format!("{i}{}", "0".repeat(63)));
+    }
+    let hashes = locked_hashes(&previous);
+    assert_eq!(hashes.len(), 3);
+
+    // conflict resolution moved bumped to a new version
+    let mut lock = LabtLock {
+        resolved: vec![
+            dep("kept", "1.0.0", repo),
+            dep("bumped", "1.1.0", repo),
+            dep("nightly", "2.0-SNAPSHOT", repo),
+            dep("library", "1.0.0", "file:///home/user/library/"),
+        ],
+        ..Default::default()
+    };
+    lock.resolved[1].sha256 = previous.resolved[1].sha256.clone();
+    restore_hashes(&mut lock, &hashes);
+    assert_eq!(
+        lock.resolved
+            .iter()
+            .map(|dep| dep.sha256.is_some())
+            .collect::<Vec<_>>(),
+        vec![true, false, false, false]
+    );
+
+    let written = lock.to_string();
+    assert!(written.contains(&format!("sha256 = \"0{}\"", "0".repeat(63))));
+    let parsed: LabtLock = written.parse().unwrap();
+    assert_eq!(parsed.resolved[0].sha256, lock.resolved[0].sha256);
+    assert_eq!(parsed.resolved[1].sha256, None);
+}
+
+#[test]
+fn lock_provenance_keeps_existing_timestamps() {
+    assert_eq!(format_utc(0), "1970-01-01T00:00:00Z");
+    assert_eq!(format_utc(1709251199), "2024-02-29T23:59:59Z");
+
+    let dep = |artifact_id: &str, base_url: &str| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: String::from("1.0.0"),
+        base_url: base_url.to_string(),
+        ..Default::default()
+    };
+    let mut lock = LabtLock {
+        resolved: vec![
+            dep("kept", "https://repo.example.com/maven2/"),
+            dep("added", "https://repo.example.com/maven2"),
+            dep("library", "file:///home/user/library/"),
+        ],
+        ..Default::default()
+    };
+    let previous = HashMap::from([(
+        String::from("com.example:kept:1.0.0"),
+        String::from("2024-01-01T00:00:00Z"),
+    )]);
+    let resolvers: Vec<Box<dyn Resolver>> = vec![Box::new(
+        super::resolvers::NetResolver::init("example", "https://repo.example.com/maven2/").unwrap(),
+    )];
+    record_provenance(&mut lock, &previous, &resolvers);
+
+    let provenance: Vec<(Option<&str>, bool)> = lock
+        .resolved
+        .iter()
+        .map(|dep| {
+            (
+                dep.resolver.as_deref(),
+                dep.resolved_at.as_deref() == Some("2024-01-01T00:00:00Z"),
+            )
+        })
+        .collect();
+    assert_eq!(
+        provenance,
+        vec![
+            (Some("example"), true),
+            (Some("example"), false),
+            (Some(PATH_REPO_STR), false)
+        ]
+    );
+    assert!(lock.resolved[1].resolved_at.is_some());
+}
+
 #[test]
 fn some_test() {
     let server = pom_faker::PomServer::new().unwrap();
@@ -3066,6 +4798,44 @@ mod test {
         )
         .is_err()); // Maybe this error was a net related error and this test will be incorrect
     }
+    #[test]
+    fn version_conflict_report() {
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+
+        server.add_project(
+            ProjectEntry::new("com.example", "module-a", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "module-e", "[1.0]")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "module-b", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "module-e", "[2.0]")),
+        );
+
+        let dependencies = vec![
+            Project::new("com.example", "module-a", "1.0.0"),
+            Project::new("com.example", "module-b", "1.0.0"),
+        ];
+        let err = resolve(
+            dependencies,
+            &mut Vec::new(),
+            Rc::new(RefCell::new(create_resolver(port))),
+        )
+        .unwrap_err();
+        let report = err
+            .chain()
+            .find_map(|err| err.downcast_ref::<super::ConflictReport>())
+            .expect("Expected a conflict report");
+
+        assert_eq!(report.artifact_id, "module-e");
+        assert_eq!(report.selected, "1.0.0");
+        assert_eq!(report.existing[0].path, vec!["com.example:module-a"]);
+        assert_eq!(report.incoming.path, vec!["com.example:module-b"]);
+        assert_eq!(report.incoming.version, "[2.0]");
+        let text = report.to_string();
+        assert!(text.contains("Labt.toml -> com.example:module-b requires [2.0]"));
+        assert!(text.contains(r#"module-e = { group_id = "com.example", version = "2.0.0" }"#));
+    }
     /// Test case: Exclusion Ignored
     ///
     /// This test verifies the resolver's behavior when a module excludes a specific version of a
@@ -3487,4 +5257,626 @@ mod test {
         assert_eq!(module_c.artifact_id, String::from("module-c"));
         assert_eq!(module_c.version, String::from("3.0.0"));
     }
+
+    #[test]
+    fn included_scopes_and_optional() {
+        use super::{ResolveOptions, Scope};
+
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+        server.add_project(
+            ProjectEntry::new("com.example", "module-a", "1.0.0")
+                .add_dependency(
+                    ProjectEntry::new("com.example", "module-b", "RELEASE")
+                        .set_scope(Scope::PROVIDED),
+                )
+                .add_dependency(
+                    ProjectEntry::new("com.example", "module-c", "3.0.0").set_scope(Scope::TEST),
+                )
+                .add_dependency(
+                    ProjectEntry::new("com.example", "module-d", "RELEASE").set_optional(true),
+                ),
+        );
+
+        let build = |options: ResolveOptions| {
+            let mut resolved = Vec::new();
+            let mut project = Project::new("com.example", "module-a", "1.0.0");
+            project.set_selected_version(Some(String::from("1.0.0")));
+            let mut wrapper =
+                ProjectWrapper::new(project, Rc::new(RefCell::new(create_resolver(port))));
+            wrapper.set_options(Some(Rc::new(options)));
+            wrapper.build_tree(&mut resolved, &mut Vec::new()).unwrap();
+            let mut names: Vec<String> = resolved
+                .iter()
+                .map(|dep| format!("{}:{}", dep.artifact_id, dep.scope))
+                .collect();
+            names.sort();
+            names
+        };
+
+        assert_eq!(build(ResolveOptions::default()), vec!["module-a:compile"]);
+
+        let mut options = ResolveOptions {
+            include_optional: true,
+            ..Default::default()
+        };
+        options.include_scopes(&[String::from("provided")]).unwrap();
+        let err = ResolveOptions::default()
+            .include_scopes(&[String::from("provded")])
+            .unwrap_err();
+        assert!(err.to_string().contains("\"provded\""), "{err}");
+        assert_eq!(
+            build(options),
+            vec!["module-a:compile", "module-b:provided", "module-d:compile"]
+        );
+    }
+
+    #[test]
+    fn corrupt_cache_falls_back_to_network() {
+        use crate::pom::Project as PomProject;
+        use crate::submodules::resolvers::{ResolverError, ResolverErrorKind, CACHE_REPO_STR};
+
+        /// A cache whose entries all fail to parse
+        struct CorruptCache;
+        impl Resolver for CorruptCache {
+            fn fetch(&self, _: &mut PomProject) -> Result<String, ResolverError> {
+                Err(ResolverError::new(
+                    "Failed to parse cache properties file",
+                    ResolverErrorKind::ParseError,
+                    None,
+                ))
+            }
+            fn get_name(&self) -> &str {
+                CACHE_REPO_STR
+            }
+            fn get_priority(&self) -> i32 {
+                10
+            }
+            fn calculate_version(&self, _: &PomProject) -> Result<String, ResolverError> {
+                Err(ResolverError::new(
+                    "Failed to parse maven-metadata.xml",
+                    ResolverErrorKind::ParseError,
+                    None,
+                ))
+            }
+        }
+
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+        server.add_project(
+            ProjectEntry::new("com.example", "module-a", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "module-b", "RELEASE")),
+        );
+        let mut resolvers: Vec<Box<dyn Resolver>> = vec![Box::new(CorruptCache)];
+        resolvers.extend(create_resolver(port));
+
+        let mut resolved = Vec::new();
+        let mut project = Project::new("com.example", "module-a", "1.0.0");
+        project.set_selected_version(Some(String::from("1.0.0")));
+        let mut wrapper = ProjectWrapper::new(project, Rc::new(RefCell::new(resolvers)));
+        wrapper.build_tree(&mut resolved, &mut Vec::new()).unwrap();
+
+        assert_eq!(resolved.len(), 2);
+        assert!(resolved.iter().all(|dep| !dep.cache_hit));
+        assert_eq!(resolved[0].base_url, format!("http://localhost:{port}"));
+    }
+
+    #[test]
+    fn pinned_group_resolution() {
+        use super::ResolveOptions;
+        use std::collections::HashMap;
+
+        let public = PomServer::new().unwrap();
+        let internal = PomServer::new().unwrap();
+        public.add_project(ProjectEntry::new("com.mycorp", "module-a", "1.0.0"));
+        internal.add_project(ProjectEntry::new("com.mycorp", "module-a", "1.0.0"));
+
+        let public_url = format!("http://localhost:{}", public.get_port());
+        let internal_url = format!("http://localhost:{}", internal.get_port());
+        let mut public_resolver = NetResolver::init("public", &public_url).unwrap();
+        // public is queried first if not pinned
+        public_resolver.set_priority(5);
+        let resolvers: Vec<Box<dyn Resolver>> = vec![
+            Box::new(public_resolver),
+            Box::new(NetResolver::init("internal", &internal_url).unwrap()),
+        ];
+
+        let options = Rc::new(ResolveOptions {
+            pins: HashMap::from([(String::from("com.mycorp.*"), String::from("internal"))]),
+            ..Default::default()
+        });
+
+        let mut resolved = Vec::new();
+        let mut project = Project::new("com.mycorp", "module-a", "1.0.0");
+        project.set_selected_version(Some(String::from("1.0.0")));
+        let mut wrapper = ProjectWrapper::new(project, Rc::new(RefCell::new(resolvers)));
+        wrapper.set_options(Some(Rc::clone(&options)));
+        wrapper.build_tree(&mut resolved, &mut Vec::new()).unwrap();
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].base_url, internal_url);
+
+        // pinned resolver does not have the artifact, public must not be used
+        let resolvers: Vec<Box<dyn Resolver>> = vec![
+            Box::new(NetResolver::init("public", &public_url).unwrap()),
+            Box::new(NetResolver::init("internal", &internal_url).unwrap()),
+        ];
+        let mut project = Project::new("com.mycorp", "module-b", "1.0.0");
+        project.set_selected_version(Some(String::from("1.0.0")));
+        public.add_project(ProjectEntry::new("com.mycorp", "module-b", "1.0.0"));
+        let mut wrapper = ProjectWrapper::new(project, Rc::new(RefCell::new(resolvers)));
+        wrapper.set_options(Some(options));
+        assert!(wrapper
+            .build_tree(&mut Vec::new(), &mut Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn pinned_dependency_resolution() {
+        use super::{DependencyPin, ResolveOptions};
+        use std::collections::HashMap;
+
+        let public = PomServer::new().unwrap();
+        let internal = PomServer::new().unwrap();
+        for server in [&public, &internal] {
+            server.add_project(
+                ProjectEntry::new("com.example", "widgets", "1.0.0")
+                    .add_dependency(ProjectEntry::new("com.example", "shared", "1.0.0")),
+            );
+            server.add_project(ProjectEntry::new("com.example", "shared", "1.0.0"));
+        }
+        let public_url = format!("http://localhost:{}", public.get_port());
+        let internal_url = format!("http://localhost:{}", internal.get_port());
+        let resolvers = || -> Vec<Box<dyn Resolver>> {
+            let mut public_resolver = NetResolver::init("public", &public_url).unwrap();
+            // public is queried first if not pinned
+            public_resolver.set_priority(5);
+            vec![
+                Box::new(public_resolver),
+                Box::new(NetResolver::init("internal", &internal_url).unwrap()),
+            ]
+        };
+        let resolve = |tree: bool| {
+            let options = Rc::new(ResolveOptions {
+                dependency_pins: HashMap::from([(
+                    String::from("com.example:widgets"),
+                    DependencyPin {
+                        resolver: String::from("internal"),
+                        tree,
+                    },
+                )]),
+                ..Default::default()
+            });
+            let mut resolved = Vec::new();
+            let mut project = Project::new("com.example", "widgets", "1.0.0");
+            project.set_selected_version(Some(String::from("1.0.0")));
+            let mut wrapper = ProjectWrapper::new(project, Rc::new(RefCell::new(resolvers())));
+            wrapper.set_options(Some(options));
+            wrapper.build_tree(&mut resolved, &mut Vec::new()).unwrap();
+            resolved
+                .into_iter()
+                .map(|dep| (dep.artifact_id, dep.base_url))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let pinned = resolve(false);
+        assert_eq!(pinned["widgets"], internal_url);
+        assert_eq!(pinned["shared"], public_url);
+
+        let tree = resolve(true);
+        assert_eq!(tree["widgets"], internal_url);
+        assert_eq!(tree["shared"], internal_url);
+    }
+
+    #[test]
+    fn test_dependencies_do_not_change_project_versions() {
+        use super::{walk_dependencies, walk_test_dependencies};
+
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+        server.add_project(
+            ProjectEntry::new("com.example", "app-core", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "shared", "1.0.0")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "test-runner", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "shared", "1.0.0"))
+                .add_dependency(ProjectEntry::new("com.example", "test-assert", "1.0.0")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "test-mock", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "shared", "2.0.0")),
+        );
+        server.add_project(ProjectEntry::new("com.example", "shared", "1.0.0"));
+        server.add_project(ProjectEntry::new("com.example", "shared", "2.0.0"));
+        server.add_project(ProjectEntry::new("com.example", "test-assert", "1.0.0"));
+
+        let resolvers = Rc::new(RefCell::new(create_resolver(port)));
+        let mut main = Vec::new();
+        walk_dependencies(
+            vec![Project::new("com.example", "app-core", "1.0.0")],
+            &mut main,
+            &resolvers,
+            &Rc::new(super::ResolveOptions::default()),
+        )
+        .unwrap();
+
+        let options = Rc::new(super::ResolveOptions {
+            test_dependencies: vec![Project::new("com.example", "test-runner", "1.0.0")],
+            ..Default::default()
+        });
+        let test = walk_test_dependencies(&main, &resolvers, &options).unwrap();
+        // shared is only locked once, in the project section
+        let artifacts: Vec<&str> = test.iter().map(|dep| dep.artifact_id.as_str()).collect();
+        assert_eq!(artifacts, vec!["test-assert", "test-runner"]);
+
+        let options = Rc::new(super::ResolveOptions {
+            test_dependencies: vec![Project::new("com.example", "test-mock", "1.0.0")],
+            ..Default::default()
+        });
+        let err = walk_test_dependencies(&main, &resolvers, &options).unwrap_err();
+        server.close();
+        assert!(err
+            .to_string()
+            .contains("com.example:shared 1.0.0 -> 2.0.0"));
+    }
+
+    #[test]
+    fn offline_lists_dependencies_missing_from_cache() {
+        use super::walk_dependencies;
+
+        // offline there are no network resolvers
+        let resolvers = Rc::new(RefCell::new(Vec::new()));
+        let options = Rc::new(super::ResolveOptions {
+            offline: true,
+            ..Default::default()
+        });
+        // locked dependencies are not fetched again
+        let mut resolved = vec![ProjectDep {
+            group_id: String::from("com.example"),
+            artifact_id: String::from("module-a"),
+            version: String::from("1.0.0"),
+            ..Default::default()
+        }];
+        let err = walk_dependencies(
+            vec![
+                Project::new("com.example", "module-a", "1.0.0"),
+                Project::new("com.example", "module-b", "1.0.0"),
+                Project::new("com.example", "module-c", "2.0.0"),
+            ],
+            &mut resolved,
+            &resolvers,
+            &options,
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(err.contains("com.example:module-b:1.0.0\n  com.example:module-c:2.0.0"));
+        assert!(!err.contains("module-a"));
+    }
+
+    #[test]
+    fn incremental_added_dependency() {
+        use super::{can_resolve_incrementally, resolution_fingerprint, walk_dependencies};
+        use crate::config::lock::LabtLock;
+
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+        server.add_project(
+            ProjectEntry::new("com.example", "module-a", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "module-e", "[1.0]")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "module-b", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "module-e", "[2.0]")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "module-c", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "module-d", "1.0.0")),
+        );
+        server.add_project(ProjectEntry::new("com.example", "module-d", "1.0.0"));
+
+        let resolvers = Rc::new(RefCell::new(create_resolver(port)));
+        let options = Rc::new(super::ResolveOptions::default());
+        let previous = vec![Project::new("com.example", "module-a", "1.0.0")];
+        let mut lock = LabtLock::default();
+        walk_dependencies(previous.clone(), &mut lock.resolved, &resolvers, &options).unwrap();
+        lock.fingerprint = Some(resolution_fingerprint(
+            &previous,
+            &resolvers.borrow(),
+            &options,
+        ));
+
+        let added = Project::new("com.example", "module-c", "1.0.0");
+        assert!(can_resolve_incrementally(
+            &lock,
+            &previous,
+            &added,
+            &resolvers.borrow(),
+            &options
+        ));
+        // a version change of an existing dependency needs a full resolution
+        assert!(!can_resolve_incrementally(
+            &lock,
+            &previous,
+            &Project::new("com.example", "module-a", "2.0.0"),
+            &resolvers.borrow(),
+            &options
+        ));
+        // and so does an outdated lock
+        assert!(!can_resolve_incrementally(
+            &lock,
+            &[],
+            &added,
+            &resolvers.borrow(),
+            &options
+        ));
+
+        let mut conflicting = LabtLock {
+            resolved: lock.resolved.clone(),
+            ..Default::default()
+        };
+        walk_dependencies(vec![added], &mut lock.resolved, &resolvers, &options).unwrap();
+        let artifacts: Vec<&str> = lock
+            .resolved
+            .iter()
+            .map(|dep| dep.artifact_id.as_str())
+            .collect();
+        assert_eq!(
+            artifacts,
+            vec!["module-e", "module-a", "module-d", "module-c"]
+        );
+
+        // the added subtree is still checked against the locked constraints
+        let err = walk_dependencies(
+            vec![Project::new("com.example", "module-b", "1.0.0")],
+            &mut conflicting.resolved,
+            &resolvers,
+            &options,
+        )
+        .unwrap_err();
+        assert!(err
+            .chain()
+            .any(|err| err.downcast_ref::<super::ConflictReport>().is_some()));
+    }
+
+    #[test]
+    fn declared_scopes_pass_to_transitive_dependencies() {
+        use super::{apply_scopes, ProjectDep, Scope};
+
+        let dep = |artifact_id: &str, scope: Scope, dependencies: &[&str]| ProjectDep {
+            group_id: String::from("com.example"),
+            artifact_id: artifact_id.to_string(),
+            version: String::from("1.0.0"),
+            scope,
+            dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        };
+        // app -> shared, leak (runtime) -> shared, leak-core, leak-core -> okio (runtime in its pom)
+        // mock (test) -> okio
+        let mut resolved = vec![
+            dep("app", Scope::COMPILE, &["com.example:shared:1.0.0"]),
+            dep(
+                "leak",
+                Scope::COMPILE,
+                &["com.example:shared:1.0.0", "com.example:leak-core:1.0.0"],
+            ),
+            dep("leak-core", Scope::COMPILE, &["com.example:okio:1.0.0"]),
+            dep("okio", Scope::RUNTIME, &[]),
+            dep("shared", Scope::COMPILE, &[]),
+            dep("mock", Scope::COMPILE, &["com.example:okio:1.0.0"]),
+            dep("stale", Scope::PROVIDED, &[]),
+        ];
+        apply_scopes(
+            &mut resolved,
+            &[
+                (String::from("com.example:app"), Scope::COMPILE),
+                (String::from("com.example:leak"), Scope::RUNTIME),
+                (String::from("com.example:mock"), Scope::TEST),
+            ],
+        );
+        let scopes: Vec<(&str, Scope)> = resolved
+            .iter()
+            .map(|dep| (dep.artifact_id.as_str(), dep.scope.clone()))
+            .collect();
+        assert_eq!(
+            scopes,
+            vec![
+                ("app", Scope::COMPILE),
+                ("leak", Scope::RUNTIME),
+                ("leak-core", Scope::RUNTIME),
+                ("okio", Scope::RUNTIME),
+                // needed by both app and leak
+                ("shared", Scope::COMPILE),
+                ("mock", Scope::TEST),
+                ("stale", Scope::PROVIDED),
+            ]
+        );
+    }
+
+    #[test]
+    fn resolution_fingerprint_changes() {
+        use super::{resolution_fingerprint, ResolveOptions};
+
+        let resolvers = create_resolver(8080);
+        let options = ResolveOptions::default();
+        let a = Project::new("com.example", "module-a", "1.0.0");
+        let b = Project::new("com.example", "module-b", "1.0.0");
+
+        let fingerprint = resolution_fingerprint(&[a.clone(), b.clone()], &resolvers, &options);
+        // order of dependencies does not matter
+        assert_eq!(
+            fingerprint,
+            resolution_fingerprint(&[b.clone(), a.clone()], &resolvers, &options)
+        );
+        // a version bump does
+        assert_ne!(
+            fingerprint,
+            resolution_fingerprint(
+                &[a.clone(), Project::new("com.example", "module-b", "1.1.0")],
+                &resolvers,
+                &options
+            )
+        );
+        // and so do the resolvers
+        assert_ne!(
+            fingerprint,
+            resolution_fingerprint(&[a.clone(), b.clone()], &create_resolver(8081), &options)
+        );
+    }
+
+    #[test]
+    fn parallel_fetch_matches_serial_walk() {
+        use super::walk_dependencies;
+        use crate::config::lock::LabtLock;
+
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+        server.add_project(
+            ProjectEntry::new("com.example", "parallel-a", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "parallel-c", "1.0.0"))
+                .add_dependency(ProjectEntry::new("com.example", "parallel-d", "1.0.0"))
+                .add_dependency(ProjectEntry::new("com.example", "parallel-e", "1.0.0")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "parallel-b", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "parallel-c", "2.0.0"))
+                .add_dependency(ProjectEntry::new("com.example", "parallel-e", "1.0.0")),
+        );
+        server.add_project(ProjectEntry::new("com.example", "parallel-c", "1.0.0"));
+        server.add_project(ProjectEntry::new("com.example", "parallel-c", "2.0.0"));
+        server.add_project(
+            ProjectEntry::new("com.example", "parallel-d", "1.0.0")
+                .add_dependency(ProjectEntry::new("com.example", "parallel-c", "2.0.0")),
+        );
+        server.add_project(ProjectEntry::new("com.example", "parallel-e", "1.0.0"));
+
+        let walk = |jobs: usize| {
+            let resolvers = Rc::new(RefCell::new(create_resolver(port)));
+            let options = Rc::new(super::ResolveOptions {
+                jobs,
+                ..Default::default()
+            });
+            let mut lock = LabtLock::default();
+            walk_dependencies(
+                vec![
+                    Project::new("com.example", "parallel-a", "1.0.0"),
+                    Project::new("com.example", "parallel-b", "1.0.0"),
+                ],
+                &mut lock.resolved,
+                &resolvers,
+                &options,
+            )
+            .unwrap();
+            lock.resolved
+                .iter()
+                .map(|dep| format!("{}:{}", dep.artifact_id, dep.version))
+                .collect::<Vec<String>>()
+        };
+        let serial = walk(1);
+        let parallel = walk(4);
+        server.close();
+
+        assert_eq!(serial, parallel);
+        assert_eq!(
+            serial,
+            vec![
+                "parallel-c:2.0.0",
+                "parallel-d:1.0.0",
+                "parallel-e:1.0.0",
+                "parallel-a:1.0.0",
+                "parallel-b:1.0.0"
+            ]
+        );
+    }
+
+    #[test]
+    fn bom_supplies_missing_versions() {
+        use super::walk_dependencies;
+
+        let server = PomServer::new().unwrap();
+        let port = server.get_port();
+        server.add_project(
+            ProjectEntry::new("com.example", "platform-bom", "1.0.0")
+                .add_dependency_management(ProjectEntry::new("com.example", "bom-d", "1.0.0"))
+                .add_dependency_management(
+                    ProjectEntry::new("com.example", "extra-bom", "1.0.0")
+                        .set_scope(crate::pom::Scope::IMPORT),
+                ),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "extra-bom", "1.0.0")
+                .add_dependency_management(ProjectEntry::new("com.example", "bom-d", "9.0.0"))
+                .add_dependency_management(ProjectEntry::new("com.example", "bom-e", "2.0.0")),
+        );
+        server.add_project(
+            ProjectEntry::new("com.example", "bom-c", "1.0.0").add_dependency(ProjectEntry::new(
+                "com.example",
+                "bom-e",
+                "",
+            )),
+        );
+        server.add_project(ProjectEntry::new("com.example", "bom-d", "1.0.0"));
+        server.add_project(ProjectEntry::new("com.example", "bom-e", "2.0.0"));
+
+        let resolvers = Rc::new(RefCell::new(create_resolver(port)));
+        let options = Rc::new(super::ResolveOptions {
+            boms: vec![Project::new("com.example", "platform-bom", "1.0.0")],
+            ..Default::default()
+        });
+
+        let mut resolved = Vec::new();
+        walk_dependencies(
+            vec![
+                Project::new("com.example", "bom-c", "1.0.0"),
+                Project::new("com.example", "bom-d", ""),
+            ],
+            &mut resolved,
+            &resolvers,
+            &options,
+        )
+        .unwrap();
+        let resolved: Vec<String> = resolved
+            .iter()
+            .map(|dep| format!("{}:{}", dep.artifact_id, dep.version))
+            .collect();
+        // declared entries win over imported ones and the boms are not dependencies
+        assert_eq!(resolved, vec!["bom-e:2.0.0", "bom-c:1.0.0", "bom-d:1.0.0"]);
+
+        let err = walk_dependencies(
+            vec![Project::new("com.example", "module-x", "")],
+            &mut Vec::new(),
+            &resolvers,
+            &options,
+        )
+        .unwrap_err();
+        server.close();
+        assert_eq!(
+            err.to_string(),
+            "Dependency com.example:module-x has no version and is not managed by a bom"
+        );
+    }
+}
+
+#[test]
+fn failed_resolution_reports_finished() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join(crate::config::LABT_TOML_FILE_NAME), "").unwrap();
+    std::fs::write(dir.path().join(LOCK_FILE), "not [a lock").unwrap();
+
+    let thread = std::thread::current().id();
+    let finished = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = Arc::clone(&finished);
+    let _subscription = subscribe(move |event| {
+        if let Event::ResolutionFinished { success, .. } = event {
+            if std::thread::current().id() == thread {
+                sink.lock().unwrap().push(*success);
+            }
+        }
+    });
+    crate::session::Session::new().enter(|| {
+        crate::set_home(Some(dir.path().join("home")));
+        crate::set_manifest_path(Some(dir.path().join(crate::config::LABT_TOML_FILE_NAME)));
+        assert!(resolve(Vec::new(), Vec::new(), ResolveOptions::default()).is_err());
+    });
+    assert_eq!(*finished.lock().unwrap(), vec![false]);
 }
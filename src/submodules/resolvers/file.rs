@@ -0,0 +1,284 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use reqwest::Url;
+
+use crate::caching::{Cache, CacheType};
+use crate::config::maven_metadata::{parse_maven_metadata, MavenMetadata};
+use crate::config::maven_settings::load_maven_settings;
+use crate::pom::{parse_pom, Project, VersionRequirement};
+
+use super::{Resolver, ResolverError, ResolverErrorKind};
+
+pub const MAVEN_LOCAL_REPO_STR: &str = "maven_local";
+
+/// The metadata maven install writes to the local repository. Repositories copied
+/// from a server have the plain maven-metadata.xml instead
+const LOCAL_METADATA_FILES: [&str; 2] = ["maven-metadata-local.xml", "maven-metadata.xml"];
+
+/// Returns the user home directory
+///
+/// # Errors
+///
+/// Returns an error if the home directory is not set
+pub fn user_home() -> anyhow::Result<PathBuf> {
+    #[cfg(not(target_os = "windows"))]
+    let home = std::env::var(crate::envs::HOME);
+    #[cfg(target_os = "windows")]
+    let home = std::env::var(crate::envs::USERPROFILE);
+    match home {
+        Ok(home) => Ok(PathBuf::from(home)),
+        Err(_) => bail!("The user home directory is not set"),
+    }
+}
+
+/// Returns the maven local repository, the localRepository of an imported
+/// settings.xml or ~/.m2/repository
+///
+/// # Errors
+///
+/// Returns an error if the user home directory is not set
+pub fn maven_local_repository() -> anyhow::Result<PathBuf> {
+    // a settings.xml that fails to load is reported when the resolvers are loaded
+    if let Some(local) = load_maven_settings()
+        .ok()
+        .flatten()
+        .and_then(|settings| settings.local_repository.clone())
+    {
+        return Ok(PathBuf::from(local));
+    }
+    Ok(user_home()?.join(".m2").join("repository"))
+}
+
+/// Resolves from a directory laid out like a maven repository e.g. the maven
+/// local repository at ~/.m2/repository. The poms are read in place and the
+/// artifacts are copied into the labt cache when dependencies are saved.
+pub struct FileResolver {
+    name: String,
+    root: PathBuf,
+    /// file:// url of root
+    base_url: String,
+    priority: i32,
+}
+
+impl FileResolver {
+    pub fn new(name: &str, root: PathBuf) -> Self {
+        let base_url = Url::from_directory_path(&root)
+            .map(|url| url.to_string())
+            .unwrap_or(root.to_string_lossy().to_string());
+        FileResolver {
+            name: name.to_string(),
+            root,
+            base_url,
+            priority: 1,
+        }
+    }
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+    /// The directory of the artifact in this repository
+    fn artifact_dir(&self, group_id: &str, artifact_id: &str) -> PathBuf {
+        let mut path = self.root.clone();
+        path.extend(group_id.split('.'));
+        path.push(artifact_id);
+        path
+    }
+    /// Copies the pom into the labt cache like the network resolvers do so that
+    /// the other labt commands find it
+    fn cache_pom(project: &Project, version: &str, pom: &Path) -> anyhow::Result<()> {
+        let mut cache = Cache::new(
+            project.get_group_id(),
+            project.get_artifact_id(),
+            version.to_string(),
+            CacheType::POM,
+        );
+        cache.use_labt_home()?;
+        let cache = cache.create().context("Failed to create cache file")?;
+        let mut reader =
+            BufReader::new(File::open(pom).context(format!("Failed to open {:?}", pom))?);
+        let mut writer = BufWriter::new(cache);
+        crate::interrupt::copy(&mut reader, &mut writer).context("Failed to copy pom to cache")?;
+        let mut cache = writer
+            .into_inner()
+            .context("Failed to flush pom to cache")?;
+        cache.complete();
+        Ok(())
+    }
+}
+
+impl Resolver for FileResolver {
+    fn fetch(&self, project: &mut Project) -> Result<String, ResolverError> {
+        let version = project
+            .get_selected_version()
+            .clone()
+            .ok_or(ResolverError::new(
+                format!(
+                    "Failed to obtain selected version from package {}:{}",
+                    project.get_group_id(),
+                    project.get_artifact_id()
+                )
+                .as_str(),
+                ResolverErrorKind::NoSelectedVersion,
+                None,
+            ))?;
+
+        let pom = self
+            .artifact_dir(&project.get_group_id(), &project.get_artifact_id())
+            .join(&version)
+            .join(format!("{}-{}.pom", project.get_artifact_id(), version));
+        if !pom.exists() {
+            return Err(ResolverError::new(
+                format!("{:?} not found", pom).as_str(),
+                ResolverErrorKind::NotFound,
+                None,
+            ));
+        }
+        log::trace!(target: "fetch", "{:?}", pom);
+
+        if let Err(err) = Self::cache_pom(project, &version, &pom) {
+            // the pom can still be read in place
+            log::warn!("Unable to cache {:?} \n {:?}", pom, err);
+        }
+        let file = File::open(&pom).map_err(|err| {
+            ResolverError::new(
+                format!("Failed to open {:?}", pom).as_str(),
+                ResolverErrorKind::Internal,
+                Some(err.into()),
+            )
+        })?;
+        let p = parse_pom(BufReader::new(file), project.to_owned()).map_err(|err| {
+            ResolverError::new(
+                format!("Failed to parse pom file at {:?}", pom).as_str(),
+                ResolverErrorKind::ParseError,
+                Some(err),
+            )
+        })?;
+        *project = p;
+        Ok(self.base_url.clone())
+    }
+    fn get_name(&self) -> &str {
+        self.name.as_str()
+    }
+    fn get_priority(&self) -> i32 {
+        self.priority
+    }
+    fn get_base_url(&self) -> Option<&str> {
+        Some(self.base_url.as_str())
+    }
+    fn calculate_version(&self, project: &Project) -> Result<String, ResolverError> {
+        // if it is a softie return imediately unless they specify LATEST or RELEASE for backward compatibility
+        if let VersionRequirement::Soft(v) = project.get_version() {
+            if v != "LATEST" && v != "RELEASE" {
+                return Ok(v.to_string());
+            }
+        }
+
+        let metadata = self.fetch_metadata(&project.get_group_id(), &project.get_artifact_id())?;
+
+        let selected_version = metadata
+            .select_version(project.get_version())
+            .map_err(|err| {
+                ResolverError::new(
+                    format!(
+                        "Failed to select correct version for {}:{} from metadata with: latest: {}, release: {} and available {:?}",
+                        project.get_group_id(),
+                        project.get_artifact_id(),
+                        metadata.latest.unwrap_or("None".to_string()),
+                        metadata.release.unwrap_or("None".to_string()),
+                        metadata.versions
+                    )
+                    .as_str(),
+                    ResolverErrorKind::NoSelectedVersion,
+                    Some(err),
+                )
+            })?;
+
+        Ok(selected_version)
+    }
+    fn fetch_metadata(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+    ) -> Result<MavenMetadata, ResolverError> {
+        let dir = self.artifact_dir(group_id, artifact_id);
+        let Some(path) = LOCAL_METADATA_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists())
+        else {
+            return Err(ResolverError::new(
+                format!(
+                    "No maven metadata for {}:{} in {:?}",
+                    group_id, artifact_id, dir
+                )
+                .as_str(),
+                ResolverErrorKind::NotFound,
+                None,
+            ));
+        };
+        let file = File::open(&path).map_err(|err| {
+            ResolverError::new(
+                format!("Failed to open {:?}", path).as_str(),
+                ResolverErrorKind::Internal,
+                Some(err.into()),
+            )
+        })?;
+        parse_maven_metadata(BufReader::new(file)).map_err(|err| {
+            ResolverError::new(
+                format!(
+                    "Failed to parse maven-metadata.xml for {}:{}",
+                    group_id, artifact_id
+                )
+                .as_str(),
+                ResolverErrorKind::ParseError,
+                Some(err),
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn file_resolver_reads_maven_layout() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("file-repo");
+    let dir = root.join("com").join("example").join("widgets");
+    std::fs::create_dir_all(dir.join("1.2.0")).unwrap();
+    std::fs::write(
+        dir.join("maven-metadata-local.xml"),
+        r#"<metadata>
+  <groupId>com.example</groupId>
+  <artifactId>widgets</artifactId>
+  <versioning>
+    <release>1.2.0</release>
+    <versions>
+      <version>1.1.0</version>
+      <version>1.2.0</version>
+    </versions>
+  </versioning>
+</metadata>"#,
+    )
+    .unwrap();
+
+    let resolver = FileResolver::new(MAVEN_LOCAL_REPO_STR, root.clone());
+    let metadata = resolver.fetch_metadata("com.example", "widgets");
+    let missing = resolver.fetch_metadata("com.example", "gadgets");
+
+    assert_eq!(
+        resolver.artifact_dir("com.example", "widgets"),
+        root.join("com/example/widgets")
+    );
+    assert!(resolver.get_base_url().unwrap().starts_with("file:///"));
+    assert_eq!(
+        metadata.unwrap().versions,
+        vec![String::from("1.1.0"), String::from("1.2.0")]
+    );
+    assert!(matches!(
+        missing.unwrap_err().kind(),
+        ResolverErrorKind::NotFound
+    ));
+}
@@ -0,0 +1,246 @@
+use std::fs::create_dir_all;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context};
+use git2::{Oid, Repository, WorktreeAddOptions};
+use reqwest::Url;
+
+use crate::config::Dependency;
+use crate::get_home;
+use crate::session::Session;
+use crate::submodules::plugin::{build_repo, fetch_origin};
+
+/// The git reference a git dependency is pinned to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GitReference {
+    Tag(String),
+    Branch(String),
+    Rev(String),
+}
+
+impl GitReference {
+    /// Reads the reference from a dependency entry. Only one of tag, branch or rev
+    /// may be set. Defaults to the master branch if none is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if more than one reference is set
+    pub fn from_dependency(dep: &Dependency) -> anyhow::Result<Self> {
+        match (&dep.tag, &dep.branch, &dep.rev) {
+            (Some(tag), None, None) => Ok(Self::Tag(tag.clone())),
+            (None, Some(branch), None) => Ok(Self::Branch(branch.clone())),
+            (None, None, Some(rev)) => Ok(Self::Rev(rev.clone())),
+            (None, None, None) => Ok(Self::Branch(String::from("master"))),
+            _ => bail!("Only one of tag, branch or rev can be set on a git dependency"),
+        }
+    }
+    /// Tags and revisions never move so their checkout can be reused without a fetch
+    fn is_immutable(&self) -> bool {
+        !matches!(self, Self::Branch(_))
+    }
+    /// A name usable as a directory and branch name
+    fn checkout_name(&self) -> String {
+        let name = match self {
+            Self::Tag(tag) => format!("tag-{tag}"),
+            Self::Branch(branch) => format!("branch-{branch}"),
+            Self::Rev(rev) => format!("rev-{rev}"),
+        };
+        name.replace(['/', '\\', ':'], "-")
+    }
+    /// A version string used when neither the entry nor the library provide one
+    pub fn version(&self) -> String {
+        match self {
+            Self::Tag(tag) => tag.strip_prefix('v').unwrap_or(tag).to_string(),
+            Self::Branch(branch) => format!("{branch}-SNAPSHOT"),
+            Self::Rev(rev) => rev.chars().take(12).collect(),
+        }
+    }
+}
+
+/// Returns the directory under labt home where a repository is cloned
+/// e.g. LABT_HOME/git/github.com/user/repo
+fn repository_dir(location: &str) -> anyhow::Result<PathBuf> {
+    let url = Url::parse(location).context(format!("Invalid git dependency url {location}"))?;
+    let mut path = get_home().context("Failed to get Labt Home")?;
+    path.push("git");
+    path.push(url.domain().unwrap_or("example.com"));
+    let url_path = url.path();
+    let url_path = url_path.strip_suffix(".git").unwrap_or(url_path);
+    path.extend(url_path.split('/').filter(|p| !p.is_empty()));
+    Ok(path)
+}
+
+fn find_commit(repo: &Repository, reference: &GitReference) -> anyhow::Result<Oid> {
+    let spec = match reference {
+        GitReference::Tag(tag) => format!("refs/tags/{tag}"),
+        GitReference::Branch(branch) => format!("refs/remotes/origin/{branch}"),
+        GitReference::Rev(rev) => rev.clone(),
+    };
+    let object = repo
+        .revparse_single(&spec)
+        .context(format!("Failed to find {spec} in git repository"))?;
+    let commit = object
+        .peel_to_commit()
+        .context(format!("{spec} does not point to a commit"))?;
+    Ok(commit.id())
+}
+
+/// Marks the repository as fetched in this session. Returns false if it already was
+fn first_fetch(path: &Path) -> bool {
+    Session::current()
+        .git_fetched
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .insert(path.to_path_buf())
+}
+
+/// Opens the clone of a git dependency, cloning it if it does not exist yet. An
+/// existing clone is only fetched if the reference is a branch or is missing,
+/// and at most once per session.
+fn open_repository(
+    location: &str,
+    path: &Path,
+    reference: &GitReference,
+) -> anyhow::Result<Repository> {
+    let git_path = path.join("git");
+    if !git_path.exists() {
+        let repo = build_repo(location, git_path)
+            .context(format!("Failed to fetch git dependency from {location}"))?;
+        first_fetch(path);
+        return Ok(repo);
+    }
+    let repo = Repository::open(&git_path).context(format!(
+        "Failed to open git dependency repository at {:?}",
+        git_path
+    ))?;
+    // tags and revisions already in the clone never move
+    let present = reference.is_immutable() && find_commit(&repo, reference).is_ok();
+    if !present && first_fetch(path) {
+        fetch_origin(&repo).context(format!("Failed to fetch git dependency from {location}"))?;
+    }
+    Ok(repo)
+}
+
+/// Clones the repository of a git dependency into labt home and checks out
+/// the requested reference. Returns the checkout directory.
+///
+/// # Errors
+///
+/// Returns an error if cloning, fetching or checking out the reference fails
+pub fn checkout_git_dependency(
+    location: &str,
+    reference: &GitReference,
+) -> anyhow::Result<PathBuf> {
+    let path = repository_dir(location)?;
+    let mut checkouts = path.join("checkouts");
+
+    if reference.is_immutable() {
+        let checkout = checkouts.join(reference.checkout_name());
+        if checkout.exists() {
+            return Ok(checkout);
+        }
+    }
+
+    let repo = open_repository(location, &path, reference)?;
+    let commit = repo.find_commit(find_commit(&repo, reference)?)?;
+
+    // branches are checked out per commit so that new commits get a fresh checkout
+    let name = match reference {
+        GitReference::Branch(_) => format!(
+            "{}-{}",
+            reference.checkout_name(),
+            &commit.id().to_string()[..12]
+        ),
+        _ => reference.checkout_name(),
+    };
+    checkouts.push(&name);
+    if checkouts.exists() {
+        return Ok(checkouts);
+    }
+    if let Some(parent) = checkouts.parent() {
+        create_dir_all(parent).context(format!(
+            "Unable to create git checkout directory at {:?}",
+            parent
+        ))?;
+    }
+
+    let branch = match repo.branch(&name, &commit, false) {
+        Ok(branch) => branch,
+        Err(err) if err.code() == git2::ErrorCode::Exists => {
+            repo.find_branch(&name, git2::BranchType::Local)?
+        }
+        Err(err) => {
+            return Err(err).context(format!("Failed to create a branch for {name}"));
+        }
+    };
+    let mut worktree_options = WorktreeAddOptions::new();
+    worktree_options.reference(Some(branch.get()));
+    repo.worktree(&name, &checkouts, Some(&worktree_options))
+        .context(format!("Failed to check out {name} from {location}"))?;
+
+    Ok(checkouts)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn git_reference_from_dependency() {
+    let mut dep = Dependency {
+        artifact_id: None,
+        group_id: String::new(),
+        version: String::new(),
+        dep_type: None,
+        resolver: None,
+        pin_tree: None,
+        scope: None,
+        path: None,
+        artifact: None,
+        git: Some(String::from("https://github.com/example/lib")),
+        tag: Some(String::from("v1.2")),
+        branch: None,
+        rev: None,
+    };
+    let reference = GitReference::from_dependency(&dep).unwrap();
+    assert_eq!(reference, GitReference::Tag(String::from("v1.2")));
+    assert_eq!(reference.version(), "1.2");
+    assert!(reference.is_immutable());
+
+    dep.branch = Some(String::from("feature/x"));
+    assert!(GitReference::from_dependency(&dep).is_err());
+
+    dep.tag = None;
+    let reference = GitReference::from_dependency(&dep).unwrap();
+    assert_eq!(reference.checkout_name(), "branch-feature-x");
+    assert!(!reference.is_immutable());
+}
+
+#[test]
+fn checkout_skips_fetch_when_commit_is_cloned() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("source");
+    let repo = Repository::init(&source).unwrap();
+    std::fs::write(source.join("Labt.toml"), "").unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_path(std::path::Path::new("Labt.toml")).unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("labt", "labt@example.com").unwrap();
+    let commit = repo
+        .commit(Some("HEAD"), &signature, &signature, "init", &tree, &[])
+        .unwrap();
+    repo.tag_lightweight("v1.0", &repo.find_object(commit, None).unwrap(), false)
+        .unwrap();
+    let location = Url::from_directory_path(&source).unwrap().to_string();
+
+    Session::new().enter(|| {
+        crate::set_home(Some(dir.path().join("home")));
+        let tag = GitReference::Tag(String::from("v1.0"));
+        let checkout = checkout_git_dependency(&location, &tag).unwrap();
+        assert!(checkout.join("Labt.toml").exists());
+
+        // fetching from the removed source would fail
+        std::fs::remove_dir_all(&source).unwrap();
+        let rev = GitReference::Rev(commit.to_string());
+        assert!(checkout_git_dependency(&location, &rev).is_ok());
+    });
+}
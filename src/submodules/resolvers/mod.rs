@@ -1,14 +1,25 @@
 use std::borrow::Borrow;
 use std::fmt::Display;
-use std::io::{self, BufReader};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use std::{error::Error, io::BufWriter};
 
 use anyhow::Context;
 use log::warn;
-use reqwest::StatusCode;
+use reqwest::{header::HeaderMap, StatusCode};
 
 use crate::caching::properties::{read_properties, PropertiesError};
-use crate::config::maven_metadata::parse_maven_metadata;
+use crate::caching::snapshot::{
+    remote_file_version, save_snapshot_metadata, snapshot_is_stale, DEFAULT_SNAPSHOT_REFRESH,
+};
+use crate::caching::validators::{read_validators, save_validators, Validators};
+use crate::caching::{file_sha1, metadata_is_stale};
+use crate::config::global::{authenticate, client_builder, send_with_retry};
+use crate::config::maven_metadata::{is_snapshot, parse_maven_metadata, MavenMetadata};
+use crate::http_recording::SendRecorded;
+use crate::metrics;
 use crate::pom::VersionRequirement;
 use crate::{
     caching::Cache,
@@ -17,6 +28,13 @@ use crate::{
 };
 
 use super::resolve::ProjectDep;
+use prefetch::PomPrefetcher;
+
+pub mod file;
+pub mod git;
+pub mod path;
+pub mod prefetch;
+
 pub const CACHE_REPO_STR: &str = "cache";
 pub const CENTRAL_REPO_STR: &str = "central";
 pub const CENTRAL_REPO_URL: &str = "https://repo1.maven.org/maven2/";
@@ -27,26 +45,118 @@ pub trait Resolver {
     fn fetch(&self, project: &mut Project) -> Result<String, ResolverError>;
     fn get_name(&self) -> &str;
     fn get_priority(&self) -> i32;
+    /// The repository url this resolver fetches from. Returns None for resolvers
+    /// that are not backed by a single repository e.g. the cache
+    fn get_base_url(&self) -> Option<&str> {
+        None
+    }
     /// Resolves the correct version of a dependency through use of maven-metadata.xml
     /// Returns the computed version
     fn calculate_version(&self, project: &Project) -> Result<String, ResolverError>;
+    /// Fetches the maven-metadata.xml listing all published versions of an artifact.
+    /// Resolvers without repository metadata return a NotFound error
+    fn fetch_metadata(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+    ) -> Result<MavenMetadata, ResolverError> {
+        Err(ResolverError::new(
+            format!(
+                "{} resolver has no maven-metadata.xml for {}:{}",
+                self.get_name(),
+                group_id,
+                artifact_id
+            )
+            .as_str(),
+            ResolverErrorKind::NotFound,
+            None,
+        ))
+    }
+    /// The Last-Modified date the repository reports for the pom of a version,
+    /// as sent in the response header. None if unknown
+    fn last_modified(&self, _group_id: &str, _artifact_id: &str, _version: &str) -> Option<String> {
+        None
+    }
+    /// Lets the resolver take poms downloaded ahead of time instead of requesting
+    /// them. Resolvers that do not download poms ignore it
+    fn set_prefetcher(&mut self, _prefetcher: Option<Arc<PomPrefetcher>>) {}
+}
+pub struct CacheResolver {
+    /// How long cached snapshot versions are used before asking the repositories for a newer build
+    snapshot_refresh: Duration,
+    /// How long cached maven-metadata.xml is used to calculate versions. None uses it forever
+    metadata_ttl: Option<Duration>,
 }
-#[derive(Default)]
-pub struct CacheResolver {}
 pub struct NetResolver {
     base_url: String,
     name: String,
     client: reqwest::blocking::Client,
     priority: i32,
+    prefetcher: Option<Arc<PomPrefetcher>>,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// The header repositories such as maven central and artifactory send with the
+/// sha1 of the file
+pub const CHECKSUM_SHA1_HEADER: &str = "x-checksum-sha1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ResolverErrorKind {
     NotFound,
     Internal,
     ParseError,
     ResponseError,
     NoSelectedVersion,
+    /// The repository rejected the credentials, 401 or 403
+    Unauthorized,
+    /// The request timed out or the repository answered 408 or 504
+    Timeout,
+    /// The downloaded file does not match the checksum the repository sent
+    ChecksumMismatch,
+    /// The repository kept answering 429 Too Many Requests
+    RateLimited,
+}
+
+impl ResolverErrorKind {
+    /// The kind of a failed response
+    pub fn from_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::NOT_FOUND | StatusCode::GONE => ResolverErrorKind::NotFound,
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ResolverErrorKind::Unauthorized,
+            StatusCode::REQUEST_TIMEOUT | StatusCode::GATEWAY_TIMEOUT => ResolverErrorKind::Timeout,
+            StatusCode::TOO_MANY_REQUESTS => ResolverErrorKind::RateLimited,
+            _ => ResolverErrorKind::ResponseError,
+        }
+    }
+    /// The kind of a request that failed without a response
+    pub fn from_request(err: &reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ResolverErrorKind::Timeout
+        } else {
+            ResolverErrorKind::Internal
+        }
+    }
+    /// Returns true if the next resolver should be tried. These failures are
+    /// specific to one repository and another one may still have the artifact.
+    pub fn try_next_resolver(&self) -> bool {
+        matches!(
+            self,
+            ResolverErrorKind::NotFound
+                | ResolverErrorKind::Unauthorized
+                | ResolverErrorKind::Timeout
+                | ResolverErrorKind::ChecksumMismatch
+                | ResolverErrorKind::RateLimited
+        )
+    }
+    /// What the user can do about the error
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            ResolverErrorKind::Unauthorized => Some("Check the credentials of the repository in the [resolvers] of Labt.toml or the servers of the maven settings.xml"),
+            ResolverErrorKind::Timeout => Some("The repository did not answer in time. Check the network or raise retries in the [network] of config.toml"),
+            ResolverErrorKind::ChecksumMismatch => Some("The file was corrupted on the way or changed on the repository. Try again or use another repository"),
+            ResolverErrorKind::RateLimited => Some("The repository is limiting requests. Wait a while, or lower downloads or raise host_interval_ms in the [network] of config.toml"),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -54,6 +164,8 @@ pub struct ResolverError {
     message: String,
     kind: ResolverErrorKind,
     source: Option<anyhow::Error>,
+    /// The http status of the response that failed
+    status: Option<StatusCode>,
 }
 
 impl ResolverError {
@@ -62,16 +174,43 @@ impl ResolverError {
             message: message.to_string(),
             kind,
             source,
+            status: None,
+        }
+    }
+    /// The error for a failed response to a request for url
+    pub fn from_response(url: &str, status: StatusCode) -> Self {
+        ResolverError {
+            status: Some(status),
+            ..ResolverError::new(
+                format!("{}: Failed to fetch {}", status.as_u16(), url).as_str(),
+                ResolverErrorKind::from_status(status),
+                None,
+            )
         }
     }
+    /// The error for a request that failed without a response
+    pub fn from_request(message: &str, err: reqwest::Error) -> Self {
+        ResolverError::new(
+            message,
+            ResolverErrorKind::from_request(&err),
+            Some(err.into()),
+        )
+    }
     pub fn kind(&self) -> ResolverErrorKind {
         self.kind
     }
+    pub fn status(&self) -> Option<StatusCode> {
+        self.status
+    }
 }
 
 impl Display for ResolverError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "{}", self.message)
+        writeln!(f, "{}", self.message)?;
+        if let Some(hint) = self.kind.hint() {
+            writeln!(f, "{}", hint)?;
+        }
+        Ok(())
     }
 }
 
@@ -80,13 +219,51 @@ impl Error for ResolverError {
         self.source.as_ref().map(|e| &**e as _)
     }
 }
+impl Default for CacheResolver {
+    fn default() -> Self {
+        CacheResolver::new()
+    }
+}
 impl CacheResolver {
     pub fn new() -> Self {
-        CacheResolver {}
+        CacheResolver {
+            snapshot_refresh: DEFAULT_SNAPSHOT_REFRESH,
+            metadata_ttl: None,
+        }
+    }
+    pub fn set_snapshot_refresh(&mut self, refresh: Duration) {
+        self.snapshot_refresh = refresh;
+    }
+    pub fn set_metadata_ttl(&mut self, ttl: Option<Duration>) {
+        self.metadata_ttl = ttl;
     }
 }
 impl Resolver for CacheResolver {
     fn fetch(&self, project: &mut Project) -> Result<String, ResolverError> {
+        // a snapshot may have a newer build, the cache is only trusted until the next refresh
+        if let Some(version) = project.get_selected_version() {
+            if is_snapshot(version)
+                && !crate::is_offline()
+                && snapshot_is_stale(
+                    &project.get_group_id(),
+                    &project.get_artifact_id(),
+                    version,
+                    self.snapshot_refresh,
+                )
+            {
+                return Err(ResolverError::new(
+                    format!(
+                        "Cached snapshot {}:{}:{} is due for a refresh",
+                        project.get_group_id(),
+                        project.get_artifact_id(),
+                        version
+                    )
+                    .as_str(),
+                    ResolverErrorKind::NotFound,
+                    None,
+                ));
+            }
+        }
         // initialize projectDep from project object
         let mut project_dep = ProjectDep::try_from(project.borrow()).map_err(|err| {
             ResolverError::new(
@@ -102,13 +279,11 @@ impl Resolver for CacheResolver {
             if let Some(prop_error) = err.downcast_ref::<PropertiesError>() {
                 match prop_error {
                     // A cache miss
-                    PropertiesError::IOError(msg) => {
-                        return ResolverError::new(
-                            msg.to_string().as_str(),
-                            ResolverErrorKind::NotFound,
-                            Some(err),
-                        )
-                    }
+                    PropertiesError::IOError(msg) => ResolverError::new(
+                        msg.to_string().as_str(),
+                        ResolverErrorKind::NotFound,
+                        Some(err),
+                    ),
                     // A malformed toml error so ideally if it is a cache resolver
                     // we should proceed to do a network fetch. hopefully it should
                     // fix the syntax errors
@@ -135,6 +310,19 @@ impl Resolver for CacheResolver {
             }
         })?;
 
+        // a truncated properties file can still be valid toml
+        let malformed = project_dep
+            .dependencies
+            .iter()
+            .find(|dep| dep.splitn(3, ':').count() != 3);
+        if project_dep.base_url.is_empty() || malformed.is_some() {
+            return Err(ResolverError::new(
+                "Cache properties file is incomplete",
+                ResolverErrorKind::ParseError,
+                None,
+            ));
+        }
+
         let deps = project_dep.dependencies.iter().map(|dep| {
             let split: Vec<&str> = dep.splitn(3, ':').collect();
             let group_id = split[0];
@@ -165,9 +353,54 @@ impl Resolver for CacheResolver {
             }
         }
 
+        // stale metadata misses newer releases, the network resolvers fetch it again
+        if let Some(ttl) = self.metadata_ttl {
+            if !crate::is_offline()
+                && metadata_is_stale(&project.get_group_id(), &project.get_artifact_id(), ttl)
+            {
+                return Err(ResolverError::new(
+                    format!(
+                        "Cached maven-metadata.xml for {}:{} is older than metadata_ttl",
+                        project.get_group_id(),
+                        project.get_artifact_id()
+                    )
+                    .as_str(),
+                    ResolverErrorKind::NotFound,
+                    None,
+                ));
+            }
+        }
+
+        let metadata = self.fetch_metadata(&project.get_group_id(), &project.get_artifact_id())?;
+
+        let selected_version = metadata
+            .select_version(project.get_version())
+            .map_err(|err| {
+                ResolverError::new(
+                    format!(
+                        "Failed to select correct version for {}:{} from metadata with: latest: {}, release: {} and available {:?}",
+                        project.get_group_id(),
+                        project.get_artifact_id(),
+                        metadata.latest.unwrap_or("None".to_string()),
+                        metadata.release.unwrap_or("None".to_string()),
+                        metadata.versions
+                    )
+                    .as_str(),
+                    ResolverErrorKind::NoSelectedVersion,
+                    Some(err),
+                )
+            })?;
+
+        Ok(selected_version)
+    }
+    fn fetch_metadata(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+    ) -> Result<MavenMetadata, ResolverError> {
         let mut cache = Cache::new(
-            project.get_group_id(),
-            project.get_artifact_id(),
+            group_id.to_string(),
+            artifact_id.to_string(),
             String::new(),
             CacheType::METADATA,
         );
@@ -195,38 +428,17 @@ impl Resolver for CacheResolver {
             )
         })?;
         let reader = io::BufReader::new(cache);
-        let metadata = parse_maven_metadata(reader).map_err(|err| {
+        parse_maven_metadata(reader).map_err(|err| {
             ResolverError::new(
                 format!(
                     "Failed to parse maven-metadata.xml for {}:{}",
-                    project.get_group_id(),
-                    project.get_artifact_id()
+                    group_id, artifact_id
                 )
                 .as_str(),
-                ResolverErrorKind::Internal,
+                ResolverErrorKind::ParseError,
                 Some(err),
             )
-        })?;
-
-        let selected_version = metadata
-            .select_version(project.get_version())
-            .map_err(|err| {
-                ResolverError::new(
-                    format!(
-                        "Failed to select correct version for {}:{} from metadata with: latest: {}, release: {} and available {:?}",
-                        project.get_group_id(),
-                        project.get_artifact_id(),
-                        metadata.latest.unwrap_or("None".to_string()),
-                        metadata.release.unwrap_or("None".to_string()),
-                        metadata.versions
-                    )
-                    .as_str(),
-                    ResolverErrorKind::NoSelectedVersion,
-                    Some(err),
-                )
-            })?;
-
-        Ok(selected_version)
+        })
     }
 }
 
@@ -246,36 +458,58 @@ impl Resolver for NetResolver {
                 None,
             ))?;
 
-        let url = if self.base_url.ends_with('/') {
-            format!(
-                "{0}{1}/{2}/{3}/{2}-{3}.pom",
-                self.base_url,
-                project.get_group_id().replace('.', "/"),
-                project.get_artifact_id(),
-                version
+        let url = if is_snapshot(&version) {
+            self.fetch_snapshot_metadata(
+                &project.get_group_id(),
+                &project.get_artifact_id(),
+                &version,
+            )?;
+            self.file_url(
+                &project.get_group_id(),
+                &project.get_artifact_id(),
+                &version,
+                "pom",
             )
         } else {
-            format!(
-                "{0}/{1}/{2}/{3}/{2}-{3}.pom",
-                self.base_url,
-                project.get_group_id().replace('.', "/"),
-                project.get_artifact_id(),
-                version
+            pom_url(
+                &self.base_url,
+                &project.get_group_id(),
+                &project.get_artifact_id(),
+                &version,
             )
         };
 
-        let res = self.client.get(&url).send().map_err(|err| {
-            ResolverError::new(
-                "Failed to complete the HTTP request for the resolver client",
-                ResolverErrorKind::Internal,
-                Some(err.into()),
-            )
-        })?;
+        let prefetched = self
+            .prefetcher
+            .as_ref()
+            .and_then(|prefetcher| prefetcher.take(&url));
+        let (status, sha1, body): (StatusCode, Option<String>, Box<dyn Read>) = match prefetched {
+            Some(pom) => (
+                StatusCode::from_u16(pom.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+                pom.sha1.clone(),
+                Box::new(pom.reader()),
+            ),
+            None => {
+                let res = metrics::timed(metrics::METADATA, || {
+                    send_with_retry(&url, || {
+                        authenticate(self.client.get(&url), &url).send_recorded()
+                    })
+                })
+                .map_err(|err| {
+                    ResolverError::from_request(
+                        "Failed to complete the HTTP request for the resolver client",
+                        err,
+                    )
+                })?;
+                let sha1 = checksum_header(res.headers());
+                (res.status(), sha1, Box::new(res))
+            }
+        };
 
-        log::trace!(target: "fetch", "{url} {}", res.status());
+        log::trace!(target: "fetch", "{url} {}", status);
 
-        if res.status().is_success() {
-            let mut reader = io::BufReader::new(res);
+        if status.is_success() {
+            let mut reader = io::BufReader::new(body);
             let mut cache = Cache::new(
                 project.get_group_id(),
                 project.get_artifact_id(),
@@ -299,14 +533,26 @@ impl Resolver for NetResolver {
                         Some(err.into()),
                     )
                 })?);
-                std::io::copy(&mut reader, &mut writer).map_err(|err| {
+                crate::interrupt::copy(&mut reader, &mut writer).map_err(|err| {
                     ResolverError::new(
                         "Failed to copy network contents to cache file",
                         ResolverErrorKind::Internal,
                         Some(err.into()),
                     )
                 })?;
-                drop(writer);
+                let mut file = writer.into_inner().map_err(|err| {
+                    ResolverError::new(
+                        "Failed to flush network contents to cache file",
+                        ResolverErrorKind::Internal,
+                        Some(err.into_error().into()),
+                    )
+                })?;
+                file.complete();
+                drop(file);
+
+                if let Some(expected) = &sha1 {
+                    verify_sha1(&cache, expected, &url)?;
+                }
 
                 let cache = cache.open().map_err(|err| {
                     ResolverError::new(
@@ -329,19 +575,8 @@ impl Resolver for NetResolver {
                 )
             })?;
             *project = p;
-        } else if matches!(res.status(), StatusCode::NOT_FOUND) {
-            // 404 not found
-            return Err(ResolverError::new(
-                format!("{}: Failed to fetch {} ", res.status().as_u16(), url).as_str(),
-                ResolverErrorKind::NotFound,
-                None,
-            ));
         } else {
-            return Err(ResolverError::new(
-                format!("{}: Failed to fetch {}", res.status().as_u16(), url).as_str(),
-                ResolverErrorKind::ResponseError,
-                None,
-            ));
+            return Err(ResolverError::from_response(&url, status));
         }
         Ok(self.base_url.clone())
     }
@@ -351,6 +586,12 @@ impl Resolver for NetResolver {
     fn get_priority(&self) -> i32 {
         self.priority
     }
+    fn get_base_url(&self) -> Option<&str> {
+        Some(self.base_url.as_str())
+    }
+    fn set_prefetcher(&mut self, prefetcher: Option<Arc<PomPrefetcher>>) {
+        self.prefetcher = prefetcher;
+    }
     fn calculate_version(&self, project: &Project) -> Result<String, ResolverError> {
         // if it is a softie return imediately unless they specify LATEST or RELEASE for backward compatibility
         if let VersionRequirement::Soft(v) = project.get_version() {
@@ -359,40 +600,77 @@ impl Resolver for NetResolver {
             }
         }
 
-        let url = if self.base_url.ends_with('/') {
-            format!(
-                "{0}{1}/{2}/maven-metadata.xml",
-                self.base_url,
-                project.get_group_id().replace('.', "/"),
-                project.get_artifact_id(),
-            )
-        } else {
-            format!(
-                "{0}/{1}/{2}/maven-metadata.xml",
-                self.base_url,
-                project.get_group_id().replace('.', "/"),
-                project.get_artifact_id(),
-            )
-        };
+        let metadata = self.fetch_metadata(&project.get_group_id(), &project.get_artifact_id())?;
+
+        // Now the reason why we are here
+        let versions = project.get_version();
 
-        let res = self.client.get(&url).send().map_err(|err| {
+        let selected_version = metadata.select_version(versions).map_err(|err| {
             ResolverError::new(
-                "Failed to complete the HTTP request for the version resolver client",
-                ResolverErrorKind::Internal,
-                Some(err.into()),
+                format!(
+                    "Failed to select correct version for {}:{} from metadata with: latest: {}, release: {} and available {:?}",
+                    project.get_group_id(),
+                    project.get_artifact_id(),
+                    metadata.latest.unwrap_or("None".to_string()),
+                    metadata.release.unwrap_or("None".to_string()),
+                    metadata.versions
+                )
+                .as_str(),
+                ResolverErrorKind::NoSelectedVersion,
+                Some(err),
             )
         })?;
 
+        Ok(selected_version)
+    }
+    fn fetch_metadata(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+    ) -> Result<MavenMetadata, ResolverError> {
+        let url = format!(
+            "{}maven-metadata.xml",
+            self.artifact_url(group_id, artifact_id)
+        );
+
+        let mut cache = Cache::new(
+            group_id.to_string(),
+            artifact_id.to_string(),
+            String::new(),
+            CacheType::METADATA,
+        );
+        let home = cache.use_labt_home();
+        let cached = home.as_ref().ok().and_then(|_| cache.get_path().ok());
+        let validators = cached
+            .as_deref()
+            .and_then(|path| read_validators(path, &url));
+
+        let mut res = self.request_metadata(&url, validators.as_ref())?;
+        if res.status() == StatusCode::NOT_MODIFIED {
+            let metadata = cached
+                .as_deref()
+                .and_then(|path| File::open(path).ok())
+                .and_then(|file| parse_maven_metadata(BufReader::new(file)).ok());
+            if let Some(metadata) = metadata {
+                log::trace!(target: "fetch", "{url} {}", res.status());
+                // the cached copy is as good as a fresh download for metadata_ttl
+                if let Some(file) = cached
+                    .as_deref()
+                    .and_then(|path| File::options().append(true).open(path).ok())
+                {
+                    let _ = file.set_modified(SystemTime::now());
+                }
+                return Ok(metadata);
+            }
+            // the cached copy is unusable so ask for the whole file
+            res = self.request_metadata(&url, None)?;
+        }
+
         if res.status().is_success() {
+            let validators = Validators::from_headers(&url, res.headers());
             let mut reader = io::BufReader::new(res);
-            let mut cache = Cache::new(
-                project.get_group_id(),
-                project.get_artifact_id(),
-                String::new(),
-                CacheType::METADATA,
-            );
 
-            let metadata_result = if let Err(err) = cache.use_labt_home() {
+            let metadata_result = if let Err(err) = home {
                 // if we are unable to initialize cache file, just ignore it.
                 // TODO have an effective way to error on this
                 warn!("Unable to cache response \n {:?}", err);
@@ -407,14 +685,28 @@ impl Resolver for NetResolver {
                         Some(err.into()),
                     )
                 })?);
-                std::io::copy(&mut reader, &mut writer).map_err(|err| {
+                crate::interrupt::copy(&mut reader, &mut writer).map_err(|err| {
                     ResolverError::new(
                         "Failed to copy network contents to cache file",
                         ResolverErrorKind::Internal,
                         Some(err.into()),
                     )
                 })?;
-                drop(writer);
+                let mut file = writer.into_inner().map_err(|err| {
+                    ResolverError::new(
+                        "Failed to flush network contents to cache file",
+                        ResolverErrorKind::Internal,
+                        Some(err.into_error().into()),
+                    )
+                })?;
+                file.complete();
+                drop(file);
+
+                if let Some(path) = &cached {
+                    if let Err(err) = save_validators(path, validators.as_ref()) {
+                        warn!("Unable to cache response validators \n {:?}", err);
+                    }
+                }
 
                 let cache = cache.open().map_err(|err| {
                     ResolverError::new(
@@ -427,60 +719,63 @@ impl Resolver for NetResolver {
                 let reader = BufReader::new(cache);
                 parse_maven_metadata(reader)
             };
-            let metadata = metadata_result.map_err(|err| {
+            metadata_result.map_err(|err| {
                 ResolverError::new(
                     format!(
                         "Failed to parse maven-metadata.xml for {}:{}",
-                        project.get_group_id(),
-                        project.get_artifact_id()
+                        group_id, artifact_id
                     )
                     .as_str(),
                     ResolverErrorKind::Internal,
                     Some(err),
                 )
-            })?;
-
-            // Now the reason why we are here
-            let versions = project.get_version();
-
-            let selected_version = metadata.select_version(versions).map_err(|err| {
-                ResolverError::new(
-                    format!(
-                        "Failed to select correct version for {}:{} from metadata with: latest: {}, release: {} and available {:?}",
-                        project.get_group_id(),
-                        project.get_artifact_id(),
-                        metadata.latest.unwrap_or("None".to_string()),
-                        metadata.release.unwrap_or("None".to_string()),
-                        metadata.versions
-                    )
-                    .as_str(),
-                    ResolverErrorKind::NoSelectedVersion,
-                    Some(err),
-                )
-            })?;
-
-            Ok(selected_version)
-        } else if matches!(res.status(), StatusCode::NOT_FOUND) {
-            // 404 not found
-            return Err(ResolverError::new(
-                format!("{}: Failed to fetch {} ", res.status().as_u16(), url).as_str(),
-                ResolverErrorKind::NotFound,
-                None,
-            ));
+            })
         } else {
-            return Err(ResolverError::new(
-                format!("{}: Failed to fetch {}", res.status().as_u16(), url).as_str(),
-                ResolverErrorKind::ResponseError,
-                None,
-            ));
+            Err(ResolverError::from_response(&url, res.status()))
+        }
+    }
+    fn last_modified(&self, group_id: &str, artifact_id: &str, version: &str) -> Option<String> {
+        let url = self.file_url(group_id, artifact_id, version, "pom");
+        let res = authenticate(self.client.head(&url), &url)
+            .send_recorded()
+            .ok()?;
+        if !res.status().is_success() {
+            return None;
         }
+        res.headers()
+            .get(reqwest::header::LAST_MODIFIED)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string())
     }
 }
 
 impl NetResolver {
+    /// Requests the maven-metadata.xml at url. With validators the repository
+    /// answers 304 Not Modified if the cached copy is still current
+    fn request_metadata(
+        &self,
+        url: &str,
+        validators: Option<&Validators>,
+    ) -> Result<reqwest::blocking::Response, ResolverError> {
+        metrics::timed(metrics::METADATA, || {
+            send_with_retry(url, || {
+                let request = authenticate(self.client.get(url), url);
+                match validators {
+                    Some(validators) => validators.apply(request),
+                    None => request,
+                }
+                .send_recorded()
+            })
+        })
+        .map_err(|err| {
+            ResolverError::from_request(
+                "Failed to complete the HTTP request for the version resolver client",
+                err,
+            )
+        })
+    }
     pub fn init(name: &str, base_url: &str) -> anyhow::Result<Self> {
-        let client = reqwest::blocking::Client::builder()
-            .user_agent(crate::USER_AGENT)
+        let client = client_builder()?
             .build()
             .context("Failed to initialize Net resolver client")?;
 
@@ -489,11 +784,153 @@ impl NetResolver {
             name: name.to_string(),
             base_url: base_url.to_string(),
             priority: 1,
+            prefetcher: None,
         })
     }
     pub fn set_priority(&mut self, priority: i32) {
         self.priority = priority;
     }
+    /// The url of the file with the extension of a version. Snapshot files are named
+    /// after the latest build in the cached snapshot metadata
+    fn file_url(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+        version: &str,
+        extension: &str,
+    ) -> String {
+        format!(
+            "{}{}/{}-{}.{}",
+            self.artifact_url(group_id, artifact_id),
+            version,
+            artifact_id,
+            remote_file_version(group_id, artifact_id, version, extension),
+            extension
+        )
+    }
+    /// Fetches the maven-metadata.xml of a snapshot version naming the files of its latest
+    /// build and caches it. Snapshots installed without metadata keep their plain file names.
+    fn fetch_snapshot_metadata(
+        &self,
+        group_id: &str,
+        artifact_id: &str,
+        version: &str,
+    ) -> Result<(), ResolverError> {
+        let url = format!(
+            "{}{}/maven-metadata.xml",
+            self.artifact_url(group_id, artifact_id),
+            version
+        );
+        let res = metrics::timed(metrics::METADATA, || {
+            send_with_retry(&url, || {
+                authenticate(self.client.get(&url), &url).send_recorded()
+            })
+        })
+        .map_err(|err| {
+            ResolverError::from_request(
+                "Failed to complete the HTTP request for the snapshot metadata",
+                err,
+            )
+        })?;
+        log::trace!(target: "fetch", "{url} {}", res.status());
+        if matches!(res.status(), StatusCode::NOT_FOUND) {
+            return Ok(());
+        }
+        if !res.status().is_success() {
+            return Err(ResolverError::from_response(&url, res.status()));
+        }
+        let body = res.bytes().map_err(|err| {
+            ResolverError::new(
+                format!("Failed to read {}", url).as_str(),
+                ResolverErrorKind::Internal,
+                Some(err.into()),
+            )
+        })?;
+        let metadata = parse_maven_metadata(BufReader::new(body.as_ref())).map_err(|err| {
+            ResolverError::new(
+                format!("Failed to parse maven-metadata.xml at {}", url).as_str(),
+                ResolverErrorKind::ParseError,
+                Some(err),
+            )
+        })?;
+        save_snapshot_metadata(group_id, artifact_id, version, &metadata, &body).map_err(|err| {
+            ResolverError::new(
+                "Failed to cache snapshot metadata",
+                ResolverErrorKind::Internal,
+                Some(err),
+            )
+        })
+    }
+    /// The url of the artifact directory in this repository, ending with a slash
+    fn artifact_url(&self, group_id: &str, artifact_id: &str) -> String {
+        if self.base_url.ends_with('/') {
+            format!(
+                "{0}{1}/{2}/",
+                self.base_url,
+                group_id.replace('.', "/"),
+                artifact_id
+            )
+        } else {
+            format!(
+                "{0}/{1}/{2}/",
+                self.base_url,
+                group_id.replace('.', "/"),
+                artifact_id
+            )
+        }
+    }
+}
+
+/// The sha1 a repository sent in the x-checksum-sha1 header
+pub fn checksum_header(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CHECKSUM_SHA1_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_lowercase())
+}
+
+/// Checks the file cached from url against the sha1 the repository sent. A file
+/// that does not match is removed so that it is not resolved from the cache.
+fn verify_sha1(cache: &Cache, expected: &str, url: &str) -> Result<(), ResolverError> {
+    let path = cache.get_path().map_err(|err| {
+        ResolverError::new(
+            "Failed to get cache file path",
+            ResolverErrorKind::Internal,
+            Some(err),
+        )
+    })?;
+    let actual = file_sha1(&path).map_err(|err| {
+        ResolverError::new(
+            format!("Failed to hash {:?}", path).as_str(),
+            ResolverErrorKind::Internal,
+            Some(err.into()),
+        )
+    })?;
+    if actual.eq_ignore_ascii_case(expected) {
+        return Ok(());
+    }
+    let _ = std::fs::remove_file(&path);
+    Err(ResolverError::new(
+        format!(
+            "Checksum mismatch for {}: the repository sent sha1 {} but the download has sha1 {}",
+            url, expected, actual
+        )
+        .as_str(),
+        ResolverErrorKind::ChecksumMismatch,
+        None,
+    ))
+}
+
+/// The url of the pom of a version in the repository at base_url
+pub fn pom_url(base_url: &str, group_id: &str, artifact_id: &str, version: &str) -> String {
+    format!(
+        "{0}{1}{2}/{3}/{4}/{3}-{4}.pom",
+        base_url,
+        if base_url.ends_with('/') { "" } else { "/" },
+        group_id.replace('.', "/"),
+        artifact_id,
+        version
+    )
 }
 
 /// Returns the default resolvers
@@ -508,3 +945,61 @@ pub fn get_default_resolvers() -> anyhow::Result<Vec<Box<dyn Resolver>>> {
     let google: Box<dyn Resolver> = Box::new(NetResolver::init(GOOGLE_REPO_STR, GOOGLE_REPO_URL)?);
     Ok(vec![cache, central, google])
 }
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn resolver_error_kinds_from_responses() {
+    let url = "https://repo.example.com/com/example/lib/1.0/lib-1.0.pom";
+    let kinds: Vec<ResolverErrorKind> = [401, 403, 404, 408, 429, 502, 504]
+        .into_iter()
+        .map(|code| ResolverError::from_response(url, StatusCode::from_u16(code).unwrap()).kind())
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            ResolverErrorKind::Unauthorized,
+            ResolverErrorKind::Unauthorized,
+            ResolverErrorKind::NotFound,
+            ResolverErrorKind::Timeout,
+            ResolverErrorKind::RateLimited,
+            ResolverErrorKind::ResponseError,
+            ResolverErrorKind::Timeout,
+        ]
+    );
+    assert!(ResolverErrorKind::RateLimited.try_next_resolver());
+    assert!(!ResolverErrorKind::ResponseError.try_next_resolver());
+
+    let err = ResolverError::from_response(url, StatusCode::UNAUTHORIZED);
+    assert_eq!(err.status(), Some(StatusCode::UNAUTHORIZED));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "401: Failed to fetch {}\n{}\n",
+            url,
+            ResolverErrorKind::Unauthorized.hint().unwrap()
+        )
+    );
+
+    let tmp = tempfile::tempdir().unwrap();
+    let dir = tmp.path().join("resolver-sha1");
+    let mut cache = Cache::new(
+        String::from("com.example"),
+        String::from("lib"),
+        String::from("1.0"),
+        CacheType::POM,
+    );
+    cache.set_cache_path(Some(dir.clone()));
+    let path = cache.get_path().unwrap();
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, "<project />").unwrap();
+    let sha1 = file_sha1(&path).unwrap();
+    let verified = verify_sha1(&cache, &sha1, url).is_ok();
+    let mismatch = verify_sha1(&cache, &"0".repeat(40), url).map_err(|err| err.kind());
+    let removed = !path.exists();
+
+    assert!(verified);
+    assert_eq!(mismatch, Err(ResolverErrorKind::ChecksumMismatch));
+    assert!(removed);
+}
@@ -0,0 +1,464 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use log::info;
+use reqwest::Url;
+
+use crate::caching::{Cache, CacheType};
+use crate::config::{Dependency, LabToml, LABT_TOML_FILE_NAME};
+use crate::pom::{Project, Scope};
+use crate::MULTI_PROGRESS_BAR;
+
+use super::git::{checkout_git_dependency, GitReference};
+use super::{Resolver, ResolverError, ResolverErrorKind};
+
+pub const PATH_REPO_STR: &str = "path";
+
+/// A local LABt library declared in Labt.toml with
+/// ```toml
+/// [dependencies]
+/// my-lib = { path = "../my-lib" }
+/// ```
+/// or `my-lib = { git = "https://...", tag = "v1.2" }`.
+/// The library is built with `labt build` and its output is consumed
+/// by the dependent project like any other artifact.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathLibrary {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    /// False for libraries that only provide a prebuilt artifact
+    pub build: bool,
+    /// The library project root
+    pub root: PathBuf,
+    /// The AAR/JAR produced by building the library
+    pub artifact: PathBuf,
+    /// The library's own dependencies as group_id, artifact_id, version
+    pub dependencies: Vec<(String, String, String)>,
+}
+
+impl PathLibrary {
+    /// The artifact packaging inferred from the artifact extension
+    pub fn packaging(&self) -> String {
+        self.artifact
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("aar")
+            .to_string()
+    }
+    fn matches(&self, project: &Project) -> bool {
+        self.group_id == project.get_group_id() && self.artifact_id == project.get_artifact_id()
+    }
+}
+
+/// Returns true for dependencies that are built locally instead of being fetched
+/// from a repository
+pub fn is_local(dep: &Dependency) -> bool {
+    dep.path.is_some() || dep.git.is_some()
+}
+
+/// Reads a Labt.toml from the given project directory
+fn read_config(root: &Path) -> anyhow::Result<LabToml> {
+    let path = root.join(LABT_TOML_FILE_NAME);
+    let toml_string = std::fs::read_to_string(&path).context(format!(
+        "Failed to read {:?}. Is this a LABt project?",
+        path
+    ))?;
+    toml::from_str(&toml_string).context(format!("Failed parsing {:?}", path))
+}
+
+/// Loads the library a path or git dependency points to. `root` is the directory of the
+/// Labt.toml that declared the dependency.
+fn load_library(
+    root: &Path,
+    artifact_id: &str,
+    dep: &Dependency,
+) -> anyhow::Result<(PathLibrary, Option<LabToml>)> {
+    let (lib_root, reference) = match &dep.git {
+        Some(location) => {
+            let reference = GitReference::from_dependency(dep)?;
+            let checkout = checkout_git_dependency(location, &reference)?;
+            let lib_root = match &dep.path {
+                Some(path) => checkout.join(path),
+                None => checkout,
+            };
+            (lib_root, Some(reference))
+        }
+        None => (root.join(dep.path.as_deref().unwrap_or(".")), None),
+    };
+    let lib_root = lib_root.canonicalize().context(format!(
+        "Path dependency {artifact_id} not found at {:?}",
+        lib_root
+    ))?;
+    // libraries that are not LABt projects must declare a prebuilt artifact
+    let config = if dep.artifact.is_some() && !lib_root.join(LABT_TOML_FILE_NAME).exists() {
+        None
+    } else {
+        Some(read_config(&lib_root)?)
+    };
+
+    let artifact_id = dep.artifact_id.clone().unwrap_or(artifact_id.to_string());
+    let group_id = match (&config, dep.group_id.is_empty()) {
+        (_, false) => dep.group_id.clone(),
+        (Some(config), true) => config.project.package.clone(),
+        (None, true) => bail!(
+            "group_id is required for dependency {artifact_id} since it is not a LABt project"
+        ),
+    };
+    let version = match (&config, &reference, dep.version.is_empty()) {
+        (_, _, false) => dep.version.clone(),
+        (Some(config), _, true) => config.project.version.clone(),
+        (None, Some(reference), true) => reference.version(),
+        (None, None, true) => {
+            bail!("version is required for dependency {artifact_id} since it is not a LABt project")
+        }
+    };
+    let artifact = match &dep.artifact {
+        Some(artifact) => lib_root.join(artifact),
+        None => lib_root.join("build").join(format!("{artifact_id}.aar")),
+    };
+
+    let library = PathLibrary {
+        group_id,
+        artifact_id,
+        version,
+        build: config.is_some(),
+        root: lib_root,
+        artifact,
+        dependencies: Vec::new(),
+    };
+    Ok((library, config))
+}
+
+fn collect_libraries(
+    root: &Path,
+    config: &LabToml,
+    libraries: &mut Vec<PathLibrary>,
+    visiting: &mut Vec<PathBuf>,
+) -> anyhow::Result<()> {
+    let deps = match &config.dependencies {
+        Some(deps) => deps,
+        None => return Ok(()),
+    };
+    for (artifact_id, dep) in deps.iter().filter(|(_, dep)| is_local(dep)) {
+        let (mut library, lib_config) = load_library(root, artifact_id, dep)?;
+        if visiting.contains(&library.root) {
+            bail!(
+                "Cyclic path dependency detected on {:?} through {:?}",
+                library.root,
+                root
+            );
+        }
+        if libraries.iter().any(|lib| lib.root == library.root) {
+            continue;
+        }
+
+        if let Some(lib_config) = lib_config {
+            library.dependencies = get_dependencies(&library.root, &lib_config)?;
+            visiting.push(library.root.clone());
+            collect_libraries(&library.root.clone(), &lib_config, libraries, visiting)?;
+            visiting.pop();
+        }
+        libraries.push(library);
+    }
+    Ok(())
+}
+
+/// Returns all the path libraries the project depends on, including the
+/// path dependencies of those libraries.
+///
+/// # Errors
+///
+/// Returns an error if a library Labt.toml cannot be read or the path dependencies form a cycle
+pub fn load_path_libraries(root: &Path, config: &LabToml) -> anyhow::Result<Vec<PathLibrary>> {
+    let root = root.canonicalize().unwrap_or(root.to_path_buf());
+    let mut libraries = Vec::new();
+    let mut visiting = vec![root.clone()];
+    collect_libraries(&root, config, &mut libraries, &mut visiting)?;
+    Ok(libraries)
+}
+
+/// Lists the dependencies declared in config as group_id, artifact_id, version.
+/// Path dependencies take their coordinates from the library Labt.toml. Boms are
+/// not dependencies and are skipped.
+///
+/// # Errors
+///
+/// Returns an error if a path dependency library cannot be loaded
+pub fn get_dependencies(
+    root: &Path,
+    config: &LabToml,
+) -> anyhow::Result<Vec<(String, String, String)>> {
+    Ok(get_scoped_dependencies(root, config)?
+        .into_iter()
+        .map(|(coordinates, _)| coordinates)
+        .collect())
+}
+
+/// group_id, artifact_id and version of a dependency
+pub type Coordinates = (String, String, String);
+
+/// Lists the dependencies declared in config like [`get_dependencies`] with the
+/// scope of their entry
+///
+/// # Errors
+///
+/// Returns an error if a path dependency library cannot be loaded or a scope is
+/// not supported
+pub fn get_scoped_dependencies(
+    root: &Path,
+    config: &LabToml,
+) -> anyhow::Result<Vec<(Coordinates, Scope)>> {
+    let mut dependencies = Vec::new();
+    if let Some(deps) = &config.dependencies {
+        for (artifact_id, dep) in deps {
+            if dep.is_bom() {
+                continue;
+            }
+            let scope = dep
+                .get_scope()
+                .context(format!("Invalid scope for dependency {artifact_id}"))?;
+            if is_local(dep) {
+                let (library, _) = load_library(root, artifact_id, dep)?;
+                dependencies.push((
+                    (library.group_id, library.artifact_id, library.version),
+                    scope,
+                ));
+            } else {
+                dependencies.push((
+                    (
+                        dep.group_id.clone(),
+                        artifact_id.clone(),
+                        dep.version.clone(),
+                    ),
+                    scope,
+                ));
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+/// Resolves path dependencies by building the library and placing its
+/// output in the labt cache
+pub struct PathResolver {
+    libraries: Vec<PathLibrary>,
+    /// Libraries already built during this run
+    built: RefCell<HashSet<PathBuf>>,
+}
+
+impl PathResolver {
+    pub fn new(libraries: Vec<PathLibrary>) -> Self {
+        PathResolver {
+            libraries,
+            built: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Builds the library if it is a LABt project and copies the result into the cache
+    fn build(library: &PathLibrary) -> anyhow::Result<()> {
+        if library.build {
+            Self::run_build(library)?;
+        }
+        if !library.artifact.exists() {
+            bail!(
+                "Artifact {:?} for dependency {}:{} was not found. Set artifact in its dependency entry.",
+                library.artifact,
+                library.group_id,
+                library.artifact_id
+            );
+        }
+        Self::install(library)
+    }
+    /// Runs `labt build` in the library project root
+    fn run_build(library: &PathLibrary) -> anyhow::Result<()> {
+        info!(target: "resolve", "Building path dependency {}:{} at {:?}", library.group_id, library.artifact_id, library.root);
+        let exe = std::env::current_exe().context("Failed to get the labt executable path")?;
+        let status = MULTI_PROGRESS_BAR.suspend(|| {
+            Command::new(exe)
+                .arg("build")
+                .current_dir(&library.root)
+                .status()
+        });
+        let status = status.context(format!("Failed to run labt build in {:?}", library.root))?;
+        if !status.success() {
+            bail!(
+                "labt build for path dependency {:?} failed with {}",
+                library.root,
+                status
+            );
+        }
+        Ok(())
+    }
+    /// Copies the library artifact into the labt cache
+    fn install(library: &PathLibrary) -> anyhow::Result<()> {
+        let mut cache = Cache::new(
+            library.group_id.clone(),
+            library.artifact_id.clone(),
+            library.version.clone(),
+            CacheType::from(library.packaging()),
+        );
+        cache.use_labt_home()?;
+        let cache = cache.create().context("Failed to create cache file")?;
+        let mut reader = BufReader::new(
+            File::open(&library.artifact)
+                .context(format!("Failed to open {:?}", library.artifact))?,
+        );
+        let mut writer = BufWriter::new(cache);
+        crate::interrupt::copy(&mut reader, &mut writer)
+            .context("Failed to copy built artifact to cache")?;
+        let mut cache = writer
+            .into_inner()
+            .context("Failed to flush built artifact to cache")?;
+        cache.complete();
+        Ok(())
+    }
+}
+
+impl Resolver for PathResolver {
+    fn fetch(&self, project: &mut Project) -> Result<String, ResolverError> {
+        let library = match self.libraries.iter().find(|lib| lib.matches(project)) {
+            Some(library) => library,
+            None => {
+                return Err(ResolverError::new(
+                    "Not a path dependency",
+                    ResolverErrorKind::NotFound,
+                    None,
+                ))
+            }
+        };
+
+        if !self.built.borrow().contains(&library.root) {
+            Self::build(library).map_err(|err| {
+                ResolverError::new(
+                    "Failed to build path dependency",
+                    ResolverErrorKind::Internal,
+                    Some(err),
+                )
+            })?;
+            self.built.borrow_mut().insert(library.root.clone());
+        }
+
+        let deps = library
+            .dependencies
+            .iter()
+            .map(|(group_id, artifact_id, version)| {
+                let mut p = Project::new(group_id, artifact_id, version);
+                p.set_selected_version(Some(version.to_string()));
+                p
+            });
+        project.set_packaging(library.packaging());
+        project.get_dependencies_mut().extend(deps);
+
+        let url = Url::from_directory_path(&library.root)
+            .map(|url| url.to_string())
+            .unwrap_or(library.root.to_string_lossy().to_string());
+        Ok(url)
+    }
+    fn get_name(&self) -> &str {
+        PATH_REPO_STR
+    }
+    fn get_priority(&self) -> i32 {
+        // local libraries always take precedence over the cache and repositories
+        i32::MAX
+    }
+    fn calculate_version(&self, project: &Project) -> Result<String, ResolverError> {
+        match self.libraries.iter().find(|lib| lib.matches(project)) {
+            Some(library) => Ok(library.version.clone()),
+            None => Err(ResolverError::new(
+                "Not a path dependency",
+                ResolverErrorKind::NotFound,
+                None,
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn load_nested_path_libraries() {
+    let tmp = tempfile::tempdir().unwrap();
+    let base = tmp.path().join("path-deps");
+    let app = base.join("app");
+    let lib = base.join("lib");
+    let core = base.join("core");
+    let prebuilt = base.join("prebuilt");
+    for dir in [&app, &lib, &core, &prebuilt] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    let project = |name: &str, deps: &str| {
+        format!(
+            "[project]\nname = \"{name}\"\ndescription = \"\"\nversion_number = 1\nversion = \"1.0.0\"\npackage = \"com.example.{name}\"\n\n[dependencies]\n{deps}\n"
+        )
+    };
+    std::fs::write(
+        app.join(LABT_TOML_FILE_NAME),
+        project(
+            "app",
+            "lib = { path = \"../lib\" }\nextra = { path = \"../prebuilt\", group_id = \"org.extra\", version = \"0.1\", artifact = \"extra.jar\" }",
+        ),
+    )
+    .unwrap();
+    std::fs::write(
+        lib.join(LABT_TOML_FILE_NAME),
+        project(
+            "lib",
+            "core = { path = \"../core\", version = \"2.0.0\", artifact = \"out/core.jar\" }\nokhttp = { group_id = \"com.squareup.okhttp3\", version = \"4.9.2\" }",
+        ),
+    )
+    .unwrap();
+    std::fs::write(core.join(LABT_TOML_FILE_NAME), project("core", "")).unwrap();
+
+    let config = read_config(&app).unwrap();
+    let libraries = load_path_libraries(&app, &config).unwrap();
+
+    assert_eq!(libraries.len(), 3);
+    let extra = libraries
+        .iter()
+        .find(|lib| lib.artifact_id == "extra")
+        .unwrap();
+    assert!(!extra.build);
+    assert_eq!(extra.group_id, "org.extra");
+    assert!(extra.dependencies.is_empty());
+
+    let libraries: Vec<&PathLibrary> = libraries
+        .iter()
+        .filter(|lib| lib.artifact_id != "extra")
+        .collect();
+    // dependencies are listed before their dependents
+    let core = &libraries[0];
+    assert_eq!(core.group_id, "com.example.core");
+    assert_eq!(core.version, "2.0.0");
+    assert_eq!(core.packaging(), "jar");
+
+    let lib = &libraries[1];
+    assert!(lib.build);
+    assert_eq!(lib.group_id, "com.example.lib");
+    assert_eq!(lib.artifact_id, "lib");
+    assert_eq!(lib.version, "1.0.0");
+    assert_eq!(lib.packaging(), "aar");
+    let mut deps = lib.dependencies.clone();
+    deps.sort();
+    assert_eq!(
+        deps,
+        vec![
+            (
+                String::from("com.example.core"),
+                String::from("core"),
+                String::from("2.0.0")
+            ),
+            (
+                String::from("com.squareup.okhttp3"),
+                String::from("okhttp"),
+                String::from("4.9.2")
+            ),
+        ]
+    );
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{BufReader, Read},
+    sync::{Arc, Condvar, Mutex, MutexGuard},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use log::trace;
+
+use crate::{
+    caching::{Cache, CacheType},
+    config::{
+        global::{authenticate, client_builder, hold_host, pace, retry_after, MAX_RETRY_AFTER},
+        maven_metadata::is_snapshot,
+    },
+    http_recording::SendRecorded,
+    interrupt, metrics,
+    pom::{parse_pom, Project, VersionRequirement},
+    session::Session,
+    submodules::resolve::ResolveOptions,
+};
+
+use super::{checksum_header, pom_url};
+
+/// Number of poms fetched at the same time if not configured
+pub const DEFAULT_JOBS: usize = 8;
+/// How often a waiting resolver checks for interruption
+const WAIT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A pom response downloaded ahead of the resolver asking for it
+#[derive(Debug, Clone)]
+pub struct PrefetchedPom {
+    pub status: u16,
+    /// The sha1 the repository sent with the pom
+    pub sha1: Option<String>,
+    pub body: Vec<u8>,
+}
+
+impl PrefetchedPom {
+    pub fn reader(self) -> impl Read {
+        std::io::Cursor::new(self.body)
+    }
+}
+
+#[derive(Debug)]
+enum Entry {
+    /// Queued or being downloaded
+    Pending,
+    /// A failed request is kept as a message so that the resolver can retry it
+    Done(Result<PrefetchedPom, String>),
+}
+
+#[derive(Debug)]
+struct Request {
+    group_id: String,
+    artifact_id: String,
+    version: String,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Request>,
+    /// group_id:artifact_id:version already submitted
+    submitted: HashSet<String>,
+    /// Responses by pom url
+    responses: HashMap<String, Entry>,
+    shutdown: bool,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+    /// (name, base url) of the network repositories in resolver order
+    repositories: Vec<(String, String)>,
+    options: ResolveOptions,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+    /// The pom urls the resolvers would try for this coordinate, in order
+    fn urls(&self, request: &Request) -> Vec<String> {
+        let pinned = self.options.pinned_resolver(&request.group_id);
+        self.repositories
+            .iter()
+            .filter(|(name, _)| pinned.is_none_or(|pin| pin == name))
+            .map(|(_, base_url)| {
+                pom_url(
+                    base_url,
+                    &request.group_id,
+                    &request.artifact_id,
+                    &request.version,
+                )
+            })
+            .collect()
+    }
+}
+
+/// Downloads poms on a pool of worker threads while the dependency tree is walked.
+/// The walk itself stays serial so conflict resolution happens in the same order every
+/// time, it only finds the poms it needs already downloaded. Fetched poms are also
+/// parsed to queue their parent and dependencies with fixed versions ahead of the walk.
+pub struct PomPrefetcher {
+    shared: Arc<Shared>,
+}
+
+impl PomPrefetcher {
+    /// Starts jobs workers fetching from the repositories, given as (name, base url)
+    /// in the order the resolvers query them.
+    pub fn new(repositories: Vec<(String, String)>, options: ResolveOptions, jobs: usize) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+            repositories,
+            options,
+        });
+        let session = Session::current();
+        for _ in 0..jobs.max(1) {
+            let shared = Arc::clone(&shared);
+            let session = Arc::clone(&session);
+            thread::spawn(move || session.enter(|| worker(shared)));
+        }
+        PomPrefetcher { shared }
+    }
+    /// Queues the pom of a coordinate. Coordinates are only fetched once and those
+    /// already in the labt cache are skipped.
+    pub fn submit(&self, group_id: &str, artifact_id: &str, version: &str) {
+        submit(&self.shared, group_id, artifact_id, version);
+    }
+    /// Takes the response for the url, waiting if it is still being downloaded.
+    /// Returns None if the url was not prefetched or its request failed, in which
+    /// case the caller fetches it itself.
+    pub fn take(&self, url: &str) -> Option<PrefetchedPom> {
+        let mut state = self.shared.lock();
+        loop {
+            match state.responses.get(url) {
+                None => return None,
+                Some(Entry::Pending) => {
+                    if state.shutdown || interrupt::is_interrupted() {
+                        return None;
+                    }
+                    state = self
+                        .shared
+                        .changed
+                        .wait_timeout(state, WAIT_INTERVAL)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .0;
+                }
+                Some(Entry::Done(_)) => {
+                    return match state.responses.remove(url) {
+                        Some(Entry::Done(Ok(pom))) => Some(pom),
+                        Some(Entry::Done(Err(err))) => {
+                            trace!(target: "fetch", "Prefetching {url} failed: {err}");
+                            None
+                        }
+                        _ => None,
+                    };
+                }
+            }
+        }
+    }
+}
+
+impl Drop for PomPrefetcher {
+    fn drop(&mut self) {
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+        state.queue.clear();
+        self.shared.changed.notify_all();
+    }
+}
+
+fn submit(shared: &Shared, group_id: &str, artifact_id: &str, version: &str) {
+    // snapshot poms are named after their latest build, found in the snapshot metadata
+    if is_snapshot(version) {
+        return;
+    }
+    let request = Request {
+        group_id: group_id.to_string(),
+        artifact_id: artifact_id.to_string(),
+        version: version.to_string(),
+    };
+    let mut state = shared.lock();
+    if state.shutdown
+        || !state
+            .submitted
+            .insert(format!("{group_id}:{artifact_id}:{version}"))
+    {
+        return;
+    }
+    // resolved earlier, the cache resolver answers before any repository
+    let mut cache = Cache::new(
+        request.group_id.clone(),
+        request.artifact_id.clone(),
+        request.version.clone(),
+        CacheType::PROPERTIES,
+    );
+    if cache.use_labt_home().is_ok() && cache.exists() {
+        return;
+    }
+    for url in shared.urls(&request) {
+        state.responses.entry(url).or_insert(Entry::Pending);
+    }
+    state.queue.push_back(request);
+    shared.changed.notify_one();
+}
+
+fn worker(shared: Arc<Shared>) {
+    let client = match client_builder().and_then(|builder| Ok(builder.build()?)) {
+        Ok(client) => client,
+        Err(_) => return,
+    };
+    loop {
+        let request = {
+            let mut state = shared.lock();
+            loop {
+                if state.shutdown || interrupt::is_interrupted() {
+                    return;
+                }
+                if let Some(request) = state.queue.pop_front() {
+                    break request;
+                }
+                state = shared
+                    .changed
+                    .wait_timeout(state, WAIT_INTERVAL)
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .0;
+            }
+        };
+        fetch_request(&shared, &client, &request);
+    }
+}
+
+/// Tries the repositories in order until one has the pom, like the resolvers do
+fn fetch_request(shared: &Shared, client: &reqwest::blocking::Client, request: &Request) {
+    let urls = shared.urls(request);
+    for (index, url) in urls.iter().enumerate() {
+        if !pace(url) {
+            return;
+        }
+        let response = metrics::timed(metrics::METADATA, || {
+            authenticate(client.get(url), url).send_recorded()
+        })
+        .and_then(|res| {
+            let status = res.status().as_u16();
+            // the resolver retries the pom, other prefetches wait with it. A longer
+            // wait than the resolver accepts is left to the resolver to give up on
+            if matches!(status, 429 | 503) {
+                if let Some(after) = retry_after(res.headers(), SystemTime::now()) {
+                    hold_host(url, after.min(MAX_RETRY_AFTER));
+                }
+            }
+            let sha1 = checksum_header(res.headers());
+            res.bytes().map(|body| PrefetchedPom {
+                status,
+                sha1,
+                body: body.to_vec(),
+            })
+        })
+        .map_err(|err| err.to_string())
+        // transient server errors are left to the resolver, which retries them
+        .and_then(|pom| match pom.status {
+            429 | 500..=599 => Err(format!("{} response", pom.status)),
+            _ => Ok(pom),
+        });
+        trace!(target: "fetch", "prefetch {url} {:?}", response.as_ref().map(|pom| pom.status));
+        let found = response.as_ref().is_ok_and(|pom| pom.status == 200);
+        let body = match &response {
+            Ok(pom) if found => Some(pom.body.clone()),
+            _ => None,
+        };
+
+        let mut state = shared.lock();
+        // the resolvers may have stopped waiting and fetched it themselves
+        if state.responses.contains_key(url) {
+            state.responses.insert(url.clone(), Entry::Done(response));
+        }
+        if found {
+            // the resolvers stop at the first repository with the pom
+            for url in &urls[index + 1..] {
+                if matches!(state.responses.get(url), Some(Entry::Pending)) {
+                    state.responses.remove(url);
+                }
+            }
+        }
+        shared.changed.notify_all();
+        drop(state);
+
+        if let Some(body) = body {
+            submit_dependencies(shared, request, &body);
+            return;
+        }
+    }
+}
+
+/// Queues the parent and the dependencies with fixed versions declared by the pom
+fn submit_dependencies(shared: &Shared, request: &Request, body: &[u8]) {
+    let project = Project::new(&request.group_id, &request.artifact_id, &request.version);
+    let Ok(project) = parse_pom(BufReader::new(body), project) else {
+        return;
+    };
+    if let Some(parent) = &project.parent {
+        submit(
+            shared,
+            &parent.group_id,
+            &parent.artifact_id,
+            &parent.version,
+        );
+    }
+    for dep in project.get_dependencies() {
+        if !shared.options.includes(&dep.get_scope(), dep.is_optional()) {
+            continue;
+        }
+        if let VersionRequirement::Soft(version) = dep.get_version() {
+            if version.contains("${") || version == "LATEST" || version == "RELEASE" {
+                continue;
+            }
+            submit(shared, &dep.get_group_id(), &dep.get_artifact_id(), version);
+        }
+    }
+}
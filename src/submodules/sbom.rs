@@ -0,0 +1,755 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use log::info;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+
+use crate::{
+    caching::{download::artifact_file_name, file_sha1, recorded_checksum, Cache},
+    config::{get_config, lock::load_labt_lock, LabToml},
+    get_project_root,
+    pom::{License, Scope},
+    LABT_VERSION,
+};
+
+use super::{
+    licenses::{read_cached_licenses, spdx_license_id},
+    resolve::{get_config_dependencies, ProjectDep},
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct SbomArgs {
+    /// The SBOM standard to write
+    #[arg(short, long, value_enum, default_value_t = SbomFormat::Cyclonedx)]
+    format: SbomFormat,
+    /// Write the SBOM to this file instead of stdout. Relative paths are resolved
+    /// from the project root
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum SbomFormat {
+    /// CycloneDX 1.5 JSON
+    Cyclonedx,
+    /// SPDX 2.3 JSON
+    Spdx,
+}
+
+pub struct Sbom {
+    args: SbomArgs,
+}
+
+impl Sbom {
+    pub fn new(args: &SbomArgs) -> Self {
+        Sbom { args: args.clone() }
+    }
+}
+
+/// The Labt.lock section a component was resolved into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Project,
+    Test,
+    Processor,
+}
+
+/// A locked dependency with the details recorded in the SBOM
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Component {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+    pub packaging: String,
+    pub scope: Scope,
+    pub section: Option<Section>,
+    /// Where the artifact was downloaded from. None for local dependencies
+    pub download_url: Option<String>,
+    /// Sha1 of the cached artifact
+    pub sha1: Option<String>,
+    pub licenses: Vec<License>,
+    /// group_id:artifact_id:version of the dependencies of this component
+    pub dependencies: Vec<String>,
+}
+
+impl Component {
+    /// Creates the component of a locked dependency, reading its checksum and
+    /// licenses from the labt cache
+    pub fn from_lock(dep: &ProjectDep, section: Section) -> Self {
+        let mut artifact = Cache::from(dep);
+        let sha1 = artifact
+            .use_labt_home()
+            .ok()
+            .and_then(|_| artifact.get_path().ok())
+            .and_then(|path| file_sha1(&path).ok().or_else(|| recorded_checksum(&path)));
+        let download_url = if dep.base_url.is_empty() || dep.base_url.starts_with("file:") {
+            None
+        } else {
+            Some(format!("{}{}", dep.get_root_url(), artifact_file_name(dep)))
+        };
+        Component {
+            group_id: dep.group_id.clone(),
+            artifact_id: dep.artifact_id.clone(),
+            version: dep.version.clone(),
+            packaging: dep.packaging.clone(),
+            scope: dep.scope.clone(),
+            section: Some(section),
+            download_url,
+            sha1,
+            licenses: read_cached_licenses(&dep.group_id, &dep.artifact_id, &dep.version)
+                .unwrap_or_default(),
+            dependencies: dep.dependencies.clone(),
+        }
+    }
+    pub fn coordinate(&self) -> String {
+        format!("{}:{}:{}", self.group_id, self.artifact_id, self.version)
+    }
+    /// The package url e.g. pkg:maven/androidx.core/core@1.12.0?type=aar
+    pub fn purl(&self) -> String {
+        let purl = format!(
+            "pkg:maven/{}/{}@{}",
+            self.group_id, self.artifact_id, self.version
+        );
+        match self.packaging.as_str() {
+            "" | "jar" | "bundle" => purl,
+            packaging => format!("{purl}?type={packaging}"),
+        }
+    }
+    /// CycloneDX component scope. Test dependencies and annotation processors are
+    /// not shipped with the app
+    fn cyclonedx_scope(&self) -> &'static str {
+        match (self.section, &self.scope) {
+            (Some(Section::Test | Section::Processor), _) => "excluded",
+            (_, Scope::PROVIDED) => "optional",
+            _ => "required",
+        }
+    }
+}
+
+/// The app the SBOM describes
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Application {
+    pub name: String,
+    pub package: String,
+    pub version: String,
+    /// group_id:artifact_id:version of the direct dependencies
+    pub dependencies: Vec<String>,
+}
+
+impl Application {
+    fn from_config(config: &LabToml, lock: &[Component]) -> anyhow::Result<Self> {
+        let direct = get_config_dependencies(config)?;
+        let dependencies = lock
+            .iter()
+            .filter(|component| component.section == Some(Section::Project))
+            .filter(|component| {
+                direct.iter().any(|dep| {
+                    dep.get_group_id() == component.group_id
+                        && dep.get_artifact_id() == component.artifact_id
+                })
+            })
+            .map(|component| component.coordinate())
+            .collect();
+        Ok(Application {
+            name: config.project.name.clone(),
+            package: config.project.package.clone(),
+            version: config.project.version.clone(),
+            dependencies,
+        })
+    }
+}
+
+/// Maps group_id:artifact_id:version to the components ref
+fn refs<'a>(
+    components: &'a [Component],
+    reference: impl Fn(&'a Component) -> String,
+) -> HashMap<String, String> {
+    components
+        .iter()
+        .map(|component| (component.coordinate(), reference(component)))
+        .collect()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDx {
+    bom_format: &'static str,
+    spec_version: &'static str,
+    version: u32,
+    metadata: CycloneDxMetadata,
+    components: Vec<CycloneDxComponent>,
+    dependencies: Vec<CycloneDxDependency>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxMetadata {
+    timestamp: String,
+    tools: CycloneDxTools,
+    component: CycloneDxComponent,
+}
+
+#[derive(Serialize)]
+struct CycloneDxTools {
+    components: Vec<CycloneDxComponent>,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxComponent {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(rename = "bom-ref", skip_serializing_if = "Option::is_none")]
+    bom_ref: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    name: String,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    purl: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    hashes: Vec<CycloneDxHash>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    licenses: Vec<CycloneDxLicense>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_references: Vec<CycloneDxReference>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    properties: Vec<CycloneDxProperty>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxHash {
+    alg: &'static str,
+    content: String,
+}
+
+#[derive(Serialize)]
+struct CycloneDxLicense {
+    license: CycloneDxLicenseChoice,
+}
+
+#[derive(Serialize)]
+struct CycloneDxLicenseChoice {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CycloneDxReference {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    url: String,
+}
+
+#[derive(Serialize)]
+struct CycloneDxProperty {
+    name: &'static str,
+    value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CycloneDxDependency {
+    #[serde(rename = "ref")]
+    reference: String,
+    depends_on: Vec<String>,
+}
+
+/// Renders the SBOM as CycloneDX 1.5 JSON
+pub fn cyclonedx(app: &Application, components: &[Component], timestamp: &str) -> String {
+    let purls = refs(components, Component::purl);
+    let depends_on = |dependencies: &[String]| -> Vec<String> {
+        dependencies
+            .iter()
+            .filter_map(|dep| purls.get(dep).cloned())
+            .collect()
+    };
+    let app_ref = format!("{}@{}", app.package, app.version);
+
+    let mut dependencies = vec![CycloneDxDependency {
+        reference: app_ref.clone(),
+        depends_on: depends_on(&app.dependencies),
+    }];
+    dependencies.extend(components.iter().map(|component| CycloneDxDependency {
+        reference: component.purl(),
+        depends_on: depends_on(&component.dependencies),
+    }));
+
+    let bom = CycloneDx {
+        bom_format: "CycloneDX",
+        spec_version: "1.5",
+        version: 1,
+        metadata: CycloneDxMetadata {
+            timestamp: timestamp.to_string(),
+            tools: CycloneDxTools {
+                components: vec![CycloneDxComponent {
+                    kind: "application",
+                    name: String::from("labt"),
+                    version: LABT_VERSION.to_string(),
+                    ..Default::default()
+                }],
+            },
+            component: CycloneDxComponent {
+                kind: "application",
+                bom_ref: Some(app_ref),
+                group: Some(app.package.clone()),
+                name: app.name.clone(),
+                version: app.version.clone(),
+                ..Default::default()
+            },
+        },
+        components: components
+            .iter()
+            .map(|component| CycloneDxComponent {
+                kind: "library",
+                bom_ref: Some(component.purl()),
+                group: Some(component.group_id.clone()),
+                name: component.artifact_id.clone(),
+                version: component.version.clone(),
+                scope: Some(component.cyclonedx_scope()),
+                purl: Some(component.purl()),
+                hashes: component
+                    .sha1
+                    .iter()
+                    .map(|sha1| CycloneDxHash {
+                        alg: "SHA-1",
+                        content: sha1.clone(),
+                    })
+                    .collect(),
+                licenses: component
+                    .licenses
+                    .iter()
+                    .map(|license| {
+                        let id = spdx_license_id(license).map(|id| id.to_string());
+                        CycloneDxLicense {
+                            license: CycloneDxLicenseChoice {
+                                name: id.is_none().then(|| license.name.clone()),
+                                id,
+                                url: license.url.clone(),
+                            },
+                        }
+                    })
+                    .collect(),
+                external_references: component
+                    .download_url
+                    .iter()
+                    .map(|url| CycloneDxReference {
+                        kind: "distribution",
+                        url: url.clone(),
+                    })
+                    .collect(),
+                properties: vec![CycloneDxProperty {
+                    name: "labt:scope",
+                    value: component.scope.to_string(),
+                }],
+            })
+            .collect(),
+        dependencies,
+    };
+    serde_json::to_string_pretty(&bom).unwrap_or_default()
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Spdx {
+    spdx_version: &'static str,
+    data_license: &'static str,
+    #[serde(rename = "SPDXID")]
+    spdx_id: &'static str,
+    name: String,
+    document_namespace: String,
+    creation_info: SpdxCreationInfo,
+    packages: Vec<SpdxPackage>,
+    relationships: Vec<SpdxRelationship>,
+}
+
+#[derive(Serialize)]
+struct SpdxCreationInfo {
+    created: String,
+    creators: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxPackage {
+    name: String,
+    #[serde(rename = "SPDXID")]
+    spdx_id: String,
+    version_info: String,
+    download_location: String,
+    files_analyzed: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    checksums: Vec<SpdxChecksum>,
+    license_concluded: &'static str,
+    license_declared: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    license_comments: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    primary_package_purpose: Option<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_refs: Vec<SpdxExternalRef>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxChecksum {
+    algorithm: &'static str,
+    checksum_value: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxExternalRef {
+    reference_category: &'static str,
+    reference_type: &'static str,
+    reference_locator: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpdxRelationship {
+    spdx_element_id: String,
+    relationship_type: &'static str,
+    related_spdx_element: String,
+}
+
+const NOASSERTION: &str = "NOASSERTION";
+
+/// SPDX ids may only contain letters, numbers, . and -
+fn spdx_ref(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("SPDXRef-{name}")
+}
+
+/// Renders the SBOM as SPDX 2.3 JSON
+pub fn spdx(app: &Application, components: &[Component], timestamp: &str) -> String {
+    let ids = refs(components, |component| {
+        spdx_ref(&format!("Package-{}", component.coordinate()))
+    });
+    let app_id = spdx_ref(&format!("Package-{}", app.package));
+
+    // the namespace has to be unique for each document
+    let mut sha = Sha1::new();
+    sha.update(timestamp.as_bytes());
+    for component in components {
+        sha.update(component.purl().as_bytes());
+    }
+    let namespace = format!(
+        "https://spdx.org/spdxdocs/{}-{}-{:x}",
+        app.package,
+        app.version,
+        sha.finalize()
+    );
+
+    let mut packages = vec![SpdxPackage {
+        name: app.name.clone(),
+        spdx_id: app_id.clone(),
+        version_info: app.version.clone(),
+        download_location: NOASSERTION.to_string(),
+        files_analyzed: false,
+        checksums: Vec::new(),
+        license_concluded: NOASSERTION,
+        license_declared: NOASSERTION.to_string(),
+        license_comments: None,
+        primary_package_purpose: Some("APPLICATION"),
+        external_refs: Vec::new(),
+        comment: None,
+    }];
+    let mut relationships = vec![SpdxRelationship {
+        spdx_element_id: String::from("SPDXRef-DOCUMENT"),
+        relationship_type: "DESCRIBES",
+        related_spdx_element: app_id.clone(),
+    }];
+    relationships.extend(
+        app.dependencies
+            .iter()
+            .filter_map(|dep| ids.get(dep))
+            .map(|id| SpdxRelationship {
+                spdx_element_id: app_id.clone(),
+                relationship_type: "DEPENDS_ON",
+                related_spdx_element: id.clone(),
+            }),
+    );
+
+    for component in components {
+        let id = spdx_ref(&format!("Package-{}", component.coordinate()));
+        let declared: Vec<Option<&str>> = component.licenses.iter().map(spdx_license_id).collect();
+        let (license_declared, license_comments) =
+            if !declared.is_empty() && declared.iter().all(Option::is_some) {
+                (
+                    declared
+                        .into_iter()
+                        .flatten()
+                        .collect::<Vec<_>>()
+                        .join(" OR "),
+                    None,
+                )
+            } else if component.licenses.is_empty() {
+                (NOASSERTION.to_string(), None)
+            } else {
+                let names: Vec<&str> = component
+                    .licenses
+                    .iter()
+                    .map(|license| license.name.as_str())
+                    .collect();
+                (
+                    NOASSERTION.to_string(),
+                    Some(format!("Declared in the pom as: {}", names.join(", "))),
+                )
+            };
+        packages.push(SpdxPackage {
+            name: format!("{}:{}", component.group_id, component.artifact_id),
+            spdx_id: id.clone(),
+            version_info: component.version.clone(),
+            download_location: component
+                .download_url
+                .clone()
+                .unwrap_or(NOASSERTION.to_string()),
+            files_analyzed: false,
+            checksums: component
+                .sha1
+                .iter()
+                .map(|sha1| SpdxChecksum {
+                    algorithm: "SHA1",
+                    checksum_value: sha1.clone(),
+                })
+                .collect(),
+            license_concluded: NOASSERTION,
+            license_declared,
+            license_comments,
+            primary_package_purpose: Some("LIBRARY"),
+            external_refs: vec![SpdxExternalRef {
+                reference_category: "PACKAGE-MANAGER",
+                reference_type: "purl",
+                reference_locator: component.purl(),
+            }],
+            comment: Some(format!("scope {}", component.scope)),
+        });
+        relationships.extend(
+            component
+                .dependencies
+                .iter()
+                .filter_map(|dep| ids.get(dep))
+                .map(|dep| SpdxRelationship {
+                    spdx_element_id: id.clone(),
+                    relationship_type: "DEPENDS_ON",
+                    related_spdx_element: dep.clone(),
+                }),
+        );
+        // test dependencies and processors are not part of the app
+        let of_app = match component.section {
+            Some(Section::Test) => Some("TEST_DEPENDENCY_OF"),
+            Some(Section::Processor) => Some("BUILD_DEPENDENCY_OF"),
+            _ => None,
+        };
+        if let Some(relationship_type) = of_app {
+            relationships.push(SpdxRelationship {
+                spdx_element_id: id,
+                relationship_type,
+                related_spdx_element: app_id.clone(),
+            });
+        }
+    }
+
+    let document = Spdx {
+        spdx_version: "SPDX-2.3",
+        data_license: "CC0-1.0",
+        spdx_id: "SPDXRef-DOCUMENT",
+        name: format!("{}-{}", app.name, app.version),
+        document_namespace: namespace,
+        creation_info: SpdxCreationInfo {
+            created: timestamp.to_string(),
+            creators: vec![format!("Tool: labt-{}", LABT_VERSION)],
+        },
+        packages,
+        relationships,
+    };
+    serde_json::to_string_pretty(&document).unwrap_or_default()
+}
+
+/// The creation time of the SBOM. SOURCE_DATE_EPOCH is used if set so that the
+/// SBOM of a build can be reproduced
+fn timestamp() -> String {
+    let secs = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|now| now.as_secs())
+                .unwrap_or_default()
+        });
+    super::resolve::format_utc(secs)
+}
+
+impl Submodule for Sbom {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let config = get_config()?;
+        let lock =
+            load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+
+        let sections = [
+            (&lock.resolved, Section::Project),
+            (&lock.test, Section::Test),
+            (&lock.processors, Section::Processor),
+        ];
+        let components: Vec<Component> = sections
+            .iter()
+            .flat_map(|(deps, section)| deps.iter().map(|dep| Component::from_lock(dep, *section)))
+            .collect();
+        let app = Application::from_config(&config, &components)?;
+
+        let sbom = match self.args.format {
+            SbomFormat::Cyclonedx => cyclonedx(&app, &components, &timestamp()),
+            SbomFormat::Spdx => spdx(&app, &components, &timestamp()),
+        };
+        match &self.args.output {
+            Some(output) => {
+                let output = get_project_root()
+                    .context("Failed to get project root directory")?
+                    .join(output);
+                std::fs::write(&output, sbom)
+                    .context(format!("Failed to write SBOM to {:?}", output))?;
+                info!(target: "sbom", "Wrote SBOM of {} components to {:?}", components.len(), output);
+            }
+            None => println!("{sbom}"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn sbom_cyclonedx_and_spdx() {
+    let core = Component {
+        group_id: String::from("androidx.core"),
+        artifact_id: String::from("core"),
+        version: String::from("1.12.0"),
+        packaging: String::from("aar"),
+        section: Some(Section::Project),
+        download_url: Some(String::from(
+            "https://maven.google.com/androidx/core/core/1.12.0/core-1.12.0.aar",
+        )),
+        sha1: Some(String::from("da39a3ee5e6b4b0d3255bfef95601890afd80709")),
+        licenses: vec![License {
+            name: String::from("The Apache Software License, Version 2.0"),
+            url: Some(String::from(
+                "http://www.apache.org/licenses/LICENSE-2.0.txt",
+            )),
+        }],
+        dependencies: vec![String::from("androidx.annotation:annotation:1.6.0")],
+        ..Default::default()
+    };
+    let annotation = Component {
+        group_id: String::from("androidx.annotation"),
+        artifact_id: String::from("annotation"),
+        version: String::from("1.6.0"),
+        packaging: String::from("jar"),
+        section: Some(Section::Project),
+        licenses: vec![License {
+            name: String::from("Custom license"),
+            url: None,
+        }],
+        ..Default::default()
+    };
+    let junit = Component {
+        group_id: String::from("junit"),
+        artifact_id: String::from("junit"),
+        version: String::from("4.13.2"),
+        packaging: String::from("jar"),
+        scope: Scope::TEST,
+        section: Some(Section::Test),
+        ..Default::default()
+    };
+    let components = vec![core, annotation, junit];
+    let app = Application {
+        name: String::from("demo"),
+        package: String::from("com.example.demo"),
+        version: String::from("1.0"),
+        dependencies: vec![String::from("androidx.core:core:1.12.0")],
+    };
+    assert_eq!(
+        components[0].purl(),
+        "pkg:maven/androidx.core/core@1.12.0?type=aar"
+    );
+
+    let bom: serde_json::Value =
+        serde_json::from_str(&cyclonedx(&app, &components, "2024-05-01T10:15:30Z")).unwrap();
+    assert_eq!(bom["bomFormat"], "CycloneDX");
+    assert_eq!(bom["metadata"]["component"]["name"], "demo");
+    let core = &bom["components"][0];
+    assert_eq!(core["scope"], "required");
+    assert_eq!(core["hashes"][0]["alg"], "SHA-1");
+    assert_eq!(core["licenses"][0]["license"]["id"], "Apache-2.0");
+    assert_eq!(core["externalReferences"][0]["type"], "distribution");
+    assert_eq!(
+        bom["components"][1]["licenses"][0]["license"]["name"],
+        "Custom license"
+    );
+    assert_eq!(bom["components"][2]["scope"], "excluded");
+    assert_eq!(
+        bom["dependencies"][0]["dependsOn"][0],
+        "pkg:maven/androidx.core/core@1.12.0?type=aar"
+    );
+    assert_eq!(
+        bom["dependencies"][1]["dependsOn"][0],
+        "pkg:maven/androidx.annotation/annotation@1.6.0"
+    );
+
+    let document: serde_json::Value =
+        serde_json::from_str(&spdx(&app, &components, "2024-05-01T10:15:30Z")).unwrap();
+    assert_eq!(document["spdxVersion"], "SPDX-2.3");
+    let core = &document["packages"][1];
+    assert_eq!(core["SPDXID"], "SPDXRef-Package-androidx.core-core-1.12.0");
+    assert_eq!(core["licenseDeclared"], "Apache-2.0");
+    assert_eq!(core["checksums"][0]["algorithm"], "SHA1");
+    assert_eq!(document["packages"][2]["licenseDeclared"], "NOASSERTION");
+    assert_eq!(document["packages"][3]["downloadLocation"], "NOASSERTION");
+    let relationships: Vec<String> = document["relationships"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            format!(
+                "{} {} {}",
+                r["spdxElementId"].as_str().unwrap(),
+                r["relationshipType"].as_str().unwrap(),
+                r["relatedSpdxElement"].as_str().unwrap()
+            )
+        })
+        .collect();
+    assert_eq!(
+        relationships,
+        vec![
+            "SPDXRef-DOCUMENT DESCRIBES SPDXRef-Package-com.example.demo",
+            "SPDXRef-Package-com.example.demo DEPENDS_ON SPDXRef-Package-androidx.core-core-1.12.0",
+            "SPDXRef-Package-androidx.core-core-1.12.0 DEPENDS_ON SPDXRef-Package-androidx.annotation-annotation-1.6.0",
+            "SPDXRef-Package-junit-junit-4.13.2 TEST_DEPENDENCY_OF SPDXRef-Package-com.example.demo",
+        ]
+    );
+}
@@ -6,7 +6,10 @@ use std::{
     io::{self, BufReader, BufWriter, Read, Write},
     path::{Path, PathBuf},
     process,
-    sync::{atomic::AtomicBool, Arc},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
     time::Duration,
 };
 
@@ -18,23 +21,33 @@ use futures_util::StreamExt;
 use indicatif::{ProgressBar, ProgressStyle};
 use log::{error, info, warn};
 use reqwest::Url;
+use serde::Serialize;
 use sha1::{Digest, Sha1};
 use toml_edit::{value, Document};
-use zip::ZipArchive;
 
 use crate::{
-    config::repository::{
-        parse_repository_xml, Archive, BitSizeType, ChannelType, RemotePackage, RepositoryXml,
-        Revision,
+    archive::{extract_zip, BarProgress},
+    caching::file_sha256,
+    config::{
+        global::{async_client_builder, client_builder},
+        repository::{
+            parse_repository_xml, Archive, BitSizeType, ChannelType, RemotePackage, RepositoryXml,
+            Revision,
+        },
     },
+    events::{emit, subscribe, Event},
     get_home,
+    http_recording::SendRecorded,
+    output::{is_json_output, print_json},
+    paths::sanitize_component,
+    session::Session,
     submodules::sdkmanager::{installed_list::SDK_PATH_ERR_STRING, ToId},
     tui::{
         self,
         sdkmanager::{PendingAccepts, PendingAction, PendingActions, SdkManager},
         Tui,
     },
-    MULTI_PROGRESS_BAR, USER_AGENT,
+    MULTI_PROGRESS_BAR,
 };
 
 // consts
@@ -43,7 +56,8 @@ pub const GOOGLE_REPO_NAME_STR: &str = "google";
 pub const DEFAULT_RESOURCES_URL: &str =
     "https://dl.google.com/android/repository/repository2-1.xml";
 pub const SDKMANAGER_TARGET: &str = "sdkmanager";
-const LOCK_FILE: &str = ".lock";
+/// Marks an sdk package directory an install is writing to
+const INSTALL_LOCK_FILE: &str = ".lock";
 
 pub const FAILED_TO_PARSE_SDK_STR: &str = "Failed to parse sdk repository config from cache. try --update-repository-list to force update config.";
 
@@ -99,6 +113,17 @@ pub struct ListArgs {
     quiet: bool,
 }
 
+/// A package in the json output of labt sdk list
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ListedPackage {
+    pub path: String,
+    pub version: String,
+    pub display_name: String,
+    pub channel: String,
+    pub obsolete: bool,
+    pub installed: bool,
+}
+
 #[derive(Clone, Args)]
 pub struct InstallArgs {
     /// The repository name
@@ -166,7 +191,7 @@ impl SdkLock {
     pub fn obtain(path: &Path, pid: u32) -> io::Result<Self> {
         create_dir_all(path)?;
 
-        let lock_file = path.join(LOCK_FILE);
+        let lock_file = path.join(INSTALL_LOCK_FILE);
 
         if lock_file.exists() {
             let other_pid = fs::read_to_string(&lock_file)?;
@@ -188,7 +213,7 @@ impl SdkLock {
     pub fn obtain_wait(path: &Path, pid: u32) -> io::Result<Self> {
         create_dir_all(path)?;
 
-        let lock_file = path.join(LOCK_FILE);
+        let lock_file = path.join(INSTALL_LOCK_FILE);
         Ok(Self {
             path: lock_file,
             pid,
@@ -316,6 +341,22 @@ impl Sdk {
         filtered.set_channel(args.channel.clone());
         filtered.apply();
 
+        if is_json_output() {
+            let installed_hash = installed.get_hash_map();
+            let packages: Vec<ListedPackage> = filtered
+                .get_packages()
+                .iter()
+                .map(|package| ListedPackage {
+                    path: package.get_path().clone(),
+                    version: package.get_revision().to_string(),
+                    display_name: package.get_display_name().clone(),
+                    channel: package.get_channel().to_string(),
+                    obsolete: package.is_obsolete(),
+                    installed: installed_hash.contains_key(&package.to_id()),
+                })
+                .collect();
+            return print_json(&packages);
+        }
         if args.no_interactive {
             let pipe = style("|").dim();
             for package in filtered.get_packages() {
@@ -366,7 +407,7 @@ impl Sdk {
     ) -> anyhow::Result<()> {
         let mut uninstaller = Uninstaller::new(quiet);
         let (host_os, bits) = Self::get_host_os_and_bits(host_os.to_owned())?;
-        let running = Arc::new(AtomicBool::new(true));
+        let running = crate::interrupt::running();
         let mut installer = Installer::new(url, bits, host_os, quiet, running);
 
         for (package, action) in actions.drain() {
@@ -399,10 +440,16 @@ impl Sdk {
         }
         installed_list.save_to_file()?; // save after uninstall since next install process may fail leaving phantom packages
 
+        // the installed packages are reported from their events
+        let total = installer.install_targets.len();
+        let done = AtomicUsize::new(0);
+        let _progress = subscribe(move |event| {
+            if let Event::SdkInstallFinished { package, version } = event {
+                let done = done.fetch_add(1, Ordering::SeqCst) + 1;
+                log::info!(target: SDKMANAGER_TARGET, "Installed {} v{} [{} of {}]", package, version, done, total);
+            }
+        });
         installer.install()?;
-        if !installer.install_targets.is_empty() {
-            log::info!(target: SDKMANAGER_TARGET, "Installed [{} of {}] packages", installer.complete_tasks.len(), installer.install_targets.len());
-        }
         for complete in installer.complete_tasks {
             installed_list.add_installed_package(complete);
         }
@@ -511,7 +558,7 @@ impl Sdk {
             installed.accept_license(&self.name, package.get_uses_license().clone());
         }
 
-        let running = Arc::new(AtomicBool::new(true));
+        let running = crate::interrupt::running();
         let mut installer = Installer::new(url, bits, host_os, args.quiet, running);
         installer.add_package(name, package.clone())?;
 
@@ -546,16 +593,13 @@ impl Sdk {
         // let repo = if !toml.exists() || self.update {
         info!(target: SDKMANAGER_TARGET, "Fetching {} repository xml from {}", name, url.as_str());
         let prog = MULTI_PROGRESS_BAR.add(ProgressBar::new_spinner());
-        let client = reqwest::blocking::Client::builder()
-            .user_agent(crate::USER_AGENT)
-            .build()
-            .context(format!(
-                "Failed to create http client to fetch {}",
-                url.as_str()
-            ))?;
+        let client = client_builder()?.build().context(format!(
+            "Failed to create http client to fetch {}",
+            url.as_str()
+        ))?;
         let resp = client
             .get(url.clone())
-            .send()
+            .send_recorded()
             .context(format!("Failed to complete request to {}", url.as_str()))?;
         if let Some(size) = resp.content_length() {
             prog.set_style(
@@ -614,6 +658,7 @@ pub mod toml_strings {
     pub const REMOTE_PACKAGE: &str = "remote_package";
     pub const CONFIG_FILE: &str = "repository.toml";
     pub const DIRECTORY: &str = "directory";
+    pub const SHA256: &str = "sha256";
 }
 
 // Entry point
@@ -688,6 +733,30 @@ pub fn get_sdk_path() -> anyhow::Result<PathBuf> {
     Ok(sdk)
 }
 
+/// Returns the directory of a package in the sdk folder. The ; separated
+/// package path e.g. build-tools;34.0.0 becomes build-tools/34.0.0
+pub fn package_dir(repository_name: &str, package_path: &str) -> anyhow::Result<PathBuf> {
+    Ok(package_dir_in(
+        get_sdk_path()?,
+        repository_name,
+        package_path,
+    ))
+}
+
+/// Returns the directory of a package in sdk. Each segment is sanitized as
+/// repositories can use any string for their paths and versions
+pub fn package_dir_in(sdk: PathBuf, repository_name: &str, package_path: &str) -> PathBuf {
+    let mut dir = sdk;
+    dir.push(sanitize_component(repository_name).as_ref());
+    dir.extend(
+        package_path
+            .split(';')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| sanitize_component(segment).into_owned()),
+    );
+    dir
+}
+
 pub fn write_repository_config(repo: &RepositoryXml, path: &Path) -> anyhow::Result<()> {
     use toml_strings::*;
     // Check for sdk folder
@@ -935,63 +1004,6 @@ pub fn parse_repository_toml(path: &Path) -> anyhow::Result<RepositoryXml> {
     Ok(repo)
 }
 
-pub fn extract_with_progress<P: AsRef<Path>>(
-    archive: &mut ZipArchive<File>,
-    directory: P,
-    prog: &indicatif::ProgressBar,
-) -> anyhow::Result<()> {
-    prog.set_length(archive.len() as u64);
-
-    let make_writable_dir_all = |outpath: &dyn AsRef<Path>| -> Result<(), zip::result::ZipError> {
-        create_dir_all(outpath.as_ref())?;
-        #[cfg(unix)]
-        {
-            // Dirs must be writable until all normal files are extracted
-            use std::os::unix::fs::PermissionsExt;
-            std::fs::set_permissions(
-                outpath.as_ref(),
-                std::fs::Permissions::from_mode(
-                    0o700 | std::fs::metadata(outpath.as_ref())?.permissions().mode(),
-                ),
-            )?;
-        }
-        Ok(())
-    };
-
-    for i in 0..archive.len() {
-        prog.inc(1);
-        let mut file = archive.by_index(i)?;
-
-        let outpath = match file.enclosed_name() {
-            Some(path) => directory.as_ref().join(path),
-            None => continue,
-        };
-
-        if file.is_dir() {
-            make_writable_dir_all(&outpath)?;
-            continue;
-        }
-
-        if let Some(p) = outpath.parent() {
-            if !p.exists() {
-                make_writable_dir_all(&p)?;
-            }
-        }
-        let mut outfile = fs::File::create(&outpath)?;
-        io::copy(&mut file, &mut outfile)?;
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-
-            if let Some(mode) = file.unix_mode() {
-                fs::set_permissions(&outpath, fs::Permissions::from_mode(mode)).unwrap();
-            }
-        }
-    }
-    prog.finish_and_clear();
-    Ok(())
-}
-
 /// Obtains a lock on the target path and deletes the package path
 struct Uninstaller {
     packages: Vec<InstalledPackage>,
@@ -1018,7 +1030,7 @@ impl Uninstaller {
         }
         let mut sdk =
             get_sdk_path().context(super::sdkmanager::installed_list::SDK_PATH_ERR_STRING)?;
-        sdk.push(&package.repository_name);
+        sdk.push(sanitize_component(&package.repository_name).as_ref());
 
         if !dir.starts_with(sdk) {
             // aint touching that, not ours
@@ -1028,7 +1040,7 @@ impl Uninstaller {
         let segments = package.path.split(';').rev().skip(1);
         for segment in segments {
             if let Some(p) = dir.file_name() {
-                if segment.eq(p) {
+                if sanitize_component(segment).as_ref().eq(p) {
                     // short circuit if path is not empty
                     if dir.is_dir() {
                         let entries = fs::read_dir(&dir)
@@ -1069,16 +1081,13 @@ impl Uninstaller {
         let dir = if let Some(dir) = &package.directory {
             dir.clone()
         } else {
-            let path: PathBuf = package.path.split(';').collect();
-            let mut sdk = get_sdk_path()?;
-            sdk.push(&package.repository_name);
-            sdk.join(path)
+            package_dir(&package.repository_name, &package.path)?
         };
 
         if !dir.exists() {
             return Ok(());
         }
-        let lock = dir.join(LOCK_FILE);
+        let lock = dir.join(INSTALL_LOCK_FILE);
         if lock.exists() && !ignore_lock {
             let pid = process::id();
             let other_pid = fs::read_to_string(&lock)?;
@@ -1256,13 +1265,10 @@ impl Installer {
         repository_name: &str,
         package: RemotePackage,
     ) -> anyhow::Result<()> {
-        let path: PathBuf = package.get_path().split(';').collect();
-        let mut sdk = get_sdk_path()?;
-        sdk.push(repository_name);
         let target = InstallerTarget {
             bits: self.bits,
             host_os: self.host_os.clone(),
-            target_path: sdk.join(path),
+            target_path: package_dir(repository_name, package.get_path())?,
             package,
             download_url: Arc::clone(&self.default_url),
             repository_name: repository_name.to_string(),
@@ -1368,7 +1374,7 @@ impl Installer {
         }
         let req = client.get(url.clone());
         let res = req
-            .send()
+            .send_recorded()
             .map_err(|err| InstallerError::FailedToSendRequest {
                 url: url.to_string(),
                 source: anyhow!(err),
@@ -1453,6 +1459,9 @@ impl Installer {
                 calculated: checksum,
             });
         }
+        // recorded so that what was installed from can be traced later
+        let sha256 =
+            file_sha256(&output).map_err(|err| InstallerError::ChecksumIOError { source: err })?;
 
         // unzip
         let file = File::open(&output).map_err(|err| {
@@ -1462,15 +1471,8 @@ impl Installer {
         let mut archive =
             zip::ZipArchive::new(file).map_err(|err| InstallerError::Other(anyhow!(err)))?;
         if !self.quiet {
-            let prog = indicatif::ProgressBar::new(archive.len() as u64).with_style(
-                ProgressStyle::with_template(
-                    "{spinner}[{percent}%] {bar:40} {per_sec} {duration} {wide_msg}",
-                )
-                .unwrap(),
-            );
-            let prog = MULTI_PROGRESS_BAR.add(prog);
-            prog.set_message(format!("Extracting {}", target.package.get_path()));
-            extract_with_progress(&mut archive, target_path, &prog).context(format!(
+            let progress = BarProgress::new(format!("Extracting {}", target.package.get_path()));
+            extract_zip(&mut archive, target_path, &progress).context(format!(
                 "Failed to unzip package archive to ({:?})",
                 target_path
             ))?;
@@ -1496,6 +1498,7 @@ impl Installer {
             directory: Some(target_path.to_path_buf()),
             channel: package.get_channel().to_owned(),
             repository_name: target.repository_name.to_string(),
+            sha256: Some(sha256),
         })
     }
 
@@ -1528,7 +1531,7 @@ impl Installer {
         }
         let req = client.get(url.clone());
         let res = req
-            .send()
+            .send_recorded()
             .await
             .map_err(|err| InstallerError::FailedToSendRequest {
                 url: url.to_string(),
@@ -1605,7 +1608,7 @@ impl Installer {
         let archive = archive.clone();
 
         // unzip
-        tokio::task::spawn_blocking(move || {
+        let sha256 = tokio::task::spawn_blocking(move || {
             let prog = prog;
             // calculate checksum
             let checksum = Self::calculate_checksum(&output_file, prog)?;
@@ -1613,6 +1616,7 @@ impl Installer {
             if !checksum.eq(archive.get_checksum()) {
                 return Err(InstallerError::ChecksumMismatch { path: package_path_id, expected: archive.get_checksum().to_string(), calculated: checksum });
             }
+            let sha256 = file_sha256(&output_file).map_err(|err| InstallerError::ChecksumIOError { source: err })?;
 
             // unzip file
             let file = File::open(&output_file).context("Failed to open download tmp file")?;
@@ -1621,15 +1625,8 @@ impl Installer {
                 &output_file, package_path_name
             ))?;
             if !quiet {
-                let prog = indicatif::ProgressBar::new(archive.len() as u64).with_style(
-                    ProgressStyle::with_template(
-                        "{spinner}[{percent}%] {bar:40} {per_sec} {duration} {wide_msg}",
-                    )
-                    .unwrap(),
-                );
-                let prog = MULTI_PROGRESS_BAR.add(prog);
-                prog.set_message(format!("Extracting {}", &package_path_name));
-                extract_with_progress(&mut archive, &extract_path, &prog).context(format!(
+                let progress = BarProgress::new(format!("Extracting {}", &package_path_name));
+                extract_zip(&mut archive, &extract_path, &progress).context(format!(
                     "Failed to unzip package archive to ({:?})",
                     extract_path
                 ))?;
@@ -1640,7 +1637,7 @@ impl Installer {
                 ))?;
             }
             info!(target: SDKMANAGER_TARGET, "Extracted {} entries to ({:?}).", archive.len(), extract_path);
-            Ok::<_, InstallerError>(())
+            Ok::<_, InstallerError>(sha256)
         }).await.map_err(|err| {
                 InstallerError::UnzipError(anyhow!(err))
             })??;
@@ -1660,18 +1657,19 @@ impl Installer {
             directory: Some(target_path.to_path_buf()),
             channel: package.get_channel().to_owned(),
             repository_name: target.repository_name.to_string(),
+            sha256: Some(sha256),
         })
     }
     /// spawns a new tokio instance to do all the installs
     fn install_async(&mut self) -> anyhow::Result<()> {
+        let session = Session::current();
         let runtime = tokio::runtime::Builder::new_multi_thread()
             .thread_name("package_installer")
+            .on_thread_start(move || session.attach())
             .enable_all()
             .build()?;
 
-        let client = reqwest::ClientBuilder::new()
-            .user_agent(USER_AGENT)
-            .build()?;
+        let client = async_client_builder()?.build()?;
         let quiet = self.quiet;
 
         let results = runtime.block_on(async {
@@ -1724,6 +1722,7 @@ impl Installer {
                             channel: target.package.get_channel().clone(),
                             url: String::new(),
                             directory: Some(target.target_path.clone()),
+                            sha256: None,
                         },
                         self.quiet,
                         true,
@@ -1738,9 +1737,7 @@ impl Installer {
         Ok(())
     }
     fn install_sync(&mut self) -> anyhow::Result<()> {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .user_agent(USER_AGENT)
-            .build()?;
+        let client = client_builder()?.build()?;
         for target in &self.install_targets {
             let installed_package = match self
                 .download_package_blocking(&client, target, self.running.clone())
@@ -1757,6 +1754,7 @@ impl Installer {
                             channel: target.package.get_channel().clone(),
                             url: String::new(),
                             directory: Some(target.target_path.clone()),
+                            sha256: None,
                         },
                         self.quiet,
                         true,
@@ -1775,18 +1773,46 @@ impl Installer {
 
     /// Starts the installation process
     pub fn install(&mut self) -> anyhow::Result<()> {
-        let r = self.running.clone();
-        ctrlc::set_handler(move || {
-            r.store(false, std::sync::atomic::Ordering::SeqCst);
-        })
-        .expect("Error setting Ctrl-C handler");
-
+        for target in &self.install_targets {
+            emit(Event::SdkInstallStarted {
+                package: target.package.get_path().to_string(),
+                version: target.package.get_revision().to_string(),
+            });
+        }
         if self.install_targets.len() > 1 {
             self.install_async()?;
         } else {
             self.install_sync()?;
         }
+        for package in &self.complete_tasks {
+            emit(Event::SdkInstallFinished {
+                package: package.path.clone(),
+                version: package.version.to_string(),
+            });
+        }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn package_dir_from_package_path() {
+    let sdk = PathBuf::from("sdk");
+    assert_eq!(
+        package_dir_in(sdk.clone(), GOOGLE_REPO_NAME_STR, "build-tools;34.0.0"),
+        sdk.join(GOOGLE_REPO_NAME_STR)
+            .join("build-tools")
+            .join("34.0.0")
+    );
+    assert_eq!(
+        package_dir_in(sdk.clone(), "vendor", "cmake;3.22.1 rc1.;"),
+        sdk.join("vendor").join("cmake").join("3.22.1 rc1_")
+    );
+    assert_eq!(
+        package_dir_in(sdk.clone(), "vendor", "extras;../../bin"),
+        sdk.join("vendor").join("extras").join(".._.._bin")
+    );
+}
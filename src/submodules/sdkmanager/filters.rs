@@ -106,17 +106,13 @@ impl<'installer, 'repo> FilteredPackages<'installer, 'repo> {
                 .filter(|p| {
                     for filter in self.single_filters.iter() {
                         match filter {
-                            SdkFilters::Installed => {
-                                // short circuit for installed
-                                if !installed_hash.contains_key(&p.to_id()) {
-                                    return false;
-                                }
+                            // short circuit for installed
+                            SdkFilters::Installed if !installed_hash.contains_key(&p.to_id()) => {
+                                return false;
                             }
-                            SdkFilters::Obsolete(obsolete) => {
-                                // short circuit for obsolete
-                                if p.is_obsolete() != *obsolete {
-                                    return false;
-                                }
+                            // short circuit for obsolete
+                            SdkFilters::Obsolete(obsolete) if p.is_obsolete() != *obsolete => {
+                                return false;
                             }
                             _ => {}
                         }
@@ -6,6 +6,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use anyhow::{bail, Context};
+use quick_xml::{events::Event, Reader};
 use toml_edit::{value, ArrayOfTables, Document, Table};
 
 use crate::config::repository::{ChannelType, Revision};
@@ -13,11 +14,15 @@ use crate::submodules::sdk::{get_sdk_path, toml_strings};
 
 use super::{ToId, ToIdLong};
 
-const INSTALLED_LIST: &str = "installed.toml";
+pub const INSTALLED_LIST: &str = "installed.toml";
 const INSTALLED_LIST_OPEN_ERR: &str = "Failed to open sdk installed.toml";
 const PACKAGE: &str = "package";
 const ACCEPTED_LICENSES: &str = "accepted_licenses";
 pub const SDK_PATH_ERR_STRING: &str = "Failed to get android sdk path";
+/// The description sdkmanager writes into the directory of a package it installs
+pub const PACKAGE_XML: &str = "package.xml";
+/// The description in the archive of a package
+const SOURCE_PROPERTIES: &str = "source.properties";
 
 #[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
 pub struct InstalledPackage {
@@ -27,6 +32,9 @@ pub struct InstalledPackage {
     pub channel: ChannelType,
     pub url: String,
     pub directory: Option<PathBuf>,
+    /// sha256 of the archive the package was installed from. None for packages
+    /// installed before it was recorded
+    pub sha256: Option<String>,
 }
 impl InstalledPackage {
     pub fn new(
@@ -42,6 +50,7 @@ impl InstalledPackage {
             channel,
             url: String::default(),
             directory: None,
+            sha256: None,
         }
     }
 }
@@ -451,6 +460,21 @@ impl FromStr for InstalledList {
                         );
                     }
 
+                    // parse the archive digest
+                    if let Some(sha256) = package.get(SHA256) {
+                        p.sha256 = Some(
+                            sha256
+                                .as_str()
+                                .ok_or_else(|| {
+                                    InstalledListErr::new(
+                                        InstalledListErrKind::ToStringErr(SHA256, position),
+                                        Some(INSTALLED_LIST.to_string()),
+                                    )
+                                })?
+                                .to_string(),
+                        );
+                    }
+
                     package_list.push(p);
                 }
             }
@@ -512,6 +536,9 @@ impl Display for InstalledList {
                 );
             }
             table.insert(toml_strings::URL, value(&package.url));
+            if let Some(sha256) = &package.sha256 {
+                table.insert(toml_strings::SHA256, value(sha256));
+            }
 
             packages.push(table);
         }
@@ -548,6 +575,9 @@ pub fn write_installed_list(
             );
         }
         table.insert(toml_strings::URL, value(package.url));
+        if let Some(sha256) = package.sha256 {
+            table.insert(toml_strings::SHA256, value(sha256));
+        }
 
         packages.push(table);
     }
@@ -621,10 +651,92 @@ pub fn update_installed_list(
     Ok(installed)
 }
 
+/// Reads the `<revision>` of the localPackage in a package.xml
+fn package_xml_revision(xml: &str) -> anyhow::Result<Option<Revision>> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut in_revision = false;
+    let mut field = None;
+    let mut revision = Revision::default();
+    loop {
+        match reader.read_event().context("Failed to parse package.xml")? {
+            Event::Start(tag) => match tag.local_name().into_inner() {
+                b"revision" => in_revision = true,
+                name if in_revision => field = Some(name.to_vec()),
+                _ => {}
+            },
+            Event::Text(text) if in_revision => {
+                let number: u32 = text
+                    .unescape()?
+                    .trim()
+                    .parse()
+                    .context("Invalid revision in package.xml")?;
+                match field.as_deref() {
+                    Some(b"major") => revision.major = number,
+                    Some(b"minor") => revision.minor = number,
+                    Some(b"micro") => revision.micro = number,
+                    Some(b"preview") => revision.preview = number,
+                    _ => {}
+                }
+            }
+            Event::End(tag) if tag.local_name().into_inner() == b"revision" => {
+                return Ok(Some(revision));
+            }
+            Event::End(_) => field = None,
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Reads Pkg.Revision of a source.properties e.g. 34.0.0 or 35.0.0 rc1
+fn source_properties_revision(properties: &str) -> anyhow::Result<Option<Revision>> {
+    let Some(value) = properties.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "Pkg.Revision").then_some(value.trim())
+    }) else {
+        return Ok(None);
+    };
+    let mut parts = value.split_whitespace();
+    let mut revision = Revision::from_str(parts.next().unwrap_or_default())
+        .context(format!("Invalid Pkg.Revision {value}"))?;
+    if let Some(preview) = parts.next().and_then(|rc| rc.strip_prefix("rc")) {
+        revision.preview = preview
+            .parse()
+            .context(format!("Invalid Pkg.Revision {value}"))?;
+    }
+    Ok(Some(revision))
+}
+
+/// The revision the files of an installed package declare, from the package.xml
+/// sdkmanager writes or else the source.properties of the archive. None if the
+/// directory has neither
+///
+/// # Errors
+///
+/// Returns an error if the file cannot be read or has no valid revision
+pub fn installed_revision(dir: &Path) -> anyhow::Result<Option<Revision>> {
+    let xml = dir.join(PACKAGE_XML);
+    if xml.exists() {
+        let contents = fs::read_to_string(&xml).context(format!("Failed to read {:?}", xml))?;
+        return package_xml_revision(&contents).context(format!("Failed to read {:?}", xml));
+    }
+    let properties = dir.join(SOURCE_PROPERTIES);
+    if properties.exists() {
+        let contents =
+            fs::read_to_string(&properties).context(format!("Failed to read {:?}", properties))?;
+        return source_properties_revision(&contents)
+            .context(format!("Failed to read {:?}", properties));
+    }
+    Ok(None)
+}
+
 #[cfg(test)]
 mod installed_list_test {
     use std::collections::HashSet;
 
+    use std::str::FromStr;
+
     use crate::{
         config::repository::{ChannelType, Revision},
         submodules::sdkmanager::{installed_list::RepositoryInfo, ToId},
@@ -642,6 +754,7 @@ mod installed_list_test {
             channel: ChannelType::Stable,
             url: "gitlab.com".to_string(),
             directory: None,
+            sha256: None,
         };
 
         let mut list = InstalledList::new();
@@ -659,6 +772,7 @@ mod installed_list_test {
             channel: ChannelType::Stable,
             url: "gitlab.com".to_string(),
             directory: None,
+            sha256: None,
         };
         let package_2: InstalledPackage = InstalledPackage {
             repository_name: "google".to_string(),
@@ -667,6 +781,7 @@ mod installed_list_test {
             channel: ChannelType::Stable,
             url: "gitlab.com".to_string(),
             directory: None,
+            sha256: None,
         };
 
         let mut list = InstalledList::new();
@@ -681,6 +796,7 @@ mod installed_list_test {
             channel: ChannelType::Stable,
             url: "gitlab.com".to_string(),
             directory: None,
+            sha256: None,
         };
 
         list.insert_installed_package(package_3);
@@ -712,6 +828,7 @@ path = "extras;google;auto"
 version = "2.0.0.0"
 channel = "stable"
 url = "http://example.com"
+sha256 = "5d1c"
 "#;
 
         let result: InstalledList = toml.parse().unwrap();
@@ -725,6 +842,7 @@ url = "http://example.com"
             channel: ChannelType::Stable,
             url: "http://example.com".to_string(),
             directory: None,
+            sha256: Some("5d1c".to_string()),
         };
 
         assert_eq!(value.to_id(), package.to_id());
@@ -763,6 +881,7 @@ url = "http://example.com"
             channel: ChannelType::Stable,
             url: "http://example.com".to_string(),
             directory: None,
+            sha256: Some("5d1c".to_string()),
         };
 
         list.add_installed_package(package.clone());
@@ -780,7 +899,44 @@ path = "extras;google;auto"
 version = "2.0.0.0"
 channel = "stable"
 url = "http://example.com"
+sha256 = "5d1c"
 "#;
         assert_eq!(list.to_string(), toml.trim_start());
     }
+
+    #[test]
+    fn installed_revisions() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>
+<ns2:repository xmlns:ns2="http://schemas.android.com/repository/android/common/02">
+<localPackage path="build-tools;34.0.0" obsolete="false">
+<type-details xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:type="ns5:genericDetailsType"/>
+<revision><major>34</major><minor>0</minor><micro>0</micro><preview>2</preview></revision>
+<display-name>Android SDK Build-Tools 34</display-name>
+<dependencies><dependency path="tools"><min-revision><major>26</major></min-revision></dependency></dependencies>
+</localPackage>
+</ns2:repository>"#;
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        assert_eq!(super::installed_revision(dir).unwrap(), None);
+        std::fs::write(
+            dir.join(super::SOURCE_PROPERTIES),
+            "Pkg.Desc = Android SDK Build-Tools 35 rc1\nPkg.Revision = 35.0.0 rc1\n",
+        )
+        .unwrap();
+        let mut expected = Revision::from_str("35.0.0").unwrap();
+        expected.preview = 1;
+        assert_eq!(super::installed_revision(dir).unwrap(), Some(expected));
+        // package.xml is preferred
+        std::fs::write(dir.join(super::PACKAGE_XML), xml).unwrap();
+        assert_eq!(
+            super::installed_revision(dir).unwrap(),
+            Some(Revision::from_str("34.0.0.2").unwrap())
+        );
+        std::fs::write(
+            dir.join(super::PACKAGE_XML),
+            "<revision><major>x</major></revision>",
+        )
+        .unwrap();
+        assert!(super::installed_revision(dir).is_err());
+    }
 }
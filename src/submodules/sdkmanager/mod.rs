@@ -1,5 +1,6 @@
 pub mod filters;
 pub mod installed_list;
+pub mod requirements;
 
 pub use installed_list::write_installed_list;
 pub use installed_list::InstalledPackage;
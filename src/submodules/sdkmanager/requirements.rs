@@ -0,0 +1,475 @@
+use std::{
+    fs::File,
+    io::{IsTerminal, Read, Seek},
+};
+
+use anyhow::{bail, Context};
+use dialoguer::Confirm;
+use log::{info, trace, warn};
+use reqwest::Url;
+use zip::ZipArchive;
+
+use crate::{
+    caching::Cache,
+    config::repository::{ChannelType, RemotePackage, RepositoryXml},
+    submodules::{
+        resolve::ProjectDep,
+        sdk::{
+            parse_repository_toml, toml_strings, Installer, Sdk, DEFAULT_URL,
+            FAILED_TO_PARSE_SDK_STR, GOOGLE_REPO_NAME_STR, SDKMANAGER_TARGET,
+        },
+    },
+};
+
+use super::installed_list::{InstalledList, InstalledPackage};
+
+/// The properties AGP adds to every AAR it builds with the minimum compileSdk
+/// of its consumers
+pub const AAR_METADATA: &str = "META-INF/com/android/build/gradle/aar-metadata.properties";
+const MIN_COMPILE_SDK: &str = "minCompileSdk";
+const PLATFORMS: &str = "platforms";
+const BUILD_TOOLS: &str = "build-tools";
+
+/// The compileSdk the dependencies need and the dependency asking for it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdkRequirement {
+    pub compile_sdk: u32,
+    pub required_by: String,
+}
+
+impl SdkRequirement {
+    /// The sdk package path of the platform e.g. platforms;android-34
+    pub fn platform_path(&self) -> String {
+        format!("{};android-{}", PLATFORMS, self.compile_sdk)
+    }
+}
+
+/// Reads minCompileSdk from the contents of an aar-metadata.properties
+pub fn parse_min_compile_sdk(properties: &str) -> Option<u32> {
+    properties.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        if key.trim() != MIN_COMPILE_SDK {
+            return None;
+        }
+        value.trim().parse().ok()
+    })
+}
+
+/// Returns the minCompileSdk of an AAR. None if it does not declare one
+///
+/// # Errors
+///
+/// Returns an error if the metadata cannot be read
+pub fn aar_min_compile_sdk<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+) -> anyhow::Result<Option<u32>> {
+    let mut entry = match archive.by_name(AAR_METADATA) {
+        Ok(entry) => entry,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(err).context(format!("Failed to open {}", AAR_METADATA)),
+    };
+    let mut properties = String::new();
+    entry
+        .read_to_string(&mut properties)
+        .context(format!("Failed to read {}", AAR_METADATA))?;
+    Ok(parse_min_compile_sdk(&properties))
+}
+
+/// Finds the highest minCompileSdk of the cached AARs in deps. Dependencies
+/// not yet downloaded are skipped.
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available or an AAR cannot be read
+pub fn required_compile_sdk(deps: &[ProjectDep]) -> anyhow::Result<Option<SdkRequirement>> {
+    let mut requirement: Option<SdkRequirement> = None;
+    for dep in deps.iter().filter(|dep| dep.packaging == "aar") {
+        crate::interrupt::check()?;
+        let mut cache = Cache::from(dep);
+        cache.use_labt_home()?;
+        let path = cache.get_path()?;
+        if !path.exists() {
+            continue;
+        }
+        let file = File::open(&path).context(format!("Failed to open {:?}", path))?;
+        let mut archive =
+            ZipArchive::new(file).context(format!("{:?} is not a valid archive", path))?;
+        let Some(compile_sdk) = aar_min_compile_sdk(&mut archive)
+            .context(format!("Failed to read the metadata of {:?}", path))?
+        else {
+            continue;
+        };
+        if requirement
+            .as_ref()
+            .is_none_or(|r| compile_sdk > r.compile_sdk)
+        {
+            requirement = Some(SdkRequirement {
+                compile_sdk,
+                required_by: format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version),
+            });
+        }
+    }
+    Ok(requirement)
+}
+
+/// The api level of a platform package path e.g. 34 for platforms;android-34
+fn platform_api(path: &str) -> Option<u32> {
+    path.strip_prefix(PLATFORMS)?
+        .strip_prefix(";android-")?
+        .parse()
+        .ok()
+}
+
+/// Returns the package paths, platforms or build-tools, with nothing installed
+/// new enough for requirement. Build tools are expected to match the major
+/// version of the compileSdk like AGP does.
+pub fn missing_packages(requirement: &SdkRequirement, installed: &InstalledList) -> Vec<String> {
+    let has_platform = installed
+        .packages
+        .iter()
+        .filter_map(|p| platform_api(&p.path))
+        .any(|api| api >= requirement.compile_sdk);
+    let has_build_tools = installed.packages.iter().any(|p| {
+        p.path.starts_with(&format!("{};", BUILD_TOOLS))
+            && p.version.major >= requirement.compile_sdk
+    });
+    let mut missing = Vec::new();
+    if !has_platform {
+        missing.push(requirement.platform_path());
+    }
+    if !has_build_tools {
+        missing.push(String::from(BUILD_TOOLS));
+    }
+    missing
+}
+
+/// Returns the newest installed platform and build-tools that satisfy requirement
+pub fn satisfying_packages<'a>(
+    requirement: &SdkRequirement,
+    installed: &'a InstalledList,
+) -> Vec<&'a InstalledPackage> {
+    let platform = installed
+        .packages
+        .iter()
+        .filter_map(|p| platform_api(&p.path).map(|api| (api, p)))
+        .filter(|(api, _)| *api >= requirement.compile_sdk)
+        .max_by_key(|(api, _)| *api)
+        .map(|(_, p)| p);
+    let build_tools = installed
+        .packages
+        .iter()
+        .filter(|p| {
+            p.path.starts_with(&format!("{};", BUILD_TOOLS))
+                && p.version.major >= requirement.compile_sdk
+        })
+        .max_by(|a, b| a.version.partial_cmp(&b.version).unwrap());
+    platform.into_iter().chain(build_tools).collect()
+}
+
+/// Picks the remote packages to install for the missing package paths. The
+/// latest stable build-tools of the compileSdk major version are preferred,
+/// then the oldest newer ones.
+pub fn select_packages(
+    requirement: &SdkRequirement,
+    missing: &[String],
+    repo: &RepositoryXml,
+) -> Vec<RemotePackage> {
+    let stable = || {
+        repo.get_remote_packages()
+            .iter()
+            .filter(|p| *p.get_channel() == ChannelType::Stable && !p.is_obsolete())
+    };
+    let mut selected = Vec::new();
+    for path in missing {
+        let package = if path == BUILD_TOOLS {
+            let build_tools = || {
+                stable().filter(|p| {
+                    p.get_path().starts_with(&format!("{};", BUILD_TOOLS))
+                        && p.get_revision().major >= requirement.compile_sdk
+                })
+            };
+            build_tools()
+                .filter(|p| p.get_revision().major == requirement.compile_sdk)
+                .max_by(|a, b| a.get_revision().partial_cmp(b.get_revision()).unwrap())
+                .or_else(|| {
+                    build_tools()
+                        .min_by(|a, b| a.get_revision().partial_cmp(b.get_revision()).unwrap())
+                })
+        } else {
+            stable()
+                .filter(|p| p.get_path() == path)
+                .max_by(|a, b| a.get_revision().partial_cmp(b.get_revision()).unwrap())
+        };
+        match package {
+            Some(package) => selected.push(package.clone()),
+            None => {
+                warn!(target: SDKMANAGER_TARGET, "No stable {} package for compileSdk {} in the {} sdk repository", path, requirement.compile_sdk, repo.get_name())
+            }
+        }
+    }
+    selected
+}
+
+/// Shows the license and asks to accept it. Returns false if it was declined
+fn prompt_license(
+    installed: &InstalledList,
+    license_id: &str,
+    package: &str,
+) -> anyhow::Result<bool> {
+    let path = installed
+        .repositories
+        .get(GOOGLE_REPO_NAME_STR)
+        .map(|repo| repo.path.join("licenses").join(license_id));
+    match path.map(std::fs::read_to_string) {
+        Some(Ok(license)) => println!("{license}"),
+        _ => {
+            warn!(target: SDKMANAGER_TARGET, "The text of license {} is not in the google sdk repository. Update it with labt sdk add google", license_id)
+        }
+    }
+    Ok(Confirm::new()
+        .with_prompt(format!("Accept license {license_id} for {package}?"))
+        .default(false)
+        .interact()?)
+}
+
+/// Installs packages from the google sdk repository. Licenses not accepted yet
+/// are shown and asked for, unless accept_licenses is set. Packages whose
+/// license is declined are not installed.
+fn install_packages(
+    packages: Vec<RemotePackage>,
+    installed: &mut InstalledList,
+    accept_licenses: bool,
+) -> anyhow::Result<()> {
+    let (host_os, bits) = Sdk::get_host_os_and_bits(None)?;
+    let running = crate::interrupt::running();
+    let mut installer = Installer::new(Url::parse(DEFAULT_URL)?, bits, host_os, false, running);
+    for package in packages {
+        let license = package.get_uses_license();
+        if !license.is_empty()
+            && installed.has_accepted(GOOGLE_REPO_NAME_STR, license) != Some(true)
+        {
+            if accept_licenses {
+                info!(target: SDKMANAGER_TARGET, "Accepting license {} for {} (--accept-licenses)", license, package.get_path());
+            } else if !prompt_license(installed, license, package.get_path())? {
+                warn!(target: SDKMANAGER_TARGET, "License {} was not accepted. Skipping {}", license, package.get_path());
+                continue;
+            }
+            installed.accept_license(GOOGLE_REPO_NAME_STR, license.clone());
+        }
+        installer.add_package(GOOGLE_REPO_NAME_STR, package)?;
+    }
+    installer.install()?;
+    let targets = installer.install_targets.len();
+    let complete = installer.complete_tasks.len();
+    for package in installer.complete_tasks {
+        installed.add_installed_package(package);
+    }
+    installed
+        .save_to_file()
+        .context("Failed to update installed package list with installed packages")?;
+    if complete != targets {
+        bail!("Installed {} of {} sdk packages", complete, targets);
+    }
+    Ok(())
+}
+
+/// Checks that the sdk has the platform and build-tools the dependencies need
+/// and offers to install the missing ones. Without a terminal to ask on, the
+/// missing packages are only reported unless accept_licenses is set, which
+/// installs them and accepts their licenses. The sdk lists are only needed to
+/// tell what is missing, so failing to read them is warned about instead of
+/// failing offline builds that have everything installed.
+///
+/// # Errors
+///
+/// Returns an error if the dependencies cannot be read or the installation fails
+pub fn ensure_sdk_packages(deps: &[ProjectDep], accept_licenses: bool) -> anyhow::Result<()> {
+    let Some(requirement) = required_compile_sdk(deps)? else {
+        return Ok(());
+    };
+    trace!(target: SDKMANAGER_TARGET, "{} requires compileSdk {}", requirement.required_by, requirement.compile_sdk);
+    let mut installed = match InstalledList::parse_from_sdk() {
+        Ok(installed) => installed,
+        Err(err) => {
+            warn!(target: SDKMANAGER_TARGET, "Unable to check for compileSdk {} required by {}. Failed to parse installed sdk packages list: {:?}", requirement.compile_sdk, requirement.required_by, err);
+            return Ok(());
+        }
+    };
+    let missing = missing_packages(&requirement, &installed);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    warn!(target: SDKMANAGER_TARGET, "{} requires compileSdk {} but {} is not installed", requirement.required_by, requirement.compile_sdk, missing.join(" and "));
+
+    let Some(repo) = installed.repositories.get(GOOGLE_REPO_NAME_STR) else {
+        warn!(target: SDKMANAGER_TARGET, "Add the google sdk repository with labt sdk add google to install them");
+        return Ok(());
+    };
+    let repo = match parse_repository_toml(&repo.path.join(toml_strings::CONFIG_FILE)) {
+        Ok(repo) => repo,
+        Err(err) => {
+            warn!(target: SDKMANAGER_TARGET, "{}: {:?}. Install them with labt sdk install", FAILED_TO_PARSE_SDK_STR, err);
+            return Ok(());
+        }
+    };
+    let packages = select_packages(&requirement, &missing, &repo);
+    if packages.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = packages
+        .iter()
+        .map(|p| format!("{} v{}", p.get_path(), p.get_revision()))
+        .collect();
+    if std::io::stdin().is_terminal() {
+        let install = Confirm::new()
+            .with_prompt(format!("Install {} now?", names.join(", ")))
+            .default(true)
+            .interact()?;
+        if !install {
+            return Ok(());
+        }
+    } else if !accept_licenses {
+        warn!(target: SDKMANAGER_TARGET, "Install {} with labt sdk install, or build with --accept-licenses to install them", names.join(", "));
+        return Ok(());
+    }
+    install_packages(packages, &mut installed, accept_licenses)
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn sdk_requirements_from_aar_metadata() {
+    use std::io::{Cursor, Write};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    use crate::config::repository::Revision;
+
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file(AAR_METADATA, SimpleFileOptions::default())
+        .unwrap();
+    writer
+        .write_all(b"aarFormatVersion=1.0\nminCompileSdk=34\nminAndroidGradlePluginVersion=8.1.1\n")
+        .unwrap();
+    let aar = writer.finish().unwrap().into_inner();
+    let mut archive = ZipArchive::new(Cursor::new(aar)).unwrap();
+    assert_eq!(aar_min_compile_sdk(&mut archive).unwrap(), Some(34));
+    assert_eq!(parse_min_compile_sdk("aarFormatVersion=1.0"), None);
+
+    let requirement = SdkRequirement {
+        compile_sdk: 34,
+        required_by: String::from("androidx.core:core:1.13.1"),
+    };
+    let revision = |major, minor| Revision {
+        major,
+        minor,
+        ..Default::default()
+    };
+    let package = |path: &str, revision: Revision| {
+        InstalledPackage::new(
+            path.to_string(),
+            revision,
+            ChannelType::Stable,
+            GOOGLE_REPO_NAME_STR.to_string(),
+        )
+    };
+    let mut installed = InstalledList::new();
+    installed.add_installed_package(package("platforms;android-33", revision(2, 0)));
+    installed.add_installed_package(package("build-tools;33.0.2", revision(33, 0)));
+    assert_eq!(
+        missing_packages(&requirement, &installed),
+        vec![
+            String::from("platforms;android-34"),
+            String::from("build-tools")
+        ]
+    );
+    installed.add_installed_package(package("platforms;android-35", revision(1, 0)));
+    installed.add_installed_package(package("build-tools;34.0.0", revision(34, 0)));
+    assert!(missing_packages(&requirement, &installed).is_empty());
+    let satisfying: Vec<&str> = satisfying_packages(&requirement, &installed)
+        .iter()
+        .map(|p| p.path.as_str())
+        .collect();
+    assert_eq!(
+        satisfying,
+        vec!["platforms;android-35", "build-tools;34.0.0"]
+    );
+
+    let remote = |path: &str, revision: Revision, channel: ChannelType| {
+        let mut package = RemotePackage::new();
+        package.set_path(path.to_string());
+        package.set_revision(revision);
+        package.set_channel(channel);
+        package
+    };
+    let mut repo = RepositoryXml::new();
+    repo.set_name(GOOGLE_REPO_NAME_STR.to_string());
+    for package in [
+        remote("platforms;android-34", revision(3, 0), ChannelType::Stable),
+        remote("build-tools;33.0.2", revision(33, 0), ChannelType::Stable),
+        remote("build-tools;34.0.0", revision(34, 0), ChannelType::Stable),
+        remote("build-tools;34.1.0", revision(34, 1), ChannelType::Stable),
+        remote("build-tools;34.2.0-rc1", revision(34, 2), ChannelType::Beta),
+        remote("build-tools;35.0.0", revision(35, 0), ChannelType::Stable),
+    ] {
+        repo.add_remote_package(package);
+    }
+    let selected: Vec<String> = select_packages(
+        &requirement,
+        &[
+            String::from("platforms;android-34"),
+            String::from("build-tools"),
+        ],
+        &repo,
+    )
+    .iter()
+    .map(|p| p.get_path().to_string())
+    .collect();
+    assert_eq!(selected, vec!["platforms;android-34", "build-tools;34.1.0"]);
+}
+
+#[test]
+fn sdk_check_warns_on_unreadable_sdk_lists() {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let tmp = tempfile::tempdir().unwrap();
+    crate::session::Session::new().enter(|| {
+        crate::set_home(Some(tmp.path().to_path_buf()));
+        let dep = ProjectDep {
+            group_id: String::from("androidx.core"),
+            artifact_id: String::from("core"),
+            version: String::from("1.13.1"),
+            packaging: String::from("aar"),
+            ..Default::default()
+        };
+        let mut cache = Cache::from(&dep);
+        cache.use_labt_home().unwrap();
+        let path = cache.get_path().unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        writer
+            .start_file(AAR_METADATA, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"minCompileSdk=34\n").unwrap();
+        writer.finish().unwrap();
+
+        let installed = tmp.path().join("sdk").join(super::installed_list::INSTALLED_LIST);
+        std::fs::create_dir_all(installed.parent().unwrap()).unwrap();
+        std::fs::write(&installed, "not [valid").unwrap();
+        assert!(ensure_sdk_packages(std::slice::from_ref(&dep), false).is_ok());
+
+        // the google repository was added but its package list is gone
+        std::fs::write(
+            &installed,
+            format!(
+                "[[repository]]\nname = \"google\"\nurl = \"{}\"\npath = {:?}\naccepted_licenses = []\n",
+                DEFAULT_URL,
+                tmp.path().join("missing")
+            ),
+        )
+        .unwrap();
+        assert!(ensure_sdk_packages(&[dep], false).is_ok());
+    });
+}
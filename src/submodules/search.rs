@@ -0,0 +1,153 @@
+use anyhow::{bail, Context};
+use clap::Args;
+use log::info;
+use serde::Serialize;
+
+use crate::{config::global::client_builder, output::is_json_output};
+
+use super::{
+    index::{load_index, merge_entries, search_central, search_index, IndexEntry, SEARCH_ROWS},
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct SearchArgs {
+    /// The artifact name or text to search for
+    pub query: String,
+    /// Also search the repository indexes downloaded with labt index and the labt cache
+    #[arg(long)]
+    pub index: bool,
+    /// The most results to show
+    #[arg(short = 'n', long, default_value_t = SEARCH_ROWS)]
+    pub limit: usize,
+    /// Print the results as json
+    #[arg(long)]
+    pub json: bool,
+}
+
+pub struct Search {
+    args: SearchArgs,
+}
+
+impl Search {
+    pub fn new(args: &SearchArgs) -> Self {
+        Search { args: args.clone() }
+    }
+}
+
+/// An artifact matching the search
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    pub group_id: String,
+    pub artifact_id: String,
+    /// The newest version known. None if the index does not list versions
+    pub latest: Option<String>,
+    /// Where the artifact was found, central or the name of an index
+    pub repositories: Vec<String>,
+}
+
+impl From<&IndexEntry> for SearchResult {
+    fn from(entry: &IndexEntry) -> Self {
+        SearchResult {
+            group_id: entry.group_id.clone(),
+            artifact_id: entry.artifact_id.clone(),
+            latest: entry.latest().map(String::from),
+            repositories: entry.repositories.clone(),
+        }
+    }
+}
+
+/// Renders a result per line as group_id:artifact_id:latest
+pub fn render_results(results: &[SearchResult]) -> String {
+    let mut out = String::new();
+    for result in results {
+        out.push_str(&result.group_id);
+        out.push(':');
+        out.push_str(&result.artifact_id);
+        if let Some(latest) = &result.latest {
+            out.push(':');
+            out.push_str(latest);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+impl Submodule for Search {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let query = self.args.query.trim();
+        if query.is_empty() {
+            bail!("Nothing to search for");
+        }
+        let offline = crate::is_offline();
+        let mut found = Vec::new();
+        if self.args.index || offline {
+            if offline && !self.args.index {
+                info!(target: "search", "Searching the downloaded indexes since labt is offline");
+            }
+            let indexed = load_index()?;
+            found.extend(search_index(&indexed, query).into_iter().cloned());
+        }
+        if !offline {
+            let client = client_builder()?
+                .build()
+                .context("Error creating download client")?;
+            let central = search_central(&client, query, self.args.limit)
+                .context(format!("Failed to search Maven Central for {query}"))?;
+            merge_entries(&mut found, central);
+        }
+        let results: Vec<SearchResult> = found
+            .iter()
+            .take(self.args.limit)
+            .map(SearchResult::from)
+            .collect();
+
+        if self.args.json || is_json_output() {
+            let json = serde_json::to_string_pretty(&results)
+                .context("Failed to serialize search results")?;
+            println!("{json}");
+        } else if results.is_empty() {
+            info!(target: "search", "No artifacts match {query}");
+        } else {
+            print!("{}", render_results(&results));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn search_results_output() {
+    let entries = [
+        IndexEntry {
+            group_id: String::from("com.squareup.okhttp3"),
+            artifact_id: String::from("okhttp"),
+            versions: vec![String::from("4.9.0"), String::from("4.12.0")],
+            repositories: vec![String::from("central"), String::from("cache")],
+        },
+        IndexEntry {
+            group_id: String::from("com.mycorp"),
+            artifact_id: String::from("okhttp-auth"),
+            versions: Vec::new(),
+            repositories: vec![String::from("internal")],
+        },
+    ];
+    let results: Vec<SearchResult> = entries.iter().map(SearchResult::from).collect();
+    assert_eq!(results[0].latest.as_deref(), Some("4.12.0"));
+    assert_eq!(
+        render_results(&results),
+        "com.squareup.okhttp3:okhttp:4.12.0\ncom.mycorp:okhttp-auth\n"
+    );
+    let json = serde_json::to_value(&results[0]).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "group_id": "com.squareup.okhttp3",
+            "artifact_id": "okhttp",
+            "latest": "4.12.0",
+            "repositories": ["central", "cache"]
+        })
+    );
+}
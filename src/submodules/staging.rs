@@ -0,0 +1,310 @@
+use std::{
+    fs::{create_dir_all, read_dir, remove_dir_all},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::warn;
+use serde::Serialize;
+
+use crate::pom::Scope;
+
+use super::{
+    artifacts::{artifact_jars, cached_artifact, split_classpaths},
+    resolve::ProjectDep,
+};
+
+/// Directory under build/ where the resolved dependencies are staged
+pub const STAGING_DIR: &str = "deps";
+pub const COMPILE_CLASSPATH_DIR: &str = "compile-classpath";
+pub const RUNTIME_CLASSPATH_DIR: &str = "runtime-classpath";
+pub const NATIVE_LIBS_DIR: &str = "native-libs";
+pub const RESOURCES_DIR: &str = "resources";
+pub const PROCESSOR_PATH_DIR: &str = "processor-path";
+/// Extracted AARs. Kept between builds since extraction is expensive
+const EXTRACTED_DIR: &str = "extracted";
+
+/// The directories dependencies are staged into for plugins.
+/// ```text
+/// build/deps
+/// ├── compile-classpath/<group_id>-<artifact_id>-<version>.jar
+/// ├── runtime-classpath/<group_id>-<artifact_id>-<version>.jar
+/// ├── native-libs/<abi>/lib*.so
+/// ├── processor-path/<group_id>-<artifact_id>-<version>.jar
+/// └── resources/<group_id>-<artifact_id>-<version>/{res,AndroidManifest.xml}
+/// ```
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct StagingDirs {
+    /// Jars needed to compile the project
+    pub compile_classpath: PathBuf,
+    /// Jars that need to be packaged with the app
+    pub runtime_classpath: PathBuf,
+    /// Native libraries of AARs grouped by abi
+    pub native_libs: PathBuf,
+    /// Android resources and manifests of AARs
+    pub resources: PathBuf,
+    /// Annotation processors and their dependencies. Never packaged with the app
+    pub processor_path: PathBuf,
+}
+
+impl StagingDirs {
+    pub fn new(root: &Path) -> Self {
+        let staging = root.join("build").join(STAGING_DIR);
+        StagingDirs {
+            compile_classpath: staging.join(COMPILE_CLASSPATH_DIR),
+            runtime_classpath: staging.join(RUNTIME_CLASSPATH_DIR),
+            native_libs: staging.join(NATIVE_LIBS_DIR),
+            resources: staging.join(RESOURCES_DIR),
+            processor_path: staging.join(PROCESSOR_PATH_DIR),
+        }
+    }
+    fn all(&self) -> [&PathBuf; 5] {
+        [
+            &self.compile_classpath,
+            &self.runtime_classpath,
+            &self.native_libs,
+            &self.resources,
+            &self.processor_path,
+        ]
+    }
+}
+
+fn staged_name(dep: &ProjectDep) -> String {
+    format!("{}-{}-{}", dep.group_id, dep.artifact_id, dep.version)
+}
+
+/// Links target at path. Uses symbolic links where available and falls back to
+/// hard links or copies for files.
+fn link(target: &Path, path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    let result = std::os::unix::fs::symlink(target, path);
+    #[cfg(windows)]
+    let result = if target.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::fs::hard_link(target, path).or_else(|_| std::fs::copy(target, path).map(|_| ()))
+    };
+    result.context(format!("Failed to link {:?} to {:?}", path, target))
+}
+
+fn link_jars(dir: &Path, name: &str, jars: &[PathBuf]) -> anyhow::Result<()> {
+    for (index, jar) in jars.iter().enumerate() {
+        let file = if index == 0 {
+            format!("{name}.jar")
+        } else {
+            format!("{name}-{index}.jar")
+        };
+        link(jar, &dir.join(file))?;
+    }
+    Ok(())
+}
+
+fn link_native_libs(native_libs: &Path, extracted: &Path, name: &str) -> anyhow::Result<()> {
+    let jni = extracted.join("jni");
+    if !jni.is_dir() {
+        return Ok(());
+    }
+    for abi in read_dir(&jni).context(format!("Failed to read {:?}", jni))? {
+        let abi = abi?.path();
+        let abi_name = match abi.file_name() {
+            Some(abi_name) if abi.is_dir() => abi_name.to_owned(),
+            _ => continue,
+        };
+        let output = native_libs.join(&abi_name);
+        create_dir_all(&output).context(format!("Failed to create {:?}", output))?;
+        for lib in read_dir(&abi).context(format!("Failed to read {:?}", abi))? {
+            let lib = lib?.path();
+            let staged = match lib.file_name() {
+                Some(file_name) => output.join(file_name),
+                None => continue,
+            };
+            if staged.exists() {
+                warn!(target: "staging", "{name} provides {:?} which was already staged by another dependency. Skipping", staged);
+                continue;
+            }
+            link(&lib, &staged)?;
+        }
+    }
+    Ok(())
+}
+
+fn link_resources(resources: &Path, extracted: &Path, name: &str) -> anyhow::Result<()> {
+    let res = extracted.join("res");
+    if !res.is_dir() {
+        return Ok(());
+    }
+    let output = resources.join(name);
+    create_dir_all(&output).context(format!("Failed to create {:?}", output))?;
+    link(&res, &output.join("res"))?;
+    let manifest = extracted.join("AndroidManifest.xml");
+    if manifest.exists() {
+        link(&manifest, &output.join("AndroidManifest.xml"))?;
+    }
+    Ok(())
+}
+
+/// Stages the cached artifacts of the dependencies and annotation processors under
+/// build/deps. The staged directories are recreated on every call so they only
+/// contain the given artifacts.
+///
+/// # Errors
+///
+/// Returns an error if an AAR cannot be extracted or the links cannot be created
+pub fn stage_artifacts(
+    root: &Path,
+    artifacts: &[(ProjectDep, PathBuf)],
+    processors: &[(ProjectDep, PathBuf)],
+) -> anyhow::Result<StagingDirs> {
+    let dirs = StagingDirs::new(root);
+    for dir in dirs.all() {
+        if dir.exists() {
+            remove_dir_all(dir).context(format!("Failed to clear {:?}", dir))?;
+        }
+        create_dir_all(dir).context(format!("Failed to create {:?}", dir))?;
+    }
+    let extracted_dir = root.join("build").join(STAGING_DIR).join(EXTRACTED_DIR);
+
+    let mut staged = Vec::with_capacity(artifacts.len());
+    for (dep, path) in artifacts {
+        crate::interrupt::check()?;
+        // skip extracting what is on neither classpath
+        if !dep.scope.on_compile_classpath() && !dep.scope.on_runtime_classpath() {
+            continue;
+        }
+        let (jars, extracted) = artifact_jars(dep, path, &extracted_dir)?;
+        staged.push((&dep.scope, (staged_name(dep), jars, extracted)));
+    }
+    let (compile, runtime) = split_classpaths(staged.iter().map(|(scope, dep)| (*scope, dep)));
+    for (name, jars, _) in compile {
+        link_jars(&dirs.compile_classpath, name, jars)?;
+    }
+    for (name, jars, extracted) in runtime {
+        link_jars(&dirs.runtime_classpath, name, jars)?;
+        if let Some(extracted) = extracted {
+            link_native_libs(&dirs.native_libs, extracted, name)?;
+        }
+    }
+    for (_, (name, _, extracted)) in &staged {
+        if let Some(extracted) = extracted {
+            link_resources(&dirs.resources, extracted, name)?;
+        }
+    }
+
+    for (dep, path) in processors {
+        crate::interrupt::check()?;
+        if dep.scope == Scope::TEST {
+            continue;
+        }
+        let (jars, _) = artifact_jars(dep, path, &extracted_dir)?;
+        link_jars(&dirs.processor_path, &staged_name(dep), &jars)?;
+    }
+    Ok(dirs)
+}
+
+/// Finds the cached artifacts of the dependencies. Dependencies without
+/// artifacts or not yet downloaded are skipped.
+fn cached_artifacts(deps: &[ProjectDep]) -> anyhow::Result<Vec<(ProjectDep, PathBuf)>> {
+    let mut artifacts = Vec::with_capacity(deps.len());
+    for dep in deps {
+        if let Some(path) = cached_artifact(dep, "staging")? {
+            artifacts.push((dep.clone(), path));
+        }
+    }
+    Ok(artifacts)
+}
+
+/// Stages the resolved dependencies and annotation processors from the labt cache.
+/// See [`stage_artifacts`]. Dependencies without artifacts or not yet downloaded
+/// are skipped.
+///
+/// # Errors
+///
+/// Returns an error if labt home is not available or staging fails
+pub fn stage_dependencies(
+    root: &Path,
+    deps: &[ProjectDep],
+    processors: &[ProjectDep],
+) -> anyhow::Result<StagingDirs> {
+    stage_artifacts(
+        root,
+        &cached_artifacts(deps)?,
+        &cached_artifacts(processors)?,
+    )
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn staging_layout_from_scopes() {
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("staging");
+    create_dir_all(&root).unwrap();
+
+    let aar = root.join("ui.aar");
+    let mut writer = zip::ZipWriter::new(std::fs::File::create(&aar).unwrap());
+    for (name, contents) in [
+        ("AndroidManifest.xml", "<manifest/>"),
+        ("classes.jar", "classes"),
+        ("libs/extra.jar", "extra"),
+        ("jni/arm64-v8a/libui.so", "so"),
+        ("res/values/values.xml", "<resources/>"),
+    ] {
+        writer
+            .start_file(name, SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(contents.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+    let jar = root.join("core.jar");
+    std::fs::write(&jar, "core").unwrap();
+    let compiler = root.join("compiler.jar");
+    std::fs::write(&compiler, "compiler").unwrap();
+
+    let dep = |artifact_id: &str, scope: Scope, packaging: &str| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: String::from("1.0"),
+        scope,
+        packaging: packaging.to_string(),
+        ..Default::default()
+    };
+    let artifacts = vec![
+        (dep("ui", Scope::COMPILE, "aar"), aar),
+        (dep("core", Scope::PROVIDED, "jar"), jar),
+    ];
+    let processors = vec![(dep("compiler", Scope::COMPILE, "jar"), compiler)];
+    stage_artifacts(&root, &artifacts, &[]).unwrap();
+    // staging again replaces the previous layout
+    let dirs = stage_artifacts(&root, &artifacts[..1], &processors).unwrap();
+
+    let list = |dir: &Path| {
+        let mut names: Vec<String> = read_dir(dir)
+            .unwrap()
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+    let compile = list(&dirs.compile_classpath);
+    let runtime = list(&dirs.runtime_classpath);
+    let processor_path = list(&dirs.processor_path);
+    let native = std::fs::read_to_string(dirs.native_libs.join("arm64-v8a/libui.so"));
+    let values = dirs
+        .resources
+        .join("com.example-ui-1.0/res/values/values.xml")
+        .exists();
+
+    assert_eq!(
+        compile,
+        vec!["com.example-ui-1.0-1.jar", "com.example-ui-1.0.jar"]
+    );
+    assert_eq!(compile, runtime);
+    assert_eq!(processor_path, vec!["com.example-compiler-1.0.jar"]);
+    assert_eq!(native.unwrap(), "so");
+    assert!(values);
+}
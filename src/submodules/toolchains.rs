@@ -0,0 +1,474 @@
+use std::{
+    fmt::Display,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    process::Command,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context};
+use log::debug;
+use serde::Serialize;
+
+use crate::{
+    config::{toolchains::ToolchainsTable, LabToml},
+    session::Session,
+    version::ComparableVersion,
+};
+
+use super::{
+    jdk::provision_jdk,
+    sdk::package_dir,
+    sdkmanager::installed_list::{InstalledList, InstalledPackage},
+};
+
+/// Prefix of the build tools package path in the sdk
+const BUILD_TOOLS_PACKAGE: &str = "build-tools";
+/// Describes the build tools package containing a tool found outside the labt sdk
+const SOURCE_PROPERTIES: &str = "source.properties";
+const PKG_REVISION: &str = "Pkg.Revision";
+const ANDROID_HOME: &str = "ANDROID_HOME";
+const ANDROID_SDK_ROOT: &str = "ANDROID_SDK_ROOT";
+const JAVA_HOME: &str = "JAVA_HOME";
+const KOTLIN_HOME: &str = "KOTLIN_HOME";
+
+/// A tool used by build plugins
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    Javac,
+    Kotlinc,
+    Aapt2,
+    D8,
+    Apksigner,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 5] = [
+        Tool::Javac,
+        Tool::Kotlinc,
+        Tool::Aapt2,
+        Tool::D8,
+        Tool::Apksigner,
+    ];
+    /// The tools shipped in the android build-tools package
+    pub fn is_build_tool(&self) -> bool {
+        matches!(self, Tool::Aapt2 | Tool::D8 | Tool::Apksigner)
+    }
+    /// File names of the tool executable on this platform
+    fn file_names(&self) -> Vec<String> {
+        let name = self.to_string();
+        if cfg!(target_os = "windows") {
+            match self {
+                Tool::Javac | Tool::Aapt2 => vec![format!("{name}.exe")],
+                _ => vec![format!("{name}.bat"), format!("{name}.cmd")],
+            }
+        } else {
+            vec![name]
+        }
+    }
+    /// The version required by the `[toolchains]` table
+    pub fn requirement<'a>(&self, table: &'a ToolchainsTable) -> Option<&'a str> {
+        match self {
+            Tool::Javac => table.javac.as_deref(),
+            Tool::Kotlinc => table.kotlinc.as_deref(),
+            Tool::Aapt2 => table.aapt2.as_deref(),
+            Tool::D8 => table.d8.as_deref(),
+            Tool::Apksigner => table.apksigner.as_deref(),
+        }
+    }
+}
+
+impl Display for Tool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tool::Javac => write!(f, "javac"),
+            Tool::Kotlinc => write!(f, "kotlinc"),
+            Tool::Aapt2 => write!(f, "aapt2"),
+            Tool::D8 => write!(f, "d8"),
+            Tool::Apksigner => write!(f, "apksigner"),
+        }
+    }
+}
+
+impl FromStr for Tool {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match Tool::ALL.iter().find(|tool| tool.to_string() == s) {
+            Some(tool) => Ok(*tool),
+            None => bail!(
+                "Unknown toolchain {s}. Expected one of javac, kotlinc, aapt2, d8 or apksigner"
+            ),
+        }
+    }
+}
+
+/// Where a toolchain was found
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolchainSource {
+    /// A package installed by labt sdkmanager
+    Sdk,
+    /// The JDK downloaded for the `[jdk]` table
+    Hermetic,
+    /// JAVA_HOME, KOTLIN_HOME, ANDROID_HOME or ANDROID_SDK_ROOT
+    Env,
+    Path,
+}
+
+impl Display for ToolchainSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolchainSource::Sdk => write!(f, "labt sdk"),
+            ToolchainSource::Hermetic => write!(f, "labt jdk"),
+            ToolchainSource::Env => write!(f, "environment"),
+            ToolchainSource::Path => write!(f, "PATH"),
+        }
+    }
+}
+
+/// A located tool
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Toolchain {
+    pub name: Tool,
+    pub path: PathBuf,
+    /// None if the version could not be determined
+    pub version: Option<String>,
+    pub source: ToolchainSource,
+}
+
+/// Checks a version against a `[toolchains]` requirement. See [`ToolchainsTable`]
+pub fn version_matches(requirement: &str, version: &str) -> bool {
+    let requirement = requirement.trim();
+    for operator in [">=", "<=", ">", "<", "="] {
+        if let Some(required) = requirement.strip_prefix(operator) {
+            let ordering =
+                ComparableVersion::new(version).cmp(&ComparableVersion::new(required.trim()));
+            return match operator {
+                ">=" => ordering.is_ge(),
+                "<=" => ordering.is_le(),
+                ">" => ordering.is_gt(),
+                "<" => ordering.is_lt(),
+                _ => ordering.is_eq(),
+            };
+        }
+    }
+    // a prefix of whole components e.g. 17 matches 17.0.2 but not 170
+    let mut parts = version.split(['.', '-', '_', '+']);
+    requirement
+        .split(['.', '-', '_', '+'])
+        .all(|required| parts.next() == Some(required))
+}
+
+/// Finds the first executable with one of the names in the PATH directories
+pub fn find_in_path(names: &[String]) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn find_in_dir(dir: &Path, names: &[String]) -> Option<PathBuf> {
+    names
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Reads Pkg.Revision from the source.properties of the package containing dir
+fn package_revision(dir: &Path) -> Option<String> {
+    let properties = std::fs::read_to_string(dir.join(SOURCE_PROPERTIES)).ok()?;
+    properties.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == PKG_REVISION).then(|| value.trim().to_string())
+    })
+}
+
+/// Formats a build tools revision the way the package directories are named e.g. 34.0.0
+fn revision_string(package: &InstalledPackage) -> String {
+    let version = &package.version;
+    let mut revision = format!("{}.{}.{}", version.major, version.minor, version.micro);
+    if version.preview > 0 {
+        revision.push_str(&format!("-rc{}", version.preview));
+    }
+    revision
+}
+
+/// Parses the version out of `javac -version` or `kotlinc -version` output.
+/// e.g. "javac 17.0.2" or "info: kotlinc-jvm 1.9.22 (JRE 17.0.2+8)"
+pub fn parse_version_output(tool: Tool, output: &str) -> Option<String> {
+    let marker = match tool {
+        Tool::Javac => "javac",
+        Tool::Kotlinc => "kotlinc-jvm",
+        _ => return None,
+    };
+    let mut words = output.split_whitespace();
+    words.find(|word| *word == marker)?;
+    words.next().map(|version| version.to_string())
+}
+
+/// Runs the tool to ask for its version. Build tools report the build tools
+/// package version from their source.properties instead.
+fn detect_version(tool: Tool, path: &Path) -> Option<String> {
+    if tool.is_build_tool() {
+        return package_revision(path.parent()?);
+    }
+    let output = Command::new(path).arg("-version").output().ok()?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    parse_version_output(tool, &text)
+}
+
+/// build-tools/<version> directories under an sdk, newest first
+fn sdk_build_tools(sdk: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = match read_dir(sdk.join(BUILD_TOOLS_PACKAGE)) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    dirs.sort_by_cached_key(|dir| {
+        std::cmp::Reverse(ComparableVersion::new(
+            &dir.file_name().unwrap_or_default().to_string_lossy(),
+        ))
+    });
+    dirs
+}
+
+/// Build tools packages installed by labt sdkmanager, newest first
+fn installed_build_tools() -> Vec<(PathBuf, String)> {
+    let list = match InstalledList::parse_from_sdk() {
+        Ok(list) => list,
+        Err(err) => {
+            debug!(target: "toolchains", "Failed to read installed sdk packages: {:?}", err);
+            return Vec::new();
+        }
+    };
+    let mut packages: Vec<&InstalledPackage> = list
+        .packages
+        .iter()
+        .filter(|package| package.path.split(';').next() == Some(BUILD_TOOLS_PACKAGE))
+        .collect();
+    packages.sort_by(|a, b| b.version.partial_cmp(&a.version).unwrap());
+    packages
+        .into_iter()
+        .filter_map(|package| {
+            let dir = match &package.directory {
+                Some(dir) => dir.clone(),
+                None => package_dir(&package.repository_name, &package.path).ok()?,
+            };
+            Some((dir, revision_string(package)))
+        })
+        .collect()
+}
+
+/// Possible locations of the tool in the order they are preferred
+fn candidates(tool: Tool, config: Option<&LabToml>) -> anyhow::Result<Vec<Toolchain>> {
+    let names = tool.file_names();
+    let mut found = Vec::new();
+    let mut push = |dir: &Path, version: Option<String>, source: ToolchainSource| {
+        if let Some(path) = find_in_dir(dir, &names) {
+            found.push(Toolchain {
+                name: tool,
+                version: version.or_else(|| detect_version(tool, &path)),
+                path,
+                source,
+            });
+        }
+    };
+
+    match tool {
+        Tool::Javac => {
+            if let Some(jdk) = config.and_then(|config| config.jdk.as_ref()) {
+                let java_home = provision_jdk(jdk)?;
+                push(&java_home.join("bin"), None, ToolchainSource::Hermetic);
+            }
+            if let Some(java_home) = std::env::var_os(JAVA_HOME) {
+                push(
+                    &PathBuf::from(java_home).join("bin"),
+                    None,
+                    ToolchainSource::Env,
+                );
+            }
+        }
+        Tool::Kotlinc => {
+            if let Some(kotlin_home) = std::env::var_os(KOTLIN_HOME) {
+                push(
+                    &PathBuf::from(kotlin_home).join("bin"),
+                    None,
+                    ToolchainSource::Env,
+                );
+            }
+        }
+        Tool::Aapt2 | Tool::D8 | Tool::Apksigner => {
+            for (dir, version) in installed_build_tools() {
+                push(&dir, Some(version), ToolchainSource::Sdk);
+            }
+            for variable in [ANDROID_HOME, ANDROID_SDK_ROOT] {
+                if let Some(sdk) = std::env::var_os(variable) {
+                    for dir in sdk_build_tools(Path::new(&sdk)) {
+                        push(&dir, None, ToolchainSource::Env);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(path) = find_in_path(&names) {
+        // PATH usually points at a tool already found through the other sources
+        let canonical = path.canonicalize().ok();
+        let known = found
+            .iter()
+            .any(|toolchain| toolchain.path.canonicalize().ok() == canonical);
+        if !known {
+            found.push(Toolchain {
+                name: tool,
+                version: detect_version(tool, &path),
+                path,
+                source: ToolchainSource::Path,
+            });
+        }
+    }
+    Ok(found)
+}
+
+/// Picks the first candidate satisfying the requirement, or the first candidate if
+/// there is no requirement.
+///
+/// # Errors
+///
+/// Returns an error listing the candidates if none of them satisfies the requirement
+pub fn select_toolchain(
+    tool: Tool,
+    candidates: Vec<Toolchain>,
+    requirement: Option<&str>,
+) -> anyhow::Result<Toolchain> {
+    let Some(requirement) = requirement else {
+        return candidates.into_iter().next().context(format!(
+            "{tool} was not found in the labt sdk, the environment or PATH"
+        ));
+    };
+    if let Some(toolchain) = candidates.iter().find(|toolchain| {
+        toolchain
+            .version
+            .as_deref()
+            .is_some_and(|version| version_matches(requirement, version))
+    }) {
+        return Ok(toolchain.clone());
+    }
+    if candidates.is_empty() {
+        bail!("{tool} {requirement} is required by Labt.toml but {tool} was not found in the labt sdk, the environment or PATH");
+    }
+    let found: Vec<String> = candidates
+        .iter()
+        .map(|toolchain| {
+            format!(
+                "  {} {} ({}) at {:?}",
+                tool,
+                toolchain.version.as_deref().unwrap_or("unknown version"),
+                toolchain.source,
+                toolchain.path
+            )
+        })
+        .collect();
+    let hint = if tool.is_build_tool() {
+        format!(
+            "Install it with labt sdk install {BUILD_TOOLS_PACKAGE};<version> or change [toolchains] in Labt.toml"
+        )
+    } else {
+        String::from("Install it or change [toolchains] in Labt.toml")
+    };
+    bail!(
+        "{tool} {requirement} is required by Labt.toml but only these were found:\n{}\n{hint}",
+        found.join("\n")
+    )
+}
+
+/// Locates the tool and verifies its version against the `[toolchains]` table.
+/// Results are cached for the rest of the session.
+///
+/// # Errors
+///
+/// Returns an error if the tool is missing or no version found satisfies the config
+pub fn resolve_toolchain(tool: Tool, config: Option<&LabToml>) -> anyhow::Result<Toolchain> {
+    let session = Session::current();
+    if let Some(toolchain) = session
+        .toolchains
+        .lock()
+        .ok()
+        .and_then(|r| r.get(&tool).cloned())
+    {
+        return Ok(toolchain);
+    }
+    let requirement = config
+        .and_then(|config| config.toolchains.as_ref())
+        .and_then(|table| tool.requirement(table));
+    let toolchain = select_toolchain(tool, candidates(tool, config)?, requirement)?;
+    debug!(target: "toolchains", "Using {tool} {:?} from {}", toolchain.path, toolchain.source);
+    if let Ok(mut resolved) = session.toolchains.lock() {
+        resolved.insert(tool, toolchain.clone());
+    }
+    Ok(toolchain)
+}
+
+/// Resolves every tool with a requirement in the `[toolchains]` table so that a
+/// mismatch fails before the build starts.
+///
+/// # Errors
+///
+/// Returns the error of the first tool that cannot be resolved
+pub fn verify_toolchains(config: &LabToml) -> anyhow::Result<Vec<Toolchain>> {
+    let Some(table) = &config.toolchains else {
+        return Ok(Vec::new());
+    };
+    Tool::ALL
+        .iter()
+        .filter(|tool| tool.requirement(table).is_some())
+        .map(|tool| resolve_toolchain(*tool, Some(config)))
+        .collect()
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn toolchain_version_requirements() {
+    assert!(version_matches("17", "17.0.2"));
+    assert!(!version_matches("17", "170.0"));
+    assert!(version_matches("34.0.0", "34.0.0"));
+    assert!(!version_matches("34.0.0", "34.0.1"));
+    assert!(version_matches(">=1.9", "1.9.22"));
+    assert!(!version_matches("<1.9", "1.9.22"));
+    assert!(version_matches("= 11", "11"));
+    assert_eq!(
+        parse_version_output(Tool::Javac, "javac 17.0.2\n"),
+        Some(String::from("17.0.2"))
+    );
+    assert_eq!(
+        parse_version_output(Tool::Kotlinc, "info: kotlinc-jvm 1.9.22 (JRE 17.0.2+8)"),
+        Some(String::from("1.9.22"))
+    );
+    assert_eq!("d8".parse::<Tool>().unwrap(), Tool::D8);
+
+    let toolchain = |version: Option<&str>, source| Toolchain {
+        name: Tool::Aapt2,
+        path: PathBuf::from("aapt2"),
+        version: version.map(String::from),
+        source,
+    };
+    let candidates = vec![
+        toolchain(Some("35.0.0"), ToolchainSource::Sdk),
+        toolchain(None, ToolchainSource::Path),
+        toolchain(Some("34.0.0"), ToolchainSource::Env),
+    ];
+    let selected = select_toolchain(Tool::Aapt2, candidates.clone(), Some("34")).unwrap();
+    assert_eq!(selected.source, ToolchainSource::Env);
+    let selected = select_toolchain(Tool::Aapt2, candidates.clone(), None).unwrap();
+    assert_eq!(selected.source, ToolchainSource::Sdk);
+    let err = select_toolchain(Tool::Aapt2, candidates, Some("33")).unwrap_err();
+    assert!(err.to_string().contains("unknown version"));
+}
@@ -0,0 +1,358 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+
+use crate::{
+    caching::graph::{load_graph, DependencyGraph, GraphNode, GraphSection},
+    config::get_config,
+    get_project_root,
+    output::{is_json_output, print_json},
+};
+
+use super::{
+    resolve::{get_config_dependencies, get_config_processors, get_config_test_dependencies},
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct TreeArgs {
+    /// The dependencies to show the tree of
+    #[arg(short, long, value_enum, default_value_t = TreeSection::Project)]
+    section: TreeSection,
+    /// Only show this many levels below the direct dependencies
+    #[arg(short, long)]
+    depth: Option<usize>,
+    /// Show the version constraints each dependency was resolved with
+    #[arg(short, long)]
+    constraints: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum, PartialEq, Eq, Debug)]
+pub enum TreeSection {
+    /// The project dependencies
+    Project,
+    /// The test dependencies
+    Test,
+    /// The annotation processors
+    Processors,
+}
+
+pub struct Tree {
+    args: TreeArgs,
+}
+
+impl Tree {
+    pub fn new(args: &TreeArgs) -> Self {
+        Tree { args: args.clone() }
+    }
+}
+
+/// Options for rendering the dependency tree
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TreeOptions {
+    pub depth: Option<usize>,
+    pub constraints: bool,
+}
+
+/// The dependency line of a node. The requested version is shown if the
+/// selected version is different.
+fn describe(dep: &GraphNode, requested: Option<&str>, options: &TreeOptions) -> String {
+    let version = match requested {
+        Some(requested) if requested != dep.version => format!("{requested} -> {}", dep.version),
+        _ => dep.version.clone(),
+    };
+    let mut line = format!(
+        "{}:{}:{} [{}] {}",
+        dep.group_id, dep.artifact_id, version, dep.scope, dep.packaging
+    );
+    if options.constraints {
+        if let Some(constraint) = &dep.constraints {
+            line.push_str(&format!(" {{{constraint}}}"));
+        }
+    }
+    line
+}
+
+fn render_children(
+    out: &mut String,
+    dep: &GraphNode,
+    graph: &DependencyGraph,
+    prefix: &str,
+    level: usize,
+    shown: &mut HashSet<String>,
+    options: &TreeOptions,
+) {
+    if options.depth.is_some_and(|depth| level > depth) {
+        return;
+    }
+    let count = dep.dependencies.len();
+    for (index, child) in dep.dependencies.iter().enumerate() {
+        let last = index + 1 == count;
+        let branch = if last { "└── " } else { "├── " };
+        let (group_id, artifact_id) = (&child.group_id, &child.artifact_id);
+        let requested = child.requested.as_deref();
+        let Some(child) = graph.find(dep.section, group_id, artifact_id) else {
+            // excluded or filtered out by scope during resolution
+            out.push_str(&format!(
+                "{prefix}{branch}{group_id}:{artifact_id} (not resolved)\n"
+            ));
+            continue;
+        };
+        let line = describe(child, requested, options);
+        let key = format!("{}:{}", child.group_id, child.artifact_id);
+        // like maven, a subtree is only expanded the first time it is shown
+        if !child.dependencies.is_empty() && shown.contains(&key) {
+            out.push_str(&format!("{prefix}{branch}{line} (*)\n"));
+            continue;
+        }
+        out.push_str(&format!("{prefix}{branch}{line}\n"));
+        shown.insert(key);
+        let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+        render_children(out, child, graph, &prefix, level + 1, shown, options);
+    }
+}
+
+/// A dependency in the json tree
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TreeNode {
+    pub group_id: String,
+    pub artifact_id: String,
+    /// The selected version. None if the dependency is not in Labt.lock
+    pub version: Option<String>,
+    /// The version the parent requested if another one was selected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packaging: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<String>,
+    /// The dependencies were already listed where this dependency first appeared
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub repeated: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    fn new(dep: &GraphNode, requested: Option<&str>, options: &TreeOptions) -> Self {
+        TreeNode {
+            group_id: dep.group_id.clone(),
+            artifact_id: dep.artifact_id.clone(),
+            version: Some(dep.version.clone()),
+            requested: requested
+                .filter(|requested| *requested != dep.version)
+                .map(String::from),
+            scope: Some(dep.scope.clone()),
+            packaging: Some(dep.packaging.clone()),
+            constraints: dep.constraints.clone().filter(|_| options.constraints),
+            ..Default::default()
+        }
+    }
+    fn unresolved(group_id: &str, artifact_id: &str) -> Self {
+        TreeNode {
+            group_id: group_id.to_string(),
+            artifact_id: artifact_id.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+fn tree_children(
+    dep: &GraphNode,
+    graph: &DependencyGraph,
+    level: usize,
+    shown: &mut HashSet<String>,
+    options: &TreeOptions,
+) -> Vec<TreeNode> {
+    if options.depth.is_some_and(|depth| level > depth) {
+        return Vec::new();
+    }
+    let mut nodes = Vec::new();
+    for child in &dep.dependencies {
+        let (group_id, artifact_id) = (&child.group_id, &child.artifact_id);
+        let requested = child.requested.as_deref();
+        let Some(child) = graph.find(dep.section, group_id, artifact_id) else {
+            nodes.push(TreeNode::unresolved(group_id, artifact_id));
+            continue;
+        };
+        let mut node = TreeNode::new(child, requested, options);
+        let key = format!("{}:{}", child.group_id, child.artifact_id);
+        if !child.dependencies.is_empty() && shown.contains(&key) {
+            node.repeated = true;
+        } else {
+            shown.insert(key);
+            node.dependencies = tree_children(child, graph, level + 1, shown, options);
+        }
+        nodes.push(node);
+    }
+    nodes
+}
+
+/// Builds the dependency tree of each root like [`render_tree`] for the json output
+pub fn build_tree(
+    roots: &[(String, String)],
+    graph: &DependencyGraph,
+    section: GraphSection,
+    options: &TreeOptions,
+) -> Vec<TreeNode> {
+    let mut shown = HashSet::new();
+    roots
+        .iter()
+        .map(
+            |(group_id, artifact_id)| match graph.find(section, group_id, artifact_id) {
+                Some(dep) => {
+                    let mut node = TreeNode::new(dep, None, options);
+                    shown.insert(format!("{group_id}:{artifact_id}"));
+                    node.dependencies = tree_children(dep, graph, 1, &mut shown, options);
+                    node
+                }
+                None => TreeNode::unresolved(group_id, artifact_id),
+            },
+        )
+        .collect()
+}
+
+/// Renders the dependency tree of each root given as (group_id, artifact_id). The
+/// roots are looked up in section of the graph and the children of a dependency
+/// in the section it was locked in.
+pub fn render_tree(
+    roots: &[(String, String)],
+    graph: &DependencyGraph,
+    section: GraphSection,
+    options: &TreeOptions,
+) -> String {
+    let mut out = String::new();
+    let mut shown = HashSet::new();
+    for (group_id, artifact_id) in roots {
+        match graph.find(section, group_id, artifact_id) {
+            Some(dep) => {
+                out.push_str(&describe(dep, None, options));
+                out.push('\n');
+                shown.insert(format!("{group_id}:{artifact_id}"));
+                render_children(&mut out, dep, graph, "", 1, &mut shown, options);
+            }
+            None => out.push_str(&format!(
+                "{group_id}:{artifact_id} (not in Labt.lock, run labt resolve)\n"
+            )),
+        }
+    }
+    out
+}
+
+impl Submodule for Tree {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let config = get_config()?;
+        let root = get_project_root().context("Failed to get the project root")?;
+        let graph = load_graph(&root)?;
+        // test dependencies share the project dependencies they depend on
+        let (roots, section) = match self.args.section {
+            TreeSection::Project => (get_config_dependencies(&config)?, GraphSection::Project),
+            TreeSection::Test => (get_config_test_dependencies(&config), GraphSection::Test),
+            TreeSection::Processors => (get_config_processors(&config), GraphSection::Processors),
+        };
+        let mut roots: Vec<(String, String)> = roots
+            .iter()
+            .map(|p| (p.get_group_id(), p.get_artifact_id()))
+            .collect();
+        roots.sort();
+        let options = TreeOptions {
+            depth: self.args.depth,
+            constraints: self.args.constraints,
+        };
+        if is_json_output() {
+            return print_json(&build_tree(&roots, &graph, section, &options));
+        }
+        print!("{}", render_tree(&roots, &graph, section, &options));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn dependency_tree_rendering() {
+    use crate::{config::lock::LabtLock, submodules::resolve::ProjectDep};
+
+    let dep = |artifact_id: &str, version: &str, dependencies: &[&str]| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: version.to_string(),
+        packaging: String::from("jar"),
+        dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        ..Default::default()
+    };
+    let deps = [
+        dep("shared", "2.0.0", &[]),
+        dep("core", "1.0.0", &["com.example:shared:1.0.0"]),
+        dep(
+            "app",
+            "1.0.0",
+            &[
+                "com.example:core:1.0.0",
+                "com.example:shared:2.0.0",
+                "com.example:excluded:1.0.0",
+            ],
+        ),
+        dep("ui", "1.0.0", &["com.example:core:1.0.0"]),
+    ];
+    let lock = LabtLock {
+        resolved: deps.to_vec(),
+        ..Default::default()
+    };
+    let graph = DependencyGraph::from_lock_with(&lock, String::new(), |_| Vec::new());
+    let section = GraphSection::Project;
+    let roots = vec![
+        (String::from("com.example"), String::from("app")),
+        (String::from("com.example"), String::from("ui")),
+        (String::from("com.example"), String::from("missing")),
+    ];
+
+    let tree = render_tree(&roots, &graph, section, &TreeOptions::default());
+    assert_eq!(
+        tree,
+        "com.example:app:1.0.0 [compile] jar
+├── com.example:core:1.0.0 [compile] jar
+│   └── com.example:shared:1.0.0 -> 2.0.0 [compile] jar
+├── com.example:shared:2.0.0 [compile] jar
+└── com.example:excluded (not resolved)
+com.example:ui:1.0.0 [compile] jar
+└── com.example:core:1.0.0 [compile] jar (*)
+com.example:missing (not in Labt.lock, run labt resolve)
+"
+    );
+
+    let json =
+        serde_json::to_value(build_tree(&roots, &graph, section, &TreeOptions::default())).unwrap();
+    assert_eq!(
+        json[0]["dependencies"][0]["dependencies"][0],
+        serde_json::json!({
+            "group_id": "com.example",
+            "artifact_id": "shared",
+            "version": "2.0.0",
+            "requested": "1.0.0",
+            "scope": "compile",
+            "packaging": "jar"
+        })
+    );
+    assert_eq!(
+        json[0]["dependencies"][2],
+        serde_json::json!({"group_id": "com.example", "artifact_id": "excluded", "version": null})
+    );
+    assert_eq!(json[1]["dependencies"][0]["repeated"], true);
+    assert_eq!(json[1]["dependencies"][0].get("dependencies"), None);
+    assert_eq!(json[2]["version"], serde_json::Value::Null);
+
+    let options = TreeOptions {
+        depth: Some(0),
+        ..Default::default()
+    };
+    assert_eq!(
+        render_tree(&roots[1..2], &graph, section, &options),
+        "com.example:ui:1.0.0 [compile] jar\n"
+    );
+}
@@ -0,0 +1,300 @@
+use std::fs::write;
+
+use anyhow::{bail, Context};
+use clap::Args;
+use log::{info, warn};
+use toml_edit::{Document, Item};
+
+use crate::{
+    config::{
+        get_config, get_config_string, get_resolve_config, get_resolvers_from_config,
+        maven_metadata::MavenMetadata, LABT_TOML_FILE_NAME,
+    },
+    get_manifest_path,
+    version::ComparableVersion,
+};
+
+use super::{
+    migrate::{get_string, set_string, DEPENDENCY_TABLES},
+    outdated::OutdatedEntry,
+    resolve::{get_config_dependencies, resolve, ResolveOptions},
+    resolvers::Resolver,
+    versions::fetch_metadata_sources,
+    Submodule,
+};
+
+#[derive(Clone, Args)]
+pub struct UpdateArgs {
+    /// Only update this dependency. Either its Labt.toml key, artifact_id or
+    /// group_id:artifact_id. Updates all dependencies if not given
+    pub artifact: Option<String>,
+    /// Print the new versions without changing Labt.toml or Labt.lock
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Also update to pre-release and snapshot versions
+    #[arg(long)]
+    pub pre_release: bool,
+}
+
+pub struct Update {
+    args: UpdateArgs,
+}
+
+impl Update {
+    pub fn new(args: &UpdateArgs) -> Self {
+        Update { args: args.clone() }
+    }
+}
+
+/// A version bump of a Labt.toml dependency
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionUpdate {
+    /// The table and key of the dependency e.g. dependencies.appcompat
+    pub entry: String,
+    pub group_id: String,
+    pub artifact_id: String,
+    pub from: String,
+    pub to: String,
+    /// The resolver the dependency is pinned to
+    pub resolver: Option<String>,
+}
+
+/// The maven dependencies of the toml with a version, optionally only the one
+/// matching filter
+pub fn updatable(doc: &Document, filter: Option<&str>) -> Vec<VersionUpdate> {
+    let mut found = Vec::new();
+    for name in DEPENDENCY_TABLES {
+        let Some(table) = doc.get(name).and_then(Item::as_table_like) else {
+            continue;
+        };
+        for (key, item) in table.iter() {
+            let Some(dep) = item.as_table_like() else {
+                continue;
+            };
+            let (Some(group_id), Some(version)) =
+                (get_string(dep, "group_id"), get_string(dep, "version"))
+            else {
+                continue;
+            };
+            if version.is_empty() || dep.contains_key("path") || dep.contains_key("git") {
+                continue;
+            }
+            let artifact_id = get_string(dep, "artifact_id").unwrap_or(key);
+            let matches = filter.is_none_or(|filter| {
+                filter == key
+                    || filter == artifact_id
+                    || filter == format!("{group_id}:{artifact_id}")
+            });
+            if matches {
+                found.push(VersionUpdate {
+                    entry: format!("{name}.{key}"),
+                    group_id: group_id.to_string(),
+                    artifact_id: artifact_id.to_string(),
+                    from: version.to_string(),
+                    to: version.to_string(),
+                    resolver: get_string(dep, "resolver").map(String::from),
+                });
+            }
+        }
+    }
+    found
+}
+
+/// The version to update from to. Pre-releases are only proposed with pre_release
+/// set. Returns None if there is nothing newer than from
+fn update_to(sources: &[(String, MavenMetadata)], from: &str, pre_release: bool) -> Option<String> {
+    let mut versions = OutdatedEntry::default();
+    versions.set_versions(sources);
+    let newest = if pre_release {
+        versions.latest
+    } else {
+        versions.release
+    };
+    // never downgrade a version that is newer than the release e.g. a pre-release
+    newest.filter(|newest| ComparableVersion::new(newest) > ComparableVersion::new(from))
+}
+
+/// Writes the new versions into the toml keeping its formatting
+pub fn apply_updates(doc: &mut Document, updates: &[VersionUpdate]) {
+    for update in updates {
+        let Some((table, key)) = update.entry.split_once('.') else {
+            continue;
+        };
+        if let Some(dep) = doc
+            .get_mut(table)
+            .and_then(Item::as_table_like_mut)
+            .and_then(|table| table.get_mut(key))
+            .and_then(Item::as_table_like_mut)
+        {
+            set_string(dep, "version", &update.to);
+        }
+    }
+}
+
+impl Submodule for Update {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let original = get_config_string()?;
+        let mut doc = original
+            .parse::<Document>()
+            .context(format!("Failed parsing {}", LABT_TOML_FILE_NAME))?;
+        let candidates = updatable(&doc, self.args.artifact.as_deref());
+        if candidates.is_empty() {
+            match &self.args.artifact {
+                Some(artifact) => {
+                    bail!("No versioned dependency {artifact} in {LABT_TOML_FILE_NAME}")
+                }
+                None => {
+                    info!(target: "update", "No dependencies to update");
+                    return Ok(());
+                }
+            }
+        }
+
+        let config = get_config()?;
+        let resolvers = get_resolvers_from_config(&config)?;
+        let mut updates = Vec::new();
+        for mut update in candidates {
+            crate::interrupt::check()?;
+            let pinned: Vec<Box<dyn Resolver>>;
+            let lookup = match &update.resolver {
+                Some(name) => {
+                    pinned = get_resolvers_from_config(&config)?
+                        .into_iter()
+                        .filter(|r| r.get_name() == name)
+                        .collect();
+                    &pinned
+                }
+                None => &resolvers,
+            };
+            let sources =
+                match fetch_metadata_sources(&update.group_id, &update.artifact_id, lookup) {
+                    Ok(sources) => sources,
+                    Err(err) => {
+                        warn!(target: "update", "{:?}", err);
+                        continue;
+                    }
+                };
+            if let Some(newest) = update_to(&sources, &update.from, self.args.pre_release) {
+                update.to = newest;
+                updates.push(update);
+            }
+        }
+
+        if updates.is_empty() {
+            info!(target: "update", "All dependencies are up to date");
+            return Ok(());
+        }
+        for update in &updates {
+            info!(target: "update", "{}: {} -> {}", update.entry, update.from, update.to);
+        }
+        if self.args.dry_run {
+            return Ok(());
+        }
+
+        apply_updates(&mut doc, &updates);
+        let path = get_manifest_path().context("Failed to get the project manifest")?;
+        write(&path, doc.to_string()).context(format!("Failed to write {:?}", path))?;
+
+        let resolved = get_resolve_config().and_then(|config| {
+            let dependencies = get_config_dependencies(&config)?;
+            let resolvers = get_resolvers_from_config(&config)?;
+            resolve(dependencies, resolvers, ResolveOptions::try_from(&config)?)
+        });
+        if let Err(err) = resolved {
+            // leave the project as it was so that it still builds
+            write(&path, original).context(format!("Failed to restore {:?}", path))?;
+            return Err(err).context(format!(
+                "Failed to resolve the updated dependencies. {LABT_TOML_FILE_NAME} was left unchanged"
+            ));
+        }
+        info!(target: "update", "Updated {} dependencies", updates.len());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn update_rewrites_versions_only() {
+    let toml = r#"[dependencies]
+appcompat = { group_id = "androidx.appcompat", version = "1.6.0" } # ui
+material = {version="1.10.0",group_id="com.google.android.material"}
+ui = { group_id = "androidx.compose.ui" }
+my-lib = { path = "../my-lib", version = "1.0" }
+
+[test_dependencies.junit]
+group_id = "junit"
+version = "4.12"
+resolver = "central"
+"#;
+    let mut doc = toml.parse::<Document>().unwrap();
+    let mut updates = updatable(&doc, None);
+    assert_eq!(
+        updates
+            .iter()
+            .map(|update| update.entry.as_str())
+            .collect::<Vec<_>>(),
+        vec![
+            "dependencies.appcompat",
+            "dependencies.material",
+            "test_dependencies.junit"
+        ]
+    );
+    assert_eq!(updates[2].resolver.as_deref(), Some("central"));
+    assert_eq!(updatable(&doc, Some("junit:junit")).len(), 1);
+    assert_eq!(updatable(&doc, Some("appcompat")).len(), 1);
+    assert!(updatable(&doc, Some("okhttp")).is_empty());
+
+    updates[0].to = String::from("1.7.0");
+    updates[1].to = String::from("1.11.0");
+    updates[2].to = String::from("4.13.2");
+    apply_updates(&mut doc, &updates);
+    assert_eq!(
+        doc.to_string(),
+        r#"[dependencies]
+appcompat = { group_id = "androidx.appcompat", version = "1.7.0" } # ui
+material = {version="1.11.0",group_id="com.google.android.material"}
+ui = { group_id = "androidx.compose.ui" }
+my-lib = { path = "../my-lib", version = "1.0" }
+
+[test_dependencies.junit]
+group_id = "junit"
+version = "4.13.2"
+resolver = "central"
+"#
+    );
+}
+
+#[test]
+fn update_skips_pre_releases() {
+    // google maven lists the androidx alphas and sets them as <release>
+    let sources = vec![(
+        String::from("google"),
+        MavenMetadata {
+            versions: [
+                "1.6.0",
+                "1.6.1",
+                "1.7.0-alpha01",
+                "1.7.0-beta02",
+                "1.7.0-rc01",
+            ]
+            .map(String::from)
+            .to_vec(),
+            release: Some(String::from("1.7.0-rc01")),
+            latest: Some(String::from("1.7.0-rc01")),
+            ..Default::default()
+        },
+    )];
+    assert_eq!(
+        update_to(&sources, "1.6.0", false).as_deref(),
+        Some("1.6.1")
+    );
+    assert_eq!(update_to(&sources, "1.6.1", false), None);
+    assert_eq!(
+        update_to(&sources, "1.6.0", true).as_deref(),
+        Some("1.7.0-rc01")
+    );
+    // already on an alpha, nothing stable is newer
+    assert_eq!(update_to(&sources, "1.7.0-alpha01", false), None);
+}
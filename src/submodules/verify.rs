@@ -0,0 +1,361 @@
+use std::{
+    fmt::Display,
+    fs::remove_file,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context};
+use clap::Args;
+use log::{error, info, warn};
+use reqwest::{StatusCode, Url};
+
+use crate::{
+    caching::{
+        checksum_path,
+        download::{artifact_file_name, download},
+        file_sha1, file_sha256,
+        properties::{read_properties, write_properties},
+        recorded_checksum, Cache,
+    },
+    config::{
+        global::{authenticate, client_builder, send_with_retry},
+        lock::load_labt_lock,
+    },
+    http_recording::SendRecorded,
+};
+
+use super::{resolve::ProjectDep, Submodule};
+
+#[derive(Clone, Args)]
+pub struct VerifyArgs {
+    /// Download missing or corrupted artifacts again
+    #[arg(long)]
+    repair: bool,
+    /// Only compare against checksums recorded at download time
+    /// and skip fetching checksums from the repositories
+    #[arg(long)]
+    offline: bool,
+}
+
+pub struct Verify {
+    args: VerifyArgs,
+}
+
+impl Verify {
+    pub fn new(args: &VerifyArgs) -> Self {
+        Verify { args: args.clone() }
+    }
+}
+
+/// The outcome of verifying a single cached artifact
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArtifactStatus {
+    /// The checksum matches
+    Ok,
+    /// The file exists but there is no checksum to compare it with
+    Unverified,
+    /// The artifact is not in the cache
+    Missing,
+    /// The checksum of the artifact does not match
+    Corrupted {
+        algorithm: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl ArtifactStatus {
+    fn needs_repair(&self) -> bool {
+        matches!(self, Self::Missing | Self::Corrupted { .. })
+    }
+}
+
+impl Display for ArtifactStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ok => write!(f, "ok"),
+            Self::Unverified => write!(f, "no checksum available"),
+            Self::Missing => write!(f, "missing"),
+            Self::Corrupted {
+                algorithm,
+                expected,
+                actual,
+            } => {
+                write!(
+                    f,
+                    "corrupted: expected {algorithm} {expected}, found {actual}"
+                )
+            }
+        }
+    }
+}
+
+/// Extracts the checksum from the contents of a maven .sha1 file. Some
+/// repositories append the file name after the hash.
+pub fn parse_checksum(contents: &str) -> Option<String> {
+    contents
+        .split_whitespace()
+        .next()
+        .filter(|hash| hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|hash| hash.to_lowercase())
+}
+
+/// Compares the artifact at path with the expected checksum
+///
+/// # Errors
+///
+/// Returns an error if the artifact exists but cannot be read
+pub fn check_artifact(path: &Path, expected: Option<&str>) -> anyhow::Result<ArtifactStatus> {
+    if !path.exists() {
+        return Ok(ArtifactStatus::Missing);
+    }
+    let expected = match expected {
+        Some(expected) => expected,
+        None => return Ok(ArtifactStatus::Unverified),
+    };
+    let actual = file_sha1(path).context(format!("Failed to read {:?}", path))?;
+    Ok(compare("sha1", expected, actual))
+}
+
+/// Compares the artifact at path with the sha256 recorded for it in Labt.lock,
+/// like the check before a build
+///
+/// # Errors
+///
+/// Returns an error if the artifact exists but cannot be read
+pub fn check_locked_artifact(path: &Path, sha256: &str) -> anyhow::Result<ArtifactStatus> {
+    if !path.exists() {
+        return Ok(ArtifactStatus::Missing);
+    }
+    let actual = file_sha256(path).context(format!("Failed to read {:?}", path))?;
+    Ok(compare("sha256", sha256, actual))
+}
+
+fn compare(algorithm: &'static str, expected: &str, actual: String) -> ArtifactStatus {
+    if actual.eq_ignore_ascii_case(expected) {
+        ArtifactStatus::Ok
+    } else {
+        ArtifactStatus::Corrupted {
+            algorithm,
+            expected: expected.to_string(),
+            actual,
+        }
+    }
+}
+
+/// Dependencies built from local paths or git have no remote checksums
+fn is_local(dep: &ProjectDep) -> bool {
+    dep.base_url.starts_with("file:")
+}
+
+/// Fetches the checksum published by the repository next to the artifact.
+/// Returns None if the repository does not publish one.
+///
+/// # Errors
+///
+/// Returns an error if the request fails or the repository answers with an
+/// error other than 404, such as refusing the credentials
+fn remote_checksum(
+    client: &reqwest::blocking::Client,
+    dep: &ProjectDep,
+) -> anyhow::Result<Option<String>> {
+    let base = Url::parse(&dep.get_root_url()).context("Error parsing repo url")?;
+    let url = base.join(&format!("{}.sha1", artifact_file_name(dep)))?;
+    let res = send_with_retry(url.as_str(), || {
+        authenticate(client.get(url.clone()), url.as_str()).send_recorded()
+    })
+    .context(format!("Failed to fetch checksum at {url}"))?;
+    if res.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    if !res.status().is_success() {
+        bail!("Failed to fetch checksum at {url}: {}", res.status());
+    }
+    let text = res
+        .text()
+        .context(format!("Failed to read checksum at {url}"))?;
+    Ok(parse_checksum(&text))
+}
+
+fn coordinate(dep: &ProjectDep) -> String {
+    format!("{}:{}:{}", dep.group_id, dep.artifact_id, dep.version)
+}
+
+impl Verify {
+    /// Checks the artifact against the sha256 locked for it, else against the
+    /// checksum from [`Verify::expected_checksum`]
+    fn status(
+        &self,
+        client: &reqwest::blocking::Client,
+        dep: &ProjectDep,
+        path: &Path,
+    ) -> anyhow::Result<ArtifactStatus> {
+        match &dep.sha256 {
+            Some(sha256) => check_locked_artifact(path, sha256),
+            None => check_artifact(path, self.expected_checksum(client, dep, path).as_deref()),
+        }
+    }
+
+    /// The checksum the artifact is expected to have. The repository is the source of
+    /// truth, falling back to the checksum recorded when the artifact was downloaded.
+    fn expected_checksum(
+        &self,
+        client: &reqwest::blocking::Client,
+        dep: &ProjectDep,
+        path: &Path,
+    ) -> Option<String> {
+        if !self.args.offline && !crate::is_offline() && !is_local(dep) {
+            match remote_checksum(client, dep) {
+                Ok(Some(checksum)) => return Some(checksum),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(target: "verify", "{}: {:?}. Using the recorded checksum", coordinate(dep), err)
+                }
+            }
+        }
+        recorded_checksum(path)
+    }
+
+    /// Downloads the artifact again and checks it like [`Verify::status`]
+    fn repair(
+        &self,
+        client: &reqwest::blocking::Client,
+        dep: &ProjectDep,
+        path: &Path,
+    ) -> anyhow::Result<()> {
+        if is_local(dep) {
+            bail!("Local dependencies cannot be repaired. Run labt resolve to build them again");
+        }
+        if crate::is_offline() {
+            bail!("Offline mode: downloading the artifact again needs network access. Run again without --offline to repair it");
+        }
+        if path.exists() {
+            remove_file(path).context(format!("Failed to remove {:?}", path))?;
+            let _ = remove_file(checksum_path(path));
+        }
+        download(dep).context(format!(
+            "Failed to download dependency from [{}]",
+            dep.get_root_url()
+        ))?;
+        let status = self.status(client, dep, path)?;
+        if status.needs_repair() {
+            bail!("Downloaded artifact is still {status}");
+        }
+        Ok(())
+    }
+}
+
+impl Submodule for Verify {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let lock =
+            load_labt_lock().context("Failed to load lock file. Try running labt resolve")?;
+        let client = client_builder()?
+            .build()
+            .context("Error creating http client")?;
+
+        let mut failed = 0;
+        let mut unverified = 0;
+        let mut checked = 0;
+        for dep in lock.sections().into_iter().flat_map(|(_, deps)| deps) {
+            checked += 1;
+            crate::interrupt::check()?;
+            let name = coordinate(dep);
+            let mut cache = Cache::from(dep);
+            cache.use_labt_home()?;
+            let path: PathBuf = cache.get_path()?;
+
+            // the cached resolution metadata used by the cache resolver
+            let mut properties = dep.clone();
+            if read_properties(&mut properties).is_err() {
+                if self.args.repair {
+                    write_properties(dep)
+                        .context(format!("Failed to rewrite cache metadata of {name}"))?;
+                    info!(target: "verify", "{name}: rewrote cache metadata");
+                } else {
+                    warn!(target: "verify", "{name}: cache metadata is missing or unreadable");
+                    failed += 1;
+                }
+            }
+
+            let status = self.status(&client, dep, &path)?;
+            match &status {
+                ArtifactStatus::Ok => info!(target: "verify", "{name}: {status}"),
+                ArtifactStatus::Unverified => {
+                    warn!(target: "verify", "{name}: {status}");
+                    unverified += 1;
+                }
+                ArtifactStatus::Missing | ArtifactStatus::Corrupted { .. } => {
+                    if !self.args.repair {
+                        error!(target: "verify", "{name}: {status}");
+                        failed += 1;
+                        continue;
+                    }
+                    match self.repair(&client, dep, &path) {
+                        Ok(_) => info!(target: "verify", "{name}: repaired ({status})"),
+                        Err(err) => {
+                            error!(target: "verify", "{name}: failed to repair: {:?}", err);
+                            failed += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        info!(target: "verify", "Verified {} artifacts. {} without checksums", checked, unverified);
+        if failed > 0 {
+            if self.args.repair {
+                bail!("{failed} problems could not be repaired");
+            }
+            bail!("Found {failed} problems in the cache. Run labt verify --repair to fix them");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn verify_detects_missing_and_corrupted() {
+    assert_eq!(
+        parse_checksum("DA39A3EE5E6B4B0D3255BFEF95601890AFD80709  empty.jar\n"),
+        Some(String::from("da39a3ee5e6b4b0d3255bfef95601890afd80709"))
+    );
+    assert_eq!(parse_checksum("<html>Not found</html>"), None);
+
+    let tmp = tempfile::tempdir().unwrap();
+    let path = tmp.path().join("verify.jar");
+    assert_eq!(
+        check_artifact(&path, None).unwrap(),
+        ArtifactStatus::Missing
+    );
+
+    std::fs::write(&path, "").unwrap();
+    let empty = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+    assert_eq!(
+        check_artifact(&path, None).unwrap(),
+        ArtifactStatus::Unverified
+    );
+    assert_eq!(
+        check_artifact(&path, Some(empty)).unwrap(),
+        ArtifactStatus::Ok
+    );
+
+    std::fs::write(&path, "truncated").unwrap();
+    let status = check_artifact(&path, Some(empty));
+    assert!(status.unwrap().needs_repair());
+
+    // the sha256 from Labt.lock
+    let truncated = "b0d2fde0ea5f7e1f4aaf1a8d6bd2f7fd3d9b4e0a1d4b2e3d9ca5fe4a6d1e9f0b";
+    let status = check_locked_artifact(&path, truncated).unwrap();
+    assert!(status.needs_repair());
+    let actual = file_sha256(&path).unwrap();
+    assert_eq!(
+        check_locked_artifact(&path, &actual.to_uppercase()).unwrap(),
+        ArtifactStatus::Ok
+    );
+    assert_eq!(
+        status.to_string(),
+        format!("corrupted: expected sha256 {truncated}, found {actual}")
+    );
+}
@@ -0,0 +1,283 @@
+use std::collections::BTreeMap;
+
+use anyhow::bail;
+use clap::Args;
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::{
+    config::{get_config, get_resolvers_from_config, maven_metadata::MavenMetadata},
+    version::ComparableVersion,
+};
+
+use super::{
+    resolvers::{get_default_resolvers, Resolver, ResolverErrorKind},
+    Submodule,
+};
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+#[derive(Clone, Args)]
+pub struct VersionsArgs {
+    /// Artifact in the form group_id:artifact_id e.g. androidx.core:core
+    pub dependency: String,
+    /// Only list this number of the newest versions
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+    /// Skip looking up release dates of the versions
+    #[arg(long)]
+    pub no_dates: bool,
+}
+
+pub struct Versions {
+    args: VersionsArgs,
+}
+
+impl Versions {
+    pub fn new(args: &VersionsArgs) -> Self {
+        Versions { args: args.clone() }
+    }
+}
+
+/// A published version of an artifact
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AvailableVersion {
+    pub version: String,
+    /// Release date in the form yyyy-mm-dd if known. A lastUpdated that is not a
+    /// timestamp is kept as it is
+    pub date: Option<String>,
+    /// Names of the resolvers whose repositories have this version
+    pub repositories: Vec<String>,
+}
+
+/// Converts a maven-metadata.xml lastUpdated timestamp (yyyyMMddHHmmss) to yyyy-mm-dd
+pub fn format_last_updated(timestamp: &str) -> Option<String> {
+    // get instead of indexing as the metadata may have any text here
+    let date = timestamp.trim().get(..8)?;
+    if !date.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}-{}-{}", &date[0..4], &date[4..6], &date[6..8]))
+}
+
+/// Converts an http date e.g. "Wed, 17 Apr 2024 18:54:12 GMT" to yyyy-mm-dd
+pub fn parse_http_date(date: &str) -> Option<String> {
+    let mut parts = date.split_whitespace().skip(1);
+    let day: u8 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|m| m.eq_ignore_ascii_case(month))? + 1;
+    let year: u16 = parts.next()?.parse().ok()?;
+    Some(format!("{year:04}-{month:02}-{day:02}"))
+}
+
+/// Merges the metadata fetched from several repositories into one list of versions
+/// ordered from the newest to the oldest. The lastUpdated timestamp of a repository
+/// is used as the date of its latest version.
+pub fn merge_metadata(sources: &[(String, MavenMetadata)]) -> Vec<AvailableVersion> {
+    let mut merged: BTreeMap<String, AvailableVersion> = BTreeMap::new();
+    for (repository, metadata) in sources {
+        let newest = metadata
+            .latest
+            .as_ref()
+            .or(metadata.release.as_ref())
+            .or(metadata.versions.last());
+        for version in &metadata.versions {
+            let entry = merged
+                .entry(version.clone())
+                .or_insert_with(|| AvailableVersion {
+                    version: version.clone(),
+                    date: None,
+                    repositories: Vec::new(),
+                });
+            if !entry.repositories.contains(repository) {
+                entry.repositories.push(repository.clone());
+            }
+            if entry.date.is_none() && newest == Some(version) {
+                entry.date = metadata
+                    .last_updated
+                    .as_deref()
+                    .map(|updated| {
+                        format_last_updated(updated).unwrap_or_else(|| updated.trim().to_string())
+                    })
+                    .filter(|date| !date.is_empty());
+            }
+        }
+    }
+    let mut versions: Vec<AvailableVersion> = merged.into_values().collect();
+    versions.sort_by_cached_key(|v| std::cmp::Reverse(ComparableVersion::new(&v.version)));
+    versions
+}
+
+/// Fetches maven-metadata.xml of an artifact from the repositories of the resolvers
+/// as (resolver name, metadata). The cache is only used if none of the repositories
+/// could be reached.
+///
+/// # Errors
+///
+/// Returns an error if no repository has metadata for the artifact
+pub fn fetch_metadata_sources(
+    group_id: &str,
+    artifact_id: &str,
+    resolvers: &[Box<dyn Resolver>],
+) -> anyhow::Result<Vec<(String, MavenMetadata)>> {
+    let mut sources = Vec::new();
+    let (remote, local): (Vec<_>, Vec<_>) = resolvers
+        .iter()
+        .partition(|resolver| resolver.get_base_url().is_some());
+
+    for group in [remote, local] {
+        for resolver in group {
+            crate::interrupt::check()?;
+            match resolver.fetch_metadata(group_id, artifact_id) {
+                Ok(metadata) => sources.push((resolver.get_name().to_string(), metadata)),
+                Err(err) if matches!(err.kind(), ResolverErrorKind::NotFound) => {
+                    debug!(target: "versions", "{}: {}", resolver.get_name(), err);
+                }
+                Err(err) => {
+                    warn!(target: "versions", "{}: {:?}", resolver.get_name(), anyhow::Error::from(err));
+                }
+            }
+        }
+        if !sources.is_empty() {
+            break;
+        }
+    }
+
+    if sources.is_empty() {
+        bail!("No repository has maven-metadata.xml for {group_id}:{artifact_id}");
+    }
+    Ok(sources)
+}
+
+/// Lists the versions of an artifact published in the repositories of the resolvers.
+/// The cache is only used if none of the repositories could be reached.
+///
+/// # Errors
+///
+/// Returns an error if no repository has metadata for the artifact
+pub fn available_versions(
+    group_id: &str,
+    artifact_id: &str,
+    resolvers: &[Box<dyn Resolver>],
+) -> anyhow::Result<Vec<AvailableVersion>> {
+    let sources = fetch_metadata_sources(group_id, artifact_id, resolvers)?;
+    Ok(merge_metadata(&sources))
+}
+
+/// Looks up the release dates of the versions from the Last-Modified header of their poms.
+/// Versions whose repositories do not report it keep the date from the metadata.
+///
+/// # Errors
+///
+/// Returns an error if interrupted
+pub fn fill_release_dates(
+    group_id: &str,
+    artifact_id: &str,
+    versions: &mut [AvailableVersion],
+    resolvers: &[Box<dyn Resolver>],
+) -> anyhow::Result<()> {
+    for version in versions {
+        crate::interrupt::check()?;
+        let date = version.repositories.iter().find_map(|repository| {
+            resolvers
+                .iter()
+                .find(|resolver| resolver.get_name() == repository)?
+                .last_modified(group_id, artifact_id, &version.version)
+                .as_deref()
+                .and_then(parse_http_date)
+        });
+        if date.is_some() {
+            version.date = date;
+        }
+    }
+    Ok(())
+}
+
+/// The resolvers configured in Labt.toml, or the default resolvers outside a project
+pub fn project_resolvers() -> anyhow::Result<Vec<Box<dyn Resolver>>> {
+    match get_config() {
+        Ok(config) => get_resolvers_from_config(&config),
+        Err(_) => get_default_resolvers(),
+    }
+}
+
+/// Splits group_id:artifact_id
+pub fn parse_artifact(dependency: &str) -> anyhow::Result<(&str, &str)> {
+    match dependency.split_once(':') {
+        Some((group_id, artifact_id))
+            if !group_id.is_empty() && !artifact_id.is_empty() && !artifact_id.contains(':') =>
+        {
+            Ok((group_id, artifact_id))
+        }
+        _ => bail!("Invalid artifact {dependency}. The allowed format is group_id:artifact_id"),
+    }
+}
+
+impl Submodule for Versions {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let (group_id, artifact_id) = parse_artifact(&self.args.dependency)?;
+        let resolvers = project_resolvers()?;
+
+        let mut versions = available_versions(group_id, artifact_id, &resolvers)?;
+        if let Some(limit) = self.args.limit {
+            versions.truncate(limit);
+        }
+        if !self.args.no_dates {
+            fill_release_dates(group_id, artifact_id, &mut versions, &resolvers)?;
+        }
+
+        let width = versions.iter().map(|v| v.version.len()).max().unwrap_or(0);
+        for version in &versions {
+            println!(
+                "{:width$}  {:10}  {}",
+                version.version,
+                version.date.as_deref().unwrap_or("-"),
+                version.repositories.join(", "),
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn versions_merge_across_repositories() {
+    assert_eq!(
+        format_last_updated("20240417185412"),
+        Some(String::from("2024-04-17"))
+    );
+    assert_eq!(format_last_updated("2024"), None);
+    assert_eq!(format_last_updated("2024é0417"), None);
+    assert_eq!(format_last_updated("2024041é"), None);
+    assert_eq!(
+        parse_http_date("Wed, 7 Apr 2024 18:54:12 GMT"),
+        Some(String::from("2024-04-07"))
+    );
+    assert_eq!(parse_http_date("yesterday"), None);
+    assert!(parse_artifact("androidx.core:core:1.0").is_err());
+
+    let mut google = MavenMetadata::new(String::from("a"), String::from("b"));
+    google.versions = vec![
+        String::from("1.0"),
+        String::from("1.1-rc1"),
+        String::from("1.1"),
+    ];
+    google.latest = Some(String::from("1.1"));
+    google.last_updated = Some(String::from("20240417185412"));
+    let mut central = MavenMetadata::new(String::from("a"), String::from("b"));
+    central.versions = vec![String::from("0.9"), String::from("1.0")];
+
+    let merged = merge_metadata(&[
+        (String::from("google"), google),
+        (String::from("central"), central),
+    ]);
+    let versions: Vec<&str> = merged.iter().map(|v| v.version.as_str()).collect();
+    assert_eq!(versions, vec!["1.1", "1.1-rc1", "1.0", "0.9"]);
+    assert_eq!(merged[0].date, Some(String::from("2024-04-17")));
+    assert_eq!(merged[1].date, None);
+    assert_eq!(merged[2].repositories, vec!["google", "central"]);
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Context};
+use clap::Args;
+
+use crate::{
+    caching::graph::{load_graph, DependencyGraph, GraphNode, GraphSection},
+    config::get_config,
+    get_project_root,
+    pom::Project,
+};
+
+use super::{
+    resolve::{get_config_dependencies, get_config_processors, get_config_test_dependencies},
+    Submodule,
+};
+
+/// The most dependency paths shown for an artifact
+const MAX_PATHS: usize = 50;
+
+#[derive(Clone, Args)]
+pub struct WhyArgs {
+    /// The locked dependency as group_id:artifact_id or just its artifact_id
+    dependency: String,
+}
+
+pub struct Why {
+    args: WhyArgs,
+}
+
+impl Why {
+    pub fn new(args: &WhyArgs) -> Self {
+        Why { args: args.clone() }
+    }
+}
+
+/// Renders how each node matching dependency is reached from the declared roots
+pub fn render_why(
+    graph: &DependencyGraph,
+    roots: &[(GraphSection, String, String)],
+    dependency: &str,
+) -> anyhow::Result<String> {
+    let (group_id, artifact_id) = match dependency.split_once(':') {
+        Some((group_id, rest)) => (Some(group_id), rest.split(':').next().unwrap_or(rest)),
+        None => (None, dependency),
+    };
+    let targets: Vec<&GraphNode> = graph
+        .nodes
+        .iter()
+        .filter(|node| {
+            node.artifact_id == artifact_id && group_id.is_none_or(|g| node.group_id == g)
+        })
+        .collect();
+    if targets.is_empty() {
+        bail!("{} is not in Labt.lock", dependency);
+    }
+
+    let mut out = String::new();
+    for target in targets {
+        out.push_str(&format!(
+            "{} [{}] {}\n",
+            target.coordinate(),
+            target.scope,
+            target.packaging
+        ));
+        let roots: Vec<&GraphNode> = roots
+            .iter()
+            .filter(|(section, _, _)| match target.section {
+                // test dependencies can reach the project dependencies they share
+                GraphSection::Project => *section != GraphSection::Processors,
+                target_section => *section == target_section,
+            })
+            .filter_map(|(section, group_id, artifact_id)| {
+                graph.find(*section, group_id, artifact_id)
+            })
+            .collect();
+        let paths = graph.paths_to(&roots, target, MAX_PATHS);
+        if paths.is_empty() {
+            out.push_str("  not reachable from Labt.toml, run labt resolve\n");
+        }
+        for path in &paths {
+            out.push_str("  Labt.toml");
+            for node in path {
+                out.push_str(&format!(" -> {}", node.coordinate()));
+            }
+            out.push('\n');
+        }
+        if paths.len() >= MAX_PATHS {
+            out.push_str(&format!("  only the first {MAX_PATHS} paths are shown\n"));
+        }
+    }
+    Ok(out)
+}
+
+impl Submodule for Why {
+    fn run(&mut self) -> anyhow::Result<()> {
+        let root = get_project_root().context("Failed to get project root directory")?;
+        let config = get_config()?;
+        let graph = load_graph(&root)?;
+
+        let declared = |section: GraphSection, projects: Vec<Project>| {
+            projects
+                .into_iter()
+                .map(move |p| (section, p.get_group_id(), p.get_artifact_id()))
+        };
+        let roots: Vec<(GraphSection, String, String)> =
+            declared(GraphSection::Project, get_config_dependencies(&config)?)
+                .chain(declared(
+                    GraphSection::Test,
+                    get_config_test_dependencies(&config),
+                ))
+                .chain(declared(
+                    GraphSection::Processors,
+                    get_config_processors(&config),
+                ))
+                .collect();
+
+        print!("{}", render_why(&graph, &roots, &self.args.dependency)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn why_lists_paths_from_declared_dependencies() {
+    use crate::{config::lock::LabtLock, submodules::resolve::ProjectDep};
+
+    let dep = |artifact_id: &str, dependencies: &[&str]| ProjectDep {
+        group_id: String::from("com.example"),
+        artifact_id: artifact_id.to_string(),
+        version: String::from("1.0.0"),
+        packaging: String::from("jar"),
+        dependencies: dependencies.iter().map(|d| d.to_string()).collect(),
+        ..Default::default()
+    };
+    let lock = LabtLock {
+        resolved: vec![
+            dep("shared", &[]),
+            dep("app", &["com.example:shared:1.0.0"]),
+            dep("orphan", &[]),
+        ],
+        test: vec![dep("junit", &["com.example:shared:1.0.0"])],
+        ..Default::default()
+    };
+    let graph = DependencyGraph::from_lock_with(&lock, String::new(), |_| Vec::new());
+    let roots = vec![
+        (
+            GraphSection::Project,
+            String::from("com.example"),
+            String::from("app"),
+        ),
+        (
+            GraphSection::Test,
+            String::from("com.example"),
+            String::from("junit"),
+        ),
+    ];
+
+    assert_eq!(
+        render_why(&graph, &roots, "com.example:shared").unwrap(),
+        "com.example:shared:1.0.0 [compile] jar
+  Labt.toml -> com.example:app:1.0.0 -> com.example:shared:1.0.0
+  Labt.toml -> com.example:junit:1.0.0 -> com.example:shared:1.0.0
+"
+    );
+    assert_eq!(
+        render_why(&graph, &roots, "orphan").unwrap(),
+        "com.example:orphan:1.0.0 [compile] jar\n  not reachable from Labt.toml, run labt resolve\n"
+    );
+    assert!(render_why(&graph, &roots, "com.example:missing").is_err());
+}
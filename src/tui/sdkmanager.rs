@@ -828,12 +828,12 @@ impl<'installed_list, 'repo> AppState<'installed_list, 'repo> {
         self.table_state.select(Some(self.selected_package));
     }
 
-    /// Returns the main table state
+    // Returns the main table state
     // pub fn get_main_table_state(&mut self) -> &mut TableState {
     //     &mut self.table_state
     // }
 
-    /// Returns the selected index
+    // Returns the selected index
     // pub fn get_selected_package_index(&self) -> usize {
     //     self.selected_package
     // }
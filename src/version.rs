@@ -0,0 +1,363 @@
+//! Maven version ordering, a port of Maven's `ComparableVersion`.
+//!
+//! Versions are split into items on `.`, `-` and transitions between digits
+//! and letters. Numbers compare numerically and known qualifiers follow
+//! `alpha < beta < milestone < rc < snapshot < release < sp`. Unknown
+//! qualifiers sort after sp, alphabetically.
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
+
+use version_compare::Cmp;
+
+/// Known qualifiers in increasing order. The empty string is a release.
+const QUALIFIERS: [&str; 7] = ["alpha", "beta", "milestone", "rc", "snapshot", "", "sp"];
+
+/// Index of the release qualifier in [`QUALIFIERS`]
+const RELEASE_INDEX: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    /// A number without leading zeros
+    Int(String),
+    /// A qualifier with aliases replaced
+    Str(String),
+    /// A sub version started by `-` or a digit/letter transition
+    List(Vec<Item>),
+}
+
+impl Item {
+    fn int(digits: &str) -> Self {
+        let trimmed = digits.trim_start_matches('0');
+        Item::Int(trimmed.to_string())
+    }
+    fn string(value: &str, followed_by_digit: bool) -> Self {
+        let value = match value {
+            "a" if followed_by_digit => "alpha",
+            "b" if followed_by_digit => "beta",
+            "m" if followed_by_digit => "milestone",
+            "ga" | "final" | "release" => "",
+            "cr" => "rc",
+            value => value,
+        };
+        Item::Str(value.to_string())
+    }
+    /// Null items are dropped from the end of a version. e.g. 1.0 is the same as 1
+    fn is_null(&self) -> bool {
+        match self {
+            Item::Int(value) => value.is_empty(),
+            Item::Str(value) => value.is_empty(),
+            Item::List(items) => items.is_empty(),
+        }
+    }
+    /// Sort key of a qualifier. Unknown qualifiers sort after the known ones
+    fn qualifier_key(value: &str) -> String {
+        match QUALIFIERS.iter().position(|q| *q == value) {
+            Some(index) => index.to_string(),
+            None => format!("{}-{value}", QUALIFIERS.len()),
+        }
+    }
+    fn compare_ints(a: &str, b: &str) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    }
+    /// Compares this item with the item at the same position in another version,
+    /// None if the other version has no item there
+    fn compare(&self, other: Option<&Item>) -> Ordering {
+        match (self, other) {
+            (Item::Int(value), None) => {
+                if value.is_empty() {
+                    Ordering::Equal
+                } else {
+                    Ordering::Greater
+                }
+            }
+            (Item::Int(a), Some(Item::Int(b))) => Self::compare_ints(a, b),
+            // 1.1 > 1-sp and 1.1 > 1-1
+            (Item::Int(_), Some(_)) => Ordering::Greater,
+
+            (Item::Str(value), None) => Self::qualifier_key(value).cmp(&RELEASE_INDEX.to_string()),
+            (Item::Str(_), Some(Item::Int(_))) => Ordering::Less,
+            (Item::Str(a), Some(Item::Str(b))) => {
+                Self::qualifier_key(a).cmp(&Self::qualifier_key(b))
+            }
+            (Item::Str(_), Some(Item::List(_))) => Ordering::Less,
+
+            (Item::List(items), None) => match items.first() {
+                Some(first) => first.compare(None),
+                None => Ordering::Equal,
+            },
+            (Item::List(_), Some(Item::Int(_))) => Ordering::Less,
+            (Item::List(_), Some(Item::Str(_))) => Ordering::Greater,
+            (Item::List(a), Some(Item::List(b))) => compare_lists(a, b),
+        }
+    }
+}
+
+fn compare_lists(a: &[Item], b: &[Item]) -> Ordering {
+    for index in 0..a.len().max(b.len()) {
+        let result = match (a.get(index), b.get(index)) {
+            (Some(left), right) => left.compare(right),
+            (None, Some(right)) => right.compare(None).reverse(),
+            (None, None) => Ordering::Equal,
+        };
+        if result != Ordering::Equal {
+            return result;
+        }
+    }
+    Ordering::Equal
+}
+
+/// Removes null items from the end of the list. Sub lists stop the removal
+/// once a non null item is found before them.
+fn normalize(items: &mut Vec<Item>) {
+    let mut index = items.len();
+    while index > 0 {
+        index -= 1;
+        if items[index].is_null() {
+            items.remove(index);
+        } else if !matches!(items[index], Item::List(_)) {
+            break;
+        }
+    }
+}
+
+/// A version that sorts the way Maven sorts versions
+#[derive(Debug, Clone)]
+pub struct ComparableVersion {
+    value: String,
+    items: Vec<Item>,
+}
+
+impl ComparableVersion {
+    pub fn new(version: &str) -> Self {
+        let lower = version.to_lowercase();
+        // stack of nested lists, the last one is being filled
+        let mut stack: Vec<Vec<Item>> = vec![Vec::new()];
+        let mut is_digit = false;
+        let mut start = 0;
+
+        let token_item = |token: &str, is_digit: bool, followed_by_digit: bool| {
+            if is_digit {
+                Item::int(token)
+            } else {
+                Item::string(token, followed_by_digit)
+            }
+        };
+        for (i, c) in lower.char_indices() {
+            let current = stack.last_mut().unwrap();
+            if c == '.' {
+                if i == start {
+                    current.push(Item::Int(String::new()));
+                } else {
+                    current.push(token_item(&lower[start..i], is_digit, false));
+                }
+                start = i + 1;
+            } else if c == '-' {
+                if i == start {
+                    current.push(Item::Int(String::new()));
+                } else {
+                    current.push(token_item(&lower[start..i], is_digit, false));
+                }
+                start = i + 1;
+                stack.push(Vec::new());
+            } else if c.is_ascii_digit() {
+                if !is_digit && i > start {
+                    // a letter to digit transition e.g. alpha1
+                    current.push(Item::string(&lower[start..i], true));
+                    start = i;
+                    stack.push(Vec::new());
+                }
+                is_digit = true;
+            } else {
+                if is_digit && i > start {
+                    // a digit to letter transition e.g. 1rc
+                    current.push(Item::int(&lower[start..i]));
+                    start = i;
+                    stack.push(Vec::new());
+                }
+                is_digit = false;
+            }
+        }
+        if lower.len() > start {
+            let current = stack.last_mut().unwrap();
+            current.push(token_item(&lower[start..], is_digit, false));
+        }
+
+        // fold the nested lists back into their parents
+        while stack.len() > 1 {
+            let mut list = stack.pop().unwrap();
+            normalize(&mut list);
+            stack.last_mut().unwrap().push(Item::List(list));
+        }
+        let mut items = stack.pop().unwrap();
+        normalize(&mut items);
+
+        ComparableVersion {
+            value: version.to_string(),
+            items,
+        }
+    }
+}
+
+impl FromStr for ComparableVersion {
+    type Err = std::convert::Infallible;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(ComparableVersion::new(s))
+    }
+}
+
+impl Display for ComparableVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl Ord for ComparableVersion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_lists(&self.items, &other.items)
+    }
+}
+
+impl PartialOrd for ComparableVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ComparableVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ComparableVersion {}
+
+/// Compares two maven versions. Has the same shape as `version_compare::compare`
+/// so that it can be used in its place, but never fails.
+#[allow(clippy::result_unit_err)]
+pub fn compare(a: &str, b: &str) -> Result<Cmp, ()> {
+    Ok(
+        match ComparableVersion::new(a).cmp(&ComparableVersion::new(b)) {
+            Ordering::Less => Cmp::Lt,
+            Ordering::Equal => Cmp::Eq,
+            Ordering::Greater => Cmp::Gt,
+        },
+    )
+}
+
+/// Checks if a compares to b with the given operator using maven ordering.
+/// Has the same shape as `version_compare::compare_to`.
+#[allow(clippy::result_unit_err)]
+pub fn compare_to(a: &str, b: &str, operator: Cmp) -> Result<bool, ()> {
+    let ordering = ComparableVersion::new(a).cmp(&ComparableVersion::new(b));
+    Ok(match operator {
+        Cmp::Eq => ordering == Ordering::Equal,
+        Cmp::Ne => ordering != Ordering::Equal,
+        Cmp::Lt => ordering == Ordering::Less,
+        Cmp::Le => ordering != Ordering::Greater,
+        Cmp::Ge => ordering != Ordering::Less,
+        Cmp::Gt => ordering == Ordering::Greater,
+    })
+}
+
+/// Orders maven versions from the lowest to the highest
+pub fn sort_versions<S: AsRef<str>>(versions: &mut [S]) {
+    versions.sort_by_cached_key(|version| ComparableVersion::new(version.as_ref()));
+}
+
+/// Checks if a maven version has an unstable qualifier such as the alpha01 of
+/// the AndroidX `1.7.0-alpha01`, beta, rc1, M1, dev or SNAPSHOT
+pub fn is_pre_release(version: &str) -> bool {
+    version
+        .to_ascii_lowercase()
+        .split(['-', '.', '_'])
+        .any(|token| {
+            let qualifier = token.trim_end_matches(|c: char| c.is_ascii_digit());
+            match qualifier {
+                "alpha" | "beta" | "rc" | "dev" | "snapshot" => true,
+                // a milestone needs its number, 11m is not one
+                "m" => qualifier.len() < token.len(),
+                _ => false,
+            }
+        })
+}
+
+#[cfg(test)]
+use pretty_assertions::assert_eq;
+
+#[test]
+fn maven_version_ordering() {
+    // from the maven ComparableVersion test suite
+    let qualifiers = [
+        "1-alpha2snapshot",
+        "1-alpha2",
+        "1-alpha-123",
+        "1-beta-2",
+        "1-beta123",
+        "1-m2",
+        "1-m11",
+        "1-rc",
+        "1-cr2",
+        "1-rc123",
+        "1-SNAPSHOT",
+        "1",
+        "1-sp",
+        "1-sp2",
+        "1-sp123",
+        "1-abc",
+        "1-def",
+        "1-pom-1",
+        "1-1-snapshot",
+        "1-1",
+        "1-2",
+        "1-123",
+    ];
+    let numbers = [
+        "2.0", "2-1", "2.0.a", "2.0.0.a", "2.0.2", "2.0.123", "2.1.0", "2.1-a", "2.1b", "2.1-c",
+        "2.1-1", "2.1.0.1", "2.2", "2.123", "11.a2", "11.a11", "11.b2", "11.b11", "11.m2",
+        "11.m11", "11", "11.a", "11b", "11c", "11m",
+    ];
+    for versions in [&qualifiers[..], &numbers[..]] {
+        for pair in versions.windows(2) {
+            assert_eq!(
+                compare(pair[0], pair[1]),
+                Ok(Cmp::Lt),
+                "{} < {}",
+                pair[0],
+                pair[1]
+            );
+        }
+        let mut shuffled: Vec<&str> = versions.iter().rev().copied().collect();
+        sort_versions(&mut shuffled);
+        assert_eq!(shuffled, versions);
+    }
+
+    for (a, b) in [
+        ("1", "1.0.0"),
+        ("1-ga", "1"),
+        ("1.final", "1"),
+        ("1-release", "1.0"),
+        ("1a1", "1-alpha-1"),
+        ("1cr", "1rc"),
+        ("1.0-SNAPSHOT", "1-snapshot"),
+    ] {
+        assert_eq!(compare(a, b), Ok(Cmp::Eq), "{a} == {b}");
+    }
+    assert_eq!(compare_to("1.0-rc1", "1.0", Cmp::Lt), Ok(true));
+    assert_eq!(compare_to("1.0", "1.0-sp1", Cmp::Lt), Ok(true));
+}
+
+#[test]
+fn pre_release_qualifiers() {
+    for version in [
+        "1.7.0-alpha01",
+        "2.0-beta",
+        "3.1.0-rc1",
+        "5.0.0-M1",
+        "1.0-dev",
+        "1.0-SNAPSHOT",
+    ] {
+        assert!(is_pre_release(version), "{version}");
+    }
+    for version in ["1.7.0", "11m", "1.0-sp1", "31.1-android"] {
+        assert!(!is_pre_release(version), "{version}");
+    }
+}